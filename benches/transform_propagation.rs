@@ -0,0 +1,75 @@
+//! Compares dirty-tracked transform propagation with recomputing every
+//! node, on a scene shaped like a few deep imported hierarchies.
+//!
+//! `cargo bench --bench transform_propagation`
+
+use rust_graphics_sandbox::transform::{Transform, TransformHierarchy};
+use std::hint::black_box;
+use std::time::Instant;
+
+const TREES: usize = 16;
+const DEPTH: usize = 8;
+const BRANCHING: usize = 3;
+const FRAMES: u32 = 200;
+
+/// Parents for `TREES` complete trees, each node before its children.
+fn scene() -> Vec<Option<usize>> {
+    let mut parents = vec![];
+    for _ in 0..TREES {
+        let root = parents.len();
+        parents.push(None);
+        let mut level = vec![root];
+        for _ in 1..DEPTH {
+            let mut next = vec![];
+            for &parent in &level {
+                for _ in 0..BRANCHING {
+                    next.push(parents.len());
+                    parents.push(Some(parent));
+                }
+            }
+            level = next;
+        }
+    }
+    parents
+}
+
+fn time(label: &str, nodes: usize, mut frame: impl FnMut(u32) -> usize) {
+    let start = Instant::now();
+    let mut recomputed = 0;
+    for i in 0..FRAMES {
+        recomputed += black_box(frame(i));
+    }
+    let per_frame = start.elapsed().as_secs_f64() * 1000.0 / FRAMES as f64;
+    println!(
+        "{label:<28} {per_frame:>8.3} ms/frame, {:>6} of {nodes} nodes recomputed",
+        recomputed / FRAMES as usize
+    );
+}
+
+fn main() {
+    let parents = scene();
+    let nodes = parents.len();
+    let roots: Vec<usize> = (0..nodes).filter(|&i| parents[i].is_none()).collect();
+    let mut locals: Vec<Transform> = (0..nodes)
+        .map(|i| Transform::from_translation(glam::vec3(i as f32, 1.0, 0.0)))
+        .collect();
+    let mut hierarchy = TransformHierarchy::new();
+    hierarchy.set_parents(parents);
+    hierarchy.propagate(|i| locals[i]);
+
+    time("full recompute", nodes, |_| {
+        hierarchy.propagate_all(|i| locals[i])
+    });
+    time("dirty, nothing moved", nodes, |_| {
+        hierarchy.propagate(|i| locals[i])
+    });
+    time("dirty, one leaf moved", nodes, |frame| {
+        let leaf = nodes - 1;
+        locals[leaf].translation.y = frame as f32;
+        hierarchy.propagate(|i| locals[i])
+    });
+    time("dirty, one root moved", nodes, |frame| {
+        locals[roots[0]].translation.y = frame as f32;
+        hierarchy.propagate(|i| locals[i])
+    });
+}
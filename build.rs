@@ -1,68 +1,94 @@
+use std::path::{Path, PathBuf};
 use std::process::Command;
+
+/// Also the module search path, so shaders can `import common.camera;`.
+const SHADER_DIR: &str = "shaders";
+
 fn main() {
-    let src = "shaders/triangle.slang";
-    Command::new("slangc")
-        .args([
-            src,
-            "-target",
-            "spirv",
-            "-o",
-            "shaders/triangle.vert.spv",
-            "-entry",
-            "vsMain",
-            "-stage",
-            "vertex",
-            "-fvk-use-entrypoint-name",
-        ])
-        .status()
-        .unwrap();
-    Command::new("slangc")
-        .args([
-            src,
-            "-target",
-            "spirv",
-            "-o",
-            "shaders/triangle.frag.spv",
-            "-entry",
-            "psMain",
-            "-stage",
-            "pixel",
-            "-fvk-use-entrypoint-name",
-        ])
-        .status()
-        .unwrap();
+    compile("triangle", &["vsMain"], "psMain");
+    compile("model", &["vsMain", "vsMainPacked"], "psMain");
+}
+
+/// Compiles `shaders/<name>.slang` into `<name>.vert.spv`, holding every
+/// vertex entry point, and `<name>.frag.spv`.
+fn compile(name: &str, vertex_entries: &[&str], pixel_entry: &str) {
+    let src = format!("{SHADER_DIR}/{name}.slang");
+    let mut vertex = vec![];
+    for entry in vertex_entries {
+        vertex.extend(["-entry", entry, "-stage", "vertex"]);
+    }
+    let stages = [
+        ("vert", vertex),
+        ("frag", vec!["-entry", pixel_entry, "-stage", "pixel"]),
+    ];
+    for (stage, entries) in stages {
+        let out = format!("{SHADER_DIR}/{name}.{stage}.spv");
+        let status = Command::new("slangc")
+            .args([
+                src.as_str(),
+                "-I",
+                SHADER_DIR,
+                "-target",
+                "spirv",
+                "-o",
+                &out,
+            ])
+            .args(entries)
+            .arg("-fvk-use-entrypoint-name")
+            .status();
+        // the app falls back to embedded WGSL, so a missing compiler
+        // shouldn't fail the build
+        match status {
+            Ok(status) if status.success() => {}
+            Ok(status) => println!("cargo:warning=slangc failed on {src} ({status})"),
+            Err(e) => println!("cargo:warning=couldn't run slangc ({e}); using fallback shaders"),
+        }
+    }
 
-    let src = "shaders/model.slang";
-    Command::new("slangc")
-        .args([
-            src,
-            "-target",
-            "spirv",
-            "-o",
-            "shaders/model.vert.spv",
-            "-entry",
-            "vsMain",
-            "-stage",
-            "vertex",
-            "-fvk-use-entrypoint-name",
-        ])
-        .status()
-        .unwrap();
-    Command::new("slangc")
-        .args([
-            src,
-            "-target",
-            "spirv",
-            "-o",
-            "shaders/model.frag.spv",
-            "-entry",
-            "psMain",
-            "-stage",
-            "pixel",
-            "-fvk-use-entrypoint-name",
-        ])
-        .status()
-        .unwrap();
+    for dependency in dependencies(Path::new(&src)) {
+        println!("cargo:rerun-if-changed={}", dependency.display());
+    }
+}
+
+/// `src` and every file it imports or includes, transitively. Missing
+/// files are listed too, so creating one triggers a rebuild.
+fn dependencies(src: &Path) -> Vec<PathBuf> {
+    let mut found = vec![];
+    let mut pending = vec![src.to_path_buf()];
+    while let Some(path) = pending.pop() {
+        if found.contains(&path) {
+            continue;
+        }
+        let text = std::fs::read_to_string(&path).unwrap_or_default();
+        let dir = path.parent().unwrap_or(Path::new(SHADER_DIR)).to_path_buf();
+        pending.extend(text.lines().filter_map(|line| referenced_file(line, &dir)));
+        found.push(path);
+    }
+    found
+}
 
-    println!("cargo:rerun-if-changed={src}");
+/// The file named by an `import a.b;`, `import "a/b.slang";` or
+/// `#include "a/b.slang"` line, looked up next to the including file and
+/// then in `SHADER_DIR`, the way slangc searches.
+fn referenced_file(line: &str, dir: &Path) -> Option<PathBuf> {
+    let line = line.trim();
+    let name = if let Some(rest) = line.strip_prefix("#include") {
+        rest.trim()
+            .trim_matches(|c| c == '"' || c == '<' || c == '>')
+            .to_string()
+    } else if let Some(rest) = line.strip_prefix("import ") {
+        let module = rest.trim().trim_end_matches(';').trim();
+        match module.strip_prefix('"') {
+            Some(quoted) => quoted.trim_end_matches('"').to_string(),
+            None => format!("{}.slang", module.replace('.', "/")),
+        }
+    } else {
+        return None;
+    };
+    let local = dir.join(&name);
+    Some(if local.exists() {
+        local
+    } else {
+        Path::new(SHADER_DIR).join(name)
+    })
 }
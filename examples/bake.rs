@@ -0,0 +1,422 @@
+//! Bakes a lightmap per model and switches between them and real-time
+//! lighting: a shadow-mapped sun over flat sky light. The baked side adds
+//! what real time leaves out, the sky's occlusion and sunlight bounced off
+//! the red and green walls. Bakes refine while you watch; meshes without
+//! TEXCOORD_1 get generated lightmap UVs first.
+//!
+//! `cargo run --example bake`
+
+use rust_graphics_sandbox::lightmapper::Lightmapper;
+use rust_graphics_sandbox::material::{Binding, MaterialTemplate};
+use rust_graphics_sandbox::mesh::{Mesh, Vertex, OCTAHEDRAL_WGSL};
+use rust_graphics_sandbox::model::EntityId;
+use rust_graphics_sandbox::shader::Shader;
+use rust_graphics_sandbox::shadow::{ShadowMap, SHADOW_WGSL};
+use rust_graphics_sandbox::texture::Texture;
+use rust_graphics_sandbox::transform::Transform;
+use rust_graphics_sandbox::uniform::{UniformLayout, UniformType};
+use rust_graphics_sandbox::{App, MaterialInstance, Plugin, PluginContext, State, World};
+use std::collections::HashMap;
+use std::sync::Arc;
+use wgpu::util::DeviceExt;
+
+const REALTIME_SHADER: &str = r#"
+struct Camera { view_proj: mat4x4<f32> };
+struct Light {
+    direction: vec3<f32>,
+    color: vec3<f32>,
+    sky: vec3<f32>,
+    light_view_proj: mat4x4<f32>,
+    shadow_filter: u32,
+    shadow_bias: f32,
+    pcf_radius: i32,
+    min_variance: f32,
+    light_bleed: f32,
+};
+struct Model { model: mat4x4<f32>, base_color: vec4<f32>, alpha_cutoff: f32 };
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(1) @binding(0) var<uniform> light: Light;
+@group(2) @binding(0) var shadow_depth: texture_depth_2d;
+@group(2) @binding(1) var shadow_depth_sampler: sampler_comparison;
+@group(2) @binding(2) var shadow_moments: texture_2d<f32>;
+@group(2) @binding(3) var shadow_moments_sampler: sampler;
+@group(3) @binding(0) var<uniform> model: Model;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) world_pos: vec3<f32>,
+};
+
+fn vertex(pos: vec3<f32>, normal: vec3<f32>) -> VSOut {
+    var out: VSOut;
+    let world_pos = model.model * vec4(pos, 1.0);
+    out.pos = camera.view_proj * world_pos;
+    out.normal = (model.model * vec4(normal, 0.0)).xyz;
+    out.world_pos = world_pos.xyz;
+    return out;
+}
+
+@vertex
+fn vsMain(@location(0) pos: vec3<f32>, @location(1) normal: vec3<f32>) -> VSOut {
+    return vertex(pos, normal);
+}
+
+@vertex
+fn vsMainPacked(@location(0) pos: vec3<f32>, @location(1) normal: vec2<f32>) -> VSOut {
+    return vertex(pos, octDecode(normal));
+}
+
+@fragment
+fn psMain(in: VSOut) -> @location(0) vec4<f32> {
+    let n = normalize(in.normal);
+    let n_dot_l = max(dot(n, -normalize(light.direction)), 0.0);
+    let lit = shadow(
+        light.light_view_proj,
+        in.world_pos,
+        light.shadow_filter,
+        light.shadow_bias,
+        light.pcf_radius,
+        light.min_variance,
+        light.light_bleed,
+    );
+    let albedo = model.base_color.rgb;
+    return vec4(albedo * (light.sky + light.color * n_dot_l * lit), 1.0);
+}
+"#;
+
+const BAKED_SHADER: &str = r#"
+struct Camera { view_proj: mat4x4<f32> };
+struct Model { model: mat4x4<f32>, base_color: vec4<f32>, alpha_cutoff: f32 };
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(1) @binding(0) var lightmap: texture_2d<f32>;
+@group(1) @binding(1) var lightmap_sampler: sampler;
+@group(2) @binding(0) var<uniform> model: Model;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv1: vec2<f32>,
+};
+
+fn vertex(pos: vec3<f32>, uv1: vec2<f32>) -> VSOut {
+    var out: VSOut;
+    out.pos = camera.view_proj * model.model * vec4(pos, 1.0);
+    out.uv1 = uv1;
+    return out;
+}
+
+@vertex
+fn vsMain(@location(0) pos: vec3<f32>, @location(3) uv1: vec2<f32>) -> VSOut {
+    return vertex(pos, uv1);
+}
+
+@vertex
+fn vsMainPacked(@location(0) pos: vec3<f32>, @location(3) uv1: vec2<f32>) -> VSOut {
+    return vertex(pos, uv1);
+}
+
+@fragment
+fn psMain(in: VSOut) -> @location(0) vec4<f32> {
+    let light = textureSample(lightmap, lightmap_sampler, in.uv1).rgb;
+    return vec4(model.base_color.rgb * light, 1.0);
+}
+"#;
+
+struct Bake {
+    /// Elevation and heading of the light, in degrees.
+    elevation: f32,
+    azimuth: f32,
+    color: [f32; 3],
+    sky: [f32; 3],
+    baked: bool,
+    shadow_map: Option<ShadowMap>,
+    lightmapper: Option<Lightmapper>,
+    layout: UniformLayout,
+    buffer: Option<Arc<wgpu::Buffer>>,
+    realtime: Option<Arc<MaterialInstance>>,
+    baked_template: Option<Arc<MaterialTemplate>>,
+    /// An instance of `baked_template` per model, binding its lightmap.
+    baked_materials: HashMap<EntityId, Arc<MaterialInstance>>,
+}
+
+impl Bake {
+    /// The way the light travels.
+    fn direction(&self) -> glam::Vec3 {
+        let (sin_e, cos_e) = self.elevation.to_radians().sin_cos();
+        let (sin_a, cos_a) = self.azimuth.to_radians().sin_cos();
+        -glam::vec3(cos_e * sin_a, sin_e, cos_e * cos_a)
+    }
+
+    fn uniform(&self) -> Vec<u8> {
+        let mut data = self.layout.zeroed();
+        self.layout
+            .write(&mut data, "direction", self.direction().to_array());
+        self.layout.write(&mut data, "color", self.color);
+        self.layout.write(&mut data, "sky", self.sky);
+        if let Some(shadow_map) = &self.shadow_map {
+            shadow_map.write_uniform(&self.layout, &mut data);
+        }
+        data
+    }
+
+    fn start_bake(&mut self, state: &State, world: &mut World) {
+        let (Some(lightmapper), Some(template)) = (&mut self.lightmapper, &self.baked_template)
+        else {
+            return;
+        };
+        lightmapper.start(state, world);
+        self.baked_materials = lightmapper
+            .lightmaps()
+            .map(|(id, lightmap)| {
+                let bindings = vec![
+                    Binding::uniform(
+                        world.camera.buffer_ref().clone(),
+                        wgpu::ShaderStages::VERTEX,
+                    ),
+                    Binding::texture(lightmap.clone(), wgpu::ShaderStages::FRAGMENT),
+                ];
+                (id, template.instantiate(state, bindings))
+            })
+            .collect();
+        self.apply(state, world);
+    }
+
+    /// Gives every model the material of the current mode.
+    fn apply(&self, state: &State, world: &mut World) {
+        let Some(realtime) = &self.realtime else {
+            return;
+        };
+        for model in &mut world.models {
+            let material = match self.baked {
+                true => self.baked_materials.get(&model.id).unwrap_or(realtime),
+                false => realtime,
+            };
+            if !Arc::ptr_eq(model.material(), material) {
+                model.set_material(&state.device, material.clone());
+            }
+        }
+    }
+}
+
+impl Plugin for Bake {
+    fn name(&self) -> &str {
+        "Bake"
+    }
+
+    fn build(&mut self, ctx: &mut PluginContext) {
+        let source = format!("{REALTIME_SHADER}{SHADOW_WGSL}{OCTAHEDRAL_WGSL}");
+        self.layout.assert_wgsl(&source, "Light");
+        let shadow_map = ShadowMap::new(ctx.state, 2048);
+        let buffer = Arc::new(ctx.state.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Light Uniform"),
+                contents: &self.uniform(),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+        ));
+        let camera = || {
+            Binding::uniform(
+                ctx.world.camera.buffer_ref().clone(),
+                wgpu::ShaderStages::VERTEX,
+            )
+        };
+        let bindings = vec![
+            camera(),
+            Binding::uniform(buffer.clone(), wgpu::ShaderStages::FRAGMENT),
+            shadow_map.binding(),
+        ];
+        let realtime = MaterialInstance::new_arc(ctx.state, bindings, &Shader::from_wgsl(&source));
+        // any texture will do for the layout; each model binds its own
+        let placeholder = Texture::from_rgba8(ctx.state, "Placeholder", 1, 1, &[255; 4], false);
+        self.baked_template = Some(MaterialTemplate::new_arc(
+            ctx.state,
+            &[
+                camera(),
+                Binding::texture(Arc::new(placeholder), wgpu::ShaderStages::FRAGMENT),
+            ],
+            &Shader::from_wgsl(BAKED_SHADER),
+        ));
+
+        for model in &mut ctx.world.models {
+            model.set_material(&ctx.state.device, realtime.clone());
+        }
+        let device = &ctx.state.device;
+        let scenery = [
+            (
+                "Ground",
+                [0.0, -1.0, 0.0],
+                [150.0, 1.0, 150.0],
+                [0.8, 0.8, 0.75],
+            ),
+            (
+                "Red Wall",
+                [-60.0, 40.0, 0.0],
+                [2.0, 40.0, 60.0],
+                [0.9, 0.1, 0.1],
+            ),
+            (
+                "Green Wall",
+                [0.0, 40.0, -60.0],
+                [60.0, 40.0, 2.0],
+                [0.1, 0.9, 0.1],
+            ),
+        ];
+        for (name, center, half, color) in scenery {
+            let mesh = Arc::new(cuboid(device, name, half.into(), color));
+            let transform = Transform::from_translation(center.into());
+            ctx.world
+                .spawn(ctx.state, name, mesh, realtime.clone(), transform);
+        }
+        ctx.world.materials.push(realtime.clone());
+        ctx.world.update_transforms();
+        ctx.world.camera.eye = glam::vec3(120.0, 100.0, 160.0);
+        ctx.world.camera.center = glam::vec3(0.0, 30.0, 0.0);
+        self.shadow_map = Some(shadow_map);
+        self.lightmapper = Some(Lightmapper::new(ctx.state));
+        self.realtime = Some(realtime);
+        self.buffer = Some(buffer);
+    }
+
+    fn update(&mut self, ctx: &mut PluginContext) {
+        let direction = self.direction();
+        let (color, sky) = (glam::Vec3::from(self.color), glam::Vec3::from(self.sky));
+        if let Some(shadow_map) = &mut self.shadow_map {
+            shadow_map.update(
+                &ctx.state.queue,
+                direction,
+                glam::vec3(0.0, 30.0, 0.0),
+                150.0,
+            );
+        }
+        if let Some(lightmapper) = &mut self.lightmapper {
+            lightmapper.update(&ctx.state.queue, direction, color, sky);
+        }
+        if let Some(buffer) = &self.buffer {
+            ctx.state.queue.write_buffer(buffer, 0, &self.uniform());
+        }
+    }
+
+    fn prepare(&mut self, state: &State, world: &World, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(shadow_map) = &self.shadow_map {
+            shadow_map.encode(state, world, encoder);
+        }
+        if let Some(lightmapper) = &mut self.lightmapper {
+            lightmapper.encode(state, world, encoder);
+        }
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, state: &State, world: &mut World) {
+        let mut start = false;
+        let mut mode_changed = false;
+        egui::Window::new("Bake").show(ctx, |ui| {
+            ui.add(egui::Slider::new(&mut self.elevation, 5.0..=90.0).text("Light elevation"));
+            ui.add(egui::Slider::new(&mut self.azimuth, 0.0..=360.0).text("Light heading"));
+            ui.horizontal(|ui| {
+                ui.label("Color: ");
+                ui.color_edit_button_rgb(&mut self.color);
+            });
+            ui.horizontal(|ui| {
+                ui.label("Sky: ");
+                ui.color_edit_button_rgb(&mut self.sky);
+            });
+            ui.separator();
+            ui.horizontal(|ui| {
+                mode_changed |= ui
+                    .selectable_value(&mut self.baked, false, "Real time")
+                    .changed();
+                mode_changed |= ui
+                    .selectable_value(&mut self.baked, true, "Baked")
+                    .changed();
+            });
+            let Some(lightmapper) = &mut self.lightmapper else {
+                return;
+            };
+            lightmapper.settings.ui(ui);
+            ui.horizontal(|ui| {
+                start = ui.button("Bake").clicked();
+                if ui
+                    .add_enabled(lightmapper.is_baking(), egui::Button::new("Stop"))
+                    .clicked()
+                {
+                    lightmapper.stop();
+                }
+            });
+            let target = lightmapper.settings.target_samples.max(1);
+            if lightmapper.is_baking() {
+                ui.add(
+                    egui::ProgressBar::new(lightmapper.samples() as f32 / target as f32)
+                        .text(format!("{} / {target} samples", lightmapper.samples())),
+                );
+            } else if lightmapper.samples() > 0 {
+                ui.label(format!(
+                    "{} samples per texel in {:.1} s",
+                    lightmapper.samples(),
+                    lightmapper.seconds()
+                ));
+            }
+            ui.label(format!(
+                "{} lightmaps, {} texels",
+                lightmapper.lightmaps().count(),
+                lightmapper.texels()
+            ));
+        });
+        if start {
+            self.baked = true;
+            self.start_bake(state, world);
+        } else if mode_changed {
+            self.apply(state, world);
+        }
+    }
+}
+
+/// A box `2 * half` across, centered on the origin, with a face per side.
+fn cuboid(device: &wgpu::Device, name: &str, half: glam::Vec3, color: [f32; 3]) -> Mesh {
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    for axis in 0..3 {
+        for sign in [-1.0, 1.0] {
+            let normal = glam::Vec3::AXES[axis] * sign;
+            let u = glam::Vec3::AXES[(axis + 1) % 3] * sign;
+            let v = glam::Vec3::AXES[(axis + 2) % 3];
+            let base = vertices.len() as u32;
+            for (a, b) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+                vertices.push(Vertex {
+                    pos: ((normal + u * a + v * b) * half).to_array(),
+                    normal: normal.to_array(),
+                    uv: [0.0; 2],
+                    uv1: [0.0; 2],
+                });
+            }
+            indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+    }
+    let mut mesh = Mesh::new(device, name, &vertices, &indices);
+    mesh.base_color = [color[0], color[1], color[2], 1.0];
+    mesh
+}
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugin(Bake {
+        elevation: 45.0,
+        azimuth: 60.0,
+        color: [1.0, 0.95, 0.9],
+        sky: [0.15, 0.18, 0.25],
+        baked: false,
+        shadow_map: None,
+        lightmapper: None,
+        layout: ShadowMap::uniform_fields(
+            UniformLayout::new()
+                .field("direction", UniformType::Vec3)
+                .field("color", UniformType::Vec3)
+                .field("sky", UniformType::Vec3),
+        ),
+        buffer: None,
+        realtime: None,
+        baked_template: None,
+        baked_materials: HashMap::new(),
+    });
+    rust_graphics_sandbox::run(app);
+}
@@ -0,0 +1,272 @@
+//! Simulates a particle fountain in a compute shader and draws the particle
+//! buffer directly as a point list.
+//!
+//! `cargo run --example compute_particles`
+
+use rust_graphics_sandbox::{App, Plugin, PluginContext, State, World};
+use wgpu::util::DeviceExt;
+
+const PARTICLE_COUNT: u32 = 65536;
+const WORKGROUP_SIZE: u32 = 64;
+
+const COMPUTE_SHADER: &str = r#"
+struct Particle { pos: vec4<f32>, vel: vec4<f32> };
+struct Params { dt: f32, time: f32, gravity: f32, speed: f32 };
+
+@group(0) @binding(0) var<storage, read_write> particles: array<Particle>;
+@group(0) @binding(1) var<uniform> params: Params;
+
+fn hash(n: u32) -> f32 {
+    var x = n * 747796405u + 2891336453u;
+    x = ((x >> ((x >> 28u) + 4u)) ^ x) * 277803737u;
+    return f32((x >> 22u) ^ x) / 4294967295.0;
+}
+
+@compute @workgroup_size(64)
+fn csMain(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if (i >= arrayLength(&particles)) {
+        return;
+    }
+    var p = particles[i];
+    // w holds the remaining lifetime in seconds
+    p.pos.w -= params.dt;
+    if (p.pos.w <= 0.0) {
+        let seed = i * 3u + u32(params.time * 1000.0);
+        let angle = hash(seed) * 6.2831853;
+        let spread = hash(seed + 1u) * 0.3;
+        p.pos = vec4(0.0, 0.0, 0.0, 1.0 + hash(seed + 2u) * 2.0);
+        p.vel = vec4(cos(angle) * spread, 1.0, sin(angle) * spread, 0.0) * params.speed;
+    }
+    p.vel.y -= params.gravity * params.dt;
+    p.pos = vec4(p.pos.xyz + p.vel.xyz * params.dt, p.pos.w);
+    particles[i] = p;
+}
+"#;
+
+const RENDER_SHADER: &str = r#"
+struct Camera { view_proj: mat4x4<f32> };
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) life: f32,
+};
+
+@vertex
+fn vsMain(@location(0) pos: vec4<f32>, @location(1) vel: vec4<f32>) -> VSOut {
+    var out: VSOut;
+    out.pos = camera.view_proj * vec4(pos.xyz, 1.0);
+    out.life = pos.w;
+    return out;
+}
+
+@fragment
+fn psMain(in: VSOut) -> @location(0) vec4<f32> {
+    return vec4(mix(vec3(1.0, 0.2, 0.05), vec3(1.0, 0.9, 0.5), clamp(in.life, 0.0, 1.0)), 1.0);
+}
+"#;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct Params {
+    dt: f32,
+    time: f32,
+    gravity: f32,
+    speed: f32,
+}
+
+const PARTICLE_ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+    wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x4];
+
+struct Particles {
+    params: Params,
+    paused: bool,
+    particle_buffer: Option<wgpu::Buffer>,
+    params_buffer: Option<wgpu::Buffer>,
+    compute_pipeline: Option<wgpu::ComputePipeline>,
+    compute_bind_group: Option<wgpu::BindGroup>,
+    render_pipeline: Option<wgpu::RenderPipeline>,
+    camera_bind_group: Option<wgpu::BindGroup>,
+}
+
+impl Plugin for Particles {
+    fn name(&self) -> &str {
+        "Compute Particles"
+    }
+
+    fn build(&mut self, ctx: &mut PluginContext) {
+        let device = &ctx.state.device;
+        ctx.world.clear();
+
+        // every particle starts dead so the first dispatch respawns them
+        let particles = vec![[0.0f32; 8]; PARTICLE_COUNT as usize];
+        let particle_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Particles"),
+            contents: bytemuck::cast_slice(&particles),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX,
+        });
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Particle Params"),
+            contents: bytemuck::cast_slice(&[self.params]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let compute_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Particle Compute"),
+            source: wgpu::ShaderSource::Wgsl(COMPUTE_SHADER.into()),
+        });
+        let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Particle Compute"),
+            layout: None,
+            module: &compute_module,
+            entry_point: Some("csMain"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        self.compute_bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &compute_pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: particle_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+        }));
+
+        let render_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Particle Render"),
+            source: wgpu::ShaderSource::Wgsl(RENDER_SHADER.into()),
+        });
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Particle Render"),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: &render_module,
+                entry_point: Some("vsMain"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: 32,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &PARTICLE_ATTRIBUTES,
+                }],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &render_module,
+                entry_point: Some("psMain"),
+                compilation_options: Default::default(),
+                targets: &[Some(ctx.state.surface_config.format.into())],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::PointList,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        self.camera_bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &render_pipeline.get_bind_group_layout(0),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: ctx.world.camera.buffer_ref().as_entire_binding(),
+            }],
+        }));
+
+        self.particle_buffer = Some(particle_buffer);
+        self.params_buffer = Some(params_buffer);
+        self.compute_pipeline = Some(compute_pipeline);
+        self.render_pipeline = Some(render_pipeline);
+    }
+
+    fn update(&mut self, ctx: &mut PluginContext) {
+        self.params.dt = if self.paused {
+            0.0
+        } else {
+            ctx.time.delta_seconds
+        };
+        self.params.time = ctx.time.elapsed_seconds;
+        if let Some(buffer) = &self.params_buffer {
+            ctx.state
+                .queue
+                .write_buffer(buffer, 0, bytemuck::cast_slice(&[self.params]));
+        }
+    }
+
+    fn render(&self, _world: &World, renderpass: &mut wgpu::RenderPass) {
+        let (Some(pipeline), Some(bind_group), Some(particles)) = (
+            &self.render_pipeline,
+            &self.camera_bind_group,
+            &self.particle_buffer,
+        ) else {
+            return;
+        };
+        renderpass.set_pipeline(pipeline);
+        renderpass.set_bind_group(0, bind_group, &[]);
+        renderpass.set_vertex_buffer(0, particles.slice(..));
+        renderpass.draw(0..PARTICLE_COUNT, 0..1);
+    }
+
+    fn encode(
+        &mut self,
+        _state: &State,
+        _world: &World,
+        encoder: &mut wgpu::CommandEncoder,
+        _target: &wgpu::TextureView,
+    ) {
+        let (Some(pipeline), Some(bind_group)) = (&self.compute_pipeline, &self.compute_bind_group)
+        else {
+            return;
+        };
+        // the result is drawn next frame, which is fine for a fountain
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Particle Simulation"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.dispatch_workgroups(PARTICLE_COUNT.div_ceil(WORKGROUP_SIZE), 1, 1);
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, _state: &State, _world: &mut World) {
+        egui::Window::new("Particles").show(ctx, |ui| {
+            ui.label(format!("{PARTICLE_COUNT} particles"));
+            ui.checkbox(&mut self.paused, "Paused");
+            ui.add(egui::Slider::new(&mut self.params.gravity, 0.0..=20.0).text("Gravity"));
+            ui.add(egui::Slider::new(&mut self.params.speed, 0.1..=10.0).text("Launch speed"));
+        });
+    }
+}
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugin(Particles {
+        params: Params {
+            dt: 0.0,
+            time: 0.0,
+            gravity: 3.0,
+            speed: 3.0,
+        },
+        paused: false,
+        particle_buffer: None,
+        params_buffer: None,
+        compute_pipeline: None,
+        compute_bind_group: None,
+        render_pipeline: None,
+        camera_bind_group: None,
+    });
+    rust_graphics_sandbox::run(app);
+}
@@ -0,0 +1,120 @@
+//! A rippling grid whose vertices are rewritten every frame with
+//! `Mesh::update_vertices`, plus a slider that changes its resolution and
+//! so grows the buffers.
+//!
+//! `cargo run --example dynamic_mesh`
+
+use rust_graphics_sandbox::mesh::Vertex;
+use rust_graphics_sandbox::transform::Transform;
+use rust_graphics_sandbox::{App, EntityId, Mesh, Plugin, PluginContext, State, World};
+use std::sync::Arc;
+
+struct Ripples {
+    resolution: u32,
+    built: u32,
+    amplitude: f32,
+    entity: Option<EntityId>,
+}
+
+impl Ripples {
+    fn vertices(&self, time: f32) -> Vec<Vertex> {
+        let n = self.resolution;
+        let height = |x: f32, z: f32| {
+            let r = (x * x + z * z).sqrt();
+            self.amplitude * (r * 8.0 - time * 3.0).sin() / (1.0 + r * 4.0)
+        };
+        let mut vertices = Vec::with_capacity(((n + 1) * (n + 1)) as usize);
+        for j in 0..=n {
+            for i in 0..=n {
+                let (u, v) = (i as f32 / n as f32, j as f32 / n as f32);
+                let (x, z) = (u * 2.0 - 1.0, v * 2.0 - 1.0);
+                let e = 1.0 / n as f32;
+                let dx = height(x + e, z) - height(x - e, z);
+                let dz = height(x, z + e) - height(x, z - e);
+                let normal = glam::vec3(-dx, 2.0 * e, -dz).normalize();
+                vertices.push(Vertex {
+                    pos: [x, height(x, z), z],
+                    normal: normal.into(),
+                    uv: [u, v],
+                    uv1: [u, v],
+                });
+            }
+        }
+        vertices
+    }
+
+    fn indices(&self) -> Vec<u32> {
+        let n = self.resolution;
+        let mut indices = vec![];
+        for j in 0..n {
+            for i in 0..n {
+                let a = j * (n + 1) + i;
+                let b = a + n + 1;
+                indices.extend_from_slice(&[a, b, a + 1, a + 1, b, b + 1]);
+            }
+        }
+        indices
+    }
+}
+
+impl Plugin for Ripples {
+    fn name(&self) -> &str {
+        "Ripples"
+    }
+
+    fn build(&mut self, ctx: &mut PluginContext) {
+        ctx.world.clear();
+        let mesh = Mesh::new(
+            &ctx.state.device,
+            "Ripples",
+            &self.vertices(0.0),
+            &self.indices(),
+        );
+        let material = ctx.world.default_material();
+        // not added to `world.meshes`, so the model is its only owner
+        self.entity = Some(ctx.world.spawn(
+            ctx.state,
+            "Ripples",
+            Arc::new(mesh),
+            material,
+            Transform::default(),
+        ));
+        self.built = self.resolution;
+    }
+
+    fn update(&mut self, ctx: &mut PluginContext) {
+        let vertices = self.vertices(ctx.time.elapsed_seconds);
+        let indices = (self.built != self.resolution).then(|| self.indices());
+        let Some(mesh) = self
+            .entity
+            .and_then(|id| ctx.world.model_mut(id))
+            .and_then(|model| model.mesh_mut())
+        else {
+            return;
+        };
+        let (device, queue) = (&ctx.state.device, &ctx.state.queue);
+        mesh.update_vertices(device, queue, &vertices);
+        if let Some(indices) = indices {
+            mesh.update_indices(device, queue, &indices);
+            self.built = self.resolution;
+        }
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, _state: &State, _world: &mut World) {
+        egui::Window::new("Ripples").show(ctx, |ui| {
+            ui.add(egui::Slider::new(&mut self.resolution, 2..=256).text("resolution"));
+            ui.add(egui::Slider::new(&mut self.amplitude, 0.0..=0.5).text("amplitude"));
+        });
+    }
+}
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugin(Ripples {
+        resolution: 64,
+        built: 0,
+        amplitude: 0.15,
+        entity: None,
+    });
+    rust_graphics_sandbox::run(app);
+}
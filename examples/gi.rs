@@ -0,0 +1,294 @@
+//! Diffuse global illumination from a probe grid, next to a red and a
+//! green wall so the bounce light is easy to see. Switch GI off to compare
+//! against flat ambient, or move the light and watch the probes catch up.
+//! Direct light is unshadowed; the probes carry the occlusion of the sky and
+//! the light bounced off lit surfaces.
+//!
+//! `cargo run --example gi`
+
+use rust_graphics_sandbox::gi::{ProbeGrid, GI_WGSL};
+use rust_graphics_sandbox::material::Binding;
+use rust_graphics_sandbox::mesh::{Mesh, Vertex, OCTAHEDRAL_WGSL};
+use rust_graphics_sandbox::shader::Shader;
+use rust_graphics_sandbox::transform::Transform;
+use rust_graphics_sandbox::uniform::{UniformLayout, UniformType};
+use rust_graphics_sandbox::{App, MaterialInstance, Plugin, PluginContext, State, World};
+use std::sync::Arc;
+use wgpu::util::DeviceExt;
+
+const SHADER: &str = r#"
+struct Camera { view_proj: mat4x4<f32> };
+struct Light {
+    direction: vec3<f32>,
+    color: vec3<f32>,
+    sky: vec3<f32>,
+    gi_min: vec3<f32>,
+    gi_enabled: u32,
+    gi_spacing: vec3<f32>,
+    gi_intensity: f32,
+    gi_counts: vec3<f32>,
+};
+struct Model { model: mat4x4<f32>, base_color: vec4<f32>, alpha_cutoff: f32 };
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(1) @binding(0) var<uniform> light: Light;
+@group(2) @binding(0) var gi_red: texture_3d<f32>;
+@group(2) @binding(1) var gi_red_sampler: sampler;
+@group(2) @binding(2) var gi_green: texture_3d<f32>;
+@group(2) @binding(3) var gi_green_sampler: sampler;
+@group(2) @binding(4) var gi_blue: texture_3d<f32>;
+@group(2) @binding(5) var gi_blue_sampler: sampler;
+@group(3) @binding(0) var<uniform> model: Model;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) world_pos: vec3<f32>,
+};
+
+fn vertex(pos: vec3<f32>, normal: vec3<f32>) -> VSOut {
+    var out: VSOut;
+    let world_pos = model.model * vec4(pos, 1.0);
+    out.pos = camera.view_proj * world_pos;
+    out.normal = (model.model * vec4(normal, 0.0)).xyz;
+    out.world_pos = world_pos.xyz;
+    return out;
+}
+
+@vertex
+fn vsMain(@location(0) pos: vec3<f32>, @location(1) normal: vec3<f32>) -> VSOut {
+    return vertex(pos, normal);
+}
+
+@vertex
+fn vsMainPacked(@location(0) pos: vec3<f32>, @location(1) normal: vec2<f32>) -> VSOut {
+    return vertex(pos, octDecode(normal));
+}
+
+@fragment
+fn psMain(in: VSOut) -> @location(0) vec4<f32> {
+    if model.base_color.a < model.alpha_cutoff {
+        discard;
+    }
+    let n = normalize(in.normal);
+    let n_dot_l = max(dot(n, -normalize(light.direction)), 0.0);
+    // a uniform sky's irradiance over pi is its radiance
+    var ambient = light.sky;
+    if light.gi_enabled == 1u {
+        ambient = gi_irradiance(in.world_pos, n, light.gi_min, light.gi_spacing, light.gi_counts)
+            * light.gi_intensity;
+    }
+    let albedo = model.base_color.rgb;
+    return vec4(albedo * (ambient + light.color * n_dot_l), 1.0);
+}
+"#;
+
+struct Gi {
+    /// Elevation and heading of the light, in degrees.
+    elevation: f32,
+    azimuth: f32,
+    color: [f32; 3],
+    sky: [f32; 3],
+    show_probes: bool,
+    grid: Option<ProbeGrid>,
+    layout: UniformLayout,
+    buffer: Option<Arc<wgpu::Buffer>>,
+}
+
+impl Gi {
+    /// The way the light travels.
+    fn direction(&self) -> glam::Vec3 {
+        let (sin_e, cos_e) = self.elevation.to_radians().sin_cos();
+        let (sin_a, cos_a) = self.azimuth.to_radians().sin_cos();
+        -glam::vec3(cos_e * sin_a, sin_e, cos_e * cos_a)
+    }
+
+    fn uniform(&self) -> Vec<u8> {
+        let mut data = self.layout.zeroed();
+        self.layout
+            .write(&mut data, "direction", self.direction().to_array());
+        self.layout.write(&mut data, "color", self.color);
+        self.layout.write(&mut data, "sky", self.sky);
+        if let Some(grid) = &self.grid {
+            grid.write_uniform(&self.layout, &mut data);
+        }
+        data
+    }
+}
+
+impl Plugin for Gi {
+    fn name(&self) -> &str {
+        "GI"
+    }
+
+    fn build(&mut self, ctx: &mut PluginContext) {
+        if !ctx.state.caps.compute {
+            log::error!("GI needs compute shaders, which this device doesn't have");
+            return;
+        }
+        let source = format!("{SHADER}{GI_WGSL}{OCTAHEDRAL_WGSL}");
+        self.layout.assert_wgsl(&source, "Light");
+        let mut grid = ProbeGrid::new(ctx.state, [16, 8, 16]);
+        let buffer = Arc::new(ctx.state.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Light Uniform"),
+                contents: &self.uniform(),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+        ));
+        let bindings = vec![
+            Binding::uniform(
+                ctx.world.camera.buffer_ref().clone(),
+                wgpu::ShaderStages::VERTEX,
+            ),
+            Binding::uniform(buffer.clone(), wgpu::ShaderStages::FRAGMENT),
+            grid.binding(),
+        ];
+        let material = MaterialInstance::new_arc(ctx.state, bindings, &Shader::from_wgsl(&source));
+        for model in &mut ctx.world.models {
+            model.set_material(&ctx.state.device, material.clone());
+        }
+        let device = &ctx.state.device;
+        let scenery = [
+            (
+                "Ground",
+                [0.0, -1.0, 0.0],
+                [150.0, 1.0, 150.0],
+                [0.8, 0.8, 0.75],
+            ),
+            (
+                "Red Wall",
+                [-60.0, 40.0, 0.0],
+                [2.0, 40.0, 60.0],
+                [0.9, 0.1, 0.1],
+            ),
+            (
+                "Green Wall",
+                [0.0, 40.0, -60.0],
+                [60.0, 40.0, 2.0],
+                [0.1, 0.9, 0.1],
+            ),
+        ];
+        for (name, center, half, color) in scenery {
+            let mesh = Arc::new(cuboid(device, name, half.into(), color));
+            let transform = Transform::from_translation(center.into());
+            ctx.world
+                .spawn(ctx.state, name, mesh, material.clone(), transform);
+        }
+        ctx.world.materials.push(material);
+        ctx.world.update_transforms();
+        ctx.world.camera.eye = glam::vec3(120.0, 100.0, 160.0);
+        ctx.world.camera.center = glam::vec3(0.0, 30.0, 0.0);
+        grid.voxelize(ctx.state, ctx.world);
+        self.grid = Some(grid);
+        self.buffer = Some(buffer);
+    }
+
+    fn update(&mut self, ctx: &mut PluginContext) {
+        let direction = self.direction();
+        if let Some(grid) = &mut self.grid {
+            grid.update(
+                &ctx.state.queue,
+                direction,
+                glam::Vec3::from(self.color),
+                glam::Vec3::from(self.sky),
+            );
+            if self.show_probes {
+                let (min, max) = grid.voxel_bounds();
+                let debug = &mut ctx.world.debug_draw;
+                debug.aabb(min, max, glam::Mat4::IDENTITY, [1.0, 1.0, 0.0, 1.0]);
+                let size = grid.settings.voxel_resolution as f32;
+                let size = (max - min).max_element() / size;
+                for probe in grid.probe_positions() {
+                    debug.cross(probe, size, [1.0; 4]);
+                }
+            }
+        }
+        if let Some(buffer) = &self.buffer {
+            ctx.state.queue.write_buffer(buffer, 0, &self.uniform());
+        }
+    }
+
+    fn prepare(&mut self, _state: &State, _world: &World, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(grid) = &mut self.grid {
+            grid.encode(encoder);
+        }
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, state: &State, world: &mut World) {
+        egui::Window::new("GI").show(ctx, |ui| {
+            let Some(grid) = &mut self.grid else {
+                ui.label("GI needs compute shaders.");
+                return;
+            };
+            ui.add(egui::Slider::new(&mut self.elevation, 5.0..=90.0).text("Light elevation"));
+            ui.add(egui::Slider::new(&mut self.azimuth, 0.0..=360.0).text("Light heading"));
+            ui.horizontal(|ui| {
+                ui.label("Color: ");
+                ui.color_edit_button_rgb(&mut self.color);
+            });
+            ui.horizontal(|ui| {
+                ui.label("Sky: ");
+                ui.color_edit_button_rgb(&mut self.sky);
+            });
+            ui.separator();
+            grid.settings.ui(ui);
+            ui.checkbox(&mut self.show_probes, "Show probes");
+            if ui.button("Voxelize").clicked() {
+                grid.voxelize(state, world);
+            }
+            if let Some(stats) = grid.stats() {
+                ui.label(format!(
+                    "{:?} voxels, {} filled, from {} triangles in {:.1} ms",
+                    stats.dims, stats.filled, stats.triangles, stats.milliseconds
+                ));
+            }
+        });
+    }
+}
+
+/// A box `2 * half` across, centered on the origin, with a face per side.
+fn cuboid(device: &wgpu::Device, name: &str, half: glam::Vec3, color: [f32; 3]) -> Mesh {
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    for axis in 0..3 {
+        for sign in [-1.0, 1.0] {
+            let normal = glam::Vec3::AXES[axis] * sign;
+            let u = glam::Vec3::AXES[(axis + 1) % 3] * sign;
+            let v = glam::Vec3::AXES[(axis + 2) % 3];
+            let base = vertices.len() as u32;
+            for (a, b) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+                vertices.push(Vertex {
+                    pos: ((normal + u * a + v * b) * half).to_array(),
+                    normal: normal.to_array(),
+                    uv: [0.0; 2],
+                    uv1: [0.0; 2],
+                });
+            }
+            indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+    }
+    let mut mesh = Mesh::new(device, name, &vertices, &indices);
+    mesh.base_color = [color[0], color[1], color[2], 1.0];
+    mesh
+}
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugin(Gi {
+        elevation: 45.0,
+        azimuth: 60.0,
+        color: [1.0, 0.95, 0.9],
+        sky: [0.15, 0.18, 0.25],
+        show_probes: false,
+        grid: None,
+        layout: ProbeGrid::uniform_fields(
+            UniformLayout::new()
+                .field("direction", UniformType::Vec3)
+                .field("color", UniformType::Vec3)
+                .field("sky", UniformType::Vec3),
+        ),
+        buffer: None,
+    });
+    rust_graphics_sandbox::run(app);
+}
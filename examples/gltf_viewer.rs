@@ -0,0 +1,50 @@
+//! Loads the glTF file given on the command line in place of the Fox.
+//!
+//! `cargo run --example gltf_viewer -- path/to/model.gltf`
+
+use rust_graphics_sandbox::transform::Transform;
+use rust_graphics_sandbox::{App, Plugin, PluginContext};
+
+struct GltfViewer {
+    path: String,
+}
+
+impl Plugin for GltfViewer {
+    fn name(&self) -> &str {
+        "glTF Viewer"
+    }
+
+    fn build(&mut self, ctx: &mut PluginContext) {
+        let settings = ctx.world.import_settings(&self.path);
+        let Some(meshes) = ctx
+            .world
+            .mesh_loaders
+            .load(&ctx.state.device, &self.path, &settings)
+        else {
+            return;
+        };
+        ctx.world.clear();
+        let material = ctx.world.default_material();
+        for mesh in meshes {
+            let name = mesh.name.clone();
+            ctx.world.meshes.push(mesh.clone());
+            ctx.world.spawn(
+                ctx.state,
+                &name,
+                mesh,
+                material.clone(),
+                Transform::default(),
+            );
+        }
+    }
+}
+
+fn main() {
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "models/Fox.gltf".to_string());
+
+    let mut app = App::new();
+    app.add_plugin(GltfViewer { path });
+    rust_graphics_sandbox::run(app);
+}
@@ -0,0 +1,216 @@
+//! Draws a grid of Foxes with a single instanced draw call using a custom
+//! pipeline hooked into the scene pass.
+//!
+//! `cargo run --example instancing`
+
+use rust_graphics_sandbox::mesh::Vertex;
+use rust_graphics_sandbox::{App, Mesh, Plugin, PluginContext, State, World};
+use std::sync::Arc;
+use wgpu::util::DeviceExt;
+
+const SHADER: &str = r#"
+struct Camera { view_proj: mat4x4<f32> };
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+};
+
+@vertex
+fn vsMain(
+    @location(0) pos: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(4) m0: vec4<f32>,
+    @location(5) m1: vec4<f32>,
+    @location(6) m2: vec4<f32>,
+    @location(7) m3: vec4<f32>,
+) -> VSOut {
+    let model = mat4x4<f32>(m0, m1, m2, m3);
+    var out: VSOut;
+    out.pos = camera.view_proj * model * vec4(pos, 1.0);
+    out.normal = (model * vec4(normal, 0.0)).xyz;
+    return out;
+}
+
+@fragment
+fn psMain(in: VSOut) -> @location(0) vec4<f32> {
+    let shade = 0.3 + 0.7 * max(dot(normalize(in.normal), normalize(vec3(0.4, 1.0, 0.3))), 0.0);
+    return vec4(vec3(1.0, 0.5, 0.2) * shade, 1.0);
+}
+"#;
+
+const INSTANCE_ATTRIBUTES: [wgpu::VertexAttribute; 4] =
+    wgpu::vertex_attr_array![4 => Float32x4, 5 => Float32x4, 6 => Float32x4, 7 => Float32x4];
+
+struct Instancing {
+    grid: u32,
+    spacing: f32,
+    dirty: bool,
+    pipeline: Option<wgpu::RenderPipeline>,
+    camera_bind_group: Option<wgpu::BindGroup>,
+    instance_buffer: Option<wgpu::Buffer>,
+    instance_count: u32,
+    mesh: Option<Arc<Mesh>>,
+}
+
+impl Instancing {
+    fn rebuild_instances(&mut self, device: &wgpu::Device) {
+        let half = (self.grid as f32 - 1.0) * 0.5;
+        let mut matrices = vec![];
+        for x in 0..self.grid {
+            for z in 0..self.grid {
+                let translation = glam::vec3(
+                    (x as f32 - half) * self.spacing,
+                    0.0,
+                    (z as f32 - half) * self.spacing,
+                );
+                let rotation = glam::Quat::from_rotation_y((x * 7 + z * 13) as f32);
+                let model = glam::Mat4::from_scale_rotation_translation(
+                    glam::Vec3::splat(0.02),
+                    rotation,
+                    translation,
+                );
+                matrices.push(model.to_cols_array_2d());
+            }
+        }
+        self.instance_buffer = Some(
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Instance Buffer"),
+                contents: bytemuck::cast_slice(&matrices),
+                usage: wgpu::BufferUsages::VERTEX,
+            }),
+        );
+        self.instance_count = matrices.len() as u32;
+        self.dirty = false;
+    }
+}
+
+impl Plugin for Instancing {
+    fn name(&self) -> &str {
+        "Instancing"
+    }
+
+    fn build(&mut self, ctx: &mut PluginContext) {
+        let device = &ctx.state.device;
+        let camera_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        self.camera_bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &camera_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: ctx.world.camera.buffer_ref().as_entire_binding(),
+            }],
+        }));
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&camera_layout],
+            push_constant_ranges: &[],
+        });
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Instancing"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        self.pipeline = Some(
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Instancing"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &module,
+                    entry_point: Some("vsMain"),
+                    buffers: &[
+                        Vertex::layout(),
+                        wgpu::VertexBufferLayout {
+                            array_stride: 64,
+                            step_mode: wgpu::VertexStepMode::Instance,
+                            attributes: &INSTANCE_ATTRIBUTES,
+                        },
+                    ],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &module,
+                    entry_point: Some("psMain"),
+                    compilation_options: Default::default(),
+                    targets: &[Some(ctx.state.surface_config.format.into())],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            }),
+        );
+
+        self.mesh = ctx.world.meshes.last().cloned();
+        ctx.world.clear();
+        self.rebuild_instances(device);
+    }
+
+    fn update(&mut self, ctx: &mut PluginContext) {
+        if self.dirty {
+            self.rebuild_instances(&ctx.state.device);
+        }
+    }
+
+    fn render(&self, _world: &World, renderpass: &mut wgpu::RenderPass) {
+        let (Some(pipeline), Some(bind_group), Some(instances), Some(mesh)) = (
+            &self.pipeline,
+            &self.camera_bind_group,
+            &self.instance_buffer,
+            &self.mesh,
+        ) else {
+            return;
+        };
+        renderpass.set_pipeline(pipeline);
+        renderpass.set_bind_group(0, bind_group, &[]);
+        renderpass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+        renderpass.set_vertex_buffer(1, instances.slice(..));
+        renderpass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        renderpass.draw_indexed(0..mesh.index_count, 0, 0..self.instance_count);
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, _state: &State, _world: &mut World) {
+        egui::Window::new("Instancing").show(ctx, |ui| {
+            let grid = ui.add(egui::Slider::new(&mut self.grid, 1..=200).text("Grid size"));
+            let spacing = ui.add(egui::Slider::new(&mut self.spacing, 0.5..=10.0).text("Spacing"));
+            self.dirty |= grid.changed() || spacing.changed();
+            ui.label(format!("{} instances, 1 draw call", self.instance_count));
+        });
+    }
+}
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugin(Instancing {
+        grid: 32,
+        spacing: 3.0,
+        dirty: true,
+        pipeline: None,
+        camera_bind_group: None,
+        instance_buffer: None,
+        instance_count: 0,
+        mesh: None,
+    });
+    rust_graphics_sandbox::run(app);
+}
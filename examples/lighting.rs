@@ -0,0 +1,227 @@
+//! Swaps the default unlit material for a WGSL Lambert plus GGX material
+//! with a directional light editable in egui, or lit by the procedural
+//! sky's sun. Turning up the bumps makes the highlight sparkle; specular
+//! anti-aliasing filters that out.
+//!
+//! `cargo run --example lighting`
+
+use rust_graphics_sandbox::material::Binding;
+use rust_graphics_sandbox::shader::Shader;
+use rust_graphics_sandbox::uniform::{UniformLayout, UniformType};
+use rust_graphics_sandbox::{App, MaterialInstance, Plugin, PluginContext, State, World};
+use std::sync::Arc;
+use wgpu::util::DeviceExt;
+
+const SHADER: &str = r#"
+struct Camera { view_proj: mat4x4<f32> };
+struct Light {
+    direction: vec3<f32>,
+    color: vec3<f32>,
+    ambient: vec3<f32>,
+    roughness: f32,
+    eye: vec3<f32>,
+    bumpiness: f32,
+    specular_aa: u32,
+};
+struct Model { model: mat4x4<f32>, base_color: vec4<f32>, alpha_cutoff: f32 };
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(1) @binding(0) var<uniform> light: Light;
+@group(2) @binding(0) var<uniform> model: Model;
+
+const PI: f32 = 3.14159265;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) world_pos: vec3<f32>,
+};
+
+@vertex
+fn vsMain(
+    @location(0) pos: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+) -> VSOut {
+    var out: VSOut;
+    let world_pos = model.model * vec4(pos, 1.0);
+    out.pos = camera.view_proj * world_pos;
+    out.normal = (model.model * vec4(normal, 0.0)).xyz;
+    out.world_pos = world_pos.xyz;
+    return out;
+}
+
+// GGX alpha squared, widened by the normal's variance across the pixel
+// (Tokuyoshi and Kaplanyan, "Improved Geometric Specular Antialiasing").
+// Bumps smaller than a pixel then blur into a rougher highlight instead
+// of flickering between fireflies.
+fn filtered_alpha2(n: vec3<f32>, alpha: f32) -> f32 {
+    let du = dpdx(n);
+    let dv = dpdy(n);
+    let variance = 0.25 * (dot(du, du) + dot(dv, dv));
+    let kernel = min(2.0 * variance, 0.18);
+    return clamp(alpha * alpha + kernel, 0.0, 1.0);
+}
+
+@fragment
+fn psMain(in: VSOut) -> @location(0) vec4<f32> {
+    // fine procedural bumps standing in for a detailed normal map
+    let bumps = sin(in.world_pos.yzx * 20.0) * light.bumpiness;
+    let n = normalize(normalize(in.normal) + bumps);
+    let alpha = light.roughness * light.roughness;
+    // derivatives need uniform control flow, so take them before discard
+    let filtered = filtered_alpha2(n, alpha);
+    if model.base_color.a < model.alpha_cutoff {
+        discard;
+    }
+    let alpha2 = select(alpha * alpha, filtered, light.specular_aa != 0u);
+
+    let l = -normalize(light.direction);
+    let v = normalize(light.eye - in.world_pos);
+    let h = normalize(l + v);
+    let n_dot_l = max(dot(n, l), 0.0);
+    let n_dot_v = max(dot(n, v), 1e-4);
+    let n_dot_h = max(dot(n, h), 0.0);
+
+    let d = n_dot_h * n_dot_h * (alpha2 - 1.0) + 1.0;
+    let distribution = alpha2 / (PI * d * d);
+    // Hammon's approximation of the height-correlated Smith term
+    let visibility = 0.5 / mix(2.0 * n_dot_l * n_dot_v, n_dot_l + n_dot_v, sqrt(alpha2));
+    let fresnel = 0.04 + 0.96 * pow(1.0 - max(dot(v, h), 0.0), 5.0);
+    let specular = distribution * visibility * fresnel;
+
+    let albedo = vec3(1.0, 0.5, 0.2);
+    // light.color is the diffuse brightness of a lit white surface, so
+    // the specular lobe is scaled by pi to match
+    let direct = light.color * n_dot_l * (albedo + PI * specular);
+    return vec4(albedo * light.ambient + direct, 1.0);
+}
+"#;
+
+struct Lighting {
+    direction: [f32; 3],
+    color: [f32; 3],
+    ambient: [f32; 3],
+    roughness: f32,
+    bumpiness: f32,
+    specular_aa: bool,
+    /// Takes direction, color and ambient from `World::sky`.
+    follow_sky: bool,
+    /// Camera position, for the view direction.
+    eye: [f32; 3],
+    layout: UniformLayout,
+    buffer: Option<Arc<wgpu::Buffer>>,
+}
+
+impl Lighting {
+    fn uniform(&self) -> Vec<u8> {
+        let mut data = self.layout.zeroed();
+        self.layout.write(&mut data, "direction", self.direction);
+        self.layout.write(&mut data, "color", self.color);
+        self.layout.write(&mut data, "ambient", self.ambient);
+        self.layout.write(&mut data, "roughness", self.roughness);
+        self.layout.write(&mut data, "eye", self.eye);
+        self.layout.write(&mut data, "bumpiness", self.bumpiness);
+        self.layout
+            .write(&mut data, "specular_aa", self.specular_aa as u32);
+        data
+    }
+}
+
+impl Plugin for Lighting {
+    fn name(&self) -> &str {
+        "Lighting"
+    }
+
+    fn build(&mut self, ctx: &mut PluginContext) {
+        self.layout.assert_wgsl(SHADER, "Light");
+        let buffer = Arc::new(ctx.state.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Light Uniform"),
+                contents: &self.uniform(),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+        ));
+        let bindings = vec![
+            Binding::uniform(
+                ctx.world.camera.buffer_ref().clone(),
+                wgpu::ShaderStages::VERTEX,
+            ),
+            Binding::uniform(buffer.clone(), wgpu::ShaderStages::FRAGMENT),
+        ];
+        let material = MaterialInstance::new_arc(ctx.state, bindings, &Shader::from_wgsl(SHADER));
+        for model in &mut ctx.world.models {
+            model.set_material(&ctx.state.device, material.clone());
+        }
+        ctx.world.materials.push(material);
+        self.buffer = Some(buffer);
+    }
+
+    fn update(&mut self, ctx: &mut PluginContext) {
+        self.eye = ctx.world.camera.eye.to_array();
+        if self.follow_sky {
+            let sky = &ctx.world.sky;
+            self.direction = sky.light_direction().to_array();
+            self.color = sky.sun_color().to_array();
+            self.ambient = sky.ambient().to_array();
+        }
+        if let Some(buffer) = &self.buffer {
+            ctx.state.queue.write_buffer(buffer, 0, &self.uniform());
+        }
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, _state: &State, world: &mut World) {
+        egui::Window::new("Light").show(ctx, |ui| {
+            if ui.checkbox(&mut self.follow_sky, "Follow sky").changed() {
+                world.sky.enabled = self.follow_sky;
+            }
+            if self.follow_sky {
+                let sky = &mut world.sky;
+                ui.add(egui::Slider::new(&mut sky.time_of_day, 0.0..=24.0).text("Time of day"));
+            }
+            ui.horizontal(|ui| {
+                ui.label("Direction: ");
+                for v in &mut self.direction {
+                    ui.add(egui::DragValue::new(v).speed(0.01));
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Color: ");
+                ui.color_edit_button_rgb(&mut self.color);
+            });
+            ui.horizontal(|ui| {
+                ui.label("Ambient: ");
+                ui.color_edit_button_rgb(&mut self.ambient);
+            });
+            ui.add(egui::Slider::new(&mut self.roughness, 0.02..=1.0).text("Roughness"));
+            ui.add(egui::Slider::new(&mut self.bumpiness, 0.0..=0.5).text("Bumps"));
+            ui.checkbox(&mut self.specular_aa, "Specular anti-aliasing");
+        });
+    }
+}
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugin(Lighting {
+        direction: [-0.5, -1.0, -0.3],
+        color: [1.0, 0.95, 0.9],
+        ambient: [0.1, 0.1, 0.15],
+        roughness: 0.2,
+        bumpiness: 0.0,
+        specular_aa: true,
+        follow_sky: false,
+        eye: [0.0; 3],
+        // vec3s are 16 apart in std140, which the layout pads for; a
+        // scalar after one fills its last 4 bytes
+        layout: UniformLayout::new()
+            .field("direction", UniformType::Vec3)
+            .field("color", UniformType::Vec3)
+            .field("ambient", UniformType::Vec3)
+            .field("roughness", UniformType::F32)
+            .field("eye", UniformType::Vec3)
+            .field("bumpiness", UniformType::F32)
+            .field("specular_aa", UniformType::U32),
+        buffer: None,
+    });
+    rust_graphics_sandbox::run(app);
+}
@@ -0,0 +1,114 @@
+//! Multiplies the model color by a lightmap sampled with the second UV
+//! set (TEXCOORD_1, or the first set when a mesh has none).
+//!
+//! `cargo run --example lightmap -- [path/to/lightmap.png]`
+//!
+//! Without a path a generated radial falloff is used.
+
+use rust_graphics_sandbox::material::Binding;
+use rust_graphics_sandbox::shader::Shader;
+use rust_graphics_sandbox::texture::Texture;
+use rust_graphics_sandbox::{App, MaterialInstance, Plugin, PluginContext};
+use std::sync::Arc;
+
+const SHADER: &str = r#"
+struct Camera { view_proj: mat4x4<f32> };
+struct Model { model: mat4x4<f32>, base_color: vec4<f32>, alpha_cutoff: f32 };
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(1) @binding(0) var lightmap: texture_2d<f32>;
+@group(1) @binding(1) var lightmap_sampler: sampler;
+@group(2) @binding(0) var<uniform> model: Model;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv1: vec2<f32>,
+};
+
+@vertex
+fn vsMain(
+    @location(0) pos: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(3) uv1: vec2<f32>,
+) -> VSOut {
+    var out: VSOut;
+    out.pos = camera.view_proj * model.model * vec4(pos, 1.0);
+    out.uv1 = uv1;
+    return out;
+}
+
+@fragment
+fn psMain(in: VSOut) -> @location(0) vec4<f32> {
+    let light = textureSample(lightmap, lightmap_sampler, in.uv1).rgb;
+    return vec4(vec3(1.0, 0.5, 0.2) * light, 1.0);
+}
+"#;
+
+const GENERATED_SIZE: u32 = 256;
+
+struct Lightmap {
+    path: Option<String>,
+}
+
+impl Plugin for Lightmap {
+    fn name(&self) -> &str {
+        "Lightmap"
+    }
+
+    fn build(&mut self, ctx: &mut PluginContext) {
+        let loaded = self.path.as_ref().and_then(|path| {
+            Texture::load(ctx.state, path, false)
+                .map_err(|e| log::warn!("Failed to load {path}: {e}"))
+                .ok()
+        });
+        let lightmap = loaded.unwrap_or_else(|| {
+            Texture::from_rgba8(
+                ctx.state,
+                "Generated Lightmap",
+                GENERATED_SIZE,
+                GENERATED_SIZE,
+                &radial_falloff(GENERATED_SIZE),
+                false,
+            )
+        });
+
+        let bindings = vec![Binding::uniform(
+            ctx.world.camera.buffer_ref().clone(),
+            wgpu::ShaderStages::VERTEX,
+        )];
+        let material = MaterialInstance::new_arc_with_lightmap(
+            ctx.state,
+            bindings,
+            &Shader::from_wgsl(SHADER),
+            Some(Arc::new(lightmap)),
+        );
+        for model in &mut ctx.world.models {
+            model.set_material(&ctx.state.device, material.clone());
+        }
+        ctx.world.materials.push(material);
+    }
+}
+
+/// Bright in the middle of UV space, falling off towards the edges.
+fn radial_falloff(size: u32) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let uv = glam::vec2(x as f32, y as f32) / size as f32 - 0.5;
+            let light = (1.0 - uv.length() * 1.6).clamp(0.15, 1.0);
+            let v = (light * 255.0) as u8;
+            pixels.extend_from_slice(&[v, v, v, 255]);
+        }
+    }
+    pixels
+}
+
+fn main() {
+    rust_graphics_sandbox::diagnostics::init();
+    let mut app = App::new();
+    app.add_plugin(Lightmap {
+        path: std::env::args().nth(1),
+    });
+    rust_graphics_sandbox::run(app);
+}
@@ -0,0 +1,178 @@
+//! Parallax occlusion mapping on a quad of generated bricks. Toggling POM
+//! swaps between the two permutations of the same material shader; look
+//! at the wall from a grazing angle to see the bricks occlude each other.
+//!
+//! `cargo run --example parallax`
+
+use rust_graphics_sandbox::mesh::{Mesh, Vertex};
+use rust_graphics_sandbox::model::EntityId;
+use rust_graphics_sandbox::parallax::ParallaxMaterial;
+use rust_graphics_sandbox::texture::Texture;
+use rust_graphics_sandbox::transform::Transform;
+use rust_graphics_sandbox::{App, Plugin, PluginContext, State, World};
+use std::sync::Arc;
+
+const TEXTURE_SIZE: u32 = 256;
+/// Bricks per texture row, and rows per texture.
+const BRICKS: u32 = 4;
+/// Times the texture repeats across the quad.
+const REPEAT: f32 = 3.0;
+
+#[derive(Default)]
+struct Parallax {
+    /// `[plain, parallax]`, built in `build`.
+    materials: Vec<ParallaxMaterial>,
+    enabled: bool,
+    /// Angle of the light around the wall's normal, in degrees.
+    light_angle: f32,
+    wall: Option<EntityId>,
+}
+
+impl Parallax {
+    fn light_direction(&self) -> glam::Vec3 {
+        let (sin, cos) = self.light_angle.to_radians().sin_cos();
+        glam::vec3(cos, sin, -1.0).normalize()
+    }
+}
+
+impl Plugin for Parallax {
+    fn name(&self) -> &str {
+        "Parallax"
+    }
+
+    fn build(&mut self, ctx: &mut PluginContext) {
+        let (albedo, height) = bricks(TEXTURE_SIZE);
+        let albedo = Arc::new(Texture::from_rgba8(
+            ctx.state,
+            "Brick Albedo",
+            TEXTURE_SIZE,
+            TEXTURE_SIZE,
+            &albedo,
+            true,
+        ));
+        let height = Arc::new(Texture::from_rgba8(
+            ctx.state,
+            "Brick Height",
+            TEXTURE_SIZE,
+            TEXTURE_SIZE,
+            &height,
+            false,
+        ));
+        let camera = ctx.world.camera.buffer_ref().clone();
+        self.materials = vec![
+            ParallaxMaterial::new(ctx.state, &camera, albedo.clone(), None),
+            ParallaxMaterial::new(ctx.state, &camera, albedo, Some(height)),
+        ];
+
+        for model in &mut ctx.world.models {
+            model.visible = false;
+        }
+        let mesh = Arc::new(quad(&ctx.state.device, 4.0));
+        let material = self.materials[self.enabled as usize].material.clone();
+        self.wall = Some(ctx.world.spawn(
+            ctx.state,
+            "Brick Wall",
+            mesh,
+            material,
+            Transform::default(),
+        ));
+        for material in &self.materials {
+            ctx.world.materials.push(material.material.clone());
+        }
+    }
+
+    fn update(&mut self, ctx: &mut PluginContext) {
+        let light_direction = self.light_direction();
+        for material in &self.materials {
+            material.update(&ctx.state.queue, ctx.world.camera.eye, light_direction);
+        }
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, state: &State, world: &mut World) {
+        egui::Window::new("Parallax").show(ctx, |ui| {
+            if ui
+                .checkbox(&mut self.enabled, "Parallax occlusion mapping")
+                .changed()
+            {
+                let material = self.materials[self.enabled as usize].material.clone();
+                if let Some(wall) = world.models.iter_mut().find(|m| Some(m.id) == self.wall) {
+                    wall.set_material(&state.device, material);
+                }
+            }
+            let settings = &mut self.materials[1].settings;
+            ui.add_enabled_ui(self.enabled, |ui| {
+                ui.add(egui::Slider::new(&mut settings.scale, 0.0..=0.2).text("Height scale"));
+                ui.add(egui::Slider::new(&mut settings.min_steps, 1..=64).text("Min steps"));
+                ui.add(egui::Slider::new(&mut settings.max_steps, 1..=128).text("Max steps"));
+            });
+            ui.add(egui::Slider::new(&mut self.light_angle, 0.0..=360.0).text("Light angle"));
+        });
+    }
+}
+
+/// A `size` wide quad facing +Z, with UVs repeating `REPEAT` times.
+fn quad(device: &wgpu::Device, size: f32) -> Mesh {
+    let half = size / 2.0;
+    let corners = [
+        ([-half, -half], [0.0, REPEAT]),
+        ([half, -half], [REPEAT, REPEAT]),
+        ([half, half], [REPEAT, 0.0]),
+        ([-half, half], [0.0, 0.0]),
+    ];
+    let vertices: Vec<Vertex> = corners
+        .iter()
+        .map(|&([x, y], uv)| Vertex {
+            pos: [x, y, 0.0],
+            normal: [0.0, 0.0, 1.0],
+            uv,
+            uv1: uv,
+        })
+        .collect();
+    Mesh::new(device, "Brick Wall", &vertices, &[0, 1, 2, 0, 2, 3])
+}
+
+/// RGBA8 albedo and height for running-bond bricks with bevelled edges
+/// and sunken mortar.
+fn bricks(size: u32) -> (Vec<u8>, Vec<u8>) {
+    let mut albedo = Vec::with_capacity((size * size * 4) as usize);
+    let mut height = Vec::with_capacity((size * size * 4) as usize);
+    let brick = size as f32 / BRICKS as f32;
+    for y in 0..size {
+        for x in 0..size {
+            let row = y as f32 / (brick / 2.0);
+            // every other row is offset by half a brick
+            let offset = if (row as u32) % 2 == 1 {
+                brick / 2.0
+            } else {
+                0.0
+            };
+            let u = ((x as f32 + offset) % brick) / brick;
+            let v = row.fract();
+            // distance to the nearest brick edge, in texels
+            let edge = (u.min(1.0 - u) * brick).min(v.min(1.0 - v) * brick / 2.0);
+            let h = ((edge - 2.0) / 4.0).clamp(0.0, 1.0);
+            // cheap per-brick tint so neighbours differ
+            let tint = ((row as u32 * 7 + ((x as f32 + offset) / brick) as u32 * 13) % 5) as f32;
+            let color = if h > 0.0 {
+                [150.0 + tint * 12.0, 60.0 + tint * 4.0, 45.0]
+            } else {
+                [170.0, 165.0, 155.0]
+            };
+            albedo.extend(color.map(|c| c as u8));
+            albedo.push(255);
+            let h = (h * 255.0) as u8;
+            height.extend_from_slice(&[h, h, h, 255]);
+        }
+    }
+    (albedo, height)
+}
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugin(Parallax {
+        enabled: true,
+        light_angle: 45.0,
+        ..Default::default()
+    });
+    rust_graphics_sandbox::run(app);
+}
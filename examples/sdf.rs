@@ -0,0 +1,402 @@
+//! Raymarches a small SDF scene in a fullscreen pass inside the scene pass.
+//! It writes depth, so the shapes intersect the rasterized fox correctly.
+//!
+//! `cargo run --example sdf`
+
+use rust_graphics_sandbox::{App, Plugin, PluginContext, State, World};
+use wgpu::util::DeviceExt;
+
+const MAX_SHAPES: usize = 16;
+
+const SHADER: &str = r#"
+struct Shape {
+    kind: u32,
+    op: u32,
+    _pad: vec2<u32>,
+    position: vec4<f32>,
+    size: vec4<f32>,
+    color: vec4<f32>,
+};
+
+struct Scene {
+    inv_view_proj: mat4x4<f32>,
+    view_proj: mat4x4<f32>,
+    count: u32,
+    max_steps: u32,
+    smoothness: f32,
+    max_distance: f32,
+    shapes: array<Shape, 16>,
+};
+
+@group(0) @binding(0) var<uniform> scene: Scene;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+};
+
+@vertex
+fn vsMain(@builtin(vertex_index) i: u32) -> VSOut {
+    // one triangle covering the screen
+    let ndc = vec2(f32(i == 1u) * 4.0 - 1.0, f32(i == 2u) * 4.0 - 1.0);
+    var out: VSOut;
+    out.pos = vec4(ndc, 0.0, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+fn shape_distance(shape: Shape, p: vec3<f32>) -> f32 {
+    let q = p - shape.position.xyz;
+    if shape.kind == 1u {
+        let d = abs(q) - shape.size.xyz;
+        return length(max(d, vec3(0.0))) + min(max(d.x, max(d.y, d.z)), 0.0);
+    }
+    return length(q) - shape.size.x;
+}
+
+// distance in x, color in yzw
+fn map(p: vec3<f32>) -> vec4<f32> {
+    var result = vec4(1e9, 0.0, 0.0, 0.0);
+    for (var i = 0u; i < scene.count; i++) {
+        let shape = scene.shapes[i];
+        let d = shape_distance(shape, p);
+        switch shape.op {
+            case 1u: { result.x = max(result.x, -d); }
+            case 2u: { result.x = max(result.x, d); }
+            case 3u: {
+                let k = max(scene.smoothness, 1e-4);
+                let h = clamp(0.5 + 0.5 * (d - result.x) / k, 0.0, 1.0);
+                result.x = mix(d, result.x, h) - k * h * (1.0 - h);
+                result = vec4(result.x, mix(shape.color.rgb, result.yzw, h));
+            }
+            default: {
+                if d < result.x {
+                    result = vec4(d, shape.color.rgb);
+                }
+            }
+        }
+    }
+    return result;
+}
+
+fn normal_at(p: vec3<f32>) -> vec3<f32> {
+    let e = vec2(0.001, 0.0);
+    return normalize(vec3(
+        map(p + e.xyy).x - map(p - e.xyy).x,
+        map(p + e.yxy).x - map(p - e.yxy).x,
+        map(p + e.yyx).x - map(p - e.yyx).x,
+    ));
+}
+
+struct FSOut {
+    @location(0) color: vec4<f32>,
+    @builtin(frag_depth) depth: f32,
+};
+
+@fragment
+fn psMain(in: VSOut) -> FSOut {
+    let near = scene.inv_view_proj * vec4(in.ndc, -1.0, 1.0);
+    let far = scene.inv_view_proj * vec4(in.ndc, 1.0, 1.0);
+    let origin = near.xyz / near.w;
+    let dir = normalize(far.xyz / far.w - origin);
+
+    var t = 0.0;
+    var hit = false;
+    for (var i = 0u; i < scene.max_steps; i++) {
+        let d = map(origin + dir * t).x;
+        if d < 0.001 * max(t, 1.0) {
+            hit = true;
+            break;
+        }
+        t += d;
+        if t > scene.max_distance {
+            break;
+        }
+    }
+    if !hit {
+        discard;
+    }
+
+    let p = origin + dir * t;
+    let clip = scene.view_proj * vec4(p, 1.0);
+    let depth = clip.z / clip.w;
+    // same range the rasterizer keeps for meshes
+    if depth < 0.0 || depth > 1.0 {
+        discard;
+    }
+
+    let n = normal_at(p);
+    let light = max(dot(n, normalize(vec3(0.4, 1.0, 0.3))), 0.0) * 0.8 + 0.2;
+    var out: FSOut;
+    out.color = vec4(map(p).yzw * light, 1.0);
+    out.depth = depth;
+    return out;
+}
+"#;
+
+#[repr(C)]
+#[derive(Copy, Clone, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShapeUniform {
+    kind: u32,
+    op: u32,
+    _pad: [u32; 2],
+    position: [f32; 4],
+    size: [f32; 4],
+    color: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct SceneUniform {
+    inv_view_proj: [[f32; 4]; 4],
+    view_proj: [[f32; 4]; 4],
+    count: u32,
+    max_steps: u32,
+    smoothness: f32,
+    max_distance: f32,
+    shapes: [ShapeUniform; MAX_SHAPES],
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ShapeKind {
+    Sphere,
+    Box,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Op {
+    Union,
+    Subtract,
+    Intersect,
+    SmoothUnion,
+}
+
+struct Shape {
+    kind: ShapeKind,
+    op: Op,
+    position: glam::Vec3,
+    /// Radius for spheres, half extents for boxes.
+    size: glam::Vec3,
+    color: [f32; 3],
+}
+
+struct Sdf {
+    shapes: Vec<Shape>,
+    max_steps: u32,
+    smoothness: f32,
+    max_distance: f32,
+    pipeline: Option<wgpu::RenderPipeline>,
+    buffer: Option<wgpu::Buffer>,
+    bind_group: Option<wgpu::BindGroup>,
+}
+
+impl Sdf {
+    fn uniform(&self, world: &World) -> SceneUniform {
+        let view_proj = world.camera.view_proj();
+        let mut shapes = [ShapeUniform::default(); MAX_SHAPES];
+        for (out, shape) in shapes.iter_mut().zip(&self.shapes) {
+            *out = ShapeUniform {
+                kind: shape.kind as u32,
+                op: shape.op as u32,
+                _pad: [0; 2],
+                position: shape.position.extend(0.0).into(),
+                size: shape.size.extend(0.0).into(),
+                color: [shape.color[0], shape.color[1], shape.color[2], 1.0],
+            };
+        }
+        SceneUniform {
+            inv_view_proj: view_proj.inverse().to_cols_array_2d(),
+            view_proj: view_proj.to_cols_array_2d(),
+            count: self.shapes.len().min(MAX_SHAPES) as u32,
+            max_steps: self.max_steps,
+            smoothness: self.smoothness,
+            max_distance: self.max_distance,
+            shapes,
+        }
+    }
+}
+
+impl Plugin for Sdf {
+    fn name(&self) -> &str {
+        "SDF"
+    }
+
+    fn build(&mut self, ctx: &mut PluginContext) {
+        let device = &ctx.state.device;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("SDF"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("SDF"),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vsMain"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("psMain"),
+                compilation_options: Default::default(),
+                targets: &[Some(ctx.state.surface_config.format.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("SDF Scene"),
+            contents: bytemuck::cast_slice(&[self.uniform(ctx.world)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("SDF"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        }));
+        self.pipeline = Some(pipeline);
+        self.buffer = Some(buffer);
+    }
+
+    fn render(&self, _world: &World, renderpass: &mut wgpu::RenderPass) {
+        let (Some(pipeline), Some(bind_group)) = (&self.pipeline, &self.bind_group) else {
+            return;
+        };
+        renderpass.set_pipeline(pipeline);
+        renderpass.set_bind_group(0, bind_group, &[]);
+        renderpass.draw(0..3, 0..1);
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, _state: &State, _world: &mut World) {
+        egui::Window::new("SDF Scene").show(ctx, |ui| {
+            ui.add(egui::Slider::new(&mut self.max_steps, 8..=256).text("Max steps"));
+            ui.add(egui::Slider::new(&mut self.max_distance, 1.0..=1000.0).text("Max distance"));
+            ui.add(egui::Slider::new(&mut self.smoothness, 0.0..=2.0).text("Smooth union k"));
+
+            let mut remove = None;
+            for (i, shape) in self.shapes.iter_mut().enumerate() {
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut shape.kind, ShapeKind::Sphere, "Sphere");
+                    ui.selectable_value(&mut shape.kind, ShapeKind::Box, "Box");
+                    egui::ComboBox::from_id_salt(("op", i))
+                        .selected_text(op_label(shape.op))
+                        .show_ui(ui, |ui| {
+                            for op in [Op::Union, Op::Subtract, Op::Intersect, Op::SmoothUnion] {
+                                ui.selectable_value(&mut shape.op, op, op_label(op));
+                            }
+                        });
+                    ui.color_edit_button_rgb(&mut shape.color);
+                    if ui.small_button("x").clicked() {
+                        remove = Some(i);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Position");
+                    for v in shape.position.as_mut() {
+                        ui.add(egui::DragValue::new(v).speed(0.05));
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Size");
+                    for v in shape.size.as_mut() {
+                        ui.add(egui::DragValue::new(v).speed(0.02).range(0.0..=100.0));
+                    }
+                });
+            }
+            if let Some(i) = remove {
+                self.shapes.remove(i);
+            }
+            if self.shapes.len() < MAX_SHAPES && ui.button("Add shape").clicked() {
+                self.shapes.push(Shape {
+                    kind: ShapeKind::Sphere,
+                    op: Op::Union,
+                    position: glam::Vec3::ZERO,
+                    size: glam::Vec3::splat(0.5),
+                    color: [0.8, 0.8, 0.8],
+                });
+            }
+        });
+    }
+
+    fn encode(
+        &mut self,
+        state: &State,
+        world: &World,
+        _encoder: &mut wgpu::CommandEncoder,
+        _target: &wgpu::TextureView,
+    ) {
+        // runs after the camera update, and queued writes land before this
+        // frame's command buffers execute
+        if let Some(buffer) = &self.buffer {
+            state
+                .queue
+                .write_buffer(buffer, 0, bytemuck::cast_slice(&[self.uniform(world)]));
+        }
+    }
+}
+
+fn op_label(op: Op) -> &'static str {
+    match op {
+        Op::Union => "Union",
+        Op::Subtract => "Subtract",
+        Op::Intersect => "Intersect",
+        Op::SmoothUnion => "Smooth union",
+    }
+}
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugin(Sdf {
+        shapes: vec![
+            Shape {
+                kind: ShapeKind::Box,
+                op: Op::Union,
+                position: glam::vec3(-1.5, 0.0, 0.0),
+                size: glam::Vec3::splat(0.6),
+                color: [0.2, 0.5, 0.9],
+            },
+            Shape {
+                kind: ShapeKind::Sphere,
+                op: Op::Subtract,
+                position: glam::vec3(-1.5, 0.0, 0.0),
+                size: glam::Vec3::splat(0.75),
+                color: [0.2, 0.5, 0.9],
+            },
+            Shape {
+                kind: ShapeKind::Sphere,
+                op: Op::SmoothUnion,
+                position: glam::vec3(1.5, 0.0, 0.0),
+                size: glam::Vec3::splat(0.6),
+                color: [0.9, 0.3, 0.3],
+            },
+            Shape {
+                kind: ShapeKind::Sphere,
+                op: Op::SmoothUnion,
+                position: glam::vec3(1.5, 0.7, 0.0),
+                size: glam::Vec3::splat(0.4),
+                color: [0.9, 0.8, 0.2],
+            },
+        ],
+        max_steps: 96,
+        smoothness: 0.3,
+        max_distance: 100.0,
+        pipeline: None,
+        buffer: None,
+        bind_group: None,
+    });
+    rust_graphics_sandbox::run(app);
+}
@@ -0,0 +1,241 @@
+//! Spot and point lights circling the fox, all shadowed from one atlas.
+//! Add lights or raise their resolution until the atlas fills up: later
+//! lights then get smaller tiles, then none, while the atlas stays the
+//! same size.
+//!
+//! `cargo run --example shadow_atlas`
+
+use rust_graphics_sandbox::material::Binding;
+use rust_graphics_sandbox::mesh::{Mesh, Vertex, OCTAHEDRAL_WGSL};
+use rust_graphics_sandbox::shader::Shader;
+use rust_graphics_sandbox::shadow_atlas::{LocalLight, ShadowAtlas, SHADOW_ATLAS_WGSL};
+use rust_graphics_sandbox::transform::Transform;
+use rust_graphics_sandbox::{App, MaterialInstance, Plugin, PluginContext, State, World};
+use std::sync::Arc;
+
+const SHADER: &str = r#"
+struct Camera { view_proj: mat4x4<f32> };
+struct Model { model: mat4x4<f32>, base_color: vec4<f32>, alpha_cutoff: f32 };
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(1) @binding(0) var<uniform> local_lights: LocalLights;
+@group(2) @binding(0) var shadow_atlas: texture_depth_2d;
+@group(2) @binding(1) var shadow_atlas_sampler: sampler_comparison;
+@group(3) @binding(0) var<uniform> model: Model;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) world_pos: vec3<f32>,
+};
+
+fn vertex(pos: vec3<f32>, normal: vec3<f32>) -> VSOut {
+    var out: VSOut;
+    let world_pos = model.model * vec4(pos, 1.0);
+    out.pos = camera.view_proj * world_pos;
+    out.normal = (model.model * vec4(normal, 0.0)).xyz;
+    out.world_pos = world_pos.xyz;
+    return out;
+}
+
+@vertex
+fn vsMain(@location(0) pos: vec3<f32>, @location(1) normal: vec3<f32>) -> VSOut {
+    return vertex(pos, normal);
+}
+
+@vertex
+fn vsMainPacked(@location(0) pos: vec3<f32>, @location(1) normal: vec2<f32>) -> VSOut {
+    return vertex(pos, octDecode(normal));
+}
+
+@fragment
+fn psMain(in: VSOut) -> @location(0) vec4<f32> {
+    if model.base_color.a < model.alpha_cutoff {
+        discard;
+    }
+    let n = normalize(in.normal);
+    var light = vec3(0.05);
+    for (var i = 0u; i < local_lights.count; i++) {
+        light += local_light(i, in.world_pos, n);
+    }
+    return vec4(model.base_color.rgb * light, 1.0);
+}
+"#;
+
+const COLORS: [[f32; 3]; 4] = [
+    [1.0, 0.8, 0.6],
+    [0.6, 0.8, 1.0],
+    [0.7, 1.0, 0.6],
+    [1.0, 0.6, 0.8],
+];
+
+struct ShadowAtlasDemo {
+    spots: usize,
+    points: usize,
+    spot_resolution: u32,
+    point_resolution: u32,
+    animate: bool,
+    angle: f32,
+    lights: Vec<LocalLight>,
+    atlas: Option<ShadowAtlas>,
+}
+
+impl ShadowAtlasDemo {
+    /// Spot lights on a high ring aimed at the fox, and point lights on a
+    /// low one, evenly spaced and turned by `angle`.
+    fn lights(&self) -> Vec<LocalLight> {
+        let ring = |i: usize, count: usize, radius: f32, height: f32| {
+            let a = self.angle + i as f32 / count as f32 * std::f32::consts::TAU;
+            glam::vec3(a.cos() * radius, height, a.sin() * radius)
+        };
+        let spots = (0..self.spots).map(|i| {
+            let position = ring(i, self.spots, 150.0, 120.0);
+            let target = glam::vec3(0.0, 20.0, 0.0);
+            LocalLight {
+                color: COLORS[i % COLORS.len()],
+                shadow_resolution: self.spot_resolution,
+                ..LocalLight::spot(position, target - position, 0.4, 400.0)
+            }
+        });
+        let points = (0..self.points).map(|i| LocalLight {
+            color: COLORS[(i + 2) % COLORS.len()].map(|c| c * 0.6),
+            shadow_resolution: self.point_resolution,
+            ..LocalLight::point(ring(i, self.points, 90.0, 40.0), 200.0)
+        });
+        spots.chain(points).collect()
+    }
+}
+
+impl Plugin for ShadowAtlasDemo {
+    fn name(&self) -> &str {
+        "Shadow Atlas"
+    }
+
+    fn build(&mut self, ctx: &mut PluginContext) {
+        let atlas = ShadowAtlas::new(ctx.state, 2048, 64);
+        let [lights, atlas_texture] = atlas.bindings();
+        let bindings = vec![
+            Binding::uniform(
+                ctx.world.camera.buffer_ref().clone(),
+                wgpu::ShaderStages::VERTEX,
+            ),
+            lights,
+            atlas_texture,
+        ];
+        let source = format!("{SHADER}{SHADOW_ATLAS_WGSL}{OCTAHEDRAL_WGSL}");
+        let material = MaterialInstance::new_arc(ctx.state, bindings, &Shader::from_wgsl(&source));
+        for model in &mut ctx.world.models {
+            model.set_material(&ctx.state.device, material.clone());
+        }
+        let ground = Arc::new(ground(&ctx.state.device, 400.0));
+        ctx.world.spawn(
+            ctx.state,
+            "Ground",
+            ground,
+            material.clone(),
+            Transform::default(),
+        );
+        ctx.world.materials.push(material);
+        ctx.world.camera.eye = glam::vec3(120.0, 140.0, 200.0);
+        ctx.world.camera.center = glam::vec3(0.0, 20.0, 0.0);
+        self.atlas = Some(atlas);
+    }
+
+    fn update(&mut self, ctx: &mut PluginContext) {
+        if self.animate {
+            self.angle += ctx.time.delta_seconds * 0.3;
+        }
+        self.lights = self.lights();
+        if let Some(atlas) = &mut self.atlas {
+            atlas.update(&ctx.state.queue, &self.lights);
+        }
+    }
+
+    fn prepare(&mut self, state: &State, world: &World, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(atlas) = &self.atlas {
+            atlas.encode(state, world, encoder);
+        }
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, _state: &State, _world: &mut World) {
+        let Some(atlas) = &mut self.atlas else {
+            return;
+        };
+        egui::Window::new("Shadow Atlas").show(ctx, |ui| {
+            ui.add(egui::Slider::new(&mut self.spots, 0..=8).text("Spot lights"));
+            ui.add(egui::Slider::new(&mut self.points, 0..=8).text("Point lights"));
+            ui.add(
+                egui::Slider::new(&mut self.spot_resolution, 0..=2048)
+                    .logarithmic(true)
+                    .text("Spot resolution"),
+            );
+            ui.add(
+                egui::Slider::new(&mut self.point_resolution, 0..=1024)
+                    .logarithmic(true)
+                    .text("Point resolution"),
+            );
+            ui.add(
+                egui::Slider::new(&mut atlas.depth_bias, 0.00001..=0.01)
+                    .logarithmic(true)
+                    .text("Depth bias"),
+            );
+            ui.add(egui::Slider::new(&mut atlas.pcf_radius, 0..=3).text("PCF radius"));
+            ui.checkbox(&mut self.animate, "Animate");
+            ui.label(format!(
+                "{} views, {:.0}% of the atlas, {} lights unshadowed",
+                atlas.views().len(),
+                atlas.occupancy() * 100.0,
+                atlas.unshadowed
+            ));
+            atlas_map_ui(ui, atlas, &self.lights);
+        });
+    }
+}
+
+/// The atlas's tiles, coloured by light.
+fn atlas_map_ui(ui: &mut egui::Ui, atlas: &ShadowAtlas, lights: &[LocalLight]) {
+    let side = 200.0;
+    let (response, painter) = ui.allocate_painter(egui::vec2(side, side), egui::Sense::hover());
+    let rect = response.rect;
+    painter.rect_filled(rect, 0.0, egui::Color32::from_gray(30));
+    let scale = side / atlas.size() as f32;
+    for view in atlas.views() {
+        let [r, g, b] = lights[view.light].color.map(|c| (c * 255.0) as u8);
+        let min = rect.min + egui::vec2(view.rect.x as f32, view.rect.y as f32) * scale;
+        let tile = egui::Rect::from_min_size(min, egui::Vec2::splat(view.rect.size as f32 * scale));
+        painter.rect_filled(tile.shrink(0.5), 0.0, egui::Color32::from_rgb(r, g, b));
+    }
+}
+
+/// A `size` wide square at y = 0 facing up.
+fn ground(device: &wgpu::Device, size: f32) -> Mesh {
+    let half = size / 2.0;
+    let corners = [[-half, half], [half, half], [half, -half], [-half, -half]];
+    let vertices: Vec<Vertex> = corners
+        .iter()
+        .map(|&[x, z]| Vertex {
+            pos: [x, 0.0, z],
+            normal: [0.0, 1.0, 0.0],
+            uv: [0.0; 2],
+            uv1: [0.0; 2],
+        })
+        .collect();
+    let mut mesh = Mesh::new(device, "Ground", &vertices, &[0, 1, 2, 0, 2, 3]);
+    mesh.base_color = [0.8, 0.8, 0.75, 1.0];
+    mesh
+}
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugin(ShadowAtlasDemo {
+        spots: 3,
+        points: 2,
+        spot_resolution: 512,
+        point_resolution: 256,
+        animate: true,
+        angle: 0.0,
+        lights: vec![],
+        atlas: None,
+    });
+    rust_graphics_sandbox::run(app);
+}
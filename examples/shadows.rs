@@ -0,0 +1,280 @@
+//! A directional light casting the fox's shadow onto a ground plane, with
+//! a switch between PCF and variance shadow maps. Turn the light towards
+//! the horizon to compare penumbrae, acne and light bleeding, or let the
+//! day/night cycle sweep the sun across the sky.
+//!
+//! `cargo run --example shadows`
+
+use rust_graphics_sandbox::material::Binding;
+use rust_graphics_sandbox::mesh::{Mesh, Vertex, OCTAHEDRAL_WGSL};
+use rust_graphics_sandbox::shader::Shader;
+use rust_graphics_sandbox::shadow::{ShadowMap, SHADOW_WGSL};
+use rust_graphics_sandbox::transform::Transform;
+use rust_graphics_sandbox::uniform::{UniformLayout, UniformType};
+use rust_graphics_sandbox::{App, MaterialInstance, Plugin, PluginContext, State, World};
+use std::sync::Arc;
+use wgpu::util::DeviceExt;
+
+const SHADER: &str = r#"
+struct Camera { view_proj: mat4x4<f32> };
+struct Light {
+    direction: vec3<f32>,
+    color: vec3<f32>,
+    ambient: vec3<f32>,
+    light_view_proj: mat4x4<f32>,
+    shadow_filter: u32,
+    shadow_bias: f32,
+    pcf_radius: i32,
+    min_variance: f32,
+    light_bleed: f32,
+};
+struct Model { model: mat4x4<f32>, base_color: vec4<f32>, alpha_cutoff: f32 };
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(1) @binding(0) var<uniform> light: Light;
+@group(2) @binding(0) var shadow_depth: texture_depth_2d;
+@group(2) @binding(1) var shadow_depth_sampler: sampler_comparison;
+@group(2) @binding(2) var shadow_moments: texture_2d<f32>;
+@group(2) @binding(3) var shadow_moments_sampler: sampler;
+@group(3) @binding(0) var<uniform> model: Model;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) world_pos: vec3<f32>,
+};
+
+fn vertex(pos: vec3<f32>, normal: vec3<f32>) -> VSOut {
+    var out: VSOut;
+    let world_pos = model.model * vec4(pos, 1.0);
+    out.pos = camera.view_proj * world_pos;
+    out.normal = (model.model * vec4(normal, 0.0)).xyz;
+    out.world_pos = world_pos.xyz;
+    return out;
+}
+
+@vertex
+fn vsMain(@location(0) pos: vec3<f32>, @location(1) normal: vec3<f32>) -> VSOut {
+    return vertex(pos, normal);
+}
+
+@vertex
+fn vsMainPacked(@location(0) pos: vec3<f32>, @location(1) normal: vec2<f32>) -> VSOut {
+    return vertex(pos, octDecode(normal));
+}
+
+@fragment
+fn psMain(in: VSOut) -> @location(0) vec4<f32> {
+    if model.base_color.a < model.alpha_cutoff {
+        discard;
+    }
+    let n = normalize(in.normal);
+    let n_dot_l = max(dot(n, -normalize(light.direction)), 0.0);
+    let lit = shadow(
+        light.light_view_proj,
+        in.world_pos,
+        light.shadow_filter,
+        light.shadow_bias,
+        light.pcf_radius,
+        light.min_variance,
+        light.light_bleed,
+    );
+    let albedo = model.base_color.rgb;
+    return vec4(albedo * (light.ambient + light.color * n_dot_l * lit), 1.0);
+}
+"#;
+
+struct Shadows {
+    /// Elevation and heading of the light, in degrees.
+    elevation: f32,
+    azimuth: f32,
+    color: [f32; 3],
+    ambient: [f32; 3],
+    /// Takes the light from `World::sky` and runs its day/night cycle.
+    follow_sky: bool,
+    shadow_map: Option<ShadowMap>,
+    layout: UniformLayout,
+    buffer: Option<Arc<wgpu::Buffer>>,
+}
+
+impl Shadows {
+    /// The way the light travels.
+    fn direction(&self) -> glam::Vec3 {
+        let (sin_e, cos_e) = self.elevation.to_radians().sin_cos();
+        let (sin_a, cos_a) = self.azimuth.to_radians().sin_cos();
+        -glam::vec3(cos_e * sin_a, sin_e, cos_e * cos_a)
+    }
+
+    fn uniform(&self) -> Vec<u8> {
+        let mut data = self.layout.zeroed();
+        self.layout
+            .write(&mut data, "direction", self.direction().to_array());
+        self.layout.write(&mut data, "color", self.color);
+        self.layout.write(&mut data, "ambient", self.ambient);
+        if let Some(shadow_map) = &self.shadow_map {
+            shadow_map.write_uniform(&self.layout, &mut data);
+        }
+        data
+    }
+}
+
+impl Plugin for Shadows {
+    fn name(&self) -> &str {
+        "Shadows"
+    }
+
+    fn build(&mut self, ctx: &mut PluginContext) {
+        let source = format!("{SHADER}{SHADOW_WGSL}{OCTAHEDRAL_WGSL}");
+        self.layout.assert_wgsl(&source, "Light");
+        let shadow_map = ShadowMap::new(ctx.state, 2048);
+        let buffer = Arc::new(ctx.state.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Light Uniform"),
+                contents: &self.uniform(),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+        ));
+        let bindings = vec![
+            Binding::uniform(
+                ctx.world.camera.buffer_ref().clone(),
+                wgpu::ShaderStages::VERTEX,
+            ),
+            Binding::uniform(buffer.clone(), wgpu::ShaderStages::FRAGMENT),
+            shadow_map.binding(),
+        ];
+        let material = MaterialInstance::new_arc(ctx.state, bindings, &Shader::from_wgsl(&source));
+        for model in &mut ctx.world.models {
+            model.set_material(&ctx.state.device, material.clone());
+        }
+        let ground = Arc::new(ground(&ctx.state.device, 400.0));
+        ctx.world.spawn(
+            ctx.state,
+            "Ground",
+            ground,
+            material.clone(),
+            Transform::default(),
+        );
+        ctx.world.materials.push(material);
+        ctx.world.camera.eye = glam::vec3(120.0, 100.0, 160.0);
+        ctx.world.camera.center = glam::vec3(0.0, 30.0, 0.0);
+        self.shadow_map = Some(shadow_map);
+        self.buffer = Some(buffer);
+    }
+
+    fn update(&mut self, ctx: &mut PluginContext) {
+        if self.follow_sky {
+            let sky = &ctx.world.sky;
+            let sun = sky.sun_direction();
+            self.elevation = sun.y.asin().to_degrees();
+            self.azimuth = sun.x.atan2(sun.z).to_degrees().rem_euclid(360.0);
+            self.color = sky.sun_color().to_array();
+            self.ambient = sky.ambient().to_array();
+        }
+        let direction = self.direction();
+        let (min, max) = bounds(ctx.world);
+        if let Some(shadow_map) = &mut self.shadow_map {
+            let center = (min + max) / 2.0;
+            let radius = ((max - min).length() / 2.0).max(1.0);
+            shadow_map.update(&ctx.state.queue, direction, center, radius);
+        }
+        if let Some(buffer) = &self.buffer {
+            ctx.state.queue.write_buffer(buffer, 0, &self.uniform());
+        }
+    }
+
+    fn prepare(&mut self, state: &State, world: &World, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(shadow_map) = &self.shadow_map {
+            shadow_map.encode(state, world, encoder);
+        }
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, _state: &State, world: &mut World) {
+        egui::Window::new("Shadows").show(ctx, |ui| {
+            if ui.checkbox(&mut self.follow_sky, "Follow sky").changed() {
+                world.sky.enabled = self.follow_sky;
+                world.sky.cycle.running = self.follow_sky;
+            }
+            if self.follow_sky {
+                world.sky.ui(ui);
+            } else {
+                ui.add(egui::Slider::new(&mut self.elevation, 5.0..=90.0).text("Light elevation"));
+                ui.add(egui::Slider::new(&mut self.azimuth, 0.0..=360.0).text("Light heading"));
+                ui.horizontal(|ui| {
+                    ui.label("Color: ");
+                    ui.color_edit_button_rgb(&mut self.color);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Ambient: ");
+                    ui.color_edit_button_rgb(&mut self.ambient);
+                });
+            }
+            if let Some(shadow_map) = &mut self.shadow_map {
+                ui.separator();
+                shadow_map.settings.ui(ui);
+            }
+        });
+    }
+}
+
+/// World-space bounds of every visible model, so the shadow map covers
+/// the whole scene.
+fn bounds(world: &World) -> (glam::Vec3, glam::Vec3) {
+    let mut min = glam::Vec3::splat(f32::MAX);
+    let mut max = glam::Vec3::splat(f32::MIN);
+    for model in world.models.iter().filter(|m| m.is_visible()) {
+        let (local_min, local_max) = model.mesh.bounds();
+        let matrix = model.global_matrix();
+        for i in 0..8 {
+            let corner = glam::vec3(
+                if i & 1 == 0 { local_min.x } else { local_max.x },
+                if i & 2 == 0 { local_min.y } else { local_max.y },
+                if i & 4 == 0 { local_min.z } else { local_max.z },
+            );
+            let corner = matrix.transform_point3(corner);
+            min = min.min(corner);
+            max = max.max(corner);
+        }
+    }
+    if min.x > max.x {
+        return (glam::Vec3::ZERO, glam::Vec3::ZERO);
+    }
+    (min, max)
+}
+
+/// A `size` wide square at y = 0 facing up.
+fn ground(device: &wgpu::Device, size: f32) -> Mesh {
+    let half = size / 2.0;
+    let corners = [[-half, half], [half, half], [half, -half], [-half, -half]];
+    let vertices: Vec<Vertex> = corners
+        .iter()
+        .map(|&[x, z]| Vertex {
+            pos: [x, 0.0, z],
+            normal: [0.0, 1.0, 0.0],
+            uv: [0.0; 2],
+            uv1: [0.0; 2],
+        })
+        .collect();
+    let mut mesh = Mesh::new(device, "Ground", &vertices, &[0, 1, 2, 0, 2, 3]);
+    mesh.base_color = [0.8, 0.8, 0.75, 1.0];
+    mesh
+}
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugin(Shadows {
+        elevation: 50.0,
+        azimuth: 120.0,
+        color: [1.0, 0.95, 0.9],
+        ambient: [0.15, 0.15, 0.2],
+        follow_sky: false,
+        shadow_map: None,
+        layout: ShadowMap::uniform_fields(
+            UniformLayout::new()
+                .field("direction", UniformType::Vec3)
+                .field("color", UniformType::Vec3)
+                .field("ambient", UniformType::Vec3),
+        ),
+        buffer: None,
+    });
+    rust_graphics_sandbox::run(app);
+}
@@ -0,0 +1,29 @@
+//! Replaces the default scene with a single triangle.
+//!
+//! `cargo run --example triangle`
+
+use rust_graphics_sandbox::mesh::create_test_mesh;
+use rust_graphics_sandbox::transform::Transform;
+use rust_graphics_sandbox::{App, Plugin, PluginContext};
+
+struct Triangle;
+
+impl Plugin for Triangle {
+    fn name(&self) -> &str {
+        "Triangle"
+    }
+
+    fn build(&mut self, ctx: &mut PluginContext) {
+        ctx.world.clear();
+        let mesh = create_test_mesh(&ctx.state.device);
+        let material = ctx.world.default_material();
+        ctx.world
+            .spawn(ctx.state, "Triangle", mesh, material, Transform::default());
+    }
+}
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugin(Triangle);
+    rust_graphics_sandbox::run(app);
+}
@@ -0,0 +1,21 @@
+// The Kernel Playground window's compute shader; it reloads on save.
+// The window's resources are bound in order in group 0: buffers as
+// read_write storage arrays, textures as rgba8unorm storage textures.
+// The first @compute entry point is dispatched; the default resources and
+// workgroup counts fit this file.
+
+@group(0) @binding(0) var<storage, read_write> values: array<f32>;
+@group(0) @binding(1) var image: texture_storage_2d<rgba8unorm, write>;
+
+@compute @workgroup_size(8, 8)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(image);
+    if id.x >= size.x || id.y >= size.y {
+        return;
+    }
+    let uv = vec2<f32>(id.xy) / vec2<f32>(size);
+    textureStore(image, id.xy, vec4(uv, 0.5, 1.0));
+    if id.y == 0u && id.x < arrayLength(&values) {
+        values[id.x] = sin(f32(id.x) * 0.1);
+    }
+}
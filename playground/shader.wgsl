@@ -0,0 +1,10 @@
+// The Shader Playground window's fragment shader; it reloads on save.
+// mainImage gets the pixel's position in the canvas, in pixels from the
+// bottom-left, and `playground` holds resolution, mouse, time, delta and
+// frame. See src/shader_playground.rs for the declarations.
+
+fn mainImage(coord: vec2<f32>) -> vec4<f32> {
+    let uv = coord / playground.resolution;
+    let color = 0.5 + 0.5 * cos(playground.time + uv.xyx + vec3(0.0, 2.0, 4.0));
+    return vec4(color, 1.0);
+}
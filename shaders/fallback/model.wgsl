@@ -0,0 +1,44 @@
+// WGSL port of model.slang, embedded in the binary for when the compiled
+// SPIR-V is missing. mesh::OCTAHEDRAL_WGSL is appended for octDecode.
+
+struct Camera { view_proj: mat4x4<f32> };
+struct Model { model: mat4x4<f32>, base_color: vec4<f32>, alpha_cutoff: f32 };
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(1) @binding(0) var<uniform> model: Model;
+// white unless the mesh is a cutout with a base color texture
+@group(1) @binding(1) var base_color_texture: texture_2d<f32>;
+@group(1) @binding(2) var base_color_sampler: sampler;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+fn vertex(pos: vec3<f32>, uv: vec2<f32>) -> VSOut {
+    var out: VSOut;
+    out.pos = camera.view_proj * model.model * vec4(pos, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+@vertex
+fn vsMain(@location(0) pos: vec3<f32>, @location(2) uv: vec2<f32>) -> VSOut {
+    return vertex(pos, uv);
+}
+
+@vertex
+fn vsMainPacked(@location(0) pos: vec3<f32>, @location(2) uv: vec2<f32>) -> VSOut {
+    return vertex(pos, uv);
+}
+
+@fragment
+fn psMain(in: VSOut) -> @location(0) vec4<f32> {
+    let alpha = model.base_color.a * textureSample(base_color_texture, base_color_sampler, in.uv).a;
+    // orange fox
+    let color = vec4(1.0, 0.5, 0.2, alpha);
+    if color.a < model.alpha_cutoff {
+        discard;
+    }
+    return color;
+}
@@ -0,0 +1,137 @@
+// Textured, lit material. With PARALLAX defined the albedo is sampled
+// through parallax occlusion mapping of the height texture.
+
+struct Camera { view_proj: mat4x4<f32> };
+struct Parallax {
+    eye: vec3<f32>,
+    scale: f32,
+    light_direction: vec3<f32>,
+    min_steps: f32,
+    max_steps: f32,
+};
+struct Model { model: mat4x4<f32>, base_color: vec4<f32>, alpha_cutoff: f32 };
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(1) @binding(0) var<uniform> params: Parallax;
+@group(2) @binding(0) var albedo: texture_2d<f32>;
+@group(2) @binding(1) var albedo_sampler: sampler;
+#ifdef PARALLAX
+@group(2) @binding(2) var height: texture_2d<f32>;
+@group(2) @binding(3) var height_sampler: sampler;
+#endif
+@group(3) @binding(0) var<uniform> model: Model;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) world_pos: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+};
+
+fn vertex(pos: vec3<f32>, normal: vec3<f32>, uv: vec2<f32>) -> VSOut {
+    var out: VSOut;
+    let world_pos = model.model * vec4(pos, 1.0);
+    out.pos = camera.view_proj * world_pos;
+    out.world_pos = world_pos.xyz;
+    out.normal = (model.model * vec4(normal, 0.0)).xyz;
+    out.uv = uv;
+    return out;
+}
+
+@vertex
+fn vsMain(
+    @location(0) pos: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+) -> VSOut {
+    return vertex(pos, normal, uv);
+}
+
+@vertex
+fn vsMainPacked(
+    @location(0) pos: vec3<f32>,
+    @location(1) normal: vec2<f32>,
+    @location(2) uv: vec2<f32>,
+) -> VSOut {
+    return vertex(pos, octDecode(normal), uv);
+}
+
+// Tangent and bitangent along +u and +v, from screen-space derivatives
+// (Schüler, "Followup: Normal Mapping Without Precomputed Tangents").
+// Meshes don't carry tangents, and this also handles mirrored UVs. WGSL's
+// window y grows downwards, so the perpendiculars are the other way round
+// from the GLSL original.
+fn cotangent_frame(n: vec3<f32>, p: vec3<f32>, uv: vec2<f32>) -> mat3x3<f32> {
+    let dp1 = dpdx(p);
+    let dp2 = dpdy(p);
+    let duv1 = dpdx(uv);
+    let duv2 = dpdy(uv);
+    let dp2perp = cross(n, dp2);
+    let dp1perp = cross(dp1, n);
+    let t = dp2perp * duv1.x + dp1perp * duv2.x;
+    let b = dp2perp * duv1.y + dp1perp * duv2.y;
+    let inv_max = inverseSqrt(max(max(dot(t, t), dot(b, b)), 1e-12));
+    return mat3x3(t * inv_max, b * inv_max, n);
+}
+
+#ifdef PARALLAX
+fn depth_at(uv: vec2<f32>, ddx: vec2<f32>, ddy: vec2<f32>) -> f32 {
+    return 1.0 - textureSampleGrad(height, height_sampler, uv, ddx, ddy).r;
+}
+
+// Steps the view ray down through depth layers until it passes under the
+// height field, then interpolates between the last two layers. Explicit
+// gradients keep mip selection right inside the non-uniform loop.
+fn parallax_uv(uv: vec2<f32>, view_ts: vec3<f32>, ddx: vec2<f32>, ddy: vec2<f32>) -> vec2<f32> {
+    // grazing views need more layers to avoid stair-stepping
+    let steps = mix(params.max_steps, params.min_steps, abs(view_ts.z));
+    let layer = 1.0 / steps;
+    let delta = -view_ts.xy / max(view_ts.z, 0.05) * params.scale * layer;
+
+    var current_uv = uv;
+    var current_layer = 0.0;
+    var depth = depth_at(current_uv, ddx, ddy);
+    for (var i = 0; i < i32(params.max_steps) && current_layer < depth; i++) {
+        current_uv += delta;
+        current_layer += layer;
+        depth = depth_at(current_uv, ddx, ddy);
+    }
+
+    let previous_uv = current_uv - delta;
+    let after = depth - current_layer;
+    let before = depth_at(previous_uv, ddx, ddy) - (current_layer - layer);
+    let weight = after / (after - before);
+    return mix(current_uv, previous_uv, clamp(weight, 0.0, 1.0));
+}
+
+// Tangent-space normal from the height field's slope, so the lighting
+// follows the parallax bumps.
+fn height_normal(uv: vec2<f32>, ddx: vec2<f32>, ddy: vec2<f32>) -> vec3<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(height));
+    let du = depth_at(uv + vec2(texel.x, 0.0), ddx, ddy) - depth_at(uv - vec2(texel.x, 0.0), ddx, ddy);
+    let dv = depth_at(uv + vec2(0.0, texel.y), ddx, ddy) - depth_at(uv - vec2(0.0, texel.y), ddx, ddy);
+    // depth grows into the surface, so it slopes the opposite way to height
+    return normalize(vec3(du, dv, 2.0 * texel.x / params.scale));
+}
+#endif
+
+@fragment
+fn psMain(in: VSOut) -> @location(0) vec4<f32> {
+    let tbn = cotangent_frame(normalize(in.normal), in.world_pos, in.uv);
+    let ddx = dpdx(in.uv);
+    let ddy = dpdy(in.uv);
+    var uv = in.uv;
+    var normal_ts = vec3(0.0, 0.0, 1.0);
+#ifdef PARALLAX
+    let view_ts = normalize(transpose(tbn) * (params.eye - in.world_pos));
+    uv = parallax_uv(in.uv, view_ts, ddx, ddy);
+    normal_ts = height_normal(uv, ddx, ddy);
+#endif
+    let color = textureSampleGrad(albedo, albedo_sampler, uv, ddx, ddy) * model.base_color;
+    if color.a < model.alpha_cutoff {
+        discard;
+    }
+    let n = normalize(tbn * normal_ts);
+    let diffuse = max(dot(n, -normalize(params.light_direction)), 0.0);
+    return vec4(color.rgb * (0.15 + 0.85 * diffuse), color.a);
+}
@@ -0,0 +1,37 @@
+// Colors models by world position, a metre per repeat, for checking
+// placement and scale. Binds like the model shader, so it can be assigned
+// to any model drawn with the default material.
+
+struct Camera { view_proj: mat4x4<f32> };
+struct Model { model: mat4x4<f32>, base_color: vec4<f32>, alpha_cutoff: f32 };
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(1) @binding(0) var<uniform> model: Model;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) world_pos: vec3<f32>,
+};
+
+fn vertex(pos: vec3<f32>) -> VSOut {
+    let world = model.model * vec4(pos, 1.0);
+    return VSOut(camera.view_proj * world, world.xyz);
+}
+
+@vertex
+fn vsMain(@location(0) pos: vec3<f32>) -> VSOut {
+    return vertex(pos);
+}
+
+@vertex
+fn vsMainPacked(@location(0) pos: vec3<f32>) -> VSOut {
+    return vertex(pos);
+}
+
+@fragment
+fn psMain(in: VSOut) -> @location(0) vec4<f32> {
+    if model.base_color.a < model.alpha_cutoff {
+        discard;
+    }
+    return vec4(fract(in.world_pos), model.base_color.a);
+}
@@ -1,11 +1,42 @@
+use crate::asset_cache;
+use crate::background::BackgroundMode;
+use crate::camera_controller::CameraMode;
+use crate::commands::{self, CommandContext};
+use crate::config::Config;
+use crate::console::Console;
+use crate::detachable::DetachableWindow;
+use crate::diagnostics;
 use crate::egui_renderer::EguiRenderer;
+use crate::frame_pacing::{FramePacer, LatencyMeter, VsyncMode};
+use crate::gpu_caps::{self, GpuCaps};
+use crate::gpu_profiler::{self, GpuProfiler};
+use crate::hdr_output::{self, HdrOutput};
+use crate::hdr_still::{HdrImage, HdrStill};
+use crate::i18n::{self, tr};
+use crate::input::{Action, Input};
+use crate::inspector::Inspector;
+use crate::jobs;
+use crate::material;
+use crate::menu::MenuBar;
+use crate::model::Model;
+use crate::plugin::{Plugin, PluginContext};
+use crate::quality::{self, QualityPreset};
+use crate::sampler::SamplerCache;
+use crate::session::SessionRecorder;
+use crate::snapping;
+use crate::time::Time;
+use crate::transient::TransientPool;
+use crate::ui_theme;
+use crate::workspace::Workspace;
 use crate::world::World;
 use egui_wgpu::{wgpu::SurfaceError, ScreenDescriptor};
+use std::path::Path;
 use std::sync::Arc;
 use std::time::Instant;
+use wgpu::util::DeviceExt;
 use winit::{
     application::ApplicationHandler,
-    dpi::PhysicalSize,
+    dpi::{PhysicalPosition, PhysicalSize},
     event::WindowEvent,
     event_loop::ActiveEventLoop,
     window::{Window, WindowId},
@@ -16,17 +47,47 @@ pub struct DepthTexture {
     pub view: wgpu::TextureView,
 }
 
+/// GPU device, surface and per-window render resources.
 pub struct State {
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub surface_config: wgpu::SurfaceConfiguration,
-    pub surface: wgpu::Surface<'static>,
+    /// `None` when running headless; frames then go to an offscreen target.
+    pub surface: Option<wgpu::Surface<'static>>,
+    /// Compositing modes the surface supports.
+    pub alpha_modes: Vec<wgpu::CompositeAlphaMode>,
+    /// Formats the surface supports.
+    pub surface_formats: Vec<wgpu::TextureFormat>,
+    /// Present modes the surface supports.
+    pub present_modes: Vec<wgpu::PresentMode>,
+    /// Set when the surface shows HDR; frames are then drawn offscreen and
+    /// encoded to it last.
+    pub hdr: Option<HdrOutput>,
+    /// Whether the window shows through where the frame's alpha is below
+    /// one; see [`State::set_transparent`].
+    pub transparent: bool,
     pub adapter: wgpu::Adapter,
+    /// Physical pixels per logical pixel of the window.
     pub scale_factor: f32,
-    pub egui_renderer: EguiRenderer,
+    /// The UI's own scale on top of `scale_factor`.
+    pub ui_scale: f32,
     pub depth_texture: DepthTexture,
+    pub model_bind_group_layout: wgpu::BindGroupLayout,
+    /// 1×1 white, which models without a base color texture bind.
+    pub white_texture: wgpu::TextureView,
+    /// Per-frame render targets shared between passes.
+    pub transient: TransientPool,
+    /// Samplers shared by every texture and material.
+    pub samplers: SamplerCache,
+    /// Per-pass draw counts and GPU times for the frame breakdown.
+    pub profiler: GpuProfiler,
+    /// Optional features the device has.
+    pub caps: GpuCaps,
 }
 
+const DEFAULT_FRAME_LATENCY: u32 = 2;
+pub const MAX_FRAME_LATENCY: u32 = 3;
+
 fn create_depth_texture(
     device: &wgpu::Device,
     config: &wgpu::SurfaceConfiguration,
@@ -41,7 +102,8 @@ fn create_depth_texture(
         sample_count: 1,
         dimension: wgpu::TextureDimension::D2,
         format: wgpu::TextureFormat::Depth32Float,
-        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        // sampled by the lens flare's occlusion test
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
         label: None,
         view_formats: &[],
     });
@@ -51,113 +113,464 @@ fn create_depth_texture(
     DepthTexture { texture, view }
 }
 
+fn create_white_texture(device: &wgpu::Device, queue: &wgpu::Queue) -> wgpu::TextureView {
+    let texture = device.create_texture_with_data(
+        queue,
+        &wgpu::TextureDescriptor {
+            label: Some("White"),
+            size: wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        },
+        wgpu::util::TextureDataOrder::LayerMajor,
+        &[255; 4],
+    );
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+/// Prefers premultiplied alpha for a transparent window, which is what the
+/// scene and egui write; falls back to whatever the surface offers first.
+fn alpha_mode(modes: &[wgpu::CompositeAlphaMode], transparent: bool) -> wgpu::CompositeAlphaMode {
+    use wgpu::CompositeAlphaMode::*;
+    let preferred: &[_] = if transparent {
+        &[PreMultiplied, PostMultiplied, Inherit]
+    } else {
+        &[Opaque]
+    };
+    preferred
+        .iter()
+        .copied()
+        .find(|mode| modes.contains(mode))
+        .unwrap_or(modes[0])
+}
+
+fn create_encoder(device: &wgpu::Device, label: &str) -> wgpu::CommandEncoder {
+    device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some(label) })
+}
+
+/// Records one pass of the frame in its own encoder, error scope and
+/// profiler entry.
+fn encode_pass(
+    state: &State,
+    label: &str,
+    f: impl FnOnce(&mut wgpu::CommandEncoder),
+) -> wgpu::CommandBuffer {
+    diagnostics::error_scope(&state.device, label, || {
+        let mut encoder = create_encoder(&state.device, label);
+        state.profiler.begin(&mut encoder, label);
+        f(&mut encoder);
+        state.profiler.end(&mut encoder);
+        encoder.finish()
+    })
+}
+
+/// `encode_pass` for compute submitted ahead of the frame.
+fn encode_compute_pass(
+    state: &State,
+    label: &str,
+    f: impl FnOnce(&mut wgpu::CommandEncoder),
+) -> wgpu::CommandBuffer {
+    diagnostics::error_scope(&state.device, label, || {
+        let mut encoder = create_encoder(&state.device, label);
+        state.profiler.begin_async_compute(&mut encoder, label);
+        f(&mut encoder);
+        state.profiler.end(&mut encoder);
+        encoder.finish()
+    })
+}
+
+async fn request_device(
+    instance: &wgpu::Instance,
+    surface: Option<&wgpu::Surface<'static>>,
+) -> (wgpu::Adapter, wgpu::Device, wgpu::Queue) {
+    let power_pref = wgpu::PowerPreference::default();
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: power_pref,
+            force_fallback_adapter: false,
+            compatible_surface: surface,
+        })
+        .await
+        .expect("Failed to find an appropriate adapter");
+
+    let (device, queue) = adapter
+        .request_device(&wgpu::DeviceDescriptor {
+            label: None,
+            required_features: gpu_caps::wanted_features(&adapter),
+            required_limits: gpu_caps::wanted_limits(&adapter),
+            experimental_features: Default::default(),
+            memory_hints: Default::default(),
+            trace: Default::default(),
+        })
+        .await
+        .expect("Failed to create device");
+
+    diagnostics::set_adapter_info(adapter.get_info());
+    diagnostics::install_device_error_handler(&device);
+
+    (adapter, device, queue)
+}
+
 impl State {
     async fn new(
         instance: &wgpu::Instance,
         surface: wgpu::Surface<'static>,
-        window: &Window,
         width: u32,
         height: u32,
+        transparent: bool,
+        hdr: bool,
     ) -> Self {
-        let power_pref = wgpu::PowerPreference::default();
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: power_pref,
-                force_fallback_adapter: false,
-                compatible_surface: Some(&surface),
-            })
-            .await
-            .expect("Failed to find an appropriate adapter");
-
-        let features = wgpu::Features::empty();
-        let (device, queue) = adapter
-            .request_device(&wgpu::DeviceDescriptor {
-                label: None,
-                required_features: features,
-                required_limits: Default::default(),
-                experimental_features: Default::default(),
-                memory_hints: Default::default(),
-                trace: Default::default(),
-            })
-            .await
-            .expect("Failed to create device");
+        let (adapter, device, queue) = request_device(instance, Some(&surface)).await;
 
         let swapchain_capabilities = surface.get_capabilities(&adapter);
-        let selected_format = wgpu::TextureFormat::Bgra8UnormSrgb;
+        let hdr = hdr && hdr_output::supported(&swapchain_capabilities.formats);
+        let selected_format = if hdr {
+            hdr_output::FORMAT
+        } else {
+            wgpu::TextureFormat::Bgra8UnormSrgb
+        };
         let swapchain_format = swapchain_capabilities
             .formats
             .iter()
             .find(|d| **d == selected_format)
+            // Android surfaces are usually RGBA only
+            .or_else(|| swapchain_capabilities.formats.iter().find(|f| f.is_srgb()))
             .expect("failed to select proper surface texture format!");
+        let present_mode = if swapchain_capabilities
+            .present_modes
+            .contains(&wgpu::PresentMode::Immediate)
+        {
+            wgpu::PresentMode::Immediate
+        } else {
+            wgpu::PresentMode::Fifo
+        };
 
+        // copying out of the swapchain is what screenshots use
+        let usage = wgpu::TextureUsages::RENDER_ATTACHMENT
+            | (swapchain_capabilities.usages & wgpu::TextureUsages::COPY_SRC);
         let surface_config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            usage,
             format: *swapchain_format,
             width,
             height,
-            present_mode: wgpu::PresentMode::Immediate,
-            desired_maximum_frame_latency: 0,
-            alpha_mode: swapchain_capabilities.alpha_modes[0],
+            present_mode,
+            desired_maximum_frame_latency: DEFAULT_FRAME_LATENCY,
+            alpha_mode: alpha_mode(&swapchain_capabilities.alpha_modes, transparent),
             view_formats: vec![],
         };
 
-        surface.configure(&device, &surface_config);
+        diagnostics::error_scope(&device, "surface configuration", || {
+            surface.configure(&device, &surface_config)
+        });
+
+        let mut state = Self::from_parts(adapter, device, queue, Some(surface), surface_config);
+        state.alpha_modes = swapchain_capabilities.alpha_modes;
+        state.surface_formats = swapchain_capabilities.formats;
+        state.present_modes = swapchain_capabilities.present_modes;
+        state.transparent = transparent;
+        if hdr {
+            state.hdr = Some(HdrOutput::new(&state.device));
+        }
+        state
+    }
+
+    /// Creates a device without a window. `surface_config` then only
+    /// describes the offscreen target's size and format.
+    pub async fn new_headless(
+        instance: &wgpu::Instance,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let (adapter, device, queue) = request_device(instance, None).await;
+
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Fifo,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+        };
 
-        let egui_renderer = EguiRenderer::new(&device, surface_config.format, window);
+        Self::from_parts(adapter, device, queue, None, surface_config)
+    }
 
+    fn from_parts(
+        adapter: wgpu::Adapter,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        surface: Option<wgpu::Surface<'static>>,
+        surface_config: wgpu::SurfaceConfiguration,
+    ) -> Self {
+        // set from the window once there is one
         let scale_factor = 1.0;
+        diagnostics::set_surface_config(&surface_config);
 
-        let depth_texture = create_depth_texture(&device, &surface_config);
+        let (depth_texture, model_bind_group_layout, white_texture) =
+            diagnostics::error_scope(&device, "render state creation", || {
+                (
+                    create_depth_texture(&device, &surface_config),
+                    Model::create_bind_group_layout(&device),
+                    create_white_texture(&device, &queue),
+                )
+            });
+        let caps = GpuCaps::new(&adapter, &device);
+        let profiler = GpuProfiler::new(&device, &queue, caps.timestamps);
 
         Self {
             device,
             queue,
             surface,
+            alpha_modes: vec![surface_config.alpha_mode],
+            surface_formats: vec![surface_config.format],
+            present_modes: vec![surface_config.present_mode],
+            hdr: None,
+            transparent: false,
             surface_config,
             adapter,
-            egui_renderer,
             scale_factor,
+            ui_scale: 1.0,
             depth_texture,
+            model_bind_group_layout,
+            white_texture,
+            transient: TransientPool::new(),
+            samplers: SamplerCache::new(),
+            profiler,
+            caps,
         }
     }
 
-    fn resize_surface(&mut self, width: u32, height: u32) {
+    /// Replaces the surface after a suspend. The device and everything
+    /// created with it survive, so nothing is reloaded.
+    pub fn attach_surface(&mut self, surface: wgpu::Surface<'static>, width: u32, height: u32) {
+        self.surface = Some(surface);
+        self.resize_surface(width.max(1), height.max(1));
+    }
+
+    /// Ignores zero sizes: a surface can't be configured with one, so a
+    /// minimized window keeps its last size until it's restored.
+    pub fn resize_surface(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
         self.surface_config.width = width;
         self.surface_config.height = height;
-        self.surface.configure(&self.device, &self.surface_config);
+        self.reconfigure();
 
         self.depth_texture = create_depth_texture(&self.device, &self.surface_config);
     }
+
+    /// What egui draws the main window's UI with.
+    pub fn screen_descriptor(&self) -> ScreenDescriptor {
+        ScreenDescriptor {
+            size_in_pixels: [self.surface_config.width, self.surface_config.height],
+            pixels_per_point: self.scale_factor * self.ui_scale,
+        }
+    }
+
+    /// What egui draws into.
+    pub fn ui_format(&self) -> wgpu::TextureFormat {
+        if self.hdr.is_some() {
+            hdr_output::UI_FORMAT
+        } else {
+            self.surface_config.format
+        }
+    }
+
+    /// Offscreen targets always keep their alpha.
+    pub fn supports_transparency(&self) -> bool {
+        use wgpu::CompositeAlphaMode::*;
+        self.surface.is_none()
+            || self
+                .alpha_modes
+                .iter()
+                .any(|mode| matches!(mode, PreMultiplied | PostMultiplied | Inherit))
+    }
+
+    /// Reconfigures the surface to be composited over what's behind the
+    /// window, or not. Returns false if transparency was asked for and the
+    /// surface can't do it; it stays opaque then.
+    pub fn set_transparent(&mut self, transparent: bool) -> bool {
+        self.transparent = transparent;
+        let mode = alpha_mode(&self.alpha_modes, transparent);
+        if mode != self.surface_config.alpha_mode {
+            self.surface_config.alpha_mode = mode;
+            self.reconfigure();
+        }
+        !transparent || self.supports_transparency()
+    }
+
+    pub fn supports_vsync(&self, mode: VsyncMode) -> bool {
+        self.present_modes.contains(&mode.present_mode())
+    }
+
+    pub fn vsync(&self) -> VsyncMode {
+        VsyncMode::from_present_mode(self.surface_config.present_mode)
+    }
+
+    /// Returns false and keeps the current mode if the surface doesn't
+    /// support `mode`.
+    pub fn set_vsync(&mut self, mode: VsyncMode) -> bool {
+        if !self.supports_vsync(mode) {
+            return false;
+        }
+        if mode.present_mode() != self.surface_config.present_mode {
+            self.surface_config.present_mode = mode.present_mode();
+            self.reconfigure();
+        }
+        true
+    }
+
+    /// How many frames the CPU may queue ahead of the display. Fewer cuts
+    /// latency, more smooths out uneven frames; surfaces clamp it to what
+    /// they support.
+    pub fn frame_latency(&self) -> u32 {
+        self.surface_config.desired_maximum_frame_latency
+    }
+
+    pub fn set_frame_latency(&mut self, frames: u32) {
+        let frames = frames.clamp(1, MAX_FRAME_LATENCY);
+        if frames != self.surface_config.desired_maximum_frame_latency {
+            self.surface_config.desired_maximum_frame_latency = frames;
+            self.reconfigure();
+        }
+    }
+
+    fn reconfigure(&self) {
+        diagnostics::set_surface_config(&self.surface_config);
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.surface_config);
+        }
+    }
+
+    /// Begins the main scene pass: clears `view` to `clear` and the depth
+    /// buffer.
+    pub fn begin_scene_pass<'a>(
+        &self,
+        encoder: &'a mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        clear: wgpu::Color,
+    ) -> wgpu::RenderPass<'a> {
+        let depth = &self.depth_texture.texture;
+        self.profiler.record_target(depth.width(), depth.height());
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("scene"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                depth_slice: None,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(clear),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        })
+    }
 }
 
+/// winit application: owns the window, [`State`], [`World`] and plugins.
 pub struct App {
     instance: wgpu::Instance,
     state: Option<State>,
+    egui_renderer: Option<EguiRenderer>,
     window: Option<Arc<Window>>,
     world: Option<World>,
-    last_frame: Instant,
-    smoothed_dt: f32,
+    time: Time,
+    config: Config,
+    input: Input,
+    show_debug_ui: bool,
+    plugins: Vec<Box<dyn Plugin>>,
+    /// Milliseconds each plugin's last `update` took, in plugin order.
+    plugin_times: Vec<f32>,
+    session: SessionRecorder,
+    pacer: FramePacer,
+    latency: LatencyMeter,
+    inspector: Inspector,
+    console: Console,
+    menu_bar: MenuBar,
+    workspace: Workspace,
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl App {
     pub fn new() -> Self {
         let instance = egui_wgpu::wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
-        let last_frame = Instant::now();
-        let smoothed_dt = 0.0f32;
+        let time = Time::new();
+        let config = Config::load();
+        let input = Input::new(config.bindings.clone());
+        let pacer = FramePacer::new(config.fps_cap);
         Self {
             instance,
             state: None,
+            egui_renderer: None,
             window: None,
             world: None,
-            last_frame,
-            smoothed_dt,
+            time,
+            config,
+            input,
+            show_debug_ui: true,
+            plugins: vec![],
+            plugin_times: vec![],
+            session: SessionRecorder::new(),
+            pacer,
+            latency: LatencyMeter::new(),
+            inspector: Inspector::new(),
+            console: Console::new(),
+            menu_bar: MenuBar::new(),
+            workspace: Workspace::load(),
         }
     }
 
+    /// Replays a recorded input session once the window is up.
+    pub fn replay_on_start(&mut self, path: &str) -> &mut Self {
+        self.session.queue_replay(path);
+        self
+    }
+
+    /// Starts with `preset` instead of the saved one.
+    pub fn quality_on_start(&mut self, preset: QualityPreset) -> &mut Self {
+        self.config.quality = Some(preset);
+        self
+    }
+
+    /// Plugins are built in registration order once the window exists.
+    pub fn add_plugin(&mut self, plugin: impl Plugin + 'static) -> &mut Self {
+        self.plugins.push(Box::new(plugin));
+        self
+    }
+
     async fn set_window(&mut self, window: Window) {
         let window = Arc::new(window);
-        let initial_width = 1920;
-        let initial_height = 1080;
+        let (initial_width, initial_height) = self
+            .workspace
+            .window
+            .map_or((1920, 1080), |w| (w.width, w.height));
 
         let _ = window.request_inner_size(PhysicalSize::new(initial_width, initial_height));
 
@@ -166,167 +579,586 @@ impl App {
             .create_surface(window.clone())
             .expect("Failed to create surface!");
 
-        let state = State::new(
+        let transparent = self
+            .config
+            .background
+            .is_some_and(|b| b.mode == BackgroundMode::Transparent);
+        let hdr = self.config.hdr.unwrap_or_default();
+        let mut state = State::new(
             &self.instance,
             surface,
-            &window,
-            initial_width,
             initial_width,
+            initial_height,
+            transparent,
+            hdr.enabled,
         )
         .await;
+        if let Some(output) = state.hdr.as_mut() {
+            output.settings = hdr;
+        } else if hdr.enabled {
+            log::warn!("This surface can't show HDR; using SDR");
+        }
+        state.scale_factor = window.scale_factor() as f32;
+        self.input.set_scale_factor(state.scale_factor);
+        if let Some(max_anisotropy) = self.config.max_anisotropy {
+            state
+                .samplers
+                .set_max_anisotropy(max_anisotropy.min(state.caps.max_anisotropy));
+        }
+        if let Some(mode) = self.config.present_mode {
+            if !state.set_vsync(mode) {
+                log::warn!(
+                    "{} isn't supported here; keeping {}",
+                    mode.label(),
+                    state.vsync().label()
+                );
+            }
+        }
+        if let Some(frames) = self.config.frame_latency {
+            state.set_frame_latency(frames);
+        }
+
+        let egui_renderer = EguiRenderer::new(&self.instance, &state, &window);
+        let language = self.config.language.as_deref().unwrap_or(i18n::DEFAULT);
+        if let Err(e) = i18n::set_language(language) {
+            log::warn!("Failed to load UI strings, using English: {e}");
+        }
+        if let Some(theme) = self.config.ui_theme {
+            theme.apply(egui_renderer.context());
+            state.ui_scale = theme.scale;
+        }
+
+        let mut world =
+            diagnostics::error_scope(&state.device, "world creation", || World::new(&state));
+        self.workspace.restore(&state, &mut world);
+        if let Some(background) = self.config.background {
+            world.background.settings = background;
+        }
+
+        for plugin in &mut self.plugins {
+            log::info!("Building plugin {}", plugin.name());
+            plugin.build(&mut PluginContext {
+                state: &state,
+                world: &mut world,
+                time: &self.time,
+                input: &self.input,
+            });
+        }
 
-        let world = World::new(&state);
+        if let Some(preset) = self.config.quality {
+            quality::apply(preset, &state, &mut world);
+        }
 
         self.window.get_or_insert(window);
         self.state.get_or_insert(state);
+        self.egui_renderer.get_or_insert(egui_renderer);
         self.world.get_or_insert(world);
     }
 
     fn handle_resized(&mut self, width: u32, height: u32) {
-        if width > 0 && height > 0 {
-            self.state.as_mut().unwrap().resize_surface(width, height);
+        self.state.as_mut().unwrap().resize_surface(width, height);
+        if let Some(world) = self.world.as_mut() {
+            world.camera.resize(width, height);
         }
     }
 
     fn handle_redraw(&mut self) {
-        let now = Instant::now();
-        let dt = now.duration_since(self.last_frame).as_secs_f32();
-        self.last_frame = now;
-        self.smoothed_dt = 0.01 * dt + 0.99 * self.smoothed_dt;
-
-        if let Some(window) = self.window.as_ref() {
-            if let Some(min) = window.is_minimized() {
-                if min {
-                    println!("Window is minimized");
-                    return;
-                }
-            }
+        if self.pacer.is_hidden() {
+            return;
         }
+        if self.pacer.take_resumed() {
+            self.time.skip();
+        }
+        self.pacer.begin_frame();
 
         let state = self.state.as_mut().unwrap();
         let world = self.world.as_mut().unwrap();
 
-        let screen_descriptor = ScreenDescriptor {
-            size_in_pixels: [state.surface_config.width, state.surface_config.height],
-            pixels_per_point: self.window.as_ref().unwrap().scale_factor() as f32,
-        };
+        self.session
+            .begin_frame(world, &mut self.input, &mut self.time);
+        world.watch.begin_frame(self.time.elapsed_seconds);
+        world
+            .watch
+            .record("frame time (ms)", self.time.smoothed_dt * 1000.0);
+        world.watch.record("models", world.models.len() as f32);
+        let scene = state
+            .profiler
+            .passes()
+            .into_iter()
+            .find(|p| p.label == "scene");
+        if let Some(scene) = scene {
+            world.watch.record("visible draws", scene.draws as f32);
+        }
+
+        world.finish_loads(state);
+        self.plugin_times.clear();
+        for plugin in &mut self.plugins {
+            let start = Instant::now();
+            plugin.update(&mut PluginContext {
+                state,
+                world,
+                time: &self.time,
+                input: &self.input,
+            });
+            self.plugin_times
+                .push(start.elapsed().as_secs_f32() * 1000.0);
+        }
+        world.update(&self.time, &self.input);
 
-        let surface_texture = state.surface.get_current_texture();
+        let transparent = world.background.is_transparent();
+        if transparent != state.transparent {
+            if let Some(window) = &self.window {
+                window.set_transparent(transparent);
+            }
+            if !state.set_transparent(transparent) {
+                log::warn!("This surface can't be composited; the background stays opaque");
+            }
+        }
 
-        match surface_texture {
-            Err(SurfaceError::Outdated) => {
-                // Ignoring outdated to allow resizing and minimization
-                println!("wgpu surface outdated");
+        // suspended: keep simulating, there's just nowhere to draw
+        let Some(surface) = state.surface.as_ref() else {
+            world.debug_draw.clear();
+            return;
+        };
+        let surface_texture = match surface.get_current_texture() {
+            Ok(surface_texture) => surface_texture,
+            Err(SurfaceError::OutOfMemory) => {
+                panic!("Out of memory acquiring the next swap chain texture")
+            }
+            Err(e @ (SurfaceError::Lost | SurfaceError::Outdated)) => {
+                // reconfigured for the window as it is now, drawn next frame
+                log::warn!("wgpu surface {e:?}, reconfiguring");
+                if let Some(window) = &self.window {
+                    let size = window.inner_size();
+                    state.resize_surface(size.width, size.height);
+                }
+                world.debug_draw.clear();
                 return;
             }
-            Err(_) => {
-                surface_texture.expect("Failed to acquire next swap chain texture");
+            Err(e) => {
+                log::warn!("Skipping a frame: {e}");
+                world.debug_draw.clear();
                 return;
             }
-            Ok(_) => {}
         };
 
-        let surface_texture = surface_texture.unwrap();
-
         let surface_view = surface_texture
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
+        // with HDR output the scene and egui draw offscreen, and the HDR
+        // pass puts them on the surface last
+        let hdr_targets = state
+            .hdr
+            .as_ref()
+            .map(|hdr| (hdr.scene_target(state), hdr.ui_target(state)));
+        let (frame_texture, frame_view) = match &hdr_targets {
+            Some((scene, _)) => (&scene.texture, &scene.view),
+            None => (&surface_texture.texture, &surface_view),
+        };
 
-        let mut encoder = state
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        let mut command_buffers = vec![];
 
-        {
-            let mut renderpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: None,
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &surface_view,
-                    depth_slice: None,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &state.depth_texture.view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: wgpu::StoreOp::Store,
-                    }),
-                    stencil_ops: None,
-                }),
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
-            world.camera.queue_uniform(&state.queue);
-            world.render(&mut renderpass);
+        let update_frame_data = world.take_frame_update();
+        world.sky.white = state
+            .hdr
+            .as_ref()
+            .map_or(1.0, |hdr| hdr.settings.headroom());
+        if update_frame_data {
+            world.queue_uniforms(&state.queue);
+        }
+        if let Some(hdr) = &state.hdr {
+            hdr.queue_uniforms(&state.queue);
+        }
+
+        // a submission of its own, so the GPU can start on it while the
+        // raster passes are still being encoded. One pass for every plugin
+        // keeps the profiler's pass budget for the frame.
+        let compute = encode_compute_pass(state, "async compute", |encoder| {
+            for plugin in &mut self.plugins {
+                plugin.compute(state, world, encoder);
+            }
+        });
+        diagnostics::error_scope(&state.device, "compute submit", || {
+            state.queue.submit(Some(compute))
+        });
+
+        // one encoder per pass so validation errors land in the pass's scope
+        if update_frame_data {
+            for plugin in &mut self.plugins {
+                let label = format!("{} prepare", plugin.name());
+                command_buffers.push(encode_pass(state, &label, |encoder| {
+                    plugin.prepare(state, world, encoder);
+                }));
+            }
+        }
+
+        command_buffers.push(encode_pass(state, "scene", |encoder| {
+            let clear = world.background.clear_color();
+            let mut renderpass = state.begin_scene_pass(encoder, frame_view, clear);
+            let drawn = world.render(&mut renderpass);
+            state.profiler.record_draws(drawn, drawn);
+            for plugin in &self.plugins {
+                plugin.render(world, &mut renderpass);
+            }
+        }));
+
+        command_buffers.push(encode_pass(state, "debug draw", |encoder| {
+            world.debug_draw.render(state, encoder, frame_view);
+        }));
+
+        command_buffers.push(encode_pass(state, "sprites", |encoder| {
+            world.sprites.render(state, encoder, frame_view);
+        }));
+
+        for plugin in &mut self.plugins {
+            let label = plugin.name().to_string();
+            command_buffers.push(encode_pass(state, &label, |encoder| {
+                plugin.encode(state, world, encoder, frame_view);
+            }));
+        }
+
+        command_buffers.push(encode_pass(state, "lens flare", |encoder| {
+            world
+                .lens_flare
+                .render(state, encoder, frame_view, &world.camera, &world.sky);
+        }));
+
+        if world.capture_frame {
+            command_buffers.push(encode_pass(
+                state,
+                "frame capture",
+                |encoder| match &state.hdr {
+                    Some(hdr) => {
+                        let sdr = hdr.encode_sdr(state, encoder, frame_texture, None);
+                        world.encode_capture(&state.device, encoder, &sdr);
+                    }
+                    None => world.encode_capture(&state.device, encoder, frame_texture),
+                },
+            ));
         }
 
         let window = self.window.as_ref().unwrap();
 
-        {
-            state.egui_renderer.begin_frame(window);
+        if self.input.just_pressed(Action::ToggleDebugUi) {
+            self.show_debug_ui = !self.show_debug_ui;
+        }
+        self.input.end_frame();
+
+        let ui_frame = {
+            let egui_renderer = self.egui_renderer.as_mut().unwrap();
+            i18n::reload_if_changed();
+            egui_renderer.begin_frame(window);
+            self.menu_bar
+                .ui(egui_renderer.context(), state, world, &mut self.workspace);
+            snapping::toolbar(egui_renderer.context(), &mut world.snapping);
 
-            egui::Window::new("Debug")
+            let viewport = glam::vec2(
+                state.surface_config.width as f32,
+                state.surface_config.height as f32,
+            ) / state.screen_descriptor().pixels_per_point;
+            world
+                .debug_draw
+                .labels_ui(egui_renderer.context(), world.camera.view_proj(), viewport);
+
+            DetachableWindow::new("Debug")
+                .open(&mut self.show_debug_ui)
                 .resizable(true)
                 .vscroll(true)
                 .default_open(false)
-                .show(state.egui_renderer.context(), |ui| {
-                    ui.label(format!("Frame time: {:.2} ms", self.smoothed_dt * 1000.0));
+                .show(egui_renderer.context(), |ui| {
+                    ui.label(format!(
+                        "Frame time: {:.2} ms",
+                        self.time.smoothed_dt * 1000.0
+                    ));
+                    transient_stats_ui(ui, state);
+                    asset_cache::ui(ui);
+                    gpu_caps::ui(ui, &state.caps);
+                    pass_breakdown_ui(ui, &state.profiler, world);
+                    plugin_schedule_ui(ui, &self.plugins, &self.plugin_times, &state.profiler);
+                    entity_groups_ui(ui, world);
+                    world.culling.ui(ui, &world.camera);
+                    ui.label(format!(
+                        "Samplers: {}, anisotropy {}x",
+                        state.samplers.len(),
+                        state.samplers.max_anisotropy()
+                    ));
                     ui.separator();
                     if drag_vec3(ui, "Camera Position: ", &mut world.camera.eye, 0.1) {
                         world.camera.update_uniform();
+                        world.camera_controller.sync_from_camera(&world.camera);
+                    }
+                    camera_controller_ui(ui, world);
+                    bindings_ui(ui, &mut self.input, &mut self.config);
+                    time_ui(ui, &mut self.time);
+                    frame_data_ui(ui, world);
+                    frame_pacing_ui(
+                        ui,
+                        &mut self.pacer,
+                        &mut self.latency,
+                        state,
+                        &mut self.config,
+                    );
+                    render_settings_ui(ui, state, world, &mut self.config);
+                    ui_theme_ui(ui, state, &mut self.config);
+                    hdr_output::ui(ui, state, &mut self.config);
+                    if world.background.ui(ui, state, &mut world.sky) {
+                        self.config.background = Some(world.background.settings);
+                        self.config.save();
                     }
+                    world.sky.ui(ui);
+                    world.lens_flare.ui(ui);
+                    ui.checkbox(
+                        &mut world.clean_screenshots,
+                        "Clean screenshots (scene only)",
+                    );
+                    self.session.ui(ui, world, &mut self.input);
                     ui.collapsing("Debug", |ui| {
                         ui.label(format!("{:?}", world.camera));
                     });
                 });
 
-            state.egui_renderer.end_frame_and_draw(
-                &state.device,
-                &state.queue,
-                &mut encoder,
-                window,
-                &surface_view,
-                &screen_descriptor,
-            );
+            if self.show_debug_ui {
+                self.inspector.ui(egui_renderer.context(), state, world);
+                self.console
+                    .ui(egui_renderer.context(), &mut world.commands);
+                world.watch.ui(egui_renderer.context());
+                for plugin in &mut self.plugins {
+                    plugin.ui(egui_renderer.context(), state, world);
+                }
+            }
+
+            diagnostics::error_console_ui(egui_renderer.context());
+            compiling_indicator_ui(egui_renderer.context());
+            jobs::indicator_ui(egui_renderer.context());
+            self.latency.overlay_ui(egui_renderer.context());
+            if world.fallback_shaders {
+                fallback_banner_ui(egui_renderer.context());
+            }
+
+            commands::run_queued(&mut CommandContext {
+                state,
+                world,
+                show_debug_ui: &mut self.show_debug_ui,
+                args: vec![],
+            });
+
+            egui_renderer.end_frame(window, state.screen_descriptor())
+        };
+
+        // the same frame again with only the scene: no egui, debug draw or
+        // overlays such as outlines. HDR stills are always clean.
+        let clean_screenshot = world.screenshot.is_some() && world.clean_screenshots;
+        let clean_capture = (clean_screenshot || world.hdr_still.is_some()).then(|| {
+            let texture = create_capture_target(state);
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            command_buffers.push(encode_pass(state, "clean capture", |encoder| {
+                {
+                    let clear = world.background.clear_color();
+                    let mut renderpass = state.begin_scene_pass(encoder, &view, clear);
+                    world.render(&mut renderpass);
+                    for plugin in &self.plugins {
+                        plugin.render(world, &mut renderpass);
+                    }
+                }
+                world.sprites.render(state, encoder, &view);
+                world
+                    .lens_flare
+                    .render(state, encoder, &view, &world.camera, &world.sky);
+            }));
+            texture
+        });
+
+        // the UI goes over the finished frame, after post-processing and
+        // every capture that leaves it out. With HDR output it's drawn
+        // offscreen and the HDR pass composites it.
+        command_buffers.push(encode_pass(state, "ui overlay", |encoder| {
+            let (target, load) = match &hdr_targets {
+                Some((_, ui)) => (&ui.view, wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT)),
+                None => (&surface_view, wgpu::LoadOp::Load),
+            };
+            let egui_renderer = self.egui_renderer.as_ref().unwrap();
+            let primitives =
+                egui_renderer.draw(&state.device, &state.queue, encoder, ui_frame, target, load);
+            state.profiler.record_draws(primitives, primitives);
+            state
+                .profiler
+                .record_target(state.surface_config.width, state.surface_config.height);
+        }));
+
+        let mut sdr_screenshot = None;
+        if let (Some(hdr), Some((scene, ui))) = (&state.hdr, &hdr_targets) {
+            command_buffers.push(encode_pass(state, "hdr output", |encoder| {
+                hdr.encode(state, encoder, &scene.view, &ui.view, &surface_view);
+                // screenshots are saved as SDR
+                if world.screenshot.is_some() {
+                    sdr_screenshot =
+                        Some(match clean_capture.as_ref().filter(|_| clean_screenshot) {
+                            Some(clean) => hdr.encode_sdr(state, encoder, clean, None),
+                            None => hdr.encode_sdr(state, encoder, &scene.texture, Some(&ui.view)),
+                        });
+                }
+            }));
         }
 
-        state.queue.submit(Some(encoder.finish()));
+        command_buffers.push(diagnostics::error_scope(&state.device, "profiler", || {
+            let mut encoder = create_encoder(&state.device, "profiler");
+            state.profiler.resolve(&mut encoder);
+            encoder.finish()
+        }));
+        diagnostics::error_scope(&state.device, "submit", || {
+            state.queue.submit(command_buffers)
+        });
+        state.transient.end_frame();
+        state.profiler.end_frame(&state.device);
+        if let Some(path) = world.screenshot.take() {
+            let texture = sdr_screenshot
+                .as_ref()
+                .or(clean_capture.as_ref().filter(|_| clean_screenshot))
+                .unwrap_or(&surface_texture.texture);
+            save_screenshot(state, texture, &path);
+        }
+        if let (Some(still), Some(texture)) = (world.hdr_still.take(), &clean_capture) {
+            save_hdr_still(state, texture, &still);
+        }
         surface_texture.present();
+        let refresh_hz = window
+            .current_monitor()
+            .and_then(|monitor| monitor.refresh_rate_millihertz())
+            .map_or(60.0, |mhz| mhz as f32 / 1000.0);
+        self.latency.presented(
+            state.vsync(),
+            state.frame_latency(),
+            refresh_hz,
+            self.time.smoothed_dt,
+        );
     }
 }
 
 impl ApplicationHandler for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        let window = event_loop
-            .create_window(Window::default_attributes())
-            .unwrap();
+        if let (Some(window), Some(state)) = (self.window.as_ref(), self.state.as_mut()) {
+            // back from `suspended`: only the surface needs recreating
+            let surface = self
+                .instance
+                .create_surface(window.clone())
+                .expect("Failed to create surface!");
+            let size = window.inner_size();
+            state.attach_surface(surface, size.width, size.height);
+            self.pacer.set_occluded(false);
+            window.request_redraw();
+            return;
+        }
+
+        // X11 only takes transparency when the window is created
+        let transparent = self
+            .config
+            .background
+            .is_some_and(|b| b.mode == BackgroundMode::Transparent);
+        let mut attributes = Window::default_attributes().with_transparent(transparent);
+        if let Some(geometry) = self.workspace.window {
+            attributes = attributes
+                .with_position(PhysicalPosition::new(geometry.x, geometry.y))
+                .with_maximized(geometry.maximized);
+        }
+        let window = event_loop.create_window(attributes).unwrap();
         pollster::block_on(self.set_window(window));
     }
 
-    fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+    /// The native window goes away on Android when the app is backgrounded;
+    /// drop the surface and keep the rest until `resumed`.
+    fn suspended(&mut self, _: &ActiveEventLoop) {
+        if let Some(state) = self.state.as_mut() {
+            state.surface = None;
+        }
+        self.pacer.set_occluded(true);
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(window) = self.window.as_ref() else {
+            return;
+        };
+        self.pacer
+            .set_minimized(window.is_minimized().unwrap_or(false));
+        // keep drawing until compiled pipelines replace the fallbacks and
+        // loads finish
+        let loading = self.world.as_ref().is_some_and(World::is_loading);
+        if self.session.is_replaying() || material::compiling_pipelines() > 0 || loading {
+            self.pacer.mark_dirty();
+        }
+
+        if let Some(egui_renderer) = self.egui_renderer.as_mut() {
+            egui_renderer.open_pending_viewports(event_loop);
+        }
+
+        if self.pacer.wants_frame() {
+            window.request_redraw();
+        } else {
+            self.pacer.idle();
+        }
+        event_loop.set_control_flow(self.pacer.control_flow());
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, id: WindowId, event: WindowEvent) {
+        // detached panels are drawn by the main window's egui pass
+        if self.window.as_ref().is_some_and(|w| w.id() != id) {
+            let handled = self
+                .egui_renderer
+                .as_mut()
+                .is_some_and(|egui| egui.handle_viewport_input(id, &event));
+            if handled && event != WindowEvent::RedrawRequested {
+                self.pacer.mark_dirty();
+            }
+            return;
+        }
+
         // let egui render to process the event first
-        self.state
+        let consumed = self
+            .egui_renderer
             .as_mut()
             .unwrap()
-            .egui_renderer
             .handle_input(self.window.as_ref().unwrap(), &event);
 
+        // live input would desync a replay, so only egui sees it
+        if !self.session.is_replaying() {
+            self.input.handle_window_event(&event, consumed);
+        }
+        if matches!(
+            event,
+            WindowEvent::KeyboardInput { .. }
+                | WindowEvent::MouseInput { .. }
+                | WindowEvent::MouseWheel { .. }
+                | WindowEvent::CursorMoved { .. }
+        ) {
+            self.latency.input();
+        }
+
+        if event != WindowEvent::RedrawRequested {
+            self.pacer.mark_dirty();
+        }
+
         match event {
             WindowEvent::CloseRequested => {
-                println!("The close button was pressed; stopping");
+                log::info!("The close button was pressed; stopping");
+                if let (Some(world), Some(window)) = (&self.world, &self.window) {
+                    self.workspace.capture(world, window);
+                    self.workspace.save();
+                }
                 event_loop.exit();
             }
             WindowEvent::RedrawRequested => {
                 self.handle_redraw();
-
-                self.window.as_ref().unwrap().request_redraw();
+            }
+            WindowEvent::Occluded(occluded) => {
+                self.pacer.set_occluded(occluded);
             }
             WindowEvent::Resized(new_size) => {
                 self.handle_resized(new_size.width, new_size.height);
             }
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                // the new physical size follows as a Resized event
+                self.state.as_mut().unwrap().scale_factor = scale_factor as f32;
+                self.input.set_scale_factor(scale_factor as f32);
+            }
             _ => (),
         }
     }
@@ -361,3 +1193,472 @@ fn drag_vec3(ui: &mut egui::Ui, label: &str, value: &mut glam::Vec3, speed: f32)
 
     changed
 }
+
+fn camera_controller_ui(ui: &mut egui::Ui, world: &mut World) {
+    let controller = &mut world.camera_controller;
+    i18n::collapsing(ui, "camera.title", |ui| {
+        ui.horizontal(|ui| {
+            ui.label(tr("camera.mode"));
+            let orbit =
+                ui.selectable_value(&mut controller.mode, CameraMode::Orbit, tr("camera.orbit"));
+            let fly = ui.selectable_value(&mut controller.mode, CameraMode::Fly, tr("camera.fly"));
+            if orbit.changed() || fly.changed() {
+                controller.sync_from_camera(&world.camera);
+            }
+        });
+        ui.add(
+            egui::Slider::new(&mut controller.fly_speed, 0.1..=50.0).text(tr("camera.fly_speed")),
+        );
+
+        let smoothing = &mut controller.smoothing;
+        ui.checkbox(&mut smoothing.enabled, tr("camera.smoothing"));
+        ui.add_enabled_ui(smoothing.enabled, |ui| {
+            ui.add(
+                egui::Slider::new(&mut smoothing.orbit_damping, 1.0..=30.0)
+                    .text(tr("camera.orbit_damping")),
+            );
+            ui.add(
+                egui::Slider::new(&mut smoothing.pan_damping, 1.0..=30.0)
+                    .text(tr("camera.pan_damping")),
+            );
+            ui.add(
+                egui::Slider::new(&mut smoothing.zoom_damping, 1.0..=30.0)
+                    .text(tr("camera.zoom_damping")),
+            );
+            ui.add(
+                egui::Slider::new(&mut smoothing.fly_acceleration, 1.0..=200.0)
+                    .text(tr("camera.fly_acceleration")),
+            );
+            ui.add(
+                egui::Slider::new(&mut smoothing.fly_damping, 1.0..=30.0)
+                    .text(tr("camera.fly_damping")),
+            );
+        });
+    });
+}
+
+fn time_ui(ui: &mut egui::Ui, time: &mut Time) {
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut time.paused, tr("time.paused"));
+        if ui
+            .add_enabled(time.paused, egui::Button::new(tr("time.step")))
+            .clicked()
+        {
+            time.step();
+        }
+        ui.add(
+            egui::Slider::new(&mut time.time_scale, 0.0..=4.0)
+                .text(tr("time.time_scale"))
+                .clamping(egui::SliderClamping::Never),
+        );
+    });
+}
+
+fn frame_data_ui(ui: &mut egui::Ui, world: &mut World) {
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut world.freeze_frame_data, tr("frame_data.freeze"))
+            .on_hover_text(tr("frame_data.freeze_hint"));
+        if ui
+            .add_enabled(
+                world.freeze_frame_data,
+                egui::Button::new(tr("frame_data.step")),
+            )
+            .clicked()
+        {
+            world.step_frame_data = true;
+        }
+    });
+}
+
+/// Offscreen, so clean captures work even when the surface can't be copied.
+fn create_capture_target(state: &State) -> wgpu::Texture {
+    let config = &state.surface_config;
+    state.device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Clean Capture"),
+        size: wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        // read by the HDR pass's SDR encode
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::COPY_SRC
+            | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    })
+}
+
+fn save_screenshot(state: &State, texture: &wgpu::Texture, path: &Path) {
+    if !texture.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+        log::warn!("This surface can't be copied from; no screenshot taken");
+        return;
+    }
+    let capture = crate::headless::read_texture(&state.device, &state.queue, texture);
+    match capture.save_png(path) {
+        Ok(()) => log::info!("Saved screenshot {}", path.display()),
+        Err(e) => log::warn!("Failed to save {}: {e}", path.display()),
+    }
+}
+
+fn save_hdr_still(state: &State, texture: &wgpu::Texture, still: &HdrStill) {
+    let saved =
+        HdrImage::read(&state.device, &state.queue, texture).and_then(|image| still.save(&image));
+    match saved {
+        Ok(files) => log::info!("Saved HDR still {}", files[0].display()),
+        Err(e) => log::warn!("{e}"),
+    }
+}
+
+fn fallback_banner_ui(ctx: &egui::Context) {
+    egui::Area::new(egui::Id::new("fallback_shaders"))
+        .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -8.0])
+        .interactable(false)
+        .show(ctx, |ui| {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                "⚠ Compiled shaders not found; drawing with the built-in fallback. \
+                 Install slangc and rebuild for the full shaders.",
+            );
+        });
+}
+
+fn compiling_indicator_ui(ctx: &egui::Context) {
+    let count = material::compiling_pipelines();
+    if count == 0 {
+        return;
+    }
+    egui::Area::new(egui::Id::new("compiling_shaders"))
+        .anchor(egui::Align2::RIGHT_BOTTOM, [-8.0, -8.0])
+        .interactable(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(format!("Compiling shaders… ({count})"));
+            });
+        });
+}
+
+fn transient_stats_ui(ui: &mut egui::Ui, state: &State) {
+    let stats = state.transient.stats();
+    let mib = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+    ui.label(format!(
+        "Transient targets: {} requests in {} textures, {:.1} MiB ({:.1} MiB saved)",
+        stats.requests,
+        stats.textures,
+        mib(stats.allocated_bytes),
+        mib(stats.saved_bytes())
+    ));
+}
+
+fn pass_breakdown_ui(ui: &mut egui::Ui, profiler: &GpuProfiler, world: &mut World) {
+    ui.collapsing("Pass Breakdown", |ui| {
+        if !profiler.timestamps_supported() {
+            ui.label("GPU times unavailable: timestamp queries not supported");
+        }
+        let passes = profiler.passes();
+        egui::Grid::new("pass_breakdown")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.strong("Pass");
+                ui.strong("Draws");
+                ui.strong("Instances");
+                ui.strong("Targets");
+                ui.strong("GPU");
+                ui.strong("Overlap");
+                ui.end_row();
+                for pass in &passes {
+                    ui.label(&pass.label);
+                    ui.label(pass.draws.to_string());
+                    ui.label(pass.instances.to_string());
+                    let targets: Vec<_> = pass
+                        .targets
+                        .iter()
+                        .map(|(w, h)| format!("{w}x{h}"))
+                        .collect();
+                    ui.label(targets.join(", "));
+                    match pass.gpu_ms {
+                        Some(ms) => ui.label(format!("{ms:.3} ms")),
+                        None => ui.label("-"),
+                    };
+                    match gpu_profiler::overlap_ms(pass, &passes) {
+                        Some(ms) if pass.async_compute => ui.label(format!("{ms:.3} ms")),
+                        _ => ui.label(""),
+                    };
+                    ui.end_row();
+                }
+            });
+        let total: f32 = passes.iter().filter_map(|p| p.gpu_ms).sum();
+        let draws: u32 = passes.iter().map(|p| p.draws).sum();
+        ui.label(format!("Total: {draws} draws, {total:.3} ms GPU"));
+        let compute: Vec<_> = passes.iter().filter(|p| p.async_compute).collect();
+        let compute_ms: f32 = compute.iter().filter_map(|p| p.gpu_ms).sum();
+        let overlap_ms: f32 = compute
+            .iter()
+            .filter_map(|p| gpu_profiler::overlap_ms(p, &passes))
+            .sum();
+        ui.label(format!(
+            "Async compute: {compute_ms:.3} ms, {overlap_ms:.3} ms alongside raster"
+        ))
+        .on_hover_text(
+            "Compute is submitted ahead of the frame; wgpu has one queue, so \
+             whether it overlaps raster work is up to the driver",
+        );
+        if ui.button("Export Trace").clicked() {
+            world.commands.queue("render.export_trace");
+        }
+    });
+}
+
+/// Plugins in the order their hooks run, with what each hook cost last frame.
+fn plugin_schedule_ui(
+    ui: &mut egui::Ui,
+    plugins: &[Box<dyn Plugin>],
+    update_times: &[f32],
+    profiler: &GpuProfiler,
+) {
+    ui.collapsing(format!("Plugins ({})", plugins.len()), |ui| {
+        let passes = profiler.passes();
+        let encode_ms = |label: &str| {
+            let pass = passes.iter().find(|p| p.label == label);
+            pass.map_or("-".to_string(), |p| format!("{:.3} ms", p.cpu_ms))
+        };
+        egui::Grid::new("plugin_schedule")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.strong("#");
+                ui.strong("Plugin");
+                ui.strong("Update");
+                ui.strong("Prepare");
+                ui.strong("Encode");
+                ui.end_row();
+                for (i, plugin) in plugins.iter().enumerate() {
+                    ui.label(i.to_string());
+                    ui.label(plugin.name());
+                    match update_times.get(i) {
+                        Some(ms) => ui.label(format!("{ms:.3} ms")),
+                        None => ui.label("-"),
+                    };
+                    ui.label(encode_ms(&format!("{} prepare", plugin.name())));
+                    ui.label(encode_ms(plugin.name()));
+                    ui.end_row();
+                }
+            });
+    });
+}
+
+/// Models sharing a mesh, a material and the same optional settings.
+struct EntityGroup {
+    mesh: String,
+    /// Numbered by first use, since materials have no names.
+    material: usize,
+    parented: bool,
+    alpha_test: bool,
+    count: u32,
+    visible: u32,
+}
+
+/// Models grouped by what they're made of.
+fn entity_groups_ui(ui: &mut egui::Ui, world: &World) {
+    ui.collapsing(format!("Entities ({})", world.models.len()), |ui| {
+        let mut materials: Vec<*const material::MaterialInstance> = vec![];
+        let mut groups: Vec<EntityGroup> = vec![];
+        for model in &world.models {
+            let material = Arc::as_ptr(model.material());
+            let material = match materials.iter().position(|&m| m == material) {
+                Some(i) => i,
+                None => {
+                    materials.push(material);
+                    materials.len() - 1
+                }
+            };
+            let (parented, alpha_test) = (model.parent.is_some(), model.alpha_cutoff.is_some());
+            let group = groups.iter_mut().find(|g| {
+                g.mesh == model.mesh.name
+                    && g.material == material
+                    && g.parented == parented
+                    && g.alpha_test == alpha_test
+            });
+            let visible = model.is_visible() as u32;
+            match group {
+                Some(group) => {
+                    group.count += 1;
+                    group.visible += visible;
+                }
+                None => groups.push(EntityGroup {
+                    mesh: model.mesh.name.clone(),
+                    material,
+                    parented,
+                    alpha_test,
+                    count: 1,
+                    visible,
+                }),
+            }
+        }
+        groups.sort_by_key(|g| std::cmp::Reverse(g.count));
+        ui.label(format!(
+            "{} meshes, {} materials in use",
+            world.meshes.len(),
+            materials.len()
+        ));
+        let spatial = world.spatial();
+        ui.label(format!(
+            "Spatial index: {} entries in {} cells",
+            spatial.len(),
+            spatial.cell_count()
+        ));
+        let yes = |flag: bool| if flag { "yes" } else { "" };
+        egui::Grid::new("entity_groups")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.strong("Mesh");
+                ui.strong("Material");
+                ui.strong("Parented");
+                ui.strong("Alpha test");
+                ui.strong("Count");
+                ui.strong("Visible");
+                ui.end_row();
+                for group in &groups {
+                    ui.label(&group.mesh);
+                    ui.label(format!("#{}", group.material));
+                    ui.label(yes(group.parented));
+                    ui.label(yes(group.alpha_test));
+                    ui.label(group.count.to_string());
+                    ui.label(group.visible.to_string());
+                    ui.end_row();
+                }
+            });
+    });
+}
+
+fn frame_pacing_ui(
+    ui: &mut egui::Ui,
+    pacer: &mut FramePacer,
+    latency: &mut LatencyMeter,
+    state: &mut State,
+    config: &mut Config,
+) {
+    i18n::collapsing(ui, "frame_pacing.title", |ui| {
+        let mut capped = pacer.fps_cap.is_some();
+        ui.horizontal(|ui| {
+            if ui
+                .checkbox(&mut capped, tr("frame_pacing.fps_cap"))
+                .changed()
+            {
+                pacer.fps_cap = capped.then_some(60.0);
+            }
+            if let Some(fps) = pacer.fps_cap.as_mut() {
+                ui.add(egui::DragValue::new(fps).range(1.0..=1000.0).suffix(" fps"));
+            }
+        });
+        ui.checkbox(&mut pacer.reactive, tr("frame_pacing.reactive"));
+        let mut vsync = state.vsync();
+        egui::ComboBox::from_label(tr("frame_pacing.present_mode"))
+            .selected_text(vsync.label())
+            .show_ui(ui, |ui| {
+                for mode in VsyncMode::ALL {
+                    ui.add_enabled_ui(state.supports_vsync(mode), |ui| {
+                        ui.selectable_value(&mut vsync, mode, mode.label())
+                            .on_disabled_hover_text(tr("common.unsupported_surface"));
+                    });
+                }
+            });
+        state.set_vsync(vsync);
+        let mut frames = state.frame_latency();
+        if ui
+            .add(
+                egui::Slider::new(&mut frames, 1..=MAX_FRAME_LATENCY)
+                    .text(tr("frame_pacing.frames_in_flight")),
+            )
+            .changed()
+        {
+            state.set_frame_latency(frames);
+        }
+        ui.checkbox(&mut latency.overlay, tr("frame_pacing.latency_overlay"));
+        if ui.button(tr("common.save")).clicked() {
+            config.fps_cap = pacer.fps_cap;
+            config.present_mode = Some(state.vsync());
+            config.frame_latency = Some(state.frame_latency());
+            config.save();
+        }
+    });
+}
+
+fn render_settings_ui(ui: &mut egui::Ui, state: &State, world: &mut World, config: &mut Config) {
+    i18n::collapsing(ui, "render_settings.title", |ui| {
+        quality::ui(ui, state, world);
+        let supported = state.caps.max_anisotropy;
+        let mut max_anisotropy = state.samplers.max_anisotropy();
+        let slider = egui::Slider::new(&mut max_anisotropy, 1..=supported.max(2))
+            .text(tr("render_settings.max_anisotropy"));
+        let response = ui.add_enabled(supported > 1, slider);
+        if supported == 1 {
+            response.on_disabled_hover_text(tr("common.unsupported_device"));
+        } else if response.changed() && state.samplers.set_max_anisotropy(max_anisotropy) {
+            world.refresh_samplers(state);
+            // no longer any preset's
+            world.quality.preset = None;
+        }
+        if ui.button(tr("common.save")).clicked() {
+            config.max_anisotropy = Some(state.samplers.max_anisotropy());
+            config.quality = world.quality.preset;
+            config.save();
+        }
+    });
+}
+
+fn ui_theme_ui(ui: &mut egui::Ui, state: &mut State, config: &mut Config) {
+    i18n::collapsing(ui, "ui_theme.title", |ui| {
+        let mut theme = config.ui_theme.unwrap_or_default();
+        if ui_theme::ui(ui, &mut theme) {
+            theme.apply(ui.ctx());
+            state.ui_scale = theme.scale;
+            config.ui_theme = Some(theme);
+        }
+        if let Some(code) = i18n::ui(ui) {
+            config.language = Some(code);
+        }
+        if ui.button(tr("common.save")).clicked() {
+            config.ui_theme = Some(theme);
+            config.language = Some(i18n::language());
+            config.save();
+        }
+    });
+}
+
+fn bindings_ui(ui: &mut egui::Ui, input: &mut Input, config: &mut Config) {
+    i18n::collapsing(ui, "bindings.title", |ui| {
+        egui::Grid::new("bindings").striped(true).show(ui, |ui| {
+            for action in Action::ALL {
+                ui.label(action.label());
+                let text = if input.rebinding() == Some(action) {
+                    tr("bindings.rebinding")
+                } else {
+                    input
+                        .bindings
+                        .get(action)
+                        .map_or(tr("bindings.unbound"), |b| b.to_string())
+                };
+                if ui.button(text).clicked() {
+                    input.start_rebinding(action);
+                }
+                if ui.small_button("x").clicked() {
+                    input.bindings.clear(action);
+                }
+                ui.end_row();
+            }
+        });
+        ui.horizontal(|ui| {
+            if ui.button(tr("common.save")).clicked() {
+                config.bindings = input.bindings.clone();
+                config.save();
+            }
+            if ui.button(tr("common.reset_defaults")).clicked() {
+                input.bindings = Default::default();
+            }
+        });
+    });
+}
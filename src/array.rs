@@ -0,0 +1,369 @@
+//! Duplicates a model in a grid, around a circle, or along a spline, for
+//! building test scenes quickly. Copies share the source's mesh and
+//! material, so they add a uniform each and no vertex data; each is a
+//! model of its own and can be edited or removed separately.
+
+use crate::app::State;
+use crate::model::EntityId;
+use crate::plugin::Plugin;
+use crate::spline::{self, Spline};
+use crate::transform::Transform;
+use crate::world::World;
+use std::collections::HashSet;
+use std::time::Instant;
+
+/// Copies past this aren't made.
+pub const MAX_COPIES: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridArray {
+    /// Along each axis, counting the source.
+    pub counts: [u32; 3],
+    /// Between neighbours, in the source's parent space.
+    pub offset: glam::Vec3,
+}
+
+impl Default for GridArray {
+    fn default() -> Self {
+        GridArray {
+            counts: [5, 1, 1],
+            offset: glam::vec3(2.0, 2.0, 2.0),
+        }
+    }
+}
+
+impl GridArray {
+    /// Transforms for every cell but the source's.
+    pub fn transforms(&self, source: Transform) -> Vec<Transform> {
+        let [nx, ny, nz] = self.counts;
+        let cells =
+            (0..nx).flat_map(|x| (0..ny).flat_map(move |y| (0..nz).map(move |z| (x, y, z))));
+        cells
+            .skip(1)
+            .take(MAX_COPIES)
+            .map(|(x, y, z)| Transform {
+                translation: source.translation
+                    + glam::vec3(x as f32, y as f32, z as f32) * self.offset,
+                ..source
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RadialArray {
+    /// Around the circle, counting the source.
+    pub count: u32,
+    /// Of the circle, from the source, in its parent space.
+    pub center: glam::Vec3,
+    pub axis: glam::Vec3,
+    /// Degrees the copies spread over; 360 closes the circle.
+    pub sweep: f32,
+    /// Turns each copy with the circle, as well as moving it.
+    pub rotate: bool,
+}
+
+impl Default for RadialArray {
+    fn default() -> Self {
+        RadialArray {
+            count: 8,
+            center: glam::vec3(-5.0, 0.0, 0.0),
+            axis: glam::Vec3::Y,
+            sweep: 360.0,
+            rotate: true,
+        }
+    }
+}
+
+impl RadialArray {
+    /// Transforms for every step around but the source's.
+    pub fn transforms(&self, source: Transform) -> Vec<Transform> {
+        let axis = self.axis.normalize_or(glam::Vec3::Y);
+        let center = source.translation + self.center;
+        // a full circle would put the last copy on the source
+        let steps = if self.sweep.abs() >= 360.0 {
+            self.count
+        } else {
+            self.count.saturating_sub(1).max(1)
+        };
+        let step = self.sweep.to_radians() / steps as f32;
+        (1..self.count as usize)
+            .take(MAX_COPIES)
+            .map(|i| {
+                let turn = glam::Quat::from_axis_angle(axis, step * i as f32);
+                Transform {
+                    translation: center + turn * (source.translation - center),
+                    rotation: if self.rotate {
+                        turn * source.rotation
+                    } else {
+                        source.rotation
+                    },
+                    scale: source.scale,
+                }
+            })
+            .collect()
+    }
+}
+
+/// `count` transforms spaced evenly by length along `spline`, from end to
+/// end, keeping the source's rotation and scale or turning its +Z along
+/// the curve when `align` is set. `parent` is the copies' parent's world
+/// matrix; the spline is in world space.
+pub fn path_transforms(
+    source: Transform,
+    spline: &Spline,
+    count: u32,
+    align: bool,
+    parent: glam::Mat4,
+) -> Vec<Transform> {
+    let length = spline.length();
+    let to_parent = parent.inverse();
+    let (_, parent_rotation, _) = to_parent.to_scale_rotation_translation();
+    let count = (count as usize).min(MAX_COPIES);
+    (0..count)
+        .map(|i| {
+            let distance = if count > 1 {
+                length * i as f32 / (count - 1) as f32
+            } else {
+                0.0
+            };
+            let t = spline.t_at_distance(distance);
+            let forward = spline.tangent(t);
+            let rotation = if align && forward != glam::Vec3::ZERO {
+                parent_rotation * spline::look_rotation(forward)
+            } else {
+                source.rotation
+            };
+            Transform {
+                translation: to_parent.transform_point3(spline.position(t)),
+                rotation,
+                scale: source.scale,
+            }
+        })
+        .collect()
+}
+
+/// Spawns a copy of `source` at each transform, under the same parent and
+/// with its color, cutoff and primitive options. Returns the copies' ids.
+pub fn duplicate(
+    state: &State,
+    world: &mut World,
+    source: EntityId,
+    transforms: &[Transform],
+) -> Vec<EntityId> {
+    let Some(model) = world.model(source) else {
+        return vec![];
+    };
+    let (mesh, material) = (model.mesh.clone(), model.material().clone());
+    let (name, base_color, alpha_cutoff) =
+        (model.name.clone(), model.base_color, model.alpha_cutoff);
+    let (parent, primitive) = (model.parent, model.primitive());
+    let mut ids = Vec::with_capacity(transforms.len());
+    for (i, &transform) in transforms.iter().enumerate() {
+        let name = format!("{name} ({})", i + 1);
+        let id = world.spawn(state, &name, mesh.clone(), material.clone(), transform);
+        let copy = world.model_mut(id).unwrap();
+        copy.base_color = base_color;
+        copy.alpha_cutoff = alpha_cutoff;
+        if primitive != copy.primitive() {
+            copy.set_primitive(&state.device, primitive);
+        }
+        world.set_parent(id, parent);
+        ids.push(id);
+    }
+    ids
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArrayLayout {
+    Grid,
+    Radial,
+}
+
+/// The Array window: grid and radial arrays of the selected model. Arrays
+/// along a spline are made from the Splines window, which has the curves.
+pub struct ArrayTool {
+    pub layout: ArrayLayout,
+    pub grid: GridArray,
+    pub radial: RadialArray,
+    /// The last array made, for Undo.
+    last: Vec<EntityId>,
+    status: String,
+}
+
+impl Default for ArrayTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ArrayTool {
+    pub fn new() -> Self {
+        ArrayTool {
+            layout: ArrayLayout::Grid,
+            grid: GridArray::default(),
+            radial: RadialArray::default(),
+            last: vec![],
+            status: String::new(),
+        }
+    }
+
+    pub fn create(&mut self, state: &State, world: &mut World, source: EntityId) {
+        let Some(model) = world.model(source) else {
+            return;
+        };
+        let start = Instant::now();
+        let transforms = match self.layout {
+            ArrayLayout::Grid => self.grid.transforms(model.transform),
+            ArrayLayout::Radial => self.radial.transforms(model.transform),
+        };
+        self.last = duplicate(state, world, source, &transforms);
+        self.status = format!(
+            "Made {} copies in {:.1} ms",
+            self.last.len(),
+            start.elapsed().as_secs_f32() * 1000.0
+        );
+        log::info!("{}", self.status);
+    }
+
+    /// Removes the last array made.
+    pub fn undo(&mut self, world: &mut World) {
+        let count = self.last.len();
+        let ids: HashSet<EntityId> = self.last.drain(..).collect();
+        world.models.retain(|m| !ids.contains(&m.id));
+        self.status = format!("Removed {count} copies");
+    }
+}
+
+impl Plugin for ArrayTool {
+    fn name(&self) -> &str {
+        "Array"
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, state: &State, world: &mut World) {
+        egui::Window::new("Array")
+            .default_open(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut self.layout, ArrayLayout::Grid, "Grid");
+                    ui.selectable_value(&mut self.layout, ArrayLayout::Radial, "Radial");
+                });
+                match self.layout {
+                    ArrayLayout::Grid => grid_ui(ui, &mut self.grid),
+                    ArrayLayout::Radial => radial_ui(ui, &mut self.radial),
+                }
+                let source = world
+                    .selection
+                    .primary()
+                    .filter(|&id| world.model(id).is_some());
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(source.is_some(), egui::Button::new("Duplicate selected"))
+                        .clicked()
+                    {
+                        if let Some(source) = source {
+                            self.create(state, world, source);
+                        }
+                    }
+                    if ui
+                        .add_enabled(!self.last.is_empty(), egui::Button::new("Undo"))
+                        .clicked()
+                    {
+                        self.undo(world);
+                    }
+                });
+                if !self.status.is_empty() {
+                    ui.label(&self.status);
+                }
+            });
+    }
+}
+
+fn vec3_ui(ui: &mut egui::Ui, label: &str, value: &mut glam::Vec3, speed: f32) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        for axis in 0..3 {
+            ui.add(egui::DragValue::new(&mut value[axis]).speed(speed));
+        }
+    });
+}
+
+fn grid_ui(ui: &mut egui::Ui, grid: &mut GridArray) {
+    ui.horizontal(|ui| {
+        ui.label("Count");
+        for count in &mut grid.counts {
+            ui.add(egui::DragValue::new(count).range(1..=100));
+        }
+    });
+    vec3_ui(ui, "Offset", &mut grid.offset, 0.1);
+    let total = grid.counts.iter().product::<u32>();
+    ui.label(format!("{} copies", total.saturating_sub(1)));
+}
+
+fn radial_ui(ui: &mut egui::Ui, radial: &mut RadialArray) {
+    ui.add(egui::Slider::new(&mut radial.count, 2..=360).text("Count"));
+    vec3_ui(ui, "Center", &mut radial.center, 0.1);
+    vec3_ui(ui, "Axis", &mut radial.axis, 0.01);
+    ui.add(egui::Slider::new(&mut radial.sweep, -360.0..=360.0).text("Sweep (°)"));
+    ui.checkbox(&mut radial.rotate, "Rotate copies");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spline::SplineKind;
+
+    fn assert_near(a: glam::Vec3, b: glam::Vec3) {
+        assert!(a.distance(b) < 1e-4, "{a} != {b}");
+    }
+
+    #[test]
+    fn grid_skips_the_source_cell() {
+        let grid = GridArray {
+            counts: [3, 2, 1],
+            offset: glam::vec3(2.0, 5.0, 0.0),
+        };
+        let source = Transform::from_translation(glam::vec3(1.0, 0.0, 0.0));
+        let copies = grid.transforms(source);
+        assert_eq!(copies.len(), 5);
+        assert!(copies.iter().all(|t| t.translation != source.translation));
+        assert_near(
+            copies.last().unwrap().translation,
+            glam::vec3(5.0, 5.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn full_circle_spaces_copies_evenly() {
+        let radial = RadialArray {
+            count: 4,
+            center: glam::vec3(-1.0, 0.0, 0.0),
+            axis: glam::Vec3::Y,
+            sweep: 360.0,
+            rotate: true,
+        };
+        let source = Transform::from_translation(glam::vec3(1.0, 0.0, 0.0));
+        let copies = radial.transforms(source);
+        assert_eq!(copies.len(), 3);
+        // a quarter turn each about (0, 0, 0)
+        assert_near(copies[0].translation, glam::vec3(0.0, 0.0, -1.0));
+        assert_near(copies[1].translation, glam::vec3(-1.0, 0.0, 0.0));
+        assert_near(copies[2].translation, glam::vec3(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn path_copies_span_the_spline() {
+        let spline = Spline::new(
+            "Line",
+            SplineKind::CatmullRom,
+            vec![glam::Vec3::ZERO, glam::vec3(10.0, 0.0, 0.0)],
+        );
+        let copies = path_transforms(Transform::default(), &spline, 3, true, glam::Mat4::IDENTITY);
+        assert_eq!(copies.len(), 3);
+        assert_near(copies[0].translation, glam::Vec3::ZERO);
+        assert_near(copies[1].translation, glam::vec3(5.0, 0.0, 0.0));
+        assert_near(copies[2].translation, glam::vec3(10.0, 0.0, 0.0));
+        // +Z turned along the curve
+        assert_near(copies[1].rotation * glam::Vec3::Z, glam::Vec3::X);
+    }
+}
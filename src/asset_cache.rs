@@ -0,0 +1,207 @@
+//! On-disk cache of import results, keyed by a hash of the source bytes,
+//! the settings they were imported with and the importer's version, so an
+//! unchanged file loads without being processed again. Imported meshes are
+//! cached. Decoded images aren't, being no quicker to read back than to
+//! decode, and neither are pipelines: wgpu's pipeline cache only exists on
+//! Vulkan. Past `BUDGET`, the least recently used entries are removed.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+const CACHE_DIR: &str = ".cache/assets";
+/// Bumped when what's cached changes shape, so stale entries miss.
+const VERSION: u32 = 2;
+/// Total entry size kept on disk.
+const BUDGET: u64 = 1024 * 1024 * 1024;
+
+static HITS: AtomicU64 = AtomicU64::new(0);
+static MISSES: AtomicU64 = AtomicU64::new(0);
+
+/// FNV-1a over `parts`, each length-prefixed so their boundaries count.
+/// Stable across runs and builds, unlike `DefaultHasher`. `version` is the
+/// importer's own, bumped when its output changes.
+pub fn key(kind: &str, version: u32, parts: &[&[u8]]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut feed = |bytes: &[u8]| {
+        for &byte in bytes {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    };
+    feed(&VERSION.to_le_bytes());
+    feed(kind.as_bytes());
+    feed(&version.to_le_bytes());
+    for part in parts {
+        feed(&(part.len() as u64).to_le_bytes());
+        feed(part);
+    }
+    format!("{kind}-{hash:016x}")
+}
+
+fn path(key: &str) -> PathBuf {
+    PathBuf::from(CACHE_DIR).join(key)
+}
+
+/// Marks a hit entry as used just now, for eviction.
+pub fn read(key: &str) -> Option<Vec<u8>> {
+    let path = path(key);
+    let bytes = std::fs::read(&path).ok();
+    let counter = if bytes.is_some() { &HITS } else { &MISSES };
+    counter.fetch_add(1, Ordering::Relaxed);
+    if bytes.is_some() {
+        let _ = std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(SystemTime::now()));
+    }
+    bytes
+}
+
+/// Written to a temporary file first, so a reader never sees half an
+/// entry.
+pub fn write(key: &str, bytes: &[u8]) {
+    let result = std::fs::create_dir_all(CACHE_DIR).and_then(|_| {
+        let temporary = path(&format!("{key}.tmp"));
+        std::fs::write(&temporary, bytes)?;
+        std::fs::rename(&temporary, path(key))
+    });
+    if let Err(e) = result {
+        log::warn!("Failed to cache {key}: {e}");
+    }
+    evict(Path::new(CACHE_DIR), BUDGET);
+}
+
+/// Removes the least recently used entries in `dir` until the rest fit in
+/// `budget` bytes.
+fn evict(dir: &Path, budget: u64) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries: Vec<(SystemTime, u64, PathBuf)> = entries
+        .filter_map(|e| {
+            let e = e.ok()?;
+            let metadata = e.metadata().ok().filter(|m| m.is_file())?;
+            Some((metadata.modified().ok()?, metadata.len(), e.path()))
+        })
+        .collect();
+    let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+    entries.sort();
+    for (_, len, path) in entries {
+        if total <= budget {
+            break;
+        }
+        match std::fs::remove_file(&path) {
+            Ok(()) => total -= len,
+            Err(e) => log::warn!("Failed to evict {}: {e}", path.display()),
+        }
+    }
+}
+
+/// Entries and their total size in bytes.
+pub fn size() -> (usize, u64) {
+    let Ok(entries) = std::fs::read_dir(CACHE_DIR) else {
+        return (0, 0);
+    };
+    entries
+        .filter_map(|e| e.ok()?.metadata().ok())
+        .filter(|m| m.is_file())
+        .fold((0, 0), |(count, bytes), m| (count + 1, bytes + m.len()))
+}
+
+pub fn clear() -> Result<(), String> {
+    match std::fs::remove_dir_all(CACHE_DIR) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// `sections` back to back, each prefixed with its length.
+pub fn pack(sections: &[&[u8]]) -> Vec<u8> {
+    let mut bytes = vec![];
+    for section in sections {
+        bytes.extend_from_slice(&(section.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(section);
+    }
+    bytes
+}
+
+/// The sections `pack` was given; `None` if `bytes` is truncated.
+pub fn unpack(mut bytes: &[u8]) -> Option<Vec<&[u8]>> {
+    let mut sections = vec![];
+    while !bytes.is_empty() {
+        let (length, rest) = bytes.split_first_chunk::<8>()?;
+        let length = usize::try_from(u64::from_le_bytes(*length)).ok()?;
+        if rest.len() < length {
+            return None;
+        }
+        let (section, rest) = rest.split_at(length);
+        sections.push(section);
+        bytes = rest;
+    }
+    Some(sections)
+}
+
+pub fn ui(ui: &mut egui::Ui) {
+    ui.collapsing("Asset Cache", |ui| {
+        let (entries, bytes) = size();
+        ui.label(format!(
+            "{entries} entries, {:.1} of {} MiB in {CACHE_DIR}",
+            bytes as f64 / (1024.0 * 1024.0),
+            BUDGET / (1024 * 1024)
+        ));
+        ui.label(format!(
+            "{} hits, {} misses this run",
+            HITS.load(Ordering::Relaxed),
+            MISSES.load(Ordering::Relaxed)
+        ));
+        if ui.button("Clear").clicked() {
+            if let Err(e) = clear() {
+                log::warn!("Failed to clear {CACHE_DIR}: {e}");
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn keys_cover_kind_version_and_part_boundaries() {
+        let key = key("gltf", 1, &[b"ab", b"c"]);
+        assert!(key.starts_with("gltf-"));
+        assert_ne!(key, super::key("gltf", 2, &[b"ab", b"c"]));
+        assert_ne!(key, super::key("image", 1, &[b"ab", b"c"]));
+        assert_ne!(key, super::key("gltf", 1, &[b"a", b"bc"]));
+        assert_eq!(key, super::key("gltf", 1, &[b"ab", b"c"]));
+    }
+
+    #[test]
+    fn sections_round_trip() {
+        let packed = pack(&[b"one", b"", b"three"]);
+        assert_eq!(unpack(&packed).unwrap(), [&b"one"[..], b"", b"three"]);
+        assert_eq!(unpack(&packed[..packed.len() - 1]), None);
+    }
+
+    #[test]
+    fn eviction_removes_the_least_recently_used() {
+        let dir = std::env::temp_dir().join("asset_cache_evict_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let now = SystemTime::now();
+        for (name, age) in [("old", 30), ("used", 0), ("newer", 10)] {
+            let path = dir.join(name);
+            std::fs::write(&path, [0; 100]).unwrap();
+            let file = std::fs::File::options().write(true).open(&path).unwrap();
+            file.set_modified(now - Duration::from_secs(age)).unwrap();
+        }
+        evict(&dir, 250);
+        assert!(!dir.join("old").exists());
+        assert!(dir.join("newer").exists() && dir.join("used").exists());
+        evict(&dir, 100);
+        assert!(!dir.join("newer").exists());
+        assert!(dir.join("used").exists());
+    }
+}
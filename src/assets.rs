@@ -0,0 +1,73 @@
+use crate::import::ImportSettings;
+use crate::mesh::{load_gltf, Mesh, MeshSource};
+use crate::scene_pack;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Loaders apply the import settings before creating GPU buffers; see
+/// `ImportSettings::apply`.
+pub type MeshLoader = fn(&wgpu::Device, &str, &ImportSettings) -> Vec<Arc<Mesh>>;
+
+/// Maps file extensions to mesh loaders so plugins can add formats.
+#[derive(Clone)]
+pub struct MeshLoaders {
+    loaders: HashMap<String, MeshLoader>,
+}
+
+impl Default for MeshLoaders {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MeshLoaders {
+    pub fn new() -> Self {
+        let mut loaders = MeshLoaders {
+            loaders: HashMap::new(),
+        };
+        loaders.register("gltf", load_gltf);
+        loaders.register("glb", load_gltf);
+        loaders.register(scene_pack::EXTENSION, scene_pack::load_pack);
+        loaders
+    }
+
+    pub fn register(&mut self, extension: &str, loader: MeshLoader) {
+        self.loaders.insert(extension.to_lowercase(), loader);
+    }
+
+    /// Registered extensions, lowercase and sorted.
+    pub fn extensions(&self) -> Vec<&str> {
+        let mut extensions: Vec<&str> = self.loaders.keys().map(String::as_str).collect();
+        extensions.sort();
+        extensions
+    }
+
+    pub fn load(
+        &self,
+        device: &wgpu::Device,
+        path: &str,
+        settings: &ImportSettings,
+    ) -> Option<Vec<Arc<Mesh>>> {
+        let extension = Path::new(path).extension()?.to_str()?.to_lowercase();
+        match self.loaders.get(&extension) {
+            Some(loader) => {
+                crate::diagnostics::record_asset(path);
+                let mut meshes = loader(device, path, settings);
+                for (index, mesh) in meshes.iter_mut().enumerate() {
+                    if let Some(mesh) = Arc::get_mut(mesh) {
+                        mesh.source = Some(MeshSource {
+                            path: path.to_string(),
+                            index,
+                        });
+                    }
+                }
+                Some(meshes)
+            }
+            None => {
+                log::warn!("No mesh loader registered for .{extension} ({path})");
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,268 @@
+//! Positional audio driven by entity transforms, with the camera as the
+//! listener. Output goes through an `AudioBackend`; the built-in
+//! `SilentBackend` only records what would be played, so demos can be laid
+//! out before a rodio or kira backend is plugged in with `Audio::with_backend`.
+
+use crate::app::State;
+use crate::camera::Camera;
+use crate::model::EntityId;
+use crate::plugin::{Plugin, PluginContext};
+use crate::world::World;
+use std::collections::HashMap;
+
+pub type VoiceId = u32;
+
+/// Where sound is heard from; follows the active camera every frame.
+#[derive(Debug, Clone, Copy)]
+pub struct AudioListener {
+    pub position: glam::Vec3,
+    pub forward: glam::Vec3,
+    pub right: glam::Vec3,
+}
+
+impl Default for AudioListener {
+    fn default() -> Self {
+        AudioListener {
+            position: glam::Vec3::ZERO,
+            forward: glam::Vec3::NEG_Z,
+            right: glam::Vec3::X,
+        }
+    }
+}
+
+impl AudioListener {
+    pub fn from_camera(camera: &Camera) -> Self {
+        let forward = (camera.center - camera.eye).normalize_or(glam::Vec3::NEG_Z);
+        AudioListener {
+            position: camera.eye,
+            forward,
+            right: forward.cross(camera.up).normalize_or(glam::Vec3::X),
+        }
+    }
+
+    /// Inverse-distance gain, silent past `max_distance`, and a left/right
+    /// pan from the source's direction.
+    pub fn spatialize(&self, position: glam::Vec3, source: &AudioSource) -> Spatial {
+        let offset = position - self.position;
+        let distance = offset.length();
+        if distance >= source.max_distance {
+            return Spatial {
+                gain: 0.0,
+                pan: 0.0,
+            };
+        }
+        let gain = source.volume * source.min_distance / distance.max(source.min_distance);
+        let pan = if distance > f32::EPSILON {
+            (offset / distance).dot(self.right)
+        } else {
+            0.0
+        };
+        Spatial { gain, pan }
+    }
+}
+
+/// A sound attached to an entity. It stops when the entity is despawned.
+#[derive(Debug, Clone)]
+pub struct AudioSource {
+    pub entity: EntityId,
+    pub clip: String,
+    pub volume: f32,
+    pub looping: bool,
+    /// Full volume inside this distance.
+    pub min_distance: f32,
+    pub max_distance: f32,
+}
+
+impl AudioSource {
+    pub fn new(entity: EntityId, clip: &str) -> Self {
+        AudioSource {
+            entity,
+            clip: clip.to_string(),
+            volume: 1.0,
+            looping: true,
+            min_distance: 1.0,
+            max_distance: 50.0,
+        }
+    }
+}
+
+/// Per-voice mix parameters. `pan` runs from -1 (left) to 1 (right).
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Spatial {
+    pub gain: f32,
+    pub pan: f32,
+}
+
+/// What an output library has to provide.
+pub trait AudioBackend {
+    fn play(&mut self, voice: VoiceId, clip: &str, looping: bool);
+    fn set_spatial(&mut self, voice: VoiceId, spatial: Spatial);
+    fn stop(&mut self, voice: VoiceId);
+}
+
+/// Logs voices starting and stopping without making any sound.
+#[derive(Default)]
+pub struct SilentBackend;
+
+impl AudioBackend for SilentBackend {
+    fn play(&mut self, voice: VoiceId, clip: &str, _looping: bool) {
+        log::info!("Audio voice {voice} playing {clip} (silent backend)");
+    }
+
+    fn set_spatial(&mut self, _voice: VoiceId, _spatial: Spatial) {}
+
+    fn stop(&mut self, voice: VoiceId) {
+        log::info!("Audio voice {voice} stopped");
+    }
+}
+
+pub struct Audio {
+    pub enabled: bool,
+    pub listener: AudioListener,
+    backend: Box<dyn AudioBackend>,
+    sources: Vec<(VoiceId, AudioSource)>,
+    mix: HashMap<VoiceId, Spatial>,
+    next_voice: VoiceId,
+    attach_entity: Option<EntityId>,
+    attach_clip: String,
+}
+
+impl Default for Audio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Audio {
+    pub fn new() -> Self {
+        Self::with_backend(Box::new(SilentBackend))
+    }
+
+    pub fn with_backend(backend: Box<dyn AudioBackend>) -> Self {
+        Audio {
+            enabled: true,
+            listener: AudioListener::default(),
+            backend,
+            sources: vec![],
+            mix: HashMap::new(),
+            next_voice: 0,
+            attach_entity: None,
+            attach_clip: String::new(),
+        }
+    }
+
+    /// Starts playing right away; the mix is updated from the next frame.
+    pub fn add_source(&mut self, source: AudioSource) -> VoiceId {
+        let voice = self.next_voice;
+        self.next_voice += 1;
+        self.backend.play(voice, &source.clip, source.looping);
+        self.sources.push((voice, source));
+        voice
+    }
+
+    pub fn remove_source(&mut self, voice: VoiceId) {
+        self.sources.retain(|(v, _)| *v != voice);
+        self.mix.remove(&voice);
+        self.backend.stop(voice);
+    }
+
+    pub fn sources(&self) -> impl Iterator<Item = (VoiceId, &AudioSource)> {
+        self.sources.iter().map(|(v, s)| (*v, s))
+    }
+
+    /// The gain and pan sent to the backend last frame.
+    pub fn mix(&self, voice: VoiceId) -> Spatial {
+        self.mix.get(&voice).copied().unwrap_or_default()
+    }
+}
+
+impl Plugin for Audio {
+    fn name(&self) -> &str {
+        "Audio"
+    }
+
+    fn update(&mut self, ctx: &mut PluginContext) {
+        self.listener = AudioListener::from_camera(&ctx.world.camera);
+
+        let gone: Vec<VoiceId> = self
+            .sources
+            .iter()
+            .filter(|(_, s)| ctx.world.model(s.entity).is_none())
+            .map(|(v, _)| *v)
+            .collect();
+        for voice in gone {
+            self.remove_source(voice);
+        }
+
+        for (voice, source) in &self.sources {
+            let spatial = match ctx.world.model(source.entity) {
+                Some(model) if self.enabled => self
+                    .listener
+                    .spatialize(model.global_matrix().w_axis.truncate(), source),
+                _ => Spatial::default(),
+            };
+            if self.mix.get(voice) != Some(&spatial) {
+                self.backend.set_spatial(*voice, spatial);
+                self.mix.insert(*voice, spatial);
+            }
+        }
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, _state: &State, world: &mut World) {
+        egui::Window::new("Audio")
+            .default_open(false)
+            .show(ctx, |ui| {
+                ui.checkbox(&mut self.enabled, "Enabled");
+                if self.sources.is_empty() {
+                    ui.label("No audio sources");
+                }
+                let mut remove = None;
+                for (voice, source) in &self.sources {
+                    let mix = self.mix(*voice);
+                    let name = world.model(source.entity).map_or("?", |m| m.name.as_str());
+                    ui.horizontal(|ui| {
+                        ui.label(format!(
+                            "{name}: {}  gain {:.2}  pan {:+.2}",
+                            source.clip, mix.gain, mix.pan
+                        ));
+                        if ui.small_button("✖").clicked() {
+                            remove = Some(*voice);
+                        }
+                    });
+                }
+                if let Some(voice) = remove {
+                    self.remove_source(voice);
+                }
+
+                ui.separator();
+                let selected = self
+                    .attach_entity
+                    .and_then(|id| world.model(id))
+                    .map_or("Entity".to_string(), |m| m.name.clone());
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_salt("audio_attach")
+                        .selected_text(selected)
+                        .show_ui(ui, |ui| {
+                            for model in &world.models {
+                                ui.selectable_value(
+                                    &mut self.attach_entity,
+                                    Some(model.id),
+                                    &model.name,
+                                );
+                            }
+                        });
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.attach_clip)
+                            .hint_text("clip path")
+                            .desired_width(140.0),
+                    );
+                    let ready = self.attach_entity.is_some() && !self.attach_clip.is_empty();
+                    if ui.add_enabled(ready, egui::Button::new("Attach")).clicked() {
+                        let entity = self.attach_entity.unwrap();
+                        let clip = std::mem::take(&mut self.attach_clip);
+                        self.add_source(AudioSource::new(entity, &clip));
+                    }
+                });
+            });
+    }
+}
@@ -0,0 +1,272 @@
+//! What the scene pass leaves behind the models: a solid color, a vertical
+//! gradient, the procedural sky, or nothing at all so the window can be
+//! composited over the desktop.
+
+use crate::app::State;
+use crate::sky::Sky;
+use serde::{Deserialize, Serialize};
+use wgpu::util::DeviceExt;
+
+const SHADER: &str = r#"
+struct Gradient {
+    top: vec4<f32>,
+    bottom: vec4<f32>,
+};
+@group(0) @binding(0) var<uniform> gradient: Gradient;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) t: f32,
+};
+
+@vertex
+fn vsMain(@builtin(vertex_index) index: u32) -> VSOut {
+    let uv = vec2(f32((index << 1u) & 2u), f32(index & 2u));
+    let ndc = uv * 2.0 - 1.0;
+    var out: VSOut;
+    out.pos = vec4(ndc, 1.0, 1.0);
+    out.t = ndc.y * 0.5 + 0.5;
+    return out;
+}
+
+@fragment
+fn psMain(in: VSOut) -> @location(0) vec4<f32> {
+    return mix(gradient.bottom, gradient.top, clamp(in.t, 0.0, 1.0));
+}
+"#;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum BackgroundMode {
+    Solid,
+    /// `top` to `bottom` down the screen.
+    Gradient,
+    /// The procedural sky while it's enabled, the solid color otherwise.
+    #[default]
+    Sky,
+    /// Cleared to zero alpha, for windows composited over the desktop or
+    /// captures composited elsewhere.
+    Transparent,
+}
+
+impl BackgroundMode {
+    pub const ALL: [BackgroundMode; 4] = [
+        BackgroundMode::Solid,
+        BackgroundMode::Gradient,
+        BackgroundMode::Sky,
+        BackgroundMode::Transparent,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            BackgroundMode::Solid => "Solid color",
+            BackgroundMode::Gradient => "Gradient",
+            BackgroundMode::Sky => "Sky",
+            BackgroundMode::Transparent => "Transparent",
+        }
+    }
+}
+
+/// Colors are linear.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackgroundSettings {
+    pub mode: BackgroundMode,
+    pub color: [f32; 3],
+    pub top: [f32; 3],
+    pub bottom: [f32; 3],
+}
+
+impl Default for BackgroundSettings {
+    fn default() -> Self {
+        BackgroundSettings {
+            mode: BackgroundMode::Sky,
+            color: [0.0; 3],
+            top: [0.05, 0.07, 0.12],
+            bottom: [0.01, 0.01, 0.015],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct GradientUniform {
+    top: [f32; 4],
+    bottom: [f32; 4],
+}
+
+pub struct Background {
+    pub settings: BackgroundSettings,
+    pipeline: wgpu::RenderPipeline,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Background {
+    pub fn new(state: &State) -> Self {
+        let device = &state.device;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Background"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Background"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Background"),
+            contents: bytemuck::bytes_of(&GradientUniform {
+                top: [0.0; 4],
+                bottom: [0.0; 4],
+            }),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Background"),
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Background"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Background"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vsMain"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("psMain"),
+                compilation_options: Default::default(),
+                targets: &[Some(state.surface_config.format.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            // only where the depth buffer is still clear
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: state.depth_texture.texture.format(),
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Background {
+            settings: BackgroundSettings::default(),
+            pipeline,
+            buffer,
+            bind_group,
+        }
+    }
+
+    pub fn is_transparent(&self) -> bool {
+        self.settings.mode == BackgroundMode::Transparent
+    }
+
+    /// What the scene pass clears to; the gradient and sky are drawn over
+    /// it.
+    pub fn clear_color(&self) -> wgpu::Color {
+        if self.is_transparent() {
+            return wgpu::Color::TRANSPARENT;
+        }
+        let [r, g, b] = self.settings.color.map(f64::from);
+        wgpu::Color { r, g, b, a: 1.0 }
+    }
+
+    /// Whether the sky should be drawn behind the scene.
+    pub fn shows_sky(&self, sky: &Sky) -> bool {
+        self.settings.mode == BackgroundMode::Sky && sky.enabled
+    }
+
+    pub fn queue_uniform(&self, queue: &wgpu::Queue) {
+        let [top, bottom] =
+            [self.settings.top, self.settings.bottom].map(|[r, g, b]| [r, g, b, 1.0]);
+        queue.write_buffer(
+            &self.buffer,
+            0,
+            bytemuck::bytes_of(&GradientUniform { top, bottom }),
+        );
+    }
+
+    /// Draws the gradient, if that's the mode. Like the sky, draw it after
+    /// the opaque models.
+    pub fn render(&self, renderpass: &mut wgpu::RenderPass) -> bool {
+        if self.settings.mode != BackgroundMode::Gradient {
+            return false;
+        }
+        renderpass.set_pipeline(&self.pipeline);
+        renderpass.set_bind_group(0, &self.bind_group, &[]);
+        renderpass.draw(0..3, 0..1);
+        true
+    }
+
+    /// Returns true when Save is clicked.
+    pub fn ui(&mut self, ui: &mut egui::Ui, state: &State, sky: &mut Sky) -> bool {
+        let mut save = false;
+        ui.collapsing("Background", |ui| {
+            let settings = &mut self.settings;
+            let before = settings.mode;
+            egui::ComboBox::from_label("Mode")
+                .selected_text(settings.mode.label())
+                .show_ui(ui, |ui| {
+                    for mode in BackgroundMode::ALL {
+                        ui.selectable_value(&mut settings.mode, mode, mode.label());
+                    }
+                });
+            if settings.mode == BackgroundMode::Sky && before != BackgroundMode::Sky {
+                sky.enabled = true;
+            }
+            match settings.mode {
+                BackgroundMode::Solid => {
+                    ui.horizontal(|ui| {
+                        ui.label("Color");
+                        ui.color_edit_button_rgb(&mut settings.color);
+                    });
+                }
+                BackgroundMode::Gradient => {
+                    ui.horizontal(|ui| {
+                        ui.label("Top");
+                        ui.color_edit_button_rgb(&mut settings.top);
+                        ui.label("Bottom");
+                        ui.color_edit_button_rgb(&mut settings.bottom);
+                    });
+                }
+                BackgroundMode::Sky => {
+                    ui.horizontal(|ui| {
+                        ui.label("Color with the sky off");
+                        ui.color_edit_button_rgb(&mut settings.color);
+                    });
+                }
+                BackgroundMode::Transparent => {
+                    if state.supports_transparency() {
+                        ui.label("On X11, save and restart if the window stays opaque.");
+                    } else {
+                        ui.label("This surface can't be composited; it stays opaque.");
+                    }
+                }
+            }
+            save = ui.button("Save").clicked();
+        });
+        save
+    }
+}
@@ -0,0 +1,310 @@
+//! Separable Gaussian blur as a reusable post node: a horizontal pass into a
+//! transient texture, then a vertical pass into the caller's target. Effects
+//! that need a blurred copy of something (shadow moments, bloom, AO) own a
+//! `Blur` for their format instead of writing their own.
+
+use crate::app::State;
+use crate::transient::{TransientDesc, TransientTexture};
+use std::sync::Arc;
+use wgpu::util::DeviceExt;
+
+const BLUR_SHADER: &str = r#"
+struct Params {
+    direction: vec2<i32>,
+    radius: i32,
+    sigma: f32,
+    // source texels per output texel along each axis, 1 or 2
+    downsample: i32,
+};
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var source: texture_2d<f32>;
+
+@vertex
+fn vsMain(@builtin(vertex_index) i: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2(f32((i << 1u) & 2u), f32(i & 2u));
+    return vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// Box-filters the source texels under output texel `p` when downsampling.
+fn fetch(p: vec2<i32>) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(source));
+    var sum = vec4(0.0);
+    for (var y = 0; y < params.downsample; y++) {
+        for (var x = 0; x < params.downsample; x++) {
+            let q = p * params.downsample + vec2(x, y);
+            sum += textureLoad(source, clamp(q, vec2(0), size - 1), 0);
+        }
+    }
+    return sum / f32(params.downsample * params.downsample);
+}
+
+@fragment
+fn psMain(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+    let center = vec2<i32>(pos.xy);
+    var sum = vec4(0.0);
+    var total = 0.0;
+    for (var i = -params.radius; i <= params.radius; i++) {
+        let x = f32(i);
+        let weight = exp(-x * x / (2.0 * params.sigma * params.sigma));
+        sum += fetch(center + params.direction * i) * weight;
+        total += weight;
+    }
+    return sum / total;
+}
+"#;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct BlurParams {
+    direction: [i32; 2],
+    radius: i32,
+    sigma: f32,
+    downsample: i32,
+    _padding: [i32; 3],
+}
+
+/// Largest `radius` the UI offers; the shader itself has no limit.
+pub const MAX_RADIUS: u32 = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlurSettings {
+    /// Taps either side of the center, per pass.
+    pub radius: u32,
+    /// Standard deviation in texels of the pass's input.
+    pub sigma: f32,
+    /// Blurs at half resolution: cheaper and twice as wide for the same
+    /// radius. Only `Blur::encode` can do this, as the output is smaller.
+    pub half_res: bool,
+}
+
+impl Default for BlurSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BlurSettings {
+    pub fn new() -> Self {
+        BlurSettings {
+            radius: 4,
+            sigma: 2.0,
+            half_res: false,
+        }
+    }
+
+    /// Returns true when anything changed.
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        changed |= ui
+            .add(egui::Slider::new(&mut self.radius, 0..=MAX_RADIUS).text("Blur radius"))
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut self.sigma, 0.5..=16.0).text("Blur sigma"))
+            .changed();
+        changed |= ui.checkbox(&mut self.half_res, "Half resolution").changed();
+        changed
+    }
+}
+
+/// A blur for textures of one format. Each encode gets its own parameter
+/// buffers, so one `Blur` can be used several times per frame.
+pub struct Blur {
+    pub format: wgpu::TextureFormat,
+    pipeline: wgpu::RenderPipeline,
+    layout: wgpu::BindGroupLayout,
+}
+
+impl Blur {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Blur"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    // loads only, so unfilterable formats like Rg32Float work
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Blur"),
+            source: wgpu::ShaderSource::Wgsl(BLUR_SHADER.into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Blur"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Blur"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vsMain"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("psMain"),
+                compilation_options: Default::default(),
+                targets: &[Some(format.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        Blur {
+            format,
+            pipeline,
+            layout,
+        }
+    }
+
+    /// Blurs `source`, `width` by `height` texels, into a texture from the
+    /// transient pool, half the size with `half_res`. Drop the returned
+    /// handle once the result has been read.
+    pub fn encode(
+        &self,
+        state: &State,
+        encoder: &mut wgpu::CommandEncoder,
+        source: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+        settings: &BlurSettings,
+    ) -> Arc<TransientTexture> {
+        let downsample = if settings.half_res { 2 } else { 1 };
+        let desc = self.desc(width.div_ceil(downsample), height.div_ceil(downsample));
+        let target = state.transient.acquire(&state.device, desc);
+        self.encode_passes(
+            state,
+            encoder,
+            source,
+            &target.view,
+            desc,
+            downsample,
+            settings,
+        );
+        target
+    }
+
+    /// Blurs `source` into `target`, both `width` by `height` texels. They
+    /// may be the same texture, which blurs in place. `settings.half_res`
+    /// is ignored.
+    #[allow(clippy::too_many_arguments)]
+    pub fn encode_into(
+        &self,
+        state: &State,
+        encoder: &mut wgpu::CommandEncoder,
+        source: &wgpu::TextureView,
+        target: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+        settings: &BlurSettings,
+    ) {
+        let desc = self.desc(width, height);
+        self.encode_passes(state, encoder, source, target, desc, 1, settings);
+    }
+
+    fn desc(&self, width: u32, height: u32) -> TransientDesc {
+        TransientDesc {
+            width,
+            height,
+            format: self.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        }
+    }
+
+    /// `desc` is the output's size; the horizontal pass also downsamples.
+    #[allow(clippy::too_many_arguments)]
+    fn encode_passes(
+        &self,
+        state: &State,
+        encoder: &mut wgpu::CommandEncoder,
+        source: &wgpu::TextureView,
+        target: &wgpu::TextureView,
+        desc: TransientDesc,
+        downsample: u32,
+        settings: &BlurSettings,
+    ) {
+        let intermediate = state.transient.acquire(&state.device, desc);
+        let passes = [
+            (
+                "blur horizontal",
+                [1, 0],
+                downsample,
+                source,
+                &intermediate.view,
+            ),
+            ("blur vertical", [0, 1], 1, &intermediate.view, target),
+        ];
+        for (label, direction, downsample, input, output) in passes {
+            let params = BlurParams {
+                direction,
+                radius: settings.radius as i32,
+                sigma: settings.sigma.max(0.01),
+                downsample: downsample as i32,
+                _padding: [0; 3],
+            };
+            let buffer = state
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Blur Params"),
+                    contents: bytemuck::cast_slice(&[params]),
+                    usage: wgpu::BufferUsages::UNIFORM,
+                });
+            let bind_group = state.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Blur"),
+                layout: &self.layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(input),
+                    },
+                ],
+            });
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(label),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: output,
+                    depth_slice: None,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+        state.profiler.record_draws(2, 2);
+        state.profiler.record_target(desc.width, desc.height);
+    }
+}
@@ -3,6 +3,7 @@ use std::fmt;
 use std::sync::Arc;
 use wgpu::util::DeviceExt;
 
+/// Perspective camera and its view-projection uniform buffer.
 pub struct Camera {
     uniform: CameraUniform,
     buffer: Arc<wgpu::Buffer>,
@@ -63,11 +64,28 @@ impl Camera {
         &self.buffer
     }
 
+    pub fn aspect_ratio(&self) -> f32 {
+        self.aspect_ratio
+    }
+
+    /// Matches the projection to a new surface size, from the next
+    /// `update_uniform`. Zero sizes, from minimized windows, are ignored.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.aspect_ratio = width as f32 / height as f32;
+        }
+    }
+
+    /// As of the last `update_uniform`.
+    pub fn view_proj(&self) -> glam::Mat4 {
+        self.projection * self.view
+    }
+
     pub fn update_uniform(&mut self) {
-        let view = glam::Mat4::look_at_rh(self.eye, self.center, self.up);
-        let projection =
+        self.view = glam::Mat4::look_at_rh(self.eye, self.center, self.up);
+        self.projection =
             glam::Mat4::perspective_rh_gl(self.fov, self.aspect_ratio, self.z_near, self.z_far);
-        self.uniform.view_proj = (projection * view).to_cols_array_2d();
+        self.uniform.view_proj = (self.projection * self.view).to_cols_array_2d();
     }
 
     pub fn queue_uniform(&self, queue: &wgpu::Queue) {
@@ -99,32 +117,18 @@ struct CameraUniform {
 }
 
 fn pretty_mat4(m: &glam::Mat4) -> String {
-    let cols = m.to_cols_array_2d();
-    let mut s = String::new();
-    for row in 0..4 {
-        s.push('\t');
-        s.push_str("[ ");
-        (0..4).for_each(|col| {
-            s.push_str(&format!("{:8.4}", cols[col][row]));
-            if col != 3 {
-                s.push_str(", ");
-            }
-        });
-        s.push_str(" ]\n");
-    }
-    s
+    pretty_array4x4(&m.to_cols_array_2d())
 }
 
 fn pretty_array4x4(m: &[[f32; 4]; 4]) -> String {
+    let rows = glam::Mat4::from_cols_array_2d(m)
+        .transpose()
+        .to_cols_array_2d();
     let mut s = String::new();
-    for row in 0..4 {
-        s.push_str("	[ ");
-        (0..4).for_each(|col| {
-            s.push_str(&format!("{:8.4}", m[col][row]));
-            if col != 3 {
-                s.push_str(", ");
-            }
-        });
+    for row in rows {
+        s.push_str("\t[ ");
+        let cells: Vec<String> = row.iter().map(|v| format!("{:8.4}", v)).collect();
+        s.push_str(&cells.join(", "));
         s.push_str(" ]\n");
     }
     s
@@ -0,0 +1,222 @@
+use crate::camera::Camera;
+use crate::input::{Action, Input};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMode {
+    Orbit,
+    Fly,
+}
+
+/// Damping rates are in 1/seconds: higher values settle faster.
+/// With `enabled` off, input is applied directly each frame so benchmark
+/// paths don't depend on frame timing.
+#[derive(Debug, Clone, Copy)]
+pub struct Smoothing {
+    pub enabled: bool,
+    pub orbit_damping: f32,
+    pub pan_damping: f32,
+    pub zoom_damping: f32,
+    pub fly_acceleration: f32,
+    pub fly_damping: f32,
+}
+
+impl Default for Smoothing {
+    fn default() -> Self {
+        Smoothing {
+            enabled: true,
+            orbit_damping: 10.0,
+            pan_damping: 10.0,
+            zoom_damping: 8.0,
+            fly_acceleration: 40.0,
+            fly_damping: 6.0,
+        }
+    }
+}
+
+pub struct CameraController {
+    pub mode: CameraMode,
+    pub smoothing: Smoothing,
+    pub orbit_sensitivity: f32,
+    pub pan_sensitivity: f32,
+    pub zoom_sensitivity: f32,
+    pub look_sensitivity: f32,
+    pub fly_speed: f32,
+    /// Set by tools dragging with a camera binding; the next update ignores
+    /// input and clears it.
+    pub held: bool,
+    yaw: f32,
+    pitch: f32,
+    distance: f32,
+    orbit_velocity: glam::Vec2,
+    pan_velocity: glam::Vec2,
+    zoom_velocity: f32,
+    fly_velocity: glam::Vec3,
+}
+
+impl CameraController {
+    pub fn new(camera: &Camera) -> Self {
+        let mut controller = CameraController {
+            mode: CameraMode::Orbit,
+            smoothing: Smoothing::default(),
+            orbit_sensitivity: 0.005,
+            pan_sensitivity: 0.002,
+            zoom_sensitivity: 0.1,
+            look_sensitivity: 0.003,
+            fly_speed: 5.0,
+            held: false,
+            yaw: 0.0,
+            pitch: 0.0,
+            distance: 1.0,
+            orbit_velocity: glam::Vec2::ZERO,
+            pan_velocity: glam::Vec2::ZERO,
+            zoom_velocity: 0.0,
+            fly_velocity: glam::Vec3::ZERO,
+        };
+        controller.sync_from_camera(camera);
+        controller
+    }
+
+    /// Re-derives yaw/pitch/distance after the camera was edited externally.
+    pub fn sync_from_camera(&mut self, camera: &Camera) {
+        let offset = camera.eye - camera.center;
+        self.distance = offset.length().max(0.01);
+        self.yaw = offset.x.atan2(offset.z);
+        self.pitch = (offset.y / self.distance).clamp(-1.0, 1.0).asin();
+        self.stop();
+    }
+
+    pub fn stop(&mut self) {
+        self.orbit_velocity = glam::Vec2::ZERO;
+        self.pan_velocity = glam::Vec2::ZERO;
+        self.zoom_velocity = 0.0;
+        self.fly_velocity = glam::Vec3::ZERO;
+    }
+
+    pub fn toggle_mode(&mut self, camera: &Camera) {
+        self.mode = match self.mode {
+            CameraMode::Orbit => CameraMode::Fly,
+            CameraMode::Fly => CameraMode::Orbit,
+        };
+        self.sync_from_camera(camera);
+    }
+
+    pub fn update(&mut self, camera: &mut Camera, input: &Input, dt: f32) {
+        if std::mem::take(&mut self.held) {
+            self.stop();
+            return;
+        }
+        match self.mode {
+            CameraMode::Orbit => self.update_orbit(camera, input, dt),
+            CameraMode::Fly => self.update_fly(camera, input, dt),
+        }
+    }
+
+    fn update_orbit(&mut self, camera: &mut Camera, input: &Input, dt: f32) {
+        let orbit_input = if input.held(Action::CameraOrbit) {
+            input.cursor_delta() * self.orbit_sensitivity
+        } else {
+            glam::Vec2::ZERO
+        };
+        let pan_input = if input.held(Action::CameraPan) {
+            input.cursor_delta() * self.pan_sensitivity * self.distance
+        } else {
+            glam::Vec2::ZERO
+        };
+        let zoom_input = -input.scroll() * self.zoom_sensitivity;
+
+        let (orbit, pan, zoom) = if self.smoothing.enabled && dt > 0.0 {
+            // inputs are impulses; velocities carry them over the following frames
+            self.orbit_velocity += orbit_input * self.smoothing.orbit_damping;
+            self.pan_velocity += pan_input * self.smoothing.pan_damping;
+            self.zoom_velocity += zoom_input * self.smoothing.zoom_damping;
+
+            let step = (
+                self.orbit_velocity * dt,
+                self.pan_velocity * dt,
+                self.zoom_velocity * dt,
+            );
+
+            self.orbit_velocity *= damp(self.smoothing.orbit_damping, dt);
+            self.pan_velocity *= damp(self.smoothing.pan_damping, dt);
+            self.zoom_velocity *= damp(self.smoothing.zoom_damping, dt);
+            step
+        } else {
+            (orbit_input, pan_input, zoom_input)
+        };
+
+        self.yaw -= orbit.x;
+        self.pitch = (self.pitch + orbit.y).clamp(-1.55, 1.55);
+        self.distance = (self.distance * (1.0 + zoom)).max(0.01);
+
+        let forward = (camera.center - camera.eye).normalize_or_zero();
+        let right = forward.cross(camera.up).normalize_or_zero();
+        let up = right.cross(forward);
+        camera.center += -right * pan.x + up * pan.y;
+
+        camera.eye = camera.center + self.distance * orbit_direction(self.yaw, self.pitch);
+    }
+
+    fn update_fly(&mut self, camera: &mut Camera, input: &Input, dt: f32) {
+        if input.held(Action::CameraLook) {
+            let look = input.cursor_delta() * self.look_sensitivity;
+            self.yaw -= look.x;
+            self.pitch = (self.pitch + look.y).clamp(-1.55, 1.55);
+        }
+
+        // the orbit direction points from center to eye, so flying looks the other way
+        let forward = -orbit_direction(self.yaw, self.pitch);
+        let right = forward.cross(camera.up).normalize_or_zero();
+
+        let mut wish = glam::Vec3::ZERO;
+        if input.held(Action::CameraForward) {
+            wish += forward;
+        }
+        if input.held(Action::CameraBack) {
+            wish -= forward;
+        }
+        if input.held(Action::CameraRight) {
+            wish += right;
+        }
+        if input.held(Action::CameraLeft) {
+            wish -= right;
+        }
+        if input.held(Action::CameraUp) {
+            wish += camera.up;
+        }
+        if input.held(Action::CameraDown) {
+            wish -= camera.up;
+        }
+        let speed = if input.held(Action::CameraBoost) {
+            self.fly_speed * 4.0
+        } else {
+            self.fly_speed
+        };
+        let target_velocity = wish.normalize_or_zero() * speed;
+
+        if self.smoothing.enabled {
+            let to_target = target_velocity - self.fly_velocity;
+            let max_step = self.smoothing.fly_acceleration * dt;
+            self.fly_velocity += to_target.clamp_length_max(max_step);
+            if wish == glam::Vec3::ZERO {
+                self.fly_velocity *= damp(self.smoothing.fly_damping, dt);
+            }
+        } else {
+            self.fly_velocity = target_velocity;
+        }
+
+        camera.eye += self.fly_velocity * dt;
+        camera.center = camera.eye + forward * self.distance;
+    }
+}
+
+fn orbit_direction(yaw: f32, pitch: f32) -> glam::Vec3 {
+    glam::vec3(
+        pitch.cos() * yaw.sin(),
+        pitch.sin(),
+        pitch.cos() * yaw.cos(),
+    )
+}
+
+fn damp(rate: f32, dt: f32) -> f32 {
+    (-rate * dt).exp()
+}
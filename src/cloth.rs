@@ -0,0 +1,599 @@
+//! A mass-spring cloth simulated on the CPU with Verlet integration and
+//! position constraints, written into its model's mesh every frame with
+//! `Mesh::update_vertices`. Particles can be pinned, are pushed around by a
+//! gusting wind and are kept out of sphere colliders attached to models.
+
+use crate::app::State;
+use crate::mesh::{Mesh, Vertex};
+use crate::model::EntityId;
+use crate::plugin::{Plugin, PluginContext};
+use crate::transform::Transform;
+use crate::world::World;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Simulation steps are this long whatever the frame rate...
+const STEP: f32 = 1.0 / 120.0;
+/// ...up to this many a frame, after which the cloth runs slow.
+const MAX_STEPS: u32 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pinning {
+    None,
+    /// The two corners on the -Z edge.
+    Corners,
+    /// The whole -Z edge.
+    Edge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClothParams {
+    /// Downward acceleration, in units per second².
+    pub gravity: f32,
+    /// Fraction of velocity lost per step.
+    pub damping: f32,
+    /// Constraint passes per step; more is stiffer.
+    pub iterations: u32,
+    /// Velocity of the air, in units per second.
+    pub wind: glam::Vec3,
+    /// 0 is a steady wind, 1 gusts between calm and double.
+    pub turbulence: f32,
+    /// Acceleration per unit of air speed across the cloth's faces.
+    pub drag: f32,
+    /// Gap kept between the cloth and colliders.
+    pub thickness: f32,
+}
+
+impl Default for ClothParams {
+    fn default() -> Self {
+        ClothParams {
+            gravity: 400.0,
+            damping: 0.01,
+            iterations: 8,
+            wind: glam::vec3(60.0, 0.0, 40.0),
+            turbulence: 0.5,
+            drag: 2.0,
+            thickness: 1.5,
+        }
+    }
+}
+
+/// A sphere following a model: `offset` and `radius` are in the model's
+/// space, so the sphere moves and scales with it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SphereCollider {
+    pub entity: EntityId,
+    pub offset: glam::Vec3,
+    pub radius: f32,
+}
+
+impl SphereCollider {
+    /// The sphere around `model`'s local bounds.
+    pub fn around(world: &World, entity: EntityId) -> Option<Self> {
+        let (min, max) = world.model(entity)?.mesh.bounds();
+        Some(SphereCollider {
+            entity,
+            offset: (min + max) * 0.5,
+            radius: ((max - min) * 0.5).max_element(),
+        })
+    }
+
+    /// World-space center and radius; `None` once the model is gone.
+    pub fn resolve(&self, world: &World) -> Option<(glam::Vec3, f32)> {
+        let matrix = world.model(self.entity)?.global_matrix();
+        let (scale, _, _) = matrix.to_scale_rotation_translation();
+        Some((
+            matrix.transform_point3(self.offset),
+            self.radius * scale.abs().max_element(),
+        ))
+    }
+}
+
+/// A square grid of particles joined by stretch, shear and bend springs.
+pub struct Cloth {
+    pub resolution: u32,
+    pub size: f32,
+    positions: Vec<glam::Vec3>,
+    previous: Vec<glam::Vec3>,
+    pinned: Vec<bool>,
+    /// Particle pairs, rest length and stiffness.
+    constraints: Vec<(u32, u32, f32, f32)>,
+}
+
+impl Cloth {
+    /// A flat `size` wide square in the XZ plane, `resolution` quads a side.
+    pub fn new(size: f32, resolution: u32) -> Self {
+        let n = resolution.max(1);
+        let step = size / n as f32;
+        let mut positions = vec![];
+        for j in 0..=n {
+            for i in 0..=n {
+                positions.push(glam::vec3(
+                    i as f32 * step - size / 2.0,
+                    0.0,
+                    j as f32 * step - size / 2.0,
+                ));
+            }
+        }
+        let index = |i: u32, j: u32| j * (n + 1) + i;
+        let mut constraints = vec![];
+        let mut join = |a: u32, b: u32, stiffness: f32| {
+            let rest = positions[a as usize].distance(positions[b as usize]);
+            constraints.push((a, b, rest, stiffness));
+        };
+        for j in 0..=n {
+            for i in 0..=n {
+                if i < n {
+                    join(index(i, j), index(i + 1, j), 1.0);
+                }
+                if j < n {
+                    join(index(i, j), index(i, j + 1), 1.0);
+                }
+                if i < n && j < n {
+                    join(index(i, j), index(i + 1, j + 1), 0.5);
+                    join(index(i + 1, j), index(i, j + 1), 0.5);
+                }
+                if i + 2 <= n {
+                    join(index(i, j), index(i + 2, j), 0.2);
+                }
+                if j + 2 <= n {
+                    join(index(i, j), index(i, j + 2), 0.2);
+                }
+            }
+        }
+        Cloth {
+            resolution: n,
+            size,
+            previous: positions.clone(),
+            pinned: vec![false; positions.len()],
+            positions,
+            constraints,
+        }
+    }
+
+    pub fn particle_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn constraint_count(&self) -> usize {
+        self.constraints.len()
+    }
+
+    /// Pins by `pinning` and frees everything else.
+    pub fn pin(&mut self, pinning: Pinning) {
+        let n = self.resolution;
+        for (index, pinned) in self.pinned.iter_mut().enumerate() {
+            let (i, j) = (index as u32 % (n + 1), index as u32 / (n + 1));
+            *pinned = match pinning {
+                Pinning::None => false,
+                Pinning::Corners => j == 0 && (i == 0 || i == n),
+                Pinning::Edge => j == 0,
+            };
+        }
+    }
+
+    /// Advances `dt` seconds, `seconds` into the run, against colliders
+    /// given in the cloth's space.
+    pub fn step(
+        &mut self,
+        dt: f32,
+        seconds: f32,
+        params: &ClothParams,
+        colliders: &[(glam::Vec3, f32)],
+    ) {
+        let mut acceleration = vec![glam::Vec3::NEG_Y * params.gravity; self.positions.len()];
+        if params.drag > 0.0 {
+            // air pushes each face along its normal, by the relative speed
+            // of air and face; a particle's mass is its share of the rest
+            // area, and it shares six faces
+            let cell = self.size / self.resolution as f32;
+            let rest_area = cell * cell * 0.5;
+            let indices = self.indices();
+            for triangle in indices.chunks_exact(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+                let p = [a, b, c].map(|i| self.positions[i]);
+                let velocity = [a, b, c]
+                    .map(|i| self.positions[i] - self.previous[i])
+                    .into_iter()
+                    .sum::<glam::Vec3>()
+                    / (3.0 * dt);
+                let center = (p[0] + p[1] + p[2]) / 3.0;
+                let gust = 1.0
+                    + params.turbulence
+                        * (seconds * 1.7 + center.x * 0.02).sin()
+                        * (seconds * 0.9 + center.z * 0.03).cos();
+                let area_normal = (p[1] - p[0]).cross(p[2] - p[0]) * 0.5;
+                let relative = params.wind * gust - velocity;
+                let area = area_normal.length();
+                let normal = area_normal.normalize_or_zero();
+                let force = normal * normal.dot(relative) * params.drag * area / rest_area / 6.0;
+                for i in [a, b, c] {
+                    acceleration[i] += force;
+                }
+            }
+        }
+
+        let keep = 1.0 - params.damping;
+        for (i, position) in self.positions.iter_mut().enumerate() {
+            if self.pinned[i] {
+                continue;
+            }
+            let velocity = (*position - self.previous[i]) * keep;
+            self.previous[i] = *position;
+            *position += velocity + acceleration[i] * dt * dt;
+        }
+
+        for _ in 0..params.iterations {
+            for &(a, b, rest, stiffness) in &self.constraints {
+                let (a, b) = (a as usize, b as usize);
+                let weights = (!self.pinned[a] as u32 as f32, !self.pinned[b] as u32 as f32);
+                let total = weights.0 + weights.1;
+                let delta = self.positions[b] - self.positions[a];
+                let length = delta.length();
+                if total == 0.0 || length <= f32::EPSILON {
+                    continue;
+                }
+                let correction = delta * ((length - rest) / length * stiffness / total);
+                self.positions[a] += correction * weights.0;
+                self.positions[b] -= correction * weights.1;
+            }
+            for (i, position) in self.positions.iter_mut().enumerate() {
+                if self.pinned[i] {
+                    continue;
+                }
+                for &(center, radius) in colliders {
+                    let offset = *position - center;
+                    let reach = radius + params.thickness;
+                    if offset.length_squared() < reach * reach {
+                        *position = center + offset.normalize_or(glam::Vec3::Y) * reach;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Positions with smooth normals and a 0..1 UV square.
+    pub fn vertices(&self) -> Vec<Vertex> {
+        let mut normals = vec![glam::Vec3::ZERO; self.positions.len()];
+        for triangle in self.indices().chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+            let p = [a, b, c].map(|i| self.positions[i]);
+            let normal = (p[1] - p[0]).cross(p[2] - p[0]);
+            for i in [a, b, c] {
+                normals[i] += normal;
+            }
+        }
+        let n = self.resolution + 1;
+        self.positions
+            .iter()
+            .zip(normals)
+            .enumerate()
+            .map(|(index, (position, normal))| {
+                let uv = [
+                    (index as u32 % n) as f32 / self.resolution as f32,
+                    (index as u32 / n) as f32 / self.resolution as f32,
+                ];
+                Vertex {
+                    pos: position.to_array(),
+                    normal: normal.normalize_or(glam::Vec3::Y).to_array(),
+                    uv,
+                    uv1: uv,
+                }
+            })
+            .collect()
+    }
+
+    /// Two triangles per quad, wound to face +Y while flat.
+    pub fn indices(&self) -> Vec<u32> {
+        let n = self.resolution;
+        let mut indices = Vec::with_capacity((n * n * 6) as usize);
+        for j in 0..n {
+            for i in 0..n {
+                let a = j * (n + 1) + i;
+                let b = a + n + 1;
+                indices.extend_from_slice(&[a, b, a + 1, a + 1, b, b + 1]);
+            }
+        }
+        indices
+    }
+}
+
+/// A UV sphere of `radius` with `segments` around and half as many down.
+pub fn sphere_mesh(device: &wgpu::Device, radius: f32, segments: u32) -> Mesh {
+    let segments = segments.max(3);
+    let rings = (segments / 2).max(2);
+    let mut vertices = vec![];
+    for j in 0..=rings {
+        let v = j as f32 / rings as f32;
+        let (sin_theta, cos_theta) = (v * std::f32::consts::PI).sin_cos();
+        for i in 0..=segments {
+            let u = i as f32 / segments as f32;
+            let (sin_phi, cos_phi) = (u * std::f32::consts::TAU).sin_cos();
+            let normal = glam::vec3(sin_theta * cos_phi, cos_theta, sin_theta * sin_phi);
+            vertices.push(Vertex {
+                pos: (normal * radius).to_array(),
+                normal: normal.to_array(),
+                uv: [u, v],
+                uv1: [u, v],
+            });
+        }
+    }
+    let mut indices = vec![];
+    for j in 0..rings {
+        for i in 0..segments {
+            let a = j * (segments + 1) + i;
+            let b = a + segments + 1;
+            indices.extend_from_slice(&[a, a + 1, b, a + 1, b + 1, b]);
+        }
+    }
+    let mut mesh = Mesh::new(device, "Sphere", &vertices, &indices);
+    mesh.double_sided = false;
+    mesh
+}
+
+/// Spawns a cloth and a ball to drop it on, and runs the simulation.
+pub struct ClothDemo {
+    pub cloth: Option<Cloth>,
+    pub params: ClothParams,
+    pub pinning: Pinning,
+    pub colliders: Vec<SphereCollider>,
+    pub paused: bool,
+    /// Settings for the next `reset`.
+    pub size: f32,
+    pub resolution: u32,
+    entity: Option<EntityId>,
+    ball: Option<EntityId>,
+    /// Where the cloth starts, in its model's space.
+    start: glam::Vec3,
+    accumulator: f32,
+    step_ms: f32,
+}
+
+impl Default for ClothDemo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClothDemo {
+    pub fn new() -> Self {
+        ClothDemo {
+            cloth: None,
+            params: ClothParams::default(),
+            pinning: Pinning::Corners,
+            colliders: vec![],
+            paused: false,
+            size: 160.0,
+            resolution: 32,
+            entity: None,
+            ball: None,
+            start: glam::Vec3::ZERO,
+            accumulator: 0.0,
+            step_ms: 0.0,
+        }
+    }
+
+    pub fn entity(&self) -> Option<EntityId> {
+        self.entity
+    }
+
+    /// Spawns the cloth above the camera's target with a ball under it.
+    pub fn spawn(&mut self, state: &State, world: &mut World) {
+        let center = world.camera.center;
+        let radius = self.size * 0.2;
+        let ball = world.spawn(
+            state,
+            "Cloth Ball",
+            Arc::new(sphere_mesh(&state.device, 1.0, 32)),
+            world.default_material(),
+            Transform {
+                translation: center,
+                scale: glam::Vec3::splat(radius),
+                ..Default::default()
+            },
+        );
+        self.colliders.extend(SphereCollider::around(world, ball));
+        self.ball = Some(ball);
+
+        let cloth = Cloth::new(self.size, self.resolution);
+        let mut mesh = Mesh::new(&state.device, "Cloth", &cloth.vertices(), &cloth.indices());
+        mesh.base_color = [0.7, 0.15, 0.2, 1.0];
+        // not added to `world.meshes`, so the model is its only owner
+        let id = world.spawn(
+            state,
+            "Cloth",
+            Arc::new(mesh),
+            world.default_material(),
+            Transform::default(),
+        );
+        self.start = center + glam::Vec3::Y * radius * 2.0;
+        self.entity = Some(id);
+        self.cloth = Some(cloth);
+        self.reset(state, world);
+    }
+
+    /// Rebuilds the cloth flat at its start at the current size and
+    /// resolution, growing the mesh's buffers if needed.
+    pub fn reset(&mut self, state: &State, world: &mut World) {
+        let mut cloth = Cloth::new(self.size, self.resolution);
+        for (position, previous) in cloth.positions.iter_mut().zip(&mut cloth.previous) {
+            *position += self.start;
+            *previous = *position;
+        }
+        cloth.pin(self.pinning);
+        let Some(mesh) = self
+            .entity
+            .and_then(|id| world.model_mut(id))
+            .and_then(|model| model.mesh_mut())
+        else {
+            return;
+        };
+        mesh.update_vertices(&state.device, &state.queue, &cloth.vertices());
+        mesh.update_indices(&state.device, &state.queue, &cloth.indices());
+        self.cloth = Some(cloth);
+        self.accumulator = 0.0;
+    }
+
+    pub fn despawn(&mut self, world: &mut World) {
+        for id in [self.entity.take(), self.ball.take()].into_iter().flatten() {
+            world.despawn(id);
+        }
+        self.cloth = None;
+    }
+}
+
+impl Plugin for ClothDemo {
+    fn name(&self) -> &str {
+        "Cloth"
+    }
+
+    fn update(&mut self, ctx: &mut PluginContext) {
+        let world = &mut *ctx.world;
+        if self.entity.is_some_and(|id| world.model(id).is_none()) {
+            self.entity = None;
+            self.cloth = None;
+        }
+        self.colliders.retain(|c| world.model(c.entity).is_some());
+        let (Some(id), Some(cloth)) = (self.entity, &mut self.cloth) else {
+            return;
+        };
+        if self.paused {
+            return;
+        }
+
+        // the cloth simulates in its model's space
+        let inverse = world.model(id).unwrap().global_matrix().inverse();
+        let (scale, _, _) = inverse.to_scale_rotation_translation();
+        let colliders: Vec<_> = self
+            .colliders
+            .iter()
+            .filter(|c| c.entity != id)
+            .filter_map(|c| c.resolve(world))
+            .map(|(center, radius)| {
+                (
+                    inverse.transform_point3(center),
+                    radius * scale.abs().max_element(),
+                )
+            })
+            .collect();
+
+        let start = Instant::now();
+        self.accumulator = (self.accumulator + ctx.time.delta_seconds).min(STEP * MAX_STEPS as f32);
+        let mut steps = 0;
+        while self.accumulator >= STEP {
+            self.accumulator -= STEP;
+            steps += 1;
+            let seconds = ctx.time.elapsed_seconds - self.accumulator;
+            cloth.step(STEP, seconds, &self.params, &colliders);
+        }
+        if steps == 0 {
+            return;
+        }
+        let vertices = cloth.vertices();
+        if let Some(mesh) = world.model_mut(id).and_then(|model| model.mesh_mut()) {
+            mesh.update_vertices(&ctx.state.device, &ctx.state.queue, &vertices);
+        }
+        self.step_ms = start.elapsed().as_secs_f32() * 1000.0;
+        world.watch.record("cloth ms", self.step_ms);
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, state: &State, world: &mut World) {
+        egui::Window::new("Cloth")
+            .default_open(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if self.entity.is_none() {
+                        if ui.button("Spawn").clicked() {
+                            self.spawn(state, world);
+                        }
+                    } else {
+                        if ui.button("Reset").clicked() {
+                            self.reset(state, world);
+                        }
+                        if ui.button("Despawn").clicked() {
+                            self.despawn(world);
+                        }
+                        let label = if self.paused { "Play" } else { "Pause" };
+                        if ui.button(label).clicked() {
+                            self.paused = !self.paused;
+                        }
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Pin");
+                    let before = self.pinning;
+                    ui.selectable_value(&mut self.pinning, Pinning::None, "None");
+                    ui.selectable_value(&mut self.pinning, Pinning::Corners, "Corners");
+                    ui.selectable_value(&mut self.pinning, Pinning::Edge, "Edge");
+                    if self.pinning != before {
+                        if let Some(cloth) = &mut self.cloth {
+                            cloth.pin(self.pinning);
+                        }
+                    }
+                });
+                ui.add(egui::Slider::new(&mut self.size, 10.0..=500.0).text("Size"));
+                ui.add(egui::Slider::new(&mut self.resolution, 2..=128).text("Resolution"));
+                ui.label("Size and resolution apply on reset.");
+
+                let params = &mut self.params;
+                ui.separator();
+                ui.add(egui::Slider::new(&mut params.gravity, 0.0..=2000.0).text("Gravity"));
+                ui.add(egui::Slider::new(&mut params.damping, 0.0..=0.2).text("Damping"));
+                ui.add(egui::Slider::new(&mut params.iterations, 1..=32).text("Iterations"));
+                ui.horizontal(|ui| {
+                    ui.label("Wind");
+                    ui.add(egui::DragValue::new(&mut params.wind.x).prefix("x: "));
+                    ui.add(egui::DragValue::new(&mut params.wind.y).prefix("y: "));
+                    ui.add(egui::DragValue::new(&mut params.wind.z).prefix("z: "));
+                });
+                ui.add(egui::Slider::new(&mut params.turbulence, 0.0..=1.0).text("Turbulence"));
+                ui.add(egui::Slider::new(&mut params.drag, 0.0..=10.0).text("Drag"));
+                ui.add(egui::Slider::new(&mut params.thickness, 0.0..=10.0).text("Thickness"));
+
+                ui.separator();
+                ui.label("Sphere colliders");
+                let mut remove = None;
+                for (i, collider) in self.colliders.iter_mut().enumerate() {
+                    let name = world.model(collider.entity).map_or("?", |m| &m.name);
+                    ui.horizontal(|ui| {
+                        ui.label(format!("#{} {name}", collider.entity));
+                        ui.add(
+                            egui::DragValue::new(&mut collider.radius)
+                                .speed(0.05)
+                                .range(0.0..=f32::MAX)
+                                .prefix("r: "),
+                        );
+                        if ui.small_button("Remove").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                }
+                if let Some(i) = remove {
+                    self.colliders.remove(i);
+                }
+                let selected = world
+                    .selection
+                    .primary()
+                    .filter(|&id| Some(id) != self.entity)
+                    .filter(|&id| self.colliders.iter().all(|c| c.entity != id));
+                if ui
+                    .add_enabled(selected.is_some(), egui::Button::new("Add selected"))
+                    .clicked()
+                {
+                    self.colliders
+                        .extend(selected.and_then(|id| SphereCollider::around(world, id)));
+                }
+
+                if let Some(cloth) = &self.cloth {
+                    ui.separator();
+                    ui.label(format!(
+                        "{} particles, {} springs, {:.2} ms a frame",
+                        cloth.particle_count(),
+                        cloth.constraint_count(),
+                        self.step_ms
+                    ));
+                }
+            });
+    }
+}
@@ -0,0 +1,452 @@
+//! Named editor actions shared by the menu bar, the command palette and
+//! the log console, which can also pass them arguments.
+//! Plugins add their own with `world.commands.register` in `build`.
+
+use crate::app::State;
+use crate::hdr_still::HdrStill;
+use crate::mesh::create_test_mesh;
+use crate::quality::{self, QualityPreset};
+use crate::scene_patch::ScenePatch;
+use crate::transform::Transform;
+use crate::world::World;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Menu {
+    File,
+    Edit,
+    View,
+    Render,
+    Help,
+}
+
+impl Menu {
+    pub const ALL: [Menu; 5] = [Menu::File, Menu::Edit, Menu::View, Menu::Render, Menu::Help];
+
+    pub fn label(&self) -> String {
+        crate::i18n::tr(match self {
+            Menu::File => "menu.file",
+            Menu::Edit => "menu.edit",
+            Menu::View => "menu.view",
+            Menu::Render => "menu.render",
+            Menu::Help => "menu.help",
+        })
+    }
+}
+
+/// What a command may touch when it runs.
+pub struct CommandContext<'a> {
+    pub state: &'a State,
+    pub world: &'a mut World,
+    pub show_debug_ui: &'a mut bool,
+    /// Words typed after the command in the console; empty from menus.
+    pub args: Vec<String>,
+}
+
+type CommandFn = Box<dyn FnMut(&mut CommandContext)>;
+
+pub struct Command {
+    pub id: String,
+    pub label: String,
+    pub menu: Menu,
+    run: CommandFn,
+}
+
+/// Commands by id. Menus and the palette only queue them; the app runs
+/// the queue once per frame after the UI.
+#[derive(Default)]
+pub struct CommandRegistry {
+    commands: Vec<Command>,
+    queued: Vec<(String, Vec<String>)>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces any command already registered under `id`.
+    pub fn register(
+        &mut self,
+        id: &str,
+        label: &str,
+        menu: Menu,
+        run: impl FnMut(&mut CommandContext) + 'static,
+    ) {
+        self.commands.retain(|c| c.id != id);
+        self.commands.push(Command {
+            id: id.to_string(),
+            label: label.to_string(),
+            menu,
+            run: Box::new(run),
+        });
+    }
+
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    pub fn in_menu(&self, menu: Menu) -> impl Iterator<Item = &Command> {
+        self.commands.iter().filter(move |c| c.menu == menu)
+    }
+
+    pub fn queue(&mut self, id: &str) {
+        self.queue_with_args(id, vec![]);
+    }
+
+    pub fn queue_with_args(&mut self, id: &str, args: Vec<String>) {
+        self.queued.push((id.to_string(), args));
+    }
+
+    /// The id of the command `name` refers to: its full id, or the part
+    /// after the menu prefix if only one command has it, e.g. `clear` for
+    /// `edit.clear`.
+    pub fn resolve(&self, name: &str) -> Result<&str, String> {
+        if let Some(command) = self.commands.iter().find(|c| c.id == name) {
+            return Ok(&command.id);
+        }
+        let matches: Vec<&str> = self
+            .commands
+            .iter()
+            .filter(|c| c.id.rsplit('.').next() == Some(name))
+            .map(|c| c.id.as_str())
+            .collect();
+        match matches[..] {
+            [id] => Ok(id),
+            [] => Err(format!("Unknown command {name}")),
+            _ => Err(format!("{name} is ambiguous: {}", matches.join(", "))),
+        }
+    }
+
+    /// Commands whose label or id fuzzy-matches `query`, best first.
+    pub fn search(&self, query: &str) -> Vec<&Command> {
+        let mut matches: Vec<(i32, &Command)> = self
+            .commands
+            .iter()
+            .filter_map(|c| {
+                let score = fuzzy_score(query, &c.label).max(fuzzy_score(query, &c.id))?;
+                Some((score, c))
+            })
+            .collect();
+        matches.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.label.cmp(&b.1.label)));
+        matches.into_iter().map(|(_, c)| c).collect()
+    }
+}
+
+/// Runs and clears the commands queued on `ctx.world.commands`.
+pub fn run_queued(ctx: &mut CommandContext) {
+    // commands get the world mutably, so the registry can't stay inside it
+    let mut registry = std::mem::take(&mut ctx.world.commands);
+    for (id, args) in std::mem::take(&mut registry.queued) {
+        match registry.commands.iter_mut().find(|c| c.id == id) {
+            Some(command) => {
+                ctx.args = args;
+                (command.run)(ctx);
+            }
+            None => log::warn!("Unknown command {id}"),
+        }
+    }
+    // keep whatever was registered or queued while running
+    let added = std::mem::take(&mut ctx.world.commands);
+    for command in added.commands {
+        registry.commands.retain(|c| c.id != command.id);
+        registry.commands.push(command);
+    }
+    registry.queued.extend(added.queued);
+    ctx.world.commands = registry;
+}
+
+/// Case-insensitive subsequence match. Consecutive characters and word
+/// starts score higher; `None` when `query` isn't a subsequence.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+    for q in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = position + text[position..].iter().position(|&c| c == q)?;
+        score += 1;
+        if previous.is_some_and(|p| p + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        previous = Some(found);
+        position = found + 1;
+    }
+    // prefer shorter labels among equal matches
+    Some(score * 100 - text.len() as i32)
+}
+
+/// Where the patch commands save and load without an argument.
+const PATCH_PATH: &str = "scene.patch.toml";
+
+/// The commands every sandbox has.
+pub fn register_builtin(registry: &mut CommandRegistry) {
+    registry.register("file.screenshot", "Capture screenshot", Menu::File, |ctx| {
+        let unix = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        ctx.world.screenshot = Some(PathBuf::from(format!("screenshot-{unix}.png")));
+    });
+    registry.register("file.hdr_still", "Capture HDR still", Menu::File, |ctx| {
+        if ctx.state.hdr.is_none() {
+            log::warn!("HDR stills need HDR output, which is off");
+            return;
+        }
+        let unix = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let mut still = HdrStill::new(format!("still-{unix}"));
+        // exposure values to bracket at, e.g. `file.hdr_still -3 0 3`
+        if !ctx.args.is_empty() {
+            match ctx.args.iter().map(|a| a.parse()).collect() {
+                Ok(brackets) => still.brackets = brackets,
+                Err(e) => {
+                    log::warn!("Brackets are exposure values in stops: {e}");
+                    return;
+                }
+            }
+        }
+        ctx.world.hdr_still = Some(still);
+    });
+    registry.register(
+        "file.clean_screenshots",
+        "Toggle clean screenshots",
+        Menu::File,
+        |ctx| {
+            ctx.world.clean_screenshots = !ctx.world.clean_screenshots;
+            log::info!(
+                "Screenshots {} the UI and overlays",
+                if ctx.world.clean_screenshots {
+                    "leave out"
+                } else {
+                    "include"
+                }
+            );
+        },
+    );
+    registry.register(
+        "file.export_patch",
+        "Export scene patch",
+        Menu::File,
+        |ctx| {
+            let path = ctx.args.first().map_or(PATCH_PATH, |s| s.as_str());
+            let patch = ScenePatch::diff(ctx.world);
+            match patch.save(path) {
+                Ok(()) => log::info!(
+                    "Wrote {} changed, {} added and {} removed model(s) to {path}",
+                    patch.changed.len(),
+                    patch.added.len(),
+                    patch.removed.len()
+                ),
+                Err(e) => log::error!("Failed to write {path}: {e}"),
+            }
+        },
+    );
+    registry.register("file.apply_patch", "Apply scene patch", Menu::File, |ctx| {
+        let path = ctx.args.first().map_or(PATCH_PATH, |s| s.as_str());
+        match ScenePatch::load(path) {
+            Ok(patch) => patch.apply(ctx.state, ctx.world),
+            Err(e) => log::error!("Failed to read {path}: {e}"),
+        }
+    });
+    registry.register("edit.spawn_triangle", "Spawn triangle", Menu::Edit, |ctx| {
+        let mesh = create_test_mesh(&ctx.state.device);
+        let material = ctx.world.default_material();
+        let transform = Transform::from_translation(ctx.world.camera.center);
+        ctx.world
+            .spawn(ctx.state, "Triangle", mesh, material, transform);
+    });
+    registry.register("edit.spawn", "Spawn mesh", Menu::Edit, |ctx| {
+        // spawn [mesh] [count]
+        let name = ctx.args.first().map_or("triangle", |s| s.as_str());
+        let count = match ctx.args.get(1).map(|s| s.parse::<u32>()) {
+            None => 1,
+            Some(Ok(count)) => count,
+            Some(Err(_)) => {
+                log::warn!("spawn: count must be a whole number");
+                return;
+            }
+        };
+        let lower = name.to_lowercase();
+        let mesh = match ctx
+            .world
+            .meshes
+            .iter()
+            .find(|m| m.name.to_lowercase().contains(&lower))
+        {
+            Some(mesh) => mesh.clone(),
+            None if lower == "triangle" => create_test_mesh(&ctx.state.device),
+            None => {
+                log::warn!("spawn: no mesh named {name}");
+                return;
+            }
+        };
+        let material = ctx.world.default_material();
+        let (min, max) = mesh.bounds();
+        let spacing = (max - min).x.max(1.0) * 1.5;
+        for i in 0..count {
+            let offset = (i as f32 - (count - 1) as f32 / 2.0) * spacing;
+            let position = ctx.world.camera.center + glam::Vec3::X * offset;
+            let transform = Transform::from_translation(position);
+            ctx.world.spawn(
+                ctx.state,
+                &mesh.name,
+                mesh.clone(),
+                material.clone(),
+                transform,
+            );
+        }
+        log::info!("Spawned {count} {}", mesh.name);
+    });
+    registry.register("edit.set", "Set value", Menu::Edit, |ctx| {
+        // set <name> <value>
+        let (Some(name), Some(value)) = (ctx.args.first(), ctx.args.get(1)) else {
+            log::info!(
+                "set: usage is set <name> <value>; names are fov (degrees), near, far, anisotropy, time (hours), day_length (seconds)"
+            );
+            return;
+        };
+        let Ok(value) = value.parse::<f32>() else {
+            log::warn!("set: {value} is not a number");
+            return;
+        };
+        let camera = &mut ctx.world.camera;
+        match name.as_str() {
+            "fov" => camera.fov = value.to_radians(),
+            "near" => camera.z_near = value,
+            "far" => camera.z_far = value,
+            "time" => ctx.world.sky.time_of_day = value.rem_euclid(24.0),
+            "day_length" => ctx.world.sky.cycle.day_length = value.max(1.0),
+            "anisotropy" => {
+                let value = (value as u16).min(ctx.state.caps.max_anisotropy);
+                if ctx.state.samplers.set_max_anisotropy(value) {
+                    ctx.world.refresh_samplers(ctx.state);
+                }
+            }
+            _ => {
+                log::warn!("set: unknown value {name}");
+                return;
+            }
+        }
+        ctx.world.camera.update_uniform();
+        log::info!("{name} = {value}");
+    });
+    registry.register("edit.clear", "Clear scene", Menu::Edit, |ctx| {
+        ctx.world.clear();
+    });
+    registry.register("view.debug_ui", "Toggle debug UI", Menu::View, |ctx| {
+        *ctx.show_debug_ui = !*ctx.show_debug_ui;
+    });
+    registry.register(
+        "view.camera_mode",
+        "Toggle orbit/fly camera",
+        Menu::View,
+        |ctx| {
+            let world = &mut *ctx.world;
+            world.camera_controller.toggle_mode(&world.camera);
+        },
+    );
+    registry.register(
+        "render.export_trace",
+        "Export frame trace",
+        Menu::Render,
+        |ctx| {
+            let unix = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs());
+            let path = PathBuf::from(format!("trace-{unix}.json"));
+            match ctx.state.profiler.write_chrome_trace(&path) {
+                Ok(()) => log::info!("Wrote frame trace to {}", path.display()),
+                Err(e) => log::error!("Failed to write frame trace: {e}"),
+            }
+        },
+    );
+    registry.register(
+        "render.freeze_culling",
+        "Freeze culling camera",
+        Menu::Render,
+        |ctx| {
+            let world = &mut *ctx.world;
+            world.culling.toggle_frozen(&world.camera);
+        },
+    );
+    registry.register(
+        "render.quality",
+        "Next quality preset",
+        Menu::Render,
+        |ctx| {
+            let preset = match ctx.args.first() {
+                Some(name) => match QualityPreset::parse(name) {
+                    Some(preset) => preset,
+                    None => {
+                        log::warn!("No quality preset {name}; try low, medium, high or ultra");
+                        return;
+                    }
+                },
+                None => ctx
+                    .world
+                    .quality
+                    .preset
+                    .map_or(QualityPreset::Low, QualityPreset::next),
+            };
+            quality::apply(preset, ctx.state, ctx.world);
+        },
+    );
+    registry.register(
+        "render.freeze_frame_data",
+        "Freeze frame data",
+        Menu::Render,
+        |ctx| {
+            ctx.world.freeze_frame_data = !ctx.world.freeze_frame_data;
+        },
+    );
+    registry.register(
+        "render.step_frame_data",
+        "Step one frame of frame data",
+        Menu::Render,
+        |ctx| {
+            ctx.world.step_frame_data = ctx.world.freeze_frame_data;
+        },
+    );
+    registry.register("render.sky", "Toggle procedural sky", Menu::Render, |ctx| {
+        ctx.world.sky.enabled = !ctx.world.sky.enabled;
+    });
+    registry.register(
+        "render.day_cycle",
+        "Play/pause day-night cycle",
+        Menu::Render,
+        |ctx| {
+            let sky = &mut ctx.world.sky;
+            sky.cycle.running = !sky.cycle.running;
+            sky.enabled |= sky.cycle.running;
+        },
+    );
+    registry.register(
+        "render.wireframe",
+        "Toggle wireframe",
+        Menu::Render,
+        |ctx| {
+            if !ctx.state.caps.wireframe {
+                log::warn!("Wireframe needs POLYGON_MODE_LINE, which this adapter lacks");
+                return;
+            }
+            let wireframe = ctx
+                .world
+                .models
+                .iter()
+                .any(|m| m.primitive().polygon_mode == wgpu::PolygonMode::Fill);
+            for model in &mut ctx.world.models {
+                let mut primitive = model.primitive();
+                primitive.polygon_mode = if wireframe {
+                    wgpu::PolygonMode::Line
+                } else {
+                    wgpu::PolygonMode::Fill
+                };
+                model.set_primitive(&ctx.state.device, primitive);
+            }
+        },
+    );
+}
@@ -0,0 +1,60 @@
+use crate::background::BackgroundSettings;
+use crate::frame_pacing::VsyncMode;
+use crate::hdr_output::HdrSettings;
+use crate::input::InputBindings;
+use crate::quality::QualityPreset;
+use crate::ui_theme::UiTheme;
+use serde::{Deserialize, Serialize};
+
+const CONFIG_PATH: &str = "sandbox.toml";
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub bindings: InputBindings,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fps_cap: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub present_mode: Option<VsyncMode>,
+    /// Frames the CPU may queue ahead of the display, 1 to 3.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frame_latency: Option<u32>,
+    /// Anisotropic filtering cap, 1 to 16; off when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_anisotropy: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<BackgroundSettings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hdr: Option<HdrSettings>,
+    /// Applied after `max_anisotropy`, so it wins when both are set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<QualityPreset>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ui_theme: Option<UiTheme>,
+    /// A file name in `lang/` without `.toml`; English when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+impl Config {
+    /// Falls back to defaults when the file is missing or can't be parsed.
+    pub fn load() -> Self {
+        let Ok(text) = std::fs::read_to_string(CONFIG_PATH) else {
+            return Config::default();
+        };
+        match toml::from_str(&text) {
+            Ok(config) => config,
+            Err(e) => {
+                log::warn!("Failed to parse {CONFIG_PATH}, using defaults: {e}");
+                Config::default()
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        let text = toml::to_string_pretty(self).expect("Failed to serialize config");
+        if let Err(e) = std::fs::write(CONFIG_PATH, text) {
+            log::warn!("Failed to write {CONFIG_PATH}: {e}");
+        }
+    }
+}
@@ -0,0 +1,154 @@
+//! In-app log console: the captured log with level filtering and search,
+//! and a command line that runs registered commands with arguments, e.g.
+//! `spawn fox 10` or `set fov 90`.
+
+use crate::commands::CommandRegistry;
+use crate::detachable::DetachableWindow;
+use crate::diagnostics::{self, LogLine};
+
+/// Commands remembered for Up/Down recall.
+const HISTORY: usize = 50;
+
+pub struct Console {
+    /// Most verbose level shown.
+    level: log::LevelFilter,
+    search: String,
+    input: String,
+    history: Vec<String>,
+    /// Index into `history` while recalling with Up/Down.
+    recall: Option<usize>,
+    /// Lines before this one were cleared from view.
+    cleared: u64,
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Console {
+            level: log::LevelFilter::Info,
+            search: String::new(),
+            input: String::new(),
+            history: vec![],
+            recall: None,
+            cleared: 0,
+        }
+    }
+
+    pub fn ui(&mut self, ctx: &egui::Context, commands: &mut CommandRegistry) {
+        DetachableWindow::new("Console")
+            .default_open(false)
+            .resizable(true)
+            .default_size([520.0, 300.0])
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_salt("console_level")
+                        .selected_text(self.level.as_str())
+                        .show_ui(ui, |ui| {
+                            for level in log::LevelFilter::iter().skip(1) {
+                                ui.selectable_value(&mut self.level, level, level.as_str());
+                            }
+                        });
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.search)
+                            .hint_text("Search")
+                            .desired_width(160.0),
+                    );
+                    if ui.button("Clear").clicked() {
+                        self.cleared = diagnostics::log_total();
+                    }
+                });
+                ui.separator();
+
+                let search = self.search.to_lowercase();
+                let lines: Vec<LogLine> = diagnostics::log_lines(self.cleared)
+                    .into_iter()
+                    .filter(|l| l.level <= self.level)
+                    .filter(|l| search.is_empty() || l.to_string().to_lowercase().contains(&search))
+                    .collect();
+                let height = ui.available_height() - 28.0;
+                egui::ScrollArea::vertical()
+                    .max_height(height.max(60.0))
+                    .stick_to_bottom(true)
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        for line in &lines {
+                            ui.colored_label(level_color(line.level), line.to_string());
+                        }
+                    });
+
+                ui.separator();
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut self.input)
+                        .hint_text("Command, e.g. spawn triangle 3")
+                        .desired_width(f32::INFINITY),
+                );
+                if response.has_focus() {
+                    self.recall_history(ui);
+                }
+                if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    self.submit(commands);
+                    response.request_focus();
+                }
+            });
+    }
+
+    fn recall_history(&mut self, ui: &egui::Ui) {
+        let (up, down) = ui.input(|i| {
+            (
+                i.key_pressed(egui::Key::ArrowUp),
+                i.key_pressed(egui::Key::ArrowDown),
+            )
+        });
+        if self.history.is_empty() || !(up || down) {
+            return;
+        }
+        let last = self.history.len() - 1;
+        self.recall = match (self.recall, up) {
+            (None, true) => Some(last),
+            (None, false) => None,
+            (Some(i), true) => Some(i.saturating_sub(1)),
+            (Some(i), false) => (i < last).then_some(i + 1),
+        };
+        self.input = self
+            .recall
+            .map_or(String::new(), |i| self.history[i].clone());
+    }
+
+    /// Queues the typed command; it runs with the rest of the frame's.
+    fn submit(&mut self, commands: &mut CommandRegistry) {
+        let line = std::mem::take(&mut self.input);
+        self.recall = None;
+        let mut words = line.split_whitespace().map(str::to_string);
+        let Some(name) = words.next() else {
+            return;
+        };
+        if self.history.last() != Some(&line) {
+            if self.history.len() == HISTORY {
+                self.history.remove(0);
+            }
+            self.history.push(line.clone());
+        }
+        log::info!("> {line}");
+        match commands.resolve(&name) {
+            Ok(id) => {
+                let id = id.to_string();
+                commands.queue_with_args(&id, words.collect());
+            }
+            Err(e) => log::warn!("{e}"),
+        }
+    }
+}
+
+fn level_color(level: log::Level) -> egui::Color32 {
+    match level {
+        log::Level::Error => egui::Color32::RED,
+        log::Level::Warn => egui::Color32::YELLOW,
+        log::Level::Info => egui::Color32::LIGHT_GRAY,
+        log::Level::Debug | log::Level::Trace => egui::Color32::GRAY,
+    }
+}
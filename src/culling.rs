@@ -0,0 +1,222 @@
+//! CPU culling of the models before the scene pass: the frustum test
+//! through the spatial index, then anything too small on screen to be
+//! worth a draw. Freezing keeps culling from where the camera was, so
+//! flying around shows what was culled and why.
+
+use crate::camera::Camera;
+use crate::debug_draw::DebugDraw;
+use crate::model::Model;
+use crate::spatial::{Aabb, Frustum, SpatialIndex};
+use std::cell::Cell;
+
+const FRUSTUM_CULLED_COLOR: [f32; 4] = [1.0, 0.2, 0.2, 1.0];
+const SIZE_CULLED_COLOR: [f32; 4] = [1.0, 0.6, 0.1, 1.0];
+const FROZEN_FRUSTUM_COLOR: [f32; 4] = [0.3, 0.8, 1.0, 1.0];
+
+/// Counts from the last scene pass.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CullStats {
+    /// Visible models with bounds; models spawned this frame aren't indexed
+    /// yet and are drawn untested.
+    pub tested: u32,
+    pub frustum_culled: u32,
+    pub size_culled: u32,
+    /// Always zero: there's no occlusion culling yet. Kept so exported
+    /// series don't change shape when there is.
+    pub occlusion_culled: u32,
+}
+
+impl CullStats {
+    pub fn drawn(&self) -> u32 {
+        self.tested - self.frustum_culled - self.size_culled - self.occlusion_culled
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Culled {
+    Frustum,
+    Size,
+}
+
+/// Where culling looks from.
+#[derive(Debug, Clone, Copy)]
+pub struct CullView {
+    pub view_proj: glam::Mat4,
+    pub eye: glam::Vec3,
+    /// 1 / tan(fov / 2): screen heights covered by a unit at unit distance.
+    pub focal: f32,
+}
+
+impl CullView {
+    pub fn from_camera(camera: &Camera) -> Self {
+        CullView {
+            view_proj: camera.view_proj(),
+            eye: camera.eye,
+            focal: 1.0 / (camera.fov * 0.5).tan(),
+        }
+    }
+
+    /// Fraction of the screen's height the bounds' sphere covers; 1 or
+    /// more with the eye inside it.
+    fn screen_size(&self, bounds: &Aabb) -> f32 {
+        let center = (bounds.min + bounds.max) * 0.5;
+        let radius = (bounds.max - bounds.min).length() * 0.5;
+        let distance = center.distance(self.eye);
+        if distance <= radius {
+            return 1.0;
+        }
+        radius * self.focal / distance
+    }
+}
+
+#[derive(Default)]
+pub struct Culling {
+    /// Models covering less of the screen's height than this are skipped;
+    /// 0 draws everything in view.
+    pub min_screen_size: f32,
+    /// Set while culling is frozen.
+    pub frozen: Option<CullView>,
+    /// Outlines culled models: red outside the frustum, orange too small.
+    pub show_culled: bool,
+    stats: Cell<CullStats>,
+}
+
+impl Culling {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stats(&self) -> CullStats {
+        self.stats.get()
+    }
+
+    pub fn view(&self, camera: &Camera) -> CullView {
+        self.frozen.unwrap_or_else(|| CullView::from_camera(camera))
+    }
+
+    pub fn toggle_frozen(&mut self, camera: &Camera) {
+        self.frozen = match self.frozen {
+            Some(_) => None,
+            None => Some(CullView::from_camera(camera)),
+        };
+    }
+
+    fn cull(&self, view: &CullView, in_view: bool, bounds: &Aabb) -> Option<Culled> {
+        if !in_view {
+            Some(Culled::Frustum)
+        } else if view.screen_size(bounds) < self.min_screen_size {
+            Some(Culled::Size)
+        } else {
+            None
+        }
+    }
+
+    /// The visible models that pass culling, in order. Records the stats.
+    pub fn visible<'a>(
+        &self,
+        models: &'a [Model],
+        spatial: &SpatialIndex,
+        camera: &Camera,
+    ) -> Vec<&'a Model> {
+        let view = self.view(camera);
+        let in_view = spatial.query_frustum(&Frustum::from_view_proj(view.view_proj));
+        let mut stats = CullStats::default();
+        let visible = models
+            .iter()
+            .filter(|m| m.is_visible())
+            .filter(|m| {
+                // models spawned since the last update aren't indexed yet
+                let Some(bounds) = spatial.bounds(m.id) else {
+                    return true;
+                };
+                stats.tested += 1;
+                match self.cull(&view, in_view.contains(&m.id), &bounds) {
+                    Some(Culled::Frustum) => stats.frustum_culled += 1,
+                    Some(Culled::Size) => stats.size_culled += 1,
+                    None => return true,
+                }
+                false
+            })
+            .collect();
+        self.stats.set(stats);
+        visible
+    }
+
+    /// Outlines the frozen frustum and, with `show_culled`, what was culled.
+    pub fn debug_draw(
+        &self,
+        debug_draw: &mut DebugDraw,
+        models: &[Model],
+        spatial: &SpatialIndex,
+        camera: &Camera,
+    ) {
+        if let Some(frozen) = &self.frozen {
+            draw_frustum(debug_draw, frozen.view_proj);
+        }
+        if !self.show_culled {
+            return;
+        }
+        let view = self.view(camera);
+        let frustum = Frustum::from_view_proj(view.view_proj);
+        for model in models.iter().filter(|m| m.is_visible()) {
+            let Some(bounds) = spatial.bounds(model.id) else {
+                continue;
+            };
+            let color = match self.cull(&view, bounds.intersects_frustum(&frustum), &bounds) {
+                Some(Culled::Frustum) => FRUSTUM_CULLED_COLOR,
+                Some(Culled::Size) => SIZE_CULLED_COLOR,
+                None => continue,
+            };
+            debug_draw.aabb(bounds.min, bounds.max, glam::Mat4::IDENTITY, color);
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, camera: &Camera) {
+        ui.collapsing("Culling", |ui| {
+            let stats = self.stats();
+            egui::Grid::new("cull_stats").show(ui, |ui| {
+                for (label, count) in [
+                    ("Tested", stats.tested),
+                    ("Frustum culled", stats.frustum_culled),
+                    ("Size culled", stats.size_culled),
+                    ("Occlusion culled", stats.occlusion_culled),
+                    ("Drawn", stats.drawn()),
+                ] {
+                    ui.label(label);
+                    ui.label(count.to_string());
+                    ui.end_row();
+                }
+            });
+            ui.add(
+                egui::Slider::new(&mut self.min_screen_size, 0.0..=0.1)
+                    .text("Min screen size")
+                    .custom_formatter(|v, _| format!("{:.1}%", v * 100.0)),
+            );
+            let mut frozen = self.frozen.is_some();
+            if ui.checkbox(&mut frozen, "Freeze culling camera").changed() {
+                self.toggle_frozen(camera);
+            }
+            ui.checkbox(&mut self.show_culled, "Show culled bounds");
+        });
+    }
+}
+
+/// The edges of `view_proj`'s frustum.
+fn draw_frustum(debug_draw: &mut DebugDraw, view_proj: glam::Mat4) {
+    let inverse = view_proj.inverse();
+    let corner = |i: usize| {
+        let ndc = glam::vec3(
+            if i & 1 == 0 { -1.0 } else { 1.0 },
+            if i & 2 == 0 { -1.0 } else { 1.0 },
+            if i & 4 == 0 { -1.0 } else { 1.0 },
+        );
+        inverse.project_point3(ndc)
+    };
+    for i in 0..8 {
+        for bit in [1, 2, 4] {
+            if i & bit == 0 {
+                debug_draw.line(corner(i), corner(i | bit), FROZEN_FRUSTUM_COLOR);
+            }
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use crate::app::State;
+use crate::mesh_builder::{BuilderVertex, MeshBuilder};
+use std::sync::Arc;
+
+const SHADER: &str = r#"
+struct Camera { view_proj: mat4x4<f32> };
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vsMain(@location(0) pos: vec3<f32>, @location(3) color: vec4<f32>) -> VSOut {
+    var out: VSOut;
+    out.pos = camera.view_proj * vec4(pos, 1.0);
+    out.color = color;
+    return out;
+}
+
+@fragment
+fn psMain(in: VSOut) -> @location(0) vec4<f32> {
+    return in.color;
+}
+"#;
+
+struct Label {
+    position: glam::Vec3,
+    text: String,
+    color: [f32; 4],
+}
+
+/// Immediate-mode lines and text labels in world space. Queue them every
+/// frame; lines are drawn over the scene without depth testing and labels
+/// are painted by egui, then both are cleared.
+pub struct DebugDraw {
+    lines: MeshBuilder,
+    labels: Vec<Label>,
+    pipeline: wgpu::RenderPipeline,
+    camera_bind_group: wgpu::BindGroup,
+}
+
+impl DebugDraw {
+    pub fn new(state: &State, camera_buffer: &Arc<wgpu::Buffer>) -> Self {
+        let device = &state.device;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Debug Draw"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let camera_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Debug Draw Camera"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Debug Draw Camera"),
+            layout: &camera_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Debug Draw"),
+            bind_group_layouts: &[&camera_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Debug Draw"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vsMain"),
+                buffers: &[BuilderVertex::layout()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("psMain"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: state.surface_config.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        DebugDraw {
+            lines: MeshBuilder::new(),
+            labels: vec![],
+            pipeline,
+            camera_bind_group,
+        }
+    }
+
+    pub fn line(&mut self, a: glam::Vec3, b: glam::Vec3, color: [f32; 4]) {
+        self.lines.line(a, b, color);
+    }
+
+    /// Three axis-aligned lines crossing at `position`.
+    pub fn cross(&mut self, position: glam::Vec3, size: f32, color: [f32; 4]) {
+        for axis in [glam::Vec3::X, glam::Vec3::Y, glam::Vec3::Z] {
+            let half = axis * size * 0.5;
+            self.line(position - half, position + half, color);
+        }
+    }
+
+    /// The twelve edges of the box `min`..`max`, transformed by `matrix`.
+    pub fn aabb(&mut self, min: glam::Vec3, max: glam::Vec3, matrix: glam::Mat4, color: [f32; 4]) {
+        let corner = |i: usize| {
+            let p = glam::vec3(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            );
+            matrix.transform_point3(p)
+        };
+        for i in 0..8 {
+            // connect each corner to the neighbours that differ in one bit
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    self.line(corner(i), corner(i | bit), color);
+                }
+            }
+        }
+    }
+
+    pub fn text(&mut self, position: glam::Vec3, text: impl Into<String>, color: [f32; 4]) {
+        self.labels.push(Label {
+            position,
+            text: text.into(),
+            color,
+        });
+    }
+
+    /// Drops everything queued this frame without drawing it.
+    pub fn clear(&mut self) {
+        self.lines.clear();
+        self.labels.clear();
+    }
+
+    /// Draws and clears the queued lines over `view` in their own pass.
+    pub fn render(
+        &mut self,
+        state: &State,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+    ) {
+        if self.lines.is_empty() {
+            return;
+        }
+        self.lines.flush(&state.device, &state.queue);
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("debug draw"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                depth_slice: None,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        self.lines.draw_lines(&mut pass);
+        let target = view.texture();
+        state.profiler.record_draws(1, 1);
+        state
+            .profiler
+            .record_target(target.width(), target.height());
+        drop(pass);
+        self.lines.clear();
+    }
+
+    /// Paints and clears the queued labels. `size` is the viewport in
+    /// logical pixels.
+    pub fn labels_ui(&mut self, ctx: &egui::Context, view_proj: glam::Mat4, size: glam::Vec2) {
+        let painter = ctx.layer_painter(egui::LayerId::background());
+        for label in self.labels.drain(..) {
+            let clip = view_proj * label.position.extend(1.0);
+            if clip.w <= 0.0 {
+                continue;
+            }
+            let ndc = clip.truncate() / clip.w;
+            let pos = egui::pos2((ndc.x + 1.0) * 0.5 * size.x, (1.0 - ndc.y) * 0.5 * size.y);
+            let [r, g, b, a] = label.color.map(|c| (c * 255.0) as u8);
+            painter.text(
+                pos,
+                egui::Align2::CENTER_BOTTOM,
+                label.text,
+                egui::FontId::monospace(14.0),
+                egui::Color32::from_rgba_unmultiplied(r, g, b, a),
+            );
+        }
+    }
+}
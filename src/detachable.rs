@@ -0,0 +1,134 @@
+//! An `egui::Window` that can be detached into its own native window, so
+//! panels can go on another monitor and leave the scene unobstructed.
+//! Closing the native window docks the panel again.
+
+use egui::{Context, Id, ViewportBuilder, ViewportId};
+
+pub struct DetachableWindow<'open> {
+    title: String,
+    open: Option<&'open mut bool>,
+    default_open: bool,
+    resizable: bool,
+    vscroll: bool,
+    default_size: Option<egui::Vec2>,
+}
+
+impl<'open> DetachableWindow<'open> {
+    pub fn new(title: impl Into<String>) -> Self {
+        DetachableWindow {
+            title: title.into(),
+            open: None,
+            default_open: true,
+            resizable: true,
+            vscroll: false,
+            default_size: None,
+        }
+    }
+
+    pub fn open(mut self, open: &'open mut bool) -> Self {
+        self.open = Some(open);
+        self
+    }
+
+    /// Whether the docked window starts expanded.
+    pub fn default_open(mut self, default_open: bool) -> Self {
+        self.default_open = default_open;
+        self
+    }
+
+    pub fn resizable(mut self, resizable: bool) -> Self {
+        self.resizable = resizable;
+        self
+    }
+
+    pub fn vscroll(mut self, vscroll: bool) -> Self {
+        self.vscroll = vscroll;
+        self
+    }
+
+    pub fn default_size(mut self, size: impl Into<egui::Vec2>) -> Self {
+        self.default_size = Some(size.into());
+        self
+    }
+
+    /// Returns `None` while the window is closed or collapsed.
+    pub fn show<R>(
+        self,
+        ctx: &Context,
+        add_contents: impl FnOnce(&mut egui::Ui) -> R,
+    ) -> Option<R> {
+        if self.open.as_deref() == Some(&false) {
+            return None;
+        }
+        let id = Id::new(("detached", &self.title));
+        let detached = ctx.data(|d| d.get_temp::<bool>(id)).unwrap_or(false);
+        // with embedded viewports there's nowhere to detach to
+        if detached && !ctx.embed_viewports() {
+            return self.show_detached(ctx, id, add_contents);
+        }
+
+        let mut window = egui::Window::new(&self.title)
+            .default_open(self.default_open)
+            .resizable(self.resizable)
+            .vscroll(self.vscroll);
+        if let Some(open) = self.open {
+            window = window.open(open);
+        }
+        if let Some(size) = self.default_size {
+            window = window.default_size(size);
+        }
+        window
+            .show(ctx, |ui| {
+                if !ctx.embed_viewports()
+                    && ui
+                        .small_button("⧉ Detach")
+                        .on_hover_text("Open in its own window")
+                        .clicked()
+                {
+                    ctx.data_mut(|d| d.insert_temp(id, true));
+                }
+                add_contents(ui)
+            })
+            .and_then(|response| response.inner)
+    }
+
+    fn show_detached<R>(
+        self,
+        ctx: &Context,
+        id: Id,
+        add_contents: impl FnOnce(&mut egui::Ui) -> R,
+    ) -> Option<R> {
+        let size = self.default_size.unwrap_or(egui::vec2(420.0, 480.0));
+        let builder = ViewportBuilder::default()
+            .with_title(&self.title)
+            .with_inner_size(size);
+        let vscroll = self.vscroll;
+        let mut add_contents = Some(add_contents);
+        let (docked, inner) =
+            ctx.show_viewport_immediate(ViewportId::from_hash_of(id), builder, |ctx, _| {
+                let mut docked = ctx.input(|i| i.viewport().close_requested());
+                egui::TopBottomPanel::top("detached_bar").show(ctx, |ui| {
+                    docked |= ui
+                        .small_button("Dock")
+                        .on_hover_text("Move back into the main window")
+                        .clicked();
+                });
+                let inner = egui::CentralPanel::default().show(ctx, |ui| {
+                    let add_contents = add_contents.take()?;
+                    if vscroll {
+                        egui::ScrollArea::vertical()
+                            .show(ui, |ui| add_contents(ui))
+                            .inner
+                            .into()
+                    } else {
+                        Some(add_contents(ui))
+                    }
+                });
+                (docked, inner.inner)
+            });
+        if docked {
+            ctx.data_mut(|d| d.insert_temp(id, false));
+        }
+        inner
+    }
+}
@@ -0,0 +1,324 @@
+//! Crash reports and GPU error tracking: a panic hook that writes what we
+//! know about the GPU, the loaded assets and the most recent log lines to
+//! `crash-<time>.txt`, plus per-scope wgpu errors for the error console and
+//! recent log lines for the log console.
+
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::{Mutex, OnceLock};
+
+const LOG_LINES: usize = 1000;
+const GPU_ERRORS: usize = 32;
+
+#[derive(Default)]
+struct Report {
+    adapter: Option<wgpu::AdapterInfo>,
+    surface_config: Option<wgpu::SurfaceConfiguration>,
+    assets: Vec<String>,
+    log: VecDeque<LogLine>,
+    /// Lines logged so far, including ones that have been dropped.
+    log_total: u64,
+    gpu_errors: VecDeque<GpuError>,
+}
+
+/// A validation or out-of-memory error and the scope label it was caught in.
+#[derive(Debug, Clone)]
+pub struct GpuError {
+    pub label: String,
+    pub message: String,
+    /// How many times it happened; per-pass errors tend to repeat every frame.
+    pub count: u32,
+}
+
+/// A captured log record.
+#[derive(Debug, Clone)]
+pub struct LogLine {
+    /// Position in the whole log, counting from 0.
+    pub index: u64,
+    pub level: log::Level,
+    pub target: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for LogLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "[{} {}] {}", self.level, self.target, self.message)
+    }
+}
+
+fn report() -> &'static Mutex<Report> {
+    static REPORT: OnceLock<Mutex<Report>> = OnceLock::new();
+    REPORT.get_or_init(Default::default)
+}
+
+fn with_report(f: impl FnOnce(&mut Report)) {
+    // a panic while the lock was held must not stop the crash report
+    let mut report = report().lock().unwrap_or_else(|e| e.into_inner());
+    f(&mut report);
+}
+
+/// Window listing recorded GPU errors; only shown once there are any.
+pub fn error_console_ui(ctx: &egui::Context) {
+    let errors = gpu_errors();
+    if errors.is_empty() {
+        return;
+    }
+    egui::Window::new("GPU Errors")
+        .resizable(true)
+        .vscroll(true)
+        .show(ctx, |ui| {
+            if ui.button("Clear").clicked() {
+                clear_gpu_errors();
+            }
+            for error in &errors {
+                ui.separator();
+                ui.colored_label(
+                    egui::Color32::RED,
+                    format!("{} (x{})", error.label, error.count),
+                );
+                ui.label(&error.message);
+            }
+        });
+}
+
+/// Forwards to env_logger and keeps the last `LOG_LINES` lines for reports
+/// and the log console.
+struct Logger {
+    inner: env_logger::Logger,
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.inner.matches(record) {
+            return;
+        }
+        self.inner.log(record);
+        let message = record.args().to_string();
+        with_report(|r| {
+            if r.log.len() == LOG_LINES {
+                r.log.pop_front();
+            }
+            r.log.push_back(LogLine {
+                index: r.log_total,
+                level: record.level(),
+                target: record.target().to_string(),
+                message,
+            });
+            r.log_total += 1;
+        });
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Sets up logging (`RUST_LOG` overrides the default filter) and the panic
+/// hook. Call once at the start of `main`.
+pub fn init() {
+    init_logger();
+
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let path = format!(
+            "crash-{}.txt",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs())
+        );
+        match std::fs::write(&path, crash_report(&info.to_string())) {
+            Ok(()) => eprintln!("Wrote crash report to {path}"),
+            Err(e) => eprintln!("Failed to write crash report {path}: {e}"),
+        }
+        previous(info);
+    }));
+}
+
+fn init_logger() {
+    let inner = env_logger::Builder::from_env(
+        env_logger::Env::default().default_filter_or("warn,rust_graphics_sandbox=info"),
+    )
+    .build();
+    log::set_max_level(inner.filter());
+    let _ = log::set_boxed_logger(Box::new(Logger { inner }));
+}
+
+pub fn set_adapter_info(info: wgpu::AdapterInfo) {
+    with_report(|r| r.adapter = Some(info));
+}
+
+pub fn set_surface_config(config: &wgpu::SurfaceConfiguration) {
+    with_report(|r| r.surface_config = Some(config.clone()));
+}
+
+pub fn record_asset(path: &str) {
+    with_report(|r| r.assets.push(path.to_string()));
+}
+
+pub fn record_gpu_error(label: &str, error: &wgpu::Error) {
+    let message = error.to_string();
+    let mut new = false;
+    with_report(|r| {
+        if let Some(existing) = r
+            .gpu_errors
+            .iter_mut()
+            .find(|e| e.label == label && e.message == message)
+        {
+            existing.count += 1;
+            return;
+        }
+        new = true;
+        if r.gpu_errors.len() == GPU_ERRORS {
+            r.gpu_errors.pop_front();
+        }
+        r.gpu_errors.push_back(GpuError {
+            label: label.to_string(),
+            message: message.clone(),
+            count: 1,
+        });
+    });
+    // not under the lock: the logger takes it too
+    if new {
+        log::error!("GPU error in {label}: {message}");
+    }
+}
+
+pub fn gpu_errors() -> Vec<GpuError> {
+    let mut errors = vec![];
+    with_report(|r| errors = r.gpu_errors.iter().cloned().collect());
+    errors
+}
+
+/// The captured log lines logged after the first `since`, oldest first.
+pub fn log_lines(since: u64) -> Vec<LogLine> {
+    let mut lines = vec![];
+    with_report(|r| lines = r.log.iter().filter(|l| l.index >= since).cloned().collect());
+    lines
+}
+
+/// How many lines have been logged so far.
+pub fn log_total() -> u64 {
+    let mut total = 0;
+    with_report(|r| total = r.log_total);
+    total
+}
+
+pub fn clear_gpu_errors() {
+    with_report(|r| r.gpu_errors.clear());
+}
+
+/// Logs uncaptured validation errors instead of wgpu's default panic, so
+/// they end up in the crash report with everything else.
+pub fn install_device_error_handler(device: &wgpu::Device) {
+    device.on_uncaptured_error(std::sync::Arc::new(|error| {
+        record_gpu_error("uncaptured", &error);
+    }));
+}
+
+/// Runs `f` inside validation and out-of-memory error scopes and records
+/// anything they catch under `label`.
+pub fn error_scope<T>(device: &wgpu::Device, label: &str, f: impl FnOnce() -> T) -> T {
+    device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let result = f();
+    for _ in 0..2 {
+        if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+            record_gpu_error(label, &error);
+        }
+    }
+    result
+}
+
+fn crash_report(panic: &str) -> String {
+    let backtrace = std::backtrace::Backtrace::force_capture();
+    let mut out = String::new();
+    let _ = writeln!(out, "{panic}\n");
+    // the panic may have come from inside `with_report` on this thread
+    let report = match report().try_lock() {
+        Ok(report) => report,
+        Err(std::sync::TryLockError::Poisoned(e)) => e.into_inner(),
+        Err(std::sync::TryLockError::WouldBlock) => {
+            let _ = writeln!(out, "Diagnostics unavailable (report was locked)");
+            let _ = writeln!(out, "\nBacktrace:\n{backtrace}");
+            return out;
+        }
+    };
+    match &report.adapter {
+        Some(a) => {
+            let _ = writeln!(
+                out,
+                "Adapter: {} ({:?}, {:?})",
+                a.name, a.device_type, a.backend
+            );
+            let _ = writeln!(out, "Driver: {} {}", a.driver, a.driver_info);
+        }
+        None => {
+            let _ = writeln!(out, "Adapter: not created yet");
+        }
+    }
+    if let Some(c) = &report.surface_config {
+        let _ = writeln!(
+            out,
+            "Surface: {}x{} {:?} {:?}",
+            c.width, c.height, c.format, c.present_mode
+        );
+    }
+
+    let _ = writeln!(out, "\nAssets:");
+    for asset in &report.assets {
+        let _ = writeln!(out, "  {asset}");
+    }
+    let _ = writeln!(out, "\nGPU errors:");
+    for error in &report.gpu_errors {
+        let _ = writeln!(
+            out,
+            "  {}: {} (x{})",
+            error.label, error.message, error.count
+        );
+    }
+    let _ = writeln!(out, "\nLast {} log lines:", report.log.len());
+    for line in &report.log {
+        let _ = writeln!(out, "  {line}");
+    }
+    let _ = writeln!(out, "\nBacktrace:\n{backtrace}");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validation(description: &str) -> wgpu::Error {
+        wgpu::Error::Validation {
+            source: description.into(),
+            description: description.to_string(),
+        }
+    }
+
+    #[test]
+    fn gpu_errors_log_through_the_installed_logger() {
+        init_logger();
+        let since = log_total();
+        record_gpu_error("diagnostics test", &validation("first"));
+        record_gpu_error("diagnostics test", &validation("second"));
+        record_gpu_error("diagnostics test", &validation("first"));
+        let errors: Vec<_> = gpu_errors()
+            .into_iter()
+            .filter(|e| e.label == "diagnostics test")
+            .map(|e| (e.message, e.count))
+            .collect();
+        assert_eq!(
+            errors,
+            [("first".to_string(), 2), ("second".to_string(), 1)]
+        );
+        let logged = log_lines(since)
+            .into_iter()
+            .filter(|l| l.message.starts_with("GPU error in diagnostics test"))
+            .count();
+        assert_eq!(logged, 2);
+    }
+}
@@ -1,13 +1,44 @@
-use egui::Context;
-use egui_wgpu::wgpu::{CommandEncoder, Device, Queue, StoreOp, TextureFormat, TextureView};
+use egui::{Context, ImmediateViewport, ViewportBuilder, ViewportId};
+use egui_wgpu::wgpu::{CommandEncoder, Device, Queue, StoreOp, TextureView};
 use egui_wgpu::{wgpu, Renderer, RendererOptions, ScreenDescriptor};
 use egui_winit::State;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
 use winit::event::WindowEvent;
-use winit::window::Window;
+use winit::event_loop::ActiveEventLoop;
+use winit::window::{Window, WindowId};
+
+/// A native window showing one of egui's immediate viewports.
+struct ViewportWindow {
+    window: Arc<Window>,
+    surface: wgpu::Surface<'static>,
+    config: wgpu::SurfaceConfiguration,
+    state: State,
+    /// Shown during the current root pass; windows that weren't are closed
+    /// when it ends.
+    used: bool,
+}
+
+/// What the immediate viewport renderer needs from outside the root pass.
+struct Viewports {
+    instance: wgpu::Instance,
+    adapter: wgpu::Adapter,
+    device: Device,
+    queue: Queue,
+    /// What the shared renderer draws into; viewport surfaces need it too.
+    format: wgpu::TextureFormat,
+    windows: HashMap<ViewportId, ViewportWindow>,
+    /// Viewports shown before they had a window; opened after the frame,
+    /// once there's an event loop to open them with.
+    pending: Vec<(ViewportId, ViewportBuilder)>,
+}
 
 pub struct EguiRenderer {
     state: State,
-    renderer: Renderer,
+    renderer: Rc<RefCell<Renderer>>,
+    viewports: Rc<RefCell<Viewports>>,
     frame_started: bool,
 }
 
@@ -17,11 +48,13 @@ impl EguiRenderer {
     }
 
     pub fn new(
-        device: &Device,
-        output_color_format: TextureFormat,
+        instance: &wgpu::Instance,
+        state: &crate::app::State,
         window: &Window,
     ) -> EguiRenderer {
         let egui_context = Context::default();
+        // Android has the one window
+        egui_context.set_embed_viewports(cfg!(target_os = "android"));
 
         let egui_state = egui_winit::State::new(
             egui_context,
@@ -31,17 +64,127 @@ impl EguiRenderer {
             None,
             Some(2 * 1024), // default dimension is 2048
         );
-        let egui_renderer = Renderer::new(device, output_color_format, RendererOptions::default());
+        let renderer = Rc::new(RefCell::new(Renderer::new(
+            &state.device,
+            state.ui_format(),
+            RendererOptions::default(),
+        )));
+        let viewports = Rc::new(RefCell::new(Viewports {
+            instance: instance.clone(),
+            adapter: state.adapter.clone(),
+            device: state.device.clone(),
+            queue: state.queue.clone(),
+            format: state.ui_format(),
+            windows: HashMap::new(),
+            pending: vec![],
+        }));
+        let (r, v) = (renderer.clone(), viewports.clone());
+        Context::set_immediate_viewport_renderer(move |ctx, viewport| {
+            render_viewport(ctx, viewport, &r, &v);
+        });
 
         EguiRenderer {
             state: egui_state,
-            renderer: egui_renderer,
+            renderer,
+            viewports,
             frame_started: false,
         }
     }
 
-    pub fn handle_input(&mut self, window: &Window, event: &WindowEvent) {
-        let _ = self.state.on_window_event(window, event);
+    /// Returns true when egui consumed the event and it shouldn't reach the scene.
+    pub fn handle_input(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        self.state.on_window_event(window, event).consumed
+    }
+
+    /// Passes an event for a detached viewport's window to egui. Returns
+    /// false if `id` isn't one of them.
+    pub fn handle_viewport_input(&mut self, id: WindowId, event: &WindowEvent) -> bool {
+        let mut viewports = self.viewports.borrow_mut();
+        let Some((&viewport, w)) = viewports
+            .windows
+            .iter_mut()
+            .find(|(_, w)| w.window.id() == id)
+        else {
+            return false;
+        };
+        match event {
+            // the viewport's ui decides what closing means
+            WindowEvent::CloseRequested => w
+                .state
+                .egui_input_mut()
+                .viewports
+                .entry(viewport)
+                .or_default()
+                .events
+                .push(egui::ViewportEvent::Close),
+            _ => {
+                let _ = w.state.on_window_event(&w.window, event);
+            }
+        }
+        true
+    }
+
+    /// Opens windows for viewports first shown this frame.
+    pub fn open_pending_viewports(&mut self, event_loop: &ActiveEventLoop) {
+        let ctx = self.context().clone();
+        let mut viewports = self.viewports.borrow_mut();
+        for (id, builder) in std::mem::take(&mut viewports.pending) {
+            if viewports.windows.contains_key(&id) {
+                continue;
+            }
+            let window = match egui_winit::create_window(&ctx, event_loop, &builder) {
+                Ok(window) => Arc::new(window),
+                Err(e) => {
+                    log::warn!("Failed to open a window for {id:?}: {e}");
+                    continue;
+                }
+            };
+            let surface = match viewports.instance.create_surface(window.clone()) {
+                Ok(surface) => surface,
+                Err(e) => {
+                    log::warn!("Failed to create a surface for {id:?}: {e}");
+                    continue;
+                }
+            };
+            let capabilities = surface.get_capabilities(&viewports.adapter);
+            let format = viewports.format;
+            if !capabilities.formats.contains(&format) {
+                // it would fail the same way every frame; keep panels docked
+                log::warn!("No {format:?} surface for {id:?}; panels can't be detached");
+                ctx.set_embed_viewports(true);
+                continue;
+            }
+            let size = window.inner_size();
+            let config = wgpu::SurfaceConfiguration {
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                format,
+                width: size.width.max(1),
+                height: size.height.max(1),
+                present_mode: wgpu::PresentMode::Fifo,
+                desired_maximum_frame_latency: 2,
+                alpha_mode: capabilities.alpha_modes[0],
+                view_formats: vec![],
+            };
+            surface.configure(&viewports.device, &config);
+            let state = State::new(
+                ctx.clone(),
+                id,
+                &window,
+                Some(window.scale_factor() as f32),
+                None,
+                Some(2 * 1024),
+            );
+            viewports.windows.insert(
+                id,
+                ViewportWindow {
+                    window,
+                    surface,
+                    config,
+                    state,
+                    used: true,
+                },
+            );
+        }
     }
 
     pub fn ppp(&mut self, v: f32) {
@@ -54,43 +197,66 @@ impl EguiRenderer {
         self.frame_started = true;
     }
 
-    pub fn end_frame_and_draw(
-        &mut self,
-        device: &Device,
-        queue: &Queue,
-        encoder: &mut CommandEncoder,
-        window: &Window,
-        window_surface_view: &TextureView,
-        screen_descriptor: &ScreenDescriptor,
-    ) {
+    /// Ends the egui pass and tessellates it. Nothing is drawn until the
+    /// frame is given to `draw`, so the UI can go anywhere in the frame's
+    /// passes.
+    pub fn end_frame(&mut self, window: &Window, screen_descriptor: ScreenDescriptor) -> UiFrame {
         if !self.frame_started {
-            panic!("begin_frame must be called before end_frame_and_draw can be called!");
+            panic!("begin_frame must be called before end_frame can be called!");
         }
 
         self.ppp(screen_descriptor.pixels_per_point);
 
         let full_output = self.state.egui_ctx().end_pass();
+        // viewports not shown this pass were closed or docked
+        self.viewports
+            .borrow_mut()
+            .windows
+            .retain(|_, w| std::mem::take(&mut w.used));
 
         self.state
             .handle_platform_output(window, full_output.platform_output);
 
-        let tris = self
+        let primitives = self
             .state
             .egui_ctx()
             .tessellate(full_output.shapes, self.state.egui_ctx().pixels_per_point());
-        for (id, image_delta) in &full_output.textures_delta.set {
-            self.renderer
-                .update_texture(device, queue, *id, image_delta);
+        self.frame_started = false;
+        UiFrame {
+            primitives,
+            textures: full_output.textures_delta,
+            screen_descriptor,
         }
-        self.renderer
-            .update_buffers(device, queue, encoder, &tris, screen_descriptor);
+    }
+
+    /// The UI overlay pass: draws `frame` into `target`, which is loaded
+    /// or cleared first according to `load`. Returns the primitives drawn.
+    pub fn draw(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        frame: UiFrame,
+        target: &TextureView,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) -> u32 {
+        let UiFrame {
+            primitives,
+            textures,
+            screen_descriptor,
+        } = frame;
+        let mut renderer = self.renderer.borrow_mut();
+        for (id, image_delta) in &textures.set {
+            renderer.update_texture(device, queue, *id, image_delta);
+        }
+        renderer.update_buffers(device, queue, encoder, &primitives, &screen_descriptor);
         let rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: window_surface_view,
+                view: target,
                 depth_slice: None,
                 resolve_target: None,
                 ops: egui_wgpu::wgpu::Operations {
-                    load: egui_wgpu::wgpu::LoadOp::Load,
+                    load,
                     store: StoreOp::Store,
                 },
             })],
@@ -100,12 +266,135 @@ impl EguiRenderer {
             occlusion_query_set: None,
         });
 
-        self.renderer
-            .render(&mut rpass.forget_lifetime(), &tris, screen_descriptor);
-        for x in &full_output.textures_delta.free {
-            self.renderer.free_texture(x)
+        renderer.render(
+            &mut rpass.forget_lifetime(),
+            &primitives,
+            &screen_descriptor,
+        );
+        for x in &textures.free {
+            renderer.free_texture(x)
         }
+        primitives.len() as u32
+    }
+}
 
-        self.frame_started = false;
+/// The main window's UI for one frame, tessellated by `end_frame` and
+/// waiting to be drawn.
+pub struct UiFrame {
+    primitives: Vec<egui::ClippedPrimitive>,
+    textures: egui::TexturesDelta,
+    screen_descriptor: ScreenDescriptor,
+}
+
+/// Runs and draws an immediate viewport into its own window, from inside
+/// the root pass. A viewport without a window yet is queued for one and
+/// drawn from the next frame.
+fn render_viewport(
+    ctx: &Context,
+    viewport: ImmediateViewport,
+    renderer: &RefCell<Renderer>,
+    viewports: &RefCell<Viewports>,
+) {
+    let ImmediateViewport {
+        ids,
+        builder,
+        mut viewport_ui_cb,
+    } = viewport;
+    // out of the map while its ui runs, which may show viewports of its own
+    let Some(mut w) = viewports.borrow_mut().windows.remove(&ids.this) else {
+        viewports.borrow_mut().pending.push((ids.this, builder));
+        // egui expects the ui to run regardless; nothing to draw it into yet
+        let mut input = egui::RawInput {
+            viewport_id: ids.this,
+            ..Default::default()
+        };
+        input.viewports.entry(ids.this).or_default().parent = Some(ids.parent);
+        let output = ctx.run(input, |ctx| viewport_ui_cb(ctx));
+        let v = viewports.borrow();
+        let mut renderer = renderer.borrow_mut();
+        for (id, image_delta) in &output.textures_delta.set {
+            renderer.update_texture(&v.device, &v.queue, *id, image_delta);
+        }
+        for id in &output.textures_delta.free {
+            renderer.free_texture(id);
+        }
+        return;
+    };
+    w.used = true;
+
+    let mut input = w.state.take_egui_input(&w.window);
+    let info = input.viewports.entry(ids.this).or_default();
+    info.parent = Some(ids.parent);
+    egui_winit::update_viewport_info(info, ctx, &w.window, false);
+    let output = ctx.run(input, |ctx| viewport_ui_cb(ctx));
+    w.state
+        .handle_platform_output(&w.window, output.platform_output);
+
+    let v = viewports.borrow();
+    let size = w.window.inner_size();
+    if size.width > 0
+        && size.height > 0
+        && (size.width, size.height) != (w.config.width, w.config.height)
+    {
+        w.config.width = size.width;
+        w.config.height = size.height;
+        w.surface.configure(&v.device, &w.config);
+    }
+    let mut renderer = renderer.borrow_mut();
+    // textures are shared, so this pass's changes apply to every viewport
+    for (id, image_delta) in &output.textures_delta.set {
+        renderer.update_texture(&v.device, &v.queue, *id, image_delta);
+    }
+    let frame = match w.surface.get_current_texture() {
+        Ok(frame) => Some(frame),
+        Err(wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost) => {
+            w.surface.configure(&v.device, &w.config);
+            None
+        }
+        Err(e) => {
+            log::warn!("Failed to draw viewport {:?}: {e}", ids.this);
+            None
+        }
+    };
+    if let Some(frame) = frame {
+        let tris = ctx.tessellate(output.shapes, output.pixels_per_point);
+        let screen_descriptor = ScreenDescriptor {
+            size_in_pixels: [w.config.width, w.config.height],
+            pixels_per_point: output.pixels_per_point,
+        };
+        let view = frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = v
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("egui viewport"),
+            });
+        renderer.update_buffers(&v.device, &v.queue, &mut encoder, &tris, &screen_descriptor);
+        {
+            let rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    depth_slice: None,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                label: Some("egui viewport render pass"),
+                occlusion_query_set: None,
+            });
+            renderer.render(&mut rpass.forget_lifetime(), &tris, &screen_descriptor);
+        }
+        v.queue.submit(Some(encoder.finish()));
+        frame.present();
+    }
+    for id in &output.textures_delta.free {
+        renderer.free_texture(id);
     }
+    drop(v);
+    viewports.borrow_mut().windows.insert(ids.this, w);
 }
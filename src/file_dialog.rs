@@ -0,0 +1,133 @@
+use std::path::{Path, PathBuf};
+
+/// In-app file picker drawn with egui, for builds without a native dialog.
+pub struct FileDialog {
+    title: String,
+    /// Lowercase, without the dot. Empty shows every file.
+    extensions: Vec<String>,
+    dir: PathBuf,
+    selected: Option<PathBuf>,
+    open: bool,
+}
+
+impl FileDialog {
+    pub fn new(title: &str, extensions: &[&str]) -> Self {
+        FileDialog {
+            title: title.to_string(),
+            extensions: extensions.iter().map(|e| e.to_lowercase()).collect(),
+            dir: std::env::current_dir().unwrap_or_default(),
+            selected: None,
+            open: false,
+        }
+    }
+
+    pub fn set_extensions(&mut self, extensions: &[&str]) {
+        self.extensions = extensions.iter().map(|e| e.to_lowercase()).collect();
+    }
+
+    pub fn open(&mut self) {
+        self.open = true;
+        self.selected = None;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Draws the dialog while it's open. Returns the chosen file on the
+    /// frame the user confirms it, closing the dialog.
+    pub fn show(&mut self, ctx: &egui::Context) -> Option<PathBuf> {
+        if !self.open {
+            return None;
+        }
+        let mut chosen = None;
+        let mut open = true;
+        egui::Window::new(&self.title)
+            .open(&mut open)
+            .collapsible(false)
+            .default_size([420.0, 360.0])
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("⬆").on_hover_text("Parent folder").clicked() {
+                        if let Some(parent) = self.dir.parent() {
+                            self.dir = parent.to_path_buf();
+                        }
+                    }
+                    ui.label(self.dir.display().to_string());
+                });
+                ui.separator();
+
+                let (dirs, files) = self.list();
+                egui::ScrollArea::vertical()
+                    .max_height(260.0)
+                    .show(ui, |ui| {
+                        for dir in dirs {
+                            if ui
+                                .selectable_label(false, format!("📁 {}", name(&dir)))
+                                .clicked()
+                            {
+                                self.dir = dir;
+                                self.selected = None;
+                            }
+                        }
+                        for file in files {
+                            let selected = self.selected.as_ref() == Some(&file);
+                            let response = ui.selectable_label(selected, name(&file));
+                            if response.double_clicked() {
+                                chosen = Some(file.clone());
+                            } else if response.clicked() {
+                                self.selected = Some(file);
+                            }
+                        }
+                    });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    let can_open = self.selected.is_some();
+                    if ui
+                        .add_enabled(can_open, egui::Button::new("Open"))
+                        .clicked()
+                    {
+                        chosen = self.selected.clone();
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.open = false;
+                    }
+                });
+            });
+
+        if !open || chosen.is_some() {
+            self.open = false;
+        }
+        chosen
+    }
+
+    /// Subfolders and matching files of the current folder, sorted by name.
+    fn list(&self) -> (Vec<PathBuf>, Vec<PathBuf>) {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return (vec![], vec![]);
+        };
+        let (mut dirs, mut files): (Vec<PathBuf>, Vec<PathBuf>) = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| !name(p).starts_with('.'))
+            .partition(|p| p.is_dir());
+        files.retain(|p| self.matches(p));
+        dirs.sort();
+        files.sort();
+        (dirs, files)
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        self.extensions.is_empty()
+            || path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| self.extensions.contains(&e.to_lowercase()))
+    }
+}
+
+fn name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
@@ -0,0 +1,512 @@
+//! A/B comparison of two captured frames: capture one, change whatever
+//! render setting is being judged, capture the other, then wipe between
+//! them or see where they differ as a heatmap. The heatmap and its
+//! statistics come from a compute shader over both captures.
+
+use crate::app::State;
+use crate::headless::read_texture;
+use crate::plugin::{Plugin, PluginContext};
+use crate::world::World;
+use wgpu::util::DeviceExt;
+
+const SHADER: &str = r#"
+struct Params {
+    gain: f32,
+    threshold: f32,
+    _pad: vec2<f32>,
+};
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var a: texture_2d<f32>;
+@group(0) @binding(2) var b: texture_2d<f32>;
+@group(0) @binding(3) var heatmap: texture_storage_2d<rgba8unorm, write>;
+
+// differences in 1/255ths
+struct Stats {
+    sum: atomic<u32>,
+    max: atomic<u32>,
+    changed: atomic<u32>,
+};
+@group(0) @binding(4) var<storage, read_write> stats: Stats;
+
+// blue, magenta, orange, white
+fn ramp(x: f32) -> vec3<f32> {
+    let c0 = vec3(0.1, 0.1, 0.8);
+    let c1 = vec3(0.9, 0.1, 0.6);
+    let c2 = vec3(1.0, 0.6, 0.1);
+    let c3 = vec3(1.0, 1.0, 1.0);
+    if (x < 1.0 / 3.0) {
+        return mix(c0, c1, x * 3.0);
+    }
+    if (x < 2.0 / 3.0) {
+        return mix(c1, c2, x * 3.0 - 1.0);
+    }
+    return mix(c2, c3, x * 3.0 - 2.0);
+}
+
+@compute @workgroup_size(8, 8)
+fn csMain(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(a);
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+    let p = vec2<i32>(id.xy);
+    let color_b = textureLoad(b, p, 0).rgb;
+    let d = abs(textureLoad(a, p, 0).rgb - color_b);
+    let diff = max(d.r, max(d.g, d.b));
+    let steps = u32(round(diff * 255.0));
+    atomicAdd(&stats.sum, steps);
+    atomicMax(&stats.max, steps);
+
+    // unchanged pixels show B, dimmed, so differences have context
+    var color = vec3(dot(color_b, vec3(0.2126, 0.7152, 0.0722)) * 0.25);
+    if (diff > params.threshold) {
+        atomicAdd(&stats.changed, 1u);
+        color = ramp(clamp(diff * params.gain, 0.0, 1.0));
+    }
+    textureStore(heatmap, p, vec4(color, 1.0));
+}
+"#;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ParamsUniform {
+    gain: f32,
+    threshold: f32,
+    _pad: [f32; 2],
+}
+
+/// How far apart two captures are; differences are the largest change
+/// in any channel, 0..1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiffStats {
+    pub mean: f32,
+    pub max: f32,
+    /// Fraction of pixels over the threshold.
+    pub changed: f32,
+}
+
+/// Compute pipeline that writes the heatmap of two same-sized textures.
+pub struct Differ {
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl Differ {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Frame Diff"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Frame Diff"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                texture_entry(1),
+                texture_entry(2),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: wgpu::TextureFormat::Rgba8Unorm,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Frame Diff"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Frame Diff"),
+            layout: Some(&layout),
+            module: &module,
+            entry_point: Some("csMain"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        Differ {
+            pipeline,
+            bind_group_layout,
+        }
+    }
+
+    /// Returns the heatmap, `Rgba8Unorm` and readable, and the statistics,
+    /// blocking until the GPU is done.
+    pub fn diff(
+        &self,
+        state: &State,
+        a: &wgpu::Texture,
+        b: &wgpu::Texture,
+        gain: f32,
+        threshold: f32,
+    ) -> (wgpu::Texture, DiffStats) {
+        let device = &state.device;
+        let size = a.size();
+        let heatmap = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Frame Diff"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Frame Diff Params"),
+            contents: bytemuck::cast_slice(&[ParamsUniform {
+                gain,
+                threshold,
+                _pad: [0.0; 2],
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let stats = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Frame Diff Stats"),
+            contents: bytemuck::cast_slice(&[0u32; 3]),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        });
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Frame Diff Stats Readback"),
+            size: stats.size(),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let view = |t: &wgpu::Texture| t.create_view(&wgpu::TextureViewDescriptor::default());
+        let (a_view, b_view, heatmap_view) = (view(a), view(b), view(&heatmap));
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Frame Diff"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&a_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&b_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&heatmap_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: stats.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Frame Diff"),
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Frame Diff"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(size.width.div_ceil(8), size.height.div_ceil(8), 1);
+        }
+        encoder.copy_buffer_to_buffer(&stats, 0, &readback, 0, stats.size());
+        state.queue.submit(Some(encoder.finish()));
+
+        let slice = readback.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |r| {
+            r.expect("Failed to map frame diff stats")
+        });
+        device
+            .poll(wgpu::PollType::wait_indefinitely())
+            .expect("Failed to wait for frame diff");
+        let [sum, max, changed] = {
+            let data = slice.get_mapped_range();
+            let values: &[u32] = bytemuck::cast_slice(&data);
+            [values[0], values[1], values[2]]
+        };
+        readback.unmap();
+
+        let pixels = (size.width * size.height).max(1) as f32;
+        let stats = DiffStats {
+            mean: sum as f32 / 255.0 / pixels,
+            max: max as f32 / 255.0,
+            changed: changed as f32 / pixels,
+        };
+        (heatmap, stats)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareView {
+    /// A left of the divider, B right of it.
+    Split,
+    Difference,
+}
+
+struct Frame {
+    texture: wgpu::Texture,
+    preview: egui::TextureHandle,
+}
+
+pub struct FrameCompare {
+    pub view: CompareView,
+    /// Where the divider sits, 0..1 across the frame.
+    pub split: f32,
+    /// Scales differences before they're colored.
+    pub gain: f32,
+    /// Differences at or below this, 0..1, count as unchanged.
+    pub threshold: f32,
+    /// What each capture shows, e.g. the setting that was on.
+    pub labels: [String; 2],
+    frames: [Option<Frame>; 2],
+    /// The slot waiting for `World::captured_frame`.
+    pending: Option<usize>,
+    heatmap: Option<(egui::TextureHandle, DiffStats)>,
+    differ: Option<Differ>,
+    dirty: bool,
+}
+
+impl Default for FrameCompare {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameCompare {
+    pub fn new() -> Self {
+        FrameCompare {
+            view: CompareView::Split,
+            split: 0.5,
+            gain: 4.0,
+            threshold: 1.0 / 255.0,
+            labels: ["A".to_string(), "B".to_string()],
+            frames: [None, None],
+            pending: None,
+            heatmap: None,
+            differ: None,
+            dirty: false,
+        }
+    }
+
+    /// The difference between the captures, once both exist.
+    pub fn stats(&self) -> Option<DiffStats> {
+        self.heatmap.as_ref().map(|(_, stats)| *stats)
+    }
+
+    fn receive(&mut self, ctx: &egui::Context, state: &State, world: &mut World) {
+        let Some(slot) = self.pending else {
+            return;
+        };
+        let Some(texture) = world.captured_frame.take() else {
+            return;
+        };
+        let capture = read_texture(&state.device, &state.queue, &texture);
+        let image = egui::ColorImage::from_rgba_unmultiplied(
+            [capture.width as usize, capture.height as usize],
+            &capture.pixels,
+        );
+        let name = ["frame compare a", "frame compare b"][slot];
+        let preview = ctx.load_texture(name, image, egui::TextureOptions::LINEAR);
+        self.frames[slot] = Some(Frame { texture, preview });
+        self.pending = None;
+        self.dirty = true;
+    }
+
+    fn rediff(&mut self, ctx: &egui::Context, state: &State) {
+        self.heatmap = None;
+        let (Some(differ), [Some(a), Some(b)]) = (&self.differ, &self.frames) else {
+            return;
+        };
+        if a.texture.size() != b.texture.size() {
+            return;
+        }
+        let (texture, stats) =
+            differ.diff(state, &a.texture, &b.texture, self.gain, self.threshold);
+        let capture = read_texture(&state.device, &state.queue, &texture);
+        let image = egui::ColorImage::from_rgba_unmultiplied(
+            [capture.width as usize, capture.height as usize],
+            &capture.pixels,
+        );
+        let preview = ctx.load_texture("frame compare diff", image, egui::TextureOptions::NEAREST);
+        self.heatmap = Some((preview, stats));
+    }
+
+    /// A and B either side of a divider that follows drags.
+    fn split_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        a: egui::TextureId,
+        b: egui::TextureId,
+        size: egui::Vec2,
+    ) {
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::drag());
+        if let Some(pointer) = response.interact_pointer_pos() {
+            self.split = ((pointer.x - rect.min.x) / rect.width()).clamp(0.0, 1.0);
+        }
+        let x = rect.min.x + rect.width() * self.split;
+        let painter = ui.painter_at(rect);
+        let uv = |from: f32, to: f32| {
+            egui::Rect::from_min_max(egui::pos2(from, 0.0), egui::pos2(to, 1.0))
+        };
+        let left = egui::Rect::from_min_max(rect.min, egui::pos2(x, rect.max.y));
+        let right = egui::Rect::from_min_max(egui::pos2(x, rect.min.y), rect.max);
+        painter.image(a, left, uv(0.0, self.split), egui::Color32::WHITE);
+        painter.image(b, right, uv(self.split, 1.0), egui::Color32::WHITE);
+        painter.vline(
+            x,
+            rect.y_range(),
+            egui::Stroke::new(2.0, egui::Color32::WHITE),
+        );
+        let font = egui::FontId::proportional(14.0);
+        painter.text(
+            rect.left_top() + egui::vec2(6.0, 4.0),
+            egui::Align2::LEFT_TOP,
+            &self.labels[0],
+            font.clone(),
+            egui::Color32::WHITE,
+        );
+        painter.text(
+            rect.right_top() + egui::vec2(-6.0, 4.0),
+            egui::Align2::RIGHT_TOP,
+            &self.labels[1],
+            font,
+            egui::Color32::WHITE,
+        );
+    }
+}
+
+impl Plugin for FrameCompare {
+    fn name(&self) -> &str {
+        "Frame Compare"
+    }
+
+    fn build(&mut self, ctx: &mut PluginContext) {
+        if !ctx.state.caps.compute {
+            log::warn!("Frame compare heatmaps need compute shaders, which this device lacks");
+            return;
+        }
+        self.differ = Some(Differ::new(&ctx.state.device));
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, state: &State, world: &mut World) {
+        self.receive(ctx, state, world);
+        egui::Window::new("Frame Compare")
+            .default_open(false)
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.label("Capture A, change a render setting, then capture B.");
+                for slot in 0..2 {
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut self.labels[slot]);
+                        let label = if self.pending == Some(slot) {
+                            "Capturing..."
+                        } else {
+                            "Capture"
+                        };
+                        if ui.button(label).clicked() {
+                            world.capture_frame = true;
+                            self.pending = Some(slot);
+                        }
+                        if self.frames[slot].is_some() {
+                            ui.label("✔");
+                        }
+                    });
+                }
+                if ui.button("Swap A and B").clicked() {
+                    self.frames.swap(0, 1);
+                    self.labels.swap(0, 1);
+                    self.dirty = true;
+                }
+
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut self.view, CompareView::Split, "Split");
+                    ui.radio_value(&mut self.view, CompareView::Difference, "Difference");
+                });
+                if self.view == CompareView::Difference {
+                    let before = (self.gain, self.threshold);
+                    ui.add(
+                        egui::Slider::new(&mut self.gain, 1.0..=64.0)
+                            .logarithmic(true)
+                            .text("Gain"),
+                    );
+                    ui.add(egui::Slider::new(&mut self.threshold, 0.0..=0.1).text("Threshold"));
+                    self.dirty |= (self.gain, self.threshold) != before;
+                }
+                if std::mem::take(&mut self.dirty) {
+                    self.rediff(ctx, state);
+                }
+
+                let [Some(a), Some(b)] = &self.frames else {
+                    return;
+                };
+                let (frame_size, b_size) = (a.texture.size(), b.texture.size());
+                if frame_size != b_size {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        "The captures differ in size; recapture one.",
+                    );
+                    return;
+                }
+                let aspect = frame_size.height as f32 / frame_size.width.max(1) as f32;
+                let width = ui.available_width().clamp(200.0, 960.0);
+                let size = egui::vec2(width, width * aspect);
+                let (a, b) = (a.preview.id(), b.preview.id());
+                match self.view {
+                    CompareView::Split => {
+                        ui.add(egui::Slider::new(&mut self.split, 0.0..=1.0).text("Divider"));
+                        self.split_ui(ui, a, b, size);
+                    }
+                    CompareView::Difference => {
+                        let Some((heatmap, stats)) = &self.heatmap else {
+                            return;
+                        };
+                        ui.label(format!(
+                            "Mean {:.4}, max {:.3}, {:.2}% of pixels changed",
+                            stats.mean,
+                            stats.max,
+                            stats.changed * 100.0
+                        ));
+                        ui.image((heatmap.id(), size));
+                    }
+                }
+            });
+    }
+}
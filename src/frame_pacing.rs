@@ -0,0 +1,227 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use winit::event_loop::ControlFlow;
+
+/// Sleeping is only accurate to a millisecond or so; the rest is spun.
+const SPIN_MARGIN: Duration = Duration::from_micros(1500);
+
+/// Decides when the next frame is drawn: not at all while the window is
+/// hidden, at most `fps_cap` times a second, and in reactive mode only
+/// after input.
+pub struct FramePacer {
+    pub fps_cap: Option<f32>,
+    /// Redraw only when a window event arrives; animations pause meanwhile.
+    pub reactive: bool,
+    occluded: bool,
+    minimized: bool,
+    dirty: bool,
+    idle: bool,
+    last_frame: Instant,
+}
+
+impl FramePacer {
+    pub fn new(fps_cap: Option<f32>) -> Self {
+        FramePacer {
+            fps_cap,
+            reactive: false,
+            occluded: false,
+            minimized: false,
+            dirty: true,
+            idle: false,
+            last_frame: Instant::now(),
+        }
+    }
+
+    pub fn set_occluded(&mut self, occluded: bool) {
+        self.occluded = occluded;
+    }
+
+    pub fn set_minimized(&mut self, minimized: bool) {
+        self.minimized = minimized;
+    }
+
+    /// Something changed that reactive mode should draw a frame for.
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.occluded || self.minimized
+    }
+
+    /// Whether to request another redraw once the event queue is empty.
+    pub fn wants_frame(&self) -> bool {
+        !self.is_hidden() && (!self.reactive || self.dirty)
+    }
+
+    /// `Wait` while there is nothing to draw so the loop stops spinning.
+    pub fn control_flow(&self) -> ControlFlow {
+        if self.wants_frame() {
+            ControlFlow::Poll
+        } else {
+            ControlFlow::Wait
+        }
+    }
+
+    /// Notes that the loop went idle, so the next frame can drop the gap.
+    pub fn idle(&mut self) {
+        self.idle = true;
+    }
+
+    /// True on the first frame after idling; the time spent idle shouldn't
+    /// count as one huge frame.
+    pub fn take_resumed(&mut self) -> bool {
+        std::mem::take(&mut self.idle)
+    }
+
+    /// Blocks until the frame cap allows the next frame, then starts it.
+    pub fn begin_frame(&mut self) {
+        self.dirty = false;
+        if let Some(fps) = self.fps_cap.filter(|fps| *fps > 0.0) {
+            let deadline = self.last_frame + Duration::from_secs_f32(1.0 / fps);
+            let now = Instant::now();
+            if deadline > now + SPIN_MARGIN {
+                std::thread::sleep(deadline - now - SPIN_MARGIN);
+            }
+            while Instant::now() < deadline {
+                std::hint::spin_loop();
+            }
+        }
+        self.last_frame = Instant::now();
+    }
+}
+
+/// How frames are handed to the display; each maps to a `wgpu::PresentMode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VsyncMode {
+    /// Waits for vblank; never tears.
+    Fifo,
+    /// Waits for vblank unless the frame is late, then tears instead of
+    /// waiting a whole refresh.
+    Adaptive,
+    /// Replaces the queued frame with newer ones; no tearing, lower latency
+    /// than `Fifo`.
+    Mailbox,
+    /// Presents at once and tears.
+    Immediate,
+}
+
+impl VsyncMode {
+    pub const ALL: [VsyncMode; 4] = [
+        VsyncMode::Fifo,
+        VsyncMode::Adaptive,
+        VsyncMode::Mailbox,
+        VsyncMode::Immediate,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            VsyncMode::Fifo => "VSync (FIFO)",
+            VsyncMode::Adaptive => "Adaptive VSync",
+            VsyncMode::Mailbox => "Mailbox",
+            VsyncMode::Immediate => "Immediate (tearing)",
+        }
+    }
+
+    pub fn present_mode(self) -> wgpu::PresentMode {
+        match self {
+            VsyncMode::Fifo => wgpu::PresentMode::Fifo,
+            VsyncMode::Adaptive => wgpu::PresentMode::FifoRelaxed,
+            VsyncMode::Mailbox => wgpu::PresentMode::Mailbox,
+            VsyncMode::Immediate => wgpu::PresentMode::Immediate,
+        }
+    }
+
+    pub fn from_present_mode(mode: wgpu::PresentMode) -> Self {
+        match mode {
+            wgpu::PresentMode::FifoRelaxed => VsyncMode::Adaptive,
+            wgpu::PresentMode::Mailbox => VsyncMode::Mailbox,
+            wgpu::PresentMode::Immediate | wgpu::PresentMode::AutoNoVsync => VsyncMode::Immediate,
+            wgpu::PresentMode::Fifo | wgpu::PresentMode::AutoVsync => VsyncMode::Fifo,
+        }
+    }
+}
+
+/// Estimates input-to-photon latency: the time from an input event
+/// arriving to the frame it affected being presented is measured, and the
+/// time from there to the screen is estimated from the present mode,
+/// frames in flight and the refresh rate.
+#[derive(Default)]
+pub struct LatencyMeter {
+    pub overlay: bool,
+    /// When the oldest input not yet in a presented frame arrived.
+    pending: Option<Instant>,
+    /// Smoothed milliseconds.
+    input_to_present: f32,
+    present_to_photon: f32,
+}
+
+impl LatencyMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn input(&mut self) {
+        self.pending.get_or_insert_with(Instant::now);
+    }
+
+    /// Call right after presenting. `frame_time` is the smoothed time
+    /// between frames in seconds.
+    pub fn presented(
+        &mut self,
+        mode: VsyncMode,
+        frame_latency: u32,
+        refresh_hz: f32,
+        frame_time: f32,
+    ) {
+        let Some(input) = self.pending.take() else {
+            return;
+        };
+        let refresh = 1000.0 / refresh_hz.max(1.0);
+        let frame = frame_time * 1000.0;
+        // frames only queue up when they're drawn faster than the display
+        // takes them
+        let queued = if frame < refresh {
+            frame_latency.saturating_sub(1) as f32 * refresh
+        } else {
+            0.0
+        };
+        let wait = match mode {
+            VsyncMode::Fifo => queued + refresh / 2.0,
+            VsyncMode::Adaptive if frame < refresh => queued + refresh / 2.0,
+            VsyncMode::Mailbox => refresh / 2.0,
+            VsyncMode::Adaptive | VsyncMode::Immediate => 0.0,
+        };
+        // scanout reaches the middle of the screen half a refresh in
+        let photon = wait + refresh / 2.0;
+        let measured = input.elapsed().as_secs_f32() * 1000.0;
+        if self.input_to_present == 0.0 {
+            self.input_to_present = measured;
+            self.present_to_photon = photon;
+        } else {
+            self.input_to_present += (measured - self.input_to_present) * 0.1;
+            self.present_to_photon += (photon - self.present_to_photon) * 0.1;
+        }
+    }
+
+    pub fn overlay_ui(&self, ctx: &egui::Context) {
+        if !self.overlay {
+            return;
+        }
+        egui::Area::new(egui::Id::new("latency_overlay"))
+            .anchor(egui::Align2::RIGHT_TOP, [-8.0, 32.0])
+            .interactable(false)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.label(format!(
+                        "Input → photon ≈ {:.1} ms",
+                        self.input_to_present + self.present_to_photon
+                    ));
+                    ui.small(format!(
+                        "{:.1} ms to present (measured), ~{:.1} ms to screen",
+                        self.input_to_present, self.present_to_photon
+                    ));
+                });
+            });
+    }
+}
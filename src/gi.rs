@@ -0,0 +1,824 @@
+//! Experimental diffuse global illumination from a grid of irradiance
+//! probes, after DDGI. The scene is voxelized on the CPU into a 3D albedo
+//! texture; every frame a compute pass traces a few rays from each probe
+//! through the voxels, lights what they hit with the sun, the sky and the
+//! previous frame's probes, and blends that into the probes' spherical
+//! harmonics. Each frame adds a bounce, so light settles over a second or so.
+//!
+//! Materials receive GI by binding `ProbeGrid::binding` as one group,
+//! declaring the textures `GI_WGSL` expects at that group, appending
+//! `uniform_fields` to a uniform of their own, and calling `gi_irradiance`.
+
+use crate::app::State;
+use crate::material::Binding;
+use crate::texture::Texture;
+use crate::uniform::{UniformLayout, UniformType};
+use crate::world::World;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Lookup function for materials. It reads these globals, which the
+/// material declares at the group it binds `ProbeGrid::binding` to:
+///
+/// ```wgsl
+/// @group(N) @binding(0) var gi_red: texture_3d<f32>;
+/// @group(N) @binding(1) var gi_red_sampler: sampler;
+/// @group(N) @binding(2) var gi_green: texture_3d<f32>;
+/// @group(N) @binding(3) var gi_green_sampler: sampler;
+/// @group(N) @binding(4) var gi_blue: texture_3d<f32>;
+/// @group(N) @binding(5) var gi_blue_sampler: sampler;
+/// ```
+pub const GI_WGSL: &str = r#"
+// Light reaching a surface at `world_pos` facing `normal`, over pi, so
+// `albedo * gi_irradiance(...)` is what the surface reflects.
+fn gi_irradiance(
+    world_pos: vec3<f32>,
+    normal: vec3<f32>,
+    gi_min: vec3<f32>,
+    gi_spacing: vec3<f32>,
+    gi_counts: vec3<f32>,
+) -> vec3<f32> {
+    let n = normalize(normal);
+    // off the surface, so probes behind it weigh less
+    let offset = n * min(gi_spacing.x, min(gi_spacing.y, gi_spacing.z)) * 0.25;
+    let uvw = ((world_pos + offset - gi_min) / gi_spacing + 0.5) / gi_counts;
+    let r = textureSampleLevel(gi_red, gi_red_sampler, uvw, 0.0);
+    let g = textureSampleLevel(gi_green, gi_green_sampler, uvw, 0.0);
+    let b = textureSampleLevel(gi_blue, gi_blue_sampler, uvw, 0.0);
+    // L1 radiance to irradiance (Ramamoorthi and Hanrahan), over pi
+    let basis = vec4(0.886227, 1.023328 * n) / 3.14159265;
+    return max(vec3(dot(r, basis), dot(g, basis), dot(b, basis)), vec3(0.0));
+}
+"#;
+
+/// Marches rays through a `Voxels` grid. Reads a global the shader
+/// declares, `var voxels: texture_3d<f32>`, holding `Voxels::create_texture`.
+pub(crate) const VOXEL_MARCH_WGSL: &str = r#"
+struct VoxelHit {
+    found: bool,
+    cell: vec3<i32>,
+    // of the face the ray entered through
+    normal: vec3<f32>,
+};
+
+// Steps voxel by voxel (Amanatides and Woo) until an occupied one, ignoring
+// the one the ray starts in.
+fn voxel_march(
+    origin: vec3<f32>,
+    dir: vec3<f32>,
+    voxel_min: vec3<f32>,
+    voxel_size: f32,
+    voxel_dims: vec3<f32>,
+) -> VoxelHit {
+    let dims = vec3<i32>(voxel_dims);
+    let p = (origin - voxel_min) / voxel_size;
+    var cell = vec3<i32>(floor(p));
+    let step = vec3<i32>(sign(dir));
+    let delta = 1.0 / max(abs(dir), vec3(1e-6));
+    var t_max = select(p - floor(p), floor(p) + 1.0 - p, dir > vec3(0.0)) * delta;
+    var normal = vec3(0.0);
+    let steps = dims.x + dims.y + dims.z;
+    for (var i = 0; i < steps; i++) {
+        if t_max.x < t_max.y && t_max.x < t_max.z {
+            cell.x += step.x;
+            t_max.x += delta.x;
+            normal = vec3(-f32(step.x), 0.0, 0.0);
+        } else if t_max.y < t_max.z {
+            cell.y += step.y;
+            t_max.y += delta.y;
+            normal = vec3(0.0, -f32(step.y), 0.0);
+        } else {
+            cell.z += step.z;
+            t_max.z += delta.z;
+            normal = vec3(0.0, 0.0, -f32(step.z));
+        }
+        if any(cell < vec3(0)) || any(cell >= dims) {
+            break;
+        }
+        if textureLoad(voxels, cell, 0).a > 0.5 {
+            return VoxelHit(true, cell, normal);
+        }
+    }
+    return VoxelHit(false, cell, normal);
+}
+"#;
+
+const TRACE_SHADER: &str = r#"
+struct Trace {
+    voxel_min: vec3<f32>,
+    voxel_size: f32,
+    voxel_dims: vec3<f32>,
+    frame: u32,
+    probe_min: vec3<f32>,
+    rays: u32,
+    probe_spacing: vec3<f32>,
+    hysteresis: f32,
+    probe_counts: vec3<f32>,
+    bounce: f32,
+    sun_direction: vec3<f32>,
+    sky: vec3<f32>,
+    sun_color: vec3<f32>,
+};
+
+@group(0) @binding(0) var<uniform> trace: Trace;
+@group(0) @binding(1) var voxels: texture_3d<f32>;
+@group(0) @binding(2) var previous_red: texture_3d<f32>;
+@group(0) @binding(3) var previous_green: texture_3d<f32>;
+@group(0) @binding(4) var previous_blue: texture_3d<f32>;
+@group(0) @binding(5) var next_red: texture_storage_3d<rgba16float, write>;
+@group(0) @binding(6) var next_green: texture_storage_3d<rgba16float, write>;
+@group(0) @binding(7) var next_blue: texture_storage_3d<rgba16float, write>;
+
+const PI: f32 = 3.14159265;
+
+// The previous frame's nearest probe, as in `gi_irradiance`.
+fn probe_irradiance(pos: vec3<f32>, n: vec3<f32>) -> vec3<f32> {
+    let counts = vec3<i32>(trace.probe_counts);
+    let cell = clamp(vec3<i32>(round((pos - trace.probe_min) / trace.probe_spacing)), vec3(0), counts - 1);
+    let r = textureLoad(previous_red, cell, 0);
+    let g = textureLoad(previous_green, cell, 0);
+    let b = textureLoad(previous_blue, cell, 0);
+    let basis = vec4(0.886227, 1.023328 * n) / PI;
+    return max(vec3(dot(r, basis), dot(g, basis), dot(b, basis)), vec3(0.0));
+}
+
+fn march(origin: vec3<f32>, dir: vec3<f32>) -> VoxelHit {
+    return voxel_march(origin, dir, trace.voxel_min, trace.voxel_size, trace.voxel_dims);
+}
+
+fn radiance(origin: vec3<f32>, dir: vec3<f32>) -> vec3<f32> {
+    let hit = march(origin, dir);
+    if !hit.found {
+        return trace.sky;
+    }
+    let albedo = textureLoad(voxels, hit.cell, 0).rgb;
+    let center = trace.voxel_min + (vec3<f32>(hit.cell) + 0.5) * trace.voxel_size;
+    // the empty voxel in front of the face that was hit
+    let surface = center + hit.normal * trace.voxel_size;
+    let to_sun = -normalize(trace.sun_direction);
+    var direct = vec3(0.0);
+    let n_dot_l = dot(hit.normal, to_sun);
+    if n_dot_l > 0.0 && !march(surface, to_sun).found {
+        direct = trace.sun_color * n_dot_l;
+    }
+    let indirect = probe_irradiance(surface, hit.normal) * trace.bounce;
+    return albedo * (direct + indirect);
+}
+
+// Spherical Fibonacci points, turned and shifted every frame so the
+// history covers the sphere.
+fn ray_direction(i: u32) -> vec3<f32> {
+    let frame = f32(trace.frame);
+    let z = 1.0 - 2.0 * (f32(i) + fract(frame * 0.618034)) / f32(trace.rays);
+    let r = sqrt(max(1.0 - z * z, 0.0));
+    let phi = f32(i) * 2.399963 + frame * 1.7;
+    return vec3(r * cos(phi), r * sin(phi), z);
+}
+
+@compute @workgroup_size(64)
+fn update(@builtin(global_invocation_id) id: vec3<u32>) {
+    let counts = vec3<u32>(trace.probe_counts);
+    if id.x >= counts.x * counts.y * counts.z {
+        return;
+    }
+    let probe = vec3(id.x % counts.x, (id.x / counts.x) % counts.y, id.x / (counts.x * counts.y));
+    let origin = trace.probe_min + vec3<f32>(probe) * trace.probe_spacing;
+    var r = vec4(0.0);
+    var g = vec4(0.0);
+    var b = vec4(0.0);
+    for (var i = 0u; i < trace.rays; i++) {
+        let dir = ray_direction(i);
+        let light = radiance(origin, dir);
+        let basis = vec4(0.282095, 0.488603 * dir);
+        r += light.r * basis;
+        g += light.g * basis;
+        b += light.b * basis;
+    }
+    let weight = 4.0 * PI / f32(trace.rays);
+    let cell = vec3<i32>(probe);
+    let h = trace.hysteresis;
+    textureStore(next_red, cell, mix(r * weight, textureLoad(previous_red, cell, 0), h));
+    textureStore(next_green, cell, mix(g * weight, textureLoad(previous_green, cell, 0), h));
+    textureStore(next_blue, cell, mix(b * weight, textureLoad(previous_blue, cell, 0), h));
+}
+"#;
+
+const VOXEL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+/// One texture per color channel, each holding L1 coefficients. Half
+/// floats are filterable and writable from compute everywhere.
+const PROBE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const WORKGROUP_SIZE: u32 = 64;
+
+/// The scene as a grid of cubic voxels, each the average color of the
+/// triangles through it, with alpha 255, or zero where empty.
+#[derive(Debug, Clone)]
+pub struct Voxels {
+    pub min: glam::Vec3,
+    pub size: f32,
+    pub dims: [u32; 3],
+    /// x fastest, then y, then z.
+    pub albedo: Vec<[u8; 4]>,
+    /// Triangles voxelized.
+    pub triangles: usize,
+}
+
+impl Voxels {
+    /// Voxelizes every visible model, with `resolution` voxels along the
+    /// longest side of the scene and one empty voxel of padding around it.
+    pub fn from_world(world: &World, resolution: u32) -> Self {
+        let Some((min, max)) = bounds(world) else {
+            return Self::empty();
+        };
+        let extent = (max - min).max(glam::Vec3::splat(1e-3));
+        let size = extent.max_element() / resolution.max(1) as f32;
+        let min = min - size;
+        let dims = ((extent / size).ceil().as_uvec3() + 2).to_array();
+        let count = dims.iter().map(|&d| d as usize).product();
+        let mut sums = vec![glam::Vec4::ZERO; count];
+        let mut triangles = 0;
+        for model in world.models.iter().filter(|m| m.is_visible()) {
+            let matrix = model.global_matrix();
+            let color = glam::Vec4::from(model.base_color).truncate().extend(1.0);
+            let positions: Vec<glam::Vec3> = model
+                .mesh
+                .positions
+                .iter()
+                .map(|&p| matrix.transform_point3(p))
+                .collect();
+            for triangle in model.mesh.indices.chunks_exact(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| positions[triangle[i] as usize]);
+                let (ab, ac) = (b - a, c - a);
+                let longest = ab.length().max(ac.length()).max((c - b).length());
+                // samples at most half a voxel apart, so none are skipped
+                let steps = (longest / (size * 0.5)).ceil().clamp(1.0, 4096.0) as u32;
+                for i in 0..=steps {
+                    for j in 0..=steps - i {
+                        let p = a + ab * (i as f32 / steps as f32) + ac * (j as f32 / steps as f32);
+                        let cell = ((p - min) / size).floor().as_ivec3();
+                        if cell.cmplt(glam::IVec3::ZERO).any()
+                            || cell.cmpge(glam::UVec3::from(dims).as_ivec3()).any()
+                        {
+                            continue;
+                        }
+                        let index = cell.x as usize
+                            + dims[0] as usize
+                                * (cell.y as usize + dims[1] as usize * cell.z as usize);
+                        sums[index] += color;
+                    }
+                }
+                triangles += 1;
+            }
+        }
+        let albedo = sums
+            .iter()
+            .map(|sum| {
+                if sum.w == 0.0 {
+                    return [0; 4];
+                }
+                let rgb = (sum.truncate() / sum.w).clamp(glam::Vec3::ZERO, glam::Vec3::ONE);
+                let [r, g, b] = (rgb * 255.0).round().to_array().map(|c| c as u8);
+                [r, g, b, 255]
+            })
+            .collect();
+        Voxels {
+            min,
+            size,
+            dims,
+            albedo,
+            triangles,
+        }
+    }
+
+    /// A single empty voxel.
+    fn empty() -> Self {
+        Voxels {
+            min: glam::Vec3::ZERO,
+            size: 1.0,
+            dims: [1; 3],
+            albedo: vec![[0; 4]],
+            triangles: 0,
+        }
+    }
+
+    /// A 3D texture of the voxels, for `VOXEL_MARCH_WGSL`.
+    pub fn create_texture(&self, state: &State) -> wgpu::Texture {
+        let [width, height, depth] = self.dims;
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: depth,
+        };
+        let texture = state.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Voxels"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: VOXEL_FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        state.queue.write_texture(
+            texture.as_image_copy(),
+            bytemuck::cast_slice(&self.albedo),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(width * 4),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+        texture
+    }
+
+    /// The voxel holding `point`, if it's inside the grid.
+    pub fn at(&self, point: glam::Vec3) -> Option<[u8; 4]> {
+        let cell = ((point - self.min) / self.size).floor().as_ivec3();
+        let [x, y, z] = self.dims.map(|d| d as i32);
+        if cell.cmplt(glam::IVec3::ZERO).any() || cell.x >= x || cell.y >= y || cell.z >= z {
+            return None;
+        }
+        Some(self.albedo[(cell.x + x * (cell.y + y * cell.z)) as usize])
+    }
+
+    pub fn filled(&self) -> usize {
+        self.albedo.iter().filter(|v| v[3] > 0).count()
+    }
+
+    /// World-space corners of the grid.
+    pub fn bounds(&self) -> (glam::Vec3, glam::Vec3) {
+        let dims = glam::UVec3::from(self.dims).as_vec3();
+        (self.min, self.min + dims * self.size)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GiSettings {
+    /// Off, materials fall back to their flat ambient and no probes are
+    /// traced.
+    pub enabled: bool,
+    pub rays_per_probe: u32,
+    /// Of the old value kept each frame; higher is smoother but slower to
+    /// follow changes.
+    pub hysteresis: f32,
+    /// Scales the light bounced off surfaces lit by the probes; 0 leaves a
+    /// single bounce.
+    pub bounce: f32,
+    /// Scales what materials receive.
+    pub intensity: f32,
+    /// Voxels along the longest side of the scene, at the next voxelize.
+    pub voxel_resolution: u32,
+}
+
+impl Default for GiSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GiSettings {
+    pub fn new() -> Self {
+        GiSettings {
+            enabled: true,
+            rays_per_probe: 64,
+            hysteresis: 0.95,
+            bounce: 0.8,
+            intensity: 1.0,
+            voxel_resolution: 64,
+        }
+    }
+
+    /// Returns true when anything changed.
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = ui
+            .checkbox(&mut self.enabled, "Global illumination")
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut self.rays_per_probe, 8..=256).text("Rays per probe"))
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut self.hysteresis, 0.0..=0.99).text("Hysteresis"))
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut self.bounce, 0.0..=1.0).text("Bounce"))
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut self.intensity, 0.0..=4.0).text("Intensity"))
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut self.voxel_resolution, 16..=256).text("Voxel resolution"))
+            .changed();
+        changed
+    }
+}
+
+/// What the last voxelize made and how long it took.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VoxelStats {
+    pub dims: [u32; 3],
+    pub filled: usize,
+    pub triangles: usize,
+    pub milliseconds: f32,
+}
+
+/// A box of irradiance probes over the voxelized scene, and the compute
+/// pass that updates them. Needs compute shaders.
+pub struct ProbeGrid {
+    pub settings: GiSettings,
+    /// Probes along each axis, fixed at creation.
+    pub counts: [u32; 3],
+    stats: Option<VoxelStats>,
+    voxel_bounds: (glam::Vec3, glam::Vec3),
+    voxel_size: f32,
+    voxel_dims: [u32; 3],
+    probe_min: glam::Vec3,
+    probe_spacing: glam::Vec3,
+    voxels: wgpu::Texture,
+    /// Sampled by materials, and read as last frame's value by the update.
+    probes: [Arc<Texture>; 3],
+    /// Written by the update, then copied into `probes`, so materials'
+    /// bind groups never change.
+    scratch: [wgpu::Texture; 3],
+    trace_layout: UniformLayout,
+    trace_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::ComputePipeline,
+    frame: u32,
+    /// Drops the history on the next update, after revoxelizing.
+    reset: bool,
+}
+
+impl ProbeGrid {
+    pub fn new(state: &State, counts: [u32; 3]) -> Self {
+        let device = &state.device;
+        let counts = counts.map(|c| c.max(2));
+        let probe_texture = |label, usage| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: counts[0],
+                    height: counts[1],
+                    depth_or_array_layers: counts[2],
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D3,
+                format: PROBE_FORMAT,
+                usage,
+                view_formats: &[],
+            })
+        };
+        let sampled = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST;
+        let storage = wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::COPY_SRC;
+        let probes = ["GI Probes Red", "GI Probes Green", "GI Probes Blue"]
+            .map(|label| Arc::new(Texture::from_texture(state, probe_texture(label, sampled))));
+        let scratch = ["GI Scratch Red", "GI Scratch Green", "GI Scratch Blue"]
+            .map(|label| probe_texture(label, storage));
+
+        let trace_layout = UniformLayout::new()
+            .field("voxel_min", UniformType::Vec3)
+            .field("voxel_size", UniformType::F32)
+            .field("voxel_dims", UniformType::Vec3)
+            .field("frame", UniformType::U32)
+            .field("probe_min", UniformType::Vec3)
+            .field("rays", UniformType::U32)
+            .field("probe_spacing", UniformType::Vec3)
+            .field("hysteresis", UniformType::F32)
+            .field("probe_counts", UniformType::Vec3)
+            .field("bounce", UniformType::F32)
+            .field("sun_direction", UniformType::Vec3)
+            .field("sky", UniformType::Vec3)
+            .field("sun_color", UniformType::Vec3);
+        let source = format!("{TRACE_SHADER}{VOXEL_MARCH_WGSL}");
+        trace_layout.assert_wgsl(&source, "Trace");
+        let trace_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GI Trace"),
+            size: trace_layout.size(),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let sampled_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D3,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let storage_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::WriteOnly,
+                format: PROBE_FORMAT,
+                view_dimension: wgpu::TextureViewDimension::D3,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("GI Trace"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                sampled_entry(1),
+                sampled_entry(2),
+                sampled_entry(3),
+                sampled_entry(4),
+                storage_entry(5),
+                storage_entry(6),
+                storage_entry(7),
+            ],
+        });
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("GI Trace"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("GI Trace"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("GI Trace"),
+            layout: Some(&layout),
+            module: &module,
+            entry_point: Some("update"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let voxels = Voxels::empty().create_texture(state);
+        let bind_group = trace_bind_group(
+            device,
+            &bind_group_layout,
+            &trace_buffer,
+            &voxels,
+            &probes,
+            &scratch,
+        );
+        ProbeGrid {
+            settings: GiSettings::new(),
+            counts,
+            stats: None,
+            voxel_bounds: (glam::Vec3::ZERO, glam::Vec3::ONE),
+            voxel_size: 1.0,
+            voxel_dims: [1; 3],
+            probe_min: glam::Vec3::ZERO,
+            probe_spacing: glam::Vec3::ONE,
+            voxels,
+            probes,
+            scratch,
+            trace_layout,
+            trace_buffer,
+            bind_group_layout,
+            bind_group,
+            pipeline,
+            frame: 0,
+            reset: true,
+        }
+    }
+
+    /// The three probe textures, with the samplers `GI_WGSL` expects, as
+    /// one material group.
+    pub fn binding(&self) -> Binding {
+        Binding::textures(self.probes.to_vec(), wgpu::ShaderStages::FRAGMENT)
+    }
+
+    /// Appends the arguments of the WGSL `gi_irradiance` to a material's
+    /// uniform, with `gi_enabled` and `gi_intensity` from the settings.
+    pub fn uniform_fields(layout: UniformLayout) -> UniformLayout {
+        layout
+            .field("gi_min", UniformType::Vec3)
+            .field("gi_enabled", UniformType::U32)
+            .field("gi_spacing", UniformType::Vec3)
+            .field("gi_intensity", UniformType::F32)
+            .field("gi_counts", UniformType::Vec3)
+    }
+
+    /// Fills the fields added by `uniform_fields`.
+    pub fn write_uniform(&self, layout: &UniformLayout, data: &mut [u8]) {
+        let counts = glam::UVec3::from(self.counts).as_vec3();
+        layout.write(data, "gi_min", self.probe_min.to_array());
+        layout.write(data, "gi_enabled", self.is_active() as u32);
+        layout.write(data, "gi_spacing", self.probe_spacing.to_array());
+        layout.write(data, "gi_intensity", self.settings.intensity);
+        layout.write(data, "gi_counts", counts.to_array());
+    }
+
+    /// Enabled and voxelized.
+    pub fn is_active(&self) -> bool {
+        self.settings.enabled && self.stats.is_some()
+    }
+
+    pub fn stats(&self) -> Option<VoxelStats> {
+        self.stats
+    }
+
+    /// World-space corners of the voxel grid.
+    pub fn voxel_bounds(&self) -> (glam::Vec3, glam::Vec3) {
+        self.voxel_bounds
+    }
+
+    /// Where each probe sits, x fastest.
+    pub fn probe_positions(&self) -> Vec<glam::Vec3> {
+        let [nx, ny, nz] = self.counts;
+        (0..nz)
+            .flat_map(|z| (0..ny).flat_map(move |y| (0..nx).map(move |x| (x, y, z))))
+            .map(|(x, y, z)| self.probe_min + glam::uvec3(x, y, z).as_vec3() * self.probe_spacing)
+            .collect()
+    }
+
+    /// Voxelizes the world at the settings' resolution and spreads the
+    /// probes over it. Call again when the scene changes.
+    pub fn voxelize(&mut self, state: &State, world: &World) {
+        let start = Instant::now();
+        let voxels = Voxels::from_world(world, self.settings.voxel_resolution);
+        self.set_voxels(state, &voxels);
+        let stats = VoxelStats {
+            milliseconds: start.elapsed().as_secs_f32() * 1000.0,
+            ..self.stats.unwrap()
+        };
+        log::info!(
+            "Voxelized {} triangles into {:?} voxels, {} filled, in {:.1} ms",
+            stats.triangles,
+            stats.dims,
+            stats.filled,
+            stats.milliseconds
+        );
+        self.stats = Some(stats);
+    }
+
+    /// Uploads `voxels` and spreads the probes over the scene inside them.
+    pub fn set_voxels(&mut self, state: &State, voxels: &Voxels) {
+        let device = &state.device;
+        self.voxels = voxels.create_texture(state);
+        self.bind_group = trace_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.trace_buffer,
+            &self.voxels,
+            &self.probes,
+            &self.scratch,
+        );
+        self.voxel_bounds = voxels.bounds();
+        self.voxel_size = voxels.size;
+        self.voxel_dims = voxels.dims;
+        // the scene inside the padding
+        let (min, max) = self.voxel_bounds;
+        let (min, max) = (min + voxels.size, max - voxels.size);
+        let steps = glam::UVec3::from(self.counts).as_vec3() - 1.0;
+        self.probe_min = min;
+        self.probe_spacing = ((max - min) / steps).max(glam::Vec3::splat(1e-3));
+        self.reset = true;
+        self.stats = Some(VoxelStats {
+            dims: voxels.dims,
+            filled: voxels.filled(),
+            triangles: voxels.triangles,
+            milliseconds: 0.0,
+        });
+    }
+
+    /// Sets this frame's light: `sun_direction` is the way it travels, and
+    /// `sky` what rays that hit nothing see.
+    pub fn update(
+        &mut self,
+        queue: &wgpu::Queue,
+        sun_direction: glam::Vec3,
+        sun_color: glam::Vec3,
+        sky: glam::Vec3,
+    ) {
+        let layout = &self.trace_layout;
+        let mut data = layout.zeroed();
+        let (voxel_min, _) = self.voxel_bounds;
+        let settings = &self.settings;
+        let hysteresis = if self.reset { 0.0 } else { settings.hysteresis };
+        layout.write(&mut data, "voxel_min", voxel_min.to_array());
+        layout.write(&mut data, "voxel_size", self.voxel_size);
+        layout.write(
+            &mut data,
+            "voxel_dims",
+            glam::UVec3::from(self.voxel_dims).as_vec3().to_array(),
+        );
+        layout.write(&mut data, "frame", self.frame);
+        layout.write(&mut data, "probe_min", self.probe_min.to_array());
+        layout.write(&mut data, "rays", settings.rays_per_probe.max(1));
+        layout.write(&mut data, "probe_spacing", self.probe_spacing.to_array());
+        layout.write(&mut data, "hysteresis", hysteresis);
+        layout.write(
+            &mut data,
+            "probe_counts",
+            glam::UVec3::from(self.counts).as_vec3().to_array(),
+        );
+        layout.write(&mut data, "bounce", settings.bounce);
+        layout.write(&mut data, "sun_direction", sun_direction.to_array());
+        layout.write(&mut data, "sky", sky.to_array());
+        layout.write(&mut data, "sun_color", sun_color.to_array());
+        queue.write_buffer(&self.trace_buffer, 0, &data);
+    }
+
+    /// Traces this frame's rays and blends them into the probes. Record it
+    /// before the scene pass, e.g. from `Plugin::prepare`, after `update`.
+    pub fn encode(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if !self.is_active() {
+            return;
+        }
+        let probes = self.counts.iter().product::<u32>();
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("GI probes"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.dispatch_workgroups(probes.div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+        for (scratch, probes) in self.scratch.iter().zip(&self.probes) {
+            encoder.copy_texture_to_texture(
+                scratch.as_image_copy(),
+                probes.texture.as_image_copy(),
+                scratch.size(),
+            );
+        }
+        self.frame = self.frame.wrapping_add(1);
+        self.reset = false;
+    }
+}
+
+fn trace_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    buffer: &wgpu::Buffer,
+    voxels: &wgpu::Texture,
+    probes: &[Arc<Texture>; 3],
+    scratch: &[wgpu::Texture; 3],
+) -> wgpu::BindGroup {
+    let voxels = voxels.create_view(&Default::default());
+    let scratch = scratch
+        .each_ref()
+        .map(|t| t.create_view(&Default::default()));
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("GI Trace"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&voxels),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(&probes[0].view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(&probes[1].view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: wgpu::BindingResource::TextureView(&probes[2].view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: wgpu::BindingResource::TextureView(&scratch[0]),
+            },
+            wgpu::BindGroupEntry {
+                binding: 6,
+                resource: wgpu::BindingResource::TextureView(&scratch[1]),
+            },
+            wgpu::BindGroupEntry {
+                binding: 7,
+                resource: wgpu::BindingResource::TextureView(&scratch[2]),
+            },
+        ],
+    })
+}
+
+/// World-space bounds of every visible model, or None without any.
+fn bounds(world: &World) -> Option<(glam::Vec3, glam::Vec3)> {
+    let mut min = glam::Vec3::splat(f32::MAX);
+    let mut max = glam::Vec3::splat(f32::MIN);
+    for model in world.models.iter().filter(|m| m.is_visible()) {
+        let matrix = model.global_matrix();
+        for &p in &model.mesh.positions {
+            let p = matrix.transform_point3(p);
+            min = min.min(p);
+            max = max.max(p);
+        }
+    }
+    (min.x <= max.x).then_some((min, max))
+}
@@ -0,0 +1,125 @@
+//! What the device can do beyond the WebGPU baseline, queried once when
+//! it's created. Subsystems check `State::caps` and turn optional features
+//! off instead of asking for ones the GPU lacks, so weaker adapters (GLES,
+//! WebGL, older mobile parts) still run.
+
+/// Push constants beyond this aren't requested, even where offered.
+const MAX_PUSH_CONSTANT_SIZE: u32 = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GpuCaps {
+    /// `PolygonMode::Line`, for wireframes.
+    pub wireframe: bool,
+    /// Timestamps between passes, for the profiler's GPU times.
+    pub timestamps: bool,
+    /// Bytes of push constants; 0 if unsupported.
+    pub push_constant_size: u32,
+    /// `multi_draw_indexed_indirect_count`, with the draw count on the GPU.
+    pub multi_draw_indirect_count: bool,
+    /// Arrays of textures in one binding.
+    pub binding_arrays: bool,
+    /// Compute shaders; GLES 3.0 and WebGL lack them.
+    pub compute: bool,
+    /// The highest anisotropy samplers filter with: 16, or 1 where it's
+    /// missing.
+    pub max_anisotropy: u16,
+    pub max_texture_size: u32,
+    /// Set when the adapter is below WebGPU's default limits and the device
+    /// was created with the adapter's own.
+    pub downlevel_limits: bool,
+}
+
+/// Optional features to request: everything `GpuCaps` covers that the
+/// adapter has.
+pub fn wanted_features(adapter: &wgpu::Adapter) -> wgpu::Features {
+    adapter.features()
+        & (wgpu::Features::POLYGON_MODE_LINE
+            | crate::gpu_profiler::FEATURES
+            | wgpu::Features::PUSH_CONSTANTS
+            | wgpu::Features::MULTI_DRAW_INDIRECT_COUNT
+            | wgpu::Features::TEXTURE_BINDING_ARRAY)
+}
+
+/// WebGPU's defaults where the adapter meets them, otherwise its own,
+/// raised for the features `wanted_features` asks for.
+pub fn wanted_limits(adapter: &wgpu::Adapter) -> wgpu::Limits {
+    let supported = adapter.limits();
+    let mut limits = wgpu::Limits::default();
+    if !limits.check_limits(&supported) {
+        log::warn!("Adapter is below the default limits; using its own");
+        limits = supported.clone();
+    }
+    let features = wanted_features(adapter);
+    if features.contains(wgpu::Features::PUSH_CONSTANTS) {
+        limits.max_push_constant_size =
+            supported.max_push_constant_size.min(MAX_PUSH_CONSTANT_SIZE);
+    }
+    if features.contains(wgpu::Features::TEXTURE_BINDING_ARRAY) {
+        limits.max_binding_array_elements_per_shader_stage =
+            supported.max_binding_array_elements_per_shader_stage;
+        limits.max_binding_array_sampler_elements_per_shader_stage =
+            supported.max_binding_array_sampler_elements_per_shader_stage;
+    }
+    limits
+}
+
+impl GpuCaps {
+    /// What `device`, created from `adapter`, was given.
+    pub fn new(adapter: &wgpu::Adapter, device: &wgpu::Device) -> Self {
+        let features = device.features();
+        let limits = device.limits();
+        let downlevel = adapter.get_downlevel_capabilities().flags;
+        GpuCaps {
+            wireframe: features.contains(wgpu::Features::POLYGON_MODE_LINE),
+            timestamps: features.contains(crate::gpu_profiler::FEATURES),
+            push_constant_size: if features.contains(wgpu::Features::PUSH_CONSTANTS) {
+                limits.max_push_constant_size
+            } else {
+                0
+            },
+            multi_draw_indirect_count: features.contains(wgpu::Features::MULTI_DRAW_INDIRECT_COUNT),
+            binding_arrays: features.contains(wgpu::Features::TEXTURE_BINDING_ARRAY),
+            compute: downlevel.contains(wgpu::DownlevelFlags::COMPUTE_SHADERS),
+            max_anisotropy: if downlevel.contains(wgpu::DownlevelFlags::ANISOTROPIC_FILTERING) {
+                16
+            } else {
+                1
+            },
+            max_texture_size: limits.max_texture_dimension_2d,
+            downlevel_limits: !wgpu::Limits::default().check_limits(&limits),
+        }
+    }
+}
+
+pub fn ui(ui: &mut egui::Ui, caps: &GpuCaps) {
+    ui.collapsing("GPU Capabilities", |ui| {
+        let yes_no = |supported: bool| if supported { "yes" } else { "no" };
+        egui::Grid::new("gpu_caps").striped(true).show(ui, |ui| {
+            let rows = [
+                ("Wireframe", yes_no(caps.wireframe).to_string()),
+                ("Timestamps", yes_no(caps.timestamps).to_string()),
+                ("Compute", yes_no(caps.compute).to_string()),
+                (
+                    "Push constants",
+                    format!("{} bytes", caps.push_constant_size),
+                ),
+                (
+                    "Multi-draw indirect count",
+                    yes_no(caps.multi_draw_indirect_count).to_string(),
+                ),
+                ("Binding arrays", yes_no(caps.binding_arrays).to_string()),
+                ("Max anisotropy", format!("{}x", caps.max_anisotropy)),
+                ("Max texture size", caps.max_texture_size.to_string()),
+                (
+                    "Downlevel limits",
+                    yes_no(caps.downlevel_limits).to_string(),
+                ),
+            ];
+            for (name, value) in rows {
+                ui.label(name);
+                ui.label(value);
+                ui.end_row();
+            }
+        });
+    });
+}
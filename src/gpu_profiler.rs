@@ -0,0 +1,350 @@
+//! Per-pass frame statistics: draw and instance counts, render target sizes
+//! and, where the device supports timestamp queries, GPU time. The app
+//! brackets each of its passes with `begin`/`end`; anything that issues
+//! draws inside one reports them with `record_draws` and `record_target`.
+//! The last frame can be exported as a chrome://tracing file.
+
+use serde::Serialize;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Passes timed per frame; later ones still get counts, just no GPU time.
+pub const MAX_PASSES: u32 = 64;
+
+/// Both features are needed to write timestamps between passes.
+pub const FEATURES: wgpu::Features =
+    wgpu::Features::TIMESTAMP_QUERY.union(wgpu::Features::TIMESTAMP_QUERY_INSIDE_ENCODERS);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PassStats {
+    pub label: String,
+    pub draws: u32,
+    pub instances: u32,
+    /// Distinct sizes rendered to, in order of first use.
+    pub targets: Vec<(u32, u32)>,
+    /// When encoding started, from the first pass of the frame.
+    pub cpu_start_ms: f32,
+    /// Time spent encoding the pass.
+    pub cpu_ms: f32,
+    /// When the pass started on the GPU, from the first pass's timestamp.
+    pub gpu_start_ms: Option<f32>,
+    /// `None` without timestamp support or until the first readback.
+    pub gpu_ms: Option<f32>,
+    /// Submitted ahead of the frame's raster passes to overlap with them.
+    pub async_compute: bool,
+}
+
+impl PassStats {
+    fn gpu_span(&self) -> Option<(f32, f32)> {
+        Some((self.gpu_start_ms?, self.gpu_start_ms? + self.gpu_ms?))
+    }
+}
+
+/// One complete ("X") event in the Chrome trace event format.
+#[derive(Serialize)]
+struct TraceEvent<'a> {
+    name: &'a str,
+    cat: &'static str,
+    ph: &'static str,
+    /// Microseconds.
+    ts: f64,
+    dur: f64,
+    pid: u32,
+    tid: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Trace<'a> {
+    trace_events: Vec<TraceEvent<'a>>,
+}
+
+/// Where the readback buffer is in its trip back to the CPU. Each holds the
+/// labels of the passes it has times for.
+enum Readback {
+    Idle,
+    Copied(Vec<String>),
+    /// `map_async` reports into the flag whether mapping worked.
+    Mapping(Vec<String>, Arc<Mutex<Option<bool>>>),
+}
+
+struct Timestamps {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    readback: Readback,
+    /// Nanoseconds per tick.
+    period: f32,
+}
+
+#[derive(Default)]
+struct Inner {
+    current: Vec<PassStats>,
+    last: Vec<PassStats>,
+    /// When the frame's first pass began encoding.
+    frame_start: Option<Instant>,
+    /// When the pass being encoded began.
+    pass_start: Option<Instant>,
+    /// Most recent GPU start and duration by pass label.
+    times: Vec<(String, f32, f32)>,
+    timestamps: Option<Timestamps>,
+}
+
+/// Lives on `State` so passes can report into it with shared access.
+#[derive(Default)]
+pub struct GpuProfiler {
+    inner: Mutex<Inner>,
+}
+
+impl GpuProfiler {
+    /// Times passes only with `timestamps`, which needs the device to have
+    /// [`FEATURES`].
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, timestamps: bool) -> Self {
+        let timestamps = timestamps.then(|| {
+            let size = MAX_PASSES as u64 * 2 * 8;
+            Timestamps {
+                query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                    label: Some("Pass Timestamps"),
+                    ty: wgpu::QueryType::Timestamp,
+                    count: MAX_PASSES * 2,
+                }),
+                resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Timestamp Resolve"),
+                    size,
+                    usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                    mapped_at_creation: false,
+                }),
+                readback_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Timestamp Readback"),
+                    size,
+                    usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                    mapped_at_creation: false,
+                }),
+                readback: Readback::Idle,
+                period: queue.get_timestamp_period(),
+            }
+        });
+        GpuProfiler {
+            inner: Mutex::new(Inner {
+                timestamps,
+                ..Default::default()
+            }),
+        }
+    }
+
+    pub fn timestamps_supported(&self) -> bool {
+        self.inner.lock().unwrap().timestamps.is_some()
+    }
+
+    /// Starts a pass; draws recorded until the next `begin` count towards it.
+    pub fn begin(&self, encoder: &mut wgpu::CommandEncoder, label: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        let now = Instant::now();
+        let frame_start = *inner.frame_start.get_or_insert(now);
+        inner.pass_start = Some(now);
+        let index = inner.current.len() as u32;
+        inner.current.push(PassStats {
+            label: label.to_string(),
+            cpu_start_ms: (now - frame_start).as_secs_f32() * 1000.0,
+            ..Default::default()
+        });
+        if let Some(timestamps) = inner.timestamps.as_ref().filter(|_| index < MAX_PASSES) {
+            encoder.write_timestamp(&timestamps.query_set, index * 2);
+        }
+    }
+
+    /// Like `begin`, for compute submitted ahead of the frame.
+    pub fn begin_async_compute(&self, encoder: &mut wgpu::CommandEncoder, label: &str) {
+        self.begin(encoder, label);
+        if let Some(pass) = self.inner.lock().unwrap().current.last_mut() {
+            pass.async_compute = true;
+        }
+    }
+
+    /// Ends the pass begun last.
+    pub fn end(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut guard = self.inner.lock().unwrap();
+        let inner = &mut *guard;
+        let Some(index) = inner.current.len().checked_sub(1) else {
+            return;
+        };
+        if let Some(start) = inner.pass_start.take() {
+            inner.current[index].cpu_ms = start.elapsed().as_secs_f32() * 1000.0;
+        }
+        let index = index as u32;
+        if let Some(timestamps) = inner.timestamps.as_ref().filter(|_| index < MAX_PASSES) {
+            encoder.write_timestamp(&timestamps.query_set, index * 2 + 1);
+        }
+    }
+
+    /// Counts `draws` draw calls covering `instances` instances in total.
+    /// Ignored outside a pass, e.g. when rendering headless.
+    pub fn record_draws(&self, draws: u32, instances: u32) {
+        if let Some(pass) = self.inner.lock().unwrap().current.last_mut() {
+            pass.draws += draws;
+            pass.instances += instances;
+        }
+    }
+
+    pub fn record_target(&self, width: u32, height: u32) {
+        if let Some(pass) = self.inner.lock().unwrap().current.last_mut() {
+            if !pass.targets.contains(&(width, height)) {
+                pass.targets.push((width, height));
+            }
+        }
+    }
+
+    /// Copies this frame's timestamps towards the CPU, unless the previous
+    /// copy hasn't been read yet. Encode after every pass.
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut guard = self.inner.lock().unwrap();
+        let inner = &mut *guard;
+        let Some(timestamps) = &mut inner.timestamps else {
+            return;
+        };
+        let count = (inner.current.len() as u32).min(MAX_PASSES) * 2;
+        if !matches!(timestamps.readback, Readback::Idle) || count == 0 {
+            return;
+        }
+        encoder.resolve_query_set(
+            &timestamps.query_set,
+            0..count,
+            &timestamps.resolve_buffer,
+            0,
+        );
+        encoder.copy_buffer_to_buffer(
+            &timestamps.resolve_buffer,
+            0,
+            &timestamps.readback_buffer,
+            0,
+            count as u64 * 8,
+        );
+        let labels = inner.current.iter().take(MAX_PASSES as usize);
+        timestamps.readback = Readback::Copied(labels.map(|p| p.label.clone()).collect());
+    }
+
+    /// Call once per frame after submitting. Publishes the frame's counts
+    /// and picks up any timestamps that have finished reading back.
+    pub fn end_frame(&self, device: &wgpu::Device) {
+        let mut guard = self.inner.lock().unwrap();
+        let inner = &mut *guard;
+        if let Some(timestamps) = &mut inner.timestamps {
+            read_back(timestamps, device, &mut inner.times);
+        }
+        inner.last = std::mem::take(&mut inner.current);
+        inner.frame_start = None;
+        for pass in &mut inner.last {
+            let time = inner.times.iter().find(|(label, ..)| *label == pass.label);
+            pass.gpu_start_ms = time.map(|(_, start, _)| *start);
+            pass.gpu_ms = time.map(|(.., ms)| *ms);
+        }
+    }
+
+    /// The last finished frame's passes, in submission order.
+    pub fn passes(&self) -> Vec<PassStats> {
+        self.inner.lock().unwrap().last.clone()
+    }
+
+    /// Writes the last frame as a Chrome trace, CPU encoding on one track
+    /// and GPU execution on another, with async compute on a third. Open it in chrome://tracing or
+    /// Perfetto. GPU times may come from a frame or two earlier.
+    pub fn write_chrome_trace(&self, path: &Path) -> Result<(), String> {
+        let passes = self.passes();
+        let us = |ms: f32| ms as f64 * 1000.0;
+        let mut trace_events = Vec::new();
+        for pass in &passes {
+            trace_events.push(TraceEvent {
+                name: &pass.label,
+                cat: "cpu",
+                ph: "X",
+                ts: us(pass.cpu_start_ms),
+                dur: us(pass.cpu_ms),
+                pid: 0,
+                tid: 0,
+            });
+            if let (Some(start), Some(ms)) = (pass.gpu_start_ms, pass.gpu_ms) {
+                trace_events.push(TraceEvent {
+                    name: &pass.label,
+                    cat: "gpu",
+                    ph: "X",
+                    ts: us(start),
+                    dur: us(ms),
+                    pid: 0,
+                    tid: if pass.async_compute { 2 } else { 1 },
+                });
+            }
+        }
+        let text = serde_json::to_string(&Trace { trace_events }).map_err(|e| e.to_string())?;
+        std::fs::write(path, text).map_err(|e| e.to_string())
+    }
+}
+
+/// How long `pass` ran on the GPU at the same time as the raster passes
+/// in `passes`. `None` without GPU times.
+pub fn overlap_ms(pass: &PassStats, passes: &[PassStats]) -> Option<f32> {
+    let (start, end) = pass.gpu_span()?;
+    let mut raster: Vec<_> = passes
+        .iter()
+        .filter(|p| !p.async_compute)
+        .filter_map(PassStats::gpu_span)
+        .collect();
+    raster.sort_by(|a, b| a.0.total_cmp(&b.0));
+    // merged, so passes that overlap each other aren't counted twice
+    let mut overlap = 0.0;
+    let mut covered = start;
+    for (from, to) in raster {
+        let (from, to) = (from.max(covered), to.min(end));
+        if to > from {
+            overlap += to - from;
+            covered = to;
+        }
+    }
+    Some(overlap)
+}
+
+/// Starts mapping a freshly copied readback, or reads one that's mapped.
+fn read_back(
+    timestamps: &mut Timestamps,
+    device: &wgpu::Device,
+    times: &mut Vec<(String, f32, f32)>,
+) {
+    match std::mem::replace(&mut timestamps.readback, Readback::Idle) {
+        Readback::Idle => {}
+        Readback::Copied(labels) => {
+            let mapped = Arc::new(Mutex::new(None));
+            let result = mapped.clone();
+            timestamps
+                .readback_buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |r| {
+                    *result.lock().unwrap() = Some(r.is_ok());
+                });
+            timestamps.readback = Readback::Mapping(labels, mapped);
+        }
+        Readback::Mapping(labels, mapped) => {
+            // never blocks; the times just arrive a frame or two late
+            let _ = device.poll(wgpu::PollType::Poll);
+            let result = *mapped.lock().unwrap();
+            match result {
+                None => timestamps.readback = Readback::Mapping(labels, mapped),
+                Some(false) => log::warn!("Failed to read back pass timestamps"),
+                Some(true) => {
+                    {
+                        let data = timestamps.readback_buffer.slice(..).get_mapped_range();
+                        let ticks: &[u64] = bytemuck::cast_slice(&data);
+                        let ms = |from: u64, to: u64| {
+                            to.wrapping_sub(from) as f32 * timestamps.period / 1_000_000.0
+                        };
+                        let first = ticks.first().copied().unwrap_or(0);
+                        times.clear();
+                        for (label, pair) in labels.iter().zip(ticks.chunks(2)) {
+                            times.push((label.clone(), ms(first, pair[0]), ms(pair[0], pair[1])));
+                        }
+                    }
+                    timestamps.readback_buffer.unmap();
+                }
+            }
+        }
+    }
+}
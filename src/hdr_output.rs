@@ -0,0 +1,420 @@
+//! HDR output for displays that take it. wgpu offers HDR swapchains as
+//! Rgba16Float in linear extended sRGB (scRGB, 1.0 at 80 nits); HDR10's
+//! PQ swapchains can't be asked for, so scRGB is the HDR path and every
+//! other surface stays SDR.
+//!
+//! With HDR on, the scene renders into a float target with 1.0 at paper
+//! white, and egui into an 8-bit one. [`HdrOutput::encode`] rolls the
+//! scene's highlights off at the display's peak, scales it to scRGB and
+//! composites the UI over it. Frame captures and screenshots get the same
+//! pass encoded to SDR instead.
+
+use crate::app::State;
+use crate::config::Config;
+use crate::transient::{TransientDesc, TransientTexture};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// The surface format HDR output needs.
+pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+/// What egui draws into with HDR on; it wants a non-sRGB 8-bit target.
+pub const UI_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8Unorm;
+/// Captures and screenshots, which expect 8-bit sRGB.
+const SDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+const SCRGB_WHITE_NITS: f32 = 80.0;
+
+const SHADER: &str = r#"
+struct Output {
+    // scene color to output
+    scale: f32,
+    // brightest scene color, relative to paper white
+    headroom: f32,
+    // UI color to output
+    ui_scale: f32,
+    ui_opacity: f32,
+};
+@group(0) @binding(0) var<uniform> output: Output;
+@group(0) @binding(1) var scene: texture_2d<f32>;
+@group(0) @binding(2) var ui: texture_2d<f32>;
+
+@vertex
+fn vsMain(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// unchanged up to the knee, then eases into the headroom
+fn rolloff(x: vec3<f32>, headroom: f32) -> vec3<f32> {
+    let knee = 0.8 * headroom;
+    let range = headroom - knee;
+    let eased = knee + range * (1.0 - exp(-(x - knee) / range));
+    return select(x, eased, x > vec3(knee));
+}
+
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    let low = c / 12.92;
+    let high = pow((c + 0.055) / 1.055, vec3(2.4));
+    return select(high, low, c <= vec3(0.04045));
+}
+
+@fragment
+fn psMain(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+    let p = vec2<i32>(pos.xy);
+    let s = textureLoad(scene, p, 0);
+    let u = textureLoad(ui, min(p, vec2<i32>(textureDimensions(ui)) - 1), 0) * output.ui_opacity;
+    let color = rolloff(max(s.rgb, vec3(0.0)), output.headroom) * output.scale;
+    // egui blends in sRGB; decode its unpremultiplied color
+    var ui_color = vec3(0.0);
+    if (u.a > 0.0) {
+        ui_color = srgb_to_linear(u.rgb / u.a) * u.a * output.ui_scale;
+    }
+    return vec4(color * (1.0 - u.a) + ui_color, s.a + u.a * (1.0 - s.a));
+}
+"#;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HdrSettings {
+    /// Read at startup, when the surface format is picked.
+    pub enabled: bool,
+    /// Nits for SDR white, i.e. 1.0 in the scene and the UI's white.
+    pub paper_white: f32,
+    /// The display's peak brightness in nits.
+    pub peak: f32,
+}
+
+impl Default for HdrSettings {
+    fn default() -> Self {
+        HdrSettings {
+            enabled: false,
+            paper_white: 200.0,
+            peak: 1000.0,
+        }
+    }
+}
+
+impl HdrSettings {
+    /// Brightest scene color the display shows, relative to paper white.
+    pub fn headroom(&self) -> f32 {
+        (self.peak / self.paper_white).max(1.0)
+    }
+}
+
+/// Whether a surface offering `formats` can show HDR.
+pub fn supported(formats: &[wgpu::TextureFormat]) -> bool {
+    formats.contains(&FORMAT)
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct OutputUniform {
+    scale: f32,
+    headroom: f32,
+    ui_scale: f32,
+    ui_opacity: f32,
+}
+
+/// Slots in the uniform buffer, one per way of encoding.
+const HDR_SLOT: u64 = 0;
+const SDR_SLOT: u64 = 256;
+const CLEAN_SLOT: u64 = 512;
+
+pub struct HdrOutput {
+    pub settings: HdrSettings,
+    layout: wgpu::BindGroupLayout,
+    hdr_pipeline: wgpu::RenderPipeline,
+    sdr_pipeline: wgpu::RenderPipeline,
+    buffer: wgpu::Buffer,
+    /// Stands in for the UI target in clean SDR encodes.
+    no_ui: wgpu::TextureView,
+}
+
+impl HdrOutput {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("HDR Output"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let texture = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("HDR Output"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                texture(1),
+                texture(2),
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("HDR Output"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |format: wgpu::TextureFormat| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("HDR Output"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &module,
+                    entry_point: Some("vsMain"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &module,
+                    entry_point: Some("psMain"),
+                    compilation_options: Default::default(),
+                    targets: &[Some(format.into())],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("HDR Output"),
+            size: CLEAN_SLOT + std::mem::size_of::<OutputUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let no_ui = device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("HDR Output No UI"),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: UI_FORMAT,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        HdrOutput {
+            settings: HdrSettings::default(),
+            hdr_pipeline: pipeline(FORMAT),
+            sdr_pipeline: pipeline(SDR_FORMAT),
+            layout,
+            buffer,
+            no_ui,
+        }
+    }
+
+    pub fn queue_uniforms(&self, queue: &wgpu::Queue) {
+        let paper_white = self.settings.paper_white / SCRGB_WHITE_NITS;
+        let slots = [
+            (
+                HDR_SLOT,
+                OutputUniform {
+                    scale: paper_white,
+                    headroom: self.settings.headroom(),
+                    ui_scale: paper_white,
+                    ui_opacity: 1.0,
+                },
+            ),
+            (
+                SDR_SLOT,
+                OutputUniform {
+                    scale: 1.0,
+                    headroom: 1.0,
+                    ui_scale: 1.0,
+                    ui_opacity: 1.0,
+                },
+            ),
+            (
+                CLEAN_SLOT,
+                OutputUniform {
+                    scale: 1.0,
+                    headroom: 1.0,
+                    ui_scale: 1.0,
+                    ui_opacity: 0.0,
+                },
+            ),
+        ];
+        for (offset, uniform) in slots {
+            queue.write_buffer(&self.buffer, offset, bytemuck::bytes_of(&uniform));
+        }
+    }
+
+    /// Where the scene renders this frame; in the surface's format, so
+    /// every pipeline made for the surface draws into it.
+    pub fn scene_target(&self, state: &State) -> Arc<TransientTexture> {
+        self.target(state, FORMAT)
+    }
+
+    /// Where egui draws this frame.
+    pub fn ui_target(&self, state: &State) -> Arc<TransientTexture> {
+        self.target(state, UI_FORMAT)
+    }
+
+    fn target(&self, state: &State, format: wgpu::TextureFormat) -> Arc<TransientTexture> {
+        let config = &state.surface_config;
+        state.transient.acquire(
+            &state.device,
+            TransientDesc {
+                width: config.width,
+                height: config.height,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::COPY_SRC,
+            },
+        )
+    }
+
+    /// Writes the scene and UI to the HDR surface.
+    pub fn encode(
+        &self,
+        state: &State,
+        encoder: &mut wgpu::CommandEncoder,
+        scene: &wgpu::TextureView,
+        ui: &wgpu::TextureView,
+        target: &wgpu::TextureView,
+    ) {
+        let bind_group = self.bind_group(state, HDR_SLOT, scene, ui);
+        self.draw(encoder, &self.hdr_pipeline, &bind_group, target);
+    }
+
+    /// Encodes the frame to SDR for captures and screenshots, with the UI
+    /// over it if given.
+    pub fn encode_sdr(
+        &self,
+        state: &State,
+        encoder: &mut wgpu::CommandEncoder,
+        scene: &wgpu::Texture,
+        ui: Option<&wgpu::TextureView>,
+    ) -> wgpu::Texture {
+        let texture = state.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("SDR Frame"),
+            size: scene.size(),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: SDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let scene = scene.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = match ui {
+            Some(ui) => self.bind_group(state, SDR_SLOT, &scene, ui),
+            None => self.bind_group(state, CLEAN_SLOT, &scene, &self.no_ui),
+        };
+        self.draw(encoder, &self.sdr_pipeline, &bind_group, &view);
+        texture
+    }
+
+    fn bind_group(
+        &self,
+        state: &State,
+        slot: u64,
+        scene: &wgpu::TextureView,
+        ui: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        state.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("HDR Output"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &self.buffer,
+                        offset: slot,
+                        size: wgpu::BufferSize::new(std::mem::size_of::<OutputUniform>() as u64),
+                    }),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(scene),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(ui),
+                },
+            ],
+        })
+    }
+
+    fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        pipeline: &wgpu::RenderPipeline,
+        bind_group: &wgpu::BindGroup,
+        target: &wgpu::TextureView,
+    ) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("HDR Output"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                depth_slice: None,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+pub fn ui(ui: &mut egui::Ui, state: &mut State, config: &mut Config) {
+    ui.collapsing("HDR Output", |ui| {
+        let settings = config.hdr.get_or_insert_with(HdrSettings::default);
+        ui.checkbox(&mut settings.enabled, "Enabled (save and restart)");
+        match &mut state.hdr {
+            Some(hdr) => {
+                ui.add(
+                    egui::Slider::new(&mut settings.paper_white, 80.0..=500.0)
+                        .text("Paper white (nits)"),
+                );
+                ui.add(
+                    egui::Slider::new(&mut settings.peak, 200.0..=4000.0)
+                        .logarithmic(true)
+                        .text("Peak (nits)"),
+                );
+                settings.peak = settings.peak.max(settings.paper_white);
+                hdr.settings = *settings;
+            }
+            None if supported(&state.surface_formats) => {
+                ui.label("Showing SDR.");
+            }
+            None => {
+                ui.label("This display's surface can't show HDR.");
+            }
+        }
+        if ui.button("Save").clicked() {
+            config.save();
+        }
+    });
+}
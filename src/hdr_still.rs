@@ -0,0 +1,188 @@
+//! HDR stills for documentation and comparisons: the scene's float buffer
+//! saved as a Radiance `.hdr`, next to PNGs of it bracketed at a few
+//! exposure values. The brackets are exposed from the one render instead of
+//! rendering again, so they line up exactly, and the `.hdr` keeps whatever
+//! every bracket clips.
+//!
+//! Only a float scene target holds more than 1.0, so stills need HDR output.
+
+use crate::hdr_output;
+use crate::headless::{self, Capture};
+use std::path::{Path, PathBuf};
+
+/// Exposure values bracketed when none are given: two stops either side.
+pub const DEFAULT_BRACKETS: [f32; 3] = [-2.0, 0.0, 2.0];
+const F16_MAX: f32 = 65504.0;
+
+/// Linear RGB pixels, row by row from the top; 1.0 is paper white.
+pub struct HdrImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[f32; 3]>,
+}
+
+impl HdrImage {
+    /// Copies an HDR output scene texture back, blocking until the GPU is
+    /// done. It needs `COPY_SRC`.
+    pub fn read(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture: &wgpu::Texture,
+    ) -> Result<Self, String> {
+        if texture.format() != hdr_output::FORMAT {
+            return Err(format!(
+                "HDR stills read {:?} targets, not {:?}",
+                hdr_output::FORMAT,
+                texture.format()
+            ));
+        }
+        let (width, height) = (texture.width(), texture.height());
+        let row = (width * 8).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("HDR Still"),
+            size: (row * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(row),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+        queue.submit(Some(encoder.finish()));
+
+        let bytes = headless::read_buffer(device, queue, &buffer);
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for row in bytes.chunks(row as usize) {
+            for texel in row[..width as usize * 8].chunks_exact(8) {
+                let [r, g, b, _]: [u16; 4] = bytemuck::pod_read_unaligned(texel);
+                // NaNs, infinities and negatives have no place in a still
+                pixels.push([r, g, b].map(|bits| match f16_to_f32(bits) {
+                    c if c.is_nan() => 0.0,
+                    c => c.clamp(0.0, F16_MAX),
+                }));
+            }
+        }
+        Ok(HdrImage {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// The image scaled by `2^ev`, clipped and encoded as 8-bit sRGB.
+    pub fn exposed(&self, ev: f32) -> Capture {
+        let scale = ev.exp2();
+        let pixels = self
+            .pixels
+            .iter()
+            .flat_map(|rgb| {
+                let [r, g, b] = rgb.map(|c| (linear_to_srgb(c * scale) * 255.0).round() as u8);
+                [r, g, b, 255]
+            })
+            .collect();
+        Capture {
+            width: self.width,
+            height: self.height,
+            pixels,
+        }
+    }
+
+    pub fn save_hdr(&self, path: impl AsRef<Path>) -> image::ImageResult<()> {
+        image::save_buffer_with_format(
+            path,
+            bytemuck::cast_slice(&self.pixels),
+            self.width,
+            self.height,
+            image::ExtendedColorType::Rgb32F,
+            image::ImageFormat::Hdr,
+        )
+    }
+
+    pub fn load_hdr(path: impl AsRef<Path>) -> image::ImageResult<Self> {
+        let image = image::open(path)?.into_rgb32f();
+        Ok(HdrImage {
+            width: image.width(),
+            height: image.height(),
+            pixels: bytemuck::cast_slice(&image.into_raw()).to_vec(),
+        })
+    }
+}
+
+/// A still to save at the end of the frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HdrStill {
+    /// Where to save, without an extension; the brackets add theirs.
+    pub path: PathBuf,
+    /// Exposure values to save PNGs at, in stops.
+    pub brackets: Vec<f32>,
+}
+
+impl HdrStill {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        HdrStill {
+            path: path.into(),
+            brackets: DEFAULT_BRACKETS.to_vec(),
+        }
+    }
+
+    /// `path.hdr`, then `path_ev-2.png` and so on for each bracket.
+    pub fn files(&self) -> Vec<PathBuf> {
+        let mut files = vec![self.path.with_extension("hdr")];
+        let stem = self.path.as_os_str().to_string_lossy();
+        for ev in &self.brackets {
+            files.push(PathBuf::from(format!("{stem}_ev{ev:+}.png")));
+        }
+        files
+    }
+
+    /// Writes every file in [`HdrStill::files`], returning them.
+    pub fn save(&self, image: &HdrImage) -> Result<Vec<PathBuf>, String> {
+        let files = self.files();
+        image
+            .save_hdr(&files[0])
+            .map_err(|e| format!("Failed to save {}: {e}", files[0].display()))?;
+        for (ev, file) in self.brackets.iter().zip(&files[1..]) {
+            image
+                .exposed(*ev)
+                .save_png(file)
+                .map_err(|e| format!("Failed to save {}: {e}", file.display()))?;
+        }
+        Ok(files)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    let c = c.clamp(0.0, 1.0);
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn f16_to_f32(bits: u16) -> f32 {
+    let exponent = (bits >> 10) & 0x1f;
+    let mantissa = (bits & 0x3ff) as f32 / 1024.0;
+    let magnitude = match exponent {
+        0 => mantissa * 2f32.powi(-14),
+        0x1f if mantissa == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1.0 + mantissa) * 2f32.powi(exponent as i32 - 15),
+    };
+    if bits & 0x8000 != 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
@@ -0,0 +1,226 @@
+use crate::app::State;
+use crate::hdr_still::HdrImage;
+use crate::world::World;
+use std::path::Path;
+
+/// Tightly packed RGBA8 pixels read back from the GPU.
+pub struct Capture {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Capture {
+    pub fn save_png(&self, path: impl AsRef<Path>) -> image::ImageResult<()> {
+        image::save_buffer(
+            path,
+            &self.pixels,
+            self.width,
+            self.height,
+            image::ExtendedColorType::Rgba8,
+        )
+    }
+
+    pub fn load_png(path: impl AsRef<Path>) -> image::ImageResult<Self> {
+        let image = image::open(path)?.into_rgba8();
+        Ok(Capture {
+            width: image.width(),
+            height: image.height(),
+            pixels: image.into_raw(),
+        })
+    }
+}
+
+/// Copies a 4-byte-per-pixel texture into a `Capture`, blocking until the
+/// GPU is done. BGRA textures are swizzled to RGBA.
+pub fn read_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> Capture {
+    let width = texture.width();
+    let height = texture.height();
+    let unpadded = width * 4;
+    let padded =
+        unpadded.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Readback Buffer"),
+        size: (padded * height) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder =
+        device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::TexelCopyBufferInfo {
+            buffer: &buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(padded),
+                rows_per_image: Some(height),
+            },
+        },
+        texture.size(),
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    slice.map_async(wgpu::MapMode::Read, |r| {
+        r.expect("Failed to map readback buffer")
+    });
+    device
+        .poll(wgpu::PollType::wait_indefinitely())
+        .expect("Failed to wait for readback");
+
+    let mut pixels = Vec::with_capacity((unpadded * height) as usize);
+    {
+        let data = slice.get_mapped_range();
+        for row in data.chunks(padded as usize) {
+            pixels.extend_from_slice(&row[..unpadded as usize]);
+        }
+    }
+    buffer.unmap();
+
+    if matches!(
+        texture.format(),
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+    ) {
+        for pixel in pixels.chunks_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    Capture {
+        width,
+        height,
+        pixels,
+    }
+}
+
+/// Copies a buffer made with `COPY_SRC` back to the CPU, blocking until
+/// the GPU is done.
+pub fn read_buffer(device: &wgpu::Device, queue: &wgpu::Queue, buffer: &wgpu::Buffer) -> Vec<u8> {
+    let readback = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Readback Buffer"),
+        size: buffer.size(),
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder =
+        device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    encoder.copy_buffer_to_buffer(buffer, 0, &readback, 0, buffer.size());
+    queue.submit(Some(encoder.finish()));
+
+    let slice = readback.slice(..);
+    slice.map_async(wgpu::MapMode::Read, |r| {
+        r.expect("Failed to map readback buffer")
+    });
+    device
+        .poll(wgpu::PollType::wait_indefinitely())
+        .expect("Failed to wait for readback");
+    let bytes = slice.get_mapped_range().to_vec();
+    readback.unmap();
+    bytes
+}
+
+/// Renders the world into an offscreen texture without a window.
+pub struct Headless {
+    pub state: State,
+    pub world: World,
+    target: wgpu::Texture,
+}
+
+impl Headless {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self::with_format(width, height, wgpu::TextureFormat::Rgba8UnormSrgb)
+    }
+
+    /// Renders into `format`, e.g. HDR output's float format for
+    /// [`Headless::render_hdr`].
+    pub fn with_format(width: u32, height: u32, format: wgpu::TextureFormat) -> Self {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let state = pollster::block_on(State::new_headless(&instance, width, height, format));
+        let world = World::new(&state);
+        let target = create_target(&state);
+
+        Headless {
+            state,
+            world,
+            target,
+        }
+    }
+
+    /// Like a window being resized; zero sizes are ignored as they are
+    /// there.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.state.resize_surface(width, height);
+        self.world.camera.resize(width, height);
+        self.target = create_target(&self.state);
+    }
+
+    pub fn render(&mut self) -> Capture {
+        self.draw();
+        read_texture(&self.state.device, &self.state.queue, &self.target)
+    }
+
+    /// Renders into a float target made with [`Headless::with_format`].
+    pub fn render_hdr(&mut self) -> Result<HdrImage, String> {
+        self.draw();
+        HdrImage::read(&self.state.device, &self.state.queue, &self.target)
+    }
+
+    fn draw(&mut self) {
+        // captures must not show fallback materials
+        for model in &self.world.models {
+            model.wait_for_pipeline();
+        }
+        self.world.camera.update_uniform();
+        self.world.update_visibility();
+        self.world.queue_uniforms(&self.state.queue);
+
+        let view = self
+            .target
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self
+            .state
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let clear = self.world.background.clear_color();
+            let mut renderpass = self.state.begin_scene_pass(&mut encoder, &view, clear);
+            self.world.render(&mut renderpass);
+        }
+        self.world
+            .debug_draw
+            .render(&self.state, &mut encoder, &view);
+        self.world.sprites.render(&self.state, &mut encoder, &view);
+        let world = &self.world;
+        world
+            .lens_flare
+            .render(&self.state, &mut encoder, &view, &world.camera, &world.sky);
+        self.world
+            .encode_capture(&self.state.device, &mut encoder, &self.target);
+        self.state.queue.submit(Some(encoder.finish()));
+        self.state.transient.end_frame();
+    }
+}
+
+fn create_target(state: &State) -> wgpu::Texture {
+    state.device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Headless Target"),
+        size: wgpu::Extent3d {
+            width: state.surface_config.width,
+            height: state.surface_config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: state.surface_config.format,
+        usage: state.surface_config.usage,
+        view_formats: &[],
+    })
+}
@@ -0,0 +1,176 @@
+//! UI strings by key, from `lang/<code>.toml`, switchable at runtime.
+//! English is compiled in and fills in whatever a translation lacks, so a
+//! partial one still shows every panel. The current table is reread when
+//! its file changes, so wording can be edited without a rebuild.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{OnceLock, RwLock};
+use std::time::SystemTime;
+
+const LANG_DIR: &str = "lang";
+pub const DEFAULT: &str = "en";
+const ENGLISH: &str = include_str!("../lang/en.toml");
+
+struct Table {
+    code: String,
+    strings: HashMap<String, String>,
+    /// Of the file it was read from; `None` if it wasn't a plain file.
+    modified: Option<SystemTime>,
+}
+
+static CURRENT: RwLock<Option<Table>> = RwLock::new(None);
+
+fn path(code: &str) -> PathBuf {
+    PathBuf::from(LANG_DIR).join(format!("{code}.toml"))
+}
+
+fn modified(code: &str) -> Option<SystemTime> {
+    std::fs::metadata(path(code))
+        .and_then(|m| m.modified())
+        .ok()
+}
+
+/// Nested tables become dotted keys: `[menu] file` is `menu.file`.
+fn parse(text: &str) -> Result<HashMap<String, String>, String> {
+    fn flatten(prefix: &str, table: toml::Table, strings: &mut HashMap<String, String>) {
+        for (key, value) in table {
+            let key = if prefix.is_empty() {
+                key
+            } else {
+                format!("{prefix}.{key}")
+            };
+            match value {
+                toml::Value::String(s) => {
+                    strings.insert(key, s);
+                }
+                toml::Value::Table(table) => flatten(&key, table, strings),
+                _ => log::warn!("{key}: UI strings must be strings"),
+            }
+        }
+    }
+    let table: toml::Table = toml::from_str(text).map_err(|e| e.to_string())?;
+    let mut strings = HashMap::new();
+    flatten("", table, &mut strings);
+    Ok(strings)
+}
+
+fn english() -> &'static HashMap<String, String> {
+    static TABLE: OnceLock<HashMap<String, String>> = OnceLock::new();
+    TABLE.get_or_init(|| parse(ENGLISH).expect("lang/en.toml is invalid"))
+}
+
+/// The string for `key` in the current language, falling back to English
+/// and then to the key itself.
+pub fn tr(key: &str) -> String {
+    let current = CURRENT.read().unwrap();
+    current
+        .as_ref()
+        .and_then(|table| table.strings.get(key))
+        .or_else(|| english().get(key))
+        .cloned()
+        .unwrap_or_else(|| key.to_string())
+}
+
+/// The current language's code.
+pub fn language() -> String {
+    CURRENT
+        .read()
+        .unwrap()
+        .as_ref()
+        .map_or(DEFAULT.to_string(), |table| table.code.clone())
+}
+
+/// Switches to `lang/<code>.toml`. English works without its file.
+pub fn set_language(code: &str) -> Result<(), String> {
+    let strings = match crate::vfs::read_to_string(path(code)) {
+        Ok(text) => parse(&text).map_err(|e| format!("{}: {e}", path(code).display()))?,
+        Err(_) if code == DEFAULT => HashMap::new(),
+        Err(e) => return Err(format!("{}: {e}", path(code).display())),
+    };
+    *CURRENT.write().unwrap() = Some(Table {
+        code: code.to_string(),
+        strings,
+        modified: modified(code),
+    });
+    Ok(())
+}
+
+/// Rereads the current table if its file changed since it was read.
+pub fn reload_if_changed() {
+    let (code, was) = match CURRENT.read().unwrap().as_ref() {
+        Some(table) => (table.code.clone(), table.modified),
+        None => return,
+    };
+    let now = modified(&code);
+    if now.is_some() && now != was {
+        match set_language(&code) {
+            Ok(()) => log::info!("Reloaded {}", path(&code).display()),
+            Err(e) => {
+                log::warn!("Failed to reload UI strings: {e}");
+                // don't retry until it changes again
+                if let Some(table) = CURRENT.write().unwrap().as_mut() {
+                    table.modified = now;
+                }
+            }
+        }
+    }
+}
+
+/// Codes and names of the languages in `lang/`, English first.
+pub fn available() -> Vec<(String, String)> {
+    let mut languages = vec![(DEFAULT.to_string(), english()["name"].clone())];
+    let Ok(entries) = std::fs::read_dir(LANG_DIR) else {
+        return languages;
+    };
+    let mut others: Vec<_> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "toml" {
+                return None;
+            }
+            let code = path.file_stem()?.to_str()?.to_string();
+            if code == DEFAULT {
+                return None;
+            }
+            let text = std::fs::read_to_string(&path).ok()?;
+            let name = parse(&text).ok()?.remove("name").unwrap_or(code.clone());
+            Some((code, name))
+        })
+        .collect();
+    others.sort();
+    languages.extend(others);
+    languages
+}
+
+/// A collapsing header titled with `key`'s string, keeping its open state
+/// across language switches.
+pub fn collapsing<R>(
+    ui: &mut egui::Ui,
+    key: &str,
+    add_contents: impl FnOnce(&mut egui::Ui) -> R,
+) -> egui::CollapsingResponse<R> {
+    egui::CollapsingHeader::new(tr(key))
+        .id_salt(key)
+        .show(ui, add_contents)
+}
+
+/// A language picker. Returns the code picked, if it changed and loaded.
+pub fn ui(ui: &mut egui::Ui) -> Option<String> {
+    let current = language();
+    let mut picked = None;
+    egui::ComboBox::from_label(tr("ui_theme.language"))
+        .selected_text(tr("name"))
+        .show_ui(ui, |ui| {
+            // only listed while open; it reads every file in `lang/`
+            for (code, name) in &available() {
+                if ui.selectable_label(*code == current, name).clicked() && *code != current {
+                    match set_language(code) {
+                        Ok(()) => picked = Some(code.clone()),
+                        Err(e) => log::warn!("Failed to switch language: {e}"),
+                    }
+                }
+            }
+        });
+    picked
+}
@@ -0,0 +1,326 @@
+use crate::mesh::{Vertex, VertexEncoding};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Which axis points up in the source file. The sandbox is Y-up.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UpAxis {
+    #[default]
+    Y,
+    Z,
+}
+
+/// Where vertex normals come from.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NormalMode {
+    /// The file's normals, or smooth ones when it has none.
+    #[default]
+    Auto,
+    /// Generated, averaged across edges sharper than `smoothing_angle`.
+    Smooth,
+    /// Generated, one per face.
+    Flat,
+}
+
+/// Per-file fixes applied while a mesh is loaded, before its buffers are
+/// created.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImportSettings {
+    /// Uniform scale, e.g. 0.01 for files authored in centimetres.
+    pub scale: f32,
+    pub up_axis: UpAxis,
+    /// Reverses triangle winding for files whose faces point inwards.
+    pub flip_winding: bool,
+    pub normals: NormalMode,
+    /// In degrees. Faces meeting at a sharper angle than this get a hard
+    /// edge when normals are generated smooth.
+    pub smoothing_angle: f32,
+    /// `Packed` trades normal and UV precision for smaller vertex buffers.
+    pub vertex_encoding: VertexEncoding,
+}
+
+impl Default for ImportSettings {
+    fn default() -> Self {
+        ImportSettings {
+            scale: 1.0,
+            up_axis: UpAxis::Y,
+            flip_winding: false,
+            normals: NormalMode::Auto,
+            smoothing_angle: 60.0,
+            vertex_encoding: VertexEncoding::Full,
+        }
+    }
+}
+
+impl ImportSettings {
+    pub fn is_identity(&self) -> bool {
+        *self == ImportSettings::default()
+    }
+
+    /// Rotates a direction into the sandbox's Y-up convention.
+    pub fn orient(&self, v: glam::Vec3) -> glam::Vec3 {
+        match self.up_axis {
+            UpAxis::Y => v,
+            UpAxis::Z => glam::vec3(v.x, v.z, -v.y),
+        }
+    }
+
+    /// Orients and scales a position.
+    pub fn point(&self, p: glam::Vec3) -> glam::Vec3 {
+        self.orient(p) * self.scale
+    }
+
+    /// `point` as a matrix.
+    pub fn matrix(&self) -> glam::Mat4 {
+        glam::Mat4::from_cols(
+            self.point(glam::Vec3::X).extend(0.0),
+            self.point(glam::Vec3::Y).extend(0.0),
+            self.point(glam::Vec3::Z).extend(0.0),
+            glam::Vec4::W,
+        )
+    }
+
+    /// `has_normals` is whether the file provided any; `triangles` is false
+    /// for points, lines and strips, which keep their winding and normals.
+    /// Returns, for each output vertex, the input vertex it came from when
+    /// normal generation had to split vertices, so other per-vertex data can
+    /// follow.
+    pub fn apply(
+        &self,
+        vertices: &mut Vec<Vertex>,
+        indices: &mut Vec<u32>,
+        has_normals: bool,
+        triangles: bool,
+    ) -> Option<Vec<u32>> {
+        for v in vertices.iter_mut() {
+            v.pos = self.point(v.pos.into()).into();
+            v.normal = self.orient(v.normal.into()).into();
+        }
+        if !triangles {
+            return None;
+        }
+        if self.flip_winding {
+            for triangle in indices.chunks_exact_mut(3) {
+                triangle.swap(1, 2);
+            }
+        }
+        let angle = match self.normals {
+            NormalMode::Auto if has_normals => return None,
+            NormalMode::Auto | NormalMode::Smooth => self.smoothing_angle,
+            NormalMode::Flat => 0.0,
+        };
+        let (generated, generated_indices, source) = generate_normals(vertices, indices, angle);
+        *vertices = generated;
+        *indices = generated_indices;
+        Some(source)
+    }
+}
+
+/// Area-weighted vertex normals that only average faces within `angle`
+/// degrees of each other. Vertices on a hard edge are split, so the result
+/// may have more vertices; the third vec maps each back to its input.
+/// Input that isn't a triangle list comes back unchanged.
+pub fn generate_normals(
+    vertices: &[Vertex],
+    indices: &[u32],
+    angle: f32,
+) -> (Vec<Vertex>, Vec<u32>, Vec<u32>) {
+    if !indices.len().is_multiple_of(3) || indices.iter().any(|&i| i as usize >= vertices.len()) {
+        let source = (0..vertices.len() as u32).collect();
+        return (vertices.to_vec(), indices.to_vec(), source);
+    }
+    let position = |i: u32| glam::Vec3::from(vertices[i as usize].pos);
+    // cross product length is twice the area, which is the weighting wanted
+    let faces: Vec<glam::Vec3> = indices
+        .chunks_exact(3)
+        .map(|t| (position(t[1]) - position(t[0])).cross(position(t[2]) - position(t[0])))
+        .collect();
+    let units: Vec<glam::Vec3> = faces.iter().map(|f| f.normalize_or_zero()).collect();
+
+    // corners sharing a position, whether or not they share an index
+    let mut corners_at: HashMap<[u32; 3], Vec<usize>> = HashMap::new();
+    for (corner, &i) in indices.iter().enumerate() {
+        let key = vertices[i as usize].pos.map(f32::to_bits);
+        corners_at.entry(key).or_default().push(corner);
+    }
+
+    let threshold = angle.to_radians().cos() - 1e-4;
+    let mut out_vertices = vec![];
+    let mut out_indices = Vec::with_capacity(indices.len());
+    let mut source = vec![];
+    let mut split: HashMap<(u32, [u32; 3]), u32> = HashMap::new();
+    for (corner, &i) in indices.iter().enumerate() {
+        let face = corner / 3;
+        let key = vertices[i as usize].pos.map(f32::to_bits);
+        let mut normal = glam::Vec3::ZERO;
+        for &other in &corners_at[&key] {
+            let other_face = other / 3;
+            if units[face].dot(units[other_face]) >= threshold {
+                normal += faces[other_face];
+            }
+        }
+        let normal = normal
+            .try_normalize()
+            .or(units[face].try_normalize())
+            .unwrap_or(glam::Vec3::Y);
+
+        let index = *split
+            .entry((i, normal.to_array().map(f32::to_bits)))
+            .or_insert_with(|| {
+                out_vertices.push(Vertex {
+                    normal: normal.into(),
+                    ..vertices[i as usize]
+                });
+                source.push(i);
+                out_vertices.len() as u32 - 1
+            });
+        out_indices.push(index);
+    }
+    (out_vertices, out_indices, source)
+}
+
+/// Scale, up axis and winding controls for one file's settings.
+pub fn settings_ui(ui: &mut egui::Ui, settings: &mut ImportSettings) {
+    ui.horizontal(|ui| {
+        ui.label("Scale");
+        ui.add(
+            egui::DragValue::new(&mut settings.scale)
+                .speed(0.001)
+                .range(0.0001..=10000.0),
+        );
+        for (label, scale) in [("×0.01", 0.01), ("×1", 1.0), ("×100", 100.0)] {
+            if ui.small_button(label).clicked() {
+                settings.scale = scale;
+            }
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Up axis");
+        ui.selectable_value(&mut settings.up_axis, UpAxis::Y, "Y-up");
+        ui.selectable_value(&mut settings.up_axis, UpAxis::Z, "Z-up");
+    });
+    ui.checkbox(&mut settings.flip_winding, "Flip winding");
+    ui.horizontal(|ui| {
+        ui.label("Normals");
+        ui.selectable_value(&mut settings.normals, NormalMode::Auto, "From file")
+            .on_hover_text("Smooth ones are generated when the file has none");
+        ui.selectable_value(&mut settings.normals, NormalMode::Smooth, "Smooth");
+        ui.selectable_value(&mut settings.normals, NormalMode::Flat, "Flat");
+    });
+    if settings.normals != NormalMode::Flat {
+        ui.add(
+            egui::Slider::new(&mut settings.smoothing_angle, 0.0..=180.0)
+                .suffix("°")
+                .text("Smoothing angle"),
+        );
+    }
+    ui.horizontal(|ui| {
+        ui.label("Vertices");
+        ui.selectable_value(&mut settings.vertex_encoding, VertexEncoding::Full, "Full");
+        ui.selectable_value(
+            &mut settings.vertex_encoding,
+            VertexEncoding::Packed,
+            "Packed",
+        )
+        .on_hover_text("Octahedral normals and half-float UVs: 24 instead of 40 bytes");
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertices(positions: &[[f32; 3]]) -> Vec<Vertex> {
+        positions
+            .iter()
+            .map(|&pos| Vertex {
+                pos,
+                normal: [0.0; 3],
+                uv: [0.0; 2],
+                uv1: [0.0; 2],
+            })
+            .collect()
+    }
+
+    /// Two triangles folded at a right angle along the Y axis, facing +Z
+    /// and +X.
+    fn fold() -> (Vec<Vertex>, Vec<u32>) {
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0],
+        ];
+        (vertices(&positions), vec![0, 2, 1, 0, 1, 3])
+    }
+
+    fn normals(vertices: &[Vertex], indices: &[u32]) -> Vec<glam::Vec3> {
+        indices
+            .iter()
+            .map(|&i| vertices[i as usize].normal.into())
+            .collect()
+    }
+
+    #[test]
+    fn flat_normals_split_every_shared_corner() {
+        let (vertices, indices) = fold();
+        let (out, out_indices, source) = generate_normals(&vertices, &indices, 0.0);
+        assert_eq!(out.len(), 6);
+        assert_eq!(source, [0, 2, 1, 0, 1, 3]);
+        let normals = normals(&out, &out_indices);
+        assert!(normals[..3].iter().all(|&n| n == glam::Vec3::Z));
+        assert!(normals[3..].iter().all(|&n| n == glam::Vec3::X));
+    }
+
+    #[test]
+    fn smooth_normals_average_across_soft_edges() {
+        let (vertices, indices) = fold();
+        let (out, out_indices, _) = generate_normals(&vertices, &indices, 100.0);
+        assert_eq!(out.len(), 4);
+        let normals = normals(&out, &out_indices);
+        let shared = glam::vec3(1.0, 0.0, 1.0).normalize();
+        assert!(normals[0].distance(shared) < 1e-5, "{}", normals[0]);
+        assert_eq!(normals[1], glam::Vec3::Z);
+        assert_eq!(normals[5], glam::Vec3::X);
+    }
+
+    #[test]
+    fn hard_edges_split_only_the_shared_vertices() {
+        let (vertices, indices) = fold();
+        let (out, out_indices, source) = generate_normals(&vertices, &indices, 60.0);
+        // the two on the fold each become two
+        assert_eq!(out.len(), 6);
+        assert_eq!(source.iter().filter(|&&i| i == 0).count(), 2);
+        assert_eq!(source.iter().filter(|&&i| i == 2).count(), 1);
+        let normals = normals(&out, &out_indices);
+        assert!(normals[..3].iter().all(|&n| n == glam::Vec3::Z));
+        assert!(normals[3..].iter().all(|&n| n == glam::Vec3::X));
+    }
+
+    #[test]
+    fn non_triangle_input_is_left_alone() {
+        let vertices = vertices(&[[0.0; 3], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]]);
+        let lines = [0, 1, 1, 2];
+        let (out, out_indices, source) = generate_normals(&vertices, &lines, 60.0);
+        assert_eq!(
+            bytemuck::cast_slice::<_, f32>(&out),
+            bytemuck::cast_slice::<_, f32>(&vertices)
+        );
+        assert_eq!(out_indices, lines);
+        assert_eq!(source, [0, 1, 2]);
+        // an index past the end
+        let (_, out_indices, _) = generate_normals(&vertices, &[0, 1, 3], 60.0);
+        assert_eq!(out_indices, [0, 1, 3]);
+
+        let mut settings_vertices = vertices.clone();
+        let mut settings_indices = lines.to_vec();
+        let settings = ImportSettings {
+            flip_winding: true,
+            ..Default::default()
+        };
+        let source = settings.apply(&mut settings_vertices, &mut settings_indices, false, false);
+        assert_eq!(source, None);
+        assert_eq!(settings_indices, lines);
+    }
+}
@@ -0,0 +1,273 @@
+//! Octahedral impostors: a mesh rendered once from a grid of directions
+//! over the upper hemisphere into one atlas, so distant copies can be drawn
+//! as a single quad showing the nearest baked view. Frames hold the mesh's
+//! local normals and coverage, and are lit where they're drawn.
+
+use crate::app::State;
+use crate::mesh::{Mesh, Vertex};
+
+/// Frames per atlas side; the bake's view matrices fill one uniform array.
+pub const MAX_FRAMES: u32 = 8;
+
+/// The hemi-octahedral mapping and frame layout, shared by the bake and
+/// the shaders that draw impostors.
+pub const WGSL: &str = r#"
+fn hemiOctDecode(uv: vec2<f32>) -> vec3<f32> {
+    let x = (uv.x + uv.y) * 0.5;
+    let z = (uv.x - uv.y) * 0.5;
+    return normalize(vec3(x, 1.0 - abs(x) - abs(z), z));
+}
+
+// below the horizon maps to the nearest horizon view
+fn hemiOctEncode(d: vec3<f32>) -> vec2<f32> {
+    let n = vec3(d.x, max(d.y, 0.0), d.z);
+    let p = n / max(abs(n.x) + n.y + abs(n.z), 1e-5);
+    return vec2(p.x + p.z, p.x - p.z);
+}
+
+// the frame's view of the mesh: right and up across the quad
+fn frameBasis(d: vec3<f32>) -> mat2x3<f32> {
+    var reference = vec3(0.0, 1.0, 0.0);
+    if (abs(d.y) > 0.99) {
+        reference = vec3(0.0, 0.0, 1.0);
+    }
+    let right = normalize(cross(-d, reference));
+    return mat2x3<f32>(right, cross(right, -d));
+}
+"#;
+
+const BAKE_SHADER: &str = r#"
+struct Views { view_proj: array<mat4x4<f32>, 64> };
+@group(0) @binding(0) var<uniform> views: Views;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+};
+
+@vertex
+fn vsMain(
+    @location(0) pos: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @builtin(instance_index) frame: u32,
+) -> VSOut {
+    var out: VSOut;
+    out.pos = views.view_proj[frame] * vec4(pos, 1.0);
+    out.normal = normal;
+    return out;
+}
+
+@fragment
+fn psMain(in: VSOut) -> @location(0) vec4<f32> {
+    return vec4(normalize(in.normal) * 0.5 + 0.5, 1.0);
+}
+"#;
+
+/// The view direction baked into frame `uv`, -1..1 across the atlas.
+pub fn frame_direction(uv: glam::Vec2) -> glam::Vec3 {
+    let x = (uv.x + uv.y) * 0.5;
+    let z = (uv.x - uv.y) * 0.5;
+    glam::vec3(x, 1.0 - x.abs() - z.abs(), z).normalize()
+}
+
+/// Local-space normals in RGB and coverage in alpha, `frames`² views of
+/// `frame_size` pixels each.
+pub struct ImpostorAtlas {
+    pub frames: u32,
+    pub frame_size: u32,
+    /// The local bounding sphere each frame is fitted to.
+    pub center: glam::Vec3,
+    pub radius: f32,
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+}
+
+impl ImpostorAtlas {
+    /// Renders every view of `mesh`, which must use the full vertex layout.
+    pub fn bake(state: &State, mesh: &Mesh, frames: u32, frame_size: u32) -> Self {
+        let device = &state.device;
+        let frames = frames.clamp(1, MAX_FRAMES);
+        let (min, max) = mesh.bounds();
+        let center = (min + max) * 0.5;
+        let radius = ((max - min).length() * 0.5).max(f32::EPSILON);
+
+        let mut views = [[[0.0f32; 4]; 4]; (MAX_FRAMES * MAX_FRAMES) as usize];
+        for j in 0..frames {
+            for i in 0..frames {
+                let uv = (glam::vec2(i as f32, j as f32) + 0.5) / frames as f32 * 2.0 - 1.0;
+                let direction = frame_direction(uv);
+                let reference = if direction.y.abs() > 0.99 {
+                    glam::Vec3::Z
+                } else {
+                    glam::Vec3::Y
+                };
+                let view =
+                    glam::Mat4::look_at_rh(center + direction * radius * 2.0, center, reference);
+                let projection = glam::Mat4::orthographic_rh(
+                    -radius,
+                    radius,
+                    -radius,
+                    radius,
+                    radius,
+                    radius * 3.0,
+                );
+                views[(j * frames + i) as usize] = (projection * view).to_cols_array_2d();
+            }
+        }
+        let views_buffer = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Impostor Views"),
+                contents: bytemuck::cast_slice(&views),
+                usage: wgpu::BufferUsages::UNIFORM,
+            },
+        );
+
+        let size = wgpu::Extent3d {
+            width: frames * frame_size,
+            height: frames * frame_size,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(&format!("{} Impostor", mesh.name)),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let depth = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Impostor Depth"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Depth32Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let depth_view = depth.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Impostor Bake"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Impostor Bake"),
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: views_buffer.as_entire_binding(),
+            }],
+        });
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Impostor Bake"),
+            source: wgpu::ShaderSource::Wgsl(BAKE_SHADER.into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Impostor Bake"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Impostor Bake"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vsMain"),
+                buffers: &[Vertex::layout()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("psMain"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::TextureFormat::Rgba8Unorm.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Impostor Bake"),
+        });
+        {
+            let mut renderpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Impostor Bake"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        // a flat normal, so filtering at the silhouette stays sane
+                        load: wgpu::LoadOp::Clear(wgpu::Color {
+                            r: 0.5,
+                            g: 1.0,
+                            b: 0.5,
+                            a: 0.0,
+                        }),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            renderpass.set_pipeline(&pipeline);
+            renderpass.set_bind_group(0, &bind_group, &[]);
+            renderpass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+            renderpass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            for j in 0..frames {
+                for i in 0..frames {
+                    let (x, y) = ((i * frame_size) as f32, (j * frame_size) as f32);
+                    let s = frame_size as f32;
+                    renderpass.set_viewport(x, y, s, s, 0.0, 1.0);
+                    let frame = j * frames + i;
+                    renderpass.draw_indexed(0..mesh.index_count, 0, frame..frame + 1);
+                }
+            }
+        }
+        state.queue.submit(Some(encoder.finish()));
+        log::info!(
+            "Baked {frames}x{frames} impostor views of {} at {frame_size} px",
+            mesh.name
+        );
+
+        ImpostorAtlas {
+            frames,
+            frame_size,
+            center,
+            radius,
+            texture,
+            view,
+        }
+    }
+}
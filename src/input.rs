@@ -0,0 +1,333 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use winit::event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    CameraForward,
+    CameraBack,
+    CameraLeft,
+    CameraRight,
+    CameraUp,
+    CameraDown,
+    CameraBoost,
+    CameraOrbit,
+    CameraPan,
+    CameraLook,
+    ToggleCameraMode,
+    ToggleDebugUi,
+    Select,
+    MultiSelect,
+    MoveSelection,
+}
+
+impl Action {
+    pub const ALL: [Action; 15] = [
+        Action::CameraForward,
+        Action::CameraBack,
+        Action::CameraLeft,
+        Action::CameraRight,
+        Action::CameraUp,
+        Action::CameraDown,
+        Action::CameraBoost,
+        Action::CameraOrbit,
+        Action::CameraPan,
+        Action::CameraLook,
+        Action::ToggleCameraMode,
+        Action::ToggleDebugUi,
+        Action::Select,
+        Action::MultiSelect,
+        Action::MoveSelection,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::CameraForward => "Camera forward",
+            Action::CameraBack => "Camera back",
+            Action::CameraLeft => "Camera left",
+            Action::CameraRight => "Camera right",
+            Action::CameraUp => "Camera up",
+            Action::CameraDown => "Camera down",
+            Action::CameraBoost => "Camera boost",
+            Action::CameraOrbit => "Camera orbit (drag)",
+            Action::CameraPan => "Camera pan (drag)",
+            Action::CameraLook => "Camera look (drag)",
+            Action::ToggleCameraMode => "Toggle camera mode",
+            Action::ToggleDebugUi => "Toggle debug UI",
+            Action::Select => "Select / pick",
+            Action::MultiSelect => "Add to selection (hold)",
+            Action::MoveSelection => "Move selection (hold, drag)",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+impl std::fmt::Display for Binding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Binding::Key(code) => write!(f, "{:?}", code),
+            Binding::Mouse(button) => write!(f, "Mouse {:?}", button),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct InputBindings {
+    map: BTreeMap<Action, Binding>,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        let map = BTreeMap::from([
+            (Action::CameraForward, Binding::Key(KeyCode::KeyW)),
+            (Action::CameraBack, Binding::Key(KeyCode::KeyS)),
+            (Action::CameraLeft, Binding::Key(KeyCode::KeyA)),
+            (Action::CameraRight, Binding::Key(KeyCode::KeyD)),
+            (Action::CameraUp, Binding::Key(KeyCode::KeyE)),
+            (Action::CameraDown, Binding::Key(KeyCode::KeyQ)),
+            (Action::CameraBoost, Binding::Key(KeyCode::ShiftLeft)),
+            (Action::CameraOrbit, Binding::Mouse(MouseButton::Left)),
+            (Action::CameraPan, Binding::Mouse(MouseButton::Middle)),
+            (Action::CameraLook, Binding::Mouse(MouseButton::Right)),
+            (Action::ToggleCameraMode, Binding::Key(KeyCode::KeyC)),
+            (Action::ToggleDebugUi, Binding::Key(KeyCode::F1)),
+            (Action::Select, Binding::Mouse(MouseButton::Left)),
+            (Action::MultiSelect, Binding::Key(KeyCode::ControlLeft)),
+            (Action::MoveSelection, Binding::Key(KeyCode::ShiftLeft)),
+        ]);
+        InputBindings { map }
+    }
+}
+
+impl InputBindings {
+    pub fn get(&self, action: Action) -> Option<Binding> {
+        self.map.get(&action).copied()
+    }
+
+    pub fn set(&mut self, action: Action, binding: Binding) {
+        self.map.insert(action, binding);
+    }
+
+    pub fn clear(&mut self, action: Action) {
+        self.map.remove(&action);
+    }
+
+    fn actions_for(&self, binding: Binding) -> impl Iterator<Item = Action> + '_ {
+        self.map
+            .iter()
+            .filter(move |(_, b)| **b == binding)
+            .map(|(a, _)| *a)
+    }
+}
+
+/// Input after UI filtering, in a form that can be recorded and replayed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum InputEvent {
+    Pressed(Binding),
+    Released(Binding),
+    CursorMoved([f32; 2]),
+    /// Cursor position changed without producing camera motion.
+    CursorWarped([f32; 2]),
+    CursorLeft,
+    Scroll(f32),
+    FocusLost,
+}
+
+/// Translates raw winit events into actions so systems never look at keycodes.
+pub struct Input {
+    pub bindings: InputBindings,
+    held: HashSet<Action>,
+    pressed: HashSet<Action>,
+    cursor: Option<glam::Vec2>,
+    cursor_delta: glam::Vec2,
+    scroll: f32,
+    rebinding: Option<Action>,
+    frame_events: Vec<InputEvent>,
+    scale_factor: f32,
+}
+
+impl Input {
+    pub fn new(bindings: InputBindings) -> Self {
+        Input {
+            bindings,
+            held: HashSet::new(),
+            pressed: HashSet::new(),
+            cursor: None,
+            cursor_delta: glam::Vec2::ZERO,
+            scroll: 0.0,
+            rebinding: None,
+            frame_events: vec![],
+            scale_factor: 1.0,
+        }
+    }
+
+    pub fn held(&self, action: Action) -> bool {
+        self.held.contains(&action)
+    }
+
+    /// True only on the frame the action's binding went down.
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    /// In logical pixels; multiply by the scale factor for framebuffer
+    /// coordinates.
+    pub fn cursor(&self) -> Option<glam::Vec2> {
+        self.cursor
+    }
+
+    /// Cursor position in framebuffer pixels, for picking and the like.
+    pub fn cursor_physical(&self) -> Option<glam::Vec2> {
+        self.cursor.map(|c| c * self.scale_factor)
+    }
+
+    pub fn cursor_delta(&self) -> glam::Vec2 {
+        self.cursor_delta
+    }
+
+    pub fn scroll(&self) -> f32 {
+        self.scroll
+    }
+
+    /// Cursor positions and pixel scrolling are converted to logical pixels,
+    /// so camera sensitivity doesn't change with the display's DPI.
+    pub fn set_scale_factor(&mut self, scale_factor: f32) {
+        self.scale_factor = scale_factor;
+    }
+
+    pub fn rebinding(&self) -> Option<Action> {
+        self.rebinding
+    }
+
+    /// The next key or mouse button press is assigned to `action`.
+    pub fn start_rebinding(&mut self, action: Action) {
+        self.rebinding = Some(action);
+    }
+
+    /// Clears per-frame state; call once after systems have read the input.
+    pub fn end_frame(&mut self) {
+        self.pressed.clear();
+        self.cursor_delta = glam::Vec2::ZERO;
+        self.scroll = 0.0;
+    }
+
+    /// Events egui consumed only release bindings and track the cursor, so
+    /// dragging a slider doesn't also move the camera.
+    pub fn handle_window_event(&mut self, event: &WindowEvent, consumed_by_ui: bool) {
+        let ignore_presses = consumed_by_ui && self.rebinding.is_none();
+        let input_event = match event {
+            WindowEvent::MouseInput { state, button, .. }
+                if !(ignore_presses && *state == ElementState::Pressed) =>
+            {
+                self.binding_event(Binding::Mouse(*button), *state)
+            }
+            WindowEvent::KeyboardInput { event, .. } => {
+                if event.repeat || (ignore_presses && event.state == ElementState::Pressed) {
+                    return;
+                }
+                match event.physical_key {
+                    PhysicalKey::Code(code) => self.binding_event(Binding::Key(code), event.state),
+                    _ => None,
+                }
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                let position = [
+                    position.x as f32 / self.scale_factor,
+                    position.y as f32 / self.scale_factor,
+                ];
+                if consumed_by_ui {
+                    Some(InputEvent::CursorWarped(position))
+                } else {
+                    Some(InputEvent::CursorMoved(position))
+                }
+            }
+            WindowEvent::CursorLeft { .. } => Some(InputEvent::CursorLeft),
+            WindowEvent::MouseWheel { delta, .. } if !consumed_by_ui => {
+                Some(InputEvent::Scroll(match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(p) => p.y as f32 / self.scale_factor / 40.0,
+                }))
+            }
+            WindowEvent::Focused(false) => Some(InputEvent::FocusLost),
+            _ => None,
+        };
+
+        if let Some(input_event) = input_event {
+            self.apply(input_event);
+            self.frame_events.push(input_event);
+        }
+    }
+
+    /// Updates action state from an already-filtered event. Live input goes
+    /// through `handle_window_event`; replays feed recorded events here.
+    pub fn apply(&mut self, event: InputEvent) {
+        match event {
+            InputEvent::Pressed(binding) => {
+                for action in self.bindings.actions_for(binding).collect::<Vec<_>>() {
+                    self.held.insert(action);
+                    self.pressed.insert(action);
+                }
+            }
+            InputEvent::Released(binding) => {
+                for action in self.bindings.actions_for(binding).collect::<Vec<_>>() {
+                    self.held.remove(&action);
+                }
+            }
+            InputEvent::CursorMoved(position) => {
+                let position = glam::Vec2::from(position);
+                if let Some(last) = self.cursor {
+                    self.cursor_delta += position - last;
+                }
+                self.cursor = Some(position);
+            }
+            InputEvent::CursorWarped(position) => {
+                self.cursor = Some(glam::Vec2::from(position));
+            }
+            InputEvent::CursorLeft => {
+                self.cursor = None;
+            }
+            InputEvent::Scroll(amount) => {
+                self.scroll += amount;
+            }
+            InputEvent::FocusLost => {
+                self.held.clear();
+            }
+        }
+    }
+
+    /// Events applied since the last call, for session recording.
+    pub fn take_frame_events(&mut self) -> Vec<InputEvent> {
+        std::mem::take(&mut self.frame_events)
+    }
+
+    /// Drops held actions and per-frame state, e.g. before a replay starts.
+    pub fn reset(&mut self) {
+        self.held.clear();
+        self.cursor = None;
+        self.frame_events.clear();
+        self.end_frame();
+    }
+
+    fn binding_event(&mut self, binding: Binding, state: ElementState) -> Option<InputEvent> {
+        if state == ElementState::Pressed {
+            if let Some(action) = self.rebinding.take() {
+                if binding != Binding::Key(KeyCode::Escape) {
+                    self.bindings.set(action, binding);
+                }
+                return None;
+            }
+        }
+
+        match state {
+            ElementState::Pressed => Some(InputEvent::Pressed(binding)),
+            ElementState::Released => Some(InputEvent::Released(binding)),
+        }
+    }
+}
@@ -0,0 +1,845 @@
+use crate::app::State;
+use crate::detachable::DetachableWindow;
+use crate::import::{self, ImportSettings};
+use crate::material::PrimitiveOptions;
+use crate::mesh::{Mesh, VertexEncoding};
+use crate::model::{EntityId, Model};
+use crate::sampler::SamplerDesc;
+use crate::selection::{self, Selection};
+use crate::shader;
+use crate::snapping::Snapping;
+use crate::transform::Transform;
+use crate::world::World;
+use std::sync::Arc;
+
+const AABB_COLOR: [f32; 4] = [0.2, 1.0, 0.3, 1.0];
+const NORMAL_COLOR: [f32; 4] = [0.3, 0.6, 1.0, 1.0];
+const TANGENT_COLOR: [f32; 4] = [1.0, 0.3, 0.3, 1.0];
+const BITANGENT_COLOR: [f32; 4] = [0.3, 1.0, 0.3, 1.0];
+const JOINT_COLOR: [f32; 4] = [1.0, 0.8, 0.1, 1.0];
+/// Normal and tangent lines beyond this are skipped on dense meshes.
+const MAX_VERTEX_LINES: usize = 20_000;
+
+/// Debug-draw overlays for the selected model, for diagnosing bad imports.
+#[derive(Default)]
+pub struct Overlays {
+    pub aabb: bool,
+    pub normals: bool,
+    pub tangents: bool,
+    pub joints: bool,
+    /// Length of normal and tangent lines, as a fraction of the bounds.
+    pub scale: f32,
+}
+
+/// Shows the model hierarchy and edits the selected model.
+pub struct Inspector {
+    pub overlays: Overlays,
+    /// Settings being edited for the selected model's file, applied with
+    /// Reimport.
+    reimport: Option<(String, ImportSettings)>,
+    group_edit: Option<GroupEdit>,
+}
+
+/// A group move, rotate or scale being dragged. egui keeps a dragged
+/// value for the whole drag, so the fields hold totals, and each frame
+/// reapplies them to the transforms the selection started with.
+struct GroupEdit {
+    pivot: glam::Vec3,
+    /// Of the selection's roots, taken on the first change.
+    start: Vec<(EntityId, Transform)>,
+    translation: glam::Vec3,
+    degrees: glam::Vec3,
+    scale: f32,
+}
+
+impl Default for Inspector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Inspector {
+    pub fn new() -> Self {
+        Inspector {
+            overlays: Overlays {
+                scale: 0.02,
+                ..Default::default()
+            },
+            reimport: None,
+            group_edit: None,
+        }
+    }
+
+    pub fn ui(&mut self, ctx: &egui::Context, state: &State, world: &mut World) {
+        DetachableWindow::new("Inspector")
+            .default_open(false)
+            .resizable(true)
+            .show(ctx, |ui| {
+                let mut edits = HierarchyEdits {
+                    selection: world.selection.clone(),
+                    toggled: vec![],
+                };
+                egui::ScrollArea::vertical()
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        let roots = world
+                            .models
+                            .iter()
+                            .filter(|m| m.parent.is_none_or(|p| world.model(p).is_none()));
+                        for model in roots {
+                            hierarchy_ui(ui, world, model, 0, &mut edits);
+                        }
+                    });
+                world.selection = edits.selection;
+                for id in edits.toggled {
+                    if let Some(model) = world.model_mut(id) {
+                        model.visible = !model.visible;
+                    }
+                }
+                world.update_visibility();
+                scene_stats_ui(ui, world);
+                ui.separator();
+
+                if world.selection.len() > 1 {
+                    group_ui(ui, world, &mut self.group_edit);
+                    return;
+                }
+                let Some(id) = world
+                    .selection
+                    .primary()
+                    .filter(|id| world.model(*id).is_some())
+                else {
+                    ui.label("Nothing selected");
+                    return;
+                };
+                parent_ui(ui, world, id);
+                let snapping = world.snapping;
+                model_ui(ui, state, &snapping, world.model_mut(id).unwrap());
+                mesh_stats_ui(ui, world, id);
+                self.reimport_ui(ui, state, world, id);
+                ui.separator();
+                overlays_ui(ui, &mut self.overlays, world.model(id).unwrap());
+            });
+
+        // drawn while the window is collapsed too
+        if let Some(id) = world.selection.primary() {
+            draw_overlays(&self.overlays, world, id);
+        }
+    }
+}
+
+impl Inspector {
+    fn reimport_ui(&mut self, ui: &mut egui::Ui, state: &State, world: &mut World, id: EntityId) {
+        let Some(source) = world.model(id).and_then(|m| m.mesh.source.clone()) else {
+            return;
+        };
+        if self
+            .reimport
+            .as_ref()
+            .is_none_or(|(path, _)| *path != source.path)
+        {
+            self.reimport = Some((source.path.clone(), world.import_settings(&source.path)));
+        }
+        let (path, settings) = self.reimport.as_mut().unwrap();
+        ui.separator();
+        egui::CollapsingHeader::new("Import").show(ui, |ui| {
+            ui.label(path.as_str());
+            import::settings_ui(ui, settings);
+            let changed = *settings != world.import_settings(path);
+            if ui
+                .add_enabled(changed, egui::Button::new("Reimport"))
+                .on_hover_text("Reloads the file for every model that uses it")
+                .clicked()
+            {
+                world.import_settings.insert(path.clone(), *settings);
+                world.reimport(state, path);
+            }
+        });
+    }
+}
+
+fn mib(bytes: u64) -> String {
+    if bytes < 1024 * 1024 {
+        format!("{:.1} KiB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.2} MiB", bytes as f64 / (1024.0 * 1024.0))
+    }
+}
+
+fn mesh_bytes(mesh: &Mesh) -> u64 {
+    mesh.vertex_buffer.size() + mesh.index_buffer.size()
+}
+
+/// How many models draw with this exact mesh.
+fn mesh_users(world: &World, mesh: &Arc<Mesh>) -> usize {
+    world
+        .models
+        .iter()
+        .filter(|m| Arc::ptr_eq(&m.mesh, mesh))
+        .count()
+}
+
+fn mesh_stats_ui(ui: &mut egui::Ui, world: &World, id: EntityId) {
+    let Some(model) = world.model(id) else {
+        return;
+    };
+    let mesh = &model.mesh;
+    egui::CollapsingHeader::new("Mesh stats").show(ui, |ui| {
+        egui::Grid::new("mesh_stats").num_columns(2).show(ui, |ui| {
+            ui.label("Vertices");
+            ui.label(mesh.positions.len().to_string());
+            ui.end_row();
+            ui.label("Indices");
+            ui.label(format!(
+                "{} ({} triangles)",
+                mesh.index_count,
+                mesh.index_count / 3
+            ));
+            ui.end_row();
+            ui.label("Attributes");
+            ui.label(mesh.attributes.join(", "));
+            ui.end_row();
+            ui.label("Vertex buffer");
+            ui.label(format!(
+                "{} ({} B stride)",
+                mib(mesh.vertex_buffer.size()),
+                mesh.encoding.stride()
+            ));
+            ui.end_row();
+            ui.label("Encoding");
+            ui.label(match mesh.encoding {
+                VertexEncoding::Full => "full".to_string(),
+                VertexEncoding::Packed => format!("packed, {} saved", mib(mesh.packed_savings())),
+            });
+            ui.end_row();
+            ui.label("Index buffer");
+            ui.label(format!("{} (u32)", mib(mesh.index_buffer.size())));
+            ui.end_row();
+            ui.label("Shared by");
+            let users = mesh_users(world, mesh);
+            ui.label(if users > 1 {
+                format!("{users} models")
+            } else {
+                "this model only".to_string()
+            });
+            ui.end_row();
+        });
+    });
+}
+
+/// Totals over every model, counting each shared mesh's buffers once.
+fn scene_stats_ui(ui: &mut egui::Ui, world: &World) {
+    let mut unique: Vec<&Arc<Mesh>> = vec![];
+    let (mut vertices, mut triangles, mut drawn_bytes) = (0, 0, 0);
+    for model in &world.models {
+        vertices += model.mesh.positions.len();
+        triangles += model.mesh.index_count as usize / 3;
+        drawn_bytes += mesh_bytes(&model.mesh);
+        if !unique.iter().any(|m| Arc::ptr_eq(m, &model.mesh)) {
+            unique.push(&model.mesh);
+        }
+    }
+    let resident: u64 = unique.iter().map(|m| mesh_bytes(m)).sum();
+    let packed: u64 = unique.iter().map(|m| m.packed_savings()).sum();
+    egui::CollapsingHeader::new("Scene stats").show(ui, |ui| {
+        ui.label(format!(
+            "{} models, {} unique meshes",
+            world.models.len(),
+            unique.len()
+        ));
+        ui.label(format!("{vertices} vertices, {triangles} triangles drawn"));
+        ui.label(format!(
+            "Mesh buffers: {} ({} saved by sharing)",
+            mib(resident),
+            mib(drawn_bytes - resident)
+        ));
+        if packed > 0 {
+            ui.label(format!("{} saved by packed vertices", mib(packed)));
+        }
+        egui::ScrollArea::vertical()
+            .id_salt("scene_meshes")
+            .max_height(120.0)
+            .show(ui, |ui| {
+                egui::Grid::new("scene_meshes")
+                    .num_columns(4)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Mesh");
+                        ui.strong("Vertices");
+                        ui.strong("Size");
+                        ui.strong("Users");
+                        ui.end_row();
+                        for mesh in &unique {
+                            ui.label(&mesh.name);
+                            ui.label(mesh.positions.len().to_string());
+                            ui.label(mib(mesh_bytes(mesh)));
+                            ui.label(mesh_users(world, mesh).to_string());
+                            ui.end_row();
+                        }
+                    });
+            });
+    });
+}
+
+fn overlays_ui(ui: &mut egui::Ui, overlays: &mut Overlays, model: &Model) {
+    ui.label("Overlays");
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut overlays.aabb, "Bounds");
+        ui.checkbox(&mut overlays.normals, "Normals");
+        ui.add_enabled_ui(!model.mesh.tangents.is_empty(), |ui| {
+            ui.checkbox(&mut overlays.tangents, "Tangents")
+                .on_disabled_hover_text("This mesh has no tangents");
+        });
+        ui.add_enabled_ui(!model.mesh.joints.is_empty(), |ui| {
+            ui.checkbox(&mut overlays.joints, "Joints")
+                .on_disabled_hover_text("This mesh has no skin");
+        });
+    });
+    if overlays.normals || overlays.tangents {
+        ui.add(
+            egui::Slider::new(&mut overlays.scale, 0.001..=0.2)
+                .logarithmic(true)
+                .text("Line length"),
+        );
+    }
+}
+
+fn draw_overlays(overlays: &Overlays, world: &mut World, id: EntityId) {
+    let Some(model) = world.models.iter().find(|m| m.id == id) else {
+        return;
+    };
+    let draw = &mut world.debug_draw;
+    let mesh = &model.mesh;
+    let matrix = model.global_matrix();
+    let (min, max) = mesh.bounds();
+
+    if overlays.aabb {
+        draw.aabb(min, max, matrix, AABB_COLOR);
+        let (scale, ..) = matrix.to_scale_rotation_translation();
+        let size = (max - min) * scale;
+        draw.text(
+            matrix.transform_point3(max),
+            format!("{:.2} x {:.2} x {:.2}", size.x, size.y, size.z),
+            AABB_COLOR,
+        );
+    }
+
+    let length = (max - min).length() * overlays.scale;
+    let normal_matrix = glam::Mat3::from_mat4(matrix).inverse().transpose();
+    let step = mesh.positions.len().div_ceil(MAX_VERTEX_LINES).max(1);
+    if overlays.normals {
+        for (p, n) in mesh.positions.iter().zip(&mesh.normals).step_by(step) {
+            let p = matrix.transform_point3(*p);
+            let n = (normal_matrix * *n).normalize_or_zero();
+            draw.line(p, p + n * length, NORMAL_COLOR);
+        }
+    }
+    if overlays.tangents {
+        let frames = mesh.positions.iter().zip(&mesh.normals).zip(&mesh.tangents);
+        for ((p, n), t) in frames.step_by(step) {
+            let p = matrix.transform_point3(*p);
+            let n = (normal_matrix * *n).normalize_or_zero();
+            let tangent = matrix.transform_vector3(t.truncate()).normalize_or_zero();
+            // w flips the bitangent for mirrored UVs
+            let bitangent = n.cross(tangent) * t.w;
+            draw.line(p, p + tangent * length, TANGENT_COLOR);
+            draw.line(p, p + bitangent * length, BITANGENT_COLOR);
+        }
+    }
+    if overlays.joints {
+        let joints: Vec<glam::Vec3> = mesh
+            .joints
+            .iter()
+            .map(|j| matrix.transform_point3(*j))
+            .collect();
+        let size = (max - min).length() * 0.01;
+        for &joint in &joints {
+            draw.cross(joint, size, JOINT_COLOR);
+        }
+        for &[a, b] in &mesh.bones {
+            if let (Some(&a), Some(&b)) = (joints.get(a), joints.get(b)) {
+                draw.line(a, b, JOINT_COLOR);
+            }
+        }
+    }
+}
+
+struct HierarchyEdits {
+    selection: Selection,
+    toggled: Vec<EntityId>,
+}
+
+/// One row per model, children indented under their parent. Models hidden
+/// only through an ancestor are greyed out.
+fn hierarchy_ui(
+    ui: &mut egui::Ui,
+    world: &World,
+    model: &Model,
+    depth: usize,
+    edits: &mut HierarchyEdits,
+) {
+    ui.horizontal(|ui| {
+        ui.add_space(depth as f32 * 12.0);
+        let mut visible = model.visible;
+        if ui
+            .checkbox(&mut visible, "")
+            .on_hover_text("Visible")
+            .changed()
+        {
+            edits.toggled.push(model.id);
+        }
+        let label = format!("{} #{}", model.name, model.id);
+        let label = if model.visible && !model.is_visible() {
+            egui::RichText::new(label).weak()
+        } else {
+            egui::RichText::new(label)
+        };
+        if ui
+            .selectable_label(edits.selection.contains(model.id), label)
+            .clicked()
+        {
+            if ui.input(|i| i.modifiers.command) {
+                edits.selection.toggle(model.id);
+            } else {
+                edits.selection.set(Some(model.id));
+            }
+        }
+    });
+    // `set_parent` rules out cycles, so depth is bounded by the model count
+    if depth < world.models.len() {
+        for child in world.children(model.id) {
+            hierarchy_ui(ui, world, child, depth + 1, edits);
+        }
+    }
+}
+
+/// Edits for a multi-selection: a transform about the group's pivot, and
+/// the values the selected models share.
+fn group_ui(ui: &mut egui::Ui, world: &mut World, edit: &mut Option<GroupEdit>) {
+    ui.label(format!(
+        "{} models selected; Ctrl+click to add or remove",
+        world.selection.len()
+    ));
+    if let Some(pivot) = selection::pivot(world) {
+        group_transform_ui(ui, world, pivot, edit);
+    }
+    ui.separator();
+    shared_values_ui(ui, world);
+}
+
+/// Drag to move, rotate or scale the selection about `pivot`. The fields
+/// are totals for the edit in progress and read zero between edits. The
+/// move and rotation snap when snapping is on.
+fn group_transform_ui(
+    ui: &mut egui::Ui,
+    world: &mut World,
+    pivot: glam::Vec3,
+    edit: &mut Option<GroupEdit>,
+) {
+    ui.label(format!(
+        "Pivot: {:.2}, {:.2}, {:.2}",
+        pivot.x, pivot.y, pivot.z
+    ));
+    let mut current = edit.take().unwrap_or(GroupEdit {
+        pivot,
+        start: vec![],
+        translation: glam::Vec3::ZERO,
+        degrees: glam::Vec3::ZERO,
+        scale: 1.0,
+    });
+    let mut changed = false;
+    // dragged or being typed into
+    let mut active = false;
+    let mut track = |response: egui::Response| {
+        changed |= response.changed();
+        active |= response.dragged() || response.has_focus();
+    };
+    ui.horizontal(|ui| {
+        ui.label("Move");
+        for axis in 0..3 {
+            track(ui.add(egui::DragValue::new(&mut current.translation[axis]).speed(0.1)));
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Rotate");
+        for axis in 0..3 {
+            track(ui.add(egui::DragValue::new(&mut current.degrees[axis]).suffix("°")));
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Scale");
+        track(
+            ui.add(
+                egui::DragValue::new(&mut current.scale)
+                    .speed(0.01)
+                    .range(0.01..=100.0),
+            ),
+        );
+    });
+    if changed {
+        if current.start.is_empty() {
+            current.start = selection::roots(world)
+                .into_iter()
+                .filter_map(|id| world.model(id).map(|m| (id, m.transform)))
+                .collect();
+        }
+        for &(id, transform) in &current.start {
+            if let Some(model) = world.model_mut(id) {
+                model.transform = transform;
+            }
+        }
+        let snapping = world.snapping;
+        let [x, y, z] = current
+            .degrees
+            .to_array()
+            .map(|d| snapping.snap_degrees(d).to_radians());
+        let delta = Transform {
+            translation: snapping.snap_position(current.translation),
+            rotation: glam::Quat::from_euler(glam::EulerRot::XYZ, x, y, z),
+            scale: glam::Vec3::splat(current.scale),
+        };
+        selection::transform_group(world, current.pivot, delta);
+    }
+    *edit = active.then_some(current);
+}
+
+/// Each value starts from the primary model's; "mixed" marks values the
+/// selection doesn't share. Changing one sets it on every selected model.
+fn shared_values_ui(ui: &mut egui::Ui, world: &mut World) {
+    let snapping = world.snapping;
+    let ids = world.selection.ids().to_vec();
+    let models: Vec<&Model> = ids.iter().filter_map(|&id| world.model(id)).collect();
+    let Some(primary) = models.last() else {
+        return;
+    };
+    let mixed = |value: &dyn Fn(&Model) -> bool| !models.iter().all(|m| value(m));
+
+    let mut visible = primary.visible;
+    let visible_mixed = mixed(&|m| m.visible == primary.visible);
+    let mut base_color = primary.base_color;
+    let color_mixed = mixed(&|m| m.base_color == primary.base_color);
+    let mut translation = primary.transform.translation;
+    let mut scale = primary.transform.scale;
+    let translation_mixed: [bool; 3] = std::array::from_fn(|axis| {
+        mixed(&|m| m.transform.translation[axis] == primary.transform.translation[axis])
+    });
+    let scale_mixed: [bool; 3] = std::array::from_fn(|axis| {
+        mixed(&|m| m.transform.scale[axis] == primary.transform.scale[axis])
+    });
+
+    let mixed_label = |ui: &mut egui::Ui, mixed: bool| {
+        if mixed {
+            ui.weak("mixed");
+        }
+    };
+    let visible_changed = ui
+        .add(egui::Checkbox::new(&mut visible, "Visible").indeterminate(visible_mixed))
+        .changed();
+    let mut color_changed = false;
+    ui.horizontal(|ui| {
+        ui.label("Base color");
+        color_changed = ui
+            .color_edit_button_rgba_unmultiplied(&mut base_color)
+            .changed();
+        mixed_label(ui, color_mixed);
+    });
+    let mut translation_changed = [false; 3];
+    let mut scale_changed = [false; 3];
+    for (label, values, mixed, changed, speed) in [
+        (
+            "Translation",
+            &mut translation,
+            translation_mixed,
+            &mut translation_changed,
+            0.1,
+        ),
+        ("Scale", &mut scale, scale_mixed, &mut scale_changed, 0.01),
+    ] {
+        ui.horizontal(|ui| {
+            ui.label(label);
+            for axis in 0..3 {
+                let prefix = if mixed[axis] { "~" } else { "" };
+                changed[axis] = ui
+                    .add(
+                        egui::DragValue::new(&mut values[axis])
+                            .speed(speed)
+                            .prefix(prefix),
+                    )
+                    .on_hover_text(if mixed[axis] { "Mixed" } else { "Shared" })
+                    .changed();
+            }
+        });
+    }
+
+    for id in ids {
+        let Some(model) = world.model_mut(id) else {
+            continue;
+        };
+        if visible_changed {
+            model.visible = visible;
+        }
+        if color_changed {
+            model.base_color = base_color;
+        }
+        for axis in 0..3 {
+            if translation_changed[axis] {
+                model.transform.translation[axis] = snapping.snap_length(translation[axis]);
+            }
+            if scale_changed[axis] {
+                model.transform.scale[axis] = scale[axis];
+            }
+        }
+    }
+}
+
+fn parent_ui(ui: &mut egui::Ui, world: &mut World, id: EntityId) {
+    let parent = world.model(id).and_then(|m| m.parent);
+    let name = |world: &World, id: Option<EntityId>| {
+        id.and_then(|id| world.model(id))
+            .map_or("None".to_string(), |m| format!("{} #{}", m.name, m.id))
+    };
+    let mut chosen = parent;
+    egui::ComboBox::from_label("Parent")
+        .selected_text(name(world, parent))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut chosen, None, "None");
+            for model in world.models.iter().filter(|m| m.id != id) {
+                ui.selectable_value(&mut chosen, Some(model.id), name(world, Some(model.id)));
+            }
+        });
+    if chosen != parent && !world.set_parent(id, chosen) {
+        log::warn!("Can't parent #{id} under its own descendant");
+    }
+}
+
+fn model_ui(ui: &mut egui::Ui, state: &State, snapping: &Snapping, model: &mut Model) {
+    ui.text_edit_singleline(&mut model.name);
+    ui.label(format!("Mesh: {}", model.mesh.name));
+
+    let t = &mut model.transform;
+    ui.horizontal(|ui| {
+        ui.label("Translation");
+        for axis in 0..3 {
+            // egui keeps the unsnapped value while dragging, so this steps
+            if ui
+                .add(egui::DragValue::new(&mut t.translation[axis]).speed(0.1))
+                .changed()
+            {
+                t.translation[axis] = snapping.snap_length(t.translation[axis]);
+            }
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Scale");
+        ui.add(egui::DragValue::new(&mut t.scale.x).speed(0.01));
+        ui.add(egui::DragValue::new(&mut t.scale.y).speed(0.01));
+        ui.add(egui::DragValue::new(&mut t.scale.z).speed(0.01));
+    });
+
+    let mut masked = model.alpha_cutoff.is_some();
+    ui.horizontal(|ui| {
+        if ui.checkbox(&mut masked, "Alpha cutoff").changed() {
+            model.alpha_cutoff = masked.then_some(0.5);
+        }
+        if let Some(cutoff) = model.alpha_cutoff.as_mut() {
+            ui.add(egui::Slider::new(cutoff, 0.0..=1.0));
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Base color");
+        ui.color_edit_button_rgba_unmultiplied(&mut model.base_color);
+    });
+
+    ui.collapsing("Primitive", |ui| {
+        let mut primitive = model.primitive();
+        if primitive_ui(ui, &mut primitive, state.caps.wireframe) {
+            model.set_primitive(&state.device, primitive);
+        }
+    });
+
+    shader_ui(ui, state, model);
+
+    let instances = Arc::strong_count(model.material().template());
+    ui.horizontal(|ui| {
+        ui.label(format!("Material template used by {instances} instance(s)"));
+        if ui
+            .button("Make unique")
+            .on_hover_text("Give this model its own instance, so sampler edits stay on it")
+            .clicked()
+        {
+            let material = model.material().duplicate(state);
+            model.set_material(&state.device, material);
+        }
+    });
+
+    let textures = model.material().textures();
+    if !textures.is_empty() {
+        ui.collapsing("Samplers", |ui| {
+            ui.label("Shared by every model with this material instance.");
+            for (i, texture) in textures.iter().enumerate() {
+                ui.push_id(i, |ui| {
+                    ui.label(format!(
+                        "Group {}, binding {}",
+                        texture.group, texture.binding
+                    ));
+                    let mut desc = texture.sampler;
+                    let filterable = texture.texture.filterable(&state.device);
+                    if sampler_ui(ui, &mut desc, filterable) {
+                        model.material().set_sampler(state, i, desc);
+                    }
+                });
+            }
+        });
+    }
+}
+
+/// Returns true when a setting changed. Filters are locked to nearest on
+/// non-filterable textures, and only comparison samplers show a compare
+/// function.
+fn sampler_ui(ui: &mut egui::Ui, desc: &mut SamplerDesc, filterable: bool) -> bool {
+    let before = *desc;
+    for (label, filter) in [
+        ("Mag filter", &mut desc.mag_filter),
+        ("Min filter", &mut desc.min_filter),
+        ("Mip filter", &mut desc.mipmap_filter),
+    ] {
+        ui.add_enabled_ui(filterable, |ui| {
+            egui::ComboBox::from_label(label)
+                .selected_text(format!("{filter:?}"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(filter, wgpu::FilterMode::Nearest, "Nearest");
+                    ui.selectable_value(filter, wgpu::FilterMode::Linear, "Linear");
+                });
+        });
+    }
+    for (label, address_mode) in [
+        ("Address U", &mut desc.address_mode_u),
+        ("Address V", &mut desc.address_mode_v),
+        ("Address W", &mut desc.address_mode_w),
+    ] {
+        egui::ComboBox::from_label(label)
+            .selected_text(format!("{address_mode:?}"))
+            .show_ui(ui, |ui| {
+                use wgpu::AddressMode as A;
+                for mode in [A::Repeat, A::MirrorRepeat, A::ClampToEdge] {
+                    ui.selectable_value(address_mode, mode, format!("{mode:?}"));
+                }
+            });
+    }
+    ui.add_enabled(
+        filterable,
+        egui::Slider::new(&mut desc.anisotropy, 1..=16).text("Anisotropy"),
+    );
+    if let Some(compare) = desc.compare.as_mut() {
+        egui::ComboBox::from_label("Compare")
+            .selected_text(format!("{compare:?}"))
+            .show_ui(ui, |ui| {
+                use wgpu::CompareFunction as C;
+                for function in [
+                    C::Never,
+                    C::Less,
+                    C::Equal,
+                    C::LessEqual,
+                    C::Greater,
+                    C::NotEqual,
+                    C::GreaterEqual,
+                    C::Always,
+                ] {
+                    ui.selectable_value(compare, function, format!("{function:?}"));
+                }
+            });
+    }
+    *desc != before
+}
+
+/// Picks the shader the model's material draws with, from the material's
+/// own and those in `shaders/`. The model gets an instance of its own, so
+/// others sharing the material keep theirs.
+fn shader_ui(ui: &mut egui::Ui, state: &State, model: &mut Model) {
+    const OWN: &str = "Material's own";
+    let current = model.material().shader_name().map(str::to_string);
+    let mut choice = None;
+    egui::ComboBox::from_label("Shader")
+        .selected_text(current.as_deref().unwrap_or(OWN))
+        .show_ui(ui, |ui| {
+            if ui.selectable_label(current.is_none(), OWN).clicked() {
+                choice = Some(None);
+            }
+            for name in shader::available() {
+                let selected = current.as_deref() == Some(name.as_str());
+                if ui.selectable_label(selected, &name).clicked() {
+                    choice = Some(Some(name));
+                }
+            }
+        });
+    let Some(choice) = choice.filter(|choice| *choice != current) else {
+        return;
+    };
+    let material = match choice {
+        None => Ok(model.material().with_own_shader(state)),
+        Some(name) => shader::load_named(&name)
+            .and_then(|shader| model.material().with_shader(state, &name, &shader)),
+    };
+    match material {
+        Ok(material) => model.set_material(&state.device, material),
+        Err(e) => log::warn!("{e}"),
+    }
+}
+
+/// Returns true when an option changed. The wireframe toggle is disabled
+/// without device support for line polygon mode.
+fn primitive_ui(
+    ui: &mut egui::Ui,
+    options: &mut PrimitiveOptions,
+    wireframe_supported: bool,
+) -> bool {
+    let before = *options;
+    egui::ComboBox::from_label("Cull mode")
+        .selected_text(format!("{:?}", options.cull_mode))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut options.cull_mode, None, "None (double sided)");
+            ui.selectable_value(&mut options.cull_mode, Some(wgpu::Face::Back), "Back");
+            ui.selectable_value(&mut options.cull_mode, Some(wgpu::Face::Front), "Front");
+        });
+    egui::ComboBox::from_label("Front face")
+        .selected_text(format!("{:?}", options.front_face))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut options.front_face, wgpu::FrontFace::Ccw, "Ccw");
+            ui.selectable_value(&mut options.front_face, wgpu::FrontFace::Cw, "Cw");
+        });
+    egui::ComboBox::from_label("Topology")
+        .selected_text(format!("{:?}", options.topology))
+        .show_ui(ui, |ui| {
+            use wgpu::PrimitiveTopology as T;
+            for topology in [
+                T::TriangleList,
+                T::TriangleStrip,
+                T::LineList,
+                T::LineStrip,
+                T::PointList,
+            ] {
+                ui.selectable_value(&mut options.topology, topology, format!("{topology:?}"));
+            }
+        });
+    let mut wireframe = options.polygon_mode == wgpu::PolygonMode::Line;
+    if ui
+        .add_enabled(
+            wireframe_supported,
+            egui::Checkbox::new(&mut wireframe, "Wireframe"),
+        )
+        .changed()
+    {
+        options.polygon_mode = if wireframe {
+            wgpu::PolygonMode::Line
+        } else {
+            wgpu::PolygonMode::Fill
+        };
+    }
+    ui.add(egui::DragValue::new(&mut options.depth_bias).prefix("Depth bias: "));
+    ui.add(
+        egui::DragValue::new(&mut options.depth_bias_slope_scale)
+            .speed(0.01)
+            .prefix("Slope scale: "),
+    );
+    *options != before
+}
@@ -0,0 +1,103 @@
+//! A small pool for CPU-side asset processing. `map` splits a batch over
+//! scoped worker threads and reports progress that the UI can show while
+//! loads run in the background.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// A batch being worked on.
+pub struct Progress {
+    pub label: String,
+    pub total: usize,
+    done: AtomicUsize,
+}
+
+impl Progress {
+    pub fn done(&self) -> usize {
+        self.done.load(Ordering::Relaxed)
+    }
+}
+
+static RUNNING: Mutex<Vec<Arc<Progress>>> = Mutex::new(Vec::new());
+
+/// Batches being worked on, oldest first.
+pub fn running() -> Vec<Arc<Progress>> {
+    RUNNING.lock().unwrap().clone()
+}
+
+/// Threads a batch is split over, leaving one for the frame.
+pub fn workers() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get().saturating_sub(1).max(1))
+}
+
+/// Runs `f` over `items` on up to `workers()` threads and returns the
+/// results in order. Blocks until the batch is done.
+pub fn map<T: Sync, R: Send>(label: &str, items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let progress = Arc::new(Progress {
+        label: label.to_string(),
+        total: items.len(),
+        done: AtomicUsize::new(0),
+    });
+    RUNNING.lock().unwrap().push(progress.clone());
+
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+    let work = || loop {
+        let i = next.fetch_add(1, Ordering::Relaxed);
+        let Some(item) = items.get(i) else {
+            break;
+        };
+        let result = f(item);
+        results.lock().unwrap()[i] = Some(result);
+        progress.done.fetch_add(1, Ordering::Relaxed);
+    };
+    let threads = workers().min(items.len());
+    if threads <= 1 {
+        work();
+    } else {
+        std::thread::scope(|scope| {
+            for _ in 0..threads {
+                scope.spawn(work);
+            }
+        });
+    }
+
+    RUNNING
+        .lock()
+        .unwrap()
+        .retain(|p| !Arc::ptr_eq(p, &progress));
+    // every index was taken exactly once
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Progress bars for running batches, in the corner above the shader
+/// compiling indicator.
+pub fn indicator_ui(ctx: &egui::Context) {
+    let running = running();
+    if running.is_empty() {
+        return;
+    }
+    egui::Area::new(egui::Id::new("running_jobs"))
+        .anchor(egui::Align2::RIGHT_BOTTOM, [-8.0, -32.0])
+        .interactable(false)
+        .show(ctx, |ui| {
+            for progress in &running {
+                let fraction = progress.done() as f32 / progress.total.max(1) as f32;
+                ui.add(
+                    egui::ProgressBar::new(fraction)
+                        .desired_width(240.0)
+                        .text(format!(
+                            "{} ({}/{})",
+                            progress.label,
+                            progress.done(),
+                            progress.total
+                        )),
+                );
+            }
+        });
+}
@@ -0,0 +1,729 @@
+//! The compute counterpart to the shader playground: a compute shader from
+//! a file, dispatched over buffers and textures set up in the window, with
+//! what they hold afterwards drawn as plots or images. Resources keep their
+//! contents between dispatches, so kernels can iterate on them.
+
+use crate::app::State;
+use crate::headless::{read_buffer, read_texture, Capture};
+use crate::plugin::Plugin;
+use crate::shader;
+use crate::shader_playground::{error_ui, file_ui};
+use crate::world::World;
+use std::time::SystemTime;
+use wgpu::naga;
+use wgpu::util::DeviceExt;
+
+/// The file the playground starts with.
+pub const DEFAULT_PATH: &str = "playground/kernel.wgsl";
+
+/// Written to a missing file from the window, to start from.
+const STARTER: &str = include_str!("../playground/kernel.wgsl");
+
+/// Storage textures are always this format, which every device can write.
+const TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+/// Largest buffer the window offers, in elements.
+const MAX_ELEMENTS: u32 = 1 << 22;
+
+/// Largest texture side the window offers.
+const MAX_TEXTURE_SIZE: u32 = 2048;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Element {
+    F32,
+    U32,
+    I32,
+}
+
+impl Element {
+    pub fn wgsl(self) -> &'static str {
+        match self {
+            Element::F32 => "f32",
+            Element::U32 => "u32",
+            Element::I32 => "i32",
+        }
+    }
+}
+
+/// What a buffer holds before the first dispatch and after a reset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fill {
+    Zeros,
+    /// Element `i` is `i`.
+    Index,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceKind {
+    /// A `read_write` storage array.
+    Buffer {
+        element: Element,
+        len: u32,
+        fill: Fill,
+    },
+    /// An rgba8unorm storage texture, write-only.
+    Texture { width: u32, height: u32 },
+}
+
+/// How a buffer's values are drawn; textures are always images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferView {
+    /// Value over index.
+    Plot,
+    /// Rows of `width` values, black to white from the smallest to the
+    /// largest.
+    Image { width: u32 },
+}
+
+/// One binding in group 0, at its index in `KernelPlayground::resources`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resource {
+    /// For the window; the shader names its own.
+    pub name: String,
+    pub kind: ResourceKind,
+    pub view: BufferView,
+}
+
+impl Resource {
+    pub fn buffer(name: &str, element: Element, len: u32) -> Self {
+        Resource {
+            name: name.to_string(),
+            kind: ResourceKind::Buffer {
+                element,
+                len,
+                fill: Fill::Zeros,
+            },
+            view: BufferView::Plot,
+        }
+    }
+
+    pub fn texture(name: &str, width: u32, height: u32) -> Self {
+        Resource {
+            name: name.to_string(),
+            kind: ResourceKind::Texture { width, height },
+            view: BufferView::Plot,
+        }
+    }
+}
+
+/// What a resource held after the last dispatch.
+pub enum Output {
+    /// Buffer elements, as floats for drawing.
+    Values(Vec<f32>),
+    Pixels(Capture),
+}
+
+enum Allocation {
+    Buffer(wgpu::Buffer),
+    Texture(wgpu::Texture),
+}
+
+struct Compiled {
+    pipeline: wgpu::ComputePipeline,
+    /// The resource kinds the layout was made for.
+    kinds: Vec<ResourceKind>,
+}
+
+pub struct KernelPlayground {
+    pub path: String,
+    pub resources: Vec<Resource>,
+    pub workgroups: [u32; 3],
+    /// Dispatches every frame while the window is open, rather than on
+    /// request. Each dispatch waits for its readback.
+    pub continuous: bool,
+    /// Of the last successful read, for recompiling when the resources
+    /// change.
+    source: Option<String>,
+    /// Of the file when it was last read, `None` until it has been;
+    /// missing files have no time.
+    modified: Option<Option<SystemTime>>,
+    compiled: Option<Compiled>,
+    /// GPU resources in `resources` order, made for `allocated_kinds`.
+    allocated: Vec<Allocation>,
+    allocated_kinds: Vec<ResourceKind>,
+    outputs: Vec<Option<Output>>,
+    /// Uploaded outputs, and the view they were drawn for.
+    previews: Vec<Option<(egui::TextureHandle, BufferView)>>,
+    dispatches: u64,
+    error: Option<String>,
+}
+
+impl Default for KernelPlayground {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KernelPlayground {
+    /// Set up for the starter kernel.
+    pub fn new() -> Self {
+        KernelPlayground {
+            path: DEFAULT_PATH.to_string(),
+            resources: vec![
+                Resource::buffer("values", Element::F32, 256),
+                Resource::texture("image", 256, 256),
+            ],
+            workgroups: [32, 32, 1],
+            continuous: false,
+            source: None,
+            modified: None,
+            compiled: None,
+            allocated: vec![],
+            allocated_kinds: vec![],
+            outputs: vec![],
+            previews: vec![],
+            dispatches: 0,
+            error: None,
+        }
+    }
+
+    /// The last compile, read or dispatch error.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// What `resources[index]` held after the last dispatch.
+    pub fn output(&self, index: usize) -> Option<&Output> {
+        self.outputs.get(index)?.as_ref()
+    }
+
+    fn kinds(&self) -> Vec<ResourceKind> {
+        self.resources.iter().map(|r| r.kind).collect()
+    }
+
+    /// Rereads the file if it changed since it was last read.
+    pub fn poll(&mut self, device: &wgpu::Device) {
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|m| m.modified())
+            .ok();
+        if self.modified == Some(modified) {
+            return;
+        }
+        self.modified = Some(modified);
+        self.reload(device);
+    }
+
+    /// Reads and compiles the file, keeping the previous pipeline when it
+    /// fails.
+    pub fn reload(&mut self, device: &wgpu::Device) {
+        match std::fs::read_to_string(&self.path) {
+            Ok(source) => {
+                self.source = Some(source);
+                self.recompile(device);
+            }
+            Err(e) => self.error = Some(format!("{}: {e}", self.path)),
+        }
+    }
+
+    fn recompile(&mut self, device: &wgpu::Device) {
+        let Some(source) = &self.source else {
+            return;
+        };
+        match self.compile(device, source) {
+            Ok(compiled) => {
+                log::info!("Compiled {}", self.path);
+                self.compiled = Some(compiled);
+                self.error = None;
+            }
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    /// A pipeline for the first compute entry point, laid out for the
+    /// current resources.
+    fn compile(&self, device: &wgpu::Device, source: &str) -> Result<Compiled, String> {
+        let module = shader::validate_wgsl(source, &self.path)?;
+        let entry_point = module
+            .entry_points
+            .iter()
+            .find(|e| e.stage == naga::ShaderStage::Compute)
+            .ok_or_else(|| format!("{} has no @compute entry point", self.path))?
+            .name
+            .clone();
+
+        let kinds = self.kinds();
+        let entries: Vec<_> = kinds
+            .iter()
+            .enumerate()
+            .map(|(i, kind)| wgpu::BindGroupLayoutEntry {
+                binding: i as u32,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: match kind {
+                    ResourceKind::Buffer { .. } => wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    ResourceKind::Texture { .. } => wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: TEXTURE_FORMAT,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                },
+                count: None,
+            })
+            .collect();
+
+        // bindings the shader declares differently from the window
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Kernel Playground"),
+            entries: &entries,
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Kernel Playground"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Kernel Playground"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Kernel Playground"),
+            layout: Some(&layout),
+            module: &module,
+            entry_point: Some(&entry_point),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        match pollster::block_on(device.pop_error_scope()) {
+            Some(error) => Err(error.to_string()),
+            None => Ok(Compiled { pipeline, kinds }),
+        }
+    }
+
+    /// Remakes every resource with its initial contents.
+    pub fn reset(&mut self, device: &wgpu::Device) {
+        self.allocated = self
+            .resources
+            .iter()
+            .map(|resource| match resource.kind {
+                ResourceKind::Buffer { element, len, fill } => {
+                    let contents = fill_bytes(element, len, fill);
+                    Allocation::Buffer(device.create_buffer_init(
+                        &wgpu::util::BufferInitDescriptor {
+                            label: Some(&resource.name),
+                            contents: &contents,
+                            usage: wgpu::BufferUsages::STORAGE
+                                | wgpu::BufferUsages::COPY_SRC
+                                | wgpu::BufferUsages::COPY_DST,
+                        },
+                    ))
+                }
+                ResourceKind::Texture { width, height } => {
+                    Allocation::Texture(device.create_texture(&wgpu::TextureDescriptor {
+                        label: Some(&resource.name),
+                        size: wgpu::Extent3d {
+                            width,
+                            height,
+                            depth_or_array_layers: 1,
+                        },
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        dimension: wgpu::TextureDimension::D2,
+                        format: TEXTURE_FORMAT,
+                        usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::COPY_SRC,
+                        view_formats: &[],
+                    }))
+                }
+            })
+            .collect();
+        self.allocated_kinds = self.kinds();
+        self.outputs = self.resources.iter().map(|_| None).collect();
+        self.previews = self.resources.iter().map(|_| None).collect();
+        self.dispatches = 0;
+    }
+
+    /// Runs the kernel once over `workgroups` and reads every resource
+    /// back, remaking resources and the pipeline first if the resources
+    /// were edited.
+    pub fn dispatch(&mut self, state: &State) {
+        let device = &state.device;
+        if self.allocated_kinds != self.kinds() {
+            self.reset(device);
+        }
+        if self
+            .compiled
+            .as_ref()
+            .is_some_and(|c| c.kinds != self.allocated_kinds)
+        {
+            self.recompile(device);
+        }
+        let Some(compiled) = &self.compiled else {
+            return;
+        };
+        if compiled.kinds != self.allocated_kinds {
+            // the recompile failed; its error is showing
+            return;
+        }
+
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let views: Vec<_> = self
+            .allocated
+            .iter()
+            .map(|allocation| match allocation {
+                Allocation::Texture(texture) => Some(texture.create_view(&Default::default())),
+                Allocation::Buffer(_) => None,
+            })
+            .collect();
+        let entries: Vec<_> = self
+            .allocated
+            .iter()
+            .zip(&views)
+            .enumerate()
+            .map(|(i, (allocation, view))| wgpu::BindGroupEntry {
+                binding: i as u32,
+                resource: match (allocation, view) {
+                    (Allocation::Buffer(buffer), _) => buffer.as_entire_binding(),
+                    (_, Some(view)) => wgpu::BindingResource::TextureView(view),
+                    (Allocation::Texture(_), None) => unreachable!(),
+                },
+            })
+            .collect();
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Kernel Playground"),
+            layout: &compiled.pipeline.get_bind_group_layout(0),
+            entries: &entries,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Kernel Playground"),
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Kernel Playground"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&compiled.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            let [x, y, z] = self.workgroups;
+            pass.dispatch_workgroups(x, y, z);
+        }
+        state.queue.submit(Some(encoder.finish()));
+        if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+            self.error = Some(error.to_string());
+            return;
+        }
+
+        self.outputs = self
+            .allocated
+            .iter()
+            .zip(&self.allocated_kinds)
+            .map(|(allocation, kind)| {
+                Some(match (allocation, kind) {
+                    (Allocation::Buffer(buffer), ResourceKind::Buffer { element, .. }) => {
+                        let bytes = read_buffer(device, &state.queue, buffer);
+                        Output::Values(decode(&bytes, *element))
+                    }
+                    (Allocation::Texture(texture), _) => {
+                        Output::Pixels(read_texture(device, &state.queue, texture))
+                    }
+                    (Allocation::Buffer(_), ResourceKind::Texture { .. }) => unreachable!(),
+                })
+            })
+            .collect();
+        self.previews = self.resources.iter().map(|_| None).collect();
+        self.dispatches += 1;
+        self.error = None;
+    }
+
+    fn resources_ui(&mut self, ui: &mut egui::Ui) {
+        let mut remove = None;
+        for (i, resource) in self.resources.iter_mut().enumerate() {
+            ui.push_id(i, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!("@binding({i})"));
+                    ui.add(egui::TextEdit::singleline(&mut resource.name).desired_width(80.0));
+                    match &mut resource.kind {
+                        ResourceKind::Buffer { element, len, fill } => {
+                            egui::ComboBox::from_id_salt("element")
+                                .width(48.0)
+                                .selected_text(element.wgsl())
+                                .show_ui(ui, |ui| {
+                                    for option in [Element::F32, Element::U32, Element::I32] {
+                                        ui.selectable_value(element, option, option.wgsl());
+                                    }
+                                });
+                            ui.add(
+                                egui::DragValue::new(len)
+                                    .range(1..=MAX_ELEMENTS)
+                                    .suffix(" elements"),
+                            );
+                            egui::ComboBox::from_id_salt("fill")
+                                .width(64.0)
+                                .selected_text(format!("{fill:?}"))
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(fill, Fill::Zeros, "Zeros");
+                                    ui.selectable_value(fill, Fill::Index, "Index");
+                                });
+                        }
+                        ResourceKind::Texture { width, height } => {
+                            ui.label("rgba8unorm");
+                            ui.add(egui::DragValue::new(width).range(1..=MAX_TEXTURE_SIZE));
+                            ui.label("×");
+                            ui.add(egui::DragValue::new(height).range(1..=MAX_TEXTURE_SIZE));
+                        }
+                    }
+                    if ui.small_button("✖").on_hover_text("Remove").clicked() {
+                        remove = Some(i);
+                    }
+                });
+            });
+        }
+        if let Some(i) = remove {
+            self.resources.remove(i);
+        }
+        ui.horizontal(|ui| {
+            if ui.button("Add buffer").clicked() {
+                let name = format!("buffer{}", self.resources.len());
+                self.resources
+                    .push(Resource::buffer(&name, Element::F32, 256));
+            }
+            if ui.button("Add texture").clicked() {
+                let name = format!("texture{}", self.resources.len());
+                self.resources.push(Resource::texture(&name, 256, 256));
+            }
+        });
+    }
+
+    fn outputs_ui(&mut self, ui: &mut egui::Ui) {
+        for (i, resource) in self.resources.iter_mut().enumerate() {
+            // edited since the dispatch, so the output is of something else
+            if self.allocated_kinds.get(i) != Some(&resource.kind) {
+                continue;
+            }
+            let Some(output) = self.outputs.get(i).and_then(Option::as_ref) else {
+                continue;
+            };
+            ui.push_id(i, |ui| {
+                ui.separator();
+                match output {
+                    Output::Values(values) => {
+                        ui.horizontal(|ui| {
+                            ui.strong(&resource.name);
+                            let mut image = matches!(resource.view, BufferView::Image { .. });
+                            ui.radio_value(&mut image, false, "Plot");
+                            ui.radio_value(&mut image, true, "Image");
+                            match (&mut resource.view, image) {
+                                (BufferView::Image { width }, true) => {
+                                    ui.add(
+                                        egui::DragValue::new(width)
+                                            .range(1..=MAX_TEXTURE_SIZE)
+                                            .prefix("width "),
+                                    );
+                                }
+                                (view, true) => {
+                                    let width = (values.len() as f32).sqrt().ceil() as u32;
+                                    *view = BufferView::Image { width };
+                                }
+                                (view, false) => *view = BufferView::Plot,
+                            }
+                        });
+                        ui.label(summary(values));
+                        if let BufferView::Image { width } = resource.view {
+                            let texture = preview(ui, &mut self.previews[i], resource, || {
+                                values_image(values, width as usize)
+                            });
+                            image_ui(ui, &texture);
+                        } else {
+                            plot_ui(ui, values);
+                        }
+                    }
+                    Output::Pixels(capture) => {
+                        ui.strong(&resource.name);
+                        let texture = preview(ui, &mut self.previews[i], resource, || {
+                            egui::ColorImage::from_rgba_unmultiplied(
+                                [capture.width as usize, capture.height as usize],
+                                &capture.pixels,
+                            )
+                        });
+                        image_ui(ui, &texture);
+                    }
+                }
+            });
+        }
+    }
+}
+
+impl Plugin for KernelPlayground {
+    fn name(&self) -> &str {
+        "Kernel Playground"
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, state: &State, _world: &mut World) {
+        egui::Window::new("Kernel Playground")
+            .default_open(false)
+            .resizable(true)
+            .show(ctx, |ui| {
+                if !state.caps.compute {
+                    ui.label("This device has no compute shaders.");
+                    return;
+                }
+                // only watched while open
+                self.poll(&state.device);
+                match file_ui(ui, &mut self.path, STARTER) {
+                    Ok(true) => self.modified = None,
+                    Ok(false) => {}
+                    Err(e) => self.error = Some(e),
+                }
+                ui.collapsing("Resources", |ui| self.resources_ui(ui));
+                ui.horizontal(|ui| {
+                    ui.label("Workgroups");
+                    for count in &mut self.workgroups {
+                        ui.add(egui::DragValue::new(count).range(1..=65535));
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let dispatch = ui.button("Dispatch").clicked();
+                    ui.checkbox(&mut self.continuous, "Every frame");
+                    if ui
+                        .button("Reset")
+                        .on_hover_text("Remake the resources with their initial contents")
+                        .clicked()
+                    {
+                        self.reset(&state.device);
+                    }
+                    if dispatch || self.continuous {
+                        self.dispatch(state);
+                    }
+                    ui.label(format!("{} dispatches", self.dispatches));
+                });
+                if let Some(error) = &self.error {
+                    error_ui(ui, error);
+                }
+                egui::ScrollArea::vertical().show(ui, |ui| self.outputs_ui(ui));
+            });
+    }
+}
+
+fn fill_bytes(element: Element, len: u32, fill: Fill) -> Vec<u8> {
+    match (fill, element) {
+        (Fill::Zeros, _) => vec![0; len as usize * 4],
+        (Fill::Index, Element::F32) => {
+            let values: Vec<f32> = (0..len).map(|i| i as f32).collect();
+            bytemuck::cast_slice(&values).to_vec()
+        }
+        (Fill::Index, Element::U32 | Element::I32) => {
+            let values: Vec<u32> = (0..len).collect();
+            bytemuck::cast_slice(&values).to_vec()
+        }
+    }
+}
+
+fn decode(bytes: &[u8], element: Element) -> Vec<f32> {
+    let words = bytes.chunks_exact(4).map(|w| w.try_into().unwrap());
+    match element {
+        Element::F32 => words.map(f32::from_le_bytes).collect(),
+        Element::U32 => words.map(|w| u32::from_le_bytes(w) as f32).collect(),
+        Element::I32 => words.map(|w| i32::from_le_bytes(w) as f32).collect(),
+    }
+}
+
+/// Smallest and largest finite values.
+fn range(values: &[f32]) -> (f32, f32) {
+    values
+        .iter()
+        .filter(|v| v.is_finite())
+        .fold((f32::MAX, f32::MIN), |(lo, hi), &v| (lo.min(v), hi.max(v)))
+}
+
+fn summary(values: &[f32]) -> String {
+    let (min, max) = range(values);
+    let finite = values.iter().filter(|v| v.is_finite()).count();
+    let mean = values.iter().filter(|v| v.is_finite()).sum::<f32>() / finite.max(1) as f32;
+    let mut text = format!("min {min:.4}, max {max:.4}, mean {mean:.4}");
+    if finite < values.len() {
+        text += &format!(", {} not finite", values.len() - finite);
+    }
+    text
+}
+
+/// Value over index, scaled to the values' range. Long buffers are drawn
+/// with a point per couple of pixels.
+fn plot_ui(ui: &mut egui::Ui, values: &[f32]) {
+    let size = egui::vec2(ui.available_width().max(200.0), 120.0);
+    let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+    if values.len() < 2 {
+        return;
+    }
+    let (min, max) = range(values);
+    let range = (max - min).max(f32::EPSILON);
+    let step = (values.len() / (rect.width() as usize / 2).max(1)).max(1);
+    let points: Vec<egui::Pos2> = values
+        .iter()
+        .enumerate()
+        .step_by(step)
+        .filter(|(_, v)| v.is_finite())
+        .map(|(i, v)| {
+            let x = i as f32 / (values.len() - 1) as f32;
+            let y = (v - min) / range;
+            egui::pos2(
+                rect.left() + x * rect.width(),
+                rect.bottom() - y * rect.height(),
+            )
+        })
+        .collect();
+    let stroke = egui::Stroke::new(1.5, ui.visuals().selection.bg_fill);
+    painter.add(egui::Shape::line(points, stroke));
+    let text = ui.visuals().weak_text_color();
+    let font = egui::FontId::monospace(10.0);
+    painter.text(
+        rect.left_top(),
+        egui::Align2::LEFT_TOP,
+        format!("{max:.3}"),
+        font.clone(),
+        text,
+    );
+    painter.text(
+        rect.left_bottom(),
+        egui::Align2::LEFT_BOTTOM,
+        format!("{min:.3}"),
+        font,
+        text,
+    );
+}
+
+/// Rows of `width` values in grey, the last row padded with black.
+fn values_image(values: &[f32], width: usize) -> egui::ColorImage {
+    let (min, max) = range(values);
+    let range = (max - min).max(f32::EPSILON);
+    let height = values.len().div_ceil(width);
+    let mut pixels = vec![egui::Color32::BLACK; width * height];
+    for (pixel, v) in pixels.iter_mut().zip(values) {
+        *pixel = if v.is_finite() {
+            egui::Color32::from_gray(((v - min) / range * 255.0) as u8)
+        } else {
+            // NaN and infinities stand out
+            egui::Color32::from_rgb(255, 0, 255)
+        };
+    }
+    egui::ColorImage::new([width, height], pixels)
+}
+
+/// The uploaded output of `resource`, made by `image` when there's none
+/// for its view yet.
+fn preview(
+    ui: &egui::Ui,
+    preview: &mut Option<(egui::TextureHandle, BufferView)>,
+    resource: &Resource,
+    image: impl FnOnce() -> egui::ColorImage,
+) -> egui::TextureHandle {
+    if let Some((texture, view)) = preview {
+        if *view == resource.view {
+            return texture.clone();
+        }
+    }
+    let options = egui::TextureOptions::NEAREST;
+    let texture = ui.ctx().load_texture(&resource.name, image(), options);
+    *preview = Some((texture.clone(), resource.view));
+    texture
+}
+
+/// Fills the window's width, up to four times the texture's size.
+fn image_ui(ui: &mut egui::Ui, texture: &egui::TextureHandle) {
+    let size = texture.size_vec2();
+    let width = ui.available_width().min(size.x * 4.0).max(64.0);
+    ui.image((texture.id(), size * (width / size.x)));
+}
@@ -0,0 +1,506 @@
+//! Lens flare from the sky's sun: a glare sprite on the sun and ghosts and
+//! a halo along the line through the screen center. The vertex shader
+//! tests a grid of depth samples around the sun, so geometry in front of
+//! it dims the whole flare without a CPU readback.
+
+use crate::app::State;
+use crate::camera::Camera;
+use crate::sky::Sky;
+use crate::texture::Texture;
+use std::sync::Arc;
+
+const SHADER: &str = r#"
+struct Flare {
+    // xy: sun in NDC, z: aspect ratio, w: intensity
+    sun: vec4<f32>,
+    // xy: sun in depth texels, z: test radius in texels
+    probe: vec4<f32>,
+};
+@group(0) @binding(0) var<uniform> flare: Flare;
+// bound as float rather than texture_depth_2d, which GLES can't load from
+@group(0) @binding(1) var depth: texture_2d<f32>;
+@group(1) @binding(0) var element_texture: texture_2d<f32>;
+@group(1) @binding(1) var element_sampler: sampler;
+
+struct Element {
+    // x: position along the flare axis, y: size, z: rotation
+    @location(0) shape: vec4<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+// Fraction of a 5x5 grid around the sun that nothing was drawn over.
+fn visibility() -> f32 {
+    let size = vec2<i32>(textureDimensions(depth));
+    var open = 0.0;
+    for (var y = -2; y <= 2; y++) {
+        for (var x = -2; x <= 2; x++) {
+            let offset = vec2(f32(x), f32(y)) * flare.probe.z * 0.5;
+            let texel = clamp(vec2<i32>(flare.probe.xy + offset), vec2(0), size - 1);
+            open += select(0.0, 1.0, textureLoad(depth, texel, 0).r >= 1.0);
+        }
+    }
+    return open / 25.0;
+}
+
+@vertex
+fn vsMain(@builtin(vertex_index) i: u32, element: Element) -> VSOut {
+    let corner = vec2(f32(i & 1u), f32(i >> 1u));
+    let c = cos(element.shape.z);
+    let s = sin(element.shape.z);
+    let local = (corner - 0.5) * 2.0 * element.shape.y;
+    let rotated = vec2(local.x * c - local.y * s, local.x * s + local.y * c);
+    // 0 on the sun, 1 at the screen center, 2 mirrored across it
+    let center = flare.sun.xy * (1.0 - element.shape.x);
+
+    // fade out as the sun leaves the screen
+    let edge = 1.0 - smoothstep(0.8, 1.2, max(abs(flare.sun.x), abs(flare.sun.y)));
+    var out: VSOut;
+    out.pos = vec4(center + vec2(rotated.x / flare.sun.z, rotated.y), 0.0, 1.0);
+    out.uv = vec2(corner.x, 1.0 - corner.y);
+    out.color = element.color;
+    out.color.a *= visibility() * edge * flare.sun.w;
+    return out;
+}
+
+@fragment
+fn psMain(in: VSOut) -> @location(0) vec4<f32> {
+    return textureSample(element_texture, element_sampler, in.uv) * in.color;
+}
+"#;
+
+/// Most elements a flare can have.
+pub const MAX_ELEMENTS: usize = 16;
+
+/// The built-in element textures, made at startup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlareShape {
+    /// Soft radial falloff, for glare and soft ghosts.
+    Glow,
+    /// Thin ring, for the halo.
+    Ring,
+    /// Hexagonal aperture ghost.
+    Hexagon,
+    /// Thin streaks crossing at the center.
+    Star,
+}
+
+impl FlareShape {
+    pub const ALL: [FlareShape; 4] = [
+        FlareShape::Glow,
+        FlareShape::Ring,
+        FlareShape::Hexagon,
+        FlareShape::Star,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            FlareShape::Glow => "Glow",
+            FlareShape::Ring => "Ring",
+            FlareShape::Hexagon => "Hexagon",
+            FlareShape::Star => "Star",
+        }
+    }
+
+    /// Alpha at `p`, from -1 to 1 across the texture.
+    fn alpha(self, p: glam::Vec2) -> f32 {
+        let r = p.length();
+        match self {
+            FlareShape::Glow => (1.0 - r).max(0.0).powi(2),
+            FlareShape::Ring => (1.0 - ((r - 0.85) / 0.08).abs()).max(0.0) * 0.8,
+            FlareShape::Hexagon => {
+                let q = p.abs();
+                let hex = (q.x * 0.866 + q.y * 0.5).max(q.y);
+                ((0.9 - hex) / 0.05).clamp(0.0, 1.0) * (0.4 + 0.6 * r)
+            }
+            FlareShape::Star => {
+                let streak = |d: f32| (1.0 - d / 0.04).max(0.0);
+                let falloff = (1.0 - r).max(0.0);
+                (streak(p.x.abs()).max(streak(p.y.abs())) * falloff).max((1.0 - r * 4.0).max(0.0))
+            }
+        }
+    }
+
+    fn create_texture(self, state: &State) -> Texture {
+        const SIZE: u32 = 64;
+        let mut pixels = Vec::with_capacity((SIZE * SIZE * 4) as usize);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let p = (glam::vec2(x as f32, y as f32) + 0.5) / SIZE as f32 * 2.0 - 1.0;
+                let alpha = (self.alpha(p).clamp(0.0, 1.0) * 255.0) as u8;
+                pixels.extend_from_slice(&[255, 255, 255, alpha]);
+            }
+        }
+        Texture::from_rgba8(state, self.label(), SIZE, SIZE, &pixels, true)
+    }
+}
+
+/// What a flare element shows.
+#[derive(Clone)]
+pub enum FlareTexture {
+    Shape(FlareShape),
+    /// Tinted by the element's color; alpha masks it.
+    Custom(Arc<Texture>),
+}
+
+#[derive(Clone)]
+pub struct FlareElement {
+    /// Along the line from the sun (0) through the screen center (1).
+    pub position: f32,
+    /// Half the height, as a fraction of half the screen height.
+    pub size: f32,
+    /// Radians.
+    pub rotation: f32,
+    pub color: [f32; 4],
+    pub texture: FlareTexture,
+}
+
+impl FlareElement {
+    pub fn new(shape: FlareShape, position: f32, size: f32, color: [f32; 4]) -> Self {
+        FlareElement {
+            position,
+            size,
+            rotation: 0.0,
+            color,
+            texture: FlareTexture::Shape(shape),
+        }
+    }
+}
+
+/// The glare on the sun, a few tinted ghosts and a halo past the center.
+pub fn default_elements() -> Vec<FlareElement> {
+    vec![
+        FlareElement::new(FlareShape::Glow, 0.0, 0.5, [1.0, 0.95, 0.85, 0.6]),
+        FlareElement::new(FlareShape::Star, 0.0, 0.35, [1.0, 0.95, 0.9, 0.5]),
+        FlareElement::new(FlareShape::Hexagon, 0.5, 0.06, [0.6, 0.8, 1.0, 0.25]),
+        FlareElement::new(FlareShape::Hexagon, 0.8, 0.1, [1.0, 0.7, 0.4, 0.2]),
+        FlareElement::new(FlareShape::Glow, 1.2, 0.05, [0.5, 1.0, 0.6, 0.3]),
+        FlareElement::new(FlareShape::Hexagon, 1.5, 0.16, [0.7, 0.6, 1.0, 0.15]),
+        FlareElement::new(FlareShape::Ring, 1.9, 0.45, [0.8, 0.9, 1.0, 0.12]),
+    ]
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct FlareUniform {
+    sun: [f32; 4],
+    probe: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ElementInstance {
+    shape: [f32; 4],
+    color: [f32; 4],
+}
+
+impl ElementInstance {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![
+        0 => Float32x4,
+        1 => Float32x4
+    ];
+}
+
+/// Draws over the finished frame, after the sprites. Only shows while
+/// the sky is enabled and its sun is up and in front of the camera.
+pub struct LensFlare {
+    pub enabled: bool,
+    pub intensity: f32,
+    /// Half-width in pixels of the area tested for occlusion.
+    pub occlusion_radius: f32,
+    pub elements: Vec<FlareElement>,
+    pipeline: wgpu::RenderPipeline,
+    flare_layout: wgpu::BindGroupLayout,
+    texture_layout: wgpu::BindGroupLayout,
+    uniform: wgpu::Buffer,
+    instances: wgpu::Buffer,
+    shapes: Vec<(FlareShape, Arc<Texture>)>,
+}
+
+impl LensFlare {
+    pub fn new(state: &State) -> Self {
+        let device = &state.device;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Lens Flare"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let flare_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Lens Flare"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let texture_layout =
+            Texture::create_bind_group_layout(device, wgpu::ShaderStages::FRAGMENT);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Lens Flare"),
+            bind_group_layouts: &[&flare_layout, &texture_layout],
+            push_constant_ranges: &[],
+        });
+        let additive = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::SrcAlpha,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Lens Flare"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vsMain"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<ElementInstance>() as u64,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &ElementInstance::ATTRIBUTES,
+                }],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("psMain"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: state.surface_config.format,
+                    blend: Some(wgpu::BlendState {
+                        color: additive,
+                        alpha: additive,
+                    }),
+                    write_mask: wgpu::ColorWrites::COLOR,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        let uniform = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Lens Flare"),
+            size: std::mem::size_of::<FlareUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let instances = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Lens Flare Elements"),
+            size: (std::mem::size_of::<ElementInstance>() * MAX_ELEMENTS) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let shapes = FlareShape::ALL
+            .iter()
+            .map(|&shape| (shape, Arc::new(shape.create_texture(state))))
+            .collect();
+
+        LensFlare {
+            enabled: true,
+            intensity: 1.0,
+            occlusion_radius: 8.0,
+            elements: default_elements(),
+            pipeline,
+            flare_layout,
+            texture_layout,
+            uniform,
+            instances,
+            shapes,
+        }
+    }
+
+    fn texture<'a>(&'a self, texture: &'a FlareTexture) -> &'a Arc<Texture> {
+        match texture {
+            FlareTexture::Shape(shape) => &self.shapes.iter().find(|(s, _)| s == shape).unwrap().1,
+            FlareTexture::Custom(texture) => texture,
+        }
+    }
+
+    /// The sun in NDC, if the flare should show.
+    fn sun_ndc(&self, camera: &Camera, sky: &Sky) -> Option<glam::Vec2> {
+        if !self.enabled || !sky.enabled || self.elements.is_empty() {
+            return None;
+        }
+        let sun = sky.sun_direction();
+        if sun.y <= 0.0 {
+            return None;
+        }
+        let clip = camera.view_proj() * (camera.eye + sun).extend(1.0);
+        (clip.w > 0.0).then(|| clip.truncate().truncate() / clip.w)
+    }
+
+    /// Adds the flare to `view` in its own pass.
+    pub fn render(
+        &self,
+        state: &State,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        camera: &Camera,
+        sky: &Sky,
+    ) {
+        let Some(ndc) = self.sun_ndc(camera, sky) else {
+            return;
+        };
+        let target = view.texture();
+        let (width, height) = (target.width() as f32, target.height() as f32);
+        let sun_color = sky.sun_color();
+        let brightness = sun_color.max_element().min(1.0);
+        let uniform = FlareUniform {
+            sun: [ndc.x, ndc.y, width / height, self.intensity * brightness],
+            probe: [
+                (ndc.x + 1.0) * 0.5 * width,
+                (1.0 - ndc.y) * 0.5 * height,
+                self.occlusion_radius * state.scale_factor,
+                0.0,
+            ],
+        };
+        state
+            .queue
+            .write_buffer(&self.uniform, 0, bytemuck::bytes_of(&uniform));
+
+        let elements = &self.elements[..self.elements.len().min(MAX_ELEMENTS)];
+        let tint = (sun_color / brightness.max(f32::EPSILON)).min(glam::Vec3::ONE);
+        let instances: Vec<ElementInstance> = elements
+            .iter()
+            .map(|e| {
+                let [r, g, b, a] = e.color;
+                ElementInstance {
+                    shape: [e.position, e.size, e.rotation, 0.0],
+                    color: [r * tint.x, g * tint.y, b * tint.z, a],
+                }
+            })
+            .collect();
+        state
+            .queue
+            .write_buffer(&self.instances, 0, bytemuck::cast_slice(&instances));
+
+        let flare_bind_group = state.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Lens Flare"),
+            layout: &self.flare_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.uniform.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&state.depth_texture.view),
+                },
+            ],
+        });
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("lens flare"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                depth_slice: None,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &flare_bind_group, &[]);
+        pass.set_vertex_buffer(0, self.instances.slice(..));
+        for (i, element) in elements.iter().enumerate() {
+            let bind_group = self
+                .texture(&element.texture)
+                .create_bind_group(state, &self.texture_layout);
+            pass.set_bind_group(1, &bind_group, &[]);
+            let i = i as u32;
+            pass.draw(0..4, i..i + 1);
+        }
+        let count = elements.len() as u32;
+        state.profiler.record_draws(count, count);
+        state
+            .profiler
+            .record_target(target.width(), target.height());
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing("Lens Flare", |ui| {
+            ui.checkbox(&mut self.enabled, "Lens flare (needs the sky)");
+            ui.add(egui::Slider::new(&mut self.intensity, 0.0..=2.0).text("Intensity"));
+            ui.add(
+                egui::Slider::new(&mut self.occlusion_radius, 1.0..=32.0)
+                    .text("Occlusion radius (px)"),
+            );
+            let mut remove = None;
+            for (i, element) in self.elements.iter_mut().enumerate() {
+                ui.push_id(i, |ui| {
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        let text = match &element.texture {
+                            FlareTexture::Shape(shape) => shape.label(),
+                            FlareTexture::Custom(_) => "Custom",
+                        };
+                        egui::ComboBox::from_id_salt("shape")
+                            .selected_text(text)
+                            .show_ui(ui, |ui| {
+                                for shape in FlareShape::ALL {
+                                    let selected = matches!(
+                                        element.texture,
+                                        FlareTexture::Shape(s) if s == shape
+                                    );
+                                    if ui.selectable_label(selected, shape.label()).clicked() {
+                                        element.texture = FlareTexture::Shape(shape);
+                                    }
+                                }
+                            });
+                        ui.color_edit_button_rgba_unmultiplied(&mut element.color);
+                        if ui.small_button("x").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                    ui.add(egui::Slider::new(&mut element.position, -1.0..=3.0).text("Position"));
+                    ui.add(egui::Slider::new(&mut element.size, 0.01..=1.0).text("Size"));
+                });
+            }
+            if let Some(i) = remove {
+                self.elements.remove(i);
+            }
+            ui.horizontal(|ui| {
+                let full = self.elements.len() >= MAX_ELEMENTS;
+                if ui.add_enabled(!full, egui::Button::new("Add")).clicked() {
+                    self.elements.push(FlareElement::new(
+                        FlareShape::Hexagon,
+                        1.0,
+                        0.1,
+                        [1.0, 1.0, 1.0, 0.2],
+                    ));
+                }
+                if ui.button("Reset").clicked() {
+                    self.elements = default_elements();
+                }
+            });
+        });
+    }
+}
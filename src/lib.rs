@@ -0,0 +1,133 @@
+//! A small wgpu + egui rendering sandbox.
+//!
+//! [`App`] owns the window, GPU [`State`] and [`World`]; extend it with
+//! [`Plugin`]s and hand it to [`run`]:
+//!
+//! ```no_run
+//! let app = rust_graphics_sandbox::App::new();
+//! rust_graphics_sandbox::run(app);
+//! ```
+
+pub mod app;
+pub mod array;
+pub mod asset_cache;
+pub mod assets;
+#[cfg(feature = "audio")]
+pub mod audio;
+pub mod background;
+pub mod blur;
+pub mod camera;
+pub mod camera_controller;
+pub mod cloth;
+pub mod commands;
+pub mod config;
+pub mod console;
+pub mod culling;
+pub mod debug_draw;
+pub mod detachable;
+pub mod diagnostics;
+pub mod egui_renderer;
+pub mod file_dialog;
+pub mod frame_compare;
+pub mod frame_pacing;
+pub mod gi;
+pub mod gpu_caps;
+pub mod gpu_profiler;
+pub mod hdr_output;
+pub mod hdr_still;
+pub mod headless;
+pub mod i18n;
+pub mod import;
+pub mod impostor;
+pub mod input;
+pub mod inspector;
+pub mod jobs;
+pub mod kernel_playground;
+pub mod lens_flare;
+pub mod lightmapper;
+pub mod material;
+pub mod measure;
+pub mod menu;
+pub mod mesh;
+pub mod mesh_builder;
+pub mod model;
+pub mod net_sync;
+pub mod outline;
+pub mod parallax;
+pub mod picking;
+pub mod pixel_inspector;
+pub mod plugin;
+pub mod procgen;
+pub mod quality;
+pub mod remote;
+pub mod sampler;
+pub mod scatter;
+pub mod scene_pack;
+pub mod scene_patch;
+pub mod scopes;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+pub mod selection;
+pub mod session;
+pub mod shader;
+pub mod shader_playground;
+pub mod shadow;
+pub mod shadow_atlas;
+pub mod sky;
+pub mod snapping;
+pub mod socket;
+pub mod spatial;
+pub mod spline;
+pub mod sprites;
+pub mod stress_test;
+pub mod texture;
+pub mod texture_file;
+pub mod time;
+pub mod trail;
+pub mod transform;
+pub mod transient;
+pub mod ui_theme;
+pub mod uniform;
+pub mod uri;
+pub mod uv_debug;
+pub mod vfs;
+pub mod watch;
+pub mod workspace;
+pub mod world;
+
+pub use app::{App, State};
+pub use assets::MeshLoaders;
+pub use camera::Camera;
+pub use material::{MaterialInstance, MaterialTemplate};
+pub use mesh::Mesh;
+pub use model::{EntityId, Model};
+pub use plugin::{Plugin, PluginContext};
+pub use world::World;
+
+use winit::event_loop::{ControlFlow, EventLoop};
+
+/// Runs the event loop until the window is closed.
+pub fn run(app: App) {
+    run_with_event_loop(app, EventLoop::new().unwrap());
+}
+
+fn run_with_event_loop(mut app: App, event_loop: EventLoop<()>) {
+    event_loop.set_control_flow(ControlFlow::Poll);
+
+    event_loop.run_app(&mut app).expect("Failed to run app");
+}
+
+/// Entry point for the Android `NativeActivity`; build with
+/// `cargo apk`/`xbuild` for an Android target.
+#[cfg(target_os = "android")]
+#[no_mangle]
+fn android_main(android_app: winit::platform::android::activity::AndroidApp) {
+    use winit::platform::android::EventLoopBuilderExtAndroid;
+
+    diagnostics::init();
+    let event_loop = EventLoop::builder()
+        .with_android_app(android_app)
+        .build()
+        .unwrap();
+    run_with_event_loop(App::new(), event_loop);
+}
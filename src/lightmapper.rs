@@ -0,0 +1,757 @@
+//! Offline lightmap baking on the GPU. Each model is drawn into a lightmap
+//! of its own in lightmap UV space: its mesh's TEXCOORD_1, or an atlas
+//! generated with a cell per pair of triangles. Every frame each texel
+//! traces a few cosine-weighted rays and a shadow ray to the sun through
+//! the scene's voxels (`gi::Voxels`), and the result is blended into a
+//! running average, so a bake refines over many frames while the scene
+//! stays interactive.
+//!
+//! Lightmaps hold light over pi, like `gi_irradiance`, so materials
+//! multiply albedo by them; bind them as `new_arc_with_lightmap` does and
+//! sample them with the second UV set.
+
+use crate::app::State;
+use crate::gi::{Voxels, VOXEL_MARCH_WGSL};
+use crate::mesh::{Mesh, Vertex, VertexEncoding, OCTAHEDRAL_WGSL};
+use crate::model::EntityId;
+use crate::sampler::SamplerDesc;
+use crate::texture::Texture;
+use crate::uniform::{UniformLayout, UniformType};
+use crate::world::World;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+const BAKE_SHADER: &str = r#"
+struct Bake {
+    voxel_min: vec3<f32>,
+    voxel_size: f32,
+    voxel_dims: vec3<f32>,
+    frame: u32,
+    sun_direction: vec3<f32>,
+    samples: u32,
+    sun_color: vec3<f32>,
+    sky: vec3<f32>,
+};
+struct Model { model: mat4x4<f32>, base_color: vec4<f32>, alpha_cutoff: f32 };
+
+@group(0) @binding(0) var<uniform> bake: Bake;
+@group(0) @binding(1) var voxels: texture_3d<f32>;
+@group(1) @binding(0) var<uniform> model: Model;
+
+const PI: f32 = 3.14159265;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) world_pos: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+};
+
+// Placed by lightmap UV rather than by a camera.
+fn vertex(pos: vec3<f32>, normal: vec3<f32>, uv1: vec2<f32>) -> VSOut {
+    var out: VSOut;
+    out.pos = vec4(uv1.x * 2.0 - 1.0, 1.0 - uv1.y * 2.0, 0.0, 1.0);
+    out.world_pos = (model.model * vec4(pos, 1.0)).xyz;
+    out.normal = (model.model * vec4(normal, 0.0)).xyz;
+    return out;
+}
+
+@vertex
+fn vsMain(
+    @location(0) pos: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(3) uv1: vec2<f32>,
+) -> VSOut {
+    return vertex(pos, normal, uv1);
+}
+
+@vertex
+fn vsMainPacked(
+    @location(0) pos: vec3<f32>,
+    @location(1) normal: vec2<f32>,
+    @location(3) uv1: vec2<f32>,
+) -> VSOut {
+    return vertex(pos, octDecode(normal), uv1);
+}
+
+fn march(origin: vec3<f32>, dir: vec3<f32>) -> VoxelHit {
+    return voxel_march(origin, dir, bake.voxel_min, bake.voxel_size, bake.voxel_dims);
+}
+
+// PCG
+fn hash(x: u32) -> u32 {
+    let state = x * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn random(seed: ptr<function, u32>) -> f32 {
+    *seed = hash(*seed);
+    return f32(*seed) / 4294967295.0;
+}
+
+fn cosine_sample(n: vec3<f32>, u1: f32, u2: f32) -> vec3<f32> {
+    let r = sqrt(u1);
+    let phi = 2.0 * PI * u2;
+    let up = select(vec3(0.0, 0.0, 1.0), vec3(1.0, 0.0, 0.0), abs(n.z) > 0.999);
+    let t = normalize(cross(up, n));
+    let b = cross(n, t);
+    return t * r * cos(phi) + b * r * sin(phi) + n * sqrt(max(1.0 - u1, 0.0));
+}
+
+fn sun_light(pos: vec3<f32>, n: vec3<f32>) -> vec3<f32> {
+    let to_sun = -normalize(bake.sun_direction);
+    let n_dot_l = dot(n, to_sun);
+    if n_dot_l <= 0.0 || march(pos, to_sun).found {
+        return vec3(0.0);
+    }
+    return bake.sun_color * n_dot_l;
+}
+
+// Sunlight and, with cosine-weighted rays, the sky and one bounce of
+// sunlight. Cosine weighting makes the mean radiance irradiance over pi.
+@fragment
+fn fsBake(in: VSOut) -> @location(0) vec4<f32> {
+    let n = normalize(in.normal);
+    // clear of the surface's own voxel
+    let origin = in.world_pos + n * bake.voxel_size;
+    var seed = hash(u32(in.pos.x) ^ hash(u32(in.pos.y) ^ hash(bake.frame)));
+    var indirect = vec3(0.0);
+    for (var i = 0u; i < bake.samples; i++) {
+        let dir = cosine_sample(n, random(&seed), random(&seed));
+        let hit = march(origin, dir);
+        if !hit.found {
+            indirect += bake.sky;
+            continue;
+        }
+        let albedo = textureLoad(voxels, hit.cell, 0).rgb;
+        let center = bake.voxel_min + (vec3<f32>(hit.cell) + 0.5) * bake.voxel_size;
+        indirect += albedo * sun_light(center + hit.normal * bake.voxel_size, hit.normal);
+    }
+    return vec4(sun_light(origin, n) + indirect / f32(bake.samples), 1.0);
+}
+"#;
+
+/// Fills texels no triangle covered from their covered neighbours, so
+/// filtering at triangle edges doesn't pull in black.
+const DILATE_SHADER: &str = r#"
+@group(0) @binding(0) var accumulated: texture_2d<f32>;
+
+@vertex
+fn vsMain(@builtin(vertex_index) i: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2(f32((i << 1u) & 2u), f32(i & 2u));
+    return vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fsDilate(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+    let size = vec2<i32>(textureDimensions(accumulated));
+    let texel = vec2<i32>(pos.xy);
+    let center = textureLoad(accumulated, texel, 0);
+    if center.a > 0.0 {
+        return vec4(center.rgb / center.a, 1.0);
+    }
+    var sum = vec4(0.0);
+    for (var y = -2; y <= 2; y++) {
+        for (var x = -2; x <= 2; x++) {
+            let value = textureLoad(accumulated, clamp(texel + vec2(x, y), vec2(0), size - 1), 0);
+            if value.a > 0.0 {
+                sum += vec4(value.rgb / value.a, 1.0);
+            }
+        }
+    }
+    if sum.a == 0.0 {
+        return vec4(0.0);
+    }
+    return vec4(sum.rgb / sum.a, 1.0);
+}
+"#;
+
+/// Blendable and filterable everywhere.
+const LIGHTMAP_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+/// Generated atlases give each cell at least this many texels across.
+const MIN_CELL_TEXELS: u32 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BakeSettings {
+    /// Rays per texel each frame.
+    pub samples_per_frame: u32,
+    /// Rays per texel at which the bake stops.
+    pub target_samples: u32,
+    /// Lightmap texels per world unit, by the square root of each model's
+    /// surface area, before `max_size`.
+    pub texels_per_unit: f32,
+    pub max_size: u32,
+    /// Generates lightmap UVs even for meshes with TEXCOORD_1.
+    pub generate_uvs: bool,
+    /// Voxels along the longest side of the scene, for the rays.
+    pub voxel_resolution: u32,
+}
+
+impl Default for BakeSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BakeSettings {
+    pub fn new() -> Self {
+        BakeSettings {
+            samples_per_frame: 8,
+            target_samples: 512,
+            texels_per_unit: 2.0,
+            max_size: 1024,
+            generate_uvs: false,
+            voxel_resolution: 128,
+        }
+    }
+
+    /// Returns true when anything changed. All but the samples per frame
+    /// apply to the next bake.
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = ui
+            .add(egui::Slider::new(&mut self.samples_per_frame, 1..=64).text("Samples per frame"))
+            .changed();
+        changed |= ui
+            .add(
+                egui::Slider::new(&mut self.target_samples, 16..=8192)
+                    .logarithmic(true)
+                    .text("Target samples"),
+            )
+            .changed();
+        changed |= ui
+            .add(
+                egui::Slider::new(&mut self.texels_per_unit, 0.1..=16.0)
+                    .logarithmic(true)
+                    .text("Texels per unit"),
+            )
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut self.max_size, 64..=4096).text("Max size"))
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut self.voxel_resolution, 16..=256).text("Voxel resolution"))
+            .changed();
+        changed |= ui
+            .checkbox(&mut self.generate_uvs, "Generate UVs for every mesh")
+            .changed();
+        changed
+    }
+}
+
+/// Lightmap UVs for `triangles` triangles, three each: a cell of a square
+/// atlas per pair, the first in its lower left half and the second in its
+/// upper right, each stretched to a right triangle. Every triangle gets
+/// the same texels whatever its size. Triangles stay at least two texels
+/// of a `size` square map apart.
+pub fn atlas_uvs(triangles: usize, size: u32) -> Vec<[f32; 2]> {
+    let cells = (triangles.div_ceil(2) as f32).sqrt().ceil().max(1.0) as usize;
+    let cell = 1.0 / cells as f32;
+    // one texel either side of every edge, in cell units
+    let p = (cells as f32 / size.max(1) as f32).min(0.15);
+    // the hypotenuses are further apart along the diagonal
+    let q = 1.0 - 2.5 * p;
+    let halves = [
+        [[p, p], [q, p], [p, q]],
+        [[1.0 - p, 1.0 - p], [1.0 - q, 1.0 - p], [1.0 - p, 1.0 - q]],
+    ];
+    (0..triangles)
+        .flat_map(|t| {
+            let (x, y) = ((t / 2) % cells, (t / 2) / cells);
+            halves[t % 2].map(|[u, v]| [(x as f32 + u) * cell, (y as f32 + v) * cell])
+        })
+        .collect()
+}
+
+/// A copy of `mesh` with a vertex per triangle corner and `atlas_uvs` in
+/// TEXCOORD_1.
+pub fn unwrap(device: &wgpu::Device, mesh: &Mesh, size: u32) -> Mesh {
+    let uv1 = atlas_uvs(mesh.indices.len() / 3, size);
+    let corners = &mesh.indices[..uv1.len()];
+    let vertices: Vec<Vertex> = corners
+        .iter()
+        .zip(&uv1)
+        .map(|(&i, &uv1)| {
+            let i = i as usize;
+            Vertex {
+                pos: mesh.positions[i].to_array(),
+                normal: mesh.normals.get(i).copied().unwrap_or_default().to_array(),
+                uv: mesh.uvs.get(i).copied().unwrap_or_default().to_array(),
+                uv1,
+            }
+        })
+        .collect();
+    let indices: Vec<u32> = (0..vertices.len() as u32).collect();
+    let mut unwrapped = Mesh::with_encoding(device, &mesh.name, &vertices, &indices, mesh.encoding);
+    unwrapped.source = mesh.source.clone();
+    unwrapped.tangents = match mesh.tangents.is_empty() {
+        true => vec![],
+        false => corners.iter().map(|&i| mesh.tangents[i as usize]).collect(),
+    };
+    unwrapped.attributes = mesh.attributes.clone();
+    if !unwrapped.attributes.iter().any(|a| a == "TEXCOORD_1") {
+        unwrapped.attributes.push("TEXCOORD_1".to_string());
+    }
+    unwrapped.double_sided = mesh.double_sided;
+    unwrapped.base_color = mesh.base_color;
+    unwrapped.alpha_cutoff = mesh.alpha_cutoff;
+    unwrapped.base_color_texture = mesh.base_color_texture.clone();
+    unwrapped
+}
+
+/// One model's lightmap: the running average, and the dilated copy
+/// materials sample.
+struct BakeTarget {
+    model: EntityId,
+    accumulation: wgpu::Texture,
+    accumulation_view: wgpu::TextureView,
+    lightmap: Arc<Texture>,
+    dilate_bind_group: wgpu::BindGroup,
+}
+
+/// Bakes a lightmap per visible model. Needs nothing optional: the rays
+/// are traced in fragment shaders.
+pub struct Lightmapper {
+    pub settings: BakeSettings,
+    targets: Vec<BakeTarget>,
+    voxel_bounds: (glam::Vec3, glam::Vec3),
+    voxel_size: f32,
+    voxel_dims: [u32; 3],
+    uniform_layout: UniformLayout,
+    uniform_buffer: wgpu::Buffer,
+    scene_layout: wgpu::BindGroupLayout,
+    scene_bind_group: Option<wgpu::BindGroup>,
+    /// By vertex encoding.
+    bake_pipelines: [wgpu::RenderPipeline; 2],
+    dilate_layout: wgpu::BindGroupLayout,
+    dilate_pipeline: wgpu::RenderPipeline,
+    samples: u32,
+    frame: u32,
+    started: Option<Instant>,
+    seconds: f32,
+}
+
+impl Lightmapper {
+    pub fn new(state: &State) -> Self {
+        let device = &state.device;
+        let uniform_layout = UniformLayout::new()
+            .field("voxel_min", UniformType::Vec3)
+            .field("voxel_size", UniformType::F32)
+            .field("voxel_dims", UniformType::Vec3)
+            .field("frame", UniformType::U32)
+            .field("sun_direction", UniformType::Vec3)
+            .field("samples", UniformType::U32)
+            .field("sun_color", UniformType::Vec3)
+            .field("sky", UniformType::Vec3);
+        let source = format!("{BAKE_SHADER}{VOXEL_MARCH_WGSL}{OCTAHEDRAL_WGSL}");
+        uniform_layout.assert_wgsl(&source, "Bake");
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Lightmap Bake"),
+            size: uniform_layout.size(),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let scene_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Lightmap Bake"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                texture_entry(1, wgpu::TextureViewDimension::D3),
+            ],
+        });
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Lightmap Bake"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Lightmap Bake"),
+            bind_group_layouts: &[&scene_layout, &state.model_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        // the new samples' share of the average comes in as the constant
+        let average = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::Constant,
+            dst_factor: wgpu::BlendFactor::OneMinusConstant,
+            operation: wgpu::BlendOperation::Add,
+        };
+        let bake_pipeline = |encoding: VertexEncoding| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Lightmap Bake"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &module,
+                    entry_point: Some(encoding.entry_point()),
+                    buffers: &[encoding.layout()],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &module,
+                    entry_point: Some("fsBake"),
+                    compilation_options: Default::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: LIGHTMAP_FORMAT,
+                        blend: Some(wgpu::BlendState {
+                            color: average,
+                            alpha: average,
+                        }),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+        let bake_pipelines = [VertexEncoding::Full, VertexEncoding::Packed].map(bake_pipeline);
+
+        let dilate_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Lightmap Dilate"),
+            entries: &[texture_entry(0, wgpu::TextureViewDimension::D2)],
+        });
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Lightmap Dilate"),
+            source: wgpu::ShaderSource::Wgsl(DILATE_SHADER.into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Lightmap Dilate"),
+            bind_group_layouts: &[&dilate_layout],
+            push_constant_ranges: &[],
+        });
+        let dilate_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Lightmap Dilate"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vsMain"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("fsDilate"),
+                compilation_options: Default::default(),
+                targets: &[Some(LIGHTMAP_FORMAT.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Lightmapper {
+            settings: BakeSettings::new(),
+            targets: vec![],
+            voxel_bounds: (glam::Vec3::ZERO, glam::Vec3::ONE),
+            voxel_size: 1.0,
+            voxel_dims: [1; 3],
+            uniform_layout,
+            uniform_buffer,
+            scene_layout,
+            scene_bind_group: None,
+            bake_pipelines,
+            dilate_layout,
+            dilate_pipeline,
+            samples: 0,
+            frame: 0,
+            started: None,
+            seconds: 0.0,
+        }
+    }
+
+    /// Starts a bake of every visible model, replacing the meshes of those
+    /// without lightmap UVs with unwrapped copies. Models sharing a mesh
+    /// share its copy. The light is whatever `update` sets.
+    pub fn start(&mut self, state: &State, world: &mut World) {
+        let device = &state.device;
+        let settings = self.settings;
+        let mut unwrapped: HashMap<*const Mesh, Arc<Mesh>> = HashMap::new();
+        self.targets.clear();
+        for model in world.models.iter_mut().filter(|m| m.is_visible()) {
+            if model.mesh.indices.len() < 3 {
+                continue;
+            }
+            let area = surface_area(&model.mesh, model.global_matrix());
+            let mut size = (area.sqrt() * settings.texels_per_unit) as u32;
+            let has_uv1 = model.mesh.attributes.iter().any(|a| a == "TEXCOORD_1");
+            if settings.generate_uvs || !has_uv1 {
+                let cells = ((model.mesh.indices.len() / 6).max(1) as f32).sqrt().ceil() as u32;
+                size = size.max(cells * MIN_CELL_TEXELS);
+                let size = size.min(settings.max_size);
+                let key = Arc::as_ptr(&model.mesh);
+                let mesh = unwrapped
+                    .entry(key)
+                    .or_insert_with(|| Arc::new(unwrap(device, &model.mesh, size)));
+                model.mesh = mesh.clone();
+            }
+            let size = size.clamp(16, settings.max_size);
+            self.targets.push(self.target(state, model.id, size));
+        }
+        let voxels = Voxels::from_world(world, settings.voxel_resolution);
+        let voxel_texture = voxels.create_texture(state);
+        let voxel_view = voxel_texture.create_view(&Default::default());
+        self.scene_bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Lightmap Bake"),
+            layout: &self.scene_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&voxel_view),
+                },
+            ],
+        }));
+        self.voxel_bounds = voxels.bounds();
+        self.voxel_size = voxels.size;
+        self.voxel_dims = voxels.dims;
+        self.samples = 0;
+        self.started = Some(Instant::now());
+        self.seconds = 0.0;
+        log::info!(
+            "Baking {} lightmaps, {} texels",
+            self.targets.len(),
+            self.texels()
+        );
+    }
+
+    fn target(&self, state: &State, model: EntityId, size: u32) -> BakeTarget {
+        let device = &state.device;
+        let texture = |label, usage| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: LIGHTMAP_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING
+                    | usage,
+                view_formats: &[],
+            })
+        };
+        let accumulation = texture("Lightmap Accumulation", wgpu::TextureUsages::empty());
+        let accumulation_view = accumulation.create_view(&Default::default());
+        let mut lightmap =
+            Texture::from_texture(state, texture("Lightmap", wgpu::TextureUsages::COPY_SRC));
+        lightmap.sampler_desc = SamplerDesc {
+            anisotropy: 1,
+            ..SamplerDesc::new().with_address_mode(wgpu::AddressMode::ClampToEdge)
+        };
+        let dilate_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Lightmap Dilate"),
+            layout: &self.dilate_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&accumulation_view),
+            }],
+        });
+        BakeTarget {
+            model,
+            accumulation,
+            accumulation_view,
+            lightmap: Arc::new(lightmap),
+            dilate_bind_group,
+        }
+    }
+
+    /// Sets the light baked from here on: `sun_direction` is the way it
+    /// travels, and `sky` what rays that hit nothing see. Restart the bake
+    /// after changing it.
+    pub fn update(
+        &mut self,
+        queue: &wgpu::Queue,
+        sun_direction: glam::Vec3,
+        sun_color: glam::Vec3,
+        sky: glam::Vec3,
+    ) {
+        let layout = &self.uniform_layout;
+        let mut data = layout.zeroed();
+        let dims = glam::UVec3::from(self.voxel_dims).as_vec3();
+        layout.write(&mut data, "voxel_min", self.voxel_bounds.0.to_array());
+        layout.write(&mut data, "voxel_size", self.voxel_size);
+        layout.write(&mut data, "voxel_dims", dims.to_array());
+        layout.write(&mut data, "frame", self.frame);
+        layout.write(&mut data, "sun_direction", sun_direction.to_array());
+        layout.write(&mut data, "samples", self.settings.samples_per_frame.max(1));
+        layout.write(&mut data, "sun_color", sun_color.to_array());
+        layout.write(&mut data, "sky", sky.to_array());
+        queue.write_buffer(&self.uniform_buffer, 0, &data);
+    }
+
+    /// Adds this frame's samples to every lightmap, until the target.
+    /// Record it from `Plugin::prepare`, after `update`.
+    pub fn encode(&mut self, state: &State, world: &World, encoder: &mut wgpu::CommandEncoder) {
+        let Some(scene_bind_group) = &self.scene_bind_group else {
+            return;
+        };
+        if !self.is_baking() {
+            return;
+        }
+        let added = self.settings.samples_per_frame.max(1);
+        let share = added as f64 / (self.samples + added) as f64;
+        let mut draws = 0;
+        for target in &self.targets {
+            let Some(model) = world.model(target.model) else {
+                continue;
+            };
+            {
+                let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("lightmap bake"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &target.accumulation_view,
+                        depth_slice: None,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            // texels no triangle covers stay at zero alpha
+                            load: match self.samples {
+                                0 => wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                                _ => wgpu::LoadOp::Load,
+                            },
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                });
+                let encoding = match model.mesh.encoding {
+                    VertexEncoding::Full => 0,
+                    VertexEncoding::Packed => 1,
+                };
+                pass.set_pipeline(&self.bake_pipelines[encoding]);
+                pass.set_blend_constant(wgpu::Color {
+                    r: share,
+                    g: share,
+                    b: share,
+                    a: share,
+                });
+                pass.set_bind_group(0, scene_bind_group, &[]);
+                pass.set_bind_group(1, model.bind_group(), &[]);
+                pass.set_vertex_buffer(0, model.mesh.vertex_buffer.slice(..));
+                pass.set_index_buffer(model.mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                pass.draw_indexed(0..model.mesh.index_count, 0, 0..1);
+            }
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("lightmap dilate"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target.lightmap.view,
+                    depth_slice: None,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_pipeline(&self.dilate_pipeline);
+            pass.set_bind_group(0, &target.dilate_bind_group, &[]);
+            pass.draw(0..3, 0..1);
+            draws += 2;
+        }
+        state.profiler.record_draws(draws, draws);
+        self.samples += added;
+        self.frame = self.frame.wrapping_add(1);
+        if !self.is_baking() {
+            self.seconds = self.started.map_or(0.0, |t| t.elapsed().as_secs_f32());
+            log::info!(
+                "Baked {} samples per texel in {:.1} s",
+                self.samples,
+                self.seconds
+            );
+        }
+    }
+
+    /// Started and short of the target samples.
+    pub fn is_baking(&self) -> bool {
+        self.scene_bind_group.is_some() && self.samples < self.settings.target_samples
+    }
+
+    /// Samples per texel so far.
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// Seconds the last finished bake took.
+    pub fn seconds(&self) -> f32 {
+        self.seconds
+    }
+
+    /// Stops the bake where it is; the lightmaps keep what they have.
+    pub fn stop(&mut self) {
+        self.settings.target_samples = self.samples;
+    }
+
+    /// The model's lightmap, once a bake has started.
+    pub fn lightmap(&self, model: EntityId) -> Option<&Arc<Texture>> {
+        self.targets
+            .iter()
+            .find(|target| target.model == model)
+            .map(|target| &target.lightmap)
+    }
+
+    pub fn lightmaps(&self) -> impl Iterator<Item = (EntityId, &Arc<Texture>)> {
+        self.targets
+            .iter()
+            .map(|target| (target.model, &target.lightmap))
+    }
+
+    /// Across every lightmap.
+    pub fn texels(&self) -> u64 {
+        self.targets
+            .iter()
+            .map(|target| {
+                let size = target.accumulation.size();
+                size.width as u64 * size.height as u64
+            })
+            .sum()
+    }
+}
+
+fn texture_entry(
+    binding: u32,
+    view_dimension: wgpu::TextureViewDimension,
+) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            view_dimension,
+            multisampled: false,
+        },
+        count: None,
+    }
+}
+
+/// World-space area of the mesh's triangles.
+fn surface_area(mesh: &Mesh, matrix: glam::Mat4) -> f32 {
+    mesh.indices
+        .chunks_exact(3)
+        .map(|triangle| {
+            let [a, b, c] =
+                [0, 1, 2].map(|i| matrix.transform_point3(mesh.positions[triangle[i] as usize]));
+            (b - a).cross(c - a).length() / 2.0
+        })
+        .sum()
+}
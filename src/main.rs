@@ -1,27 +1,64 @@
-mod app;
-mod camera;
-mod egui_renderer;
-mod material;
-mod mesh;
-mod model;
-mod shader;
-mod world;
-
-use winit::event_loop::{ControlFlow, EventLoop};
+use rust_graphics_sandbox::App;
 
 fn main() {
-    #[cfg(not(target_arch = "wasm32"))]
-    {
-        pollster::block_on(run());
-    }
-}
+    rust_graphics_sandbox::diagnostics::init();
 
-async fn run() {
-    let event_loop = EventLoop::new().unwrap();
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).is_some_and(|a| a == "pack") {
+        std::process::exit(rust_graphics_sandbox::scene_pack::run_cli(&args[2..]));
+    }
 
-    event_loop.set_control_flow(ControlFlow::Poll);
+    let mut app = App::new();
+    if let Some(i) = args.iter().position(|a| a == "--replay") {
+        let path = args.get(i + 1).expect("--replay needs a session file");
+        app.replay_on_start(path);
+    }
+    if let Some(i) = args.iter().position(|a| a == "--quality") {
+        let name = args.get(i + 1).expect("--quality needs a preset");
+        match rust_graphics_sandbox::quality::QualityPreset::parse(name) {
+            Some(preset) => {
+                app.quality_on_start(preset);
+            }
+            None => log::error!("No quality preset {name}; try low, medium, high or ultra"),
+        }
+    }
+    if let Some(i) = args.iter().position(|a| a == "--mount") {
+        let path = args.get(i + 1).expect("--mount needs a zip archive");
+        if let Err(e) = rust_graphics_sandbox::vfs::mount_zip("", path) {
+            log::error!("Failed to mount {e}");
+        }
+    }
+    if let Some(i) = args.iter().position(|a| a == "--remote") {
+        let port = args
+            .get(i + 1)
+            .and_then(|p| p.parse().ok())
+            .unwrap_or(rust_graphics_sandbox::remote::DEFAULT_PORT);
+        app.add_plugin(rust_graphics_sandbox::remote::RemoteControl::new(port));
+    }
 
-    let mut app = app::App::new();
+    app.add_plugin(rust_graphics_sandbox::stress_test::StressTest::new());
+    app.add_plugin(rust_graphics_sandbox::array::ArrayTool::new());
+    app.add_plugin(rust_graphics_sandbox::procgen::Procgen::new());
+    app.add_plugin(rust_graphics_sandbox::measure::Measure::new());
+    app.add_plugin(rust_graphics_sandbox::outline::Outline::new());
+    app.add_plugin(rust_graphics_sandbox::net_sync::NetSync::new());
+    app.add_plugin(rust_graphics_sandbox::uv_debug::UvDebug::new());
+    app.add_plugin(rust_graphics_sandbox::scatter::Scatter::new());
+    app.add_plugin(rust_graphics_sandbox::cloth::ClothDemo::new());
+    app.add_plugin(rust_graphics_sandbox::socket::Sockets::new());
+    app.add_plugin(rust_graphics_sandbox::trail::Trails::new());
+    app.add_plugin(rust_graphics_sandbox::spline::Splines::new());
+    app.add_plugin(rust_graphics_sandbox::frame_compare::FrameCompare::new());
+    app.add_plugin(rust_graphics_sandbox::pixel_inspector::PixelInspector::new());
+    app.add_plugin(rust_graphics_sandbox::shader_playground::ShaderPlayground::new());
+    app.add_plugin(rust_graphics_sandbox::kernel_playground::KernelPlayground::new());
+    app.add_plugin(rust_graphics_sandbox::scopes::Scopes::new());
+    // after tools that drag with the Select binding, so it can yield to them
+    app.add_plugin(rust_graphics_sandbox::selection::SelectionTool::new());
+    #[cfg(feature = "audio")]
+    app.add_plugin(rust_graphics_sandbox::audio::Audio::new());
+    #[cfg(feature = "scripting")]
+    app.add_plugin(rust_graphics_sandbox::scripting::Scripting::new());
 
-    event_loop.run_app(&mut app).expect("Failed to run app");
+    rust_graphics_sandbox::run(app);
 }
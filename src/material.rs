@@ -1,132 +1,683 @@
 use crate::app::State;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
 
+use crate::mesh::VertexEncoding;
+use crate::sampler::SamplerDesc;
 use crate::shader::Shader;
+use crate::texture::Texture;
 
+/// One bind group of a material, in `bindings` order.
 pub struct Binding {
-    pub buffer: Arc<wgpu::Buffer>,
+    pub resource: BindingResource,
     pub visibility: wgpu::ShaderStages,
 }
 
-pub struct Material {
+pub enum BindingResource {
+    /// A uniform buffer at binding 0.
+    Uniform(Arc<wgpu::Buffer>),
+    /// Texture `i` at binding `2 * i` and a sampler made from its
+    /// `SamplerDesc` right after, each laid out for the texture's
+    /// dimension and format. Sharing a group keeps materials within the
+    /// four bind groups every device supports.
+    Textures(Vec<(Arc<Texture>, SamplerDesc)>),
+}
+
+impl Binding {
+    pub fn uniform(buffer: Arc<wgpu::Buffer>, visibility: wgpu::ShaderStages) -> Self {
+        Binding {
+            resource: BindingResource::Uniform(buffer),
+            visibility,
+        }
+    }
+
+    /// Samples with the texture's own sampler settings until changed with
+    /// `with_sampler`.
+    pub fn texture(texture: Arc<Texture>, visibility: wgpu::ShaderStages) -> Self {
+        Self::textures(vec![texture], visibility)
+    }
+
+    /// Several textures in one group, each with its own sampler.
+    pub fn textures(textures: Vec<Arc<Texture>>, visibility: wgpu::ShaderStages) -> Self {
+        Binding {
+            resource: BindingResource::Textures(
+                textures
+                    .into_iter()
+                    .map(|texture| {
+                        let sampler = texture.sampler_desc;
+                        (texture, sampler)
+                    })
+                    .collect(),
+            ),
+            visibility,
+        }
+    }
+
+    /// Sampler settings for every texture in a texture binding; uniforms
+    /// ignore them.
+    pub fn with_sampler(mut self, desc: SamplerDesc) -> Self {
+        if let BindingResource::Textures(textures) = &mut self.resource {
+            for (_, sampler) in textures {
+                *sampler = desc;
+            }
+        }
+        self
+    }
+}
+
+/// A texture a material samples, and how.
+#[derive(Clone)]
+pub struct MaterialTexture {
+    /// The bind group it is in.
+    pub group: usize,
+    /// Its binding in the group; the sampler's is the next one.
+    pub binding: u32,
+    pub texture: Arc<Texture>,
+    pub sampler: SamplerDesc,
+}
+
+/// Rasterizer settings that need their own pipeline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PrimitiveOptions {
+    pub cull_mode: Option<wgpu::Face>,
+    pub front_face: wgpu::FrontFace,
+    pub topology: wgpu::PrimitiveTopology,
+    /// Polygon offset: constant depth bias in depth-buffer units.
+    pub depth_bias: i32,
+    pub depth_bias_slope_scale: f32,
+    /// `Line` draws wireframes; needs `Features::POLYGON_MODE_LINE`.
+    pub polygon_mode: wgpu::PolygonMode,
+    /// The vertex buffer layout, and with it the vertex entry point.
+    pub vertex_encoding: VertexEncoding,
+}
+
+impl Default for PrimitiveOptions {
+    fn default() -> Self {
+        PrimitiveOptions {
+            cull_mode: None,
+            front_face: wgpu::FrontFace::Ccw,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            depth_bias: 0,
+            depth_bias_slope_scale: 0.0,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            vertex_encoding: VertexEncoding::Full,
+        }
+    }
+}
+
+impl Eq for PrimitiveOptions {}
+
+impl std::hash::Hash for PrimitiveOptions {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.cull_mode.hash(state);
+        self.front_face.hash(state);
+        self.topology.hash(state);
+        self.depth_bias.hash(state);
+        self.depth_bias_slope_scale.to_bits().hash(state);
+        self.polygon_mode.hash(state);
+        self.vertex_encoding.hash(state);
+    }
+}
+
+/// Pipelines still being built on background threads.
+static COMPILING: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of pipelines currently compiling, for loading indicators.
+pub fn compiling_pipelines() -> usize {
+    COMPILING.load(Ordering::Relaxed)
+}
+
+/// A render pipeline that may still be compiling on a background thread.
+#[derive(Clone, Default)]
+pub struct PipelineHandle(Arc<OnceLock<wgpu::RenderPipeline>>);
+
+impl PipelineHandle {
+    /// `None` until compilation has finished.
+    pub fn get(&self) -> Option<&wgpu::RenderPipeline> {
+        self.0.get()
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.0.get().is_some()
+    }
+
+    /// Blocks until the pipeline is compiled.
+    pub fn wait(&self) -> &wgpu::RenderPipeline {
+        self.0.wait()
+    }
+}
+
+/// The shared part of a material: bind group layouts, shaders and the
+/// pipelines compiled from them. Any number of [`MaterialInstance`]s draw
+/// with it, each binding its own uniforms and textures.
+pub struct MaterialTemplate {
     bind_group_layouts: Vec<wgpu::BindGroupLayout>,
-    pub bind_groups: Vec<wgpu::BindGroup>,
     pipeline_layout: wgpu::PipelineLayout,
-    pub pipeline: Arc<wgpu::RenderPipeline>,
+    vertex_module: wgpu::ShaderModule,
+    pixel_module: wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    /// Whether the shader has `vsMainPacked`, for packed meshes.
+    packed: bool,
+    /// Options `pipeline` was built with.
+    pub primitive: PrimitiveOptions,
+    pub pipeline: PipelineHandle,
+    variants: Mutex<HashMap<PrimitiveOptions, PipelineHandle>>,
+    /// For templates made by `with_shader`: the one whose layouts they
+    /// share, and the name of the shader swapped in.
+    base: Option<(Arc<MaterialTemplate>, String)>,
+    /// Templates made from this one by `with_shader`, while in use.
+    shader_variants: Mutex<HashMap<String, Weak<MaterialTemplate>>>,
 }
 
-impl Material {
-    pub fn new_arc(state: &State, bindings: Vec<Binding>, shader: &Shader) -> Arc<Self> {
-        let mut bind_groups = vec![];
-        let mut bind_group_layouts = vec![];
-        for binding in bindings {
-            bind_group_layouts.push(state.device.create_bind_group_layout(
-                &wgpu::BindGroupLayoutDescriptor {
-                    label: None,
-                    entries: &[wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: binding.visibility,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    }],
-                },
-            ));
-            bind_groups.push(state.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                layout: bind_group_layouts.last().unwrap(),
-                entries: &[wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: binding.buffer.as_entire_binding(),
-                }],
-                label: None,
-            }));
-        }
+impl MaterialTemplate {
+    /// Lays out one group per binding, shaped by `bindings`: instances
+    /// must bind the same number of uniforms and textures, with textures
+    /// of the same dimensions and formats.
+    pub fn new_arc(state: &State, bindings: &[Binding], shader: &Shader) -> Arc<Self> {
+        let bind_group_layouts: Vec<_> = bindings
+            .iter()
+            .map(|binding| match &binding.resource {
+                BindingResource::Uniform(_) => uniform_layout(&state.device, binding.visibility),
+                BindingResource::Textures(list) => {
+                    let entries: Vec<_> = texture_slots(0, list)
+                        .iter()
+                        .flat_map(|slot| {
+                            slot.texture.layout_entries(
+                                &state.device,
+                                binding.visibility,
+                                &slot.sampler,
+                                slot.binding,
+                            )
+                        })
+                        .collect();
+                    state
+                        .device
+                        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                            label: None,
+                            entries: &entries,
+                        })
+                }
+            })
+            .collect();
 
-        let swapchain_capabilities = state.surface.get_capabilities(&state.adapter);
-        let swapchain_format = swapchain_capabilities.formats[0];
+        let swapchain_format = state.surface_config.format;
 
         let pipeline_layout =
             state
                 .device
                 .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                     label: None,
-                    bind_group_layouts: &bind_group_layouts.iter().collect::<Vec<_>>(),
+                    bind_group_layouts: &bind_group_layouts
+                        .iter()
+                        .chain(std::iter::once(&state.model_bind_group_layout))
+                        .collect::<Vec<_>>(),
                     push_constant_ranges: &[],
                 });
-        let pipeline = Arc::new(
-            state
-                .device
-                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                    label: None,
-                    layout: Some(&pipeline_layout),
-                    vertex: wgpu::VertexState {
-                        module: &state
-                            .device
-                            .create_shader_module(wgpu::ShaderModuleDescriptor {
-                                label: None,
-                                source: wgpu::ShaderSource::SpirV(
-                                    bytemuck::cast_slice(&shader.vertex_binary).into(),
-                                ),
-                            }),
-                        entry_point: Some("vsMain"),
-                        buffers: &[wgpu::VertexBufferLayout {
-                            array_stride: 32,
-                            step_mode: wgpu::VertexStepMode::Vertex,
-                            attributes: &[
-                                wgpu::VertexAttribute {
-                                    offset: 0,
-                                    shader_location: 0,
-                                    format: wgpu::VertexFormat::Float32x3,
-                                },
-                                wgpu::VertexAttribute {
-                                    offset: 12,
-                                    shader_location: 1,
-                                    format: wgpu::VertexFormat::Float32x3,
-                                },
-                                wgpu::VertexAttribute {
-                                    offset: 24,
-                                    shader_location: 2,
-                                    format: wgpu::VertexFormat::Float32x2,
-                                },
-                            ],
-                        }],
-                        compilation_options: Default::default(),
-                    },
-                    fragment: Some(wgpu::FragmentState {
-                        module: &state
-                            .device
-                            .create_shader_module(wgpu::ShaderModuleDescriptor {
-                                label: None,
-                                source: wgpu::ShaderSource::SpirV(
-                                    bytemuck::cast_slice(&shader.pixel_binary).into(),
-                                ),
-                            }),
-                        entry_point: Some("psMain"),
-                        compilation_options: Default::default(),
-                        targets: &[Some(swapchain_format.into())],
-                    }),
-                    primitive: wgpu::PrimitiveState::default(),
-                    depth_stencil: Some(wgpu::DepthStencilState {
-                        format: wgpu::TextureFormat::Depth32Float,
-                        depth_write_enabled: true,
-                        depth_compare: wgpu::CompareFunction::Less,
-                        stencil: wgpu::StencilState::default(),
-                        bias: wgpu::DepthBiasState::default(),
-                    }),
-                    multisample: wgpu::MultisampleState::default(),
-                    multiview: None,
-                    cache: None,
-                }),
+        let (vertex_module, pixel_module) = shader.create_modules(&state.device);
+
+        let primitive = PrimitiveOptions::default();
+        let pipeline = compile_pipeline(
+            &state.device,
+            &pipeline_layout,
+            &vertex_module,
+            &pixel_module,
+            swapchain_format,
+            primitive,
         );
 
-        Arc::new(Material {
+        Arc::new(MaterialTemplate {
             bind_group_layouts,
-            bind_groups,
             pipeline_layout,
+            vertex_module,
+            pixel_module,
+            format: swapchain_format,
+            packed: shader.has_entry_point(VertexEncoding::Packed.entry_point()),
+            primitive,
+            variants: Mutex::new(HashMap::from([(primitive, pipeline.clone())])),
             pipeline,
+            base: None,
+            shader_variants: Mutex::default(),
+        })
+    }
+
+    /// A template with this one's layouts drawing with `shader` instead,
+    /// for comparing shaders object by object. Made once per `name` and
+    /// shared while anything uses it. Its default pipeline is built before
+    /// returning, so a shader whose entry points or bindings don't fit the
+    /// layout is an error here instead of a model that never draws.
+    pub fn with_shader(
+        self: &Arc<Self>,
+        device: &wgpu::Device,
+        name: &str,
+        shader: &Shader,
+    ) -> Result<Arc<Self>, String> {
+        let base = self.base();
+        let cached = base.shader_variants.lock().unwrap().get(name).cloned();
+        if let Some(template) = cached.and_then(|t| t.upgrade()) {
+            return Ok(template);
+        }
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let (vertex_module, pixel_module) = shader.create_modules(device);
+        let pipeline = create_pipeline(
+            device,
+            &base.pipeline_layout,
+            &vertex_module,
+            &pixel_module,
+            base.format,
+            base.primitive,
+        );
+        if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+            return Err(format!("Shader {name} doesn't fit the material: {error}"));
+        }
+        let pipeline = PipelineHandle(Arc::new(OnceLock::from(pipeline)));
+        let template = Arc::new(MaterialTemplate {
+            bind_group_layouts: base.bind_group_layouts.clone(),
+            pipeline_layout: base.pipeline_layout.clone(),
+            vertex_module,
+            pixel_module,
+            format: base.format,
+            packed: shader.has_entry_point(VertexEncoding::Packed.entry_point()),
+            primitive: base.primitive,
+            variants: Mutex::new(HashMap::from([(base.primitive, pipeline.clone())])),
+            pipeline,
+            base: Some((base.clone(), name.to_string())),
+            shader_variants: Mutex::default(),
+        });
+        base.shader_variants
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), Arc::downgrade(&template));
+        Ok(template)
+    }
+
+    /// The template `with_shader` made this one from, or this one.
+    pub fn base(self: &Arc<Self>) -> &Arc<Self> {
+        match &self.base {
+            Some((base, _)) => base,
+            None => self,
+        }
+    }
+
+    /// The name given to `with_shader`, `None` for the material's own
+    /// shader.
+    pub fn shader_name(&self) -> Option<&str> {
+        self.base.as_ref().map(|(_, name)| name.as_str())
+    }
+
+    /// An instance binding `bindings`, which must match the template's
+    /// layout.
+    pub fn instantiate(
+        self: &Arc<Self>,
+        state: &State,
+        bindings: Vec<Binding>,
+    ) -> Arc<MaterialInstance> {
+        let mut bind_groups = vec![];
+        let mut uniforms = vec![];
+        let mut textures = vec![];
+        for (group, binding) in bindings.into_iter().enumerate() {
+            let layout = &self.bind_group_layouts[group];
+            let bind_group = match binding.resource {
+                BindingResource::Uniform(buffer) => {
+                    let bind_group = uniform_bind_group(&state.device, layout, &buffer);
+                    uniforms.push((group, buffer, binding.visibility));
+                    bind_group
+                }
+                BindingResource::Textures(list) => {
+                    let slots = texture_slots(group, &list);
+                    let bind_group = texture_bind_group(state, layout, slots.iter());
+                    textures.extend(slots);
+                    bind_group
+                }
+            };
+            bind_groups.push(bind_group);
+        }
+        Arc::new(MaterialInstance {
+            template: self.clone(),
+            bind_groups: Mutex::new(bind_groups),
+            uniforms,
+            textures: Mutex::new(textures),
         })
     }
+
+    /// The pipeline for `options`. The first request starts compiling it
+    /// in the background; later ones share the same handle. Packed variants
+    /// of shaders without `vsMainPacked` never become ready.
+    pub fn pipeline_variant(
+        &self,
+        device: &wgpu::Device,
+        options: PrimitiveOptions,
+    ) -> PipelineHandle {
+        if options.vertex_encoding == VertexEncoding::Packed && !self.packed {
+            log::warn!("Material has no vsMainPacked entry point; packed meshes won't draw");
+            return PipelineHandle::default();
+        }
+        let mut variants = self.variants.lock().unwrap();
+        variants
+            .entry(options)
+            .or_insert_with(|| {
+                compile_pipeline(
+                    device,
+                    &self.pipeline_layout,
+                    &self.vertex_module,
+                    &self.pixel_module,
+                    self.format,
+                    options,
+                )
+            })
+            .clone()
+    }
+}
+
+/// A template's bind groups: the uniform buffers and textures one set of
+/// objects draws with. Instances of a template share its pipelines, so
+/// they're cheap to make per object.
+pub struct MaterialInstance {
+    template: Arc<MaterialTemplate>,
+    /// Texture groups are rebuilt when their sampler changes.
+    bind_groups: Mutex<Vec<wgpu::BindGroup>>,
+    /// Group, buffer and visibility of each uniform binding.
+    uniforms: Vec<(usize, Arc<wgpu::Buffer>, wgpu::ShaderStages)>,
+    textures: Mutex<Vec<MaterialTexture>>,
+}
+
+impl MaterialInstance {
+    /// A new template with a single instance of it.
+    pub fn new_arc(state: &State, bindings: Vec<Binding>, shader: &Shader) -> Arc<Self> {
+        Self::new_arc_with_lightmap(state, bindings, shader, None)
+    }
+
+    /// Like `new_arc`, with an optional lightmap bound in its own group
+    /// (texture at 0, sampler at 1) right after `bindings` and before the
+    /// model group. Shaders sample it with the vertex's second UV set.
+    pub fn new_arc_with_lightmap(
+        state: &State,
+        mut bindings: Vec<Binding>,
+        shader: &Shader,
+        lightmap: Option<Arc<Texture>>,
+    ) -> Arc<Self> {
+        if let Some(lightmap) = lightmap {
+            bindings.push(Binding::texture(lightmap, wgpu::ShaderStages::FRAGMENT));
+        }
+        MaterialTemplate::new_arc(state, &bindings, shader).instantiate(state, bindings)
+    }
+
+    pub fn template(&self) -> &Arc<MaterialTemplate> {
+        &self.template
+    }
+
+    /// The template's default pipeline.
+    pub fn pipeline(&self) -> &PipelineHandle {
+        &self.template.pipeline
+    }
+
+    /// Options the template's default pipeline was built with.
+    pub fn primitive(&self) -> PrimitiveOptions {
+        self.template.primitive
+    }
+
+    pub fn pipeline_variant(
+        &self,
+        device: &wgpu::Device,
+        options: PrimitiveOptions,
+    ) -> PipelineHandle {
+        self.template.pipeline_variant(device, options)
+    }
+
+    /// The groups to bind before the model group, in order.
+    pub fn bind_groups(&self) -> Vec<wgpu::BindGroup> {
+        self.bind_groups.lock().unwrap().clone()
+    }
+
+    pub fn textures(&self) -> Vec<MaterialTexture> {
+        self.textures.lock().unwrap().clone()
+    }
+
+    /// Another instance of the same template binding the same buffers and
+    /// textures, whose samplers can then be changed without touching this
+    /// one's.
+    pub fn duplicate(&self, state: &State) -> Arc<Self> {
+        self.template.instantiate(state, self.bindings())
+    }
+
+    /// Like `duplicate`, drawing with `shader` instead; see
+    /// `MaterialTemplate::with_shader`.
+    pub fn with_shader(
+        &self,
+        state: &State,
+        name: &str,
+        shader: &Shader,
+    ) -> Result<Arc<Self>, String> {
+        let template = self.template.with_shader(&state.device, name, shader)?;
+        Ok(template.instantiate(state, self.bindings()))
+    }
+
+    /// Like `duplicate`, drawing with the shader the material was made
+    /// with.
+    pub fn with_own_shader(&self, state: &State) -> Arc<Self> {
+        self.template.base().instantiate(state, self.bindings())
+    }
+
+    pub fn shader_name(&self) -> Option<&str> {
+        self.template.shader_name()
+    }
+
+    /// What `instantiate` would need to bind the same buffers and textures.
+    fn bindings(&self) -> Vec<Binding> {
+        let textures = self.textures();
+        let groups = self.uniforms.len() + {
+            let mut groups: Vec<_> = textures.iter().map(|t| t.group).collect();
+            groups.dedup();
+            groups.len()
+        };
+        (0..groups)
+            .map(|group| match self.uniforms.iter().find(|u| u.0 == group) {
+                Some((_, buffer, visibility)) => Binding::uniform(buffer.clone(), *visibility),
+                None => Binding {
+                    resource: BindingResource::Textures(
+                        textures
+                            .iter()
+                            .filter(|t| t.group == group)
+                            .map(|t| (t.texture.clone(), t.sampler))
+                            .collect(),
+                    ),
+                    // the layout already has the visibility
+                    visibility: wgpu::ShaderStages::NONE,
+                },
+            })
+            .collect()
+    }
+
+    /// Swaps the sampler of `textures()[index]`. The bind group layout
+    /// stays, so a comparison sampler can't become a regular one or the
+    /// other way round, and non-filterable textures stay nearest.
+    pub fn set_sampler(&self, state: &State, index: usize, desc: SamplerDesc) {
+        let mut textures = self.textures.lock().unwrap();
+        let slot = &mut textures[index];
+        if desc.compare.is_some() != slot.sampler.compare.is_some()
+            || (desc.filters() && !slot.texture.filterable(&state.device))
+        {
+            log::warn!("Sampler {desc:?} doesn't fit material group {}", slot.group);
+            return;
+        }
+        slot.sampler = desc;
+        let group = slot.group;
+        self.rebuild_texture_group(state, &textures, group);
+    }
+
+    /// Rebuilds every texture group with samplers fresh from the cache,
+    /// after its settings changed.
+    pub fn refresh_samplers(&self, state: &State) {
+        let textures = self.textures.lock().unwrap();
+        let mut groups: Vec<_> = textures.iter().map(|t| t.group).collect();
+        groups.dedup();
+        for group in groups {
+            self.rebuild_texture_group(state, &textures, group);
+        }
+    }
+
+    fn rebuild_texture_group(&self, state: &State, textures: &[MaterialTexture], group: usize) {
+        let slots = textures.iter().filter(|t| t.group == group);
+        let layout = &self.template.bind_group_layouts[group];
+        let bind_group = texture_bind_group(state, layout, slots);
+        self.bind_groups.lock().unwrap()[group] = bind_group;
+    }
+}
+
+/// Where each texture of a texture binding in `group` goes.
+fn texture_slots(group: usize, list: &[(Arc<Texture>, SamplerDesc)]) -> Vec<MaterialTexture> {
+    list.iter()
+        .enumerate()
+        .map(|(i, (texture, sampler))| MaterialTexture {
+            group,
+            binding: 2 * i as u32,
+            texture: texture.clone(),
+            sampler: *sampler,
+        })
+        .collect()
+}
+
+fn uniform_layout(device: &wgpu::Device, visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: None,
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }],
+    })
+}
+
+fn uniform_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: buffer.as_entire_binding(),
+        }],
+        label: None,
+    })
+}
+
+/// The bind group for one texture group, with samplers from the cache.
+fn texture_bind_group<'a>(
+    state: &State,
+    layout: &wgpu::BindGroupLayout,
+    slots: impl Iterator<Item = &'a MaterialTexture>,
+) -> wgpu::BindGroup {
+    let slots: Vec<_> = slots
+        .map(|slot| (slot, state.samplers.get(&state.device, slot.sampler)))
+        .collect();
+    let entries: Vec<_> = slots
+        .iter()
+        .flat_map(|(slot, sampler)| {
+            [
+                wgpu::BindGroupEntry {
+                    binding: slot.binding,
+                    resource: wgpu::BindingResource::TextureView(&slot.texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: slot.binding + 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ]
+        })
+        .collect();
+    state.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout,
+        entries: &entries,
+    })
+}
+
+/// Builds the pipeline on its own thread so the caller never stalls on the
+/// driver's shader compiler.
+fn compile_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    vertex_module: &wgpu::ShaderModule,
+    pixel_module: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    options: PrimitiveOptions,
+) -> PipelineHandle {
+    let handle = PipelineHandle::default();
+    let slot = handle.clone();
+    let (device, layout) = (device.clone(), layout.clone());
+    let (vertex_module, pixel_module) = (vertex_module.clone(), pixel_module.clone());
+
+    COMPILING.fetch_add(1, Ordering::Relaxed);
+    std::thread::spawn(move || {
+        let pipeline = create_pipeline(
+            &device,
+            &layout,
+            &vertex_module,
+            &pixel_module,
+            format,
+            options,
+        );
+        let _ = slot.0.set(pipeline);
+        COMPILING.fetch_sub(1, Ordering::Relaxed);
+    });
+    handle
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    vertex_module: &wgpu::ShaderModule,
+    pixel_module: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    options: PrimitiveOptions,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: None,
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: vertex_module,
+            entry_point: Some(options.vertex_encoding.entry_point()),
+            buffers: &[options.vertex_encoding.layout()],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: pixel_module,
+            entry_point: Some("psMain"),
+            compilation_options: Default::default(),
+            targets: &[Some(format.into())],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: options.topology,
+            // models are always drawn with u32 indices
+            strip_index_format: options
+                .topology
+                .is_strip()
+                .then_some(wgpu::IndexFormat::Uint32),
+            front_face: options.front_face,
+            cull_mode: options.cull_mode,
+            polygon_mode: options.polygon_mode,
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth32Float,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState {
+                constant: options.depth_bias,
+                slope_scale: options.depth_bias_slope_scale,
+                clamp: 0.0,
+            },
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    })
 }
@@ -0,0 +1,138 @@
+use crate::app::State;
+use crate::input::Action;
+use crate::picking;
+use crate::plugin::{Plugin, PluginContext};
+use crate::world::World;
+
+const POINT_COLOR: [f32; 4] = [1.0, 0.9, 0.2, 1.0];
+const LINE_COLOR: [f32; 4] = [1.0, 0.6, 0.1, 1.0];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeasureMode {
+    /// Two points.
+    Distance,
+    /// Three points; the angle is at the second.
+    Angle,
+}
+
+impl MeasureMode {
+    fn point_count(self) -> usize {
+        match self {
+            MeasureMode::Distance => 2,
+            MeasureMode::Angle => 3,
+        }
+    }
+}
+
+/// Picks points on models with the select binding and labels the distance
+/// or angle between them. A click after a finished measurement starts over.
+pub struct Measure {
+    pub active: bool,
+    pub mode: MeasureMode,
+    points: Vec<glam::Vec3>,
+}
+
+impl Default for Measure {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Measure {
+    pub fn new() -> Self {
+        Measure {
+            active: false,
+            mode: MeasureMode::Distance,
+            points: vec![],
+        }
+    }
+
+    pub fn points(&self) -> &[glam::Vec3] {
+        &self.points
+    }
+
+    pub fn distance(&self) -> Option<f32> {
+        match self.points[..] {
+            [a, b] => Some(a.distance(b)),
+            _ => None,
+        }
+    }
+
+    /// In degrees.
+    pub fn angle(&self) -> Option<f32> {
+        match self.points[..] {
+            [a, b, c] => Some((a - b).angle_between(c - b).to_degrees()),
+            _ => None,
+        }
+    }
+}
+
+impl Plugin for Measure {
+    fn name(&self) -> &str {
+        "Measure"
+    }
+
+    fn update(&mut self, ctx: &mut PluginContext) {
+        if self.active && ctx.input.just_pressed(Action::Select) {
+            if let Some(cursor) = ctx.input.cursor() {
+                let size = glam::vec2(
+                    ctx.state.surface_config.width as f32,
+                    ctx.state.surface_config.height as f32,
+                ) / ctx.state.scale_factor;
+                let ray = picking::cursor_ray(&ctx.world.camera, cursor, size);
+                if let Some(hit) = picking::raycast(ctx.world, &ray) {
+                    if self.points.len() >= self.mode.point_count() {
+                        self.points.clear();
+                    }
+                    self.points.push(hit.point);
+                }
+            }
+        }
+
+        let draw = &mut ctx.world.debug_draw;
+        let marker = ctx.world.camera.eye.distance(ctx.world.camera.center) * 0.02;
+        for &point in &self.points {
+            draw.cross(point, marker, POINT_COLOR);
+        }
+        for pair in self.points.windows(2) {
+            draw.line(pair[0], pair[1], LINE_COLOR);
+        }
+        if let Some(distance) = self.distance() {
+            let middle = (self.points[0] + self.points[1]) * 0.5;
+            draw.text(middle, format!("{distance:.3}"), POINT_COLOR);
+        }
+        if let Some(angle) = self.angle() {
+            draw.text(self.points[1], format!("{angle:.1}°"), POINT_COLOR);
+        }
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, _state: &State, _world: &mut World) {
+        egui::Window::new("Measure").show(ctx, |ui| {
+            ui.checkbox(&mut self.active, "Pick points (select binding)");
+            ui.horizontal(|ui| {
+                for (mode, label) in [
+                    (MeasureMode::Distance, "Distance"),
+                    (MeasureMode::Angle, "Angle"),
+                ] {
+                    if ui.radio_value(&mut self.mode, mode, label).changed() {
+                        self.points.clear();
+                    }
+                }
+            });
+            ui.label(format!(
+                "Points: {}/{}",
+                self.points.len(),
+                self.mode.point_count()
+            ));
+            if let Some(distance) = self.distance() {
+                ui.label(format!("Distance: {distance:.4}"));
+            }
+            if let Some(angle) = self.angle() {
+                ui.label(format!("Angle: {angle:.2}°"));
+            }
+            if ui.button("Clear").clicked() {
+                self.points.clear();
+            }
+        });
+    }
+}
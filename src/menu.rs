@@ -0,0 +1,260 @@
+use crate::app::State;
+use crate::commands::{CommandRegistry, Menu};
+use crate::file_dialog::FileDialog;
+use crate::i18n::tr;
+use crate::import::{self, ImportSettings};
+use crate::workspace::Workspace;
+use crate::world::World;
+
+/// The window's top menu bar, the dialogs it opens and the Ctrl+P
+/// command palette.
+pub struct MenuBar {
+    open_model: FileDialog,
+    open_scene: FileDialog,
+    import: Option<PendingImport>,
+    palette: CommandPalette,
+}
+
+/// A file picked in one of the open dialogs, waiting for its import
+/// settings to be confirmed.
+struct PendingImport {
+    path: String,
+    settings: ImportSettings,
+    /// Open Scene replaces the models; Open Model adds to them.
+    replace: bool,
+}
+
+impl Default for MenuBar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MenuBar {
+    pub fn new() -> Self {
+        MenuBar {
+            open_model: FileDialog::new("Open Model", &[]),
+            open_scene: FileDialog::new("Open Scene", &[]),
+            import: None,
+            palette: CommandPalette::default(),
+        }
+    }
+
+    pub fn ui(
+        &mut self,
+        ctx: &egui::Context,
+        state: &State,
+        world: &mut World,
+        workspace: &mut Workspace,
+    ) {
+        egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
+            egui::MenuBar::new().ui(ui, |ui| {
+                ui.menu_button(tr("menu.file"), |ui| {
+                    let extensions = world.mesh_loaders.extensions();
+                    if ui.button(tr("menu.open_model")).clicked() {
+                        self.open_model.set_extensions(&extensions);
+                        self.open_model.open();
+                    }
+                    if ui
+                        .button(tr("menu.open_scene"))
+                        .on_hover_text(tr("menu.open_scene_hint"))
+                        .clicked()
+                    {
+                        self.open_scene.set_extensions(&extensions);
+                        self.open_scene.open();
+                    }
+                    ui.menu_button(tr("menu.recent"), |ui| {
+                        if workspace.recent.is_empty() {
+                            ui.label(tr("menu.recent_empty"));
+                        }
+                        for path in workspace.recent.clone() {
+                            if ui
+                                .button(&path)
+                                .on_hover_text(tr("menu.recent_hint"))
+                                .clicked()
+                            {
+                                world.load_model_in_background(state, &path);
+                                workspace.opened_model(&path);
+                            }
+                        }
+                    });
+                    ui.separator();
+                    command_buttons(ui, &mut world.commands, Menu::File);
+                });
+                for menu in [Menu::Edit, Menu::View, Menu::Render] {
+                    ui.menu_button(menu.label(), |ui| {
+                        command_buttons(ui, &mut world.commands, menu);
+                    });
+                }
+                ui.menu_button(Menu::Help.label(), |ui| {
+                    if ui.button(tr("menu.command_palette")).clicked() {
+                        self.palette.open();
+                    }
+                    command_buttons(ui, &mut world.commands, Menu::Help);
+                });
+            });
+        });
+
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::P)) {
+            self.palette.open();
+        }
+        self.palette.ui(ctx, &mut world.commands);
+
+        for (dialog, replace) in [(&mut self.open_model, false), (&mut self.open_scene, true)] {
+            if let Some(path) = dialog.show(ctx) {
+                let path = path.to_string_lossy().into_owned();
+                self.import = Some(PendingImport {
+                    settings: world.import_settings(&path),
+                    path,
+                    replace,
+                });
+            }
+        }
+        self.import_ui(ctx, state, world, workspace);
+    }
+
+    /// Lets the picked file's scale, up axis and winding be adjusted before
+    /// it loads. The settings are remembered for the file.
+    fn import_ui(
+        &mut self,
+        ctx: &egui::Context,
+        state: &State,
+        world: &mut World,
+        workspace: &mut Workspace,
+    ) {
+        let Some(import) = &mut self.import else {
+            return;
+        };
+        let mut confirmed = false;
+        let mut cancelled = false;
+        egui::Window::new("Import Settings")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(&import.path);
+                ui.separator();
+                import::settings_ui(ui, &mut import.settings);
+                ui.separator();
+                ui.horizontal(|ui| {
+                    confirmed = ui.button("Import").clicked();
+                    cancelled = ui.button("Cancel").clicked();
+                });
+            });
+        if cancelled {
+            self.import = None;
+        }
+        if !confirmed {
+            return;
+        }
+        let import = self.import.take().unwrap();
+        if import.settings.is_identity() {
+            world.import_settings.remove(&import.path);
+        } else {
+            world
+                .import_settings
+                .insert(import.path.clone(), import.settings);
+        }
+        if import.replace {
+            world.clear();
+            world.load_model_in_background(state, &import.path);
+            workspace.opened_scene(&import.path);
+        } else {
+            world.load_model_in_background(state, &import.path);
+            workspace.opened_model(&import.path);
+        }
+    }
+}
+
+/// Queues the clicked command; the menu closes itself.
+fn command_buttons(ui: &mut egui::Ui, commands: &mut CommandRegistry, menu: Menu) {
+    let mut clicked = None;
+    for command in commands.in_menu(menu) {
+        if ui.button(&command.label).clicked() {
+            clicked = Some(command.id.clone());
+        }
+    }
+    if let Some(id) = clicked {
+        commands.queue(&id);
+    }
+}
+
+/// Fuzzy search over every registered command. Arrow keys move the
+/// selection, Enter runs it, Escape closes.
+#[derive(Default)]
+struct CommandPalette {
+    open: bool,
+    query: String,
+    selected: usize,
+    focus: bool,
+}
+
+impl CommandPalette {
+    fn open(&mut self) {
+        self.open = true;
+        self.query.clear();
+        self.selected = 0;
+        self.focus = true;
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, commands: &mut CommandRegistry) {
+        if !self.open {
+            return;
+        }
+        let mut run = None;
+        egui::Window::new("Command Palette")
+            .title_bar(false)
+            .anchor(egui::Align2::CENTER_TOP, [0.0, 40.0])
+            .fixed_size([360.0, 0.0])
+            .show(ctx, |ui| {
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut self.query)
+                        .hint_text("Type a command")
+                        .desired_width(f32::INFINITY),
+                );
+                if std::mem::take(&mut self.focus) {
+                    response.request_focus();
+                }
+                if response.changed() {
+                    self.selected = 0;
+                }
+
+                let results = commands.search(&self.query);
+                let (up, down, enter, escape) = ui.input(|i| {
+                    (
+                        i.key_pressed(egui::Key::ArrowUp),
+                        i.key_pressed(egui::Key::ArrowDown),
+                        i.key_pressed(egui::Key::Enter),
+                        i.key_pressed(egui::Key::Escape),
+                    )
+                });
+                if down {
+                    self.selected = (self.selected + 1).min(results.len().saturating_sub(1));
+                }
+                if up {
+                    self.selected = self.selected.saturating_sub(1);
+                }
+                if escape {
+                    self.open = false;
+                }
+
+                ui.separator();
+                for (i, command) in results.iter().enumerate().take(12) {
+                    let label = format!("{}  ·  {}", command.label, command.menu.label());
+                    if ui.selectable_label(i == self.selected, label).clicked() {
+                        run = Some(command.id.clone());
+                    }
+                }
+                if results.is_empty() {
+                    ui.label("No matching commands");
+                } else if enter {
+                    run = Some(results[self.selected.min(results.len() - 1)].id.clone());
+                }
+            });
+
+        if let Some(id) = run {
+            commands.queue(&id);
+            self.open = false;
+        }
+    }
+}
@@ -1,18 +1,392 @@
-use std::sync::Arc;
+use crate::app::State;
+use crate::import::ImportSettings;
+use crate::texture::Texture;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
 use wgpu::util::DeviceExt;
 
+/// Which file, and which of its meshes, a loaded mesh came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MeshSource {
+    pub path: String,
+    pub index: usize,
+}
+
+/// Tightly packed RGBA8 rows, e.g. a decoded glTF image.
+pub struct MeshImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// Vertex and index buffers for one glTF primitive.
 pub struct Mesh {
+    pub name: String,
+    /// Set by `MeshLoaders::load`; `None` for generated meshes.
+    pub source: Option<MeshSource>,
     pub vertex_buffer: wgpu::Buffer,
+    /// How `vertex_buffer` is laid out; the CPU copies are always full.
+    pub encoding: VertexEncoding,
     pub index_buffer: wgpu::Buffer,
     pub index_count: u32,
+    /// CPU copies of the vertex positions and indices, for picking.
+    pub positions: Vec<glam::Vec3>,
+    pub indices: Vec<u32>,
+    /// CPU copies for the debug overlays. `tangents` is empty when the file
+    /// has none.
+    pub normals: Vec<glam::Vec3>,
+    pub tangents: Vec<glam::Vec4>,
+    /// TEXCOORD_0, for the UV layout viewer.
+    pub uvs: Vec<glam::Vec2>,
+    /// The vertex attributes the source provided, by glTF semantic name.
+    /// Missing ones are zero-filled or generated in the vertex buffer.
+    pub attributes: Vec<String>,
+    /// Skin joints in the mesh's bind pose, and the parent-child pairs
+    /// between them. Empty for unskinned meshes.
+    pub joints: Vec<glam::Vec3>,
+    pub bones: Vec<[usize; 2]>,
+    /// Each joint's node name and bind-pose transform in the mesh's space,
+    /// in the same order as `joints`.
+    pub joint_names: Vec<String>,
+    pub joint_matrices: Vec<glam::Mat4>,
+    /// From the glTF material; single-sided meshes get back-face culling.
+    pub double_sided: bool,
+    pub base_color: [f32; 4],
+    /// Set for glTF `alphaMode: MASK`; fragments with lower alpha are discarded.
+    pub alpha_cutoff: Option<f32>,
+    /// The base color texture of MASK materials, whose alpha the cutout
+    /// multiplies in; read with TEXCOORD_0. Other materials don't load one.
+    pub base_color_texture: Option<Arc<MeshImage>>,
+    /// `base_color_texture` uploaded, by the first model that binds it.
+    base_color_view: OnceLock<wgpu::TextureView>,
+}
+
+const VERTEX_USAGE: wgpu::BufferUsages =
+    wgpu::BufferUsages::VERTEX.union(wgpu::BufferUsages::COPY_DST);
+const INDEX_USAGE: wgpu::BufferUsages =
+    wgpu::BufferUsages::INDEX.union(wgpu::BufferUsages::COPY_DST);
+/// Part of `import_gltf`'s cache key. Bump it whenever the importer's
+/// output changes for the same file, e.g. how normals are generated.
+const IMPORT_VERSION: u32 = 2;
+
+impl Mesh {
+    /// A double-sided, white mesh with POSITION, NORMAL and TEXCOORD_0.
+    /// Loaders fill in the rest of the fields afterwards.
+    pub fn new(device: &wgpu::Device, name: &str, vertices: &[Vertex], indices: &[u32]) -> Self {
+        Self::with_encoding(device, name, vertices, indices, VertexEncoding::Full)
+    }
+
+    /// Like `new`, storing the vertex buffer as `encoding`.
+    pub fn with_encoding(
+        device: &wgpu::Device,
+        name: &str,
+        vertices: &[Vertex],
+        indices: &[u32],
+        encoding: VertexEncoding,
+    ) -> Self {
+        let vertex_buffer = create_buffer(
+            device,
+            "Vertex Buffer",
+            &encoding.encode(vertices),
+            VERTEX_USAGE,
+        );
+        let index_buffer = create_buffer(device, "Index Buffer", indices, INDEX_USAGE);
+        Self::from_buffers(
+            name,
+            vertex_buffer,
+            encoding,
+            index_buffer,
+            vertices,
+            indices,
+        )
+    }
+
+    /// Like `with_encoding`, around buffers that already hold `vertices`
+    /// as `encoding` and `indices`.
+    pub fn from_buffers(
+        name: &str,
+        vertex_buffer: wgpu::Buffer,
+        encoding: VertexEncoding,
+        index_buffer: wgpu::Buffer,
+        vertices: &[Vertex],
+        indices: &[u32],
+    ) -> Self {
+        let mut mesh = Mesh {
+            name: name.to_string(),
+            source: None,
+            vertex_buffer,
+            encoding,
+            index_buffer,
+            index_count: 0,
+            positions: vec![],
+            indices: vec![],
+            normals: vec![],
+            tangents: vec![],
+            uvs: vec![],
+            attributes: ["POSITION", "NORMAL", "TEXCOORD_0"]
+                .map(String::from)
+                .to_vec(),
+            joints: vec![],
+            bones: vec![],
+            joint_names: vec![],
+            joint_matrices: vec![],
+            double_sided: true,
+            base_color: [1.0; 4],
+            alpha_cutoff: None,
+            base_color_texture: None,
+            base_color_view: OnceLock::new(),
+        };
+        mesh.copy_vertices(vertices);
+        mesh.copy_indices(indices);
+        mesh
+    }
+
+    /// Replaces the vertex data, writing into the existing buffer when it
+    /// is big enough and growing it otherwise. Tangents are dropped if the
+    /// vertex count changes.
+    pub fn update_vertices(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        vertices: &[Vertex],
+    ) {
+        let bytes = self.encoding.encode(vertices);
+        if bytes.len() as u64 > self.vertex_buffer.size() {
+            self.vertex_buffer = create_grown_buffer(device, "Vertex Buffer", &bytes, VERTEX_USAGE);
+        } else {
+            queue.write_buffer(&self.vertex_buffer, 0, &bytes);
+        }
+        self.copy_vertices(vertices);
+    }
+
+    /// Like `update_vertices`; only the first `indices.len()` are drawn.
+    pub fn update_indices(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, indices: &[u32]) {
+        let bytes: &[u8] = bytemuck::cast_slice(indices);
+        if bytes.len() as u64 > self.index_buffer.size() {
+            self.index_buffer = create_grown_buffer(device, "Index Buffer", bytes, INDEX_USAGE);
+        } else {
+            queue.write_buffer(&self.index_buffer, 0, bytes);
+        }
+        self.copy_indices(indices);
+    }
+
+    fn copy_vertices(&mut self, vertices: &[Vertex]) {
+        if self.tangents.len() != vertices.len() {
+            self.tangents.clear();
+        }
+        self.positions = vertices.iter().map(|v| glam::Vec3::from(v.pos)).collect();
+        self.normals = vertices
+            .iter()
+            .map(|v| glam::Vec3::from(v.normal))
+            .collect();
+        self.uvs = vertices.iter().map(|v| glam::Vec2::from(v.uv)).collect();
+    }
+
+    fn copy_indices(&mut self, indices: &[u32]) {
+        self.index_count = indices.len() as u32;
+        self.indices = indices.to_vec();
+    }
+
+    /// Local-space axis-aligned bounds; zero for an empty mesh.
+    /// `base_color_texture` on the GPU, if there is one.
+    pub fn base_color_view(&self, state: &State) -> Option<&wgpu::TextureView> {
+        let image = self.base_color_texture.as_ref()?;
+        Some(self.base_color_view.get_or_init(|| {
+            let (width, height) = (image.width, image.height);
+            Texture::from_rgba8(state, &self.name, width, height, &image.pixels, true).view
+        }))
+    }
+
+    pub fn bounds(&self) -> (glam::Vec3, glam::Vec3) {
+        if self.positions.is_empty() {
+            return (glam::Vec3::ZERO, glam::Vec3::ZERO);
+        }
+        self.positions.iter().fold(
+            (glam::Vec3::splat(f32::MAX), glam::Vec3::splat(f32::MIN)),
+            |(min, max), &p| (min.min(p), max.max(p)),
+        )
+    }
+
+    /// Vertex bytes saved over the full `Vertex` layout.
+    pub fn packed_savings(&self) -> u64 {
+        let full = std::mem::size_of::<Vertex>() as u64;
+        self.positions.len() as u64 * (full - self.encoding.stride())
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Vertex {
+    pub pos: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+    /// TEXCOORD_1, e.g. for lightmaps; a copy of `uv` when the mesh has none.
+    pub uv1: [f32; 2],
 }
 
+impl Vertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32x3,
+        2 => Float32x2,
+        3 => Float32x2
+    ];
+
+    pub fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Vertex>() as u64,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// How a mesh's vertices are stored on the GPU. Pipelines pick the
+/// matching layout and vertex entry point through `PrimitiveOptions`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum VertexEncoding {
+    /// `Vertex`, 40 bytes, drawn with `vsMain`.
+    #[default]
+    Full,
+    /// `PackedVertex`, 24 bytes, drawn with `vsMainPacked`.
+    Packed,
+}
+
+impl VertexEncoding {
+    pub fn stride(self) -> u64 {
+        match self {
+            VertexEncoding::Full => std::mem::size_of::<Vertex>() as u64,
+            VertexEncoding::Packed => std::mem::size_of::<PackedVertex>() as u64,
+        }
+    }
+
+    pub fn layout(self) -> wgpu::VertexBufferLayout<'static> {
+        match self {
+            VertexEncoding::Full => Vertex::layout(),
+            VertexEncoding::Packed => PackedVertex::layout(),
+        }
+    }
+
+    /// The vertex shader entry point that reads this layout.
+    pub fn entry_point(self) -> &'static str {
+        match self {
+            VertexEncoding::Full => "vsMain",
+            VertexEncoding::Packed => "vsMainPacked",
+        }
+    }
+
+    /// The vertex buffer contents.
+    pub fn encode(self, vertices: &[Vertex]) -> Vec<u8> {
+        match self {
+            VertexEncoding::Full => bytemuck::cast_slice(vertices).to_vec(),
+            VertexEncoding::Packed => {
+                let packed: Vec<PackedVertex> = vertices.iter().map(PackedVertex::new).collect();
+                bytemuck::cast_slice(&packed).to_vec()
+            }
+        }
+    }
+}
+
+/// `Vertex` with an octahedral-encoded normal in two snorm16s and half-float
+/// UVs. UVs keep about three decimal digits, so heavily tiled coordinates
+/// lose precision far from zero.
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-struct Vertex {
-    pos: [f32; 3],
-    normal: [f32; 3],
-    uv: [f32; 2],
+pub struct PackedVertex {
+    pub pos: [f32; 3],
+    pub normal: [i16; 2],
+    pub uv: [u16; 2],
+    pub uv1: [u16; 2],
+}
+
+impl PackedVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+        0 => Float32x3,
+        1 => Snorm16x2,
+        2 => Float16x2,
+        3 => Float16x2
+    ];
+
+    pub fn new(vertex: &Vertex) -> Self {
+        PackedVertex {
+            pos: vertex.pos,
+            normal: octahedral_encode(vertex.normal.into()),
+            uv: vertex.uv.map(f16_bits),
+            uv1: vertex.uv1.map(f16_bits),
+        }
+    }
+
+    pub fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<PackedVertex>() as u64,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// `octDecode` for WGSL shaders with a `vsMainPacked` entry point; append
+/// it to the shader source. The normal arrives at location 1 as a vec2.
+pub const OCTAHEDRAL_WGSL: &str = r#"
+fn octDecode(e: vec2<f32>) -> vec3<f32> {
+    var n = vec3(e, 1.0 - abs(e.x) - abs(e.y));
+    let t = max(-n.z, 0.0);
+    n.x += select(t, -t, n.x >= 0.0);
+    n.y += select(t, -t, n.y >= 0.0);
+    return normalize(n);
+}
+"#;
+
+/// Projects a unit vector onto an octahedron unfolded into the -1..1 square;
+/// `octDecode` in the shaders reverses it.
+pub fn octahedral_encode(n: glam::Vec3) -> [i16; 2] {
+    let sum = n.x.abs() + n.y.abs() + n.z.abs();
+    if sum == 0.0 {
+        return [0, 0];
+    }
+    let n = n / sum;
+    let mut p = glam::vec2(n.x, n.y);
+    if n.z < 0.0 {
+        // fold the lower half over the diagonals
+        let sign = glam::vec2(
+            if p.x >= 0.0 { 1.0 } else { -1.0 },
+            if p.y >= 0.0 { 1.0 } else { -1.0 },
+        );
+        p = (glam::Vec2::ONE - glam::vec2(p.y, p.x).abs()) * sign;
+    }
+    (p.clamp(glam::Vec2::NEG_ONE, glam::Vec2::ONE) * i16::MAX as f32)
+        .round()
+        .to_array()
+        .map(|c| c as i16)
+}
+
+/// IEEE half-precision bits, rounding to nearest.
+fn f16_bits(x: f32) -> u16 {
+    let bits = x.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent == 0xff {
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+    let e = exponent - 127 + 15;
+    if e >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if e <= 0 {
+        // subnormal, or zero when too small
+        if e < -10 {
+            return sign;
+        }
+        let m = mantissa | 0x80_0000;
+        let shift = (14 - e) as u32;
+        return sign | ((m >> shift) + ((m >> (shift - 1)) & 1)) as u16;
+    }
+    // a rounding carry out of the mantissa correctly bumps the exponent
+    let half = ((e as u32) << 10) | (mantissa >> 13);
+    sign | (half + ((mantissa >> 12) & 1)) as u16
 }
 
 pub fn create_test_mesh(device: &wgpu::Device) -> Arc<Mesh> {
@@ -21,99 +395,440 @@ pub fn create_test_mesh(device: &wgpu::Device) -> Arc<Mesh> {
             pos: [0.0, 0.5, 0.0],
             normal: [0.0, 0.0, 1.0],
             uv: [0.5, 0.0],
+            uv1: [0.5, 0.0],
         },
         Vertex {
             pos: [-0.5, -0.5, 0.0],
             normal: [0.0, 0.0, 1.0],
             uv: [0.0, 1.0],
+            uv1: [0.0, 1.0],
         },
         Vertex {
             pos: [0.5, -0.5, 0.0],
             normal: [0.0, 0.0, 1.0],
             uv: [1.0, 1.0],
+            uv1: [1.0, 1.0],
         },
     ];
 
-    let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: Some("Vertex Buffer"),
-        contents: bytemuck::cast_slice(&verts),
-        usage: wgpu::BufferUsages::VERTEX,
-    });
+    Arc::new(Mesh::new(device, "Triangle", &verts, &[0, 1, 2]))
+}
+
+/// The small parts of a primitive, cached as JSON.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct PrimitiveInfo {
+    pub name: String,
+    pub attributes: Vec<String>,
+    pub joint_names: Vec<String>,
+    pub bones: Vec<[usize; 2]>,
+    pub double_sided: bool,
+    pub base_color: [f32; 4],
+    pub alpha_cutoff: Option<f32>,
+}
+
+/// A primitive's CPU data, decoded and with the import settings applied.
+pub(crate) struct Primitive {
+    pub info: PrimitiveInfo,
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    pub tangents: Vec<glam::Vec4>,
+    pub joint_matrices: Vec<glam::Mat4>,
+    /// Not part of the cached primitive; see `read_cutout_textures`.
+    pub base_color_texture: Option<Arc<MeshImage>>,
+}
+
+impl PrimitiveInfo {
+    /// Fills in what `Mesh::new` leaves at its defaults.
+    pub fn apply(
+        self,
+        mesh: &mut Mesh,
+        tangents: Vec<glam::Vec4>,
+        joint_matrices: Vec<glam::Mat4>,
+    ) {
+        if tangents.len() == mesh.positions.len() {
+            mesh.tangents = tangents;
+        }
+        mesh.attributes = self.attributes;
+        mesh.joints = joint_matrices.iter().map(|m| m.w_axis.truncate()).collect();
+        mesh.bones = self.bones;
+        mesh.joint_names = self.joint_names;
+        mesh.joint_matrices = joint_matrices;
+        mesh.double_sided = self.double_sided;
+        mesh.base_color = self.base_color;
+        mesh.alpha_cutoff = self.alpha_cutoff;
+    }
+}
+
+impl Primitive {
+    pub fn into_mesh(self, device: &wgpu::Device, encoding: VertexEncoding) -> Mesh {
+        let mut mesh = Mesh::with_encoding(
+            device,
+            &self.info.name,
+            &self.vertices,
+            &self.indices,
+            encoding,
+        );
+        self.info
+            .apply(&mut mesh, self.tangents, self.joint_matrices);
+        mesh.base_color_texture = self.base_color_texture;
+        mesh
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let info = serde_json::to_vec(&self.info).unwrap_or_default();
+        let tangents: Vec<[f32; 4]> = self.tangents.iter().map(|t| t.to_array()).collect();
+        let joint_matrices: Vec<[f32; 16]> = self
+            .joint_matrices
+            .iter()
+            .map(|m| m.to_cols_array())
+            .collect();
+        crate::asset_cache::pack(&[
+            &info,
+            bytemuck::cast_slice(&self.vertices),
+            bytemuck::cast_slice(&self.indices),
+            bytemuck::cast_slice(&tangents),
+            bytemuck::cast_slice(&joint_matrices),
+        ])
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let [info, vertices, indices, tangents, joint_matrices] =
+            <[&[u8]; 5]>::try_from(crate::asset_cache::unpack(bytes)?).ok()?;
+        Some(Primitive {
+            info: serde_json::from_slice(info).ok()?,
+            // sections aren't aligned, so these copy
+            vertices: bytemuck::pod_collect_to_vec(vertices),
+            indices: bytemuck::pod_collect_to_vec(indices),
+            tangents: decode_tangents(tangents),
+            joint_matrices: decode_matrices(joint_matrices),
+            base_color_texture: None,
+        })
+    }
+}
+
+pub(crate) fn decode_tangents(bytes: &[u8]) -> Vec<glam::Vec4> {
+    let tangents: Vec<[f32; 4]> = bytemuck::pod_collect_to_vec(bytes);
+    tangents.into_iter().map(glam::Vec4::from_array).collect()
+}
+
+pub(crate) fn decode_matrices(bytes: &[u8]) -> Vec<glam::Mat4> {
+    let matrices: Vec<[f32; 16]> = bytemuck::pod_collect_to_vec(bytes);
+    matrices.iter().map(glam::Mat4::from_cols_array).collect()
+}
+
+pub fn load_gltf(device: &wgpu::Device, path: &str, settings: &ImportSettings) -> Vec<Arc<Mesh>> {
+    import_gltf(path, settings)
+        .into_iter()
+        .map(|prim| Arc::new(prim.into_mesh(device, settings.vertex_encoding)))
+        .collect()
+}
+
+/// The CPU side of `load_gltf`, which needs no device. Empty when the file
+/// can't be read.
+pub(crate) fn import_gltf(path: &str, settings: &ImportSettings) -> Vec<Primitive> {
+    let (gltf, buffs) = match read_gltf(path) {
+        Ok(imported) => imported,
+        Err(e) => {
+            log::warn!("Failed to import {path}: {e}");
+            return vec![];
+        }
+    };
+    let doc = &gltf.document;
+
+    // the document and every buffer, which covers a .glb's blob and a
+    // .gltf's external .bin files alike
+    let document = doc.clone().into_json();
+    let document = serde_json::to_vec(&document).unwrap_or_default();
+    let settings_json = serde_json::to_vec(settings).unwrap_or_default();
+    let mut parts: Vec<&[u8]> = vec![&document, &settings_json];
+    parts.extend(buffs.iter().map(|b| &b.0[..]));
+    let key = crate::asset_cache::key("gltf", IMPORT_VERSION, &parts);
 
-    let indices = [0, 1, 2];
-    let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: Some("Index Buffer"),
-        contents: bytemuck::cast_slice(&indices),
-        usage: wgpu::BufferUsages::INDEX,
+    let cached = crate::asset_cache::read(&key).and_then(|bytes| {
+        let sections = crate::asset_cache::unpack(&bytes)?;
+        sections
+            .into_iter()
+            .map(Primitive::decode)
+            .collect::<Option<Vec<_>>>()
     });
+    if let Some(mut primitives) = cached {
+        read_cutout_textures(path, doc, &buffs, &mut primitives);
+        return primitives;
+    }
+    let primitives: Vec<_> = doc
+        .meshes()
+        .flat_map(|mesh| mesh.primitives().map(move |prim| (mesh.clone(), prim)))
+        .collect();
+    let label = format!("Importing {path}");
+    let primitives = crate::jobs::map(&label, &primitives, |(mesh, prim)| {
+        read_primitive(doc, &buffs, mesh, prim, settings)
+    });
+    let mut primitives = match primitives.into_iter().collect::<Result<Vec<_>, _>>() {
+        Ok(primitives) => primitives,
+        Err(e) => {
+            log::warn!("Failed to import {path}: {e}");
+            return vec![];
+        }
+    };
+    let encoded: Vec<_> = primitives.iter().map(Primitive::encode).collect();
+    let sections: Vec<&[u8]> = encoded.iter().map(Vec::as_slice).collect();
+    crate::asset_cache::write(&key, &crate::asset_cache::pack(&sections));
+    read_cutout_textures(path, doc, &buffs, &mut primitives);
+    primitives
+}
 
-    println!("VERTICES: {:?}", &verts[..3]);
-    println!("INDICES: {:?}", &indices[..3]);
+/// Gives MASK primitives their base color texture, decoding each image
+/// once however many primitives share it. Images have their own cache.
+fn read_cutout_textures(
+    path: &str,
+    doc: &gltf::Document,
+    buffs: &[gltf::buffer::Data],
+    primitives: &mut [Primitive],
+) {
+    let mut images: HashMap<usize, Option<Arc<MeshImage>>> = HashMap::new();
+    let sources = doc.meshes().flat_map(|mesh| mesh.primitives());
+    for (primitive, source) in primitives.iter_mut().zip(sources) {
+        let material = source.material();
+        if material.alpha_mode() != gltf::material::AlphaMode::Mask {
+            continue;
+        }
+        let Some(info) = material.pbr_metallic_roughness().base_color_texture() else {
+            continue;
+        };
+        let image = info.texture().source();
+        primitive.base_color_texture = images
+            .entry(image.index())
+            .or_insert_with(|| match read_image(path, buffs, &image) {
+                Ok(image) => Some(Arc::new(image)),
+                Err(e) => {
+                    log::warn!("{path}: image {}: {e}", image.index());
+                    None
+                }
+            })
+            .clone();
+    }
+}
 
-    Arc::new(Mesh {
-        vertex_buffer,
-        index_buffer,
-        index_count: indices.len() as u32,
+/// An image from a buffer view or a file beside `path`, through the VFS.
+fn read_image(
+    path: &str,
+    buffs: &[gltf::buffer::Data],
+    image: &gltf::Image,
+) -> Result<MeshImage, String> {
+    let bytes = match image.source() {
+        gltf::image::Source::View { view, .. } => buffs[view.buffer().index()]
+            .get(view.offset()..view.offset() + view.length())
+            .ok_or("the buffer view is out of bounds")?
+            .to_vec(),
+        gltf::image::Source::Uri { uri, .. } if !uri.contains(':') => {
+            let base = std::path::Path::new(path)
+                .parent()
+                .unwrap_or(std::path::Path::new(""));
+            crate::vfs::read(base.join(crate::uri::percent_decode(uri)))
+                .map_err(|e| format!("{uri}: {e}"))?
+        }
+        gltf::image::Source::Uri { .. } => return Err("data URIs aren't supported".to_string()),
+    };
+    let (width, height, pixels) = crate::texture::decode_image(&bytes)?;
+    Ok(MeshImage {
+        width,
+        height,
+        pixels,
     })
 }
 
-pub fn load_gltf(device: &wgpu::Device, path: &str) -> Vec<Arc<Mesh>> {
-    let (doc, buffs, _) = gltf::import(path).unwrap();
-    let mut meshes = vec![];
-
-    for mesh in doc.meshes() {
-        for prim in mesh.primitives() {
-            let reader = prim.reader(|b| Some(&buffs[b.index()]));
-
-            let positions: Vec<[f32; 3]> = reader
-                .read_positions()
-                .map(|v| v.collect())
-                .unwrap_or_else(Vec::new);
-            let normals: Vec<[f32; 3]> = reader
-                .read_normals()
-                .map(|v| v.collect())
-                .unwrap_or_else(|| vec![[0.0; 3]; positions.len()]);
-            let uvs: Vec<[f32; 2]> = reader
-                .read_tex_coords(0)
-                .map(|v| v.into_f32().collect())
-                .unwrap_or_else(|| vec![[0.0; 2]; positions.len()]);
-
-            let verts: Vec<Vertex> = positions
-                .iter()
-                .enumerate()
-                .map(|(i, &pos)| Vertex {
-                    pos,
-                    normal: normals.get(i).copied().unwrap_or([0.0; 3]),
-                    uv: uvs.get(i).copied().unwrap_or([0.0; 2]),
-                })
-                .collect();
-
-            let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Vertex Buffer"),
-                contents: bytemuck::cast_slice(&verts),
-                usage: wgpu::BufferUsages::VERTEX,
-            });
-
-            let indices: Vec<u32> = reader
-                .read_indices()
-                .map(|v| v.into_u32().collect())
-                .unwrap_or_else(|| (0..positions.len() as u32).collect());
-
-            println!("VERTICES: {:?}", &verts[..3]);
-            println!("INDICES: {:?}", &indices[..3]);
-
-            let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Index Buffer"),
-                contents: bytemuck::cast_slice(&indices),
-                usage: wgpu::BufferUsages::INDEX,
-            });
-
-            meshes.push(Arc::new(Mesh {
-                vertex_buffer,
-                index_buffer,
-                index_count: indices.len() as u32,
-            }));
-        }
-    }
-    meshes
+/// The document and its buffers, through the VFS. Images aren't loaded
+/// here; decoding every texture was most of the load time for textured
+/// scenes, so only cutouts' base color textures are, afterwards.
+fn read_gltf(path: &str) -> Result<(gltf::Gltf, Vec<gltf::buffer::Data>), String> {
+    let bytes = crate::vfs::read(path).map_err(|e| e.to_string())?;
+    let gltf = gltf::Gltf::from_slice(&bytes).map_err(|e| e.to_string())?;
+    let base = std::path::Path::new(path)
+        .parent()
+        .unwrap_or(std::path::Path::new(""));
+    let mut blob = gltf.blob.clone();
+    let mut buffs = vec![];
+    for buffer in gltf.document.buffers() {
+        let data = match buffer.source() {
+            // external files resolve through the VFS too
+            gltf::buffer::Source::Uri(uri) if !uri.contains(':') => {
+                let mut data = crate::vfs::read(base.join(crate::uri::percent_decode(uri)))
+                    .map_err(|e| format!("{uri}: {e}"))?;
+                data.resize(data.len().next_multiple_of(4), 0);
+                gltf::buffer::Data(data)
+            }
+            source => gltf::buffer::Data::from_source_and_blob(source, None, &mut blob)
+                .map_err(|e| e.to_string())?,
+        };
+        if data.len() < buffer.length() {
+            return Err(format!("buffer {} is truncated", buffer.index()));
+        }
+        buffs.push(data);
+    }
+    Ok((gltf, buffs))
+}
+
+fn read_primitive(
+    doc: &gltf::Document,
+    buffs: &[gltf::buffer::Data],
+    mesh: &gltf::Mesh,
+    prim: &gltf::Primitive,
+    settings: &ImportSettings,
+) -> Result<Primitive, String> {
+    let reader = prim.reader(|b| Some(&buffs[b.index()]));
+    let material = prim.material();
+
+    let positions: Vec<[f32; 3]> = reader
+        .read_positions()
+        .map(|v| v.collect())
+        .unwrap_or_default();
+    let has_normals = reader.read_normals().is_some();
+    let normals: Vec<[f32; 3]> = reader
+        .read_normals()
+        .map(|v| v.collect())
+        .unwrap_or_else(|| vec![[0.0; 3]; positions.len()]);
+    let mut tangents: Vec<glam::Vec4> = reader
+        .read_tangents()
+        .map(|v| {
+            v.map(|t| settings.orient(glam::Vec3::from_slice(&t)).extend(t[3]))
+                .collect()
+        })
+        .unwrap_or_default();
+    let uvs: Vec<[f32; 2]> = reader
+        .read_tex_coords(0)
+        .map(|v| v.into_f32().collect())
+        .unwrap_or_else(|| vec![[0.0; 2]; positions.len()]);
+    let uvs1: Vec<[f32; 2]> = reader
+        .read_tex_coords(1)
+        .map(|v| v.into_f32().collect())
+        .unwrap_or_else(|| uvs.clone());
+
+    let mut verts: Vec<Vertex> = positions
+        .iter()
+        .enumerate()
+        .map(|(i, &pos)| Vertex {
+            pos,
+            normal: normals.get(i).copied().unwrap_or([0.0; 3]),
+            uv: uvs.get(i).copied().unwrap_or([0.0; 2]),
+            uv1: uvs1.get(i).copied().unwrap_or([0.0; 2]),
+        })
+        .collect();
+
+    let mut indices: Vec<u32> = reader
+        .read_indices()
+        .map(|v| v.into_u32().collect())
+        .unwrap_or_else(|| (0..positions.len() as u32).collect());
+    if let Some(&i) = indices.iter().find(|&&i| i as usize >= positions.len()) {
+        return Err(format!(
+            "{}: index {i} is past its {} vertices",
+            mesh.name().unwrap_or("Unnamed"),
+            positions.len()
+        ));
+    }
+    let triangles = prim.mode() == gltf::mesh::Mode::Triangles;
+    if let Some(source) = settings.apply(&mut verts, &mut indices, has_normals, triangles) {
+        if !tangents.is_empty() {
+            tangents = source.iter().map(|&i| tangents[i as usize]).collect();
+        }
+    }
+
+    let (joint_names, mut joint_matrices, bones) = skin_joints(doc, buffs, mesh);
+    // joints keep unit scale so what's attached to them doesn't pick up
+    // the file's
+    let to_sandbox = settings.matrix();
+    for matrix in &mut joint_matrices {
+        *matrix = to_sandbox * *matrix * to_sandbox.inverse();
+    }
+
+    Ok(Primitive {
+        info: PrimitiveInfo {
+            name: mesh.name().unwrap_or("Unnamed").to_string(),
+            attributes: prim.attributes().map(|(s, _)| s.to_string()).collect(),
+            joint_names,
+            bones,
+            double_sided: material.double_sided(),
+            base_color: material.pbr_metallic_roughness().base_color_factor(),
+            alpha_cutoff: (material.alpha_mode() == gltf::material::AlphaMode::Mask)
+                .then(|| material.alpha_cutoff().unwrap_or(0.5)),
+        },
+        vertices: verts,
+        indices,
+        tangents,
+        joint_matrices,
+        base_color_texture: None,
+    })
+}
+
+/// Names and bind-pose transforms of the joints of the skin on the node
+/// that uses `mesh`, and the joint pairs that form bones.
+fn skin_joints(
+    doc: &gltf::Document,
+    buffs: &[gltf::buffer::Data],
+    mesh: &gltf::Mesh,
+) -> (Vec<String>, Vec<glam::Mat4>, Vec<[usize; 2]>) {
+    let Some(skin) = doc
+        .nodes()
+        .find(|n| n.mesh().is_some_and(|m| m.index() == mesh.index()))
+        .and_then(|n| n.skin())
+    else {
+        return (vec![], vec![], vec![]);
+    };
+    let reader = skin.reader(|b| Some(&buffs[b.index()]));
+    // a joint's bind transform is its inverse bind matrix's inverse
+    let matrices: Vec<glam::Mat4> = match reader.read_inverse_bind_matrices() {
+        Some(matrices) => matrices
+            .map(|m| glam::Mat4::from_cols_array_2d(&m).inverse())
+            .collect(),
+        None => return (vec![], vec![], vec![]),
+    };
+    let names = skin
+        .joints()
+        .enumerate()
+        .map(|(i, j)| j.name().map_or_else(|| format!("Joint {i}"), String::from))
+        .collect();
+    let nodes: Vec<usize> = skin.joints().map(|j| j.index()).collect();
+    let mut bones = vec![];
+    for (parent, joint) in skin.joints().enumerate() {
+        for child in joint.children() {
+            if let Some(child) = nodes.iter().position(|&n| n == child.index()) {
+                bones.push([parent, child]);
+            }
+        }
+    }
+    (names, matrices, bones)
+}
+
+fn create_buffer<T: bytemuck::Pod>(
+    device: &wgpu::Device,
+    label: &str,
+    contents: &[T],
+    usage: wgpu::BufferUsages,
+) -> wgpu::Buffer {
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some(label),
+        contents: bytemuck::cast_slice(contents),
+        usage,
+    })
+}
+
+/// Rounds the size up to a power of two so geometry that grows a little
+/// each frame doesn't reallocate every frame.
+fn create_grown_buffer(
+    device: &wgpu::Device,
+    label: &str,
+    contents: &[u8],
+    usage: wgpu::BufferUsages,
+) -> wgpu::Buffer {
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: (contents.len() as u64).next_power_of_two(),
+        usage,
+        mapped_at_creation: true,
+    });
+    buffer
+        .slice(..contents.len() as u64)
+        .get_mapped_range_mut()
+        .copy_from_slice(contents);
+    buffer.unmap();
+    buffer
 }
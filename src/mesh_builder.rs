@@ -0,0 +1,204 @@
+//! Immediate-mode geometry: queue triangles, quads and lines during a
+//! frame, `flush` them to the GPU once, draw, then `clear` for the next.
+
+/// A GPU buffer that is rewritten every frame and reallocated, to the next
+/// power of two, only when the data outgrows it.
+pub struct GrowableBuffer {
+    label: &'static str,
+    usage: wgpu::BufferUsages,
+    buffer: Option<wgpu::Buffer>,
+    len: u64,
+}
+
+impl GrowableBuffer {
+    /// `COPY_DST` is added to `usage`.
+    pub fn new(label: &'static str, usage: wgpu::BufferUsages) -> Self {
+        GrowableBuffer {
+            label,
+            usage: usage | wgpu::BufferUsages::COPY_DST,
+            buffer: None,
+            len: 0,
+        }
+    }
+
+    pub fn write<T: bytemuck::Pod>(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        data: &[T],
+    ) {
+        let bytes: &[u8] = bytemuck::cast_slice(data);
+        self.len = bytes.len() as u64;
+        if bytes.is_empty() {
+            return;
+        }
+        if self.buffer.as_ref().is_none_or(|b| b.size() < self.len) {
+            // copies must be a multiple of four bytes
+            let size = self
+                .len
+                .next_power_of_two()
+                .max(wgpu::COPY_BUFFER_ALIGNMENT);
+            self.buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(self.label),
+                size,
+                usage: self.usage,
+                mapped_at_creation: false,
+            }));
+        }
+        queue.write_buffer(self.buffer.as_ref().unwrap(), 0, bytes);
+    }
+
+    /// The part written by the last `write`; `None` if that was empty.
+    pub fn slice(&self) -> Option<wgpu::BufferSlice<'_>> {
+        (self.len > 0).then(|| self.buffer.as_ref().unwrap().slice(..self.len))
+    }
+
+    /// Allocated bytes, which may exceed what was last written.
+    pub fn capacity(&self) -> u64 {
+        self.buffer.as_ref().map_or(0, |b| b.size())
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BuilderVertex {
+    pub pos: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl BuilderVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32x3,
+        2 => Float32x2,
+        3 => Float32x4
+    ];
+
+    pub fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<BuilderVertex>() as u64,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+
+    /// Position and color only, for lines and flat-colored shapes.
+    pub fn colored(pos: glam::Vec3, color: [f32; 4]) -> Self {
+        BuilderVertex {
+            pos: pos.into(),
+            normal: [0.0; 3],
+            uv: [0.0; 2],
+            color,
+        }
+    }
+}
+
+/// Triangles and lines share one vertex buffer and get an index buffer
+/// each, so one builder feeds both a triangle-list and a line-list
+/// pipeline using `BuilderVertex::layout`.
+pub struct MeshBuilder {
+    vertices: Vec<BuilderVertex>,
+    triangles: Vec<u32>,
+    lines: Vec<u32>,
+    vertex_buffer: GrowableBuffer,
+    triangle_buffer: GrowableBuffer,
+    line_buffer: GrowableBuffer,
+    flushed_triangles: u32,
+    flushed_lines: u32,
+}
+
+impl Default for MeshBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MeshBuilder {
+    pub fn new() -> Self {
+        MeshBuilder {
+            vertices: vec![],
+            triangles: vec![],
+            lines: vec![],
+            vertex_buffer: GrowableBuffer::new("Mesh Builder Vertices", wgpu::BufferUsages::VERTEX),
+            triangle_buffer: GrowableBuffer::new(
+                "Mesh Builder Triangles",
+                wgpu::BufferUsages::INDEX,
+            ),
+            line_buffer: GrowableBuffer::new("Mesh Builder Lines", wgpu::BufferUsages::INDEX),
+            flushed_triangles: 0,
+            flushed_lines: 0,
+        }
+    }
+
+    /// Returns the index for use with `triangle_indices` and `line_indices`.
+    pub fn vertex(&mut self, vertex: BuilderVertex) -> u32 {
+        self.vertices.push(vertex);
+        self.vertices.len() as u32 - 1
+    }
+
+    pub fn triangle_indices(&mut self, a: u32, b: u32, c: u32) {
+        self.triangles.extend_from_slice(&[a, b, c]);
+    }
+
+    pub fn line_indices(&mut self, a: u32, b: u32) {
+        self.lines.extend_from_slice(&[a, b]);
+    }
+
+    pub fn triangle(&mut self, vertices: [BuilderVertex; 3]) {
+        let [a, b, c] = vertices.map(|v| self.vertex(v));
+        self.triangle_indices(a, b, c);
+    }
+
+    /// Corners in winding order; split along `a`-`c`.
+    pub fn quad(&mut self, vertices: [BuilderVertex; 4]) {
+        let [a, b, c, d] = vertices.map(|v| self.vertex(v));
+        self.triangle_indices(a, b, c);
+        self.triangle_indices(a, c, d);
+    }
+
+    pub fn line(&mut self, a: glam::Vec3, b: glam::Vec3, color: [f32; 4]) {
+        let a = self.vertex(BuilderVertex::colored(a, color));
+        let b = self.vertex(BuilderVertex::colored(b, color));
+        self.line_indices(a, b);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.triangles.is_empty() && self.lines.is_empty()
+    }
+
+    /// Uploads everything queued since the last `clear`.
+    pub fn flush(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.vertex_buffer.write(device, queue, &self.vertices);
+        self.triangle_buffer.write(device, queue, &self.triangles);
+        self.line_buffer.write(device, queue, &self.lines);
+        self.flushed_triangles = self.triangles.len() as u32;
+        self.flushed_lines = self.lines.len() as u32;
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.triangles.clear();
+        self.lines.clear();
+    }
+
+    /// Draws the flushed triangles with whatever pipeline is set.
+    pub fn draw_triangles(&self, pass: &mut wgpu::RenderPass) {
+        self.draw(pass, &self.triangle_buffer, self.flushed_triangles);
+    }
+
+    /// Draws the flushed lines with whatever pipeline is set.
+    pub fn draw_lines(&self, pass: &mut wgpu::RenderPass) {
+        self.draw(pass, &self.line_buffer, self.flushed_lines);
+    }
+
+    fn draw(&self, pass: &mut wgpu::RenderPass, indices: &GrowableBuffer, count: u32) {
+        let (Some(vertices), Some(indices)) = (self.vertex_buffer.slice(), indices.slice()) else {
+            return;
+        };
+        pass.set_vertex_buffer(0, vertices);
+        pass.set_index_buffer(indices, wgpu::IndexFormat::Uint32);
+        pass.draw_indexed(0..count, 0, 0..1);
+    }
+}
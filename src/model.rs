@@ -1,18 +1,242 @@
-use crate::material::Material;
+use crate::app::State;
+use crate::material::{MaterialInstance, PipelineHandle, PrimitiveOptions};
 use crate::mesh::Mesh;
+use crate::sampler::SamplerDesc;
+use crate::transform::Transform;
 use std::sync::Arc;
+use wgpu::util::DeviceExt;
 
+pub type EntityId = u32;
+
+/// A mesh drawn with a material at a transform; the sandbox's entity.
 pub struct Model {
+    pub id: EntityId,
+    pub name: String,
     pub mesh: Arc<Mesh>,
-    pub material: Arc<Material>,
+    material: Arc<MaterialInstance>,
+    /// Relative to the parent, if any.
+    pub transform: Transform,
+    /// `transform` under every ancestor's; see `World::update_transforms`.
+    global: glam::Mat4,
+    pub base_color: [f32; 4],
+    pub alpha_cutoff: Option<f32>,
+    /// Hides this model and every model parented under it.
+    pub visible: bool,
+    pub parent: Option<EntityId>,
+    /// `visible` combined with every ancestor's; see `World::update_visibility`.
+    inherited_visible: bool,
+    /// Each skin joint's transform in the mesh's space, in the order of
+    /// `Mesh::joint_names`. Starts as the bind pose; animation writes it.
+    pub pose: Vec<glam::Mat4>,
+    primitive: PrimitiveOptions,
+    pipeline: PipelineHandle,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ModelUniform {
+    model: [[f32; 4]; 4],
+    base_color: [f32; 4],
+    /// 0 disables the alpha test.
+    alpha_cutoff: f32,
+    _padding: [f32; 3],
+}
+
+impl ModelUniform {
+    fn new(matrix: glam::Mat4, base_color: [f32; 4], alpha_cutoff: Option<f32>) -> Self {
+        ModelUniform {
+            model: matrix.to_cols_array_2d(),
+            base_color,
+            alpha_cutoff: alpha_cutoff.unwrap_or(0.0),
+            _padding: [0.0; 3],
+        }
+    }
 }
 
 impl Model {
-    pub fn render(&self, renderpass: &mut wgpu::RenderPass) {
-        renderpass.set_pipeline(&self.material.pipeline);
-        renderpass.set_bind_group(0, &self.material.bind_groups[0], &[]);
+    pub fn new(
+        state: &State,
+        id: EntityId,
+        name: &str,
+        mesh: Arc<Mesh>,
+        material: Arc<MaterialInstance>,
+        transform: Transform,
+    ) -> Self {
+        let global = transform.matrix();
+        let uniform = ModelUniform::new(global, mesh.base_color, mesh.alpha_cutoff);
+        let buffer = state
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Model Uniform"),
+                contents: bytemuck::cast_slice(&[uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+        let base_color_view = mesh.base_color_view(state).unwrap_or(&state.white_texture);
+        let sampler = state.samplers.get(&state.device, SamplerDesc::new());
+        let bind_group = state.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &state.model_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(base_color_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+            label: None,
+        });
+
+        let (base_color, alpha_cutoff) = (mesh.base_color, mesh.alpha_cutoff);
+        let mut primitive = material.primitive();
+        primitive.vertex_encoding = mesh.encoding;
+        if !mesh.double_sided {
+            primitive.cull_mode = Some(wgpu::Face::Back);
+        }
+        let pipeline = material.pipeline_variant(&state.device, primitive);
+
+        Model {
+            id,
+            name: name.to_string(),
+            pose: mesh.joint_matrices.clone(),
+            mesh,
+            material,
+            transform,
+            global,
+            base_color,
+            alpha_cutoff,
+            visible: true,
+            parent: None,
+            inherited_visible: true,
+            primitive,
+            pipeline,
+            buffer,
+            bind_group,
+        }
+    }
+
+    /// The uniform, then the base color texture and its sampler, which
+    /// shaders without an alpha test can leave out.
+    pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        let mut entries = vec![wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }];
+        entries.extend(crate::texture::layout_entries(
+            wgpu::ShaderStages::FRAGMENT,
+            wgpu::TextureViewDimension::D2,
+            wgpu::TextureSampleType::Float { filterable: true },
+            false,
+            1,
+        ));
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Model Bind Group Layout"),
+            entries: &entries,
+        })
+    }
+
+    /// World-space matrix, as of the last `World::update_transforms`.
+    pub fn global_matrix(&self) -> glam::Mat4 {
+        self.global
+    }
+
+    pub(crate) fn set_global_matrix(&mut self, matrix: glam::Mat4) {
+        self.global = matrix;
+    }
+
+    /// Whether this model and all its ancestors are visible, as of the
+    /// last `World::update_visibility`.
+    pub fn is_visible(&self) -> bool {
+        self.inherited_visible
+    }
+
+    pub(crate) fn set_inherited_visible(&mut self, visible: bool) {
+        self.inherited_visible = visible;
+    }
+
+    /// The mesh for editing in place, e.g. with `Mesh::update_vertices`.
+    /// `None` while anything else holds it, such as `World::meshes` or
+    /// another model; spawn procedural meshes without sharing them.
+    pub fn mesh_mut(&mut self) -> Option<&mut Mesh> {
+        Arc::get_mut(&mut self.mesh)
+    }
+
+    pub fn material(&self) -> &Arc<MaterialInstance> {
+        &self.material
+    }
+
+    /// Swaps the material, keeping this model's primitive options.
+    pub fn set_material(&mut self, device: &wgpu::Device, material: Arc<MaterialInstance>) {
+        self.pipeline = material.pipeline_variant(device, self.primitive);
+        self.material = material;
+    }
+
+    pub fn primitive(&self) -> PrimitiveOptions {
+        self.primitive
+    }
+
+    /// Switches to the material's pipeline variant for `primitive`.
+    pub fn set_primitive(&mut self, device: &wgpu::Device, primitive: PrimitiveOptions) {
+        self.primitive = primitive;
+        self.pipeline = self.material.pipeline_variant(device, primitive);
+    }
+
+    /// False while the model's pipeline variant is still compiling.
+    pub fn is_pipeline_ready(&self) -> bool {
+        self.pipeline.is_ready()
+    }
+
+    /// Blocks until the model's own pipeline can be used, e.g. before a
+    /// capture that must not show the fallback.
+    pub fn wait_for_pipeline(&self) {
+        self.pipeline.wait();
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    pub fn queue_uniform(&self, queue: &wgpu::Queue) {
+        let uniform = ModelUniform::new(self.global, self.base_color, self.alpha_cutoff);
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    /// Draws with `fallback` until the model's own pipeline has compiled;
+    /// skips the draw if neither is ready or the fallback can't read the
+    /// mesh's vertex encoding. Returns whether anything was drawn.
+    pub fn render(&self, renderpass: &mut wgpu::RenderPass, fallback: &MaterialInstance) -> bool {
+        let fallback_pipeline = fallback
+            .pipeline()
+            .get()
+            .filter(|_| fallback.primitive().vertex_encoding == self.mesh.encoding);
+        let (material, pipeline) = match (self.pipeline.get(), fallback_pipeline) {
+            (Some(pipeline), _) => (&*self.material, pipeline),
+            (None, Some(pipeline)) => (fallback, pipeline),
+            (None, None) => return false,
+        };
+        renderpass.set_pipeline(pipeline);
+        let bind_groups = material.bind_groups();
+        for (i, bind_group) in bind_groups.iter().enumerate() {
+            renderpass.set_bind_group(i as u32, bind_group, &[]);
+        }
+        // the model group always follows the material's own groups
+        renderpass.set_bind_group(bind_groups.len() as u32, &self.bind_group, &[]);
         renderpass.set_vertex_buffer(0, self.mesh.vertex_buffer.slice(..));
         renderpass.set_index_buffer(self.mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
         renderpass.draw_indexed(0..self.mesh.index_count, 0, 0..1);
+        true
     }
 }
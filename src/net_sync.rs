@@ -0,0 +1,443 @@
+//! Shares the camera pose and selected entity between sandbox instances
+//! over TCP, so two machines can review a scene from the same viewpoint.
+//! One instance hosts and relays; the others join it. Hosting only accepts
+//! connections from this machine unless LAN peers are allowed. Messages are
+//! single text lines:
+//!
+//! ```text
+//! camera <eye xyz> <center xyz> <up xyz>
+//! select <id | none>
+//! ```
+
+use crate::app::State;
+use crate::model::EntityId;
+use crate::plugin::{Plugin, PluginContext};
+use crate::world::World;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+pub const DEFAULT_PORT: u16 = 7878;
+/// Camera updates are sent at most this often.
+const SEND_INTERVAL: f32 = 1.0 / 30.0;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// Unsent bytes a peer can fall behind by before it counts as stalled.
+const MAX_OUTGOING: usize = 64 * 1024;
+/// Messages are tiny, so a peer that sends more than this without a
+/// newline is dropped.
+const MAX_LINE: usize = 4 * 1024;
+/// Bytes read from one peer per update, so a flood can't hold up a frame.
+const MAX_READ: usize = 16 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Message {
+    Camera([f32; 9]),
+    Select(Option<EntityId>),
+}
+
+impl Message {
+    fn encode(&self) -> String {
+        match self {
+            Message::Camera(pose) => {
+                let values: Vec<String> = pose.iter().map(|v| v.to_string()).collect();
+                format!("camera {}\n", values.join(" "))
+            }
+            Message::Select(Some(id)) => format!("select {id}\n"),
+            Message::Select(None) => "select none\n".to_string(),
+        }
+    }
+
+    fn decode(line: &str) -> Option<Message> {
+        let mut words = line.split_whitespace();
+        match words.next()? {
+            "camera" => {
+                let values: Vec<f32> = words
+                    .map(|w| w.parse().ok().filter(|v: &f32| v.is_finite()))
+                    .collect::<Option<_>>()?;
+                Some(Message::Camera(values.try_into().ok()?))
+            }
+            "select" => match words.next()? {
+                "none" => Some(Message::Select(None)),
+                id => Some(Message::Select(Some(id.parse().ok()?))),
+            },
+            _ => None,
+        }
+    }
+}
+
+struct Peer {
+    stream: TcpStream,
+    address: String,
+    incoming: Vec<u8>,
+    /// Whole lines, minus whatever of the first the socket has taken.
+    outgoing: Vec<u8>,
+}
+
+impl Peer {
+    fn new(stream: TcpStream) -> std::io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        let address = stream
+            .peer_addr()
+            .map_or("?".to_string(), |a| a.to_string());
+        Ok(Peer {
+            stream,
+            address,
+            incoming: vec![],
+            outgoing: vec![],
+        })
+    }
+
+    /// Complete lines received so far; `None` once the peer is gone or has
+    /// sent a line too long to be a message.
+    fn receive(&mut self) -> Option<Vec<String>> {
+        let mut chunk = [0; 4096];
+        let mut read = 0;
+        while read < MAX_READ {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return None,
+                Ok(n) => {
+                    self.incoming.extend_from_slice(&chunk[..n]);
+                    read += n;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => return None,
+            }
+        }
+        let mut lines = vec![];
+        while let Some(end) = self.incoming.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.incoming.drain(..=end).collect();
+            lines.push(String::from_utf8_lossy(&line).into_owned());
+        }
+        if self.incoming.len() > MAX_LINE {
+            log::warn!("Camera sync peer {} sent an overlong line", self.address);
+            return None;
+        }
+        Some(lines)
+    }
+
+    /// Queues a line for [`Peer::flush`]; `false` if the peer has fallen so
+    /// far behind that it must have stalled.
+    fn send(&mut self, text: &str) -> bool {
+        self.outgoing.extend_from_slice(text.as_bytes());
+        self.outgoing.len() <= MAX_OUTGOING
+    }
+
+    /// Writes as much of the queue as the socket takes without blocking;
+    /// `false` once the peer is gone.
+    fn flush(&mut self) -> bool {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return false,
+                Ok(n) => {
+                    self.outgoing.drain(..n);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => return false,
+            }
+        }
+        true
+    }
+}
+
+pub struct NetSync {
+    /// Apply poses and selections from peers.
+    pub follow: bool,
+    /// Send ours to peers.
+    pub broadcast: bool,
+    /// Host for other machines too, not just this one.
+    pub lan: bool,
+    listener: Option<TcpListener>,
+    peers: Vec<Peer>,
+    last_camera: Option<[f32; 9]>,
+    last_selected: Option<EntityId>,
+    since_send: f32,
+    port: u16,
+    address: String,
+    status: String,
+}
+
+impl Default for NetSync {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NetSync {
+    pub fn new() -> Self {
+        NetSync {
+            follow: true,
+            broadcast: true,
+            lan: false,
+            listener: None,
+            peers: vec![],
+            last_camera: None,
+            last_selected: None,
+            since_send: 0.0,
+            port: DEFAULT_PORT,
+            address: format!("127.0.0.1:{DEFAULT_PORT}"),
+            status: "Offline".to_string(),
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.listener.is_some() || !self.peers.is_empty()
+    }
+
+    pub fn host(&mut self, port: u16) {
+        self.disconnect();
+        let interface = if self.lan { "0.0.0.0" } else { "127.0.0.1" };
+        match TcpListener::bind((interface, port)).and_then(|l| {
+            l.set_nonblocking(true)?;
+            Ok(l)
+        }) {
+            Ok(listener) => {
+                log::info!("Camera sync hosting on port {port}");
+                self.status = format!("Hosting on port {port}");
+                self.listener = Some(listener);
+            }
+            Err(e) => {
+                log::warn!("Camera sync failed to listen on port {port}: {e}");
+                self.status = format!("Failed to host: {e}");
+            }
+        }
+    }
+
+    pub fn join(&mut self, address: &str) {
+        self.disconnect();
+        match connect(address).and_then(Peer::new) {
+            Ok(peer) => {
+                log::info!("Camera sync joined {address}");
+                self.status = format!("Joined {address}");
+                self.peers.push(peer);
+                // the host's view wins until we move
+                self.last_camera = None;
+            }
+            Err(e) => {
+                log::warn!("Camera sync failed to join {address}: {e}");
+                self.status = format!("Failed to join: {e}");
+            }
+        }
+    }
+
+    pub fn disconnect(&mut self) {
+        self.listener = None;
+        self.peers.clear();
+        self.status = "Offline".to_string();
+    }
+
+    fn send_all(&mut self, message: Message, except: Option<&str>) {
+        let text = message.encode();
+        self.peers.retain_mut(|peer| {
+            let keep = except == Some(peer.address.as_str()) || peer.send(&text);
+            if !keep {
+                log::info!("Camera sync peer {} dropped", peer.address);
+            }
+            keep
+        });
+    }
+
+    fn apply(&mut self, message: Message, world: &mut World) {
+        match message {
+            Message::Camera(pose) => {
+                let camera = &mut world.camera;
+                camera.eye = glam::Vec3::from_slice(&pose[0..3]);
+                camera.center = glam::Vec3::from_slice(&pose[3..6]);
+                camera.up = glam::Vec3::from_slice(&pose[6..9]);
+                camera.update_uniform();
+                world.camera_controller.sync_from_camera(&world.camera);
+                self.last_camera = Some(pose);
+            }
+            Message::Select(id) => {
+                world
+                    .selection
+                    .set(id.filter(|id| world.model(*id).is_some()));
+                self.last_selected = world.selection.primary();
+            }
+        }
+    }
+}
+
+/// Gives up after a few seconds instead of freezing the frame for the
+/// OS's full connect timeout.
+fn connect(address: &str) -> std::io::Result<TcpStream> {
+    let mut last_error = std::io::Error::new(ErrorKind::InvalidInput, "no address");
+    for address in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, CONNECT_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+fn camera_pose(world: &World) -> [f32; 9] {
+    let c = &world.camera;
+    let mut pose = [0.0; 9];
+    pose[0..3].copy_from_slice(&c.eye.to_array());
+    pose[3..6].copy_from_slice(&c.center.to_array());
+    pose[6..9].copy_from_slice(&c.up.to_array());
+    pose
+}
+
+impl Plugin for NetSync {
+    fn name(&self) -> &str {
+        "Network Sync"
+    }
+
+    fn update(&mut self, ctx: &mut PluginContext) {
+        if let Some(listener) = &self.listener {
+            while let Ok((stream, _)) = listener.accept() {
+                match Peer::new(stream) {
+                    Ok(mut peer) => {
+                        log::info!("Camera sync peer {} joined", peer.address);
+                        // bring the newcomer up to date
+                        let pose = Message::Camera(camera_pose(ctx.world));
+                        let selected = Message::Select(ctx.world.selection.primary());
+                        if peer.send(&pose.encode()) && peer.send(&selected.encode()) {
+                            self.peers.push(peer);
+                        }
+                    }
+                    Err(e) => log::warn!("Camera sync failed to accept a peer: {e}"),
+                }
+            }
+        }
+
+        let mut received = vec![];
+        self.peers.retain_mut(|peer| {
+            let Some(lines) = peer.receive() else {
+                log::info!("Camera sync peer {} left", peer.address);
+                return false;
+            };
+            for line in lines {
+                match Message::decode(&line) {
+                    Some(message) => received.push((peer.address.clone(), message)),
+                    None => log::warn!("Camera sync ignored {:?}", line.trim_end()),
+                }
+            }
+            true
+        });
+        for (from, message) in received {
+            if self.follow {
+                self.apply(message, ctx.world);
+            }
+            // the host relays so every peer sees every change
+            if self.listener.is_some() {
+                self.send_all(message, Some(&from));
+            }
+        }
+
+        self.since_send += ctx.time.real_delta_seconds;
+        if self.broadcast && !self.peers.is_empty() {
+            if self.last_selected != ctx.world.selection.primary() {
+                self.last_selected = ctx.world.selection.primary();
+                self.send_all(Message::Select(ctx.world.selection.primary()), None);
+            }
+            let pose = camera_pose(ctx.world);
+            if self.last_camera != Some(pose) && self.since_send >= SEND_INTERVAL {
+                self.last_camera = Some(pose);
+                self.since_send = 0.0;
+                self.send_all(Message::Camera(pose), None);
+            }
+        }
+
+        self.peers.retain_mut(|peer| {
+            let keep = peer.flush();
+            if !keep {
+                log::info!("Camera sync peer {} dropped", peer.address);
+            }
+            keep
+        });
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, _state: &State, _world: &mut World) {
+        egui::Window::new("Network Sync")
+            .default_open(false)
+            .show(ctx, |ui| {
+                ui.label(&self.status);
+                if self.is_connected() {
+                    ui.label(format!("{} peer(s)", self.peers.len()));
+                    for peer in &self.peers {
+                        ui.small(&peer.address);
+                    }
+                    ui.checkbox(&mut self.follow, "Follow peers");
+                    ui.checkbox(&mut self.broadcast, "Share my view");
+                    if ui.button("Disconnect").clicked() {
+                        self.disconnect();
+                    }
+                    return;
+                }
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut self.port).prefix("port "));
+                    if ui.button("Host").clicked() {
+                        self.host(self.port);
+                    }
+                });
+                ui.checkbox(&mut self.lan, "Allow LAN peers")
+                    .on_hover_text("Anyone on the network can then join and move your camera");
+                ui.horizontal(|ui| {
+                    ui.add(egui::TextEdit::singleline(&mut self.address).desired_width(140.0));
+                    if ui.button("Join").clicked() {
+                        let address = self.address.clone();
+                        self.join(&address);
+                    }
+                });
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_round_trip() {
+        let pose = [1.0, -2.5, 3.0, 0.0, 0.0, 0.0, 0.0, 1.0, 1e-3];
+        for message in [
+            Message::Camera(pose),
+            Message::Select(Some(7)),
+            Message::Select(None),
+        ] {
+            let line = message.encode();
+            assert!(line.ends_with('\n'));
+            assert_eq!(Message::decode(&line), Some(message));
+        }
+    }
+
+    #[test]
+    fn garbage_lines_are_ignored() {
+        for line in [
+            "",
+            "\n",
+            "camera",
+            "camera 1 2 3",
+            "camera 1 2 3 4 5 6 7 8 9 10",
+            "camera 1 2 3 4 5 6 7 8 x",
+            "camera 1 2 3 4 5 6 7 8 NaN",
+            "camera inf 2 3 4 5 6 7 8 9",
+            "select",
+            "select someone",
+            "teleport 1 2 3",
+        ] {
+            assert_eq!(Message::decode(line), None, "{line:?}");
+        }
+    }
+
+    #[test]
+    fn overlong_lines_drop_the_peer() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut sender = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let mut peer = Peer::new(listener.accept().unwrap().0).unwrap();
+        sender.write_all(&[b'x'; MAX_LINE + 1]).unwrap();
+        for _ in 0..100 {
+            match peer.receive() {
+                Some(lines) => assert!(lines.is_empty()),
+                None => return,
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("peer was kept");
+    }
+}
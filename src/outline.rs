@@ -0,0 +1,448 @@
+//! Screen-space outlines: a prepass writing normals and view depth for every
+//! model, followed by a Sobel filter over both. With "edges only" it doubles as a
+//! way to spot normal seams in imported meshes.
+
+use crate::app::State;
+use crate::mesh::{VertexEncoding, OCTAHEDRAL_WGSL};
+use crate::plugin::{Plugin, PluginContext};
+use crate::transient::TransientDesc;
+use crate::world::World;
+
+const PREPASS_SHADER: &str = r#"
+struct Camera { view_proj: mat4x4<f32> };
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+struct Model {
+    model: mat4x4<f32>,
+    base_color: vec4<f32>,
+    alpha_cutoff: f32,
+};
+@group(1) @binding(0) var<uniform> model: Model;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) depth: f32,
+};
+
+fn vertex(pos: vec3<f32>, normal: vec3<f32>) -> VSOut {
+    var out: VSOut;
+    out.pos = camera.view_proj * model.model * vec4(pos, 1.0);
+    out.normal = (model.model * vec4(normal, 0.0)).xyz;
+    // clip w is the view-space distance for a perspective projection
+    out.depth = out.pos.w;
+    return out;
+}
+
+@vertex
+fn vsMain(@location(0) pos: vec3<f32>, @location(1) normal: vec3<f32>) -> VSOut {
+    return vertex(pos, normal);
+}
+
+@vertex
+fn vsMainPacked(@location(0) pos: vec3<f32>, @location(1) normal: vec2<f32>) -> VSOut {
+    return vertex(pos, octDecode(normal));
+}
+
+@fragment
+fn psMain(in: VSOut) -> @location(0) vec4<f32> {
+    if (model.alpha_cutoff > 0.0 && model.base_color.a < model.alpha_cutoff) {
+        discard;
+    }
+    return vec4(normalize(in.normal), in.depth);
+}
+"#;
+
+const EDGE_SHADER: &str = r#"
+struct Params {
+    thickness: f32,
+    depth_threshold: f32,
+    normal_threshold: f32,
+    edges_only: f32,
+    color: vec4<f32>,
+};
+@group(0) @binding(0) var<uniform> params: Params;
+// xyz: world normal, w: view depth
+@group(0) @binding(1) var prepass: texture_2d<f32>;
+
+@vertex
+fn vsMain(@builtin(vertex_index) i: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2(f32((i << 1u) & 2u), f32(i & 2u));
+    return vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+fn load(p: vec2<i32>) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(prepass));
+    return textureLoad(prepass, clamp(p, vec2(0), size - 1), 0);
+}
+
+@fragment
+fn psMain(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+    var kx = array<f32, 9>(-1.0, 0.0, 1.0, -2.0, 0.0, 2.0, -1.0, 0.0, 1.0);
+    var ky = array<f32, 9>(-1.0, -2.0, -1.0, 0.0, 0.0, 0.0, 1.0, 2.0, 1.0);
+    let center = vec2<i32>(pos.xy);
+    let spacing = i32(max(params.thickness, 1.0));
+
+    var depth_gradient = vec2(0.0);
+    var normal_x = vec3(0.0);
+    var normal_y = vec3(0.0);
+    for (var i = 0; i < 9; i++) {
+        let p = center + vec2(i % 3 - 1, i / 3 - 1) * spacing;
+        let sample = load(p);
+        depth_gradient += vec2(kx[i], ky[i]) * sample.w;
+        normal_x += kx[i] * sample.xyz;
+        normal_y += ky[i] * sample.xyz;
+    }
+
+    // relative to the center depth so distant surfaces don't all outline
+    let depth_edge = length(depth_gradient) / load(center).w;
+    let normal_edge = sqrt(dot(normal_x, normal_x) + dot(normal_y, normal_y));
+    let edge = max(
+        step(params.depth_threshold, depth_edge),
+        step(params.normal_threshold, normal_edge),
+    );
+
+    if (params.edges_only > 0.0) {
+        return vec4(mix(vec3(1.0), params.color.rgb, edge), 1.0);
+    }
+    return vec4(params.color.rgb, params.color.a * edge);
+}
+"#;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct OutlineParams {
+    thickness: f32,
+    depth_threshold: f32,
+    normal_threshold: f32,
+    edges_only: f32,
+    color: [f32; 4],
+}
+
+const PREPASS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+struct Pipelines {
+    prepass: wgpu::RenderPipeline,
+    prepass_packed: wgpu::RenderPipeline,
+    edge: wgpu::RenderPipeline,
+    edge_layout: wgpu::BindGroupLayout,
+    camera_bind_group: wgpu::BindGroup,
+    params: wgpu::Buffer,
+}
+
+pub struct Outline {
+    pub enabled: bool,
+    /// Sobel sample spacing in pixels.
+    pub thickness: f32,
+    /// Depth gradient relative to the pixel's depth.
+    pub depth_threshold: f32,
+    pub normal_threshold: f32,
+    pub color: [f32; 4],
+    /// Shows only the detected edges on white instead of the scene.
+    pub edges_only: bool,
+    pipelines: Option<Pipelines>,
+}
+
+impl Default for Outline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Outline {
+    pub fn new() -> Self {
+        Outline {
+            enabled: false,
+            thickness: 1.0,
+            depth_threshold: 0.1,
+            normal_threshold: 0.8,
+            color: [0.0, 0.0, 0.0, 1.0],
+            edges_only: false,
+            pipelines: None,
+        }
+    }
+}
+
+impl Pipelines {
+    fn new(state: &State, camera_buffer: &wgpu::Buffer) -> Self {
+        let device = &state.device;
+        let uniform_entry = |binding, visibility| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let camera_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Outline Camera"),
+            entries: &[uniform_entry(0, wgpu::ShaderStages::VERTEX)],
+        });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Outline Camera"),
+            layout: &camera_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+        });
+        let prepass_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Outline Prepass"),
+            source: wgpu::ShaderSource::Wgsl(format!("{PREPASS_SHADER}{OCTAHEDRAL_WGSL}").into()),
+        });
+        let prepass_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Outline Prepass"),
+            bind_group_layouts: &[&camera_layout, &state.model_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let prepass_pipeline = |encoding: VertexEncoding| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Outline Prepass"),
+                layout: Some(&prepass_layout),
+                vertex: wgpu::VertexState {
+                    module: &prepass_module,
+                    entry_point: Some(encoding.entry_point()),
+                    buffers: &[encoding.layout()],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &prepass_module,
+                    entry_point: Some("psMain"),
+                    compilation_options: Default::default(),
+                    targets: &[Some(PREPASS_FORMAT.into())],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+        let prepass = prepass_pipeline(VertexEncoding::Full);
+        let prepass_packed = prepass_pipeline(VertexEncoding::Packed);
+
+        let edge_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Outline Edges"),
+            entries: &[
+                uniform_entry(0, wgpu::ShaderStages::FRAGMENT),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let edge_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Outline Edges"),
+            source: wgpu::ShaderSource::Wgsl(EDGE_SHADER.into()),
+        });
+        let edge_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Outline Edges"),
+            bind_group_layouts: &[&edge_layout],
+            push_constant_ranges: &[],
+        });
+        let edge = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Outline Edges"),
+            layout: Some(&edge_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &edge_module,
+                entry_point: Some("vsMain"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &edge_module,
+                entry_point: Some("psMain"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: state.surface_config.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let params = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Outline Params"),
+            size: std::mem::size_of::<OutlineParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Pipelines {
+            prepass,
+            prepass_packed,
+            edge,
+            edge_layout,
+            camera_bind_group,
+            params,
+        }
+    }
+}
+
+impl Plugin for Outline {
+    fn name(&self) -> &str {
+        "Outline"
+    }
+
+    fn build(&mut self, ctx: &mut PluginContext) {
+        self.pipelines = Some(Pipelines::new(ctx.state, ctx.world.camera.buffer_ref()));
+    }
+
+    fn encode(
+        &mut self,
+        state: &State,
+        world: &World,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+    ) {
+        let Some(pipelines) = &self.pipelines else {
+            return;
+        };
+        if !self.enabled {
+            return;
+        }
+        let desc = TransientDesc {
+            width: state.surface_config.width,
+            height: state.surface_config.height,
+            // xyz: world normal, w: view depth
+            format: PREPASS_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        };
+        let prepass = state.transient.acquire(&state.device, desc);
+        let depth = state.transient.acquire(
+            &state.device,
+            TransientDesc {
+                format: wgpu::TextureFormat::Depth32Float,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                ..desc
+            },
+        );
+        let bind_group = state.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Outline Edges"),
+            layout: &pipelines.edge_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: pipelines.params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&prepass.view),
+                },
+            ],
+        });
+
+        let params = OutlineParams {
+            thickness: self.thickness,
+            depth_threshold: self.depth_threshold,
+            normal_threshold: self.normal_threshold,
+            edges_only: if self.edges_only { 1.0 } else { 0.0 },
+            color: self.color,
+        };
+        state
+            .queue
+            .write_buffer(&pipelines.params, 0, bytemuck::cast_slice(&[params]));
+
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("outline prepass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &prepass.view,
+                    depth_slice: None,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        // background sits at the far plane
+                        load: wgpu::LoadOp::Clear(wgpu::Color {
+                            a: world.camera.z_far as f64,
+                            ..wgpu::Color::TRANSPARENT
+                        }),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_bind_group(0, &pipelines.camera_bind_group, &[]);
+            for model in world.models.iter().filter(|m| m.is_visible()) {
+                pass.set_pipeline(match model.mesh.encoding {
+                    VertexEncoding::Full => &pipelines.prepass,
+                    VertexEncoding::Packed => &pipelines.prepass_packed,
+                });
+                pass.set_bind_group(1, model.bind_group(), &[]);
+                pass.set_vertex_buffer(0, model.mesh.vertex_buffer.slice(..));
+                pass.set_index_buffer(model.mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                pass.draw_indexed(0..model.mesh.index_count, 0, 0..1);
+            }
+        }
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("outline edges"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                depth_slice: None,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_pipeline(&pipelines.edge);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, _state: &State, _world: &mut World) {
+        egui::Window::new("Outline")
+            .default_open(false)
+            .show(ctx, |ui| {
+                ui.checkbox(&mut self.enabled, "Enabled");
+                ui.checkbox(&mut self.edges_only, "Edges only");
+                ui.add(egui::Slider::new(&mut self.thickness, 1.0..=4.0).text("Thickness"));
+                ui.add(
+                    egui::Slider::new(&mut self.depth_threshold, 0.01..=1.0)
+                        .logarithmic(true)
+                        .text("Depth threshold"),
+                );
+                ui.add(
+                    egui::Slider::new(&mut self.normal_threshold, 0.05..=4.0)
+                        .text("Normal threshold"),
+                );
+                ui.horizontal(|ui| {
+                    ui.label("Color");
+                    ui.color_edit_button_rgba_unmultiplied(&mut self.color);
+                });
+            });
+    }
+}
@@ -0,0 +1,125 @@
+//! A textured material with an optional height texture slot. With a height
+//! texture its shader is built with `PARALLAX`, which parallax occlusion
+//! maps the albedo; without one the same source compiles to a plain
+//! textured material.
+
+use crate::app::State;
+use crate::material::{Binding, MaterialInstance};
+use crate::mesh::OCTAHEDRAL_WGSL;
+use crate::shader::{self, Shader};
+use crate::texture::Texture;
+use crate::uniform::{UniformLayout, UniformType};
+use std::sync::Arc;
+use wgpu::util::DeviceExt;
+
+pub const PARALLAX_WGSL: &str = include_str!("../shaders/parallax.wgsl");
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParallaxSettings {
+    /// Depth of the deepest point of the height map, in UV units.
+    pub scale: f32,
+    /// Ray-march layers when looking straight at the surface.
+    pub min_steps: u32,
+    /// Ray-march layers at grazing angles, and the loop's upper bound.
+    pub max_steps: u32,
+}
+
+impl Default for ParallaxSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ParallaxSettings {
+    pub fn new() -> Self {
+        ParallaxSettings {
+            scale: 0.05,
+            min_steps: 8,
+            max_steps: 32,
+        }
+    }
+}
+
+pub struct ParallaxMaterial {
+    pub material: Arc<MaterialInstance>,
+    /// Whether the height slot is filled and the `PARALLAX` permutation
+    /// built.
+    pub parallax: bool,
+    pub settings: ParallaxSettings,
+    params: Arc<wgpu::Buffer>,
+    layout: UniformLayout,
+}
+
+impl ParallaxMaterial {
+    pub fn new(
+        state: &State,
+        camera: &Arc<wgpu::Buffer>,
+        albedo: Arc<Texture>,
+        height: Option<Arc<Texture>>,
+    ) -> Self {
+        let parallax = height.is_some();
+        let defines: &[&str] = if parallax { &["PARALLAX"] } else { &[] };
+        let source = shader::preprocess(&format!("{PARALLAX_WGSL}{OCTAHEDRAL_WGSL}"), defines);
+
+        let layout = UniformLayout::new()
+            .field("eye", UniformType::Vec3)
+            .field("scale", UniformType::F32)
+            .field("light_direction", UniformType::Vec3)
+            .field("min_steps", UniformType::F32)
+            .field("max_steps", UniformType::F32);
+        layout.assert_wgsl(&source, "Parallax");
+        let params = Arc::new(
+            state
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Parallax Params"),
+                    contents: &layout.zeroed(),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                }),
+        );
+
+        // the slot stays filled without a height map so both permutations
+        // share a layout
+        let height = height.unwrap_or_else(|| {
+            Arc::new(Texture::from_rgba8(
+                state,
+                "Flat Height",
+                1,
+                1,
+                &[255; 4],
+                false,
+            ))
+        });
+        let bindings = vec![
+            Binding::uniform(camera.clone(), wgpu::ShaderStages::VERTEX),
+            Binding::uniform(params.clone(), wgpu::ShaderStages::FRAGMENT),
+            Binding::textures(vec![albedo, height], wgpu::ShaderStages::FRAGMENT),
+        ];
+
+        ParallaxMaterial {
+            material: MaterialInstance::new_arc(state, bindings, &Shader::Wgsl(source)),
+            parallax,
+            settings: ParallaxSettings::new(),
+            params,
+            layout,
+        }
+    }
+
+    /// Writes `settings` and the view and light for this frame.
+    pub fn update(&self, queue: &wgpu::Queue, eye: glam::Vec3, light_direction: glam::Vec3) {
+        let settings = &self.settings;
+        let mut data = self.layout.zeroed();
+        self.layout.write(&mut data, "eye", eye.to_array());
+        self.layout.write(&mut data, "scale", settings.scale);
+        self.layout
+            .write(&mut data, "light_direction", light_direction.to_array());
+        self.layout
+            .write(&mut data, "min_steps", settings.min_steps.max(1) as f32);
+        self.layout.write(
+            &mut data,
+            "max_steps",
+            settings.max_steps.max(settings.min_steps).max(1) as f32,
+        );
+        queue.write_buffer(&self.params, 0, &data);
+    }
+}
@@ -0,0 +1,128 @@
+use crate::camera::Camera;
+use crate::model::EntityId;
+use crate::world::World;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Ray {
+    pub origin: glam::Vec3,
+    /// Normalized.
+    pub direction: glam::Vec3,
+}
+
+impl Ray {
+    pub fn at(&self, distance: f32) -> glam::Vec3 {
+        self.origin + self.direction * distance
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Hit {
+    pub entity: EntityId,
+    /// World-space point on the surface.
+    pub point: glam::Vec3,
+    pub distance: f32,
+    /// World-space corners of the triangle hit.
+    pub triangle: [glam::Vec3; 3],
+}
+
+/// Ray through `cursor` (logical pixels from the top-left) for a viewport of
+/// `size` logical pixels.
+pub fn cursor_ray(camera: &Camera, cursor: glam::Vec2, size: glam::Vec2) -> Ray {
+    let ndc = glam::vec2(cursor.x / size.x * 2.0 - 1.0, 1.0 - cursor.y / size.y * 2.0);
+    let inverse = camera.view_proj().inverse();
+    let near = inverse.project_point3(ndc.extend(-1.0));
+    let far = inverse.project_point3(ndc.extend(1.0));
+    Ray {
+        origin: near,
+        direction: (far - near).normalize(),
+    }
+}
+
+/// Where `point` appears, in logical pixels from the top-left; `None` if
+/// it's behind the camera. The inverse of `cursor_ray`.
+pub fn world_to_cursor(camera: &Camera, point: glam::Vec3, size: glam::Vec2) -> Option<glam::Vec2> {
+    let clip = camera.view_proj() * point.extend(1.0);
+    if clip.w <= 0.0 {
+        return None;
+    }
+    let ndc = clip.truncate().truncate() / clip.w;
+    Some(glam::vec2(ndc.x + 1.0, 1.0 - ndc.y) * 0.5 * size)
+}
+
+/// Maps the `extent` pixels at `min` of a `size` frame onto the whole of
+/// clip space, for rendering just that region. Applied after the camera's
+/// projection.
+pub fn region_projection(min: glam::Vec2, extent: glam::Vec2, size: glam::Vec2) -> glam::Mat4 {
+    let center = (min + extent * 0.5) / size;
+    let ndc = glam::vec2(center.x * 2.0 - 1.0, 1.0 - center.y * 2.0);
+    let scale = size / extent;
+    glam::Mat4::from_cols(
+        glam::vec4(scale.x, 0.0, 0.0, 0.0),
+        glam::vec4(0.0, scale.y, 0.0, 0.0),
+        glam::Vec4::Z,
+        glam::vec4(-scale.x * ndc.x, -scale.y * ndc.y, 0.0, 1.0),
+    )
+}
+
+/// Closest model triangle hit by `ray`, tested on the CPU against the
+/// positions of each mesh whose bounds it passes through. Back faces count,
+/// so the inside of a mesh can be picked; hidden models can't.
+pub fn raycast(world: &World, ray: &Ray) -> Option<Hit> {
+    raycast_filtered(world, ray, |_| true)
+}
+
+/// `raycast` against only the models `keep` accepts.
+pub fn raycast_filtered(world: &World, ray: &Ray, keep: impl Fn(EntityId) -> bool) -> Option<Hit> {
+    let mut closest: Option<Hit> = None;
+    for (id, entry) in world.spatial().query_ray(ray) {
+        // candidates come nearest first, so nothing further can be closer
+        if closest.is_some_and(|hit| hit.distance < entry) {
+            break;
+        }
+        let Some(model) = world.model(id).filter(|m| m.is_visible() && keep(m.id)) else {
+            continue;
+        };
+        let matrix = model.global_matrix();
+        let mesh = &model.mesh;
+        for triangle in mesh.indices.chunks_exact(3) {
+            let [a, b, c] =
+                [0, 1, 2].map(|i| matrix.transform_point3(mesh.positions[triangle[i] as usize]));
+            let Some(distance) = intersect_triangle(ray, a, b, c) else {
+                continue;
+            };
+            if closest.is_none_or(|hit| distance < hit.distance) {
+                closest = Some(Hit {
+                    entity: model.id,
+                    point: ray.at(distance),
+                    distance,
+                    triangle: [a, b, c],
+                });
+            }
+        }
+    }
+    closest
+}
+
+/// Möller–Trumbore; returns the distance along the ray.
+fn intersect_triangle(ray: &Ray, a: glam::Vec3, b: glam::Vec3, c: glam::Vec3) -> Option<f32> {
+    let ab = b - a;
+    let ac = c - a;
+    let p = ray.direction.cross(ac);
+    let det = ab.dot(p);
+    if det.abs() < f32::EPSILON {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let t = ray.origin - a;
+    let u = t.dot(p) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = t.cross(ab);
+    let v = ray.direction.dot(q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let distance = ac.dot(q) * inv_det;
+    (distance > 0.0).then_some(distance)
+}
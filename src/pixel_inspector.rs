@@ -0,0 +1,605 @@
+//! Shows what's under the cursor: the presented color, and the view depth,
+//! world normal and model of the surface there. The surface values come
+//! from a one-pixel prepass zoomed onto the cursor, and everything is read
+//! back a frame or two late so inspecting never stalls the GPU.
+
+use crate::app::State;
+use crate::mesh::{VertexEncoding, OCTAHEDRAL_WGSL};
+use crate::model::EntityId;
+use crate::picking;
+use crate::plugin::{Plugin, PluginContext};
+use crate::world::World;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+const SHADER: &str = r#"
+struct Camera { view_proj: mat4x4<f32> };
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+struct Model {
+    model: mat4x4<f32>,
+    base_color: vec4<f32>,
+    alpha_cutoff: f32,
+};
+@group(1) @binding(0) var<uniform> model: Model;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) depth: f32,
+    @location(2) @interpolate(flat) id: u32,
+};
+
+struct PSOut {
+    @location(0) normal: vec4<f32>,
+    @location(1) depth: u32,
+    @location(2) id: u32,
+};
+
+fn vertex(pos: vec3<f32>, normal: vec3<f32>, id: u32) -> VSOut {
+    var out: VSOut;
+    out.pos = camera.view_proj * model.model * vec4(pos, 1.0);
+    out.normal = (model.model * vec4(normal, 0.0)).xyz;
+    out.depth = out.pos.w;
+    out.id = id;
+    return out;
+}
+
+// models are drawn with their entity id as the instance
+@vertex
+fn vsMain(
+    @location(0) pos: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @builtin(instance_index) id: u32,
+) -> VSOut {
+    return vertex(pos, normal, id);
+}
+
+@vertex
+fn vsMainPacked(
+    @location(0) pos: vec3<f32>,
+    @location(1) normal: vec2<f32>,
+    @builtin(instance_index) id: u32,
+) -> VSOut {
+    return vertex(pos, octDecode(normal), id);
+}
+
+@fragment
+fn psMain(in: VSOut) -> PSOut {
+    if (model.alpha_cutoff > 0.0 && model.base_color.a < model.alpha_cutoff) {
+        discard;
+    }
+    // 0 is left for the background
+    return PSOut(vec4(normalize(in.normal), 0.0), bitcast<u32>(in.depth), in.id + 1u);
+}
+"#;
+
+// downlevel targets can't render to 32-bit float formats, so the depth's
+// bits go out as a u32
+const NORMAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+const ID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+/// Where each value lands in the readback buffer.
+const NORMAL_OFFSET: u64 = 0;
+const DEPTH_OFFSET: u64 = 256;
+const ID_OFFSET: u64 = 512;
+const COLOR_OFFSET: u64 = 768;
+
+/// What was under the cursor a frame or two ago.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PixelSample {
+    /// Physical pixels from the top-left.
+    pub pixel: [u32; 2],
+    /// As presented, before the UI. `None` if the surface can't be copied.
+    pub color: Option<[u8; 4]>,
+    /// The rest are `None` over the background.
+    pub entity: Option<EntityId>,
+    /// Along the view axis.
+    pub depth: Option<f32>,
+    pub position: Option<glam::Vec3>,
+    pub normal: Option<glam::Vec3>,
+}
+
+struct Targets {
+    normal: wgpu::Texture,
+    view_depth: wgpu::Texture,
+    id: wgpu::Texture,
+    depth: wgpu::Texture,
+}
+
+struct Pipelines {
+    full: wgpu::RenderPipeline,
+    packed: wgpu::RenderPipeline,
+    camera: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+    targets: Targets,
+    readback: wgpu::Buffer,
+}
+
+impl Pipelines {
+    fn new(state: &State) -> Self {
+        let device = &state.device;
+        let camera = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pixel Inspector Camera"),
+            size: std::mem::size_of::<[[f32; 4]; 4]>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let camera_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Pixel Inspector Camera"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Pixel Inspector Camera"),
+            layout: &camera_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera.as_entire_binding(),
+            }],
+        });
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Pixel Inspector"),
+            source: wgpu::ShaderSource::Wgsl(format!("{SHADER}{OCTAHEDRAL_WGSL}").into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Pixel Inspector"),
+            bind_group_layouts: &[&camera_layout, &state.model_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |encoding: VertexEncoding| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Pixel Inspector"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &module,
+                    entry_point: Some(encoding.entry_point()),
+                    buffers: &[encoding.layout()],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &module,
+                    entry_point: Some("psMain"),
+                    compilation_options: Default::default(),
+                    targets: &[
+                        Some(NORMAL_FORMAT.into()),
+                        Some(DEPTH_FORMAT.into()),
+                        Some(ID_FORMAT.into()),
+                    ],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+
+        let target = |label, format, usage| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage,
+                view_formats: &[],
+            })
+        };
+        let copied = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC;
+        let targets = Targets {
+            normal: target("Pixel Inspector Normal", NORMAL_FORMAT, copied),
+            view_depth: target("Pixel Inspector View Depth", DEPTH_FORMAT, copied),
+            id: target("Pixel Inspector Id", ID_FORMAT, copied),
+            depth: target(
+                "Pixel Inspector Depth",
+                wgpu::TextureFormat::Depth32Float,
+                wgpu::TextureUsages::RENDER_ATTACHMENT,
+            ),
+        };
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pixel Inspector Readback"),
+            size: COLOR_OFFSET + 4,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Pipelines {
+            full: pipeline(VertexEncoding::Full),
+            packed: pipeline(VertexEncoding::Packed),
+            camera,
+            camera_bind_group,
+            targets,
+            readback,
+        }
+    }
+}
+
+/// Where the readback buffer is in its round trip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    Idle,
+    /// Copies encoded this frame; mapped once they've been submitted.
+    Copied,
+    Mapping,
+}
+
+/// What a pending readback was taken of.
+#[derive(Debug, Clone, Copy)]
+struct Probe {
+    pixel: [u32; 2],
+    ray: picking::Ray,
+    forward: glam::Vec3,
+    /// Whether the frame's color was copied too, and if it's BGRA.
+    color: Option<bool>,
+}
+
+pub struct PixelInspector {
+    pub active: bool,
+    /// Follows the cursor with a tooltip, not just the window.
+    pub tooltip: bool,
+    pub sample: Option<PixelSample>,
+    pipelines: Option<Pipelines>,
+    stage: Stage,
+    mapped: Arc<AtomicBool>,
+    /// The cursor's pixel this frame, if it's over the scene.
+    cursor: Option<Probe>,
+    pending: Option<Probe>,
+    /// The last presented frame, for its color.
+    frame: Option<wgpu::Texture>,
+}
+
+impl Default for PixelInspector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PixelInspector {
+    pub fn new() -> Self {
+        PixelInspector {
+            active: false,
+            tooltip: true,
+            sample: None,
+            pipelines: None,
+            stage: Stage::Idle,
+            mapped: Arc::new(AtomicBool::new(false)),
+            cursor: None,
+            pending: None,
+            frame: None,
+        }
+    }
+
+    /// Maps last frame's copies, and reads them once they're mapped.
+    fn receive(&mut self, state: &State) {
+        let Some(pipelines) = &self.pipelines else {
+            return;
+        };
+        match self.stage {
+            Stage::Idle => {}
+            Stage::Copied => {
+                let mapped = self.mapped.clone();
+                pipelines
+                    .readback
+                    .slice(..)
+                    .map_async(wgpu::MapMode::Read, move |r| {
+                        if r.is_ok() {
+                            mapped.store(true, Ordering::Release);
+                        }
+                    });
+                self.stage = Stage::Mapping;
+            }
+            Stage::Mapping => {
+                let _ = state.device.poll(wgpu::PollType::Poll);
+                if !self.mapped.swap(false, Ordering::Acquire) {
+                    return;
+                }
+                let probe = self.pending.take();
+                {
+                    let data = pipelines.readback.slice(..).get_mapped_range();
+                    if let Some(probe) = probe {
+                        self.sample = Some(decode(&data, &probe));
+                    }
+                }
+                pipelines.readback.unmap();
+                self.stage = Stage::Idle;
+            }
+        }
+    }
+}
+
+fn decode(data: &[u8], probe: &Probe) -> PixelSample {
+    let at = |offset: u64, len: usize| &data[offset as usize..offset as usize + len];
+    let normal: [u16; 3] = bytemuck::pod_read_unaligned(at(NORMAL_OFFSET, 6));
+    let depth = f32::from_bits(bytemuck::pod_read_unaligned(at(DEPTH_OFFSET, 4)));
+    let id: u32 = bytemuck::pod_read_unaligned(at(ID_OFFSET, 4));
+    let color = probe.color.map(|bgra| {
+        let mut color: [u8; 4] = at(COLOR_OFFSET, 4).try_into().unwrap();
+        if bgra {
+            color.swap(0, 2);
+        }
+        color
+    });
+    let hit = id > 0;
+    // the prepass depth runs along the view axis, the ray doesn't
+    let position = probe.ray.origin
+        + probe.ray.direction * depth / probe.ray.direction.dot(probe.forward).max(1e-6);
+    PixelSample {
+        pixel: probe.pixel,
+        color,
+        entity: hit.then(|| id - 1),
+        depth: hit.then_some(depth),
+        position: hit.then_some(position),
+        normal: hit.then(|| glam::Vec3::from_array(normal.map(f16_to_f32))),
+    }
+}
+
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1f => f32::INFINITY,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+fn srgb_to_linear(c: u8) -> f32 {
+    let c = c as f32 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn sample_ui(ui: &mut egui::Ui, sample: &PixelSample, world: &World) {
+    ui.label(format!("Pixel {}, {}", sample.pixel[0], sample.pixel[1]));
+    match sample.color {
+        Some(color) => {
+            ui.horizontal(|ui| {
+                let [r, g, b, a] = color;
+                let (rect, _) =
+                    ui.allocate_exact_size(egui::vec2(16.0, 16.0), egui::Sense::hover());
+                ui.painter()
+                    .rect_filled(rect, 2.0, egui::Color32::from_rgb(r, g, b));
+                ui.label(format!("{r} {g} {b} {a}  #{r:02x}{g:02x}{b:02x}"));
+            });
+            let linear = color.map(srgb_to_linear);
+            ui.label(format!(
+                "Linear {:.4} {:.4} {:.4}",
+                linear[0], linear[1], linear[2]
+            ));
+        }
+        None => {
+            ui.label("Color unavailable: the surface can't be copied");
+        }
+    }
+    let Some(id) = sample.entity else {
+        ui.label("Background");
+        return;
+    };
+    let name = world.model(id).map_or("(despawned)", |m| &m.name);
+    ui.label(format!("Model #{id} {name}"));
+    if let Some(depth) = sample.depth {
+        ui.label(format!("Depth {depth:.4}"));
+    }
+    if let Some(p) = sample.position {
+        ui.label(format!("Position {:.3} {:.3} {:.3}", p.x, p.y, p.z));
+    }
+    if let Some(n) = sample.normal {
+        ui.label(format!("Normal {:.3} {:.3} {:.3}", n.x, n.y, n.z));
+    }
+}
+
+impl Plugin for PixelInspector {
+    fn name(&self) -> &str {
+        "Pixel Inspector"
+    }
+
+    fn build(&mut self, ctx: &mut PluginContext) {
+        self.pipelines = Some(Pipelines::new(ctx.state));
+    }
+
+    fn update(&mut self, ctx: &mut PluginContext) {
+        self.receive(ctx.state);
+        // shared, so a capture someone else asked for isn't taken from them
+        if let Some(frame) = &ctx.world.captured_frame {
+            self.frame = Some(frame.clone());
+        }
+        self.cursor = None;
+        if !self.active {
+            self.frame = None;
+            return;
+        }
+        let Some(cursor) = ctx.input.cursor() else {
+            return;
+        };
+        let config = &ctx.state.surface_config;
+        let physical = glam::vec2(config.width as f32, config.height as f32);
+        let size = physical / ctx.state.scale_factor;
+        let pixel = (cursor * ctx.state.scale_factor).floor();
+        if pixel.x < 0.0 || pixel.y < 0.0 || pixel.x >= physical.x || pixel.y >= physical.y {
+            return;
+        }
+        let camera = &ctx.world.camera;
+        self.cursor = Some(Probe {
+            pixel: [pixel.x as u32, pixel.y as u32],
+            ray: picking::cursor_ray(camera, cursor, size),
+            forward: (camera.center - camera.eye).normalize_or_zero(),
+            color: None,
+        });
+        // the color comes from a copy of this frame, read next frame
+        ctx.world.capture_frame = true;
+    }
+
+    fn prepare(&mut self, state: &State, world: &World, encoder: &mut wgpu::CommandEncoder) {
+        let Some(pipelines) = &self.pipelines else {
+            return;
+        };
+        let Some(mut probe) = self.cursor else {
+            return;
+        };
+        if self.stage != Stage::Idle {
+            return;
+        }
+        let config = &state.surface_config;
+        let size = glam::vec2(config.width as f32, config.height as f32);
+        let pixel = glam::vec2(probe.pixel[0] as f32, probe.pixel[1] as f32);
+        let view_proj =
+            picking::region_projection(pixel, glam::Vec2::ONE, size) * world.camera.view_proj();
+        state.queue.write_buffer(
+            &pipelines.camera,
+            0,
+            bytemuck::cast_slice(&view_proj.to_cols_array_2d()),
+        );
+
+        let view = |t: &wgpu::Texture| t.create_view(&wgpu::TextureViewDescriptor::default());
+        let targets = &pipelines.targets;
+        let (normal_view, view_depth_view, id_view, depth_view) = (
+            view(&targets.normal),
+            view(&targets.view_depth),
+            view(&targets.id),
+            view(&targets.depth),
+        );
+        {
+            let attachment = |view| {
+                Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    depth_slice: None,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })
+            };
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("pixel inspector"),
+                color_attachments: &[
+                    attachment(&normal_view),
+                    attachment(&view_depth_view),
+                    attachment(&id_view),
+                ],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_bind_group(0, &pipelines.camera_bind_group, &[]);
+            for model in world.models.iter().filter(|m| m.is_visible()) {
+                pass.set_pipeline(match model.mesh.encoding {
+                    VertexEncoding::Full => &pipelines.full,
+                    VertexEncoding::Packed => &pipelines.packed,
+                });
+                pass.set_bind_group(1, model.bind_group(), &[]);
+                pass.set_vertex_buffer(0, model.mesh.vertex_buffer.slice(..));
+                pass.set_index_buffer(model.mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                pass.draw_indexed(0..model.mesh.index_count, 0, model.id..model.id + 1);
+            }
+        }
+
+        let copy =
+            |encoder: &mut wgpu::CommandEncoder, texture: wgpu::TexelCopyTextureInfo, offset| {
+                encoder.copy_texture_to_buffer(
+                    texture,
+                    wgpu::TexelCopyBufferInfo {
+                        buffer: &pipelines.readback,
+                        layout: wgpu::TexelCopyBufferLayout {
+                            offset,
+                            bytes_per_row: None,
+                            rows_per_image: None,
+                        },
+                    },
+                    wgpu::Extent3d {
+                        width: 1,
+                        height: 1,
+                        depth_or_array_layers: 1,
+                    },
+                );
+            };
+        copy(encoder, targets.normal.as_image_copy(), NORMAL_OFFSET);
+        copy(encoder, targets.view_depth.as_image_copy(), DEPTH_OFFSET);
+        copy(encoder, targets.id.as_image_copy(), ID_OFFSET);
+        // last frame's color, which is close enough under a still cursor
+        let frame = self.frame.as_ref().filter(|f| {
+            let size = f.size();
+            probe.pixel[0] < size.width && probe.pixel[1] < size.height
+        });
+        probe.color = frame.and_then(|frame| match frame.format() {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => Some(false),
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => Some(true),
+            _ => None,
+        });
+        if let (Some(frame), Some(_)) = (frame, probe.color) {
+            let origin = wgpu::Origin3d {
+                x: probe.pixel[0],
+                y: probe.pixel[1],
+                z: 0,
+            };
+            copy(
+                encoder,
+                wgpu::TexelCopyTextureInfo {
+                    origin,
+                    ..frame.as_image_copy()
+                },
+                COLOR_OFFSET,
+            );
+        }
+        self.pending = Some(probe);
+        self.stage = Stage::Copied;
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, _state: &State, world: &mut World) {
+        egui::Window::new("Pixel Inspector")
+            .default_open(false)
+            .show(ctx, |ui| {
+                ui.checkbox(&mut self.active, "Inspect under cursor");
+                ui.checkbox(&mut self.tooltip, "Show at cursor");
+                match &self.sample {
+                    Some(sample) if self.active => sample_ui(ui, sample, world),
+                    _ => {
+                        ui.label("Hover the scene to inspect it.");
+                    }
+                }
+            });
+        if self.active && self.tooltip && self.cursor.is_some() && !ctx.is_pointer_over_area() {
+            if let Some(sample) = &self.sample {
+                egui::Tooltip::always_open(
+                    ctx.clone(),
+                    egui::LayerId::background(),
+                    egui::Id::new("pixel inspector"),
+                    egui::PopupAnchor::Pointer,
+                )
+                .gap(12.0)
+                .show(|ui| sample_ui(ui, sample, world));
+            }
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use crate::app::State;
+use crate::input::Input;
+use crate::time::Time;
+use crate::world::World;
+
+pub struct PluginContext<'a> {
+    pub state: &'a State,
+    pub world: &'a mut World,
+    pub time: &'a Time,
+    pub input: &'a Input,
+}
+
+/// Extension point for subsystems that live outside app.rs. Every hook has
+/// an empty default so plugins only implement what they need.
+pub trait Plugin {
+    fn name(&self) -> &str;
+
+    /// Called once after the device and world exist. Register asset loaders
+    /// and spawn startup entities here.
+    fn build(&mut self, _ctx: &mut PluginContext) {}
+
+    /// Called every frame before the world updates its camera and uniforms.
+    fn update(&mut self, _ctx: &mut PluginContext) {}
+
+    /// Records passes the scene pass reads from, such as shadow maps. Runs
+    /// after the world's update with its uniforms queued; each plugin gets
+    /// its own encoder, like `encode`.
+    fn prepare(&mut self, _state: &State, _world: &World, _encoder: &mut wgpu::CommandEncoder) {}
+
+    /// Records compute work that doesn't read this frame's passes, such as
+    /// analysing last frame's capture. It's submitted on its own ahead of
+    /// the frame so it can overlap with raster work where the driver runs
+    /// compute asynchronously; wgpu exposes a single queue, so that's up
+    /// to the driver.
+    fn compute(&mut self, _state: &State, _world: &World, _encoder: &mut wgpu::CommandEncoder) {}
+
+    /// Draws into the main scene pass after the world's models.
+    fn render(&self, _world: &World, _renderpass: &mut wgpu::RenderPass) {}
+
+    /// Records extra passes after the scene pass and before egui. Each plugin
+    /// gets its own encoder; GPU errors are reported under the plugin name.
+    fn encode(
+        &mut self,
+        _state: &State,
+        _world: &World,
+        _encoder: &mut wgpu::CommandEncoder,
+        _target: &wgpu::TextureView,
+    ) {
+    }
+
+    fn ui(&mut self, _ctx: &egui::Context, _state: &State, _world: &mut World) {}
+}
@@ -0,0 +1,394 @@
+//! GPU noise textures (Perlin, simplex, Worley fBm) for heightmaps, masks
+//! and material inputs without any texture assets on disk.
+
+use crate::app::State;
+use crate::headless::read_texture;
+use crate::plugin::{Plugin, PluginContext};
+use crate::texture::Texture;
+use crate::world::World;
+use wgpu::util::DeviceExt;
+
+const SHADER: &str = r#"
+struct Params {
+    kind: u32,
+    seed: u32,
+    octaves: u32,
+    _pad: u32,
+    frequency: f32,
+    lacunarity: f32,
+    gain: f32,
+    _pad2: f32,
+};
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var output: texture_storage_2d<rgba8unorm, write>;
+
+fn hash(p: vec2<u32>) -> u32 {
+    var h = (p.x * 0x8da6b343u) ^ (p.y * 0xd8163841u) ^ (params.seed * 0xcb1ab31fu);
+    h = (h ^ (h >> 16u)) * 0x7feb352du;
+    h = (h ^ (h >> 15u)) * 0x846ca68bu;
+    return h ^ (h >> 16u);
+}
+
+fn gradient(c: vec2<i32>) -> vec2<f32> {
+    let a = f32(hash(bitcast<vec2<u32>>(c))) * (6.2831853 / 4294967295.0);
+    return vec2(cos(a), sin(a));
+}
+
+fn perlin(p: vec2<f32>) -> f32 {
+    let i = vec2<i32>(floor(p));
+    let f = fract(p);
+    let u = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+    let a = dot(gradient(i), f);
+    let b = dot(gradient(i + vec2(1, 0)), f - vec2(1.0, 0.0));
+    let c = dot(gradient(i + vec2(0, 1)), f - vec2(0.0, 1.0));
+    let d = dot(gradient(i + vec2(1, 1)), f - vec2(1.0, 1.0));
+    return mix(mix(a, b, u.x), mix(c, d, u.x), u.y) * 0.7 + 0.5;
+}
+
+fn simplex(p: vec2<f32>) -> f32 {
+    let k1 = 0.366025404;
+    let k2 = 0.211324865;
+    let i = floor(p + (p.x + p.y) * k1);
+    let a = p - i + (i.x + i.y) * k2;
+    let o = select(vec2(0.0, 1.0), vec2(1.0, 0.0), a.x > a.y);
+    let b = a - o + k2;
+    let c = a - 1.0 + 2.0 * k2;
+    let h = max(0.5 - vec3(dot(a, a), dot(b, b), dot(c, c)), vec3(0.0));
+    let ii = vec2<i32>(i);
+    let n = h * h * h * h * vec3(
+        dot(a, gradient(ii)),
+        dot(b, gradient(ii + vec2<i32>(o))),
+        dot(c, gradient(ii + vec2(1, 1))),
+    );
+    return dot(n, vec3(70.0)) * 0.5 + 0.5;
+}
+
+fn worley(p: vec2<f32>) -> f32 {
+    let i = vec2<i32>(floor(p));
+    let f = fract(p);
+    var d = 8.0;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let o = vec2(x, y);
+            let h = hash(bitcast<vec2<u32>>(i + o));
+            let jitter = vec2(f32(h & 0xffffu), f32(h >> 16u)) / 65535.0;
+            d = min(d, length(vec2<f32>(o) + jitter - f));
+        }
+    }
+    return min(d, 1.0);
+}
+
+fn noise(p: vec2<f32>) -> f32 {
+    switch params.kind {
+        case 1u: { return simplex(p); }
+        case 2u: { return worley(p); }
+        default: { return perlin(p); }
+    }
+}
+
+@compute @workgroup_size(8, 8)
+fn csMain(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(output);
+    if id.x >= size.x || id.y >= size.y {
+        return;
+    }
+    var p = vec2<f32>(id.xy) / f32(size.x) * params.frequency;
+    var amplitude = 1.0;
+    var sum = 0.0;
+    var total = 0.0;
+    for (var octave = 0u; octave < max(params.octaves, 1u); octave++) {
+        sum += amplitude * noise(p);
+        total += amplitude;
+        p *= params.lacunarity;
+        amplitude *= params.gain;
+    }
+    let v = clamp(sum / total, 0.0, 1.0);
+    textureStore(output, vec2<i32>(id.xy), vec4(v, v, v, 1.0));
+}
+"#;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoiseKind {
+    Perlin,
+    Simplex,
+    Worley,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoiseParams {
+    pub kind: NoiseKind,
+    pub seed: u32,
+    pub octaves: u32,
+    /// Cells across the texture for the first octave.
+    pub frequency: f32,
+    pub lacunarity: f32,
+    pub gain: f32,
+}
+
+impl Default for NoiseParams {
+    fn default() -> Self {
+        NoiseParams {
+            kind: NoiseKind::Perlin,
+            seed: 0,
+            octaves: 4,
+            frequency: 8.0,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ParamsUniform {
+    kind: u32,
+    seed: u32,
+    octaves: u32,
+    _pad: u32,
+    frequency: f32,
+    lacunarity: f32,
+    gain: f32,
+    _pad2: f32,
+}
+
+/// Compute pipeline that fills square grayscale `Rgba8Unorm` textures.
+pub struct NoiseGenerator {
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl NoiseGenerator {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Noise"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Noise"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: wgpu::TextureFormat::Rgba8Unorm,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Noise"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Noise"),
+            layout: Some(&layout),
+            module: &module,
+            entry_point: Some("csMain"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        NoiseGenerator {
+            pipeline,
+            bind_group_layout,
+        }
+    }
+
+    pub fn generate(&self, state: &State, params: &NoiseParams, size: u32) -> Texture {
+        let texture = state.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Noise"),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let texture = Texture::from_texture(state, texture);
+
+        let uniform = ParamsUniform {
+            kind: params.kind as u32,
+            seed: params.seed,
+            octaves: params.octaves,
+            _pad: 0,
+            frequency: params.frequency,
+            lacunarity: params.lacunarity,
+            gain: params.gain,
+            _pad2: 0.0,
+        };
+        let buffer = state
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Noise Params"),
+                contents: bytemuck::cast_slice(&[uniform]),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+        let bind_group = state.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Noise"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+            ],
+        });
+
+        let mut encoder = state
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Noise"),
+            });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Noise"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            let groups = size.div_ceil(8);
+            pass.dispatch_workgroups(groups, groups, 1);
+        }
+        state.queue.submit(Some(encoder.finish()));
+
+        texture
+    }
+}
+
+/// Egui window for tweaking noise parameters with a live preview.
+pub struct Procgen {
+    pub params: NoiseParams,
+    pub size: u32,
+    /// The latest result, for use as a heightmap, mask or material input.
+    pub texture: Option<Texture>,
+    generator: Option<NoiseGenerator>,
+    preview: Option<egui::TextureHandle>,
+    dirty: bool,
+    status: String,
+}
+
+impl Default for Procgen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Procgen {
+    pub fn new() -> Self {
+        Procgen {
+            params: NoiseParams::default(),
+            size: 256,
+            texture: None,
+            generator: None,
+            preview: None,
+            dirty: true,
+            status: String::new(),
+        }
+    }
+
+    fn regenerate(&mut self, ctx: &egui::Context, state: &State) {
+        let Some(generator) = &self.generator else {
+            return;
+        };
+        let texture = generator.generate(state, &self.params, self.size);
+        let capture = read_texture(&state.device, &state.queue, &texture.texture);
+        let image = egui::ColorImage::from_rgba_unmultiplied(
+            [capture.width as usize, capture.height as usize],
+            &capture.pixels,
+        );
+        self.preview = Some(ctx.load_texture("noise", image, egui::TextureOptions::LINEAR));
+        self.texture = Some(texture);
+    }
+}
+
+impl Plugin for Procgen {
+    fn name(&self) -> &str {
+        "Procgen"
+    }
+
+    fn build(&mut self, ctx: &mut PluginContext) {
+        if !ctx.state.caps.compute {
+            log::warn!("Procedural noise needs compute shaders, which this device lacks");
+            return;
+        }
+        self.generator = Some(NoiseGenerator::new(&ctx.state.device));
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, state: &State, _world: &mut World) {
+        egui::Window::new("Procedural Noise")
+            .default_open(false)
+            .show(ctx, |ui| {
+                let before = (self.params, self.size);
+                let p = &mut self.params;
+                ui.horizontal(|ui| {
+                    for (kind, label) in [
+                        (NoiseKind::Perlin, "Perlin"),
+                        (NoiseKind::Simplex, "Simplex"),
+                        (NoiseKind::Worley, "Worley"),
+                    ] {
+                        ui.selectable_value(&mut p.kind, kind, label);
+                    }
+                });
+                ui.add(egui::DragValue::new(&mut p.seed).prefix("Seed: "));
+                ui.add(egui::Slider::new(&mut p.octaves, 1..=8).text("Octaves"));
+                ui.add(egui::Slider::new(&mut p.frequency, 1.0..=64.0).text("Frequency"));
+                ui.add(egui::Slider::new(&mut p.lacunarity, 1.0..=4.0).text("Lacunarity"));
+                ui.add(egui::Slider::new(&mut p.gain, 0.0..=1.0).text("Gain"));
+                egui::ComboBox::from_label("Size")
+                    .selected_text(format!("{0}x{0}", self.size))
+                    .show_ui(ui, |ui| {
+                        for size in [128, 256, 512, 1024] {
+                            ui.selectable_value(&mut self.size, size, format!("{size}x{size}"));
+                        }
+                    });
+                if (self.params, self.size) != before {
+                    self.dirty = true;
+                }
+                if std::mem::take(&mut self.dirty) {
+                    self.regenerate(ctx, state);
+                }
+
+                if let Some(preview) = &self.preview {
+                    ui.image((preview.id(), egui::vec2(256.0, 256.0)));
+                }
+                if ui.button("Save noise.png").clicked() {
+                    if let Some(texture) = &self.texture {
+                        let capture = read_texture(&state.device, &state.queue, &texture.texture);
+                        self.status = match capture.save_png("noise.png") {
+                            Ok(()) => "Saved noise.png".to_string(),
+                            Err(e) => format!("Failed to save noise.png: {e}"),
+                        };
+                    }
+                }
+                if !self.status.is_empty() {
+                    ui.label(&self.status);
+                }
+            });
+    }
+}
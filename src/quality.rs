@@ -0,0 +1,137 @@
+//! Named bundles of render settings, switchable at runtime, with the frame
+//! time measured under each so they can be compared. The renderer has no
+//! MSAA, render scale or live shadow maps yet, so presets cover texture
+//! filtering, post effects and small-object culling.
+
+use crate::app::State;
+use crate::world::World;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QualityPreset {
+    Low,
+    Medium,
+    High,
+    Ultra,
+}
+
+/// What a preset sets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QualitySettings {
+    /// Capped at what the device supports.
+    pub max_anisotropy: u16,
+    pub lens_flare: bool,
+    /// See `Culling::min_screen_size`.
+    pub min_screen_size: f32,
+}
+
+impl QualityPreset {
+    pub const ALL: [QualityPreset; 4] = [
+        QualityPreset::Low,
+        QualityPreset::Medium,
+        QualityPreset::High,
+        QualityPreset::Ultra,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            QualityPreset::Low => "Low",
+            QualityPreset::Medium => "Medium",
+            QualityPreset::High => "High",
+            QualityPreset::Ultra => "Ultra",
+        }
+    }
+
+    /// Case-insensitive, for the console and command line.
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|p| p.label().eq_ignore_ascii_case(name))
+    }
+
+    pub fn settings(self) -> QualitySettings {
+        let (max_anisotropy, lens_flare, min_screen_size) = match self {
+            QualityPreset::Low => (1, false, 0.01),
+            QualityPreset::Medium => (4, false, 0.004),
+            QualityPreset::High => (8, true, 0.001),
+            QualityPreset::Ultra => (16, true, 0.0),
+        };
+        QualitySettings {
+            max_anisotropy,
+            lens_flare,
+            min_screen_size,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+}
+
+#[derive(Default)]
+pub struct Quality {
+    /// The preset applied last; `None` before any is.
+    pub preset: Option<QualityPreset>,
+    /// Total seconds and frames rendered under each preset, in `ALL` order.
+    frame_times: [(f64, u32); 4],
+}
+
+impl Quality {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts a frame towards the current preset's average.
+    pub fn record_frame(&mut self, seconds: f32) {
+        if let Some(preset) = self.preset {
+            self.frame_times[preset as usize].0 += seconds as f64;
+            self.frame_times[preset as usize].1 += 1;
+        }
+    }
+
+    /// Average frame time in milliseconds under `preset`, if it's been used.
+    pub fn average_ms(&self, preset: QualityPreset) -> Option<f32> {
+        let (seconds, frames) = self.frame_times[preset as usize];
+        (frames > 0).then(|| (seconds / frames as f64 * 1000.0) as f32)
+    }
+}
+
+/// Applies `preset` to the renderer and the world.
+pub fn apply(preset: QualityPreset, state: &State, world: &mut World) {
+    let settings = preset.settings();
+    if state
+        .samplers
+        .set_max_anisotropy(settings.max_anisotropy.min(state.caps.max_anisotropy))
+    {
+        world.refresh_samplers(state);
+    }
+    world.lens_flare.enabled = settings.lens_flare;
+    world.culling.min_screen_size = settings.min_screen_size;
+    world.quality.preset = Some(preset);
+    log::info!("Quality preset: {}", preset.label());
+}
+
+/// Preset picker and each preset's average frame time this run.
+pub fn ui(ui: &mut egui::Ui, state: &State, world: &mut World) {
+    ui.horizontal(|ui| {
+        ui.label("Quality");
+        for preset in QualityPreset::ALL {
+            let selected = world.quality.preset == Some(preset);
+            if ui.selectable_label(selected, preset.label()).clicked() {
+                apply(preset, state, world);
+            }
+        }
+    });
+    egui::Grid::new("quality_frame_times")
+        .striped(true)
+        .show(ui, |ui| {
+            for preset in QualityPreset::ALL {
+                ui.label(preset.label());
+                match world.quality.average_ms(preset) {
+                    Some(ms) => ui.label(format!("{ms:.2} ms")),
+                    None => ui.label("-"),
+                };
+                ui.end_row();
+            }
+        });
+}
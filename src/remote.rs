@@ -0,0 +1,510 @@
+//! Minimal HTTP endpoint so test harnesses and CI jobs can drive the
+//! sandbox. Start it with `--remote <port>`; it only listens on localhost.
+//! Every request is a POST, with parameters in the query string or a
+//! form-encoded body, and every reply is JSON:
+//!
+//! ```text
+//! curl -d "" localhost:7879/stats
+//! curl -d "" localhost:7879/load_model?path=assets/fox.glb
+//! curl -d "eye=0,2,5&center=0,0,0" localhost:7879/set_camera
+//! curl -d "" localhost:7879/screenshot?path=out.png
+//! curl -d "" localhost:7879/command?id=edit.clear
+//! ```
+//!
+//! Web pages open in a browser can reach localhost too, so requests that
+//! carry an `Origin` or name another `Host` are refused, and screenshots
+//! only go under `screenshots/`.
+//!
+//! Connections are accepted and read on their own threads, which hand
+//! whole requests to `update`; a slow client never holds up a frame.
+//! Requests still wait for the next frame, so a reactive or hidden window
+//! won't answer until it draws again.
+
+use crate::diagnostics;
+use crate::material;
+use crate::plugin::{Plugin, PluginContext};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+pub const DEFAULT_PORT: u16 = 7879;
+/// A client that connects but never finishes its request is dropped.
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+/// Most a request's line, headers and body can take up together.
+const MAX_REQUEST: u64 = 8 * 1024;
+/// Connections past this many at once are closed straight away.
+const MAX_CONNECTIONS: usize = 8;
+/// Where `/screenshot` writes, whatever path it's given.
+const SCREENSHOT_DIR: &str = "screenshots";
+
+struct Request {
+    method: String,
+    path: String,
+    params: HashMap<String, String>,
+    /// Whether there was an `Origin` header, which browsers add.
+    origin: bool,
+    host: Option<String>,
+}
+
+struct Response {
+    status: u16,
+    body: String,
+}
+
+/// A request read off a connection, and where its connection's thread
+/// waits for the reply.
+type Pending = (Request, mpsc::Sender<Response>);
+
+impl Response {
+    fn ok(body: String) -> Self {
+        Response { status: 200, body }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Response {
+            status,
+            body: format!("{{\"error\":{}}}", json_string(message)),
+        }
+    }
+}
+
+pub struct RemoteControl {
+    port: u16,
+    requests: Option<mpsc::Receiver<Pending>>,
+}
+
+impl RemoteControl {
+    pub fn new(port: u16) -> Self {
+        RemoteControl {
+            port,
+            requests: None,
+        }
+    }
+
+    fn handle(&mut self, ctx: &mut PluginContext, request: &Request) -> Response {
+        let param = |name: &str| request.params.get(name).map(String::as_str);
+        match request.path.as_str() {
+            "/stats" => Response::ok(stats_json(ctx)),
+            "/commands" => {
+                let ids: Vec<String> = ctx
+                    .world
+                    .commands
+                    .commands()
+                    .iter()
+                    .map(|c| json_string(&c.id))
+                    .collect();
+                Response::ok(format!("{{\"commands\":[{}]}}", ids.join(",")))
+            }
+            "/load_model" => {
+                let Some(path) = param("path") else {
+                    return Response::error(400, "missing path");
+                };
+                let ids = ctx.world.load_model(ctx.state, path);
+                if ids.is_empty() {
+                    return Response::error(422, &format!("nothing loaded from {path}"));
+                }
+                let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+                Response::ok(format!("{{\"entities\":[{}]}}", ids.join(",")))
+            }
+            "/set_camera" => {
+                let eye = param("eye").map(parse_vec3);
+                let center = param("center").map(parse_vec3);
+                let up = param("up").map(parse_vec3);
+                if [eye, center, up].iter().any(|v| matches!(v, Some(None))) {
+                    return Response::error(400, "vectors are written x,y,z");
+                }
+                let camera = &mut ctx.world.camera;
+                camera.eye = eye.flatten().unwrap_or(camera.eye);
+                camera.center = center.flatten().unwrap_or(camera.center);
+                camera.up = up.flatten().unwrap_or(camera.up);
+                camera.update_uniform();
+                ctx.world
+                    .camera_controller
+                    .sync_from_camera(&ctx.world.camera);
+                Response::ok("{}".to_string())
+            }
+            "/screenshot" => {
+                let name = param("path").unwrap_or("screenshot-remote.png");
+                let Some(path) = screenshot_path(name) else {
+                    return Response::error(400, "path must be relative, without ..");
+                };
+                if let Some(dir) = path.parent() {
+                    if let Err(e) = std::fs::create_dir_all(dir) {
+                        return Response::error(500, &format!("{}: {e}", dir.display()));
+                    }
+                }
+                let path_json = json_string(&path.to_string_lossy());
+                // written once the current frame is presented
+                ctx.world.screenshot = Some(path);
+                Response::ok(format!("{{\"path\":{path_json}}}"))
+            }
+            "/command" => {
+                let Some(id) = param("id") else {
+                    return Response::error(400, "missing id");
+                };
+                if !ctx.world.commands.commands().iter().any(|c| c.id == id) {
+                    return Response::error(404, &format!("unknown command {id}"));
+                }
+                ctx.world.commands.queue(id);
+                Response::ok("{}".to_string())
+            }
+            _ => Response::error(404, "unknown endpoint"),
+        }
+    }
+}
+
+impl Plugin for RemoteControl {
+    fn name(&self) -> &str {
+        "Remote Control"
+    }
+
+    fn build(&mut self, _ctx: &mut PluginContext) {
+        match TcpListener::bind(("127.0.0.1", self.port)) {
+            Ok(listener) => {
+                log::info!("Remote control listening on http://127.0.0.1:{}", self.port);
+                let (sender, receiver) = mpsc::channel();
+                std::thread::spawn(move || accept(listener, sender));
+                self.requests = Some(receiver);
+            }
+            Err(e) => log::warn!("Remote control failed to listen on port {}: {e}", self.port),
+        }
+    }
+
+    fn update(&mut self, ctx: &mut PluginContext) {
+        let Some(requests) = &self.requests else {
+            return;
+        };
+        let pending: Vec<Pending> = requests.try_iter().collect();
+        for (request, reply) in pending {
+            log::info!("Remote control: {}", request.path);
+            let response = self.handle(ctx, &request);
+            // the client may have hung up meanwhile
+            let _ = reply.send(response);
+        }
+    }
+}
+
+/// Runs on its own thread until the app exits, with a thread per
+/// connection, up to `MAX_CONNECTIONS`, so one client can't hold up the
+/// others.
+fn accept(listener: TcpListener, requests: mpsc::Sender<Pending>) {
+    let open = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                log::warn!("Remote control accept failed: {e}");
+                continue;
+            }
+        };
+        if open.load(Ordering::Relaxed) >= MAX_CONNECTIONS {
+            log::warn!("Remote control has too many connections; closing one");
+            continue;
+        }
+        open.fetch_add(1, Ordering::Relaxed);
+        let requests = requests.clone();
+        let open = open.clone();
+        std::thread::spawn(move || {
+            serve(stream, &requests);
+            open.fetch_sub(1, Ordering::Relaxed);
+        });
+    }
+}
+
+/// Reads one request off `stream`, waits for `update` to answer it and
+/// writes the reply.
+fn serve(mut stream: TcpStream, requests: &mpsc::Sender<Pending>) {
+    let response = match read_request(&stream) {
+        Ok(request) => match refuse(&request) {
+            Some(refusal) => refusal,
+            None => {
+                let (reply, response) = mpsc::channel();
+                if requests.send((request, reply)).is_err() {
+                    return;
+                }
+                match response.recv() {
+                    Ok(response) => response,
+                    Err(_) => return,
+                }
+            }
+        },
+        Err(e) => Response::error(400, &e.to_string()),
+    };
+    if let Err(e) = write_response(&mut stream, &response) {
+        log::warn!("Remote control failed to reply: {e}");
+    }
+}
+
+fn read_request(stream: &TcpStream) -> std::io::Result<Request> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.take(MAX_REQUEST));
+    let too_long = || std::io::Error::new(ErrorKind::InvalidData, "request is too long");
+
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if !line.ends_with('\n') {
+        return Err(too_long());
+    }
+    let mut words = line.split_whitespace();
+    let (Some(method), Some(target)) = (words.next(), words.next()) else {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            "malformed request line",
+        ));
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut params = parse_query(query);
+
+    let mut content_length = 0;
+    let mut origin = false;
+    let mut host = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            break;
+        }
+        if !header.ends_with('\n') {
+            return Err(too_long());
+        }
+        if header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            } else if name.eq_ignore_ascii_case("origin") {
+                origin = true;
+            } else if name.eq_ignore_ascii_case("host") {
+                host = Some(value.trim().to_string());
+            }
+        }
+    }
+    if content_length > 0 {
+        if content_length > MAX_REQUEST as usize {
+            return Err(too_long());
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body)?;
+        params.extend(parse_query(&String::from_utf8_lossy(&body)));
+    }
+
+    Ok(Request {
+        method: method.to_string(),
+        path: crate::uri::percent_decode(path),
+        params,
+        origin,
+        host,
+    })
+}
+
+/// Why `request` won't be served, if it won't. A page in a browser can
+/// send GETs from links and images and POSTs from forms, but those POSTs
+/// carry an `Origin`, and a page reaching us through DNS rebinding still
+/// names its own `Host`.
+fn refuse(request: &Request) -> Option<Response> {
+    if request.method != "POST" {
+        return Some(Response::error(405, "requests must be POSTs"));
+    }
+    if request.origin {
+        return Some(Response::error(403, "requests from web pages are refused"));
+    }
+    if request
+        .host
+        .as_deref()
+        .is_some_and(|host| !is_localhost(host))
+    {
+        return Some(Response::error(403, "requests must be to localhost"));
+    }
+    None
+}
+
+/// `host` is a `Host` header value, with or without a port.
+fn is_localhost(host: &str) -> bool {
+    let name = match host.strip_prefix('[') {
+        Some(v6) => v6.split(']').next().unwrap_or(""),
+        None => host.split(':').next().unwrap_or(""),
+    };
+    name.eq_ignore_ascii_case("localhost") || name == "127.0.0.1" || name == "::1"
+}
+
+/// `name` under `SCREENSHOT_DIR`, if it's a plain relative path.
+fn screenshot_path(name: &str) -> Option<PathBuf> {
+    let relative = Path::new(name);
+    let plain = relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)));
+    (plain && relative.file_name().is_some()).then(|| Path::new(SCREENSHOT_DIR).join(relative))
+}
+
+fn write_response(stream: &mut TcpStream, response: &Response) -> std::io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        422 => "Unprocessable Entity",
+        500 => "Internal Server Error",
+        _ => "Bad Request",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {reason}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        response.body.len(),
+        response.body
+    )
+}
+
+fn stats_json(ctx: &PluginContext) -> String {
+    let world = &ctx.world;
+    let triangles: usize = world.models.iter().map(|m| m.mesh.indices.len() / 3).sum();
+    let frame_ms = ctx.time.smoothed_dt * 1000.0;
+    let fps = if frame_ms > 0.0 {
+        1000.0 / frame_ms
+    } else {
+        0.0
+    };
+    let transient = ctx.state.transient.stats();
+    let selected = world
+        .selection
+        .primary()
+        .map_or("null".to_string(), |id| id.to_string());
+    format!(
+        "{{\"frame_ms\":{frame_ms},\"fps\":{fps},\"elapsed_seconds\":{},\"models\":{},\"meshes\":{},\"triangles\":{triangles},\"selected\":{selected},\"compiling_pipelines\":{},\"gpu_errors\":{},\"transient_textures\":{},\"transient_bytes\":{}}}",
+        ctx.time.elapsed_seconds,
+        world.models.len(),
+        world.meshes.len(),
+        material::compiling_pipelines(),
+        diagnostics::gpu_errors().len(),
+        transient.textures,
+        transient.allocated_bytes,
+    )
+}
+
+fn parse_vec3(text: &str) -> Option<glam::Vec3> {
+    let values: Vec<f32> = text
+        .split(',')
+        .map(|v| v.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    Some(glam::Vec3::from_array(values.try_into().ok()?))
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            // query strings also write spaces as +
+            let decode = |text: &str| crate::uri::percent_decode(&text.replace('+', " "));
+            (decode(key), decode(value))
+        })
+        .collect()
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, origin: bool, host: Option<&str>) -> Request {
+        Request {
+            method: method.to_string(),
+            path: "/stats".to_string(),
+            params: HashMap::new(),
+            origin,
+            host: host.map(String::from),
+        }
+    }
+
+    #[test]
+    fn only_local_posts_are_served() {
+        let status = |r: &Request| refuse(r).map(|r| r.status);
+        assert_eq!(status(&request("POST", false, None)), None);
+        assert_eq!(
+            status(&request("POST", false, Some("localhost:7879"))),
+            None
+        );
+        assert_eq!(status(&request("POST", false, Some("127.0.0.1"))), None);
+        assert_eq!(status(&request("POST", false, Some("[::1]:7879"))), None);
+        assert_eq!(status(&request("GET", false, None)), Some(405));
+        assert_eq!(status(&request("POST", true, None)), Some(403));
+        assert_eq!(
+            status(&request("POST", false, Some("evil.example:7879"))),
+            Some(403)
+        );
+        assert_eq!(
+            status(&request("POST", false, Some("localhost.evil.example"))),
+            Some(403)
+        );
+    }
+
+    /// `text` sent through a local connection and read back as a request.
+    fn read(text: &[u8]) -> std::io::Result<Request> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client.write_all(text).unwrap();
+        client.shutdown(std::net::Shutdown::Write).unwrap();
+        read_request(&listener.accept().unwrap().0)
+    }
+
+    #[test]
+    fn plus_is_a_space_only_in_queries() {
+        let request =
+            read(b"POST /a+b%20c?x=1+2&y=%2B HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nz=3+4")
+                .unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/a+b c");
+        assert_eq!(request.params["x"], "1 2");
+        assert_eq!(request.params["y"], "+");
+        assert_eq!(request.params["z"], "3 4");
+        assert_eq!(request.host.as_deref(), Some("localhost"));
+        assert!(!request.origin);
+    }
+
+    #[test]
+    fn oversized_requests_are_errors() {
+        let long_header = format!("POST /stats HTTP/1.1\r\nX: {}\r\n\r\n", "a".repeat(9000));
+        assert!(read(long_header.as_bytes()).is_err());
+        let long_line = format!("POST /{} HTTP/1.1\r\n\r\n", "a".repeat(9000));
+        assert!(read(long_line.as_bytes()).is_err());
+        let long_body = b"POST /stats HTTP/1.1\r\nContent-Length: 100000\r\n\r\n";
+        assert!(read(long_body).is_err());
+    }
+
+    #[test]
+    fn screenshots_stay_in_their_directory() {
+        assert_eq!(
+            screenshot_path("shots/a.png"),
+            Some(Path::new(SCREENSHOT_DIR).join("shots/a.png"))
+        );
+        for name in [
+            "",
+            "/etc/a.png",
+            "../a.png",
+            "shots/../../a.png",
+            "./a.png",
+            "shots/..",
+        ] {
+            assert_eq!(screenshot_path(name), None, "{name:?}");
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! Sampler settings as a hashable value, and a cache so textures and
+//! materials asking for the same settings share one `wgpu::Sampler`.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Everything that goes into a sampler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SamplerDesc {
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    pub address_mode_u: wgpu::AddressMode,
+    pub address_mode_v: wgpu::AddressMode,
+    pub address_mode_w: wgpu::AddressMode,
+    /// Requested level, capped by the cache's `max_anisotropy`; 1 is off.
+    /// Only applies when all three filters are linear.
+    pub anisotropy: u16,
+    /// Makes a comparison sampler, for depth textures such as shadow maps.
+    pub compare: Option<wgpu::CompareFunction>,
+}
+
+impl Default for SamplerDesc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SamplerDesc {
+    /// Linear filtering, repeating in every direction, with as much
+    /// anisotropy as the render settings allow.
+    pub fn new() -> Self {
+        SamplerDesc {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            anisotropy: 16,
+            compare: None,
+        }
+    }
+
+    pub fn with_filter(mut self, filter: wgpu::FilterMode) -> Self {
+        self.mag_filter = filter;
+        self.min_filter = filter;
+        self.mipmap_filter = filter;
+        self
+    }
+
+    pub fn with_address_mode(mut self, address_mode: wgpu::AddressMode) -> Self {
+        self.address_mode_u = address_mode;
+        self.address_mode_v = address_mode;
+        self.address_mode_w = address_mode;
+        self
+    }
+
+    /// Whether any filter is linear. Non-filterable textures need a
+    /// sampler where none are.
+    pub fn filters(&self) -> bool {
+        [self.mag_filter, self.min_filter, self.mipmap_filter].contains(&wgpu::FilterMode::Linear)
+    }
+
+    /// The layout entry type a sampler with these settings binds as.
+    pub fn binding_type(&self) -> wgpu::SamplerBindingType {
+        if self.compare.is_some() {
+            wgpu::SamplerBindingType::Comparison
+        } else if self.filters() {
+            wgpu::SamplerBindingType::Filtering
+        } else {
+            wgpu::SamplerBindingType::NonFiltering
+        }
+    }
+
+    fn descriptor(&self, max_anisotropy: u16) -> wgpu::SamplerDescriptor<'static> {
+        // wgpu rejects anisotropy unless every filter is linear
+        let linear = [self.mag_filter, self.min_filter, self.mipmap_filter]
+            .iter()
+            .all(|f| *f == wgpu::FilterMode::Linear);
+        wgpu::SamplerDescriptor {
+            label: Some("Cached Sampler"),
+            address_mode_u: self.address_mode_u,
+            address_mode_v: self.address_mode_v,
+            address_mode_w: self.address_mode_w,
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: self.mipmap_filter,
+            anisotropy_clamp: if linear {
+                self.anisotropy.clamp(1, max_anisotropy)
+            } else {
+                1
+            },
+            compare: self.compare,
+            ..Default::default()
+        }
+    }
+}
+
+struct Inner {
+    samplers: HashMap<SamplerDesc, wgpu::Sampler>,
+    max_anisotropy: u16,
+}
+
+/// Lives on `State`; samplers are created on first request and kept until
+/// the anisotropy cap changes, since there are only ever a handful.
+pub struct SamplerCache {
+    inner: Mutex<Inner>,
+}
+
+impl Default for SamplerCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SamplerCache {
+    /// Starts with anisotropy off.
+    pub fn new() -> Self {
+        SamplerCache {
+            inner: Mutex::new(Inner {
+                samplers: HashMap::new(),
+                max_anisotropy: 1,
+            }),
+        }
+    }
+
+    pub fn get(&self, device: &wgpu::Device, desc: SamplerDesc) -> wgpu::Sampler {
+        let mut inner = self.inner.lock().unwrap();
+        let max_anisotropy = inner.max_anisotropy;
+        inner
+            .samplers
+            .entry(desc)
+            .or_insert_with(|| device.create_sampler(&desc.descriptor(max_anisotropy)))
+            .clone()
+    }
+
+    pub fn max_anisotropy(&self) -> u16 {
+        self.inner.lock().unwrap().max_anisotropy
+    }
+
+    /// Caps every sampler's anisotropy at `value` (1 to 16). Returns true
+    /// when it changed; samplers are then recreated on their next `get`,
+    /// and bind groups holding the old ones need rebuilding, see
+    /// `World::refresh_samplers`.
+    pub fn set_max_anisotropy(&self, value: u16) -> bool {
+        let value = value.clamp(1, 16);
+        let mut inner = self.inner.lock().unwrap();
+        if inner.max_anisotropy == value {
+            return false;
+        }
+        inner.max_anisotropy = value;
+        inner.samplers.clear();
+        true
+    }
+
+    /// Distinct samplers created since the last change of the cap.
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().samplers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -0,0 +1,1308 @@
+//! Scatters thousands of instanced grass tufts and rocks over a model's
+//! surface. Points are spread over its triangles by area, thinned by an
+//! optional density map and a slope limit, and grouped into chunks that are
+//! frustum- and distance-culled each frame, one draw per visible chunk.
+//! Blades sway in the wind in the vertex shader and dither out with
+//! distance. Past the impostor distance each layer crossfades to quads
+//! showing baked views of its mesh. The instances are a snapshot: scatter
+//! again after moving the target.
+
+use crate::app::State;
+use crate::headless::Capture;
+use crate::impostor::{self, ImpostorAtlas};
+use crate::mesh::{Mesh, Vertex, VertexEncoding};
+use crate::model::EntityId;
+use crate::plugin::{Plugin, PluginContext};
+use crate::spatial::{Aabb, Frustum};
+use crate::transform::Transform;
+use crate::world::World;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
+use wgpu::util::DeviceExt;
+
+const SHADER: &str = r#"
+struct Camera { view_proj: mat4x4<f32> };
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+struct Frame {
+    // xy: direction, z: strength, w: frequency
+    wind: vec4<f32>,
+    // w: seconds
+    eye: vec4<f32>,
+    // x: fade start, y: fade end
+    fade: vec4<f32>,
+    // x: impostor crossfade start, y: end
+    lod: vec4<f32>,
+    light_direction: vec4<f32>,
+    light_color: vec4<f32>,
+    ambient: vec4<f32>,
+};
+@group(1) @binding(0) var<uniform> frame: Frame;
+
+struct Layer {
+    color: vec4<f32>,
+    // x: mesh height, y: sway
+    params: vec4<f32>,
+};
+@group(2) @binding(0) var<uniform> layer: Layer;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) fade: f32,
+    @location(2) shade: f32,
+    // 0 near, 1 fully impostor
+    @location(3) lod: f32,
+};
+
+fn instanceLod(origin: vec3<f32>) -> f32 {
+    return smoothstep(frame.lod.x, frame.lod.y, distance(origin, frame.eye.xyz));
+}
+
+// screen-door fades keep the pass opaque and sortless; the mesh and its
+// impostor use the same pattern so their crossfade leaves no holes
+fn ditherThreshold(pos: vec4<f32>) -> f32 {
+    let cell = vec2<u32>(pos.xy) % vec2(4u);
+    let bayer = array<f32, 16>(0.0, 8.0, 2.0, 10.0, 12.0, 4.0, 14.0, 6.0,
+                               3.0, 11.0, 1.0, 9.0, 15.0, 7.0, 13.0, 5.0);
+    return (bayer[cell.y * 4u + cell.x] + 0.5) / 16.0;
+}
+
+fn shade(color: vec3<f32>, normal: vec3<f32>) -> vec4<f32> {
+    // two-sided: blades are single quads
+    let n_dot_l = abs(dot(normal, -frame.light_direction.xyz));
+    let light = frame.ambient.rgb + frame.light_color.rgb * n_dot_l;
+    return vec4(layer.color.rgb * color * light, 1.0);
+}
+
+@vertex
+fn vsMain(
+    @location(0) pos: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(4) m0: vec4<f32>,
+    @location(5) m1: vec4<f32>,
+    @location(6) m2: vec4<f32>,
+    @location(7) m3: vec4<f32>,
+    // x: wind phase, y: brightness
+    @location(8) params: vec4<f32>,
+) -> VSOut {
+    let model = mat4x4<f32>(m0, m1, m2, m3);
+    var world = model * vec4(pos, 1.0);
+
+    // bend the top of the mesh most, with gusts rolling across the field
+    let height = clamp(pos.y / layer.params.x, 0.0, 1.0);
+    let bend = height * height * layer.params.y;
+    let time = frame.eye.w * frame.wind.w;
+    let rolling = dot(world.xz, frame.wind.xy) * 0.02;
+    let gust = 0.6 + 0.4 * sin(time * 0.37 + rolling);
+    let sway = sin(time + params.x + rolling) * 0.3 + gust;
+    world = vec4(world.xyz + vec3(frame.wind.x, 0.0, frame.wind.y) * frame.wind.z * bend * sway, 1.0);
+
+    var out: VSOut;
+    out.pos = camera.view_proj * world;
+    out.normal = normalize((model * vec4(normal, 0.0)).xyz);
+    out.fade = 1.0 - smoothstep(frame.fade.x, frame.fade.y, distance(world.xyz, frame.eye.xyz));
+    out.shade = params.y;
+    out.lod = instanceLod(m3.xyz);
+    return out;
+}
+
+@fragment
+fn psMain(in: VSOut) -> @location(0) vec4<f32> {
+    let threshold = ditherThreshold(in.pos);
+    if (in.fade <= threshold || in.lod > threshold) {
+        discard;
+    }
+    return shade(vec3(in.shade), in.normal);
+}
+
+struct Impostor {
+    // xyz: local center, w: radius
+    bounds: vec4<f32>,
+    // x: frames per side
+    params: vec4<f32>,
+};
+@group(3) @binding(0) var<uniform> impostor: Impostor;
+@group(3) @binding(1) var atlas: texture_2d<f32>;
+@group(3) @binding(2) var atlas_sampler: sampler;
+
+struct ImpostorOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) fade: f32,
+    @location(2) shade: f32,
+    @location(3) lod: f32,
+    @location(4) right: vec3<f32>,
+    @location(5) up: vec3<f32>,
+    @location(6) forward: vec3<f32>,
+};
+
+// a quad facing the camera showing the nearest baked view; no sway
+@vertex
+fn vsImpostor(
+    @builtin(vertex_index) corner: u32,
+    @location(4) m0: vec4<f32>,
+    @location(5) m1: vec4<f32>,
+    @location(6) m2: vec4<f32>,
+    @location(7) m3: vec4<f32>,
+    @location(8) params: vec4<f32>,
+) -> ImpostorOut {
+    let model = mat4x4<f32>(m0, m1, m2, m3);
+    // instances are uniformly scaled
+    let scale = length(m0.xyz);
+    let rotation = mat3x3<f32>(m0.xyz / scale, m1.xyz / scale, m2.xyz / scale);
+    let center = (model * vec4(impostor.bounds.xyz, 1.0)).xyz;
+    let view = transpose(rotation) * normalize(frame.eye.xyz - center);
+
+    let frames = impostor.params.x;
+    let cell = clamp(floor((hemiOctEncode(view) * 0.5 + 0.5) * frames), vec2(0.0), vec2(frames - 1.0));
+    let basis = frameBasis(hemiOctDecode((cell + 0.5) / frames * 2.0 - 1.0));
+    let xy = vec2(f32(corner & 1u), f32(corner >> 1u)) * 2.0 - 1.0;
+    let local = impostor.bounds.xyz + (basis[0] * xy.x + basis[1] * xy.y) * impostor.bounds.w;
+    let world = model * vec4(local, 1.0);
+
+    var out: ImpostorOut;
+    out.pos = camera.view_proj * world;
+    out.uv = (cell + vec2(xy.x, -xy.y) * 0.5 + 0.5) / frames;
+    out.fade = 1.0 - smoothstep(frame.fade.x, frame.fade.y, distance(world.xyz, frame.eye.xyz));
+    out.shade = params.y;
+    out.lod = instanceLod(m3.xyz);
+    out.right = rotation[0];
+    out.up = rotation[1];
+    out.forward = rotation[2];
+    return out;
+}
+
+@fragment
+fn psImpostor(in: ImpostorOut) -> @location(0) vec4<f32> {
+    let texel = textureSample(atlas, atlas_sampler, in.uv);
+    let threshold = ditherThreshold(in.pos);
+    if (texel.a < 0.5 || in.fade <= threshold || in.lod <= threshold) {
+        discard;
+    }
+    let local = texel.rgb * 2.0 - 1.0;
+    let normal = normalize(in.right * local.x + in.up * local.y + in.forward * local.z);
+    return shade(vec3(in.shade), normal);
+}
+"#;
+
+const INSTANCE_ATTRIBUTES: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+    4 => Float32x4,
+    5 => Float32x4,
+    6 => Float32x4,
+    7 => Float32x4,
+    8 => Float32x4
+];
+
+/// Scattering stops here, whatever the density.
+pub const MAX_INSTANCES: usize = 250_000;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ScatterInstance {
+    pub model: [[f32; 4]; 4],
+    /// Wind phase and brightness.
+    pub params: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct FrameUniform {
+    wind: [f32; 4],
+    eye: [f32; 4],
+    fade: [f32; 4],
+    lod: [f32; 4],
+    light_direction: [f32; 4],
+    light_color: [f32; 4],
+    ambient: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LayerUniform {
+    color: [f32; 4],
+    params: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ImpostorUniform {
+    bounds: [f32; 4],
+    params: [f32; 4],
+}
+
+/// Grayscale weights, projected straight down onto the target's bounds.
+pub struct DensityMap {
+    pub width: u32,
+    pub height: u32,
+    /// 0..1, row by row from the -Z edge.
+    pub values: Vec<f32>,
+}
+
+impl DensityMap {
+    /// The red channel, e.g. of a Procgen noise texture read back.
+    pub fn from_capture(capture: &Capture) -> Self {
+        DensityMap {
+            width: capture.width,
+            height: capture.height,
+            values: capture
+                .pixels
+                .chunks_exact(4)
+                .map(|p| p[0] as f32 / 255.0)
+                .collect(),
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let capture = Capture::load_png(path).map_err(|e| e.to_string())?;
+        Ok(Self::from_capture(&capture))
+    }
+
+    /// Nearest texel at `uv`, clamped to the edges.
+    pub fn sample(&self, uv: glam::Vec2) -> f32 {
+        let x = (uv.x * self.width as f32) as i64;
+        let y = (uv.y * self.height as f32) as i64;
+        let x = x.clamp(0, self.width as i64 - 1) as usize;
+        let y = y.clamp(0, self.height as i64 - 1) as usize;
+        self.values[y * self.width as usize + x]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScatterRules {
+    /// Instances per 100 square units of surface.
+    pub density: f32,
+    /// Steepest surface, in degrees from flat, that gets instances.
+    pub max_slope: f32,
+    pub min_scale: f32,
+    pub max_scale: f32,
+    /// 0 stands instances upright, 1 tilts them with the surface.
+    pub align_to_normal: f32,
+    pub seed: u32,
+}
+
+impl Default for ScatterRules {
+    fn default() -> Self {
+        ScatterRules {
+            density: 4.0,
+            max_slope: 35.0,
+            min_scale: 4.0,
+            max_scale: 8.0,
+            align_to_normal: 0.3,
+            seed: 1,
+        }
+    }
+}
+
+/// Deterministic splitmix64, so the same seed scatters the same way.
+struct Rng(u64);
+
+impl Rng {
+    fn next_f32(&mut self) -> f32 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+/// Instances spread over `surface` transformed by `matrix`, following
+/// `rules` and weighted by `density_map`.
+pub fn scatter(
+    surface: &Mesh,
+    matrix: glam::Mat4,
+    rules: &ScatterRules,
+    density_map: Option<&DensityMap>,
+) -> Vec<ScatterInstance> {
+    let (min, max) = surface.bounds();
+    let bounds = Aabb::transformed(min, max, matrix);
+    let extent = (bounds.max - bounds.min).max(glam::Vec3::splat(f32::EPSILON));
+    let min_up = rules.max_slope.to_radians().cos();
+    let mut rng = Rng(rules.seed as u64);
+    let mut instances = vec![];
+    for triangle in surface.indices.chunks_exact(3) {
+        let [a, b, c] =
+            [0, 1, 2].map(|i| matrix.transform_point3(surface.positions[triangle[i] as usize]));
+        let cross = (b - a).cross(c - a);
+        let area = cross.length() * 0.5;
+        if area <= f32::EPSILON {
+            continue;
+        }
+        let normal = cross / (area * 2.0);
+        // either winding may face up
+        let normal = if normal.y < 0.0 { -normal } else { normal };
+        if normal.y < min_up {
+            continue;
+        }
+        let expected = area * rules.density / 100.0;
+        let count = expected as u32 + (rng.next_f32() < expected.fract()) as u32;
+        for _ in 0..count {
+            let (mut u, mut v) = (rng.next_f32(), rng.next_f32());
+            if u + v > 1.0 {
+                (u, v) = (1.0 - u, 1.0 - v);
+            }
+            let position = a + (b - a) * u + (c - a) * v;
+            let keep = rng.next_f32();
+            if let Some(map) = density_map {
+                let uv = (position - bounds.min) / extent;
+                if keep >= map.sample(glam::vec2(uv.x, uv.z)) {
+                    continue;
+                }
+            }
+            let up = glam::Vec3::Y
+                .lerp(normal, rules.align_to_normal)
+                .normalize();
+            let yaw = rng.next_f32() * std::f32::consts::TAU;
+            let rotation =
+                glam::Quat::from_rotation_arc(glam::Vec3::Y, up) * glam::Quat::from_rotation_y(yaw);
+            let scale = rules.min_scale + (rules.max_scale - rules.min_scale) * rng.next_f32();
+            let model = glam::Mat4::from_scale_rotation_translation(
+                glam::Vec3::splat(scale),
+                rotation,
+                position,
+            );
+            instances.push(ScatterInstance {
+                model: model.to_cols_array_2d(),
+                params: [
+                    rng.next_f32() * std::f32::consts::TAU,
+                    0.8 + 0.4 * rng.next_f32(),
+                    0.0,
+                    0.0,
+                ],
+            });
+            if instances.len() == MAX_INSTANCES {
+                log::warn!("Scatter stopped at {MAX_INSTANCES} instances");
+                return instances;
+            }
+        }
+    }
+    instances
+}
+
+struct Chunk {
+    bounds: Aabb,
+    instances: Range<u32>,
+}
+
+/// One mesh scattered with its own rules.
+pub struct ScatterLayer {
+    pub name: String,
+    pub mesh: Arc<Mesh>,
+    pub rules: ScatterRules,
+    pub color: [f32; 4],
+    /// How far the top of the mesh bends in the wind, in mesh units.
+    pub sway: f32,
+    pub visible: bool,
+    instance_count: u32,
+    chunks: Vec<Chunk>,
+    instance_buffer: Option<wgpu::Buffer>,
+    uniform: Option<(wgpu::Buffer, wgpu::BindGroup)>,
+    /// Baked on first use; cleared when the mesh or bake settings change.
+    impostor: Option<(ImpostorAtlas, wgpu::BindGroup)>,
+}
+
+impl ScatterLayer {
+    pub fn new(
+        name: &str,
+        mesh: Arc<Mesh>,
+        rules: ScatterRules,
+        color: [f32; 4],
+        sway: f32,
+    ) -> Self {
+        ScatterLayer {
+            name: name.to_string(),
+            mesh,
+            rules,
+            color,
+            sway,
+            visible: true,
+            instance_count: 0,
+            chunks: vec![],
+            instance_buffer: None,
+            uniform: None,
+            impostor: None,
+        }
+    }
+
+    pub fn instance_count(&self) -> u32 {
+        self.instance_count
+    }
+
+    /// Sorts `instances` into square chunks `chunk_size` wide and uploads
+    /// them.
+    fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        mut instances: Vec<ScatterInstance>,
+        chunk_size: f32,
+    ) {
+        let key = |i: &ScatterInstance| {
+            let p = glam::Vec4::from(i.model[3]).truncate();
+            (
+                (p.x / chunk_size).floor() as i32,
+                (p.z / chunk_size).floor() as i32,
+            )
+        };
+        instances.sort_by_key(key);
+        let (min, max) = self.mesh.bounds();
+        // room for the wind to push the tops sideways
+        let margin = glam::Vec3::splat(self.sway);
+        let mut chunks: HashMap<(i32, i32), Chunk> = HashMap::new();
+        for (index, instance) in instances.iter().enumerate() {
+            let matrix = glam::Mat4::from_cols_array_2d(&instance.model);
+            let bounds = Aabb::transformed(min - margin, max + margin, matrix);
+            let index = index as u32;
+            chunks
+                .entry(key(instance))
+                .and_modify(|c| {
+                    c.bounds =
+                        Aabb::new(c.bounds.min.min(bounds.min), c.bounds.max.max(bounds.max));
+                    c.instances.end = index + 1;
+                })
+                .or_insert(Chunk {
+                    bounds,
+                    instances: index..index + 1,
+                });
+        }
+        self.chunks = chunks.into_values().collect();
+        self.instance_count = instances.len() as u32;
+        self.instance_buffer = (!instances.is_empty()).then(|| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Scatter Instances"),
+                contents: bytemuck::cast_slice(&instances),
+                usage: wgpu::BufferUsages::VERTEX,
+            })
+        });
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Wind {
+    /// Degrees around +Y from +X.
+    pub heading: f32,
+    pub strength: f32,
+    /// Sway cycles per second, in radians.
+    pub frequency: f32,
+}
+
+/// When layers swap to impostors and how those are baked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImpostorLod {
+    pub enabled: bool,
+    /// Instances start crossfading to impostors this far from the camera...
+    pub distance: f32,
+    /// ...and are all impostor this much further out.
+    pub band: f32,
+    /// Views per atlas side, up to `impostor::MAX_FRAMES`.
+    pub frames: u32,
+    /// Pixels per view.
+    pub frame_size: u32,
+}
+
+impl Default for ImpostorLod {
+    fn default() -> Self {
+        ImpostorLod {
+            enabled: true,
+            distance: 250.0,
+            band: 60.0,
+            frames: 8,
+            frame_size: 64,
+        }
+    }
+}
+
+struct Pipeline {
+    pipeline: wgpu::RenderPipeline,
+    impostor_pipeline: wgpu::RenderPipeline,
+    layer_layout: wgpu::BindGroupLayout,
+    impostor_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    camera_bind_group: wgpu::BindGroup,
+    frame_buffer: wgpu::Buffer,
+    frame_bind_group: wgpu::BindGroup,
+}
+
+pub struct Scatter {
+    pub layers: Vec<ScatterLayer>,
+    /// The model scattered over.
+    pub target: Option<EntityId>,
+    pub density_map: Option<DensityMap>,
+    pub wind: Wind,
+    /// Instances dither out between these distances from the camera and
+    /// chunks past the end aren't drawn.
+    pub fade_start: f32,
+    pub fade_end: f32,
+    /// Width of the square chunks culled together.
+    pub chunk_size: f32,
+    pub impostors: ImpostorLod,
+    pipeline: Option<Pipeline>,
+    /// This frame's draws: layer and instance range, as meshes and as
+    /// impostors. Chunks in the crossfade band are in both.
+    visible: Vec<(usize, Range<u32>)>,
+    visible_impostors: Vec<(usize, Range<u32>)>,
+    density_path: String,
+    dirty: bool,
+    status: String,
+}
+
+impl Default for Scatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scatter {
+    pub fn new() -> Self {
+        Scatter {
+            layers: vec![],
+            target: None,
+            density_map: None,
+            wind: Wind {
+                heading: 30.0,
+                strength: 1.0,
+                frequency: 1.5,
+            },
+            fade_start: 900.0,
+            fade_end: 1200.0,
+            chunk_size: 100.0,
+            impostors: ImpostorLod::default(),
+            pipeline: None,
+            visible: vec![],
+            visible_impostors: vec![],
+            density_path: "noise.png".to_string(),
+            dirty: false,
+            status: String::new(),
+        }
+    }
+
+    /// Rescatters every layer over the target.
+    pub fn rebuild(&mut self, device: &wgpu::Device, world: &World) {
+        let Some(model) = self.target.and_then(|id| world.model(id)) else {
+            for layer in &mut self.layers {
+                layer.upload(device, vec![], self.chunk_size);
+            }
+            return;
+        };
+        let start = std::time::Instant::now();
+        let matrix = model.global_matrix();
+        for layer in &mut self.layers {
+            let instances = scatter(&model.mesh, matrix, &layer.rules, self.density_map.as_ref());
+            layer.upload(device, instances, self.chunk_size);
+        }
+        let total: u32 = self.layers.iter().map(|l| l.instance_count).sum();
+        let chunks: usize = self.layers.iter().map(|l| l.chunks.len()).sum();
+        self.status = format!(
+            "Scattered {total} instances in {chunks} chunks over {} ({:.1} ms)",
+            model.name,
+            start.elapsed().as_secs_f32() * 1000.0
+        );
+    }
+
+    fn create_pipeline(state: &State, world: &World) -> Pipeline {
+        let device = &state.device;
+        let uniform_layout = |label| {
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some(label),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            })
+        };
+        let camera_layout = uniform_layout("Scatter Camera");
+        let frame_layout = uniform_layout("Scatter Frame");
+        let layer_layout = uniform_layout("Scatter Layer");
+        let bind_group = |layout, buffer: &wgpu::Buffer| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }],
+            })
+        };
+        let camera_bind_group = bind_group(&camera_layout, world.camera.buffer_ref());
+        let frame_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Scatter Frame"),
+            size: std::mem::size_of::<FrameUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let frame_bind_group = bind_group(&frame_layout, &frame_buffer);
+        let impostor_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Scatter Impostor"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Scatter Impostor"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Scatter"),
+            source: wgpu::ShaderSource::Wgsl(format!("{}{SHADER}", impostor::WGSL).into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Scatter"),
+            bind_group_layouts: &[&camera_layout, &frame_layout, &layer_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Scatter"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vsMain"),
+                buffers: &[
+                    Vertex::layout(),
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<ScatterInstance>() as u64,
+                        step_mode: wgpu::VertexStepMode::Instance,
+                        attributes: &INSTANCE_ATTRIBUTES,
+                    },
+                ],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("psMain"),
+                compilation_options: Default::default(),
+                targets: &[Some(state.surface_config.format.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        let impostor_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Scatter Impostors"),
+                bind_group_layouts: &[
+                    &camera_layout,
+                    &frame_layout,
+                    &layer_layout,
+                    &impostor_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+        let impostor_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Scatter Impostors"),
+            layout: Some(&impostor_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vsImpostor"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<ScatterInstance>() as u64,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &INSTANCE_ATTRIBUTES,
+                }],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("psImpostor"),
+                compilation_options: Default::default(),
+                targets: &[Some(state.surface_config.format.into())],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        Pipeline {
+            pipeline,
+            impostor_pipeline,
+            layer_layout,
+            impostor_layout,
+            sampler,
+            camera_bind_group,
+            frame_buffer,
+            frame_bind_group,
+        }
+    }
+
+    /// Picks this frame's chunks and writes the uniforms.
+    fn cull(&mut self, state: &State, world: &World, seconds: f32) {
+        let Some(pipeline) = &self.pipeline else {
+            return;
+        };
+        let eye = world.camera.eye;
+        let (light_direction, light_color, ambient) = if world.sky.enabled {
+            let sky = &world.sky;
+            (sky.light_direction(), sky.sun_color(), sky.ambient())
+        } else {
+            (
+                glam::vec3(-0.4, -1.0, -0.3).normalize(),
+                glam::Vec3::splat(0.8),
+                glam::Vec3::splat(0.3),
+            )
+        };
+        let heading = self.wind.heading.to_radians();
+        let frame = FrameUniform {
+            wind: [
+                heading.cos(),
+                heading.sin(),
+                self.wind.strength,
+                self.wind.frequency,
+            ],
+            eye: eye.extend(seconds).to_array(),
+            fade: [
+                self.fade_start,
+                self.fade_end.max(self.fade_start + 1.0),
+                0.0,
+                0.0,
+            ],
+            lod: if self.impostors.enabled {
+                let start = self.impostors.distance;
+                [start, start + self.impostors.band.max(1.0), 0.0, 0.0]
+            } else {
+                [f32::MAX * 0.5, f32::MAX, 0.0, 0.0]
+            },
+            light_direction: light_direction.extend(0.0).to_array(),
+            light_color: light_color.extend(1.0).to_array(),
+            ambient: ambient.extend(1.0).to_array(),
+        };
+        state
+            .queue
+            .write_buffer(&pipeline.frame_buffer, 0, bytemuck::bytes_of(&frame));
+
+        let frustum = Frustum::from_view_proj(world.camera.view_proj());
+        let lod = self.impostors;
+        let lod_end = lod.distance + lod.band.max(1.0);
+        self.visible.clear();
+        self.visible_impostors.clear();
+        for (index, layer) in self.layers.iter_mut().enumerate() {
+            if !layer.visible || layer.instance_buffer.is_none() {
+                continue;
+            }
+            let mesh_height = layer.mesh.bounds().1.y.max(f32::EPSILON);
+            let uniform = LayerUniform {
+                color: layer.color,
+                params: [mesh_height, layer.sway, 0.0, 0.0],
+            };
+            let (buffer, _) = layer.uniform.get_or_insert_with(|| {
+                let buffer = state.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Scatter Layer"),
+                    size: std::mem::size_of::<LayerUniform>() as u64,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                let bind_group = state.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Scatter Layer"),
+                    layout: &pipeline.layer_layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    }],
+                });
+                (buffer, bind_group)
+            });
+            state
+                .queue
+                .write_buffer(buffer, 0, bytemuck::bytes_of(&uniform));
+            if lod.enabled && layer.impostor.is_none() {
+                let atlas = ImpostorAtlas::bake(state, &layer.mesh, lod.frames, lod.frame_size);
+                let uniform = ImpostorUniform {
+                    bounds: atlas.center.extend(atlas.radius).to_array(),
+                    params: [atlas.frames as f32, 0.0, 0.0, 0.0],
+                };
+                let buffer = state
+                    .device
+                    .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some("Scatter Impostor"),
+                        contents: bytemuck::bytes_of(&uniform),
+                        usage: wgpu::BufferUsages::UNIFORM,
+                    });
+                let bind_group = state.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Scatter Impostor"),
+                    layout: &pipeline.impostor_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::TextureView(&atlas.view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: wgpu::BindingResource::Sampler(&pipeline.sampler),
+                        },
+                    ],
+                });
+                layer.impostor = Some((atlas, bind_group));
+            }
+            for chunk in &layer.chunks {
+                let bounds = chunk.bounds;
+                let nearest = eye.clamp(bounds.min, bounds.max).distance(eye);
+                if nearest >= self.fade_end || !bounds.intersects_frustum(&frustum) {
+                    continue;
+                }
+                let farthest = (eye - bounds.min)
+                    .abs()
+                    .max((bounds.max - eye).abs())
+                    .length();
+                if !lod.enabled || nearest < lod_end {
+                    self.visible.push((index, chunk.instances.clone()));
+                }
+                if lod.enabled && farthest > lod.distance {
+                    self.visible_impostors
+                        .push((index, chunk.instances.clone()));
+                }
+            }
+        }
+    }
+
+    /// Spawns a hilly square `size` wide and makes it the target.
+    fn spawn_terrain(&mut self, state: &State, world: &mut World, size: f32) {
+        let mesh = Arc::new(terrain_mesh(&state.device, size, 96, 40.0));
+        let material = world.default_material();
+        let mut transform = Transform::default();
+        transform.translation.y = world.camera.center.y - 60.0;
+        let id = world.spawn(state, "Terrain", mesh, material, transform);
+        world.update_transforms();
+        self.target = Some(id);
+        self.dirty = true;
+    }
+}
+
+/// A few tapered blades crossing at the root, one unit tall.
+pub fn grass_mesh(device: &wgpu::Device) -> Mesh {
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    for blade in 0..3 {
+        let angle = blade as f32 * std::f32::consts::TAU / 3.0;
+        let (sin, cos) = angle.sin_cos();
+        let side = glam::vec3(cos, 0.0, sin) * 0.08;
+        let normal = glam::vec3(-sin, 0.0, cos);
+        let lean = normal * 0.15;
+        let base = vertices.len() as u32;
+        let vertex = |pos: glam::Vec3, v: f32| Vertex {
+            pos: pos.into(),
+            normal: normal.into(),
+            uv: [0.5, 1.0 - v],
+            uv1: [0.5, 1.0 - v],
+        };
+        vertices.extend([
+            vertex(-side, 0.0),
+            vertex(side, 0.0),
+            vertex(-side * 0.6 + glam::Vec3::Y * 0.5 + lean * 0.3, 0.5),
+            vertex(side * 0.6 + glam::Vec3::Y * 0.5 + lean * 0.3, 0.5),
+            vertex(glam::Vec3::Y + lean, 1.0),
+        ]);
+        indices.extend([0, 1, 3, 0, 3, 2, 2, 3, 4].map(|i| base + i));
+    }
+    Mesh::new(device, "Grass", &vertices, &indices)
+}
+
+/// A lumpy, flat-shaded octahedron sitting on y = 0, about one unit wide.
+pub fn rock_mesh(device: &wgpu::Device) -> Mesh {
+    let corners = [
+        glam::vec3(0.55, 0.25, 0.0),
+        glam::vec3(0.0, 0.3, 0.45),
+        glam::vec3(-0.5, 0.2, 0.05),
+        glam::vec3(0.05, 0.25, -0.5),
+        glam::vec3(0.1, 0.7, 0.05),
+        glam::vec3(0.0, -0.1, 0.0),
+    ];
+    let faces = [
+        [0, 4, 1],
+        [1, 4, 2],
+        [2, 4, 3],
+        [3, 4, 0],
+        [1, 5, 0],
+        [2, 5, 1],
+        [3, 5, 2],
+        [0, 5, 3],
+    ];
+    let mut vertices = vec![];
+    for face in faces {
+        let [a, b, c] = face.map(|i| corners[i]);
+        let normal = (b - a).cross(c - a).normalize();
+        for p in [a, b, c] {
+            vertices.push(Vertex {
+                pos: p.into(),
+                normal: normal.into(),
+                uv: [0.0; 2],
+                uv1: [0.0; 2],
+            });
+        }
+    }
+    let indices: Vec<u32> = (0..vertices.len() as u32).collect();
+    Mesh::new(device, "Rock", &vertices, &indices)
+}
+
+/// A `size` wide grid of `cells`² quads with rolling hills up to `height`.
+pub fn terrain_mesh(device: &wgpu::Device, size: f32, cells: u32, height: f32) -> Mesh {
+    let elevation = |x: f32, z: f32| {
+        let (x, z) = (x / size * 6.0, z / size * 6.0);
+        height
+            * (0.5 * (x.sin() * z.cos())
+                + 0.3 * (x * 2.3 + 1.0).sin() * (z * 1.7).sin()
+                + 0.2 * (z * 3.1 - x).cos())
+    };
+    let step = size / cells as f32;
+    let mut vertices = vec![];
+    for j in 0..=cells {
+        for i in 0..=cells {
+            let x = i as f32 * step - size / 2.0;
+            let z = j as f32 * step - size / 2.0;
+            let dx = elevation(x + step, z) - elevation(x - step, z);
+            let dz = elevation(x, z + step) - elevation(x, z - step);
+            let normal = glam::vec3(-dx, 2.0 * step, -dz).normalize();
+            let uv = [i as f32 / cells as f32, j as f32 / cells as f32];
+            vertices.push(Vertex {
+                pos: [x, elevation(x, z), z],
+                normal: normal.into(),
+                uv,
+                uv1: uv,
+            });
+        }
+    }
+    let row = cells + 1;
+    let mut indices = vec![];
+    for j in 0..cells {
+        for i in 0..cells {
+            let a = j * row + i;
+            indices.extend([a, a + row, a + 1, a + 1, a + row, a + row + 1]);
+        }
+    }
+    let mut mesh = Mesh::new(device, "Terrain", &vertices, &indices);
+    mesh.base_color = [0.35, 0.45, 0.25, 1.0];
+    mesh
+}
+
+impl Plugin for Scatter {
+    fn name(&self) -> &str {
+        "Scatter"
+    }
+
+    fn build(&mut self, ctx: &mut PluginContext) {
+        let device = &ctx.state.device;
+        self.pipeline = Some(Self::create_pipeline(ctx.state, ctx.world));
+        self.layers = vec![
+            ScatterLayer::new(
+                "Grass",
+                Arc::new(grass_mesh(device)),
+                ScatterRules::default(),
+                [0.35, 0.6, 0.2, 1.0],
+                0.25,
+            ),
+            ScatterLayer::new(
+                "Rocks",
+                Arc::new(rock_mesh(device)),
+                ScatterRules {
+                    density: 0.05,
+                    max_slope: 60.0,
+                    min_scale: 4.0,
+                    max_scale: 14.0,
+                    align_to_normal: 1.0,
+                    seed: 7,
+                },
+                [0.5, 0.48, 0.45, 1.0],
+                0.0,
+            ),
+        ];
+    }
+
+    fn update(&mut self, ctx: &mut PluginContext) {
+        if self.target.is_some_and(|id| ctx.world.model(id).is_none()) {
+            self.target = None;
+            self.dirty = true;
+        }
+        if std::mem::take(&mut self.dirty) {
+            self.rebuild(&ctx.state.device, ctx.world);
+        }
+        self.cull(ctx.state, ctx.world, ctx.time.elapsed_seconds);
+        if !self.layers.iter().all(|l| l.instance_count == 0) {
+            let count = |draws: &[(usize, Range<u32>)]| -> u32 {
+                draws.iter().map(|(_, r)| r.end - r.start).sum()
+            };
+            let watch = &mut ctx.world.watch;
+            watch.record("scatter visible instances", count(&self.visible) as f32);
+            watch.record("scatter impostors", count(&self.visible_impostors) as f32);
+        }
+    }
+
+    fn render(&self, _world: &World, renderpass: &mut wgpu::RenderPass) {
+        let Some(pipeline) = &self.pipeline else {
+            return;
+        };
+        if self.visible.is_empty() && self.visible_impostors.is_empty() {
+            return;
+        }
+        renderpass.set_pipeline(&pipeline.pipeline);
+        renderpass.set_bind_group(0, &pipeline.camera_bind_group, &[]);
+        renderpass.set_bind_group(1, &pipeline.frame_bind_group, &[]);
+        let mut bound = None;
+        for (index, range) in &self.visible {
+            let layer = &self.layers[*index];
+            let (Some(instances), Some((_, bind_group))) = (&layer.instance_buffer, &layer.uniform)
+            else {
+                continue;
+            };
+            if bound != Some(*index) {
+                renderpass.set_bind_group(2, bind_group, &[]);
+                renderpass.set_vertex_buffer(0, layer.mesh.vertex_buffer.slice(..));
+                renderpass.set_vertex_buffer(1, instances.slice(..));
+                renderpass
+                    .set_index_buffer(layer.mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                bound = Some(*index);
+            }
+            renderpass.draw_indexed(0..layer.mesh.index_count, 0, range.clone());
+        }
+
+        if self.visible_impostors.is_empty() {
+            return;
+        }
+        renderpass.set_pipeline(&pipeline.impostor_pipeline);
+        let mut bound = None;
+        for (index, range) in &self.visible_impostors {
+            let layer = &self.layers[*index];
+            let (Some(instances), Some((_, bind_group)), Some((_, impostor))) =
+                (&layer.instance_buffer, &layer.uniform, &layer.impostor)
+            else {
+                continue;
+            };
+            if bound != Some(*index) {
+                renderpass.set_bind_group(2, bind_group, &[]);
+                renderpass.set_bind_group(3, impostor, &[]);
+                renderpass.set_vertex_buffer(0, instances.slice(..));
+                bound = Some(*index);
+            }
+            renderpass.draw(0..4, range.clone());
+        }
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, state: &State, world: &mut World) {
+        egui::Window::new("Scatter")
+            .default_open(false)
+            .vscroll(true)
+            .show(ctx, |ui| {
+                let target = self
+                    .target
+                    .and_then(|id| world.model(id))
+                    .map_or("none", |m| &m.name);
+                ui.label(format!("Target: {target}"));
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(
+                            world.selection.primary().is_some(),
+                            egui::Button::new("Use selected"),
+                        )
+                        .clicked()
+                    {
+                        self.target = world.selection.primary();
+                        self.dirty = true;
+                    }
+                    if ui.button("Spawn terrain").clicked() {
+                        self.spawn_terrain(state, world, 1600.0);
+                    }
+                    if ui.button("Rescatter").clicked() {
+                        self.dirty = true;
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Density map");
+                    ui.text_edit_singleline(&mut self.density_path);
+                    if ui.button("Load").clicked() {
+                        match DensityMap::load(&self.density_path) {
+                            Ok(map) => {
+                                self.density_map = Some(map);
+                                self.dirty = true;
+                            }
+                            Err(e) => {
+                                self.status = format!("Failed to load {}: {e}", self.density_path)
+                            }
+                        }
+                    }
+                    if ui
+                        .add_enabled(self.density_map.is_some(), egui::Button::new("Clear"))
+                        .clicked()
+                    {
+                        self.density_map = None;
+                        self.dirty = true;
+                    }
+                });
+
+                ui.separator();
+                ui.add(egui::Slider::new(&mut self.wind.heading, 0.0..=360.0).text("Wind heading"));
+                ui.add(egui::Slider::new(&mut self.wind.strength, 0.0..=4.0).text("Wind strength"));
+                ui.add(egui::Slider::new(&mut self.wind.frequency, 0.1..=6.0).text("Sway speed"));
+                ui.add(egui::Slider::new(&mut self.fade_start, 50.0..=3000.0).text("Fade start"));
+                ui.add(egui::Slider::new(&mut self.fade_end, 50.0..=3000.0).text("Fade end"));
+                let chunk = ui
+                    .add(egui::Slider::new(&mut self.chunk_size, 25.0..=400.0).text("Chunk size"));
+                self.dirty |= chunk.drag_stopped();
+
+                ui.separator();
+                let lod = &mut self.impostors;
+                let bake = (lod.frames, lod.frame_size);
+                ui.checkbox(&mut lod.enabled, "Impostors");
+                ui.add_enabled_ui(lod.enabled, |ui| {
+                    ui.add(
+                        egui::Slider::new(&mut lod.distance, 10.0..=3000.0)
+                            .text("Impostor distance"),
+                    );
+                    ui.add(egui::Slider::new(&mut lod.band, 1.0..=500.0).text("Crossfade band"));
+                    ui.add(
+                        egui::Slider::new(&mut lod.frames, 2..=impostor::MAX_FRAMES)
+                            .text("Views per side"),
+                    );
+                    egui::ComboBox::from_label("View size")
+                        .selected_text(format!("{} px", lod.frame_size))
+                        .show_ui(ui, |ui| {
+                            for size in [32, 64, 128, 256] {
+                                ui.selectable_value(
+                                    &mut lod.frame_size,
+                                    size,
+                                    format!("{size} px"),
+                                );
+                            }
+                        });
+                    if ui.button("Rebake").clicked() || bake != (lod.frames, lod.frame_size) {
+                        for layer in &mut self.layers {
+                            layer.impostor = None;
+                        }
+                    }
+                });
+
+                for (i, layer) in self.layers.iter_mut().enumerate() {
+                    ui.push_id(i, |ui| {
+                        ui.separator();
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut layer.visible, &layer.name);
+                            ui.color_edit_button_rgba_unmultiplied(&mut layer.color);
+                            ui.label(format!("{} instances", layer.instance_count));
+                        });
+                        let before = layer.rules;
+                        let rules = &mut layer.rules;
+                        ui.add(
+                            egui::Slider::new(&mut rules.density, 0.01..=40.0)
+                                .logarithmic(true)
+                                .text("Per 100 units²"),
+                        );
+                        ui.add(
+                            egui::Slider::new(&mut rules.max_slope, 0.0..=90.0).text("Max slope°"),
+                        );
+                        ui.add(
+                            egui::Slider::new(&mut rules.min_scale, 0.1..=40.0).text("Min scale"),
+                        );
+                        ui.add(
+                            egui::Slider::new(&mut rules.max_scale, 0.1..=40.0).text("Max scale"),
+                        );
+                        ui.add(
+                            egui::Slider::new(&mut rules.align_to_normal, 0.0..=1.0)
+                                .text("Align to surface"),
+                        );
+                        ui.add(egui::DragValue::new(&mut rules.seed).prefix("Seed: "));
+                        rules.max_scale = rules.max_scale.max(rules.min_scale);
+                        ui.add(egui::Slider::new(&mut layer.sway, 0.0..=1.0).text("Sway"));
+                        let meshes: Vec<_> = world
+                            .meshes
+                            .iter()
+                            .filter(|m| m.encoding == VertexEncoding::Full)
+                            .collect();
+                        egui::ComboBox::from_label("Mesh")
+                            .selected_text(&layer.mesh.name)
+                            .show_ui(ui, |ui| {
+                                for mesh in meshes {
+                                    if ui
+                                        .selectable_label(
+                                            Arc::ptr_eq(mesh, &layer.mesh),
+                                            &mesh.name,
+                                        )
+                                        .clicked()
+                                    {
+                                        layer.mesh = mesh.clone();
+                                        layer.impostor = None;
+                                        self.dirty = true;
+                                    }
+                                }
+                            });
+                        // rescatter once a drag ends rather than every step
+                        if layer.rules != before && !ui.ctx().is_using_pointer() {
+                            self.dirty = true;
+                        }
+                    });
+                }
+
+                ui.separator();
+                let count = |draws: &[(usize, Range<u32>)]| -> u32 {
+                    draws.iter().map(|(_, r)| r.end - r.start).sum()
+                };
+                let chunks: usize = self.layers.iter().map(|l| l.chunks.len()).sum();
+                ui.label(format!(
+                    "{} of {chunks} chunks drawn as meshes, {} instances",
+                    self.visible.len(),
+                    count(&self.visible)
+                ));
+                ui.label(format!(
+                    "{} chunks drawn as impostors, {} instances",
+                    self.visible_impostors.len(),
+                    count(&self.visible_impostors)
+                ));
+                if !self.status.is_empty() {
+                    ui.label(&self.status);
+                }
+            });
+    }
+}
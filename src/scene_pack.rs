@@ -0,0 +1,406 @@
+//! `.meshpack`: a file's meshes with the import settings already applied,
+//! laid out so loading is reading. Written by `sandbox pack <scene.gltf>`.
+//!
+//! After an 8-byte magic, the version and the primitive count (`u32`s),
+//! each primitive is its info as length-prefixed JSON padded to 4 bytes,
+//! then its vertex, index, tangent and joint counts (`u64`s) and those
+//! arrays in the in-memory layout, little-endian, then its base color
+//! texture's width and height (`u32`s, zero for none) and RGBA8 pixels.
+//! Vertex and index data is
+//! read from the file straight into buffers mapped at creation, with no
+//! copy in between.
+
+use crate::import::ImportSettings;
+use crate::mesh::{self, Mesh, MeshImage, Primitive, PrimitiveInfo, Vertex, VertexEncoding};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::sync::Arc;
+
+pub const EXTENSION: &str = "meshpack";
+const MAGIC: &[u8; 8] = b"SBXPACK\0";
+const VERSION: u32 = 2;
+
+/// Imports `source` with `settings` and writes its meshes to `dest`.
+/// Returns how many were written.
+pub fn pack(source: &str, dest: &str, settings: &ImportSettings) -> Result<usize, String> {
+    let primitives = mesh::import_gltf(source, settings);
+    if primitives.is_empty() {
+        return Err(format!("{source} has no meshes"));
+    }
+    write_pack(dest, &primitives)?;
+    Ok(primitives.len())
+}
+
+fn write_pack(dest: &str, primitives: &[Primitive]) -> Result<(), String> {
+    let file = std::fs::File::create(dest).map_err(|e| e.to_string())?;
+    let mut out = BufWriter::new(file);
+    let mut write = |bytes: &[u8]| out.write_all(bytes).map_err(|e| e.to_string());
+    write(MAGIC)?;
+    write(&VERSION.to_le_bytes())?;
+    write(&(primitives.len() as u32).to_le_bytes())?;
+    for prim in primitives {
+        let info = serde_json::to_vec(&prim.info).map_err(|e| e.to_string())?;
+        write(&(info.len() as u32).to_le_bytes())?;
+        write(&info)?;
+        write(&[0; 3][..info.len().next_multiple_of(4) - info.len()])?;
+        let tangents: Vec<[f32; 4]> = prim.tangents.iter().map(|t| t.to_array()).collect();
+        let joints: Vec<[f32; 16]> = prim
+            .joint_matrices
+            .iter()
+            .map(|m| m.to_cols_array())
+            .collect();
+        for count in [
+            prim.vertices.len(),
+            prim.indices.len(),
+            tangents.len(),
+            joints.len(),
+        ] {
+            write(&(count as u64).to_le_bytes())?;
+        }
+        write(bytemuck::cast_slice(&prim.vertices))?;
+        write(bytemuck::cast_slice(&prim.indices))?;
+        write(bytemuck::cast_slice(&tangents))?;
+        write(bytemuck::cast_slice(&joints))?;
+        let (size, pixels) = match &prim.base_color_texture {
+            Some(image) => ([image.width, image.height], &image.pixels[..]),
+            None => ([0; 2], &[][..]),
+        };
+        write(bytemuck::cast_slice(&size))?;
+        write(pixels)?;
+    }
+    out.flush().map_err(|e| e.to_string())
+}
+
+/// A `MeshLoader` for `.meshpack` files. Only `vertex_encoding` applies;
+/// the other settings were applied when the file was packed.
+pub fn load_pack(device: &wgpu::Device, path: &str, settings: &ImportSettings) -> Vec<Arc<Mesh>> {
+    match read_pack(device, path, settings.vertex_encoding) {
+        Ok(meshes) => meshes,
+        Err(e) => {
+            log::warn!("Failed to load {path}: {e}");
+            vec![]
+        }
+    }
+}
+
+/// Reads a pack, keeping track of how much of it is left so sizes read
+/// from it can be checked before anything is allocated for them.
+struct PackReader {
+    reader: BufReader<Box<dyn Read + Send>>,
+    remaining: u64,
+}
+
+impl PackReader {
+    fn read(&mut self, bytes: &mut [u8]) -> Result<(), String> {
+        self.reader.read_exact(bytes).map_err(|e| e.to_string())?;
+        self.remaining = self.remaining.saturating_sub(bytes.len() as u64);
+        Ok(())
+    }
+
+    /// The byte sizes of sections of `count` items of `size` bytes each,
+    /// read one after the other; an error if they don't fit in what's left.
+    fn sections<const N: usize>(&self, sections: [(u64, usize); N]) -> Result<[usize; N], String> {
+        let past_the_end = || "truncated or corrupt: sizes run past the end".to_string();
+        let mut sizes = [0; N];
+        let mut total = 0u64;
+        for (bytes, (count, size)) in sizes.iter_mut().zip(sections) {
+            let section = count.checked_mul(size as u64).ok_or_else(past_the_end)?;
+            total = total
+                .checked_add(section)
+                .filter(|&total| total <= self.remaining)
+                .ok_or_else(past_the_end)?;
+            *bytes = usize::try_from(section).map_err(|e| e.to_string())?;
+        }
+        Ok(sizes)
+    }
+}
+
+fn read_pack(
+    device: &wgpu::Device,
+    path: &str,
+    encoding: VertexEncoding,
+) -> Result<Vec<Arc<Mesh>>, String> {
+    let remaining = crate::vfs::len(path).map_err(|e| e.to_string())?;
+    let file = crate::vfs::open(path).map_err(|e| e.to_string())?;
+    let mut pack = PackReader {
+        reader: BufReader::new(file),
+        remaining,
+    };
+
+    let mut magic = [0; 8];
+    pack.read(&mut magic)?;
+    if &magic != MAGIC {
+        return Err("not a meshpack file".to_string());
+    }
+    let mut word = [0; 4];
+    pack.read(&mut word)?;
+    let version = u32::from_le_bytes(word);
+    if version != VERSION {
+        return Err(format!(
+            "version {version}, expected {VERSION}; pack it again"
+        ));
+    }
+    pack.read(&mut word)?;
+    let count = u32::from_le_bytes(word);
+
+    let mut meshes = vec![];
+    for _ in 0..count {
+        pack.read(&mut word)?;
+        let length = u32::from_le_bytes(word) as usize;
+        let [padded] = pack.sections([(length.next_multiple_of(4) as u64, 1)])?;
+        let mut info = vec![0; padded];
+        pack.read(&mut info)?;
+        let info: PrimitiveInfo =
+            serde_json::from_slice(&info[..length]).map_err(|e| e.to_string())?;
+        let mut counts = [0; 4];
+        for count in &mut counts {
+            let mut bytes = [0; 8];
+            pack.read(&mut bytes)?;
+            *count = u64::from_le_bytes(bytes);
+        }
+        let [vertex_count, index_count, tangent_count, joint_count] = counts;
+        let [vertex_bytes, index_bytes, tangent_bytes, joint_bytes] = pack.sections([
+            (vertex_count, std::mem::size_of::<Vertex>()),
+            (index_count, std::mem::size_of::<u32>()),
+            (tangent_count, 16),
+            (joint_count, 64),
+        ])?;
+
+        let mut mesh = match encoding {
+            VertexEncoding::Full => {
+                let vertex_buffer = mapped_buffer(
+                    device,
+                    "Vertex Buffer",
+                    vertex_bytes,
+                    wgpu::BufferUsages::VERTEX,
+                );
+                let index_buffer = mapped_buffer(
+                    device,
+                    "Index Buffer",
+                    index_bytes,
+                    wgpu::BufferUsages::INDEX,
+                );
+                {
+                    let mut vertices = vertex_buffer.slice(..).get_mapped_range_mut();
+                    pack.read(&mut vertices[..vertex_bytes])?;
+                    let mut indices = index_buffer.slice(..).get_mapped_range_mut();
+                    pack.read(&mut indices[..index_bytes])?;
+                }
+                let mesh = {
+                    let vertices = vertex_buffer.slice(..).get_mapped_range();
+                    let indices = index_buffer.slice(..).get_mapped_range();
+                    Mesh::from_buffers(
+                        &info.name,
+                        vertex_buffer.clone(),
+                        encoding,
+                        index_buffer.clone(),
+                        bytemuck::cast_slice(&vertices[..vertex_bytes]),
+                        bytemuck::cast_slice(&indices[..index_bytes]),
+                    )
+                };
+                vertex_buffer.unmap();
+                index_buffer.unmap();
+                mesh
+            }
+            // re-encoded on the CPU, so there's nothing to read straight in
+            VertexEncoding::Packed => {
+                let mut vertices = vec![
+                    bytemuck::Zeroable::zeroed();
+                    vertex_bytes / std::mem::size_of::<Vertex>()
+                ];
+                pack.read(bytemuck::cast_slice_mut(&mut vertices))?;
+                let mut indices = vec![0u32; index_bytes / std::mem::size_of::<u32>()];
+                pack.read(bytemuck::cast_slice_mut(&mut indices))?;
+                Mesh::with_encoding(device, &info.name, &vertices, &indices, encoding)
+            }
+        };
+
+        let mut tangents = vec![0; tangent_bytes];
+        pack.read(&mut tangents)?;
+        let mut joints = vec![0; joint_bytes];
+        pack.read(&mut joints)?;
+        info.apply(
+            &mut mesh,
+            mesh::decode_tangents(&tangents),
+            mesh::decode_matrices(&joints),
+        );
+        let mut size = [0; 8];
+        pack.read(&mut size)?;
+        let [width, height]: [u32; 2] = bytemuck::pod_read_unaligned(&size);
+        if width > 0 && height > 0 {
+            let [pixel_bytes] = pack.sections([(width as u64 * height as u64, 4)])?;
+            let mut pixels = vec![0; pixel_bytes];
+            pack.read(&mut pixels)?;
+            mesh.base_color_texture = Some(Arc::new(MeshImage {
+                width,
+                height,
+                pixels,
+            }));
+        }
+        meshes.push(Arc::new(mesh));
+    }
+    Ok(meshes)
+}
+
+/// A buffer mapped for writing, at least `size` bytes.
+fn mapped_buffer(
+    device: &wgpu::Device,
+    label: &str,
+    size: usize,
+    usage: wgpu::BufferUsages,
+) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        // mapped sizes must be a multiple of 4; empty meshes still get one
+        size: (size as u64)
+            .next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT)
+            .max(4),
+        usage: usage | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: true,
+    })
+}
+
+/// `sandbox pack <scene.gltf> [-o <out.meshpack>]`, with the import
+/// settings the workspace has for the file. Returns the exit code.
+pub fn run_cli(args: &[String]) -> i32 {
+    let Some(source) = args.first() else {
+        eprintln!("usage: sandbox pack <scene.gltf> [-o <out.{EXTENSION}>]");
+        return 2;
+    };
+    let dest = match args.iter().position(|a| a == "-o") {
+        Some(i) => match args.get(i + 1) {
+            Some(dest) => dest.clone(),
+            None => {
+                eprintln!("-o needs a path");
+                return 2;
+            }
+        },
+        None => std::path::Path::new(source)
+            .with_extension(EXTENSION)
+            .display()
+            .to_string(),
+    };
+    let workspace = crate::workspace::Workspace::load();
+    let settings = workspace.imports.get(source).copied().unwrap_or_default();
+    match pack(source, &dest, &settings) {
+        Ok(count) => {
+            println!("Packed {count} mesh(es) from {source} into {dest}");
+            0
+        }
+        Err(e) => {
+            eprintln!("Failed to pack {source}: {e}");
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle() -> Primitive {
+        let vertex = |x: f32, y: f32| Vertex {
+            pos: [x, y, 0.0],
+            normal: [0.0, 0.0, 1.0],
+            uv: [x, y],
+            uv1: [0.0; 2],
+        };
+        Primitive {
+            info: PrimitiveInfo {
+                name: "Triangle".to_string(),
+                attributes: vec![],
+                joint_names: vec![],
+                bones: vec![],
+                double_sided: false,
+                base_color: [1.0; 4],
+                alpha_cutoff: Some(0.5),
+            },
+            vertices: vec![vertex(0.0, 0.0), vertex(1.0, 0.0), vertex(0.0, 1.0)],
+            indices: vec![0, 1, 2],
+            tangents: vec![glam::Vec4::X; 3],
+            joint_matrices: vec![],
+            base_color_texture: Some(Arc::new(MeshImage {
+                width: 2,
+                height: 1,
+                pixels: vec![255, 255, 255, 0, 255, 255, 255, 255],
+            })),
+        }
+    }
+
+    /// `bytes` as a file that's removed when the test ends.
+    struct TempPack(std::path::PathBuf);
+
+    impl TempPack {
+        fn new(name: &str, bytes: &[u8]) -> Self {
+            let path = std::env::temp_dir().join(format!("scene_pack_{name}.{EXTENSION}"));
+            std::fs::write(&path, bytes).unwrap();
+            TempPack(path)
+        }
+
+        fn read(&self, encoding: VertexEncoding) -> Result<Vec<Arc<Mesh>>, String> {
+            let (device, _) = wgpu::Device::noop(&Default::default());
+            read_pack(&device, &self.0.to_string_lossy(), encoding)
+        }
+    }
+
+    impl Drop for TempPack {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn packed() -> Vec<u8> {
+        let pack = TempPack::new("source", &[]);
+        write_pack(&pack.0.to_string_lossy(), &[triangle()]).unwrap();
+        std::fs::read(&pack.0).unwrap()
+    }
+
+    #[test]
+    fn packs_round_trip() {
+        let pack = TempPack::new("round_trip", &packed());
+        let source = triangle();
+        for encoding in [VertexEncoding::Full, VertexEncoding::Packed] {
+            let meshes = pack.read(encoding).unwrap();
+            assert_eq!(meshes.len(), 1);
+            let mesh = &meshes[0];
+            assert_eq!(mesh.name, "Triangle");
+            assert_eq!(mesh.indices, source.indices);
+            let positions: Vec<_> = source.vertices.iter().map(|v| v.pos.into()).collect();
+            assert_eq!(mesh.positions, positions);
+            assert_eq!(mesh.tangents, source.tangents);
+            assert_eq!(mesh.alpha_cutoff, Some(0.5));
+            let texture = mesh.base_color_texture.as_ref().unwrap();
+            assert_eq!((texture.width, texture.height), (2, 1));
+            assert_eq!(
+                texture.pixels,
+                source.base_color_texture.as_ref().unwrap().pixels
+            );
+        }
+    }
+
+    #[test]
+    fn truncated_packs_are_errors() {
+        let bytes = packed();
+        assert!(TempPack::new("whole", &bytes)
+            .read(VertexEncoding::Full)
+            .is_ok());
+        for length in [0, 12, 20, bytes.len() / 2, bytes.len() - 1] {
+            let pack = TempPack::new(&format!("truncated_{length}"), &bytes[..length]);
+            for encoding in [VertexEncoding::Full, VertexEncoding::Packed] {
+                assert!(pack.read(encoding).is_err(), "{length} bytes");
+            }
+        }
+    }
+
+    #[test]
+    fn huge_counts_are_errors() {
+        let mut bytes = packed();
+        // the vertex count follows the header and the padded info
+        let info = u32::from_le_bytes(bytes[16..20].try_into().unwrap()) as usize;
+        let at = 20 + info.next_multiple_of(4);
+        for count in [u64::MAX, u64::MAX / 64, 1 << 40] {
+            bytes[at..at + 8].copy_from_slice(&count.to_le_bytes());
+            let pack = TempPack::new("huge", &bytes);
+            assert!(pack.read(VertexEncoding::Full).is_err(), "{count}");
+        }
+    }
+}
@@ -0,0 +1,273 @@
+//! The differences between the scene as its files loaded it and as it is
+//! now, saved as a TOML patch that can be applied again after a reload.
+//! Models are matched by the file and mesh they came from, so a patch
+//! survives restarts; models with generated meshes aren't recorded.
+
+use crate::app::State;
+use crate::model::Model;
+use crate::transform::Transform;
+use crate::world::World;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A loaded model: its mesh's file and index there, and which of the
+/// models using that mesh it is, in spawn order.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ModelKey {
+    pub path: String,
+    pub mesh: usize,
+    pub instance: usize,
+}
+
+/// Settings that differ from the loaded defaults; `None` where unchanged.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelChanges {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub translation: Option<[f32; 3]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<[f32; 4]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scale: Option<[f32; 3]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_color: Option<[f32; 4]>,
+    /// 0 turns the alpha test off.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alpha_cutoff: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visible: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<ModelKey>,
+    /// Index into `World::materials`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub material: Option<usize>,
+}
+
+impl ModelChanges {
+    pub fn is_empty(&self) -> bool {
+        *self == ModelChanges::default()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangedModel {
+    #[serde(flatten)]
+    pub key: ModelKey,
+    #[serde(flatten)]
+    pub changes: ModelChanges,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScenePatch {
+    /// The files the scene was loaded from, in order.
+    pub files: Vec<String>,
+    /// Loaded models that were edited.
+    pub changed: Vec<ChangedModel>,
+    /// Loaded models that were despawned.
+    pub removed: Vec<ModelKey>,
+    /// Models spawned since, with every setting that isn't a default.
+    pub added: Vec<ChangedModel>,
+}
+
+/// Each model's key, for models whose mesh came from a file.
+fn model_keys(world: &World) -> Vec<Option<ModelKey>> {
+    let mut counts: HashMap<(String, usize), usize> = HashMap::new();
+    world
+        .models
+        .iter()
+        .map(|model| {
+            let source = model.mesh.source.as_ref()?;
+            let count = counts
+                .entry((source.path.clone(), source.index))
+                .or_default();
+            *count += 1;
+            Some(ModelKey {
+                path: source.path.clone(),
+                mesh: source.index,
+                instance: *count - 1,
+            })
+        })
+        .collect()
+}
+
+/// Every model the loaded files spawned.
+fn loaded_keys(world: &World) -> Vec<ModelKey> {
+    let mut keys = vec![];
+    let mut loads: HashMap<&str, usize> = HashMap::new();
+    for (path, meshes) in world.loaded_files() {
+        let instance = loads.entry(path).or_default();
+        for mesh in 0..*meshes {
+            keys.push(ModelKey {
+                path: path.clone(),
+                mesh,
+                instance: *instance,
+            });
+        }
+        *instance += 1;
+    }
+    keys
+}
+
+fn changes(world: &World, model: &Model, parent: Option<ModelKey>) -> ModelChanges {
+    let t = &model.transform;
+    let default = Transform::default();
+    let material = world
+        .materials
+        .iter()
+        .position(|m| Arc::ptr_eq(m, model.material()));
+    ModelChanges {
+        name: (model.name != model.mesh.name).then(|| model.name.clone()),
+        translation: (t.translation != default.translation).then_some(t.translation.into()),
+        rotation: (t.rotation != default.rotation).then_some(t.rotation.into()),
+        scale: (t.scale != default.scale).then_some(t.scale.into()),
+        base_color: (model.base_color != model.mesh.base_color).then_some(model.base_color),
+        alpha_cutoff: (model.alpha_cutoff != model.mesh.alpha_cutoff)
+            .then_some(model.alpha_cutoff.unwrap_or(0.0)),
+        visible: (!model.visible).then_some(false),
+        parent,
+        material: material.filter(|&m| m != 0),
+    }
+}
+
+impl ScenePatch {
+    /// What changed in `world` since its files were loaded.
+    pub fn diff(world: &World) -> Self {
+        let keys = model_keys(world);
+        let key_of = |id| {
+            let index = world.models.iter().position(|m| m.id == id)?;
+            keys[index].clone()
+        };
+        let loaded = loaded_keys(world);
+        let mut patch = ScenePatch {
+            files: world
+                .loaded_files()
+                .iter()
+                .map(|(p, _)| p.clone())
+                .collect(),
+            ..Default::default()
+        };
+        let mut skipped = 0;
+        for (model, key) in world.models.iter().zip(&keys) {
+            let Some(key) = key else {
+                skipped += 1;
+                continue;
+            };
+            let changes = changes(world, model, model.parent.and_then(key_of));
+            let model = ChangedModel {
+                key: key.clone(),
+                changes,
+            };
+            if !loaded.contains(key) {
+                patch.added.push(model);
+            } else if !model.changes.is_empty() {
+                patch.changed.push(model);
+            }
+        }
+        for key in loaded {
+            if !keys.contains(&Some(key.clone())) {
+                patch.removed.push(key);
+            }
+        }
+        if skipped > 0 {
+            log::warn!("{skipped} model(s) with generated meshes left out of the patch");
+        }
+        patch
+    }
+
+    /// Reloads the patch's files into `world`, replacing its models, then
+    /// applies the changes.
+    pub fn apply(&self, state: &State, world: &mut World) {
+        world.clear();
+        for path in &self.files {
+            world.load_model(state, path);
+        }
+        for added in &self.added {
+            let key = &added.key;
+            let mesh = world.meshes.iter().find(|m| {
+                m.source
+                    .as_ref()
+                    .is_some_and(|s| s.path == key.path && s.index == key.mesh)
+            });
+            let Some(mesh) = mesh.cloned() else {
+                log::warn!("No mesh {} in {} to spawn", key.mesh, key.path);
+                continue;
+            };
+            let material = world.default_material();
+            let name = mesh.name.clone();
+            world.spawn(state, &name, mesh, material, Transform::default());
+        }
+
+        let keys = model_keys(world);
+        let id_of = |key: &ModelKey| {
+            let index = keys.iter().position(|k| k.as_ref() == Some(key))?;
+            Some(world.models[index].id)
+        };
+        let mut edits = vec![];
+        for model in self.changed.iter().chain(&self.added) {
+            match id_of(&model.key) {
+                Some(id) => {
+                    let parent = model.changes.parent.as_ref().and_then(id_of);
+                    edits.push((id, &model.changes, parent));
+                }
+                None => log::warn!("Patch refers to missing model {:?}", model.key),
+            }
+        }
+        let removed: Vec<_> = self.removed.iter().filter_map(id_of).collect();
+
+        for (id, changes, parent) in edits {
+            if parent.is_some() && !world.set_parent(id, parent) {
+                log::warn!("Can't parent #{id} under its own descendant");
+            }
+            let material = changes
+                .material
+                .and_then(|i| world.materials.get(i).cloned());
+            let Some(model) = world.model_mut(id) else {
+                continue;
+            };
+            let t = &mut model.transform;
+            if let Some(name) = &changes.name {
+                model.name = name.clone();
+            }
+            if let Some(translation) = changes.translation {
+                t.translation = translation.into();
+            }
+            if let Some(rotation) = changes.rotation {
+                t.rotation = glam::Quat::from_array(rotation);
+            }
+            if let Some(scale) = changes.scale {
+                t.scale = scale.into();
+            }
+            if let Some(base_color) = changes.base_color {
+                model.base_color = base_color;
+            }
+            if let Some(cutoff) = changes.alpha_cutoff {
+                model.alpha_cutoff = (cutoff > 0.0).then_some(cutoff);
+            }
+            if let Some(visible) = changes.visible {
+                model.visible = visible;
+            }
+            if let Some(material) = material {
+                model.set_material(&state.device, material);
+            }
+        }
+        // after matching, so despawning doesn't renumber instances
+        for id in removed {
+            world.despawn(id);
+        }
+        world.update_visibility();
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        toml::from_str(&text).map_err(|e| e.to_string())
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let text = toml::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, text).map_err(|e| e.to_string())
+    }
+}
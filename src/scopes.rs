@@ -0,0 +1,596 @@
+//! Histogram and waveform scopes of the presented frame, for judging
+//! exposure and grading changes by more than eye. A compute shader bins
+//! every pixel of `World::captured_frame` and paints the waveform; both are
+//! read back a frame late so the scopes never stall the GPU.
+//!
+//! There's no HDR target to read: the scene renders straight into the
+//! display-referred surface, so values are as presented, clipped to 0..1.
+
+use crate::app::State;
+use crate::plugin::{Plugin, PluginContext};
+use crate::world::World;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+const SHADER: &str = r#"
+struct Params {
+    columns: u32,
+    levels: u32,
+    // 1 if the frame is sRGB, so loads come back linear
+    encode: u32,
+    gain: f32,
+};
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var frame: texture_2d<f32>;
+// 256 bins each of red, green, blue, then luma
+@group(0) @binding(2) var<storage, read_write> histogram: array<atomic<u32>>;
+// columns x levels counts each of red, green, blue
+@group(0) @binding(3) var<storage, read_write> waveform: array<atomic<u32>>;
+@group(0) @binding(4) var image: texture_storage_2d<rgba8unorm, write>;
+
+fn to_srgb(c: vec3<f32>) -> vec3<f32> {
+    let low = c * 12.92;
+    let high = 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055;
+    return select(high, low, c <= vec3(0.0031308));
+}
+
+@compute @workgroup_size(8, 8)
+fn accumulate(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(frame);
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+    var c = clamp(textureLoad(frame, vec2<i32>(id.xy), 0).rgb, vec3(0.0), vec3(1.0));
+    if (params.encode == 1u) {
+        c = to_srgb(c);
+    }
+    let luma = dot(c, vec3(0.2126, 0.7152, 0.0722));
+    let bins = vec4<u32>(round(vec4(c, luma) * 255.0));
+    for (var ch = 0u; ch < 4u; ch++) {
+        atomicAdd(&histogram[ch * 256u + bins[ch]], 1u);
+    }
+    let column = id.x * params.columns / size.x;
+    let levels = vec3<u32>(round(c * f32(params.levels - 1u)));
+    for (var ch = 0u; ch < 3u; ch++) {
+        atomicAdd(&waveform[(ch * params.levels + levels[ch]) * params.columns + column], 1u);
+    }
+}
+
+@compute @workgroup_size(8, 8)
+fn paint(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.columns || id.y >= params.levels) {
+        return;
+    }
+    // level 0 at the bottom
+    let level = params.levels - 1u - id.y;
+    let tints = array(vec3(1.0, 0.25, 0.25), vec3(0.25, 1.0, 0.25), vec3(0.3, 0.45, 1.0));
+    var color = vec3(0.0);
+    for (var ch = 0u; ch < 3u; ch++) {
+        let count = atomicLoad(&waveform[(ch * params.levels + level) * params.columns + id.x]);
+        color += tints[ch] * (1.0 - exp(-f32(count) * params.gain));
+    }
+    textureStore(image, vec2<i32>(id.xy), vec4(min(color, vec3(1.0)), 1.0));
+}
+"#;
+
+pub const BINS: usize = 256;
+/// Waveform resolution: columns across the frame, levels from 0 to 1.
+pub const COLUMNS: u32 = 256;
+pub const LEVELS: u32 = 128;
+
+const HISTOGRAM_SIZE: u64 = (4 * BINS * 4) as u64;
+const WAVEFORM_SIZE: u64 = (3 * COLUMNS * LEVELS * 4) as u64;
+const IMAGE_ROW: u32 = COLUMNS * 4;
+/// Where each value lands in the readback buffer.
+const HISTOGRAM_OFFSET: u64 = 0;
+const IMAGE_OFFSET: u64 = HISTOGRAM_SIZE;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ParamsUniform {
+    columns: u32,
+    levels: u32,
+    encode: u32,
+    gain: f32,
+}
+
+/// Pixel counts per 8-bit level of the frame as presented.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    pub red: [u32; BINS],
+    pub green: [u32; BINS],
+    pub blue: [u32; BINS],
+    /// Rec. 709 weights over the encoded channels.
+    pub luma: [u32; BINS],
+}
+
+impl Histogram {
+    pub fn pixels(&self) -> u32 {
+        self.luma.iter().sum()
+    }
+
+    /// 0..1.
+    pub fn mean_luma(&self) -> f32 {
+        let sum: u64 = self
+            .luma
+            .iter()
+            .enumerate()
+            .map(|(i, &n)| i as u64 * n as u64)
+            .sum();
+        sum as f32 / 255.0 / self.pixels().max(1) as f32
+    }
+
+    /// Fractions of pixels with any channel at 0 and at 255.
+    pub fn clipped(&self) -> (f32, f32) {
+        let pixels = self.pixels().max(1) as f32;
+        let [low, high] = [0, BINS - 1].map(|bin| {
+            [self.red[bin], self.green[bin], self.blue[bin]]
+                .into_iter()
+                .max()
+                .unwrap_or(0)
+        });
+        (low as f32 / pixels, high as f32 / pixels)
+    }
+}
+
+struct Pipelines {
+    accumulate: wgpu::ComputePipeline,
+    paint: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    params: wgpu::Buffer,
+    histogram: wgpu::Buffer,
+    waveform: wgpu::Buffer,
+    image: wgpu::Texture,
+    readback: wgpu::Buffer,
+}
+
+impl Pipelines {
+    fn new(device: &wgpu::Device) -> Self {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Scopes"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let storage_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Scopes"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                storage_entry(2),
+                storage_entry(3),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: wgpu::TextureFormat::Rgba8Unorm,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Scopes"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Scopes"),
+                layout: Some(&layout),
+                module: &module,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+
+        let buffer = |label, size, usage| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size,
+                usage,
+                mapped_at_creation: false,
+            })
+        };
+        let storage = wgpu::BufferUsages::STORAGE
+            | wgpu::BufferUsages::COPY_SRC
+            | wgpu::BufferUsages::COPY_DST;
+        let image = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Scopes Waveform"),
+            size: wgpu::Extent3d {
+                width: COLUMNS,
+                height: LEVELS,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        Pipelines {
+            accumulate: pipeline("accumulate"),
+            paint: pipeline("paint"),
+            bind_group_layout,
+            params: buffer(
+                "Scopes Params",
+                std::mem::size_of::<ParamsUniform>() as u64,
+                wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            ),
+            histogram: buffer("Scopes Histogram", HISTOGRAM_SIZE, storage),
+            waveform: buffer("Scopes Waveform Counts", WAVEFORM_SIZE, storage),
+            image,
+            readback: buffer(
+                "Scopes Readback",
+                IMAGE_OFFSET + (IMAGE_ROW * LEVELS) as u64,
+                wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            ),
+        }
+    }
+}
+
+/// Where the readback buffer is in its round trip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    Idle,
+    /// Copies encoded this frame; mapped once they've been submitted.
+    Copied,
+    Mapping,
+}
+
+pub struct Scopes {
+    /// Set while the window is expanded; nothing is captured or computed
+    /// otherwise.
+    pub active: bool,
+    /// Scales the histogram logarithmically, so small counts show.
+    pub log_scale: bool,
+    /// Waveform intensity; 1 puts an evenly spread frame at about 63%.
+    pub brightness: f32,
+    pub histogram: Option<Histogram>,
+    pipelines: Option<Pipelines>,
+    stage: Stage,
+    mapped: Arc<AtomicBool>,
+    /// The last presented frame.
+    frame: Option<wgpu::Texture>,
+    /// Read back but not yet uploaded to egui.
+    waveform: Option<egui::ColorImage>,
+    waveform_texture: Option<egui::TextureHandle>,
+}
+
+impl Default for Scopes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scopes {
+    pub fn new() -> Self {
+        Scopes {
+            active: false,
+            log_scale: false,
+            brightness: 2.0,
+            histogram: None,
+            pipelines: None,
+            stage: Stage::Idle,
+            mapped: Arc::new(AtomicBool::new(false)),
+            frame: None,
+            waveform: None,
+            waveform_texture: None,
+        }
+    }
+
+    /// Maps last frame's copies, and reads them once they're mapped.
+    fn receive(&mut self, state: &State) {
+        let Some(pipelines) = &self.pipelines else {
+            return;
+        };
+        if self.stage == Stage::Copied {
+            let mapped = self.mapped.clone();
+            pipelines
+                .readback
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |r| {
+                    if r.is_ok() {
+                        mapped.store(true, Ordering::Release);
+                    }
+                });
+            self.stage = Stage::Mapping;
+        }
+        if self.stage != Stage::Mapping {
+            return;
+        }
+        let _ = state.device.poll(wgpu::PollType::Poll);
+        if !self.mapped.swap(false, Ordering::Acquire) {
+            return;
+        }
+        {
+            let data = pipelines.readback.slice(..).get_mapped_range();
+            let counts: &[u32] = bytemuck::cast_slice(
+                &data[HISTOGRAM_OFFSET as usize..(HISTOGRAM_OFFSET + HISTOGRAM_SIZE) as usize],
+            );
+            let channel =
+                |i: usize| -> [u32; BINS] { counts[i * BINS..(i + 1) * BINS].try_into().unwrap() };
+            self.histogram = Some(Histogram {
+                red: channel(0),
+                green: channel(1),
+                blue: channel(2),
+                luma: channel(3),
+            });
+            self.waveform = Some(egui::ColorImage::from_rgba_unmultiplied(
+                [COLUMNS as usize, LEVELS as usize],
+                &data[IMAGE_OFFSET as usize..],
+            ));
+        }
+        pipelines.readback.unmap();
+        self.stage = Stage::Idle;
+    }
+}
+
+fn histogram_ui(ui: &mut egui::Ui, histogram: &Histogram, log_scale: bool) {
+    let size = egui::vec2(ui.available_width().max(256.0), 120.0);
+    let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, egui::Color32::from_gray(16));
+    let channels = [
+        &histogram.red,
+        &histogram.green,
+        &histogram.blue,
+        &histogram.luma,
+    ];
+    // the end bins hold everything clipped, and would flatten the rest
+    let peak = channels
+        .iter()
+        .flat_map(|c| c[1..BINS - 1].iter())
+        .copied()
+        .max()
+        .unwrap_or(0)
+        .max(1) as f32;
+    let height = |n: u32| {
+        let h = if log_scale {
+            (n as f32).ln_1p() / peak.ln_1p()
+        } else {
+            n as f32 / peak
+        };
+        rect.max.y - rect.height() * h.min(1.0)
+    };
+    let x = |bin: usize| rect.min.x + rect.width() * (bin as f32 + 0.5) / BINS as f32;
+
+    let bar = rect.width() / BINS as f32;
+    for (bin, &n) in histogram.luma.iter().enumerate() {
+        let top = height(n);
+        painter.rect_filled(
+            egui::Rect::from_min_max(
+                egui::pos2(x(bin) - bar / 2.0, top),
+                egui::pos2(x(bin) + bar / 2.0, rect.max.y),
+            ),
+            0.0,
+            egui::Color32::from_gray(90),
+        );
+    }
+    let tints = [
+        egui::Color32::from_rgb(255, 70, 70),
+        egui::Color32::from_rgb(70, 255, 70),
+        egui::Color32::from_rgb(90, 120, 255),
+    ];
+    for (channel, tint) in channels.iter().zip(tints) {
+        let points = channel
+            .iter()
+            .enumerate()
+            .map(|(bin, &n)| egui::pos2(x(bin), height(n)))
+            .collect();
+        painter.add(egui::Shape::line(points, egui::Stroke::new(1.0, tint)));
+    }
+}
+
+fn waveform_ui(ui: &mut egui::Ui, texture: &egui::TextureHandle) {
+    let size = egui::vec2(ui.available_width().max(256.0), 160.0);
+    let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+    painter.image(texture.id(), rect, uv, egui::Color32::WHITE);
+    let font = egui::FontId::monospace(10.0);
+    for percent in [0, 25, 50, 75, 100] {
+        let y = rect.max.y - rect.height() * percent as f32 / 100.0;
+        painter.hline(
+            rect.x_range(),
+            y,
+            egui::Stroke::new(1.0, egui::Color32::from_white_alpha(40)),
+        );
+        painter.text(
+            egui::pos2(
+                rect.min.x + 2.0,
+                y.clamp(rect.min.y + 6.0, rect.max.y - 6.0),
+            ),
+            egui::Align2::LEFT_CENTER,
+            format!("{percent}"),
+            font.clone(),
+            egui::Color32::from_white_alpha(120),
+        );
+    }
+}
+
+impl Plugin for Scopes {
+    fn name(&self) -> &str {
+        "Scopes"
+    }
+
+    fn build(&mut self, ctx: &mut PluginContext) {
+        if !ctx.state.caps.compute {
+            log::warn!("Scopes need compute shaders, which this device lacks");
+            return;
+        }
+        self.pipelines = Some(Pipelines::new(&ctx.state.device));
+    }
+
+    fn update(&mut self, ctx: &mut PluginContext) {
+        self.receive(ctx.state);
+        if !self.active {
+            self.frame = None;
+            return;
+        }
+        // shared, so a capture someone else asked for isn't taken from them
+        if let Some(frame) = &ctx.world.captured_frame {
+            self.frame = Some(frame.clone());
+        }
+        ctx.world.capture_frame = true;
+    }
+
+    fn compute(&mut self, state: &State, _world: &World, encoder: &mut wgpu::CommandEncoder) {
+        if self.stage != Stage::Idle {
+            return;
+        }
+        let (Some(pipelines), Some(frame)) = (&self.pipelines, self.frame.take()) else {
+            return;
+        };
+        let size = frame.size();
+        let pixels_per_column = (size.width * size.height) as f32 / COLUMNS as f32;
+        state.queue.write_buffer(
+            &pipelines.params,
+            0,
+            bytemuck::cast_slice(&[ParamsUniform {
+                columns: COLUMNS,
+                levels: LEVELS,
+                encode: frame.format().is_srgb() as u32,
+                gain: self.brightness * LEVELS as f32 / pixels_per_column.max(1.0),
+            }]),
+        );
+        let view = |t: &wgpu::Texture| t.create_view(&wgpu::TextureViewDescriptor::default());
+        let (frame_view, image_view) = (view(&frame), view(&pipelines.image));
+        let bind_group = state.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Scopes"),
+            layout: &pipelines.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: pipelines.params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&frame_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: pipelines.histogram.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: pipelines.waveform.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&image_view),
+                },
+            ],
+        });
+
+        encoder.clear_buffer(&pipelines.histogram, 0, None);
+        encoder.clear_buffer(&pipelines.waveform, 0, None);
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("scopes"),
+                timestamp_writes: None,
+            });
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.set_pipeline(&pipelines.accumulate);
+            pass.dispatch_workgroups(size.width.div_ceil(8), size.height.div_ceil(8), 1);
+            pass.set_pipeline(&pipelines.paint);
+            pass.dispatch_workgroups(COLUMNS.div_ceil(8), LEVELS.div_ceil(8), 1);
+        }
+        encoder.copy_buffer_to_buffer(
+            &pipelines.histogram,
+            0,
+            &pipelines.readback,
+            HISTOGRAM_OFFSET,
+            HISTOGRAM_SIZE,
+        );
+        encoder.copy_texture_to_buffer(
+            pipelines.image.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &pipelines.readback,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: IMAGE_OFFSET,
+                    bytes_per_row: Some(IMAGE_ROW),
+                    rows_per_image: None,
+                },
+            },
+            pipelines.image.size(),
+        );
+        self.stage = Stage::Copied;
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, _state: &State, _world: &mut World) {
+        if let Some(image) = self.waveform.take() {
+            match &mut self.waveform_texture {
+                Some(texture) => texture.set(image, egui::TextureOptions::LINEAR),
+                None => {
+                    self.waveform_texture = Some(ctx.load_texture(
+                        "scopes waveform",
+                        image,
+                        egui::TextureOptions::LINEAR,
+                    ))
+                }
+            }
+        }
+        let shown = egui::Window::new("Scopes")
+            .default_open(false)
+            .show(ctx, |ui| {
+                let Some(histogram) = &self.histogram else {
+                    ui.label("Waiting for a frame…");
+                    return;
+                };
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.log_scale, "Log scale");
+                    ui.add(
+                        egui::Slider::new(&mut self.brightness, 0.25..=16.0)
+                            .logarithmic(true)
+                            .text("Waveform brightness"),
+                    );
+                });
+                ui.label("Histogram");
+                histogram_ui(ui, histogram, self.log_scale);
+                let (shadows, highlights) = histogram.clipped();
+                ui.label(format!(
+                    "Mean luma {:.3}   Clipped: shadows {:.2}%, highlights {:.2}%",
+                    histogram.mean_luma(),
+                    shadows * 100.0,
+                    highlights * 100.0
+                ));
+                if let Some(texture) = &self.waveform_texture {
+                    ui.label("Waveform");
+                    waveform_ui(ui, texture);
+                }
+            });
+        self.active = shown.is_some_and(|r| r.inner.is_some());
+    }
+}
@@ -0,0 +1,393 @@
+use crate::app::State;
+use crate::model::EntityId;
+use crate::plugin::{Plugin, PluginContext};
+use crate::time::Time;
+use crate::transform::Transform;
+use crate::world::World;
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Scope, AST, FLOAT, INT};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::SystemTime;
+
+const SCRIPTS_DIR: &str = "scripts";
+const POLL_INTERVAL: f32 = 0.5;
+
+enum ScriptCommand {
+    Spawn { id: EntityId, mesh: usize },
+    Despawn(EntityId),
+    SetCamera { eye: glam::Vec3, center: glam::Vec3 },
+    Watch { name: String, value: f32 },
+}
+
+/// World snapshot the script API reads and writes; changes are applied to
+/// the real world once the script returns.
+#[derive(Default)]
+struct ScriptContext {
+    commands: Vec<ScriptCommand>,
+    transforms: HashMap<EntityId, Transform>,
+    /// Everything the running script has spawned, including in earlier
+    /// calls; some may have been despawned since.
+    spawned: Vec<EntityId>,
+    next_id: EntityId,
+    mesh_count: usize,
+}
+
+struct Script {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    ast: Option<AST>,
+    scope: Scope<'static>,
+    spawned: Vec<EntityId>,
+    error: Option<String>,
+}
+
+pub struct Scripting {
+    engine: Engine,
+    context: Rc<RefCell<ScriptContext>>,
+    scripts: Vec<Script>,
+    since_poll: f32,
+    pub enabled: bool,
+}
+
+impl Default for Scripting {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scripting {
+    pub fn new() -> Self {
+        let context = Rc::new(RefCell::new(ScriptContext::default()));
+        let mut engine = Engine::new();
+        register_api(&mut engine, &context);
+        engine.on_print(|s| log::info!(target: "script", "{s}"));
+
+        Scripting {
+            engine,
+            context,
+            scripts: vec![],
+            // poll immediately on the first frame
+            since_poll: POLL_INTERVAL,
+            enabled: true,
+        }
+    }
+
+    fn run_frame(&mut self, state: &State, world: &mut World, time: &Time) {
+        // hot reload keeps working while paused
+        self.since_poll += time.real_delta_seconds;
+        if self.since_poll >= POLL_INTERVAL {
+            self.since_poll = 0.0;
+            self.poll_scripts(state, world);
+        }
+
+        let (dt, time) = (time.delta_seconds, time.elapsed_seconds);
+        for i in 0..self.scripts.len() {
+            let has_update = self.scripts[i]
+                .ast
+                .as_ref()
+                .is_some_and(|ast| ast.iter_functions().any(|f| f.name == "update"));
+            if has_update {
+                self.run(state, world, i, |engine, scope, ast| {
+                    engine
+                        .call_fn::<Dynamic>(scope, ast, "update", (dt as FLOAT, time as FLOAT))
+                        .map(|_| ())
+                });
+            }
+        }
+    }
+
+    /// Despawns everything spawned by scripts and reloads them from disk.
+    pub fn reload_all(&mut self, state: &State, world: &mut World) {
+        for script in &mut self.scripts {
+            for id in script.spawned.drain(..) {
+                world.despawn(id);
+            }
+        }
+        self.scripts.clear();
+        self.poll_scripts(state, world);
+    }
+
+    fn poll_scripts(&mut self, state: &State, world: &mut World) {
+        let Ok(entries) = std::fs::read_dir(SCRIPTS_DIR) else {
+            return;
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|e| e == "rhai"))
+            .collect();
+        paths.sort();
+
+        // scripts whose file disappeared take their entities with them
+        self.scripts.retain_mut(|script| {
+            let keep = paths.contains(&script.path);
+            if !keep {
+                for id in script.spawned.drain(..) {
+                    world.despawn(id);
+                }
+            }
+            keep
+        });
+
+        for path in paths {
+            let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+            let index = match self.scripts.iter().position(|s| s.path == path) {
+                Some(i) if self.scripts[i].modified == modified => continue,
+                Some(i) => i,
+                None => {
+                    self.scripts.push(Script {
+                        path: path.clone(),
+                        modified: None,
+                        ast: None,
+                        scope: Scope::new(),
+                        spawned: vec![],
+                        error: None,
+                    });
+                    self.scripts.len() - 1
+                }
+            };
+            self.scripts[index].modified = modified;
+            self.load(state, world, index);
+        }
+    }
+
+    fn load(&mut self, state: &State, world: &mut World, index: usize) {
+        let path = self.scripts[index].path.clone();
+        let ast = match self.engine.compile_file(path.clone()) {
+            Ok(ast) => ast,
+            Err(e) => {
+                // keep running the previous version until the error is fixed
+                log::warn!("Failed to compile {}: {e}", path.display());
+                self.scripts[index].error = Some(e.to_string());
+                return;
+            }
+        };
+        log::info!("Loaded script {}", path.display());
+
+        let script = &mut self.scripts[index];
+        for id in script.spawned.drain(..) {
+            world.despawn(id);
+        }
+        script.scope = Scope::new();
+        script.error = None;
+        let has_init = ast.iter_functions().any(|f| f.name == "init");
+        script.ast = Some(ast);
+
+        self.run(state, world, index, |engine, scope, ast| {
+            engine.run_ast_with_scope(scope, ast)?;
+            if has_init {
+                let _ = engine.call_fn::<Dynamic>(scope, ast, "init", ())?;
+            }
+            Ok(())
+        });
+    }
+
+    fn run(
+        &mut self,
+        state: &State,
+        world: &mut World,
+        index: usize,
+        f: impl FnOnce(&Engine, &mut Scope<'static>, &AST) -> Result<(), Box<EvalAltResult>>,
+    ) {
+        {
+            let mut context = self.context.borrow_mut();
+            context.commands.clear();
+            context.spawned = self.scripts[index].spawned.clone();
+            context.next_id = world.next_id();
+            context.mesh_count = world.meshes.len();
+            context.transforms = world.models.iter().map(|m| (m.id, m.transform)).collect();
+        }
+
+        let script = &mut self.scripts[index];
+        let Some(ast) = script.ast.as_ref() else {
+            return;
+        };
+        if let Err(e) = f(&self.engine, &mut script.scope, ast) {
+            log::warn!("Script error in {}: {e}", script.path.display());
+            script.error = Some(e.to_string());
+        }
+
+        let mut context = self.context.borrow_mut();
+        for command in context.commands.drain(..) {
+            match command {
+                ScriptCommand::Spawn { id, mesh } => {
+                    let mesh = world.meshes[mesh].clone();
+                    let material = world.default_material();
+                    let name = format!("{} (script)", mesh.name);
+                    let spawned = world.spawn(state, &name, mesh, material, Transform::default());
+                    debug_assert_eq!(spawned, id);
+                }
+                ScriptCommand::Despawn(id) => {
+                    world.despawn(id);
+                }
+                ScriptCommand::SetCamera { eye, center } => {
+                    world.camera.eye = eye;
+                    world.camera.center = center;
+                    world.camera_controller.sync_from_camera(&world.camera);
+                }
+                ScriptCommand::Watch { name, value } => world.watch.record(&name, value),
+            }
+        }
+        for model in &mut world.models {
+            if let Some(transform) = context.transforms.get(&model.id) {
+                model.transform = *transform;
+            }
+        }
+        script.spawned = std::mem::take(&mut context.spawned);
+        script.spawned.retain(|id| world.model(*id).is_some());
+    }
+}
+
+impl Plugin for Scripting {
+    fn name(&self) -> &str {
+        "Scripting"
+    }
+
+    fn update(&mut self, ctx: &mut PluginContext) {
+        if self.enabled {
+            self.run_frame(ctx.state, ctx.world, ctx.time);
+        }
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, state: &State, world: &mut World) {
+        egui::Window::new("Scripts")
+            .default_open(false)
+            .show(ctx, |ui| {
+                ui.checkbox(&mut self.enabled, "Run scripts");
+                if self.scripts.is_empty() {
+                    ui.label(format!("No .rhai files in {SCRIPTS_DIR}/"));
+                }
+                for script in &self.scripts {
+                    let name = script
+                        .path
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy();
+                    match &script.error {
+                        Some(e) => ui.colored_label(egui::Color32::RED, format!("{name}: {e}")),
+                        None => ui.label(format!("{name}: {} entities", script.spawned.len())),
+                    };
+                }
+                if ui.button("Reload all").clicked() {
+                    self.reload_all(state, world);
+                }
+            });
+    }
+}
+
+/// What the running script spawned and hasn't despawned.
+fn owned(ctx: &ScriptContext) -> Vec<EntityId> {
+    ctx.spawned
+        .iter()
+        .copied()
+        .filter(|id| ctx.transforms.contains_key(id))
+        .collect()
+}
+
+fn register_api(engine: &mut Engine, context: &Rc<RefCell<ScriptContext>>) {
+    let ctx = context.clone();
+    engine.register_fn(
+        "spawn_mesh",
+        move |mesh: INT| -> Result<INT, Box<EvalAltResult>> {
+            let mut ctx = ctx.borrow_mut();
+            if ctx.mesh_count == 0 {
+                return Err("spawn_mesh: no meshes are loaded".into());
+            }
+            let mesh = (mesh.max(0) as usize).min(ctx.mesh_count.saturating_sub(1));
+            let id = ctx.next_id;
+            ctx.next_id += 1;
+            ctx.commands.push(ScriptCommand::Spawn { id, mesh });
+            ctx.transforms.insert(id, Transform::default());
+            ctx.spawned.push(id);
+            Ok(id as INT)
+        },
+    );
+
+    let ctx = context.clone();
+    engine.register_fn("despawn", move |id: INT| {
+        let mut ctx = ctx.borrow_mut();
+        ctx.transforms.remove(&(id as EntityId));
+        ctx.commands.push(ScriptCommand::Despawn(id as EntityId));
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("mesh_count", move || -> INT {
+        ctx.borrow().mesh_count as INT
+    });
+
+    // a script's entities are the ones it spawned, not the whole scene
+    let ctx = context.clone();
+    engine.register_fn("entity_count", move || -> INT {
+        owned(&ctx.borrow()).len() as INT
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("entities", move || -> Array {
+        let mut ids = owned(&ctx.borrow());
+        ids.sort();
+        ids.into_iter().map(|id| Dynamic::from(id as INT)).collect()
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("position", move |id: INT| -> Array {
+        let ctx = ctx.borrow();
+        let t = ctx
+            .transforms
+            .get(&(id as EntityId))
+            .map_or(glam::Vec3::ZERO, |t| t.translation);
+        vec![t.x.into(), t.y.into(), t.z.into()]
+    });
+
+    let ctx = context.clone();
+    engine.register_fn(
+        "set_position",
+        move |id: INT, x: FLOAT, y: FLOAT, z: FLOAT| {
+            if let Some(t) = ctx.borrow_mut().transforms.get_mut(&(id as EntityId)) {
+                t.translation = glam::vec3(x, y, z);
+            }
+        },
+    );
+
+    // euler angles in degrees, applied yaw (Y), then pitch (X), then roll (Z)
+    let ctx = context.clone();
+    engine.register_fn(
+        "set_rotation",
+        move |id: INT, yaw: FLOAT, pitch: FLOAT, roll: FLOAT| {
+            if let Some(t) = ctx.borrow_mut().transforms.get_mut(&(id as EntityId)) {
+                t.rotation = glam::Quat::from_euler(
+                    glam::EulerRot::YXZ,
+                    yaw.to_radians(),
+                    pitch.to_radians(),
+                    roll.to_radians(),
+                );
+            }
+        },
+    );
+
+    let ctx = context.clone();
+    engine.register_fn("set_scale", move |id: INT, scale: FLOAT| {
+        if let Some(t) = ctx.borrow_mut().transforms.get_mut(&(id as EntityId)) {
+            t.scale = glam::Vec3::splat(scale);
+        }
+    });
+
+    let ctx = context.clone();
+    engine.register_fn(
+        "set_camera",
+        move |ex: FLOAT, ey: FLOAT, ez: FLOAT, cx: FLOAT, cy: FLOAT, cz: FLOAT| {
+            ctx.borrow_mut().commands.push(ScriptCommand::SetCamera {
+                eye: glam::vec3(ex, ey, ez),
+                center: glam::vec3(cx, cy, cz),
+            });
+        },
+    );
+
+    let ctx = context.clone();
+    engine.register_fn("watch", move |name: &str, value: FLOAT| {
+        ctx.borrow_mut().commands.push(ScriptCommand::Watch {
+            name: name.to_string(),
+            value,
+        });
+    });
+}
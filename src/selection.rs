@@ -0,0 +1,716 @@
+//! Which entities are selected, and the viewport tool that picks them:
+//! click to select one, Ctrl+click to toggle, Ctrl+drag to box-select,
+//! Shift+drag to move. Group edits move, rotate and scale the selection
+//! about its pivot.
+//!
+//! Box selection renders entity ids for just the box and reads them back,
+//! so it picks what's visible in it: occluded models are left out, and
+//! ones only partly inside are caught. The ids arrive a frame or two late.
+
+use crate::app::State;
+use crate::input::Action;
+use crate::mesh::VertexEncoding;
+use crate::model::EntityId;
+use crate::picking;
+use crate::plugin::{Plugin, PluginContext};
+use crate::transform::Transform;
+use crate::world::World;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Logical pixels the cursor moves before a press counts as a drag.
+const DRAG_THRESHOLD: f32 = 4.0;
+const PIVOT_COLOR: [f32; 4] = [1.0, 0.6, 0.1, 1.0];
+
+const ID_SHADER: &str = r#"
+struct Camera { view_proj: mat4x4<f32> };
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+struct Model {
+    model: mat4x4<f32>,
+    base_color: vec4<f32>,
+    alpha_cutoff: f32,
+};
+@group(1) @binding(0) var<uniform> model: Model;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) @interpolate(flat) id: u32,
+};
+
+// models are drawn with their entity id as the instance; both vertex
+// encodings lead with the position, and nothing else is needed
+@vertex
+fn vsMain(@location(0) pos: vec3<f32>, @builtin(instance_index) id: u32) -> VSOut {
+    var out: VSOut;
+    out.pos = camera.view_proj * model.model * vec4(pos, 1.0);
+    out.id = id;
+    return out;
+}
+
+@fragment
+fn psMain(in: VSOut) -> @location(0) u32 {
+    if (model.alpha_cutoff > 0.0 && model.base_color.a < model.alpha_cutoff) {
+        discard;
+    }
+    // 0 is left for the background
+    return in.id + 1u;
+}
+"#;
+
+const ID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+
+/// Selected entities in the order they were added. The last is the
+/// primary one, which single-entity tools and the inspector's details use.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Selection {
+    ids: Vec<EntityId>,
+}
+
+impl Selection {
+    pub fn primary(&self) -> Option<EntityId> {
+        self.ids.last().copied()
+    }
+
+    pub fn ids(&self) -> &[EntityId] {
+        &self.ids
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn contains(&self, id: EntityId) -> bool {
+        self.ids.contains(&id)
+    }
+
+    /// Replaces the selection with `id`, or clears it.
+    pub fn set(&mut self, id: Option<EntityId>) {
+        self.ids.clear();
+        self.ids.extend(id);
+    }
+
+    /// Adds `id` as the primary, moving it if it was already selected.
+    pub fn add(&mut self, id: EntityId) {
+        self.remove(id);
+        self.ids.push(id);
+    }
+
+    pub fn remove(&mut self, id: EntityId) {
+        self.ids.retain(|&i| i != id);
+    }
+
+    pub fn toggle(&mut self, id: EntityId) {
+        if self.contains(id) {
+            self.remove(id);
+        } else {
+            self.add(id);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.ids.clear();
+    }
+}
+
+fn has_selected_ancestor(world: &World, id: EntityId) -> bool {
+    let mut parent = world.model(id).and_then(|m| m.parent);
+    // `set_parent` rules out cycles, so this ends
+    while let Some(p) = parent {
+        if world.selection.contains(p) {
+            return true;
+        }
+        parent = world.model(p).and_then(|m| m.parent);
+    }
+    false
+}
+
+/// Whether a group edit moves `id`: it's selected or under a model that is.
+pub fn moves_with_selection(world: &World, id: EntityId) -> bool {
+    world.selection.contains(id) || has_selected_ancestor(world, id)
+}
+
+/// Selected models that still exist and have no selected ancestor, so a
+/// group edit moves each subtree once.
+pub fn roots(world: &World) -> Vec<EntityId> {
+    world
+        .selection
+        .ids()
+        .iter()
+        .copied()
+        .filter(|&id| world.model(id).is_some() && !has_selected_ancestor(world, id))
+        .collect()
+}
+
+/// The centre of the selected models' world bounds.
+pub fn pivot(world: &World) -> Option<glam::Vec3> {
+    let centers: Vec<glam::Vec3> = world
+        .selection
+        .ids()
+        .iter()
+        .filter_map(|&id| world.model(id))
+        .map(|model| {
+            let (min, max) = model.mesh.bounds();
+            model.global_matrix().transform_point3((min + max) * 0.5)
+        })
+        .collect();
+    (!centers.is_empty()).then(|| centers.iter().sum::<glam::Vec3>() / centers.len() as f32)
+}
+
+/// Applies `delta`, a world-space transform about `pivot`, to every
+/// selected subtree.
+pub fn transform_group(world: &mut World, pivot: glam::Vec3, delta: Transform) {
+    let about_pivot =
+        glam::Mat4::from_translation(pivot) * delta.matrix() * glam::Mat4::from_translation(-pivot);
+    for id in roots(world) {
+        let parent = world
+            .model(id)
+            .and_then(|m| m.parent)
+            .and_then(|p| world.model(p))
+            .map_or(glam::Mat4::IDENTITY, |p| p.global_matrix());
+        let model = world.model_mut(id).unwrap();
+        let local = parent.inverse() * about_pivot * parent * model.transform.matrix();
+        let (scale, rotation, translation) = local.to_scale_rotation_translation();
+        model.transform = Transform {
+            translation,
+            rotation,
+            scale,
+        };
+    }
+}
+
+/// Click and box selection in the viewport.
+pub struct SelectionTool {
+    pub enabled: bool,
+    /// Where the Select binding went down, while it's held.
+    press: Option<glam::Vec2>,
+    /// The rectangle being dragged, in logical pixels.
+    dragging: Option<(glam::Vec2, glam::Vec2)>,
+    /// Whether Ctrl was held when the press started.
+    additive: bool,
+    moving: Option<Move>,
+    id_pipelines: Option<IdPipelines>,
+    /// A box waiting to be rendered.
+    requested: Option<Region>,
+    /// The box being read back.
+    pending: Option<Region>,
+    stage: Stage,
+    mapped: Arc<AtomicBool>,
+}
+
+impl Default for SelectionTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SelectionTool {
+    pub fn new() -> Self {
+        SelectionTool {
+            enabled: true,
+            press: None,
+            dragging: None,
+            additive: false,
+            moving: None,
+            id_pipelines: None,
+            requested: None,
+            pending: None,
+            stage: Stage::Idle,
+            mapped: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Maps last frame's copy, and adds its ids once it's mapped.
+    fn receive(&mut self, state: &State, world: &mut World) {
+        let Some(targets) = self.id_pipelines.as_ref().and_then(|p| p.targets.as_ref()) else {
+            return;
+        };
+        match self.stage {
+            Stage::Idle => {}
+            Stage::Copied => {
+                let mapped = self.mapped.clone();
+                targets
+                    .readback
+                    .slice(..)
+                    .map_async(wgpu::MapMode::Read, move |r| {
+                        if r.is_ok() {
+                            mapped.store(true, Ordering::Release);
+                        }
+                    });
+                self.stage = Stage::Mapping;
+            }
+            Stage::Mapping => {
+                let _ = state.device.poll(wgpu::PollType::Poll);
+                if !self.mapped.swap(false, Ordering::Acquire) {
+                    return;
+                }
+                if let Some(region) = self.pending.take() {
+                    let data = targets.readback.slice(..).get_mapped_range();
+                    for id in decode_ids(&data, &region) {
+                        world.selection.add(id);
+                    }
+                }
+                targets.readback.unmap();
+                self.stage = Stage::Idle;
+            }
+        }
+    }
+
+    fn click(world: &mut World, cursor: glam::Vec2, size: glam::Vec2, additive: bool) {
+        let ray = picking::cursor_ray(&world.camera, cursor, size);
+        let hit = picking::raycast(world, &ray).map(|hit| hit.entity);
+        match (hit, additive) {
+            (Some(id), true) => world.selection.toggle(id),
+            (hit, false) => world.selection.set(hit),
+            (None, true) => {}
+        }
+    }
+
+    /// Starts moving the model under `ray`, selecting it first if it
+    /// isn't.
+    fn grab(world: &mut World, ray: &picking::Ray) -> Option<Move> {
+        let hit = picking::raycast(world, ray)?;
+        if !moves_with_selection(world, hit.entity) {
+            world.selection.set(Some(hit.entity));
+        }
+        let origin = origin(world, world.selection.primary()?)?;
+        Some(Move {
+            normal: (world.camera.center - world.camera.eye).normalize_or_zero(),
+            offset: hit.point - origin,
+        })
+    }
+
+    /// Moves the selection so the grabbed point follows `ray`, with the
+    /// primary model's origin snapped.
+    fn drag(world: &mut World, grab: &Move, ray: &picking::Ray) {
+        let Some(origin) = world.selection.primary().and_then(|id| origin(world, id)) else {
+            return;
+        };
+        let grabbed = origin + grab.offset;
+        let facing = ray.direction.dot(grab.normal);
+        if facing.abs() < 1e-4 {
+            return;
+        }
+        let distance = (grabbed - ray.origin).dot(grab.normal) / facing;
+        if distance <= 0.0 {
+            return;
+        }
+        let target = ray.at(distance) - grab.offset;
+        let snapped = world
+            .snapping
+            .snap_drag(world, ray, target, |id| moves_with_selection(world, id));
+        let delta = Transform {
+            translation: snapped - origin,
+            ..Default::default()
+        };
+        transform_group(world, origin, delta);
+    }
+
+    /// The physical pixels under a box between `a` and `b`, in logical
+    /// pixels, clipped to the frame; `None` if that leaves nothing.
+    fn box_region(state: &State, a: glam::Vec2, b: glam::Vec2) -> Option<Region> {
+        let frame = glam::vec2(
+            state.surface_config.width as f32,
+            state.surface_config.height as f32,
+        );
+        let min = (a.min(b) * state.scale_factor)
+            .floor()
+            .max(glam::Vec2::ZERO);
+        let max = (a.max(b) * state.scale_factor).ceil().min(frame);
+        if !max.cmpgt(min).all() {
+            return None;
+        }
+        let extent = (max - min).as_uvec2();
+        Some(Region {
+            min: min.as_uvec2(),
+            // a box larger than a texture can be is scaled down to fit
+            extent: extent.min(glam::UVec2::splat(state.caps.max_texture_size)),
+            frame_extent: extent,
+        })
+    }
+}
+
+/// A Shift+drag moving the selection across the plane facing the camera
+/// through the primary model's origin.
+struct Move {
+    normal: glam::Vec3,
+    /// From the primary model's origin to the point grabbed.
+    offset: glam::Vec3,
+}
+
+fn origin(world: &World, id: EntityId) -> Option<glam::Vec3> {
+    world.model(id).map(|m| m.global_matrix().w_axis.truncate())
+}
+
+/// Physical pixels of the frame a box covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Region {
+    min: glam::UVec2,
+    /// Of the id target; `frame_extent` unless that's too large.
+    extent: glam::UVec2,
+    frame_extent: glam::UVec2,
+}
+
+impl Region {
+    /// Bytes per row of the readback, padded as copies require.
+    fn padded_row(&self) -> u32 {
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        (self.extent.x * 4).div_ceil(align) * align
+    }
+}
+
+/// Where the readback buffer is in its round trip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    Idle,
+    /// Copies encoded this frame; mapped once they've been submitted.
+    Copied,
+    Mapping,
+}
+
+/// Sized to the last box, and replaced when one needs another size.
+struct IdTargets {
+    extent: glam::UVec2,
+    id: wgpu::Texture,
+    depth: wgpu::Texture,
+    readback: wgpu::Buffer,
+}
+
+struct IdPipelines {
+    full: wgpu::RenderPipeline,
+    packed: wgpu::RenderPipeline,
+    camera: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+    targets: Option<IdTargets>,
+}
+
+impl IdPipelines {
+    fn new(state: &State) -> Self {
+        let device = &state.device;
+        let camera = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Box Select Camera"),
+            size: std::mem::size_of::<[[f32; 4]; 4]>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let camera_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Box Select Camera"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Box Select Camera"),
+            layout: &camera_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera.as_entire_binding(),
+            }],
+        });
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Box Select"),
+            source: wgpu::ShaderSource::Wgsl(ID_SHADER.into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Box Select"),
+            bind_group_layouts: &[&camera_layout, &state.model_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |encoding: VertexEncoding| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Box Select"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &module,
+                    entry_point: Some("vsMain"),
+                    buffers: &[encoding.layout()],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &module,
+                    entry_point: Some("psMain"),
+                    compilation_options: Default::default(),
+                    targets: &[Some(ID_FORMAT.into())],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+        IdPipelines {
+            full: pipeline(VertexEncoding::Full),
+            packed: pipeline(VertexEncoding::Packed),
+            camera,
+            camera_bind_group,
+            targets: None,
+        }
+    }
+
+    fn targets(&mut self, device: &wgpu::Device, region: &Region) -> &IdTargets {
+        if self
+            .targets
+            .as_ref()
+            .is_some_and(|t| t.extent != region.extent)
+        {
+            self.targets = None;
+        }
+        self.targets.get_or_insert_with(|| {
+            let texture = |label, format, usage| {
+                device.create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: wgpu::Extent3d {
+                        width: region.extent.x,
+                        height: region.extent.y,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage,
+                    view_formats: &[],
+                })
+            };
+            IdTargets {
+                extent: region.extent,
+                id: texture(
+                    "Box Select Id",
+                    ID_FORMAT,
+                    wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                ),
+                depth: texture(
+                    "Box Select Depth",
+                    wgpu::TextureFormat::Depth32Float,
+                    wgpu::TextureUsages::RENDER_ATTACHMENT,
+                ),
+                readback: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Box Select Readback"),
+                    size: region.padded_row() as u64 * region.extent.y as u64,
+                    usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                    mapped_at_creation: false,
+                }),
+            }
+        })
+    }
+
+    /// Renders the ids under `region` and copies them to the readback.
+    fn encode(
+        &mut self,
+        state: &State,
+        world: &World,
+        encoder: &mut wgpu::CommandEncoder,
+        region: &Region,
+    ) {
+        let frame = glam::vec2(
+            state.surface_config.width as f32,
+            state.surface_config.height as f32,
+        );
+        let view_proj =
+            picking::region_projection(region.min.as_vec2(), region.frame_extent.as_vec2(), frame)
+                * world.camera.view_proj();
+        state.queue.write_buffer(
+            &self.camera,
+            0,
+            bytemuck::cast_slice(&view_proj.to_cols_array_2d()),
+        );
+        self.targets(&state.device, region);
+        let targets = self.targets.as_ref().unwrap();
+        let view = |t: &wgpu::Texture| t.create_view(&wgpu::TextureViewDescriptor::default());
+        let (id_view, depth_view) = (view(&targets.id), view(&targets.depth));
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("box select"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &id_view,
+                    depth_slice: None,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_bind_group(0, &self.camera_bind_group, &[]);
+            for model in world.models.iter().filter(|m| m.is_visible()) {
+                pass.set_pipeline(match model.mesh.encoding {
+                    VertexEncoding::Full => &self.full,
+                    VertexEncoding::Packed => &self.packed,
+                });
+                pass.set_bind_group(1, model.bind_group(), &[]);
+                pass.set_vertex_buffer(0, model.mesh.vertex_buffer.slice(..));
+                pass.set_index_buffer(model.mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                pass.draw_indexed(0..model.mesh.index_count, 0, model.id..model.id + 1);
+            }
+        }
+        encoder.copy_texture_to_buffer(
+            targets.id.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &targets.readback,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(region.padded_row()),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: region.extent.x,
+                height: region.extent.y,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
+
+/// The distinct entities in a readback of `region`.
+fn decode_ids(data: &[u8], region: &Region) -> Vec<EntityId> {
+    let mut ids: Vec<EntityId> = data
+        .chunks_exact(region.padded_row() as usize)
+        .flat_map(|row| row[..region.extent.x as usize * 4].chunks_exact(4))
+        .map(|texel| u32::from_le_bytes(texel.try_into().unwrap()))
+        .filter(|&id| id > 0)
+        .map(|id| id - 1)
+        .collect();
+    ids.sort_unstable();
+    ids.dedup();
+    ids
+}
+
+impl Plugin for SelectionTool {
+    fn name(&self) -> &str {
+        "Selection"
+    }
+
+    fn build(&mut self, ctx: &mut PluginContext) {
+        self.id_pipelines = Some(IdPipelines::new(ctx.state));
+    }
+
+    fn update(&mut self, ctx: &mut PluginContext) {
+        self.receive(ctx.state, ctx.world);
+        let world = &mut *ctx.world;
+        world
+            .selection
+            .ids
+            .retain(|&id| world.models.iter().any(|m| m.id == id));
+        if let Some(pivot) = pivot(world).filter(|_| world.selection.len() > 1) {
+            let size = world.camera.eye.distance(pivot) * 0.03;
+            world.debug_draw.cross(pivot, size, PIVOT_COLOR);
+        }
+        // another tool is dragging with the same binding
+        if !self.enabled || world.camera_controller.held {
+            self.press = None;
+            self.dragging = None;
+            self.moving = None;
+            return;
+        }
+
+        let input = ctx.input;
+        let size = glam::vec2(
+            ctx.state.surface_config.width as f32,
+            ctx.state.surface_config.height as f32,
+        ) / ctx.state.scale_factor;
+        if input.just_pressed(Action::Select) && input.held(Action::MoveSelection) {
+            self.moving = input.cursor().and_then(|cursor| {
+                Self::grab(world, &picking::cursor_ray(&world.camera, cursor, size))
+            });
+        }
+        if let Some(grab) = &self.moving {
+            match input.cursor().filter(|_| input.held(Action::Select)) {
+                Some(cursor) => {
+                    let ray = picking::cursor_ray(&world.camera, cursor, size);
+                    Self::drag(world, grab, &ray);
+                    world.camera_controller.held = true;
+                }
+                None => self.moving = None,
+            }
+            return;
+        }
+        if input.just_pressed(Action::Select) {
+            self.press = input.cursor();
+            self.additive = input.held(Action::MultiSelect);
+        }
+        let Some(start) = self.press else {
+            return;
+        };
+        let cursor = input.cursor().unwrap_or(start);
+        if input.held(Action::Select) {
+            // plain drags orbit the camera; Ctrl+drags draw a box
+            if self.additive && (self.dragging.is_some() || cursor.distance(start) > DRAG_THRESHOLD)
+            {
+                self.dragging = Some((start, cursor));
+                world.camera_controller.held = true;
+            }
+            return;
+        }
+
+        // released
+        self.press = None;
+        match self.dragging.take() {
+            Some((a, b)) => self.requested = Self::box_region(ctx.state, a, b),
+            None if cursor.distance(start) <= DRAG_THRESHOLD => {
+                Self::click(world, start, size, self.additive)
+            }
+            // an orbit, not a click
+            None => {}
+        }
+    }
+
+    fn prepare(&mut self, state: &State, world: &World, encoder: &mut wgpu::CommandEncoder) {
+        // a box made while the last is still reading waits for it
+        if self.stage != Stage::Idle {
+            return;
+        }
+        let (Some(pipelines), Some(region)) = (&mut self.id_pipelines, self.requested.take())
+        else {
+            return;
+        };
+        pipelines.encode(state, world, encoder, &region);
+        self.pending = Some(region);
+        self.stage = Stage::Copied;
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, _state: &State, _world: &mut World) {
+        let Some((a, b)) = self.dragging else {
+            return;
+        };
+        let rect = egui::Rect::from_two_pos(egui::pos2(a.x, a.y), egui::pos2(b.x, b.y));
+        // input is in window points, egui's may be scaled further
+        let rect = rect / ctx.zoom_factor();
+        let painter = ctx.layer_painter(egui::LayerId::background());
+        painter.rect_filled(rect, 0.0, egui::Color32::from_white_alpha(16));
+        painter.rect_stroke(
+            rect,
+            0.0,
+            egui::Stroke::new(1.0, egui::Color32::WHITE),
+            egui::StrokeKind::Inside,
+        );
+    }
+}
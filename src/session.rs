@@ -0,0 +1,199 @@
+use crate::input::{Input, InputEvent};
+use crate::time::Time;
+use crate::world::World;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    pub dt: f32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<InputEvent>,
+}
+
+/// Input events and frame timings from the start of a recording, plus the
+/// camera pose they started from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub camera_eye: [f32; 3],
+    pub camera_center: [f32; 3],
+    pub frames: Vec<RecordedFrame>,
+}
+
+impl Session {
+    pub fn new(world: &World) -> Self {
+        Session {
+            camera_eye: world.camera.eye.into(),
+            camera_center: world.camera.center.into(),
+            frames: vec![],
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        toml::from_str(&text).map_err(|e| format!("{path}: {e}"))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let text = toml::to_string(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, text).map_err(|e| format!("{path}: {e}"))
+    }
+
+    /// Puts the camera back where the recording started.
+    pub fn restore_camera(&self, world: &mut World) {
+        world.camera.eye = self.camera_eye.into();
+        world.camera.center = self.camera_center.into();
+        world.camera_controller.sync_from_camera(&world.camera);
+    }
+}
+
+enum Mode {
+    Idle,
+    Recording(Session),
+    Replaying { session: Session, frame: usize },
+}
+
+/// Records live input into a `Session` or drives `Input`/`Time` from one.
+pub struct SessionRecorder {
+    mode: Mode,
+    pending_replay: Option<String>,
+    pub path: String,
+    pub status: String,
+}
+
+impl Default for SessionRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SessionRecorder {
+    pub fn new() -> Self {
+        SessionRecorder {
+            mode: Mode::Idle,
+            pending_replay: None,
+            path: "session.toml".to_string(),
+            status: String::new(),
+        }
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        matches!(self.mode, Mode::Replaying { .. })
+    }
+
+    pub fn is_recording(&self) -> bool {
+        matches!(self.mode, Mode::Recording(_))
+    }
+
+    /// Starts replaying `path` on the next frame.
+    pub fn queue_replay(&mut self, path: &str) {
+        self.pending_replay = Some(path.to_string());
+    }
+
+    pub fn start_recording(&mut self, world: &World, input: &mut Input) {
+        // events from before the recording started shouldn't end up in it
+        input.take_frame_events();
+        self.mode = Mode::Recording(Session::new(world));
+        self.status = "Recording".to_string();
+    }
+
+    pub fn stop(&mut self) {
+        match std::mem::replace(&mut self.mode, Mode::Idle) {
+            Mode::Recording(session) => {
+                self.status = match session.save(&self.path) {
+                    Ok(()) => format!("Saved {} frames to {}", session.frames.len(), self.path),
+                    Err(e) => format!("Failed to save session: {e}"),
+                };
+                log::info!("{}", self.status);
+            }
+            Mode::Replaying { .. } => {
+                self.status = "Replay stopped".to_string();
+            }
+            Mode::Idle => (),
+        }
+    }
+
+    fn start_replay(&mut self, path: &str, world: &mut World, input: &mut Input, time: &mut Time) {
+        match Session::load(path) {
+            Ok(session) => {
+                session.restore_camera(world);
+                input.reset();
+                time.reset();
+                self.status = format!("Replaying {path}");
+                self.mode = Mode::Replaying { session, frame: 0 };
+            }
+            Err(e) => {
+                self.status = format!("Failed to load session: {e}");
+                log::info!("{}", self.status);
+            }
+        }
+    }
+
+    /// Call once per frame in place of `Time::update`, before anything reads
+    /// input. Records this frame, or overrides it with the replayed one.
+    pub fn begin_frame(&mut self, world: &mut World, input: &mut Input, time: &mut Time) {
+        if let Some(path) = self.pending_replay.take() {
+            self.start_replay(&path, world, input, time);
+        }
+        if !self.is_replaying() {
+            time.update();
+        }
+
+        match &mut self.mode {
+            Mode::Idle => {
+                input.take_frame_events();
+            }
+            Mode::Recording(session) => {
+                session.frames.push(RecordedFrame {
+                    dt: time.real_delta_seconds,
+                    events: input.take_frame_events(),
+                });
+            }
+            Mode::Replaying { session, frame } => {
+                input.take_frame_events();
+                let Some(recorded) = session.frames.get(*frame) else {
+                    log::info!("Replay finished after {frame} frames");
+                    self.status = format!("Replay finished after {frame} frames");
+                    self.mode = Mode::Idle;
+                    time.update();
+                    return;
+                };
+                for event in &recorded.events {
+                    input.apply(*event);
+                }
+                time.advance(recorded.dt);
+                *frame += 1;
+            }
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, world: &mut World, input: &mut Input) {
+        ui.collapsing("Session Recording", |ui| {
+            ui.horizontal(|ui| {
+                ui.label("File: ");
+                ui.text_edit_singleline(&mut self.path);
+            });
+            ui.horizontal(|ui| {
+                if self.is_recording() || self.is_replaying() {
+                    if ui.button("Stop").clicked() {
+                        self.stop();
+                    }
+                } else {
+                    if ui.button("Record").clicked() {
+                        self.start_recording(world, input);
+                    }
+                    if ui.button("Replay").clicked() {
+                        self.queue_replay(&self.path.clone());
+                    }
+                }
+            });
+            if let Mode::Replaying { session, frame } = &self.mode {
+                ui.add(egui::ProgressBar::new(
+                    *frame as f32 / session.frames.len().max(1) as f32,
+                ));
+            }
+            if !self.status.is_empty() {
+                ui.label(&self.status);
+            }
+        });
+    }
+}
@@ -1,15 +1,190 @@
-pub struct Shader {
-    pub vertex_binary: Vec<u8>,
-    pub pixel_binary: Vec<u8>,
+use wgpu::naga;
+
+/// `shaders/model.slang` as WGSL, for builds where slangc didn't run.
+pub const FALLBACK_MODEL_WGSL: &str = include_str!("../shaders/fallback/model.wgsl");
+
+/// Where `available` looks for shaders to assign to models.
+pub const SHADER_DIR: &str = "shaders";
+
+/// First word of every SPIR-V module.
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+pub enum Shader {
+    SpirV {
+        vertex_binary: Vec<u8>,
+        pixel_binary: Vec<u8>,
+    },
+    /// A single WGSL module containing both `vsMain` and `psMain`.
+    Wgsl(String),
 }
 
 impl Shader {
     pub fn new(vertex_path: &str, pixel_path: &str) -> Self {
-        let vertex_binary = std::fs::read(vertex_path).unwrap();
-        let pixel_binary = std::fs::read(pixel_path).unwrap();
-        Shader {
+        let vertex_binary = crate::vfs::read(vertex_path).unwrap();
+        let pixel_binary = crate::vfs::read(pixel_path).unwrap();
+        Shader::SpirV {
             vertex_binary,
             pixel_binary,
         }
     }
+
+    /// Like `new`, but reports missing or malformed SPIR-V, e.g. left by a
+    /// failed slangc run, instead of panicking.
+    pub fn load(vertex_path: &str, pixel_path: &str) -> Result<Self, String> {
+        let read = |path: &str| {
+            let binary = crate::vfs::read(path).map_err(|e| format!("{path}: {e}"))?;
+            let magic = binary
+                .get(..4)
+                .map(|w| u32::from_le_bytes(w.try_into().unwrap()));
+            if binary.len() % 4 != 0 || magic != Some(SPIRV_MAGIC) {
+                return Err(format!("{path} isn't SPIR-V"));
+            }
+            Ok(binary)
+        };
+        Ok(Shader::SpirV {
+            vertex_binary: read(vertex_path)?,
+            pixel_binary: read(pixel_path)?,
+        })
+    }
+
+    pub fn from_wgsl(source: &str) -> Self {
+        Shader::Wgsl(source.to_string())
+    }
+
+    /// One permutation of a WGSL source, see `preprocess`.
+    pub fn from_wgsl_with_defines(source: &str, defines: &[&str]) -> Self {
+        Shader::Wgsl(preprocess(source, defines))
+    }
+
+    /// Whether the vertex stage defines `name`. SPIR-V stores entry point
+    /// names as nul-terminated literals, so a byte search is enough.
+    pub fn has_entry_point(&self, name: &str) -> bool {
+        match self {
+            Shader::SpirV { vertex_binary, .. } => {
+                let literal = format!("{name}\0");
+                vertex_binary
+                    .windows(literal.len())
+                    .any(|w| w == literal.as_bytes())
+            }
+            Shader::Wgsl(source) => source.contains(&format!("fn {name}(")),
+        }
+    }
+
+    /// Returns the vertex and pixel modules; for WGSL both are the same module.
+    pub fn create_modules(
+        &self,
+        device: &wgpu::Device,
+    ) -> (wgpu::ShaderModule, wgpu::ShaderModule) {
+        match self {
+            Shader::SpirV {
+                vertex_binary,
+                pixel_binary,
+            } => {
+                let vertex = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: None,
+                    source: wgpu::ShaderSource::SpirV(wgpu::util::make_spirv_raw(vertex_binary)),
+                });
+                let pixel = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: None,
+                    source: wgpu::ShaderSource::SpirV(wgpu::util::make_spirv_raw(pixel_binary)),
+                });
+                (vertex, pixel)
+            }
+            Shader::Wgsl(source) => {
+                let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: None,
+                    source: wgpu::ShaderSource::Wgsl(source.as_str().into()),
+                });
+                (module.clone(), module)
+            }
+        }
+    }
+}
+
+/// Names of the shaders in `SHADER_DIR` that `load_named` can load:
+/// `<name>.wgsl` sources and compiled `<name>.vert.spv` and
+/// `<name>.frag.spv` pairs, sorted.
+pub fn available() -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(SHADER_DIR) else {
+        return vec![];
+    };
+    let files: Vec<String> = entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .collect();
+    let mut names: Vec<String> = files
+        .iter()
+        .filter_map(|file| {
+            if let Some(name) = file.strip_suffix(".wgsl") {
+                return Some(name.to_string());
+            }
+            let name = file.strip_suffix(".vert.spv")?;
+            files
+                .contains(&format!("{name}.frag.spv"))
+                .then(|| name.to_string())
+        })
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Loads one of `available`'s shaders, the compiled pair over the WGSL
+/// source when both exist. WGSL that calls `octDecode` without defining it
+/// gets `mesh::OCTAHEDRAL_WGSL`, as the fallback model shader does.
+pub fn load_named(name: &str) -> Result<Shader, String> {
+    let path = |extension: &str| format!("{SHADER_DIR}/{name}.{extension}");
+    if let Ok(shader) = Shader::load(&path("vert.spv"), &path("frag.spv")) {
+        return Ok(shader);
+    }
+    let wgsl = path("wgsl");
+    let mut source = crate::vfs::read_to_string(&wgsl).map_err(|e| format!("{wgsl}: {e}"))?;
+    if source.contains("octDecode(") && !source.contains("fn octDecode(") {
+        source.push_str(crate::mesh::OCTAHEDRAL_WGSL);
+    }
+    Ok(Shader::Wgsl(source))
+}
+
+/// Parses and validates WGSL with naga, for errors that quote the source
+/// with `path` and line numbers; the device's own are terser.
+pub fn validate_wgsl(source: &str, path: &str) -> Result<naga::Module, String> {
+    let module = naga::front::wgsl::parse_str(source)
+        .map_err(|e| e.emit_to_string_with_path(source, path))?;
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(&module)
+    .map_err(|e| e.emit_to_string_with_path(source, path))?;
+    Ok(module)
+}
+
+/// Keeps the lines of `#ifdef NAME` / `#ifndef NAME` blocks (with optional
+/// `#else`, nestable) whose condition holds for `defines`, for WGSL, which
+/// has no preprocessor of its own. Dropped and directive lines become
+/// blank so error line numbers still match the source.
+pub fn preprocess(source: &str, defines: &[&str]) -> String {
+    // whether each open block, and every block around it, is active
+    let mut active = vec![true];
+    let mut out = String::with_capacity(source.len());
+    for line in source.lines() {
+        let trimmed = line.trim();
+        let enclosing = *active.last().unwrap();
+        if let Some(name) = trimmed.strip_prefix("#ifdef ") {
+            active.push(enclosing && defines.contains(&name.trim()));
+        } else if let Some(name) = trimmed.strip_prefix("#ifndef ") {
+            active.push(enclosing && !defines.contains(&name.trim()));
+        } else if trimmed == "#else" {
+            let taken = active.pop().unwrap();
+            let enclosing = *active.last().expect("#else without #ifdef");
+            active.push(enclosing && !taken);
+        } else if trimmed == "#endif" {
+            active.pop();
+            assert!(!active.is_empty(), "#endif without #ifdef");
+        } else if enclosing {
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+    assert_eq!(active.len(), 1, "unterminated #ifdef");
+    out
 }
@@ -0,0 +1,389 @@
+//! A Shadertoy-style panel: a fragment shader from a file drawn over a
+//! canvas in its own window, reloaded whenever the file is saved. The file
+//! only defines `fn mainImage(coord: vec2<f32>) -> vec4<f32>`; the uniforms
+//! and entry points are appended after it, so compile errors point at the
+//! file's own lines.
+
+use crate::app::State;
+use crate::plugin::{Plugin, PluginContext};
+use crate::shader;
+use crate::uniform::{UniformLayout, UniformType};
+use crate::world::World;
+use std::time::SystemTime;
+
+/// The file the playground starts with.
+pub const DEFAULT_PATH: &str = "playground/shader.wgsl";
+
+/// Written to a missing file from the window, to start from.
+const STARTER: &str = include_str!("../playground/shader.wgsl");
+
+/// What every playground shader can read.
+const UNIFORMS_WGSL: &str = r#"
+struct Playground {
+    // canvas size in pixels
+    resolution: vec2<f32>,
+    // xy: where the button was last held, zw: where it went down, negated
+    // once released, as on Shadertoy; all from the bottom-left
+    mouse: vec4<f32>,
+    // seconds since the playground started or was restarted
+    time: f32,
+    delta: f32,
+    frame: u32,
+};
+@group(0) @binding(0) var<uniform> playground: Playground;
+"#;
+
+/// A triangle covering the canvas, and `mainImage` per pixel.
+const ENTRY_WGSL: &str = r#"
+struct PlaygroundOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn playgroundVs(@builtin(vertex_index) i: u32) -> PlaygroundOut {
+    let uv = vec2(f32((i << 1u) & 2u), f32(i & 2u));
+    return PlaygroundOut(vec4(uv * 2.0 - 1.0, 0.0, 1.0), uv);
+}
+
+@fragment
+fn playgroundFs(in: PlaygroundOut) -> @location(0) vec4<f32> {
+    return mainImage(in.uv * playground.resolution);
+}
+"#;
+
+/// Smallest canvas the window shows, in points.
+const MIN_WIDTH: f32 = 256.0;
+
+struct Gpu {
+    format: wgpu::TextureFormat,
+    pipeline_layout: wgpu::PipelineLayout,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+pub struct ShaderPlayground {
+    pub path: String,
+    /// Stops `time` and `frame`.
+    pub paused: bool,
+    time: f32,
+    delta: f32,
+    frame: u32,
+    mouse: glam::Vec4,
+    layout: UniformLayout,
+    gpu: Option<Gpu>,
+    /// The last shader that compiled; it keeps drawing while the file
+    /// has errors.
+    pipeline: Option<wgpu::RenderPipeline>,
+    /// Of the file when it was last read, `None` until it has been;
+    /// missing files have no time.
+    modified: Option<Option<SystemTime>>,
+    error: Option<String>,
+}
+
+impl Default for ShaderPlayground {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShaderPlayground {
+    pub fn new() -> Self {
+        ShaderPlayground {
+            path: DEFAULT_PATH.to_string(),
+            paused: false,
+            time: 0.0,
+            delta: 0.0,
+            frame: 0,
+            mouse: glam::Vec4::ZERO,
+            layout: UniformLayout::new()
+                .field("resolution", UniformType::Vec2)
+                .field("mouse", UniformType::Vec4)
+                .field("time", UniformType::F32)
+                .field("delta", UniformType::F32)
+                .field("frame", UniformType::U32),
+            gpu: None,
+            pipeline: None,
+            modified: None,
+            error: None,
+        }
+    }
+
+    /// The last compile or read error, if the file has one.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Whether a shader has compiled and the canvas draws.
+    pub fn is_ready(&self) -> bool {
+        self.pipeline.is_some()
+    }
+
+    pub fn restart(&mut self) {
+        self.time = 0.0;
+        self.frame = 0;
+    }
+
+    /// Rereads the file if it changed since it was last read.
+    pub fn poll(&mut self, device: &wgpu::Device) {
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|m| m.modified())
+            .ok();
+        if self.modified == Some(modified) {
+            return;
+        }
+        self.modified = Some(modified);
+        self.reload(device);
+    }
+
+    /// Reads and compiles the file, keeping the previous pipeline when it
+    /// fails.
+    pub fn reload(&mut self, device: &wgpu::Device) {
+        let result = std::fs::read_to_string(&self.path)
+            .map_err(|e| format!("{}: {e}", self.path))
+            .and_then(|source| self.compile(device, &source));
+        match result {
+            Ok(pipeline) => {
+                log::info!("Compiled {}", self.path);
+                self.pipeline = Some(pipeline);
+                self.error = None;
+            }
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    /// `source` with the uniforms and entry points, as a pipeline for the
+    /// UI's format. Errors are naga's, with the file's line numbers.
+    fn compile(&self, device: &wgpu::Device, source: &str) -> Result<wgpu::RenderPipeline, String> {
+        let Some(gpu) = &self.gpu else {
+            return Err("The playground isn't built yet".to_string());
+        };
+        let source = format!("{source}\n{UNIFORMS_WGSL}{ENTRY_WGSL}");
+        shader::validate_wgsl(&source, &self.path)?;
+
+        // anything naga let through that the device doesn't support
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Shader Playground"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shader Playground"),
+            layout: Some(&gpu.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("playgroundVs"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("playgroundFs"),
+                compilation_options: Default::default(),
+                targets: &[Some(gpu.format.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        match pollster::block_on(device.pop_error_scope()) {
+            Some(error) => Err(error.to_string()),
+            None => Ok(pipeline),
+        }
+    }
+
+    fn uniform(&self, resolution: glam::Vec2) -> Vec<u8> {
+        let mut data = self.layout.zeroed();
+        self.layout
+            .write(&mut data, "resolution", resolution.to_array());
+        self.layout.write(&mut data, "mouse", self.mouse.to_array());
+        self.layout.write(&mut data, "time", self.time);
+        self.layout.write(&mut data, "delta", self.delta);
+        self.layout.write(&mut data, "frame", self.frame);
+        data
+    }
+
+    /// The canvas: the shader drawn by egui's pass through a paint
+    /// callback, with drags over it as the mouse uniform.
+    fn canvas_ui(&mut self, ui: &mut egui::Ui, state: &State) {
+        let (Some(gpu), Some(pipeline)) = (&self.gpu, &self.pipeline) else {
+            return;
+        };
+        let width = ui.available_width().max(MIN_WIDTH);
+        let (rect, response) =
+            ui.allocate_exact_size(egui::vec2(width, width * 9.0 / 16.0), egui::Sense::drag());
+        let ppp = ui.ctx().pixels_per_point();
+        let resolution = glam::vec2(rect.width(), rect.height()) * ppp;
+        // canvas pixels from the bottom-left
+        let to_canvas = |pos: egui::Pos2| {
+            let pos = (pos - rect.min) * ppp;
+            glam::vec2(pos.x, resolution.y - pos.y)
+        };
+        if let Some(pos) = response.interact_pointer_pos() {
+            let pos = to_canvas(pos);
+            if response.drag_started() {
+                self.mouse.z = pos.x;
+                self.mouse.w = pos.y;
+            }
+            self.mouse.x = pos.x;
+            self.mouse.y = pos.y;
+        }
+        if response.drag_stopped() {
+            self.mouse.z = -self.mouse.z.abs();
+            self.mouse.w = -self.mouse.w.abs();
+        }
+        state
+            .queue
+            .write_buffer(&gpu.buffer, 0, &self.uniform(resolution));
+        ui.painter().add(egui_wgpu::Callback::new_paint_callback(
+            rect,
+            Canvas {
+                pipeline: pipeline.clone(),
+                bind_group: gpu.bind_group.clone(),
+            },
+        ));
+    }
+}
+
+struct Canvas {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+}
+
+impl egui_wgpu::CallbackTrait for Canvas {
+    fn paint(
+        &self,
+        _info: egui::PaintCallbackInfo,
+        render_pass: &mut wgpu::RenderPass<'static>,
+        _callback_resources: &egui_wgpu::CallbackResources,
+    ) {
+        // egui has set the viewport to the canvas
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+impl Plugin for ShaderPlayground {
+    fn name(&self) -> &str {
+        "Shader Playground"
+    }
+
+    fn build(&mut self, ctx: &mut PluginContext) {
+        self.layout.assert_wgsl(UNIFORMS_WGSL, "Playground");
+        let device = &ctx.state.device;
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Shader Playground"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shader Playground"),
+            size: self.layout.size(),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Shader Playground"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shader Playground"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        self.gpu = Some(Gpu {
+            format: ctx.state.ui_format(),
+            pipeline_layout,
+            buffer,
+            bind_group,
+        });
+    }
+
+    fn update(&mut self, ctx: &mut PluginContext) {
+        if self.paused {
+            self.delta = 0.0;
+            return;
+        }
+        self.delta = ctx.time.real_delta_seconds;
+        self.time += self.delta;
+        self.frame = self.frame.wrapping_add(1);
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, state: &State, _world: &mut World) {
+        egui::Window::new("Shader Playground")
+            .default_open(false)
+            .resizable(true)
+            .show(ctx, |ui| {
+                // only watched while open
+                self.poll(&state.device);
+                match file_ui(ui, &mut self.path, STARTER) {
+                    Ok(true) => self.modified = None,
+                    Ok(false) => {}
+                    Err(e) => self.error = Some(e),
+                }
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.paused, "Pause");
+                    if ui.button("Restart").clicked() {
+                        self.restart();
+                    }
+                    ui.label(format!("{:.2} s, frame {}", self.time, self.frame));
+                });
+                if let Some(error) = &self.error {
+                    error_ui(ui, error);
+                }
+                self.canvas_ui(ui, state);
+            });
+    }
+}
+
+/// The path to a playground's file, and a button to start a missing one
+/// from `starter`. Returns true when the path was edited, so the file
+/// should be read again.
+pub(crate) fn file_ui(ui: &mut egui::Ui, path: &mut String, starter: &str) -> Result<bool, String> {
+    ui.horizontal(|ui| {
+        ui.label("File");
+        let edited = ui.text_edit_singleline(path).lost_focus();
+        let file = std::path::Path::new(path.as_str());
+        if !file.exists()
+            && ui
+                .button("Create")
+                .on_hover_text("Start the file from a template")
+                .clicked()
+        {
+            if let Some(dir) = file.parent() {
+                let _ = std::fs::create_dir_all(dir);
+            }
+            std::fs::write(file, starter).map_err(|e| format!("{path}: {e}"))?;
+        }
+        Ok(edited)
+    })
+    .inner
+}
+
+/// A compile error, in the monospace it was laid out for.
+pub(crate) fn error_ui(ui: &mut egui::Ui, error: &str) {
+    egui::ScrollArea::vertical()
+        .max_height(160.0)
+        .show(ui, |ui| {
+            ui.label(
+                egui::RichText::new(error)
+                    .monospace()
+                    .color(ui.visuals().error_fg_color),
+            );
+        });
+}
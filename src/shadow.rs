@@ -0,0 +1,533 @@
+//! Directional light shadow map with two ways of filtering it: percentage
+//! closer filtering of the depth map, or variance shadow maps, which store
+//! depth moments that can be blurred like any other texture and sampled
+//! with Chebyshev's bound.
+//!
+//! Materials receive shadows by binding `ShadowMap::binding` as one group,
+//! declaring the textures `SHADOW_WGSL` expects at that group, appending
+//! `uniform_fields` to a uniform of their own, and calling `shadow`.
+
+use crate::app::State;
+use crate::blur::{Blur, BlurSettings};
+use crate::material::Binding;
+use crate::mesh::{VertexEncoding, OCTAHEDRAL_WGSL};
+use crate::sampler::SamplerDesc;
+use crate::texture::Texture;
+use crate::uniform::{UniformLayout, UniformType};
+use crate::world::World;
+use std::sync::Arc;
+use wgpu::util::DeviceExt;
+
+/// Lookup functions for materials. They read these globals, which the
+/// material declares at the group it binds `ShadowMap::binding` to:
+///
+/// ```wgsl
+/// @group(N) @binding(0) var shadow_depth: texture_depth_2d;
+/// @group(N) @binding(1) var shadow_depth_sampler: sampler_comparison;
+/// @group(N) @binding(2) var shadow_moments: texture_2d<f32>;
+/// @group(N) @binding(3) var shadow_moments_sampler: sampler;
+/// ```
+pub const SHADOW_WGSL: &str = r#"
+// Shadow map UV in xy, light depth in z.
+fn shadow_coord(light_view_proj: mat4x4<f32>, world_pos: vec3<f32>) -> vec3<f32> {
+    let clip = light_view_proj * vec4(world_pos, 1.0);
+    let ndc = clip.xyz / clip.w;
+    return vec3(ndc.xy * vec2(0.5, -0.5) + 0.5, ndc.z);
+}
+
+fn shadow_pcf(coord: vec3<f32>, bias: f32, radius: i32) -> f32 {
+    let texel = 1.0 / vec2<f32>(textureDimensions(shadow_depth));
+    var lit = 0.0;
+    for (var y = -radius; y <= radius; y++) {
+        for (var x = -radius; x <= radius; x++) {
+            let uv = coord.xy + vec2(f32(x), f32(y)) * texel;
+            lit += textureSampleCompareLevel(shadow_depth, shadow_depth_sampler, uv, coord.z - bias);
+        }
+    }
+    let taps = 2 * radius + 1;
+    return lit / f32(taps * taps);
+}
+
+fn shadow_variance(coord: vec3<f32>, bias: f32, min_variance: f32, light_bleed: f32) -> f32 {
+    let moments = textureSampleLevel(shadow_moments, shadow_moments_sampler, coord.xy, 0.0).xy;
+    let depth = coord.z - bias;
+    if depth <= moments.x {
+        return 1.0;
+    }
+    let variance = max(moments.y - moments.x * moments.x, min_variance);
+    let delta = depth - moments.x;
+    let p_max = variance / (variance + delta * delta);
+    // the bound overestimates light where occluders overlap; cutting off
+    // its low end trades that bleeding for harder penumbrae
+    return clamp((p_max - light_bleed) / (1.0 - light_bleed), 0.0, 1.0);
+}
+
+// 1 where lit, 0 in shadow. `shadow_filter` is `ShadowFilter as u32`.
+fn shadow(
+    light_view_proj: mat4x4<f32>,
+    world_pos: vec3<f32>,
+    shadow_filter: u32,
+    bias: f32,
+    pcf_radius: i32,
+    min_variance: f32,
+    light_bleed: f32,
+) -> f32 {
+    let coord = shadow_coord(light_view_proj, world_pos);
+    // outside the light's frustum nothing is known to occlude
+    if any(coord.xy < vec2(0.0)) || any(coord.xy > vec2(1.0)) || coord.z > 1.0 {
+        return 1.0;
+    }
+    if shadow_filter == 1u {
+        return shadow_variance(coord, bias, min_variance, light_bleed);
+    }
+    return shadow_pcf(coord, bias, pcf_radius);
+}
+"#;
+
+const CASTER_SHADER: &str = r#"
+struct Light { view_proj: mat4x4<f32> };
+@group(0) @binding(0) var<uniform> light: Light;
+
+struct Model {
+    model: mat4x4<f32>,
+    base_color: vec4<f32>,
+    alpha_cutoff: f32,
+};
+@group(1) @binding(0) var<uniform> model: Model;
+
+@vertex
+fn vsMain(@location(0) pos: vec3<f32>) -> @builtin(position) vec4<f32> {
+    return light.view_proj * model.model * vec4(pos, 1.0);
+}
+
+@vertex
+fn vsMainPacked(@location(0) pos: vec3<f32>, @location(1) normal: vec2<f32>) -> @builtin(position) vec4<f32> {
+    return light.view_proj * model.model * vec4(pos, 1.0);
+}
+
+// depth and depth squared, for the variance filter
+@fragment
+fn psMoments(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+    if (model.alpha_cutoff > 0.0 && model.base_color.a < model.alpha_cutoff) {
+        discard;
+    }
+    return vec4(pos.z, pos.z * pos.z, 0.0, 1.0);
+}
+
+@fragment
+fn psDepth() {
+    if (model.alpha_cutoff > 0.0 && model.base_color.a < model.alpha_cutoff) {
+        discard;
+    }
+}
+"#;
+
+pub(crate) const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+/// Half floats are renderable and filterable everywhere; 32-bit moments
+/// would need an optional feature to filter.
+const MOMENTS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// How shadow lookups filter the map. The discriminants match the
+/// `shadow_filter` argument of the WGSL `shadow`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShadowFilter {
+    /// Averages depth comparisons over a square of texels.
+    Pcf = 0,
+    /// Chebyshev's bound on blurred depth moments.
+    Variance = 1,
+}
+
+impl ShadowFilter {
+    pub const ALL: [ShadowFilter; 2] = [ShadowFilter::Pcf, ShadowFilter::Variance];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ShadowFilter::Pcf => "PCF",
+            ShadowFilter::Variance => "Variance",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShadowSettings {
+    pub filter: ShadowFilter,
+    /// Subtracted from the receiver's light depth, which spans the light's
+    /// whole frustum from 0 to 1.
+    pub depth_bias: f32,
+    /// PCF taps either side of the center texel.
+    pub pcf_radius: u32,
+    /// Floor for the variance, against acne from half-float moments.
+    pub min_variance: f32,
+    /// Fraction of the variance bound treated as full shadow.
+    pub light_bleed: f32,
+    /// Applied to the moments; only used by the variance filter.
+    pub blur: BlurSettings,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShadowSettings {
+    pub fn new() -> Self {
+        ShadowSettings {
+            filter: ShadowFilter::Pcf,
+            depth_bias: 0.002,
+            pcf_radius: 1,
+            min_variance: 0.00002,
+            light_bleed: 0.3,
+            blur: BlurSettings {
+                radius: 3,
+                sigma: 1.5,
+                half_res: false,
+            },
+        }
+    }
+
+    /// Returns true when anything changed.
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            for filter in ShadowFilter::ALL {
+                changed |= ui
+                    .selectable_value(&mut self.filter, filter, filter.label())
+                    .changed();
+            }
+        });
+        changed |= ui
+            .add(
+                egui::Slider::new(&mut self.depth_bias, 0.0001..=0.05)
+                    .logarithmic(true)
+                    .text("Depth bias"),
+            )
+            .changed();
+        match self.filter {
+            ShadowFilter::Pcf => {
+                changed |= ui
+                    .add(egui::Slider::new(&mut self.pcf_radius, 0..=4).text("PCF radius"))
+                    .changed();
+            }
+            ShadowFilter::Variance => {
+                changed |= ui
+                    .add(
+                        egui::Slider::new(&mut self.min_variance, 0.000001..=0.001)
+                            .logarithmic(true)
+                            .text("Min variance"),
+                    )
+                    .changed();
+                changed |= ui
+                    .add(
+                        egui::Slider::new(&mut self.light_bleed, 0.0..=0.9).text("Bleed reduction"),
+                    )
+                    .changed();
+                // full resolution only: the moments are blurred in place
+                let mut blur = self.blur;
+                changed |= blur.ui(ui);
+                self.blur = BlurSettings {
+                    half_res: false,
+                    ..blur
+                };
+            }
+        }
+        changed
+    }
+}
+
+/// A square shadow map for one directional light, and the passes that
+/// render and filter it.
+pub struct ShadowMap {
+    pub settings: ShadowSettings,
+    pub size: u32,
+    depth: Arc<Texture>,
+    moments: Arc<Texture>,
+    light_view_proj: glam::Mat4,
+    light_buffer: wgpu::Buffer,
+    light_bind_group: wgpu::BindGroup,
+    /// Indexed by `ShadowFilter`, then by vertex encoding.
+    pipelines: [[wgpu::RenderPipeline; 2]; 2],
+    blur: Blur,
+}
+
+impl ShadowMap {
+    pub fn new(state: &State, size: u32) -> Self {
+        let device = &state.device;
+        let target = |label, format| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+        };
+        let clamp = SamplerDesc {
+            anisotropy: 1,
+            ..SamplerDesc::new().with_address_mode(wgpu::AddressMode::ClampToEdge)
+        };
+        let mut depth = Texture::from_texture(state, target("Shadow Depth", DEPTH_FORMAT));
+        // linear comparisons give 2x2 PCF for free
+        depth.sampler_desc = SamplerDesc {
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..clamp
+        };
+        let mut moments = Texture::from_texture(state, target("Shadow Moments", MOMENTS_FORMAT));
+        moments.sampler_desc = clamp;
+
+        let light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Shadow Light"),
+            contents: bytemuck::cast_slice(&glam::Mat4::IDENTITY.to_cols_array()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let light_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Shadow Light"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Shadow Light"),
+            layout: &light_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: light_buffer.as_entire_binding(),
+            }],
+        });
+
+        let pipelines =
+            ShadowFilter::ALL.map(|filter| caster_pipelines(state, &light_layout, filter));
+
+        ShadowMap {
+            settings: ShadowSettings::new(),
+            size,
+            depth: Arc::new(depth),
+            moments: Arc::new(moments),
+            light_view_proj: glam::Mat4::IDENTITY,
+            light_buffer,
+            light_bind_group,
+            pipelines,
+            blur: Blur::new(device, MOMENTS_FORMAT),
+        }
+    }
+
+    /// The depth map and the moments, with the samplers `SHADOW_WGSL`
+    /// expects, as one material group.
+    pub fn binding(&self) -> Binding {
+        Binding::textures(
+            vec![self.depth.clone(), self.moments.clone()],
+            wgpu::ShaderStages::FRAGMENT,
+        )
+    }
+
+    /// Appends the arguments of the WGSL `shadow` to a material's uniform.
+    pub fn uniform_fields(layout: UniformLayout) -> UniformLayout {
+        layout
+            .field("light_view_proj", UniformType::Mat4)
+            .field("shadow_filter", UniformType::U32)
+            .field("shadow_bias", UniformType::F32)
+            .field("pcf_radius", UniformType::I32)
+            .field("min_variance", UniformType::F32)
+            .field("light_bleed", UniformType::F32)
+    }
+
+    /// Fills the fields added by `uniform_fields`.
+    pub fn write_uniform(&self, layout: &UniformLayout, data: &mut [u8]) {
+        let settings = &self.settings;
+        layout.write(
+            data,
+            "light_view_proj",
+            self.light_view_proj.to_cols_array(),
+        );
+        layout.write(data, "shadow_filter", settings.filter as u32);
+        layout.write(data, "shadow_bias", settings.depth_bias);
+        layout.write(data, "pcf_radius", settings.pcf_radius as i32);
+        layout.write(data, "min_variance", settings.min_variance);
+        layout.write(data, "light_bleed", settings.light_bleed);
+    }
+
+    pub fn light_view_proj(&self) -> glam::Mat4 {
+        self.light_view_proj
+    }
+
+    /// Points the light along `direction`, covering a sphere of `radius`
+    /// around `center`.
+    pub fn update(
+        &mut self,
+        queue: &wgpu::Queue,
+        direction: glam::Vec3,
+        center: glam::Vec3,
+        radius: f32,
+    ) {
+        let direction = direction.normalize();
+        let up = if direction.y.abs() > 0.99 {
+            glam::Vec3::Z
+        } else {
+            glam::Vec3::Y
+        };
+        let view = glam::Mat4::look_at_rh(center - direction * radius * 2.0, center, up);
+        let projection =
+            glam::Mat4::orthographic_rh(-radius, radius, -radius, radius, radius, radius * 3.0);
+        self.light_view_proj = projection * view;
+        queue.write_buffer(
+            &self.light_buffer,
+            0,
+            bytemuck::cast_slice(&self.light_view_proj.to_cols_array()),
+        );
+    }
+
+    /// Renders every visible model into the map and, for the variance
+    /// filter, blurs the moments. Record it before the scene pass, e.g.
+    /// from `Plugin::prepare`.
+    pub fn encode(&self, state: &State, world: &World, encoder: &mut wgpu::CommandEncoder) {
+        let filter = self.settings.filter;
+        let moments = [Some(wgpu::RenderPassColorAttachment {
+            view: &self.moments.view,
+            depth_slice: None,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                // empty texels read as the far plane
+                load: wgpu::LoadOp::Clear(wgpu::Color {
+                    r: 1.0,
+                    g: 1.0,
+                    b: 0.0,
+                    a: 1.0,
+                }),
+                store: wgpu::StoreOp::Store,
+            },
+        })];
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("shadow casters"),
+                color_attachments: match filter {
+                    ShadowFilter::Pcf => &[],
+                    ShadowFilter::Variance => &moments,
+                },
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_bind_group(0, &self.light_bind_group, &[]);
+            let mut draws = 0;
+            for model in world.models.iter().filter(|m| m.is_visible()) {
+                let encoding = match model.mesh.encoding {
+                    VertexEncoding::Full => 0,
+                    VertexEncoding::Packed => 1,
+                };
+                pass.set_pipeline(&self.pipelines[filter as usize][encoding]);
+                pass.set_bind_group(1, model.bind_group(), &[]);
+                pass.set_vertex_buffer(0, model.mesh.vertex_buffer.slice(..));
+                pass.set_index_buffer(model.mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                pass.draw_indexed(0..model.mesh.index_count, 0, 0..1);
+                draws += 1;
+            }
+            let depth = &self.depth.texture;
+            state.profiler.record_draws(draws, draws);
+            state.profiler.record_target(depth.width(), depth.height());
+        }
+
+        if filter == ShadowFilter::Variance {
+            let view = &self.moments.view;
+            self.blur.encode_into(
+                state,
+                encoder,
+                view,
+                view,
+                self.size,
+                self.size,
+                &self.settings.blur,
+            );
+        }
+    }
+}
+
+/// Depth-only (PCF) or moment-writing (variance) pipelines for drawing
+/// shadow casters, for full and packed vertices. Group 0 holds the light's
+/// view-projection, as laid out by `light_layout`.
+pub(crate) fn caster_pipelines(
+    state: &State,
+    light_layout: &wgpu::BindGroupLayout,
+    filter: ShadowFilter,
+) -> [wgpu::RenderPipeline; 2] {
+    let device = &state.device;
+    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Shadow Casters"),
+        source: wgpu::ShaderSource::Wgsl(format!("{CASTER_SHADER}{OCTAHEDRAL_WGSL}").into()),
+    });
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Shadow Casters"),
+        bind_group_layouts: &[light_layout, &state.model_bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    let pipeline = |encoding: VertexEncoding| {
+        let (entry_point, targets, bias) = match filter {
+            ShadowFilter::Pcf => (
+                "psDepth",
+                &[][..],
+                // slope-scaled, so surfaces facing away from the light
+                // don't shadow themselves
+                wgpu::DepthBiasState {
+                    constant: 0,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                },
+            ),
+            // the moments carry the unbiased depth
+            ShadowFilter::Variance => (
+                "psMoments",
+                &[Some(MOMENTS_FORMAT.into())][..],
+                wgpu::DepthBiasState::default(),
+            ),
+        };
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shadow Casters"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some(encoding.entry_point()),
+                buffers: &[encoding.layout()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                targets,
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias,
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    };
+    [VertexEncoding::Full, VertexEncoding::Packed].map(pipeline)
+}
@@ -0,0 +1,636 @@
+//! Spot and point lights whose shadow maps share one depth texture. Each
+//! frame the lights' views are packed into the atlas by a buddy allocator;
+//! when it runs out of room, later views get smaller tiles and finally no
+//! shadow, so shadow memory stays at the atlas size however many lights
+//! there are.
+
+use crate::app::State;
+use crate::material::Binding;
+use crate::mesh::VertexEncoding;
+use crate::sampler::SamplerDesc;
+use crate::shadow::{self, ShadowFilter};
+use crate::texture::Texture;
+use crate::world::World;
+use std::sync::Arc;
+
+/// Lights the uniform has room for; the rest are ignored.
+pub const MAX_LIGHTS: usize = 16;
+/// Shadow views the uniform has room for: a spot light takes one, a point
+/// light six.
+pub const MAX_SHADOW_VIEWS: usize = 64;
+
+/// Types, and functions reading these globals, which the material declares
+/// at the groups it binds `ShadowAtlas::bindings` to:
+///
+/// ```wgsl
+/// @group(N) @binding(0) var<uniform> local_lights: LocalLights;
+/// @group(M) @binding(0) var shadow_atlas: texture_depth_2d;
+/// @group(M) @binding(1) var shadow_atlas_sampler: sampler_comparison;
+/// ```
+pub const SHADOW_ATLAS_WGSL: &str = r#"
+struct LocalLight {
+    position: vec3<f32>,
+    range: f32,
+    color: vec3<f32>,
+    // 0: spot, 1: point
+    kind: u32,
+    direction: vec3<f32>,
+    cos_angle: f32,
+    first_view: i32,
+    // 0 when the light got no room in the atlas
+    view_count: i32,
+};
+
+struct ShadowView {
+    view_proj: mat4x4<f32>,
+    // atlas UV offset in xy, scale in zw
+    rect: vec4<f32>,
+};
+
+struct LocalLights {
+    count: u32,
+    bias: f32,
+    pcf_radius: i32,
+    lights: array<LocalLight, 16>,
+    views: array<ShadowView, 64>,
+};
+
+fn atlas_shadow(view: ShadowView, world_pos: vec3<f32>) -> f32 {
+    let clip = view.view_proj * vec4(world_pos, 1.0);
+    let ndc = clip.xyz / clip.w;
+    let uv = ndc.xy * vec2(0.5, -0.5) + 0.5;
+    let atlas_size = vec2<f32>(textureDimensions(shadow_atlas));
+    let texel = 1.0 / atlas_size;
+    // keep filter taps inside the tile so neighbours don't bleed in
+    let half_texel = 0.5 / (view.rect.zw * atlas_size);
+    let radius = local_lights.pcf_radius;
+    var lit = 0.0;
+    for (var y = -radius; y <= radius; y++) {
+        for (var x = -radius; x <= radius; x++) {
+            let tile_uv = clamp(uv + vec2(f32(x), f32(y)) * texel / view.rect.zw, half_texel, 1.0 - half_texel);
+            let atlas_uv = view.rect.xy + tile_uv * view.rect.zw;
+            lit += textureSampleCompareLevel(shadow_atlas, shadow_atlas_sampler, atlas_uv, ndc.z - local_lights.bias);
+        }
+    }
+    let taps = 2 * radius + 1;
+    return lit / f32(taps * taps);
+}
+
+// Cube face views are stored +X, -X, +Y, -Y, +Z, -Z.
+fn point_face(to_fragment: vec3<f32>) -> i32 {
+    let a = abs(to_fragment);
+    if a.x >= a.y && a.x >= a.z {
+        return select(0, 1, to_fragment.x < 0.0);
+    }
+    if a.y >= a.z {
+        return select(2, 3, to_fragment.y < 0.0);
+    }
+    return select(4, 5, to_fragment.z < 0.0);
+}
+
+// Diffuse light reaching a surface from light `i`, shadowed.
+fn local_light(i: u32, world_pos: vec3<f32>, n: vec3<f32>) -> vec3<f32> {
+    let light = local_lights.lights[i];
+    let to_fragment = world_pos - light.position;
+    let distance = length(to_fragment);
+    let l = -to_fragment / distance;
+    let falloff = pow(clamp(1.0 - distance / light.range, 0.0, 1.0), 2.0);
+    var cone = 1.0;
+    var view = light.first_view;
+    if light.kind == 0u {
+        cone = smoothstep(light.cos_angle, mix(light.cos_angle, 1.0, 0.2), dot(-l, light.direction));
+    } else {
+        view += point_face(to_fragment);
+    }
+    var lit = 1.0;
+    if light.view_count > 0 {
+        lit = atlas_shadow(local_lights.views[view], world_pos);
+    }
+    return light.color * max(dot(n, l), 0.0) * falloff * cone * lit;
+}
+"#;
+
+/// A square of the atlas, in texels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtlasRect {
+    pub x: u32,
+    pub y: u32,
+    pub size: u32,
+}
+
+impl AtlasRect {
+    /// UV offset and scale of the rect within an atlas `atlas_size` wide.
+    pub fn uv_transform(&self, atlas_size: u32) -> [f32; 4] {
+        let atlas_size = atlas_size as f32;
+        [
+            self.x as f32 / atlas_size,
+            self.y as f32 / atlas_size,
+            self.size as f32 / atlas_size,
+            self.size as f32 / atlas_size,
+        ]
+    }
+}
+
+/// Buddy allocator of power-of-two squares within a square atlas. Freeing
+/// all four quarters of a square makes the whole square free again.
+///
+/// ```
+/// use rust_graphics_sandbox::shadow_atlas::AtlasAllocator;
+///
+/// let mut atlas = AtlasAllocator::new(1024, 128);
+/// let big = atlas.allocate(512).unwrap();
+/// // the other three quarters fill the atlas
+/// for _ in 0..3 {
+///     atlas.allocate(512).unwrap();
+/// }
+/// assert_eq!(atlas.allocate(512), None);
+/// atlas.free(big);
+/// assert_eq!(atlas.allocate_or_smaller(1024).map(|r| r.size), Some(512));
+/// ```
+#[derive(Debug, Clone)]
+pub struct AtlasAllocator {
+    size: u32,
+    min_tile: u32,
+    /// Free squares' corners, by level; level `l` squares are `size >> l`
+    /// wide.
+    free: Vec<Vec<(u32, u32)>>,
+}
+
+impl AtlasAllocator {
+    /// `size` and `min_tile` are rounded up to powers of two.
+    pub fn new(size: u32, min_tile: u32) -> Self {
+        let size = size.next_power_of_two();
+        let min_tile = min_tile.next_power_of_two().min(size);
+        let levels = (size / min_tile).trailing_zeros() as usize + 1;
+        let mut allocator = AtlasAllocator {
+            size,
+            min_tile,
+            free: vec![vec![]; levels],
+        };
+        allocator.clear();
+        allocator
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn min_tile(&self) -> u32 {
+        self.min_tile
+    }
+
+    /// Frees everything.
+    pub fn clear(&mut self) {
+        for level in &mut self.free {
+            level.clear();
+        }
+        self.free[0].push((0, 0));
+    }
+
+    fn level(&self, size: u32) -> usize {
+        (self.size / size).trailing_zeros() as usize
+    }
+
+    /// A square at least `size` wide, rounded up to a power of two between
+    /// the minimum tile and the atlas size. `None` when no such square is
+    /// free.
+    pub fn allocate(&mut self, size: u32) -> Option<AtlasRect> {
+        let size = size.next_power_of_two().clamp(self.min_tile, self.size);
+        let level = self.level(size);
+        // the smallest free square that fits, split down to size
+        let from = (0..=level).rev().find(|&l| !self.free[l].is_empty())?;
+        let (x, y) = self.free[from].pop().unwrap();
+        for l in from..level {
+            let half = self.size >> (l + 1);
+            self.free[l + 1].extend([(x + half, y), (x, y + half), (x + half, y + half)]);
+        }
+        Some(AtlasRect { x, y, size })
+    }
+
+    /// Like `allocate`, halving the size until something fits.
+    pub fn allocate_or_smaller(&mut self, size: u32) -> Option<AtlasRect> {
+        let mut size = size.next_power_of_two().clamp(self.min_tile, self.size);
+        loop {
+            if let Some(rect) = self.allocate(size) {
+                return Some(rect);
+            }
+            if size == self.min_tile {
+                return None;
+            }
+            size /= 2;
+        }
+    }
+
+    pub fn free(&mut self, rect: AtlasRect) {
+        let (mut x, mut y) = (rect.x, rect.y);
+        let mut level = self.level(rect.size);
+        // merge with the three buddies while they're all free
+        while level > 0 {
+            let parent = self.size >> (level - 1);
+            let (px, py) = (x / parent * parent, y / parent * parent);
+            let half = parent / 2;
+            let buddies = [
+                (px, py),
+                (px + half, py),
+                (px, py + half),
+                (px + half, py + half),
+            ];
+            let free = &mut self.free[level];
+            let all_free = buddies
+                .iter()
+                .filter(|&&b| b != (x, y))
+                .all(|b| free.contains(b));
+            if !all_free {
+                break;
+            }
+            free.retain(|b| !buddies.contains(b));
+            (x, y) = (px, py);
+            level -= 1;
+        }
+        self.free[level].push((x, y));
+    }
+
+    /// Texels not in any allocation.
+    pub fn free_area(&self) -> u64 {
+        self.free
+            .iter()
+            .enumerate()
+            .map(|(level, squares)| squares.len() as u64 * (self.size as u64 >> level).pow(2))
+            .sum()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LocalLightKind {
+    /// Shines along `direction` within `angle` of it, in radians.
+    Spot {
+        direction: glam::Vec3,
+        angle: f32,
+    },
+    Point,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LocalLight {
+    pub kind: LocalLightKind,
+    pub position: glam::Vec3,
+    pub color: [f32; 3],
+    /// Distance where the light fades out, and the far plane of its
+    /// shadow views.
+    pub range: f32,
+    /// Requested tile size per view; 0 casts no shadow.
+    pub shadow_resolution: u32,
+}
+
+impl LocalLight {
+    pub fn spot(position: glam::Vec3, direction: glam::Vec3, angle: f32, range: f32) -> Self {
+        LocalLight {
+            kind: LocalLightKind::Spot {
+                direction: direction.normalize(),
+                angle,
+            },
+            position,
+            color: [1.0; 3],
+            range,
+            shadow_resolution: 512,
+        }
+    }
+
+    pub fn point(position: glam::Vec3, range: f32) -> Self {
+        LocalLight {
+            kind: LocalLightKind::Point,
+            position,
+            color: [1.0; 3],
+            range,
+            shadow_resolution: 256,
+        }
+    }
+
+    /// View-projections of the light's shadow views: one for a spot light,
+    /// six cube faces for a point light.
+    pub fn view_projs(&self) -> Vec<glam::Mat4> {
+        let near = (self.range * 0.01).max(0.01);
+        let look = |direction: glam::Vec3, up: glam::Vec3, fov: f32| {
+            glam::Mat4::perspective_rh(fov, 1.0, near, self.range)
+                * glam::Mat4::look_at_rh(self.position, self.position + direction, up)
+        };
+        match self.kind {
+            LocalLightKind::Spot { direction, angle } => {
+                let up = if direction.y.abs() > 0.99 {
+                    glam::Vec3::Z
+                } else {
+                    glam::Vec3::Y
+                };
+                vec![look(direction, up, (angle * 2.0).min(3.0))]
+            }
+            LocalLightKind::Point => [
+                (glam::Vec3::X, -glam::Vec3::Y),
+                (-glam::Vec3::X, -glam::Vec3::Y),
+                (glam::Vec3::Y, glam::Vec3::Z),
+                (-glam::Vec3::Y, -glam::Vec3::Z),
+                (glam::Vec3::Z, -glam::Vec3::Y),
+                (-glam::Vec3::Z, -glam::Vec3::Y),
+            ]
+            .map(|(direction, up)| look(direction, up, std::f32::consts::FRAC_PI_2))
+            .to_vec(),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct GpuLight {
+    position: [f32; 3],
+    range: f32,
+    color: [f32; 3],
+    kind: u32,
+    direction: [f32; 3],
+    cos_angle: f32,
+    first_view: i32,
+    view_count: i32,
+    _padding: [i32; 2],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct GpuView {
+    view_proj: [[f32; 4]; 4],
+    rect: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct GpuLights {
+    count: u32,
+    bias: f32,
+    pcf_radius: i32,
+    _padding: u32,
+    lights: [GpuLight; MAX_LIGHTS],
+    views: [GpuView; MAX_SHADOW_VIEWS],
+}
+
+/// Each caster view's view-projection sits in its own 256-byte slot, the
+/// minimum dynamic uniform offset alignment.
+const VIEW_SLOT: u64 = 256;
+
+/// One shadow view placed in the atlas this frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtlasView {
+    /// Index into the lights passed to `update`.
+    pub light: usize,
+    pub view_proj: glam::Mat4,
+    pub rect: AtlasRect,
+}
+
+pub struct ShadowAtlas {
+    /// Subtracted from the receiver's depth in the light's view.
+    pub depth_bias: f32,
+    pub pcf_radius: u32,
+    allocator: AtlasAllocator,
+    views: Vec<AtlasView>,
+    /// Lights that asked for a shadow and got none this frame.
+    pub unshadowed: usize,
+    depth: Arc<Texture>,
+    lights_buffer: Arc<wgpu::Buffer>,
+    view_buffer: wgpu::Buffer,
+    view_bind_group: wgpu::BindGroup,
+    pipelines: [wgpu::RenderPipeline; 2],
+}
+
+impl ShadowAtlas {
+    /// An atlas `size` texels square, handing out tiles no smaller than
+    /// `min_tile`.
+    pub fn new(state: &State, size: u32, min_tile: u32) -> Self {
+        let device = &state.device;
+        let allocator = AtlasAllocator::new(size, min_tile);
+        let size = allocator.size();
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Shadow Atlas"),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: shadow::DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let mut depth = Texture::from_texture(state, texture);
+        depth.sampler_desc = SamplerDesc {
+            anisotropy: 1,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..SamplerDesc::new().with_address_mode(wgpu::AddressMode::ClampToEdge)
+        };
+
+        let lights_buffer = Arc::new(device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Local Lights"),
+            size: std::mem::size_of::<GpuLights>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        }));
+        let view_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shadow Atlas Views"),
+            size: VIEW_SLOT * MAX_SHADOW_VIEWS as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let view_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Shadow Atlas Views"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(64),
+                },
+                count: None,
+            }],
+        });
+        let view_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Shadow Atlas Views"),
+            layout: &view_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &view_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(64),
+                }),
+            }],
+        });
+
+        ShadowAtlas {
+            depth_bias: 0.0005,
+            pcf_radius: 1,
+            allocator,
+            views: vec![],
+            unshadowed: 0,
+            depth: Arc::new(depth),
+            lights_buffer,
+            view_buffer,
+            view_bind_group,
+            pipelines: shadow::caster_pipelines(state, &view_layout, ShadowFilter::Pcf),
+        }
+    }
+
+    pub fn size(&self) -> u32 {
+        self.allocator.size()
+    }
+
+    /// The lights uniform and the atlas with its comparison sampler, as
+    /// two material groups.
+    pub fn bindings(&self) -> [Binding; 2] {
+        [
+            Binding::uniform(self.lights_buffer.clone(), wgpu::ShaderStages::FRAGMENT),
+            Binding::texture(self.depth.clone(), wgpu::ShaderStages::FRAGMENT),
+        ]
+    }
+
+    /// This frame's views, as placed by the last `update`.
+    pub fn views(&self) -> &[AtlasView] {
+        &self.views
+    }
+
+    /// Fraction of the atlas in use.
+    pub fn occupancy(&self) -> f32 {
+        let area = (self.size() as u64).pow(2);
+        1.0 - self.allocator.free_area() as f32 / area as f32
+    }
+
+    /// Repacks the atlas for `lights` and uploads them. Lights are packed
+    /// in order, so put the most important first.
+    pub fn update(&mut self, queue: &wgpu::Queue, lights: &[LocalLight]) {
+        self.allocator.clear();
+        self.views.clear();
+        self.unshadowed = 0;
+
+        let mut data: GpuLights = bytemuck::Zeroable::zeroed();
+        data.count = lights.len().min(MAX_LIGHTS) as u32;
+        data.bias = self.depth_bias;
+        data.pcf_radius = self.pcf_radius as i32;
+        for (i, light) in lights.iter().take(MAX_LIGHTS).enumerate() {
+            let (kind, direction, cos_angle) = match light.kind {
+                LocalLightKind::Spot { direction, angle } => (0, direction, angle.cos()),
+                LocalLightKind::Point => (1, glam::Vec3::ZERO, -1.0),
+            };
+            let first_view = self.views.len();
+            let view_count = self.place(i, light);
+            if light.shadow_resolution > 0 && view_count == 0 {
+                self.unshadowed += 1;
+            }
+            data.lights[i] = GpuLight {
+                position: light.position.to_array(),
+                range: light.range,
+                color: light.color,
+                kind,
+                direction: direction.to_array(),
+                cos_angle,
+                first_view: first_view as i32,
+                view_count: view_count as i32,
+                _padding: [0; 2],
+            };
+        }
+
+        let size = self.size();
+        for (i, view) in self.views.iter().enumerate() {
+            data.views[i] = GpuView {
+                view_proj: view.view_proj.to_cols_array_2d(),
+                rect: view.rect.uv_transform(size),
+            };
+            queue.write_buffer(
+                &self.view_buffer,
+                i as u64 * VIEW_SLOT,
+                bytemuck::cast_slice(&view.view_proj.to_cols_array()),
+            );
+        }
+        queue.write_buffer(&self.lights_buffer, 0, bytemuck::bytes_of(&data));
+    }
+
+    /// Allocates all of a light's views at one size, or none of them.
+    /// Returns how many were placed.
+    fn place(&mut self, light_index: usize, light: &LocalLight) -> usize {
+        if light.shadow_resolution == 0 {
+            return 0;
+        }
+        let view_projs = light.view_projs();
+        if self.views.len() + view_projs.len() > MAX_SHADOW_VIEWS {
+            return 0;
+        }
+        let mut size = light.shadow_resolution;
+        loop {
+            let mut rects = vec![];
+            for _ in &view_projs {
+                match self.allocator.allocate(size) {
+                    Some(rect) => rects.push(rect),
+                    None => break,
+                }
+            }
+            if rects.len() == view_projs.len() {
+                self.views
+                    .extend(
+                        view_projs
+                            .iter()
+                            .zip(rects)
+                            .map(|(&view_proj, rect)| AtlasView {
+                                light: light_index,
+                                view_proj,
+                                rect,
+                            }),
+                    );
+                return view_projs.len();
+            }
+            for rect in rects {
+                self.allocator.free(rect);
+            }
+            if size <= self.allocator.min_tile() {
+                return 0;
+            }
+            size /= 2;
+        }
+    }
+
+    /// Renders every visible model into each view's tile. Record it before
+    /// the scene pass, e.g. from `Plugin::prepare`.
+    pub fn encode(&self, state: &State, world: &World, encoder: &mut wgpu::CommandEncoder) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("shadow atlas"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        let mut draws = 0;
+        for (i, view) in self.views.iter().enumerate() {
+            let rect = view.rect;
+            let (x, y, size) = (rect.x as f32, rect.y as f32, rect.size as f32);
+            pass.set_viewport(x, y, size, size, 0.0, 1.0);
+            pass.set_scissor_rect(rect.x, rect.y, rect.size, rect.size);
+            pass.set_bind_group(0, &self.view_bind_group, &[i as u32 * VIEW_SLOT as u32]);
+            for model in world.models.iter().filter(|m| m.is_visible()) {
+                let encoding = match model.mesh.encoding {
+                    VertexEncoding::Full => 0,
+                    VertexEncoding::Packed => 1,
+                };
+                pass.set_pipeline(&self.pipelines[encoding]);
+                pass.set_bind_group(1, model.bind_group(), &[]);
+                pass.set_vertex_buffer(0, model.mesh.vertex_buffer.slice(..));
+                pass.set_index_buffer(model.mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                pass.draw_indexed(0..model.mesh.index_count, 0, 0..1);
+                draws += 1;
+            }
+        }
+        state.profiler.record_draws(draws, draws);
+        state.profiler.record_target(self.size(), self.size());
+    }
+}
@@ -0,0 +1,466 @@
+//! Analytic daylight sky after Preetham, Shirley and Smits, "A Practical
+//! Analytic Model for Daylight" (1999). The sun follows the time of day,
+//! and the same model gives the sun's color and the sky's ambient light, so
+//! lighting shaders can read [`Sky::light_direction`], [`Sky::sun_color`]
+//! and [`Sky::ambient`] instead of sampling an HDR environment.
+
+use crate::app::State;
+use crate::camera::Camera;
+use bytemuck::Zeroable;
+use wgpu::util::DeviceExt;
+
+const SHADER: &str = r#"
+struct Sky {
+    inv_view_proj: mat4x4<f32>,
+    // w: exposure
+    eye: vec4<f32>,
+    // w: angle from the zenith
+    sun: vec4<f32>,
+    // w: brightness left as the sun sets
+    sun_color: vec4<f32>,
+    // Perez coefficients for Y, x and y
+    a: vec4<f32>,
+    b: vec4<f32>,
+    c: vec4<f32>,
+    d: vec4<f32>,
+    e: vec4<f32>,
+    // zenith Yxy over the Perez function at the zenith
+    // w: brightest output, relative to SDR white
+    zenith: vec4<f32>,
+};
+@group(0) @binding(0) var<uniform> sky: Sky;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+};
+
+@vertex
+fn vsMain(@builtin(vertex_index) index: u32) -> VSOut {
+    let uv = vec2(f32((index << 1u) & 2u), f32(index & 2u));
+    let ndc = uv * 2.0 - 1.0;
+    var out: VSOut;
+    // on the far plane, behind everything already drawn
+    out.pos = vec4(ndc, 1.0, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+fn perez(cos_theta: f32, gamma: f32, cos_gamma: f32) -> vec3<f32> {
+    return (1.0 + sky.a.xyz * exp(sky.b.xyz / cos_theta))
+        * (1.0 + sky.c.xyz * exp(sky.d.xyz * gamma) + sky.e.xyz * cos_gamma * cos_gamma);
+}
+
+fn yxy_to_rgb(yxy: vec3<f32>) -> vec3<f32> {
+    let big_y = yxy.x;
+    let x = yxy.y * big_y / yxy.z;
+    let z = (1.0 - yxy.y - yxy.z) * big_y / yxy.z;
+    return vec3(
+        3.2406 * x - 1.5372 * big_y - 0.4986 * z,
+        -0.9689 * x + 1.8758 * big_y + 0.0415 * z,
+        0.0557 * x - 0.2040 * big_y + 1.0570 * z,
+    );
+}
+
+@fragment
+fn psMain(in: VSOut) -> @location(0) vec4<f32> {
+    let far = sky.inv_view_proj * vec4(in.ndc, 1.0, 1.0);
+    let dir = normalize(far.xyz / far.w - sky.eye.xyz);
+    // the model only covers the upper hemisphere; below it the horizon
+    // fades to a darker ground
+    let up = max(dir.y, 0.001);
+    let cos_gamma = clamp(dot(normalize(vec3(dir.x, up, dir.z)), sky.sun.xyz), -1.0, 1.0);
+    let yxy = sky.zenith.xyz * perez(up, acos(cos_gamma), cos_gamma);
+    var color = max(yxy_to_rgb(yxy), vec3(0.0)) * sky.sun_color.w;
+    if (dot(dir, sky.sun.xyz) > 0.99996) {
+        color += sky.sun_color.rgb * 20.0;
+    }
+    color *= mix(0.3, 1.0, smoothstep(-0.1, 0.0, dir.y));
+    let night = vec3(0.004, 0.006, 0.015);
+    let white = sky.zenith.w;
+    return vec4(white * (1.0 - exp(-(color * sky.eye.w + night) / white)), 1.0);
+}
+"#;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct SkyUniform {
+    inv_view_proj: [[f32; 4]; 4],
+    eye: [f32; 4],
+    sun: [f32; 4],
+    sun_color: [f32; 4],
+    a: [f32; 4],
+    b: [f32; 4],
+    c: [f32; 4],
+    d: [f32; 4],
+    e: [f32; 4],
+    zenith: [f32; 4],
+}
+
+/// Perez distribution coefficients A to E, each for Y, x and y.
+type Perez = [glam::Vec3; 5];
+
+fn perez_coefficients(turbidity: f32) -> Perez {
+    let t = turbidity;
+    [
+        glam::vec3(
+            0.1787 * t - 1.4630,
+            -0.0193 * t - 0.2592,
+            -0.0167 * t - 0.2608,
+        ),
+        glam::vec3(
+            -0.3554 * t + 0.4275,
+            -0.0665 * t + 0.0008,
+            -0.0950 * t + 0.0092,
+        ),
+        glam::vec3(
+            -0.0227 * t + 5.3251,
+            -0.0004 * t + 0.2125,
+            -0.0079 * t + 0.2102,
+        ),
+        glam::vec3(
+            0.1206 * t - 2.5771,
+            -0.0641 * t - 0.8989,
+            -0.0441 * t - 1.6537,
+        ),
+        glam::vec3(
+            -0.0670 * t + 0.3703,
+            -0.0033 * t + 0.0452,
+            -0.0109 * t + 0.0529,
+        ),
+    ]
+}
+
+fn perez(p: &Perez, cos_theta: f32, gamma: f32) -> glam::Vec3 {
+    let [a, b, c, d, e] = *p;
+    let cos_gamma = gamma.cos();
+    (1.0 + a * (b / cos_theta).exp()) * (1.0 + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+}
+
+/// Zenith luminance (kcd/m²) and chromaticity for a sun `theta` radians
+/// from the zenith.
+fn zenith_yxy(turbidity: f32, theta: f32) -> glam::Vec3 {
+    let t = turbidity;
+    let chi = (4.0 / 9.0 - t / 120.0) * (std::f32::consts::PI - 2.0 * theta);
+    let luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+    let theta = glam::vec4(theta * theta * theta, theta * theta, theta, 1.0);
+    let chromaticity = |t2: glam::Vec4, t1: glam::Vec4, t0: glam::Vec4| {
+        t * t * t2.dot(theta) + t * t1.dot(theta) + t0.dot(theta)
+    };
+    let x = chromaticity(
+        glam::vec4(0.00166, -0.00375, 0.00209, 0.0),
+        glam::vec4(-0.02903, 0.06377, -0.03202, 0.00394),
+        glam::vec4(0.11693, -0.21196, 0.06052, 0.25886),
+    );
+    let y = chromaticity(
+        glam::vec4(0.00275, -0.00610, 0.00317, 0.0),
+        glam::vec4(-0.04214, 0.08970, -0.04153, 0.00516),
+        glam::vec4(0.15346, -0.26756, 0.06670, 0.26688),
+    );
+    glam::vec3(luminance.max(0.0), x, y)
+}
+
+fn yxy_to_rgb(yxy: glam::Vec3) -> glam::Vec3 {
+    let (big_y, x, y) = (yxy.x, yxy.y, yxy.z);
+    let xyz = glam::vec3(x * big_y / y, big_y, (1.0 - x - y) * big_y / y);
+    glam::vec3(
+        glam::vec3(3.2406, -1.5372, -0.4986).dot(xyz),
+        glam::vec3(-0.9689, 1.8758, 0.0415).dot(xyz),
+        glam::vec3(0.0557, -0.2040, 1.0570).dot(xyz),
+    )
+    .max(glam::Vec3::ZERO)
+}
+
+/// Moves the sun through the day in real time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DayCycle {
+    pub running: bool,
+    /// Seconds of game time per 24 hours.
+    pub day_length: f32,
+    /// Eases `Sky::exposure` towards `key` over the sky's brightness, so
+    /// dusk and night stay readable.
+    pub auto_exposure: bool,
+    /// Tone-mapped brightness auto exposure aims for.
+    pub key: f32,
+}
+
+impl Default for DayCycle {
+    fn default() -> Self {
+        DayCycle {
+            running: false,
+            day_length: 120.0,
+            auto_exposure: true,
+            key: 0.6,
+        }
+    }
+}
+
+pub struct Sky {
+    /// Draws the sky behind the scene; the lighting getters work either way.
+    pub enabled: bool,
+    /// Local solar time in hours, 0..24.
+    pub time_of_day: f32,
+    /// 1..365; sets the sun's declination.
+    pub day_of_year: u32,
+    /// Degrees north.
+    pub latitude: f32,
+    /// Haze, from 2 (clear) to 10 (hazy).
+    pub turbidity: f32,
+    /// Scales the sky's luminance before tone mapping.
+    pub exposure: f32,
+    /// Scales `sun_color`.
+    pub sun_intensity: f32,
+    /// Brightest the sky tone maps to, relative to SDR white. Above one
+    /// only with HDR output, so the sun and bright sky keep their
+    /// highlights.
+    pub white: f32,
+    pub cycle: DayCycle,
+    pipeline: wgpu::RenderPipeline,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Sky {
+    pub fn new(state: &State) -> Self {
+        let device = &state.device;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Sky"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Sky"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sky"),
+            contents: bytemuck::bytes_of(&SkyUniform::zeroed()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Sky"),
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Sky"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Sky"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vsMain"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("psMain"),
+                compilation_options: Default::default(),
+                targets: &[Some(state.surface_config.format.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            // only where the depth buffer is still clear
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: state.depth_texture.texture.format(),
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Sky {
+            enabled: false,
+            time_of_day: 10.0,
+            day_of_year: 172,
+            latitude: 40.0,
+            turbidity: 3.0,
+            exposure: 0.1,
+            sun_intensity: 1.0,
+            white: 1.0,
+            cycle: DayCycle::default(),
+            pipeline,
+            buffer,
+            bind_group,
+        }
+    }
+
+    /// Unit vector towards the sun; +Y is up, -Z north and +X east.
+    pub fn sun_direction(&self) -> glam::Vec3 {
+        let year = std::f32::consts::TAU * (self.day_of_year as f32 + 10.0) / 365.0;
+        let declination = -23.44_f32.to_radians() * year.cos();
+        let hour_angle = (self.time_of_day - 12.0) / 24.0 * std::f32::consts::TAU;
+        let latitude = self.latitude.to_radians();
+        let (sin_lat, cos_lat) = latitude.sin_cos();
+        let (sin_dec, cos_dec) = declination.sin_cos();
+        glam::vec3(
+            -cos_dec * hour_angle.sin(),
+            sin_lat * sin_dec + cos_lat * cos_dec * hour_angle.cos(),
+            -(cos_lat * sin_dec - sin_lat * cos_dec * hour_angle.cos()),
+        )
+        .normalize()
+    }
+
+    /// The way sunlight travels, for a directional light.
+    pub fn light_direction(&self) -> glam::Vec3 {
+        -self.sun_direction()
+    }
+
+    /// How much of the sky's light is left as the sun sets: 1 by day, 0
+    /// once it's well below the horizon.
+    fn daylight(&self) -> f32 {
+        let elevation = self.sun_direction().y;
+        ((elevation + 0.1) / 0.15).clamp(0.0, 1.0)
+    }
+
+    /// Linear sunlight color after the atmosphere, using Preetham's
+    /// Rayleigh and aerosol extinction at 650, 570 and 475 nm. Black
+    /// while the sun is down.
+    pub fn sun_color(&self) -> glam::Vec3 {
+        let theta = self.sun_direction().y.clamp(-1.0, 1.0).acos();
+        if theta >= std::f32::consts::FRAC_PI_2 {
+            return glam::Vec3::ZERO;
+        }
+        // Kasten and Young's relative optical air mass
+        let degrees = theta.to_degrees();
+        let air_mass = 1.0 / (theta.cos() + 0.50572 * (96.07995 - degrees).powf(-1.6364));
+        let beta = 0.04608 * self.turbidity - 0.04586;
+        let wavelengths = glam::vec3(0.65, 0.57, 0.475);
+        let rayleigh = 0.008735 * wavelengths.powf(-4.08);
+        let aerosol = beta * wavelengths.powf(-1.3);
+        (-air_mass * (rayleigh + aerosol)).exp() * self.sun_intensity
+    }
+
+    /// Linear color of the light from the sky straight up, a stand-in for
+    /// ambient light.
+    pub fn ambient(&self) -> glam::Vec3 {
+        let zenith = zenith_yxy(self.turbidity, self.sun_theta());
+        let color = yxy_to_rgb(zenith) * self.daylight() * self.exposure;
+        glam::Vec3::ONE - (-color).exp()
+    }
+
+    /// The sun's angle from the zenith, kept just above the horizon where
+    /// the model stops being valid.
+    fn sun_theta(&self) -> f32 {
+        let y = self.sun_direction().y.max(0.02);
+        y.acos()
+    }
+
+    /// Advances the day/night cycle by `dt` seconds of game time, moving
+    /// on to the next day of the year at midnight.
+    pub fn advance(&mut self, dt: f32) {
+        if !self.cycle.running || dt <= 0.0 {
+            return;
+        }
+        self.time_of_day += dt * 24.0 / self.cycle.day_length.max(1.0);
+        while self.time_of_day >= 24.0 {
+            self.time_of_day -= 24.0;
+            self.day_of_year = self.day_of_year % 365 + 1;
+        }
+        if self.cycle.auto_exposure {
+            // zenith luminance in kcd/m²; a floor keeps night from blowing up
+            let luminance = zenith_yxy(self.turbidity, self.sun_theta()).x * self.daylight();
+            let target = (self.cycle.key / luminance.max(0.5)).clamp(0.01, 1.0);
+            self.exposure += (target - self.exposure) * (1.0 - (-2.0 * dt).exp());
+        }
+    }
+
+    pub fn queue_uniform(&self, queue: &wgpu::Queue, camera: &Camera) {
+        let theta = self.sun_theta();
+        let coefficients = perez_coefficients(self.turbidity);
+        let zenith = zenith_yxy(self.turbidity, theta) / perez(&coefficients, 1.0, theta);
+        let row = |v: glam::Vec3| v.extend(0.0).to_array();
+        let uniform = SkyUniform {
+            inv_view_proj: camera.view_proj().inverse().to_cols_array_2d(),
+            eye: camera.eye.extend(self.exposure).to_array(),
+            sun: self.sun_direction().extend(theta).to_array(),
+            sun_color: self.sun_color().extend(self.daylight()).to_array(),
+            a: row(coefficients[0]),
+            b: row(coefficients[1]),
+            c: row(coefficients[2]),
+            d: row(coefficients[3]),
+            e: row(coefficients[4]),
+            zenith: zenith.extend(self.white.max(1.0)).to_array(),
+        };
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+
+    /// Fills the background of the scene pass. Draw it after the opaque
+    /// models so it only shades pixels they left uncovered.
+    pub fn render(&self, renderpass: &mut wgpu::RenderPass) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        renderpass.set_pipeline(&self.pipeline);
+        renderpass.set_bind_group(0, &self.bind_group, &[]);
+        renderpass.draw(0..3, 0..1);
+        true
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing("Sky", |ui| {
+            ui.checkbox(&mut self.enabled, "Procedural sky");
+            let hours = self.time_of_day as u32;
+            let minutes = (self.time_of_day.fract() * 60.0) as u32;
+            ui.add(
+                egui::Slider::new(&mut self.time_of_day, 0.0..=24.0)
+                    .text(format!("Time of day ({hours:02}:{minutes:02})")),
+            );
+            ui.horizontal(|ui| {
+                if ui.button("-1 h").clicked() {
+                    self.time_of_day = (self.time_of_day - 1.0).rem_euclid(24.0);
+                }
+                if ui.button("+1 h").clicked() {
+                    self.time_of_day = (self.time_of_day + 1.0).rem_euclid(24.0);
+                }
+                let label = if self.cycle.running { "Pause" } else { "Play" };
+                if ui.button(label).clicked() {
+                    self.cycle.running = !self.cycle.running;
+                }
+            });
+            ui.add(
+                egui::Slider::new(&mut self.cycle.day_length, 10.0..=1200.0)
+                    .logarithmic(true)
+                    .suffix(" s")
+                    .text("Day length"),
+            );
+            ui.add(egui::Slider::new(&mut self.day_of_year, 1..=365).text("Day of year"));
+            ui.add(egui::Slider::new(&mut self.latitude, -90.0..=90.0).text("Latitude"));
+            ui.add(egui::Slider::new(&mut self.turbidity, 2.0..=10.0).text("Turbidity"));
+            ui.checkbox(&mut self.cycle.auto_exposure, "Auto exposure");
+            if self.cycle.auto_exposure {
+                ui.add(egui::Slider::new(&mut self.cycle.key, 0.1..=1.0).text("Key"));
+            }
+            ui.add_enabled(
+                !self.cycle.auto_exposure || !self.cycle.running,
+                egui::Slider::new(&mut self.exposure, 0.01..=1.0)
+                    .logarithmic(true)
+                    .text("Exposure"),
+            );
+            ui.add(egui::Slider::new(&mut self.sun_intensity, 0.0..=4.0).text("Sun intensity"));
+            let sun = self.sun_direction();
+            ui.label(format!(
+                "Sun elevation {:.1}°",
+                sun.y.clamp(-1.0, 1.0).asin().to_degrees()
+            ));
+        });
+    }
+}
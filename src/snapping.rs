@@ -0,0 +1,131 @@
+//! Snapping for transform edits: positions to a grid, rotations to an
+//! angle step, and dragged positions onto the model vertex under the
+//! cursor. Toggled from a toolbar under the menu bar.
+
+use crate::i18n::tr;
+use crate::model::EntityId;
+use crate::picking::{self, Ray};
+use crate::world::World;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Snapping {
+    pub grid: bool,
+    /// World units between grid lines.
+    pub grid_size: f32,
+    pub angle: bool,
+    /// Degrees.
+    pub angle_step: f32,
+    /// Only viewport drags can snap to vertices; fields in the inspector
+    /// have no cursor to pick one with.
+    pub vertex: bool,
+}
+
+impl Default for Snapping {
+    fn default() -> Self {
+        Snapping {
+            grid: false,
+            grid_size: 1.0,
+            angle: false,
+            angle_step: 15.0,
+            vertex: false,
+        }
+    }
+}
+
+impl Snapping {
+    /// `value`, a position along one axis or a distance, on the grid.
+    pub fn snap_length(&self, value: f32) -> f32 {
+        if self.grid && self.grid_size > 0.0 {
+            (value / self.grid_size).round() * self.grid_size
+        } else {
+            value
+        }
+    }
+
+    pub fn snap_position(&self, position: glam::Vec3) -> glam::Vec3 {
+        position.map(|v| self.snap_length(v))
+    }
+
+    pub fn snap_degrees(&self, degrees: f32) -> f32 {
+        if self.angle && self.angle_step > 0.0 {
+            (degrees / self.angle_step).round() * self.angle_step
+        } else {
+            degrees
+        }
+    }
+
+    /// Where a viewport drag aimed at `target`, under the cursor's `ray`,
+    /// lands: on the nearest vertex of the model under the cursor when
+    /// vertex snapping is on and there is one, otherwise on the grid.
+    /// Models `ignore` accepts, like the ones being dragged, are looked
+    /// through.
+    pub fn snap_drag(
+        &self,
+        world: &World,
+        ray: &Ray,
+        target: glam::Vec3,
+        ignore: impl Fn(EntityId) -> bool,
+    ) -> glam::Vec3 {
+        self.vertex
+            .then(|| nearest_vertex(world, ray, ignore))
+            .flatten()
+            .unwrap_or_else(|| self.snap_position(target))
+    }
+}
+
+/// The corner of the triangle under `ray` nearest to where it's hit.
+pub fn nearest_vertex(
+    world: &World,
+    ray: &Ray,
+    ignore: impl Fn(EntityId) -> bool,
+) -> Option<glam::Vec3> {
+    let hit = picking::raycast_filtered(world, ray, |id| !ignore(id))?;
+    hit.triangle.into_iter().min_by(|a, b| {
+        a.distance_squared(hit.point)
+            .total_cmp(&b.distance_squared(hit.point))
+    })
+}
+
+/// The snapping toolbar, under the menu bar.
+pub fn toolbar(ctx: &egui::Context, snapping: &mut Snapping) {
+    egui::TopBottomPanel::top("snapping").show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            ui.label(tr("snapping.title"));
+            ui.toggle_value(&mut snapping.grid, tr("snapping.grid"));
+            ui.add_enabled(
+                snapping.grid,
+                egui::DragValue::new(&mut snapping.grid_size)
+                    .speed(0.05)
+                    .range(0.01..=100.0),
+            );
+            ui.separator();
+            ui.toggle_value(&mut snapping.angle, tr("snapping.angle"));
+            ui.add_enabled(
+                snapping.angle,
+                egui::DragValue::new(&mut snapping.angle_step)
+                    .suffix("°")
+                    .range(1.0..=180.0),
+            );
+            ui.separator();
+            ui.toggle_value(&mut snapping.vertex, tr("snapping.vertex"))
+                .on_hover_text(tr("snapping.vertex_hint"));
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn angles_snap_to_the_step() {
+        let snapping = Snapping {
+            angle: true,
+            angle_step: 15.0,
+            ..Default::default()
+        };
+        assert_eq!(snapping.snap_degrees(22.0), 15.0);
+        assert_eq!(snapping.snap_degrees(-23.0), -30.0);
+        assert_eq!(Snapping::default().snap_degrees(22.0), 22.0);
+    }
+}
@@ -0,0 +1,313 @@
+//! Sockets attach models to a named joint of a skinned model: a sword in a
+//! hand, a hat on a head. Attached models are parented to the skinned model
+//! and their transforms are rewritten from its `Model::pose` every frame,
+//! so they follow whatever animates the joints.
+
+use crate::app::State;
+use crate::model::EntityId;
+use crate::plugin::{Plugin, PluginContext};
+use crate::transform::Transform;
+use crate::world::World;
+
+const SOCKET_COLORS: [[f32; 4]; 3] = [
+    [1.0, 0.2, 0.2, 1.0],
+    [0.2, 1.0, 0.2, 1.0],
+    [0.3, 0.5, 1.0, 1.0],
+];
+
+pub struct Socket {
+    pub name: String,
+    /// The skinned model.
+    pub model: EntityId,
+    /// A name from `Mesh::joint_names`, so the socket survives reimports
+    /// that reorder joints.
+    pub joint: String,
+    /// Relative to the joint; attached models are placed exactly here.
+    pub offset: Transform,
+    pub attached: Vec<EntityId>,
+}
+
+impl Socket {
+    /// Relative to the skinned model, from its current pose; `None` if the
+    /// model or joint is gone.
+    pub fn local_matrix(&self, world: &World) -> Option<glam::Mat4> {
+        let model = world.model(self.model)?;
+        let joint = model
+            .mesh
+            .joint_names
+            .iter()
+            .position(|n| *n == self.joint)?;
+        Some(*model.pose.get(joint)? * self.offset.matrix())
+    }
+
+    /// In world space, as of the model's last `World::update_transforms`.
+    pub fn global_matrix(&self, world: &World) -> Option<glam::Mat4> {
+        let global = world.model(self.model)?.global_matrix();
+        Some(global * self.local_matrix(world)?)
+    }
+}
+
+#[derive(Default)]
+pub struct Sockets {
+    pub sockets: Vec<Socket>,
+    /// Draws each socket's axes and name.
+    pub show: bool,
+    new_joint: usize,
+}
+
+impl Sockets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a socket on `joint` of `model`. Returns its index, or `None`
+    /// if the model has no such joint.
+    pub fn add(&mut self, world: &World, model: EntityId, joint: &str) -> Option<usize> {
+        world
+            .model(model)?
+            .mesh
+            .joint_names
+            .iter()
+            .any(|n| n == joint)
+            .then(|| {
+                self.sockets.push(Socket {
+                    name: joint.to_string(),
+                    model,
+                    joint: joint.to_string(),
+                    offset: Transform::default(),
+                    attached: vec![],
+                });
+                self.sockets.len() - 1
+            })
+    }
+
+    /// Parents `id` to the socket's model, taking it off any other socket.
+    /// False if that would make a model its own ancestor.
+    pub fn attach(&mut self, world: &mut World, socket: usize, id: EntityId) -> bool {
+        let Some(model) = self.sockets.get(socket).map(|s| s.model) else {
+            return false;
+        };
+        if !world.set_parent(id, Some(model)) {
+            return false;
+        }
+        for socket in &mut self.sockets {
+            socket.attached.retain(|&a| a != id);
+        }
+        self.sockets[socket].attached.push(id);
+        true
+    }
+
+    /// Unparents `id`, leaving it where it is in the world.
+    pub fn detach(&mut self, world: &mut World, id: EntityId) {
+        for socket in &mut self.sockets {
+            socket.attached.retain(|&a| a != id);
+        }
+        let Some(model) = world.model_mut(id) else {
+            return;
+        };
+        let (scale, rotation, translation) = model.global_matrix().to_scale_rotation_translation();
+        model.transform = Transform {
+            translation,
+            rotation,
+            scale,
+        };
+        world.set_parent(id, None);
+    }
+
+    pub fn remove(&mut self, world: &mut World, socket: usize) {
+        let removed = self.sockets.remove(socket);
+        for id in removed.attached {
+            self.detach(world, id);
+        }
+    }
+
+    /// Moves every attached model onto its socket. Models reparented
+    /// elsewhere are let go.
+    pub fn sync(&mut self, world: &mut World) {
+        self.sockets.retain(|s| world.model(s.model).is_some());
+        for socket in &mut self.sockets {
+            socket.attached.retain(|&id| {
+                world
+                    .model(id)
+                    .is_some_and(|m| m.parent == Some(socket.model))
+            });
+            let Some(matrix) = socket.local_matrix(world) else {
+                continue;
+            };
+            let (scale, rotation, translation) = matrix.to_scale_rotation_translation();
+            for &id in &socket.attached {
+                if let Some(model) = world.model_mut(id) {
+                    model.transform = Transform {
+                        translation,
+                        rotation,
+                        scale,
+                    };
+                }
+            }
+        }
+    }
+
+    fn draw(&self, world: &mut World) {
+        for socket in &self.sockets {
+            let (Some(matrix), Some(model)) =
+                (socket.global_matrix(world), world.model(socket.model))
+            else {
+                continue;
+            };
+            let (min, max) = model.mesh.bounds();
+            let size = model.global_matrix().transform_vector3(max - min).length() * 0.05;
+            let origin = matrix.w_axis.truncate();
+            for (axis, color) in [matrix.x_axis, matrix.y_axis, matrix.z_axis]
+                .into_iter()
+                .zip(SOCKET_COLORS)
+            {
+                let axis = axis.truncate().normalize_or_zero() * size;
+                world.debug_draw.line(origin, origin + axis, color);
+            }
+            world
+                .debug_draw
+                .text(origin, socket.name.clone(), [1.0, 1.0, 1.0, 1.0]);
+        }
+    }
+}
+
+impl Plugin for Sockets {
+    fn name(&self) -> &str {
+        "Sockets"
+    }
+
+    fn update(&mut self, ctx: &mut PluginContext) {
+        self.sync(ctx.world);
+        if self.show {
+            self.draw(ctx.world);
+        }
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, _state: &State, world: &mut World) {
+        egui::Window::new("Sockets")
+            .default_open(false)
+            .show(ctx, |ui| {
+                ui.checkbox(&mut self.show, "Show sockets");
+                let skinned = world
+                    .selection
+                    .primary()
+                    .and_then(|id| world.model(id))
+                    .filter(|m| !m.mesh.joint_names.is_empty());
+                match skinned {
+                    Some(model) => {
+                        let (id, joints) = (model.id, model.mesh.joint_names.clone());
+                        self.new_joint = self.new_joint.min(joints.len() - 1);
+                        ui.horizontal(|ui| {
+                            egui::ComboBox::from_id_salt("new socket joint")
+                                .selected_text(&joints[self.new_joint])
+                                .show_ui(ui, |ui| {
+                                    for (i, joint) in joints.iter().enumerate() {
+                                        ui.selectable_value(&mut self.new_joint, i, joint);
+                                    }
+                                });
+                            if ui.button("Add socket").clicked() {
+                                self.add(world, id, &joints[self.new_joint]);
+                            }
+                        });
+                    }
+                    None => {
+                        ui.label("Select a skinned model to add sockets to it.");
+                    }
+                }
+
+                let mut remove = None;
+                let mut attach = None;
+                let mut detach = None;
+                for (i, socket) in self.sockets.iter_mut().enumerate() {
+                    ui.push_id(i, |ui| {
+                        ui.separator();
+                        let Some(model) = world.model(socket.model) else {
+                            return;
+                        };
+                        ui.horizontal(|ui| {
+                            ui.text_edit_singleline(&mut socket.name);
+                            ui.label(format!("on {}", model.name));
+                        });
+                        egui::ComboBox::from_label("Joint")
+                            .selected_text(&socket.joint)
+                            .show_ui(ui, |ui| {
+                                for joint in &model.mesh.joint_names {
+                                    ui.selectable_value(&mut socket.joint, joint.clone(), joint);
+                                }
+                            });
+                        let t = &mut socket.offset;
+                        ui.horizontal(|ui| {
+                            ui.label("Offset");
+                            ui.add(egui::DragValue::new(&mut t.translation.x).speed(0.1));
+                            ui.add(egui::DragValue::new(&mut t.translation.y).speed(0.1));
+                            ui.add(egui::DragValue::new(&mut t.translation.z).speed(0.1));
+                        });
+                        let (y, x, z) = t.rotation.to_euler(glam::EulerRot::YXZ);
+                        let mut euler = [x, y, z].map(f32::to_degrees);
+                        ui.horizontal(|ui| {
+                            ui.label("Rotation");
+                            let mut changed = false;
+                            for angle in &mut euler {
+                                changed |= ui
+                                    .add(egui::DragValue::new(angle).speed(1.0).suffix("°"))
+                                    .changed();
+                            }
+                            if changed {
+                                let [x, y, z] = euler.map(f32::to_radians);
+                                t.rotation = glam::Quat::from_euler(glam::EulerRot::YXZ, y, x, z);
+                            }
+                        });
+                        let mut scale = t.scale.x;
+                        ui.horizontal(|ui| {
+                            ui.label("Scale");
+                            if ui
+                                .add(egui::DragValue::new(&mut scale).speed(0.01))
+                                .changed()
+                            {
+                                t.scale = glam::Vec3::splat(scale);
+                            }
+                        });
+
+                        for &id in &socket.attached {
+                            ui.horizontal(|ui| {
+                                let name = world.model(id).map_or("?", |m| &m.name);
+                                ui.label(format!("#{id} {name}"));
+                                if ui.small_button("Detach").clicked() {
+                                    detach = Some(id);
+                                }
+                            });
+                        }
+                        ui.horizontal(|ui| {
+                            let selected = world
+                                .selection
+                                .primary()
+                                .filter(|&id| id != socket.model && !socket.attached.contains(&id));
+                            if ui
+                                .add_enabled(
+                                    selected.is_some(),
+                                    egui::Button::new("Attach selected"),
+                                )
+                                .clicked()
+                            {
+                                attach = selected.map(|id| (i, id));
+                            }
+                            if ui.button("Remove").clicked() {
+                                remove = Some(i);
+                            }
+                        });
+                    });
+                }
+                if let Some((socket, id)) = attach {
+                    if !self.attach(world, socket, id) {
+                        log::warn!("Can't attach #{id} under its own descendant");
+                    }
+                }
+                if let Some(id) = detach {
+                    self.detach(world, id);
+                }
+                if let Some(socket) = remove {
+                    self.remove(world, socket);
+                }
+            });
+    }
+}
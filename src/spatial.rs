@@ -0,0 +1,487 @@
+//! A uniform hash grid over the models' world-space bounds, kept up to
+//! date by `World::update_transforms` as models move. Culling and picking
+//! use it as a broad phase; `query_aabb` and `query_sphere` are there for
+//! proximity checks such as triggers.
+
+use crate::model::EntityId;
+use crate::picking::Ray;
+use std::collections::{HashMap, HashSet};
+
+/// Bounds covering more cells than this go in a list that every query
+/// checks, instead of into each cell.
+const MAX_CELLS: i32 = 64;
+
+/// World-space axis-aligned bounds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: glam::Vec3,
+    pub max: glam::Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: glam::Vec3, max: glam::Vec3) -> Self {
+        Aabb { min, max }
+    }
+
+    /// The bounds of `min`..`max` after transforming by `matrix`.
+    pub fn transformed(min: glam::Vec3, max: glam::Vec3, matrix: glam::Mat4) -> Self {
+        let mut out = Aabb::new(glam::Vec3::splat(f32::MAX), glam::Vec3::splat(f32::MIN));
+        for i in 0..8 {
+            let corner = glam::vec3(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            );
+            let p = matrix.transform_point3(corner);
+            out.min = out.min.min(p);
+            out.max = out.max.max(p);
+        }
+        out
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.cmple(other.max).all() && other.min.cmple(self.max).all()
+    }
+
+    pub fn intersects_sphere(&self, center: glam::Vec3, radius: f32) -> bool {
+        center.clamp(self.min, self.max).distance_squared(center) <= radius * radius
+    }
+
+    /// Slab test; the distance along `ray` to where it enters, if it does.
+    pub fn intersects_ray(&self, ray: &Ray) -> Option<f32> {
+        let inverse = ray.direction.recip();
+        let a = (self.min - ray.origin) * inverse;
+        let b = (self.max - ray.origin) * inverse;
+        let near = a.min(b).max_element().max(0.0);
+        let far = a.max(b).min_element();
+        (near <= far).then_some(near)
+    }
+
+    /// False only if the bounds are entirely outside one of the planes.
+    pub fn intersects_frustum(&self, frustum: &Frustum) -> bool {
+        frustum.planes.iter().all(|plane| {
+            let normal = plane.truncate();
+            // the corner furthest along the plane normal
+            let corner = glam::Vec3::select(normal.cmpge(glam::Vec3::ZERO), self.max, self.min);
+            normal.dot(corner) + plane.w >= 0.0
+        })
+    }
+}
+
+/// The six planes of a view-projection, normals pointing inwards.
+#[derive(Debug, Clone, Copy)]
+pub struct Frustum {
+    pub planes: [glam::Vec4; 6],
+}
+
+impl Frustum {
+    /// For matrices with OpenGL's -1..1 clip depth, like `Camera`'s.
+    pub fn from_view_proj(view_proj: glam::Mat4) -> Self {
+        let row = |i| view_proj.row(i);
+        let planes = [
+            row(3) + row(0),
+            row(3) - row(0),
+            row(3) + row(1),
+            row(3) - row(1),
+            row(3) + row(2),
+            row(3) - row(2),
+        ];
+        Frustum {
+            planes: planes.map(|p| p / p.truncate().length()),
+        }
+    }
+
+    /// The bounds of the eight corners; `None` if three of the planes
+    /// don't meet in a point, e.g. without a far plane.
+    pub fn bounds(&self) -> Option<Aabb> {
+        let mut bounds = Aabb::new(glam::Vec3::splat(f32::MAX), glam::Vec3::splat(f32::MIN));
+        for i in 0..8 {
+            // one of left/right, bottom/top and near/far each
+            let [a, b, c] = [i & 1, 2 + (i >> 1 & 1), 4 + (i >> 2)].map(|p| self.planes[p]);
+            let corner = meet(a, b, c)?;
+            bounds.min = bounds.min.min(corner);
+            bounds.max = bounds.max.max(corner);
+        }
+        Some(bounds)
+    }
+}
+
+/// Where three planes meet.
+fn meet(a: glam::Vec4, b: glam::Vec4, c: glam::Vec4) -> Option<glam::Vec3> {
+    let (na, nb, nc) = (a.truncate(), b.truncate(), c.truncate());
+    let det = na.dot(nb.cross(nc));
+    let point = -(a.w * nb.cross(nc) + b.w * nc.cross(na) + c.w * na.cross(nb)) / det;
+    (det.abs() > 1e-6 && point.is_finite()).then_some(point)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    bounds: Aabb,
+    /// Inclusive cell range; `None` when in `large`.
+    cells: Option<(glam::IVec3, glam::IVec3)>,
+}
+
+pub struct SpatialIndex {
+    cell_size: f32,
+    cells: HashMap<glam::IVec3, Vec<EntityId>>,
+    entries: HashMap<EntityId, Entry>,
+    large: Vec<EntityId>,
+    /// Inclusive range of every cell that has held an entry. Only grows
+    /// until `clear`, which is enough to bound the walks below.
+    extent: Option<(glam::IVec3, glam::IVec3)>,
+}
+
+impl Default for SpatialIndex {
+    fn default() -> Self {
+        Self::new(100.0)
+    }
+}
+
+impl SpatialIndex {
+    pub fn new(cell_size: f32) -> Self {
+        SpatialIndex {
+            cell_size,
+            cells: HashMap::new(),
+            entries: HashMap::new(),
+            large: vec![],
+            extent: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Occupied cells, not counting the oversized list.
+    pub fn cell_count(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn bounds(&self, id: EntityId) -> Option<Aabb> {
+        self.entries.get(&id).map(|e| e.bounds)
+    }
+
+    fn cell_range(&self, bounds: &Aabb) -> Option<(glam::IVec3, glam::IVec3)> {
+        let min = (bounds.min / self.cell_size).floor().as_ivec3();
+        let max = (bounds.max / self.cell_size).floor().as_ivec3();
+        let size = (max - min + 1).as_i64vec3();
+        (size.x * size.y * size.z <= MAX_CELLS as i64).then_some((min, max))
+    }
+
+    /// Inserts `id` or moves it to `bounds`.
+    pub fn update(&mut self, id: EntityId, bounds: Aabb) {
+        let cells = self.cell_range(&bounds);
+        if let Some(entry) = self.entries.get_mut(&id) {
+            if entry.cells == cells {
+                entry.bounds = bounds;
+                return;
+            }
+            self.remove(id);
+        }
+        match cells {
+            Some((min, max)) => {
+                for cell in cells_in(min, max) {
+                    self.cells.entry(cell).or_default().push(id);
+                }
+                self.extent = Some(match self.extent {
+                    Some((low, high)) => (low.min(min), high.max(max)),
+                    None => (min, max),
+                });
+            }
+            None => self.large.push(id),
+        }
+        self.entries.insert(id, Entry { bounds, cells });
+    }
+
+    pub fn remove(&mut self, id: EntityId) -> bool {
+        let Some(entry) = self.entries.remove(&id) else {
+            return false;
+        };
+        match entry.cells {
+            Some((min, max)) => {
+                for cell in cells_in(min, max) {
+                    if let Some(ids) = self.cells.get_mut(&cell) {
+                        ids.retain(|&i| i != id);
+                        if ids.is_empty() {
+                            self.cells.remove(&cell);
+                        }
+                    }
+                }
+            }
+            None => self.large.retain(|&i| i != id),
+        }
+        true
+    }
+
+    /// Removes every entry `keep` returns false for.
+    pub fn retain(&mut self, mut keep: impl FnMut(EntityId) -> bool) {
+        let stale: Vec<EntityId> = self
+            .entries
+            .keys()
+            .copied()
+            .filter(|&id| !keep(id))
+            .collect();
+        for id in stale {
+            self.remove(id);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.entries.clear();
+        self.large.clear();
+        self.extent = None;
+    }
+
+    /// Calls `visit` with the entries of each occupied cell that `region`
+    /// touches, by looking the range up or by going through the occupied
+    /// cells, whichever is fewer.
+    fn for_each_cell(&self, region: &Aabb, mut visit: impl FnMut(&[EntityId])) {
+        let Some((low, high)) = self.extent else {
+            return;
+        };
+        // float to int casts saturate, so huge regions are fine
+        let min = (region.min / self.cell_size).floor().as_ivec3().max(low);
+        let max = (region.max / self.cell_size).floor().as_ivec3().min(high);
+        if min.cmpgt(max).any() {
+            return;
+        }
+        let size = (max - min + 1).as_i64vec3();
+        if size.x * size.y * size.z <= self.cells.len() as i64 {
+            for cell in cells_in(min, max) {
+                if let Some(ids) = self.cells.get(&cell) {
+                    visit(ids);
+                }
+            }
+        } else {
+            for (&cell, ids) in &self.cells {
+                if cell.cmpge(min).all() && cell.cmple(max).all() {
+                    visit(ids);
+                }
+            }
+        }
+    }
+
+    /// The cells `ray` passes through within the extent, in order.
+    fn ray_cells(&self, ray: &Ray) -> Vec<glam::IVec3> {
+        let Some((min, max)) = self.extent else {
+            return vec![];
+        };
+        let direction = ray.direction;
+        if direction == glam::Vec3::ZERO || !direction.is_finite() {
+            return vec![];
+        }
+        let grid = Aabb::new(
+            min.as_vec3() * self.cell_size,
+            (max + 1).as_vec3() * self.cell_size,
+        );
+        let Some(enter) = grid.intersects_ray(ray) else {
+            return vec![];
+        };
+        let mut cell = (ray.at(enter) / self.cell_size)
+            .floor()
+            .as_ivec3()
+            .clamp(min, max);
+
+        // distance along the ray to the next boundary on each axis, and
+        // between boundaries
+        let step = glam::IVec3::from_array(direction.to_array().map(|d| {
+            if d > 0.0 {
+                1
+            } else if d < 0.0 {
+                -1
+            } else {
+                0
+            }
+        }));
+        let mut next = glam::Vec3::INFINITY;
+        for axis in 0..3 {
+            if step[axis] != 0 {
+                let boundary = (cell[axis] + step[axis].max(0)) as f32 * self.cell_size;
+                next[axis] = (boundary - ray.origin[axis]) / direction[axis];
+            }
+        }
+        let delta = self.cell_size / direction.abs();
+
+        let mut cells = vec![cell];
+        loop {
+            let axis = next.min_position();
+            cell[axis] += step[axis];
+            if cell[axis] < min[axis] || cell[axis] > max[axis] {
+                return cells;
+            }
+            next[axis] += delta[axis];
+            cells.push(cell);
+        }
+    }
+
+    /// Entries in the cells `region` touches that pass `test`, each once.
+    fn query(&self, region: &Aabb, test: impl Fn(&Aabb) -> bool) -> Vec<EntityId> {
+        let mut seen = HashSet::new();
+        let mut found = vec![];
+        let mut check = |id: EntityId| {
+            if seen.insert(id) && test(&self.entries[&id].bounds) {
+                found.push(id);
+            }
+        };
+        self.for_each_cell(region, |ids| ids.iter().for_each(|&id| check(id)));
+        for &id in &self.large {
+            check(id);
+        }
+        found
+    }
+
+    /// Entries whose bounds overlap `bounds`.
+    pub fn query_aabb(&self, bounds: &Aabb) -> Vec<EntityId> {
+        self.query(bounds, |b| b.intersects(bounds))
+    }
+
+    /// Entries whose bounds overlap the sphere.
+    pub fn query_sphere(&self, center: glam::Vec3, radius: f32) -> Vec<EntityId> {
+        let region = Aabb::new(center - radius, center + radius);
+        self.query(&region, |b| b.intersects_sphere(center, radius))
+    }
+
+    /// Entries whose bounds `ray` passes through, nearest entry point
+    /// first. Walks the cells along the ray.
+    pub fn query_ray(&self, ray: &Ray) -> Vec<(EntityId, f32)> {
+        let mut seen = HashSet::new();
+        let mut hits = vec![];
+        let mut check = |id: EntityId| {
+            if seen.insert(id) {
+                if let Some(distance) = self.entries[&id].bounds.intersects_ray(ray) {
+                    hits.push((id, distance));
+                }
+            }
+        };
+        for cell in self.ray_cells(ray) {
+            for &id in self.cells.get(&cell).into_iter().flatten() {
+                check(id);
+            }
+        }
+        for &id in &self.large {
+            check(id);
+        }
+        hits.sort_by(|a, b| a.1.total_cmp(&b.1));
+        hits
+    }
+
+    /// Entries at least partly inside `frustum`: overlapping its bounds
+    /// and not outside any plane. Only the cells in its bounds are looked
+    /// at.
+    pub fn query_frustum(&self, frustum: &Frustum) -> HashSet<EntityId> {
+        let everywhere = Aabb::new(glam::Vec3::splat(f32::MIN), glam::Vec3::splat(f32::MAX));
+        let region = frustum.bounds().unwrap_or(everywhere);
+        self.query(&region, |b| {
+            b.intersects(&region) && b.intersects_frustum(frustum)
+        })
+        .into_iter()
+        .collect()
+    }
+}
+
+fn cells_in(min: glam::IVec3, max: glam::IVec3) -> impl Iterator<Item = glam::IVec3> {
+    (min.x..=max.x).flat_map(move |x| {
+        (min.y..=max.y).flat_map(move |y| (min.z..=max.z).map(move |z| glam::ivec3(x, y, z)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic numbers in 0..1, so failures reproduce.
+    fn random(seed: &mut u32) -> f32 {
+        *seed ^= *seed << 13;
+        *seed ^= *seed >> 17;
+        *seed ^= *seed << 5;
+        (*seed >> 8) as f32 / (1 << 24) as f32
+    }
+
+    fn random_vec3(seed: &mut u32, scale: f32) -> glam::Vec3 {
+        (glam::vec3(random(seed), random(seed), random(seed)) * 2.0 - 1.0) * scale
+    }
+
+    /// Mostly small boxes spread over several cells, plus a few too big for
+    /// the grid.
+    fn scene(seed: &mut u32) -> SpatialIndex {
+        let mut index = SpatialIndex::new(10.0);
+        for id in 0..300 {
+            let center = random_vec3(seed, 100.0);
+            let size = if id % 50 == 0 {
+                80.0
+            } else {
+                8.0 * random(seed)
+            };
+            let half = glam::Vec3::splat(size);
+            index.update(id, Aabb::new(center - half, center + half));
+        }
+        index
+    }
+
+    #[test]
+    fn ray_query_matches_brute_force() {
+        let mut seed = 7;
+        let index = scene(&mut seed);
+        for i in 0..200 {
+            // from outside the grid and from inside it, some along an axis
+            let origin = random_vec3(&mut seed, if i % 2 == 0 { 200.0 } else { 50.0 });
+            let mut direction = random_vec3(&mut seed, 1.0);
+            if i % 5 == 0 {
+                direction *= glam::Vec3::X;
+            }
+            let ray = Ray {
+                origin,
+                direction: direction.normalize(),
+            };
+            let mut expected: Vec<_> = index
+                .entries
+                .iter()
+                .filter_map(|(&id, e)| Some((id, e.bounds.intersects_ray(&ray)?)))
+                .collect();
+            expected.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+            let mut actual = index.query_ray(&ray);
+            actual.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+            assert_eq!(actual, expected, "{ray:?}");
+        }
+    }
+
+    #[test]
+    fn frustum_query_matches_brute_force() {
+        let mut seed = 11;
+        let index = scene(&mut seed);
+        for _ in 0..50 {
+            let eye = random_vec3(&mut seed, 150.0);
+            let target = random_vec3(&mut seed, 50.0);
+            let view = glam::Mat4::look_at_rh(eye, target, glam::Vec3::Y);
+            let far = 20.0 + 200.0 * random(&mut seed);
+            let proj = glam::Mat4::perspective_rh_gl(1.0, 1.5, 0.1, far);
+            let frustum = Frustum::from_view_proj(proj * view);
+            let region = frustum.bounds().unwrap();
+            let expected: HashSet<_> = index
+                .entries
+                .iter()
+                .filter(|(_, e)| {
+                    e.bounds.intersects(&region) && e.bounds.intersects_frustum(&frustum)
+                })
+                .map(|(&id, _)| id)
+                .collect();
+            assert_eq!(index.query_frustum(&frustum), expected);
+        }
+    }
+
+    #[test]
+    fn frustum_bounds_hold_its_corners() {
+        let proj = glam::Mat4::perspective_rh_gl(1.0, 1.0, 1.0, 10.0);
+        let bounds = Frustum::from_view_proj(proj).bounds().unwrap();
+        let half = 10.0 * 0.5f32.tan();
+        assert!(bounds
+            .min
+            .abs_diff_eq(glam::vec3(-half, -half, -10.0), 1e-3));
+        assert!(bounds.max.abs_diff_eq(glam::vec3(half, half, -1.0), 1e-3));
+    }
+}
@@ -0,0 +1,888 @@
+//! Splines are curves through world-space control points, edited by
+//! dragging their handles in the viewport. `FollowSpline` moves a model or
+//! the camera along one at a steady speed, for camera paths and moving
+//! platforms.
+
+use crate::app::State;
+use crate::array;
+use crate::input::Action;
+use crate::model::EntityId;
+use crate::picking;
+use crate::plugin::{Plugin, PluginContext};
+use crate::world::World;
+use serde::{Deserialize, Serialize};
+
+/// Steps per segment when measuring arc length.
+const LENGTH_SAMPLES: usize = 16;
+/// Lines per segment when drawing.
+const DRAW_SAMPLES: usize = 24;
+/// How close the cursor has to be to grab a handle, in logical pixels.
+const GRAB_RADIUS: f32 = 10.0;
+
+const CURVE_COLOR: [f32; 4] = [0.3, 0.8, 1.0, 1.0];
+const ANCHOR_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const HANDLE_COLOR: [f32; 4] = [0.6, 0.6, 0.6, 1.0];
+const ACTIVE_COLOR: [f32; 4] = [1.0, 0.8, 0.1, 1.0];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SplineKind {
+    /// Passes through every point.
+    CatmullRom,
+    /// Passes through every third point; the two between are handles.
+    Bezier,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SplineFile {
+    name: String,
+    kind: SplineKind,
+    closed: bool,
+    points: Vec<[f32; 3]>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spline {
+    pub name: String,
+    pub kind: SplineKind,
+    /// Joins the last point back to the first.
+    pub closed: bool,
+    /// In world space. For `Bezier`: anchor, handle, handle, anchor, ...
+    pub points: Vec<glam::Vec3>,
+}
+
+impl Spline {
+    pub fn new(name: &str, kind: SplineKind, points: Vec<glam::Vec3>) -> Self {
+        Spline {
+            name: name.to_string(),
+            kind,
+            closed: false,
+            points,
+        }
+    }
+
+    pub fn segment_count(&self) -> usize {
+        let n = self.points.len();
+        match (self.kind, self.closed) {
+            (SplineKind::CatmullRom, false) => n.saturating_sub(1),
+            (SplineKind::CatmullRom, true) if n >= 2 => n,
+            (SplineKind::CatmullRom, true) => 0,
+            (SplineKind::Bezier, false) => n.saturating_sub(1) / 3,
+            (SplineKind::Bezier, true) => n / 3,
+        }
+    }
+
+    /// The cubic Bézier control points of segment `i`.
+    pub fn segment(&self, i: usize) -> [glam::Vec3; 4] {
+        let n = self.points.len();
+        let p = |j: usize| self.points[j % n];
+        match self.kind {
+            SplineKind::Bezier => [p(3 * i), p(3 * i + 1), p(3 * i + 2), p(3 * i + 3)],
+            SplineKind::CatmullRom => {
+                let (p1, p2) = (p(i), p(i + 1));
+                // open ends mirror their neighbour
+                let p0 = match i {
+                    _ if self.closed => p(i + n - 1),
+                    0 => p1 * 2.0 - p2,
+                    _ => p(i - 1),
+                };
+                let p3 = match i + 2 {
+                    _ if self.closed => p(i + 2),
+                    j if j >= n => p2 * 2.0 - p1,
+                    j => p(j),
+                };
+                [p1, p1 + (p2 - p0) / 6.0, p2 - (p3 - p1) / 6.0, p2]
+            }
+        }
+    }
+
+    /// Splits `t`, 0..1 along the whole spline, into a segment and the
+    /// parameter within it.
+    fn locate(&self, t: f32) -> Option<(usize, f32)> {
+        let count = self.segment_count();
+        if count == 0 {
+            return None;
+        }
+        let x = t.clamp(0.0, 1.0) * count as f32;
+        let i = (x as usize).min(count - 1);
+        Some((i, x - i as f32))
+    }
+
+    /// At `t`, 0..1 along the whole spline. Segments share `t` evenly
+    /// whatever their length; see `t_at_distance`.
+    pub fn position(&self, t: f32) -> glam::Vec3 {
+        match self.locate(t) {
+            Some((i, s)) => bezier(self.segment(i), s),
+            None => self.points.first().copied().unwrap_or_default(),
+        }
+    }
+
+    /// Normalized direction of travel at `t`.
+    pub fn tangent(&self, t: f32) -> glam::Vec3 {
+        let Some((i, s)) = self.locate(t) else {
+            return glam::Vec3::ZERO;
+        };
+        let c = self.segment(i);
+        // handles on top of their anchor leave no derivative at the ends
+        bezier_derivative(c, s)
+            .try_normalize()
+            .unwrap_or_else(|| (c[3] - c[0]).normalize_or_zero())
+    }
+
+    /// Length so far at each of `LENGTH_SAMPLES` even steps of `t` per
+    /// segment, starting with 0.
+    pub fn arc_lengths(&self) -> Vec<f32> {
+        let samples = self.segment_count() * LENGTH_SAMPLES;
+        let mut lengths = Vec::with_capacity(samples + 1);
+        lengths.push(0.0);
+        let mut previous = self.position(0.0);
+        for k in 1..=samples {
+            let point = self.position(k as f32 / samples as f32);
+            lengths.push(lengths[k - 1] + point.distance(previous));
+            previous = point;
+        }
+        lengths
+    }
+
+    pub fn length(&self) -> f32 {
+        self.arc_lengths().last().copied().unwrap_or(0.0)
+    }
+
+    /// The `t` that lies `distance` along the curve, clamped to its ends.
+    pub fn t_at_distance(&self, distance: f32) -> f32 {
+        let lengths = self.arc_lengths();
+        let samples = lengths.len() - 1;
+        if samples == 0 {
+            return 0.0;
+        }
+        let k = lengths.partition_point(|&l| l < distance).clamp(1, samples);
+        let (a, b) = (lengths[k - 1], lengths[k]);
+        let s = if b > a {
+            ((distance - a) / (b - a)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        ((k - 1) as f32 + s) / samples as f32
+    }
+
+    /// Whether point `i` lies on the curve rather than being a handle.
+    pub fn is_anchor(&self, i: usize) -> bool {
+        self.kind == SplineKind::CatmullRom || i.is_multiple_of(3)
+    }
+
+    /// Moves point `i`; Bézier anchors take their handles with them.
+    pub fn move_point(&mut self, i: usize, to: glam::Vec3) {
+        let n = self.points.len();
+        let delta = to - self.points[i];
+        self.points[i] = to;
+        if self.kind == SplineKind::Bezier && self.is_anchor(i) {
+            if i + 1 < n {
+                self.points[i + 1] += delta;
+            }
+            if i > 0 {
+                self.points[i - 1] += delta;
+            } else if self.closed && n > 1 {
+                self.points[n - 1] += delta;
+            }
+        }
+    }
+
+    /// Adds an anchor after the last one, with handles a third of the way
+    /// along for Bézier splines.
+    pub fn extend(&mut self, to: glam::Vec3) {
+        let n = self.points.len();
+        if self.kind == SplineKind::CatmullRom || n == 0 {
+            self.points.push(to);
+            return;
+        }
+        // closed Bézier splines end in the handles back to the first anchor
+        let last = if self.closed && n >= 3 {
+            n - 3
+        } else {
+            n - 1 - (n - 1) % 3
+        };
+        let from = self.points[last];
+        let inserted = [from.lerp(to, 1.0 / 3.0), from.lerp(to, 2.0 / 3.0), to];
+        self.points.splice(last + 1..last + 1, inserted);
+    }
+
+    /// Removes point `i`, or for Bézier splines the anchor it belongs to
+    /// and that anchor's handles.
+    pub fn remove_point(&mut self, i: usize) {
+        let n = self.points.len();
+        if self.kind == SplineKind::CatmullRom || n <= 3 {
+            self.points.remove(i.min(n - 1));
+            return;
+        }
+        let anchor = i - i % 3;
+        let mut removed = if self.closed {
+            vec![(anchor + n - 1) % n, anchor, anchor + 1]
+        } else if anchor == 0 {
+            vec![0, 1, 2]
+        } else if anchor + 1 >= n {
+            vec![anchor - 2, anchor - 1, anchor]
+        } else {
+            vec![anchor - 1, anchor, anchor + 1]
+        };
+        removed.sort_unstable();
+        for j in removed.into_iter().rev().filter(|&j| j < n) {
+            self.points.remove(j);
+        }
+    }
+
+    /// Rewrites the points for `kind`. Catmull-Rom to Bézier keeps the
+    /// curve exactly; the other way keeps the anchors.
+    pub fn set_kind(&mut self, kind: SplineKind) {
+        if kind == self.kind {
+            return;
+        }
+        if kind == SplineKind::Bezier && self.segment_count() > 0 {
+            let mut points = vec![self.points[0]];
+            for i in 0..self.segment_count() {
+                points.extend_from_slice(&self.segment(i)[1..]);
+            }
+            if self.closed {
+                points.pop();
+            }
+            self.points = points;
+        } else if kind == SplineKind::CatmullRom {
+            self.points = self.points.iter().step_by(3).copied().collect();
+        }
+        self.kind = kind;
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let file: SplineFile = toml::from_str(&text).map_err(|e| format!("{path}: {e}"))?;
+        Ok(Spline {
+            name: file.name,
+            kind: file.kind,
+            closed: file.closed,
+            points: file.points.into_iter().map(glam::Vec3::from).collect(),
+        })
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let file = SplineFile {
+            name: self.name.clone(),
+            kind: self.kind,
+            closed: self.closed,
+            points: self.points.iter().map(|p| p.to_array()).collect(),
+        };
+        let text = toml::to_string_pretty(&file).map_err(|e| e.to_string())?;
+        std::fs::write(path, text).map_err(|e| e.to_string())
+    }
+}
+
+fn bezier(c: [glam::Vec3; 4], s: f32) -> glam::Vec3 {
+    let u = 1.0 - s;
+    c[0] * (u * u * u) + c[1] * (3.0 * u * u * s) + c[2] * (3.0 * u * s * s) + c[3] * (s * s * s)
+}
+
+fn bezier_derivative(c: [glam::Vec3; 4], s: f32) -> glam::Vec3 {
+    let u = 1.0 - s;
+    (c[1] - c[0]) * (3.0 * u * u) + (c[2] - c[1]) * (6.0 * u * s) + (c[3] - c[2]) * (3.0 * s * s)
+}
+
+/// Turns +Z to `forward`, keeping +Y as near up as it can.
+pub fn look_rotation(forward: glam::Vec3) -> glam::Quat {
+    let right = glam::Vec3::Y.cross(forward).normalize_or(glam::Vec3::X);
+    let up = forward.cross(right);
+    glam::Quat::from_mat3(&glam::Mat3::from_cols(right, up, forward))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FollowTarget {
+    Model(EntityId),
+    Camera,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FollowMode {
+    Loop,
+    PingPong,
+    /// Stops at the end.
+    Once,
+}
+
+pub struct FollowSpline {
+    /// Index into `Splines::splines`.
+    pub spline: usize,
+    pub target: FollowTarget,
+    /// World units per second.
+    pub speed: f32,
+    pub mode: FollowMode,
+    /// Turns models' +Z along the curve, and points the camera at
+    /// `look_ahead` further on instead of leaving its target be.
+    pub orient: bool,
+    pub look_ahead: f32,
+    pub playing: bool,
+    /// How far along the curve, in world units.
+    pub distance: f32,
+    reverse: bool,
+}
+
+impl FollowSpline {
+    pub fn new(spline: usize, target: FollowTarget) -> Self {
+        FollowSpline {
+            spline,
+            target,
+            speed: 20.0,
+            mode: FollowMode::Loop,
+            orient: true,
+            look_ahead: 20.0,
+            playing: true,
+            distance: 0.0,
+            reverse: false,
+        }
+    }
+
+    /// Moves `dt` seconds further along a spline `length` long.
+    pub fn advance(&mut self, length: f32, dt: f32) {
+        if !self.playing || length <= 0.0 {
+            return;
+        }
+        // only ping-pong runs backwards; a finished run starts over
+        if self.mode != FollowMode::PingPong {
+            self.reverse = false;
+        }
+        if self.mode == FollowMode::Once && self.distance >= length {
+            self.distance = 0.0;
+        }
+        let direction = if self.reverse { -1.0 } else { 1.0 };
+        self.distance += self.speed * dt * direction;
+        match self.mode {
+            FollowMode::Loop => self.distance = self.distance.rem_euclid(length),
+            FollowMode::PingPong => {
+                if !(0.0..=length).contains(&self.distance) {
+                    self.reverse = !self.reverse;
+                    // reflect off whichever end was passed
+                    let end = if self.distance > length { length } else { 0.0 };
+                    self.distance = 2.0 * end - self.distance;
+                }
+                self.distance = self.distance.clamp(0.0, length);
+            }
+            FollowMode::Once => {
+                if !(0.0..=length).contains(&self.distance) {
+                    self.playing = false;
+                }
+                self.distance = self.distance.clamp(0.0, length);
+            }
+        }
+    }
+
+    /// Puts the target at `distance` along `spline`.
+    pub fn apply(&self, spline: &Spline, world: &mut World) {
+        let t = spline.t_at_distance(self.distance);
+        let position = spline.position(t);
+        let mut forward = spline.tangent(t);
+        if self.reverse {
+            forward = -forward;
+        }
+        match self.target {
+            FollowTarget::Camera => {
+                world.camera.eye = position;
+                if self.orient {
+                    let step = if self.reverse {
+                        -self.look_ahead
+                    } else {
+                        self.look_ahead
+                    };
+                    let mut ahead = self.distance + step;
+                    if self.mode == FollowMode::Loop {
+                        ahead = ahead.rem_euclid(spline.length().max(f32::EPSILON));
+                    }
+                    let target = spline.position(spline.t_at_distance(ahead));
+                    // past the end there's nothing ahead to look at
+                    world.camera.center = if target.distance(position) > 1e-3 {
+                        target
+                    } else {
+                        position + forward
+                    };
+                }
+                world.camera_controller.sync_from_camera(&world.camera);
+            }
+            FollowTarget::Model(id) => {
+                let parent = world
+                    .model(id)
+                    .and_then(|m| m.parent)
+                    .and_then(|p| world.model(p))
+                    .map_or(glam::Mat4::IDENTITY, |p| p.global_matrix().inverse());
+                let Some(model) = world.model_mut(id) else {
+                    return;
+                };
+                model.transform.translation = parent.transform_point3(position);
+                if self.orient && forward != glam::Vec3::ZERO {
+                    let (_, parent_rotation, _) = parent.to_scale_rotation_translation();
+                    model.transform.rotation = parent_rotation * look_rotation(forward);
+                }
+            }
+        }
+    }
+}
+
+struct Drag {
+    point: usize,
+    /// Facing the camera through the point when the drag began.
+    normal: glam::Vec3,
+    /// From the point to where the cursor grabbed it.
+    offset: glam::Vec3,
+}
+
+pub struct Splines {
+    pub splines: Vec<Spline>,
+    pub followers: Vec<FollowSpline>,
+    /// The spline whose handles are shown and can be dragged.
+    pub editing: Option<usize>,
+    /// Draws every spline, not just the one being edited.
+    pub show_all: bool,
+    selected_point: Option<usize>,
+    drag: Option<Drag>,
+    path: String,
+    /// Copies "Duplicate along" makes, ends included.
+    pub array_count: u32,
+    /// Turns the copies along the curve.
+    pub array_align: bool,
+}
+
+impl Default for Splines {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Splines {
+    pub fn new() -> Self {
+        Splines {
+            splines: vec![],
+            followers: vec![],
+            editing: None,
+            show_all: true,
+            selected_point: None,
+            drag: None,
+            path: "path.spline.toml".to_string(),
+            array_count: 10,
+            array_align: true,
+        }
+    }
+
+    /// Returns the spline's index.
+    pub fn add(&mut self, spline: Spline) -> usize {
+        self.splines.push(spline);
+        self.splines.len() - 1
+    }
+
+    /// Copies the selected model along spline `index`.
+    fn array_ui(&mut self, ui: &mut egui::Ui, state: &State, world: &mut World, index: usize) {
+        let source = world
+            .selection
+            .primary()
+            .filter(|&id| world.model(id).is_some());
+        ui.horizontal(|ui| {
+            let clicked = ui
+                .add_enabled(
+                    source.is_some(),
+                    egui::Button::new("Duplicate selected along"),
+                )
+                .clicked();
+            ui.add(egui::DragValue::new(&mut self.array_count).range(1..=10_000));
+            ui.checkbox(&mut self.array_align, "Align");
+            let (Some(source), true) = (source, clicked) else {
+                return;
+            };
+            let model = world.model(source).unwrap();
+            let parent = model
+                .parent
+                .and_then(|p| world.model(p))
+                .map_or(glam::Mat4::IDENTITY, |p| p.global_matrix());
+            let transforms = array::path_transforms(
+                model.transform,
+                &self.splines[index],
+                self.array_count,
+                self.array_align,
+                parent,
+            );
+            let copies = array::duplicate(state, world, source, &transforms);
+            log::info!(
+                "Made {} copies along {}",
+                copies.len(),
+                self.splines[index].name
+            );
+        });
+    }
+
+    /// Followers of the spline go with it.
+    pub fn remove(&mut self, index: usize) {
+        self.splines.remove(index);
+        self.followers.retain(|f| f.spline != index);
+        for follower in &mut self.followers {
+            if follower.spline > index {
+                follower.spline -= 1;
+            }
+        }
+        self.editing = match self.editing {
+            Some(i) if i == index => None,
+            Some(i) if i > index => Some(i - 1),
+            editing => editing,
+        };
+        self.selected_point = None;
+        self.drag = None;
+    }
+
+    /// Grabs, drags and lets go of the edited spline's handles with the
+    /// select binding.
+    fn edit(&mut self, ctx: &mut PluginContext) {
+        let Some(spline) = self.editing.and_then(|i| self.splines.get_mut(i)) else {
+            self.drag = None;
+            return;
+        };
+        let Some(cursor) = ctx.input.cursor() else {
+            return;
+        };
+        let size = glam::vec2(
+            ctx.state.surface_config.width as f32,
+            ctx.state.surface_config.height as f32,
+        ) / ctx.state.scale_factor;
+        let camera = &ctx.world.camera;
+        let ray = picking::cursor_ray(camera, cursor, size);
+
+        if ctx.input.just_pressed(Action::Select) {
+            let nearest = spline
+                .points
+                .iter()
+                .enumerate()
+                .filter_map(|(i, &p)| {
+                    let screen = picking::world_to_cursor(camera, p, size)?;
+                    Some((i, screen.distance(cursor)))
+                })
+                .filter(|&(_, d)| d <= GRAB_RADIUS)
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            self.drag = nearest.map(|(point, _)| {
+                let p = spline.points[point];
+                let normal = (camera.center - camera.eye).normalize_or_zero();
+                let grabbed = ray.at((p - ray.origin).dot(normal) / ray.direction.dot(normal));
+                Drag {
+                    point,
+                    normal,
+                    offset: grabbed - p,
+                }
+            });
+            if let Some(drag) = &self.drag {
+                self.selected_point = Some(drag.point);
+            }
+        }
+
+        let Some(drag) = self.drag.as_ref().filter(|d| d.point < spline.points.len()) else {
+            self.drag = None;
+            return;
+        };
+        if !ctx.input.held(Action::Select) {
+            self.drag = None;
+            return;
+        }
+        ctx.world.camera_controller.held = true;
+        let p = spline.points[drag.point];
+        let facing = ray.direction.dot(drag.normal);
+        if facing.abs() > 1e-4 {
+            let distance = (p - ray.origin).dot(drag.normal) / facing;
+            if distance > 0.0 {
+                let world = &*ctx.world;
+                let target = ray.at(distance) - drag.offset;
+                let snapped = world.snapping.snap_drag(world, &ray, target, |_| false);
+                spline.move_point(drag.point, snapped);
+            }
+        }
+    }
+
+    fn draw(&self, world: &mut World) {
+        let marker = world.camera.eye.distance(world.camera.center) * 0.015;
+        for (index, spline) in self.splines.iter().enumerate() {
+            let editing = self.editing == Some(index);
+            if !editing && !self.show_all {
+                continue;
+            }
+            let steps = spline.segment_count() * DRAW_SAMPLES;
+            for k in 0..steps {
+                let a = spline.position(k as f32 / steps as f32);
+                let b = spline.position((k + 1) as f32 / steps as f32);
+                world.debug_draw.line(a, b, CURVE_COLOR);
+            }
+            if !editing {
+                continue;
+            }
+            let n = spline.points.len();
+            for (i, &point) in spline.points.iter().enumerate() {
+                let color = if self.selected_point == Some(i) {
+                    ACTIVE_COLOR
+                } else if spline.is_anchor(i) {
+                    ANCHOR_COLOR
+                } else {
+                    HANDLE_COLOR
+                };
+                let size = if spline.is_anchor(i) {
+                    marker
+                } else {
+                    marker * 0.6
+                };
+                world.debug_draw.cross(point, size, color);
+                if spline.is_anchor(i) {
+                    continue;
+                }
+                // handles hang off the anchor they shape
+                let anchor = match i % 3 {
+                    1 => Some(i - 1),
+                    _ if i + 1 < n => Some(i + 1),
+                    _ if spline.closed => Some(0),
+                    _ => None,
+                };
+                if let Some(anchor) = anchor {
+                    world
+                        .debug_draw
+                        .line(spline.points[anchor], point, HANDLE_COLOR);
+                }
+            }
+        }
+    }
+
+    /// A few points across the view's target, facing the camera.
+    fn new_spline(&self, world: &World, kind: SplineKind) -> Spline {
+        let camera = &world.camera;
+        let forward = (camera.center - camera.eye).normalize_or_zero();
+        let right = forward.cross(camera.up).normalize_or(glam::Vec3::X);
+        let spacing = camera.eye.distance(camera.center) * 0.2;
+        let count = match kind {
+            SplineKind::CatmullRom => 4,
+            SplineKind::Bezier => 7,
+        };
+        let points = (0..count)
+            .map(|i| {
+                let x = i as f32 / (count - 1) as f32 * 3.0 - 1.5;
+                camera.center + right * x * spacing + camera.up * (x * 2.0).sin() * spacing * 0.5
+            })
+            .collect();
+        Spline::new(&format!("Spline {}", self.splines.len() + 1), kind, points)
+    }
+
+    fn spline_ui(&mut self, ui: &mut egui::Ui, index: usize) {
+        let spline = &mut self.splines[index];
+        ui.horizontal(|ui| {
+            let mut kind = spline.kind;
+            ui.radio_value(&mut kind, SplineKind::CatmullRom, "Catmull-Rom");
+            ui.radio_value(&mut kind, SplineKind::Bezier, "Bézier");
+            if kind != spline.kind {
+                spline.set_kind(kind);
+                self.selected_point = None;
+            }
+            ui.checkbox(&mut spline.closed, "Closed");
+        });
+        ui.label(format!(
+            "{} points, {} segments, {:.2} long",
+            spline.points.len(),
+            spline.segment_count(),
+            spline.length()
+        ));
+        ui.label("Drag handles with the select binding.");
+
+        if let Some(i) = self.selected_point.filter(|&i| i < spline.points.len()) {
+            let mut point = spline.points[i];
+            ui.horizontal(|ui| {
+                let role = if spline.is_anchor(i) {
+                    "Point"
+                } else {
+                    "Handle"
+                };
+                ui.label(format!("{role} {i}"));
+                let mut changed = false;
+                for value in [&mut point.x, &mut point.y, &mut point.z] {
+                    changed |= ui.add(egui::DragValue::new(value).speed(0.1)).changed();
+                }
+                if changed {
+                    spline.move_point(i, point);
+                }
+            });
+        }
+        ui.horizontal(|ui| {
+            if ui.button("Add point").clicked() {
+                let end = spline.position(1.0);
+                let step = spline.length() / spline.segment_count().max(1) as f32;
+                let direction = spline.tangent(1.0);
+                let direction = if direction == glam::Vec3::ZERO {
+                    glam::Vec3::X
+                } else {
+                    direction
+                };
+                spline.extend(end + direction * step.max(1.0));
+            }
+            let can_remove = self.selected_point.is_some() && spline.points.len() > 1;
+            if ui
+                .add_enabled(can_remove, egui::Button::new("Remove point"))
+                .clicked()
+            {
+                if let Some(i) = self.selected_point.take() {
+                    spline.remove_point(i);
+                }
+            }
+        });
+    }
+
+    fn followers_ui(&mut self, ui: &mut egui::Ui, world: &World) {
+        let mut remove = None;
+        for (i, follower) in self.followers.iter_mut().enumerate() {
+            let Some(spline) = self.splines.get(follower.spline) else {
+                continue;
+            };
+            ui.push_id(i, |ui| {
+                ui.separator();
+                let target = match follower.target {
+                    FollowTarget::Camera => "Camera".to_string(),
+                    FollowTarget::Model(id) => world
+                        .model(id)
+                        .map_or(format!("#{id}"), |m| format!("#{id} {}", m.name)),
+                };
+                ui.horizontal(|ui| {
+                    ui.label(format!("{target} on {}", spline.name));
+                    ui.checkbox(&mut follower.playing, "Play");
+                    if ui.small_button("Remove").clicked() {
+                        remove = Some(i);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut follower.mode, FollowMode::Loop, "Loop");
+                    ui.radio_value(&mut follower.mode, FollowMode::PingPong, "Ping-pong");
+                    ui.radio_value(&mut follower.mode, FollowMode::Once, "Once");
+                });
+                ui.add(egui::Slider::new(&mut follower.speed, 0.0..=200.0).text("Speed"));
+                let length = spline.length();
+                ui.add(egui::Slider::new(&mut follower.distance, 0.0..=length).text("Distance"));
+                let orient = match follower.target {
+                    FollowTarget::Camera => "Look ahead",
+                    FollowTarget::Model(_) => "Face along curve",
+                };
+                ui.checkbox(&mut follower.orient, orient);
+                if follower.target == FollowTarget::Camera && follower.orient {
+                    ui.add(
+                        egui::Slider::new(&mut follower.look_ahead, 0.1..=200.0).text("Look ahead"),
+                    );
+                }
+            });
+        }
+        if let Some(i) = remove {
+            self.followers.remove(i);
+        }
+    }
+}
+
+impl Plugin for Splines {
+    fn name(&self) -> &str {
+        "Splines"
+    }
+
+    fn update(&mut self, ctx: &mut PluginContext) {
+        self.edit(ctx);
+
+        let world = &mut *ctx.world;
+        self.followers.retain(|f| match f.target {
+            FollowTarget::Model(id) => world.model(id).is_some(),
+            FollowTarget::Camera => true,
+        });
+        for follower in &mut self.followers {
+            let Some(spline) = self.splines.get(follower.spline) else {
+                continue;
+            };
+            follower.advance(spline.length(), ctx.time.delta_seconds);
+            follower.apply(spline, world);
+        }
+
+        self.draw(world);
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, state: &State, world: &mut World) {
+        egui::Window::new("Splines")
+            .default_open(false)
+            .vscroll(true)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    for (kind, label) in [
+                        (SplineKind::CatmullRom, "New Catmull-Rom"),
+                        (SplineKind::Bezier, "New Bézier"),
+                    ] {
+                        if ui.button(label).clicked() {
+                            let spline = self.new_spline(world, kind);
+                            self.editing = Some(self.add(spline));
+                            self.selected_point = None;
+                        }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.path);
+                    if ui.button("Load").clicked() {
+                        match Spline::load(&self.path) {
+                            Ok(spline) => {
+                                self.editing = Some(self.add(spline));
+                                self.selected_point = None;
+                            }
+                            Err(e) => log::error!("Failed to load spline: {e}"),
+                        }
+                    }
+                    let editing = self.editing.and_then(|i| self.splines.get(i));
+                    if ui
+                        .add_enabled(editing.is_some(), egui::Button::new("Save"))
+                        .clicked()
+                    {
+                        if let Some(Err(e)) = editing.map(|s| s.save(&self.path)) {
+                            log::error!("Failed to save {}: {e}", self.path);
+                        }
+                    }
+                });
+                ui.checkbox(&mut self.show_all, "Show all splines");
+
+                let mut remove = None;
+                for i in 0..self.splines.len() {
+                    ui.push_id(i, |ui| {
+                        ui.horizontal(|ui| {
+                            let editing = self.editing == Some(i);
+                            if ui.selectable_label(editing, "Edit").clicked() {
+                                self.editing = if editing { None } else { Some(i) };
+                                self.selected_point = None;
+                            }
+                            ui.text_edit_singleline(&mut self.splines[i].name);
+                            if ui.small_button("Remove").clicked() {
+                                remove = Some(i);
+                            }
+                        });
+                    });
+                }
+                if let Some(i) = remove {
+                    self.remove(i);
+                }
+
+                if let Some(index) = self.editing.filter(|&i| i < self.splines.len()) {
+                    ui.separator();
+                    self.spline_ui(ui, index);
+                    ui.horizontal(|ui| {
+                        let selected = world.selection.primary();
+                        if ui
+                            .add_enabled(
+                                selected.is_some(),
+                                egui::Button::new("Follow with selected"),
+                            )
+                            .clicked()
+                        {
+                            if let Some(id) = selected {
+                                self.followers
+                                    .retain(|f| f.target != FollowTarget::Model(id));
+                                self.followers
+                                    .push(FollowSpline::new(index, FollowTarget::Model(id)));
+                            }
+                        }
+                        if ui.button("Follow with camera").clicked() {
+                            self.followers.retain(|f| f.target != FollowTarget::Camera);
+                            self.followers
+                                .push(FollowSpline::new(index, FollowTarget::Camera));
+                        }
+                    });
+                    self.array_ui(ui, state, world, index);
+                }
+                self.followers_ui(ui, world);
+            });
+    }
+}
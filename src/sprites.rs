@@ -0,0 +1,286 @@
+use crate::app::State;
+use crate::mesh_builder::GrowableBuffer;
+use crate::texture::Texture;
+use std::sync::Arc;
+
+const SHADER: &str = r#"
+struct Screen { size: vec2<f32> };
+@group(0) @binding(0) var<uniform> screen: Screen;
+@group(1) @binding(0) var sprite_texture: texture_2d<f32>;
+@group(1) @binding(1) var sprite_sampler: sampler;
+
+struct Instance {
+    @location(0) position_size: vec4<f32>,
+    @location(1) region: vec4<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) rotation: vec4<f32>,
+};
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vsMain(@builtin(vertex_index) i: u32, instance: Instance) -> VSOut {
+    let corner = vec2(f32(i & 1u), f32(i >> 1u));
+    let local = (corner - 0.5) * instance.position_size.zw;
+    let c = cos(instance.rotation.x);
+    let s = sin(instance.rotation.x);
+    let pixel = instance.position_size.xy + vec2(local.x * c - local.y * s, local.x * s + local.y * c);
+
+    var out: VSOut;
+    let ndc = pixel / screen.size * 2.0 - 1.0;
+    out.pos = vec4(ndc.x, -ndc.y, 0.0, 1.0);
+    out.uv = mix(instance.region.xy, instance.region.zw, corner);
+    out.color = instance.color;
+    return out;
+}
+
+@fragment
+fn psMain(in: VSOut) -> @location(0) vec4<f32> {
+    return textureSample(sprite_texture, sprite_sampler, in.uv) * in.color;
+}
+"#;
+
+/// A screen-space quad, drawn after the 3D scene.
+#[derive(Clone)]
+pub struct Sprite {
+    /// Center in logical pixels from the top-left corner of the window.
+    pub position: glam::Vec2,
+    pub size: glam::Vec2,
+    /// Radians, clockwise on screen.
+    pub rotation: f32,
+    /// UV rectangle of `texture` to show: min x, min y, max x, max y.
+    pub region: [f32; 4],
+    pub color: [f32; 4],
+    /// Higher values draw on top.
+    pub z: f32,
+    /// Solid `color` when `None`.
+    pub texture: Option<Arc<Texture>>,
+}
+
+impl Default for Sprite {
+    fn default() -> Self {
+        Sprite {
+            position: glam::Vec2::ZERO,
+            size: glam::Vec2::splat(64.0),
+            rotation: 0.0,
+            region: [0.0, 0.0, 1.0, 1.0],
+            color: [1.0; 4],
+            z: 0.0,
+            texture: None,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct SpriteInstance {
+    position_size: [f32; 4],
+    region: [f32; 4],
+    color: [f32; 4],
+    rotation: [f32; 4],
+}
+
+impl SpriteInstance {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+        0 => Float32x4,
+        1 => Float32x4,
+        2 => Float32x4,
+        3 => Float32x4
+    ];
+}
+
+/// Orthographic 2D layer. Sprites are sorted by `z` and drawn in one
+/// instanced draw per run of sprites sharing a texture.
+pub struct SpriteLayer {
+    pub sprites: Vec<Sprite>,
+    pipeline: wgpu::RenderPipeline,
+    texture_layout: wgpu::BindGroupLayout,
+    screen_buffer: wgpu::Buffer,
+    screen_bind_group: wgpu::BindGroup,
+    white: Arc<Texture>,
+    instances: GrowableBuffer,
+    pub draw_calls: u32,
+}
+
+impl SpriteLayer {
+    pub fn new(state: &State) -> Self {
+        let device = &state.device;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Sprites"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let screen_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Sprite Screen"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let texture_layout =
+            Texture::create_bind_group_layout(device, wgpu::ShaderStages::FRAGMENT);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Sprites"),
+            bind_group_layouts: &[&screen_layout, &texture_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Sprites"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vsMain"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<SpriteInstance>() as u64,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &SpriteInstance::ATTRIBUTES,
+                }],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("psMain"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: state.surface_config.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let screen_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Sprite Screen"),
+            size: 16,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let screen_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Sprite Screen"),
+            layout: &screen_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: screen_buffer.as_entire_binding(),
+            }],
+        });
+        let white = Arc::new(Texture::from_rgba8(state, "White", 1, 1, &[255; 4], true));
+
+        SpriteLayer {
+            sprites: vec![],
+            pipeline,
+            texture_layout,
+            screen_buffer,
+            screen_bind_group,
+            white,
+            instances: GrowableBuffer::new("Sprite Instances", wgpu::BufferUsages::VERTEX),
+            draw_calls: 0,
+        }
+    }
+
+    /// Draws all sprites over `view` in their own pass.
+    pub fn render(
+        &mut self,
+        state: &State,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+    ) {
+        self.draw_calls = 0;
+        if self.sprites.is_empty() {
+            return;
+        }
+
+        let mut order: Vec<&Sprite> = self.sprites.iter().collect();
+        order.sort_by(|a, b| {
+            a.z.total_cmp(&b.z)
+                .then_with(|| texture_key(a).cmp(&texture_key(b)))
+        });
+
+        let instances: Vec<SpriteInstance> = order
+            .iter()
+            .map(|s| SpriteInstance {
+                position_size: [s.position.x, s.position.y, s.size.x, s.size.y],
+                region: s.region,
+                color: s.color,
+                rotation: [s.rotation, 0.0, 0.0, 0.0],
+            })
+            .collect();
+        self.instances
+            .write(&state.device, &state.queue, &instances);
+
+        let screen = [
+            state.surface_config.width as f32 / state.scale_factor,
+            state.surface_config.height as f32 / state.scale_factor,
+            0.0,
+            0.0,
+        ];
+        state
+            .queue
+            .write_buffer(&self.screen_buffer, 0, bytemuck::cast_slice(&screen));
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("sprites"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                depth_slice: None,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.screen_bind_group, &[]);
+        pass.set_vertex_buffer(0, self.instances.slice().unwrap());
+
+        let mut start = 0;
+        while start < order.len() {
+            let key = texture_key(order[start]);
+            let end = start
+                + order[start..]
+                    .iter()
+                    .take_while(|s| texture_key(s) == key)
+                    .count();
+            let texture = order[start].texture.as_ref().unwrap_or(&self.white);
+            let bind_group = texture.create_bind_group(state, &self.texture_layout);
+            pass.set_bind_group(1, &bind_group, &[]);
+            pass.draw(0..4, start as u32..end as u32);
+            self.draw_calls += 1;
+            start = end;
+        }
+        let target = view.texture();
+        state
+            .profiler
+            .record_draws(self.draw_calls, order.len() as u32);
+        state
+            .profiler
+            .record_target(target.width(), target.height());
+    }
+}
+
+fn texture_key(sprite: &Sprite) -> usize {
+    sprite
+        .texture
+        .as_ref()
+        .map_or(0, |t| Arc::as_ptr(t) as usize)
+}
@@ -0,0 +1,131 @@
+use crate::app::State;
+use crate::model::EntityId;
+use crate::plugin::Plugin;
+use crate::transform::Transform;
+use crate::world::World;
+use std::time::Instant;
+
+/// Spawns an N×N×N grid of one mesh to see how spawning and drawing scale.
+pub struct StressTest {
+    pub size: u32,
+    pub spacing: f32,
+    pub mesh: usize,
+    spawned: Vec<EntityId>,
+    status: String,
+}
+
+impl Default for StressTest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StressTest {
+    pub fn new() -> Self {
+        StressTest {
+            size: 8,
+            spacing: 150.0,
+            mesh: 0,
+            spawned: vec![],
+            status: String::new(),
+        }
+    }
+
+    /// Transforms get a jittered rotation and scale from the grid position,
+    /// and materials cycle through everything the world has loaded.
+    pub fn spawn(&mut self, state: &State, world: &mut World) {
+        let Some(mesh) = world.meshes.get(self.mesh).cloned() else {
+            return;
+        };
+        let start = Instant::now();
+        let n = self.size;
+        let offset = (n as f32 - 1.0) * self.spacing * 0.5;
+        for x in 0..n {
+            for y in 0..n {
+                for z in 0..n {
+                    let i = (x * n + y) * n + z;
+                    let hash = hash(i);
+                    let transform = Transform {
+                        translation: glam::vec3(x as f32, y as f32, z as f32) * self.spacing
+                            - glam::Vec3::splat(offset),
+                        rotation: glam::Quat::from_rotation_y(hash * std::f32::consts::TAU),
+                        scale: glam::Vec3::splat(0.5 + hash),
+                    };
+                    let material = world.materials[i as usize % world.materials.len()].clone();
+                    let name = format!("{} (stress {x},{y},{z})", mesh.name);
+                    let id = world.spawn(state, &name, mesh.clone(), material, transform);
+                    self.spawned.push(id);
+                }
+            }
+        }
+        self.status = format!(
+            "Spawned {} in {:.1} ms",
+            n * n * n,
+            start.elapsed().as_secs_f32() * 1000.0
+        );
+        log::info!("{}", self.status);
+    }
+
+    pub fn despawn(&mut self, world: &mut World) {
+        let start = Instant::now();
+        let count = self.spawned.len();
+        let ids: std::collections::HashSet<EntityId> = self.spawned.drain(..).collect();
+        world.models.retain(|m| !ids.contains(&m.id));
+        self.status = format!(
+            "Despawned {count} in {:.1} ms",
+            start.elapsed().as_secs_f32() * 1000.0
+        );
+        log::info!("{}", self.status);
+    }
+}
+
+/// Cheap deterministic 0..1 value per index.
+fn hash(i: u32) -> f32 {
+    let mut h = i.wrapping_mul(0x9E37_79B9);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85EB_CA6B);
+    h ^= h >> 13;
+    (h & 0xFFFF) as f32 / 65535.0
+}
+
+impl Plugin for StressTest {
+    fn name(&self) -> &str {
+        "Stress Test"
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, state: &State, world: &mut World) {
+        egui::Window::new("Stress Test")
+            .default_open(false)
+            .show(ctx, |ui| {
+                ui.add(egui::Slider::new(&mut self.size, 1..=32).text("N (N³ entities)"));
+                ui.add(egui::Slider::new(&mut self.spacing, 1.0..=500.0).text("Spacing"));
+                let selected = world.meshes.get(self.mesh).map_or("", |m| &m.name);
+                egui::ComboBox::from_label("Mesh")
+                    .selected_text(selected)
+                    .show_ui(ui, |ui| {
+                        for (i, mesh) in world.meshes.iter().enumerate() {
+                            ui.selectable_value(&mut self.mesh, i, &mesh.name);
+                        }
+                    });
+                ui.horizontal(|ui| {
+                    if ui.button("Spawn").clicked() {
+                        self.spawn(state, world);
+                    }
+                    if ui
+                        .add_enabled(!self.spawned.is_empty(), egui::Button::new("Despawn"))
+                        .clicked()
+                    {
+                        self.despawn(world);
+                    }
+                });
+                ui.label(format!(
+                    "{} spawned, {} models total",
+                    self.spawned.len(),
+                    world.models.len()
+                ));
+                if !self.status.is_empty() {
+                    ui.label(&self.status);
+                }
+            });
+    }
+}
@@ -0,0 +1,402 @@
+use crate::app::State;
+use crate::sampler::SamplerDesc;
+use std::path::Path;
+
+/// A sampled texture: the texture, its default view and a sampler. Most are
+/// plain 2D; DDS and KTX2 files can also hold cube maps, arrays and volumes.
+pub struct Texture {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    /// Sampler settings `create_bind_group` fetches from the sampler cache;
+    /// material bindings can pick others.
+    pub sampler_desc: SamplerDesc,
+    /// How shaders see `view`, e.g. `texture_cube` for `Cube`.
+    pub view_dimension: wgpu::TextureViewDimension,
+}
+
+/// Pixels for a texture of any shape, level by level: every layer (or
+/// depth slice) of mip 0 back to back, then mip 1, and so on.
+pub struct TextureData {
+    pub format: wgpu::TextureFormat,
+    pub view_dimension: wgpu::TextureViewDimension,
+    /// `depth_or_array_layers` is the depth of a volume, or the layer count
+    /// of anything else, with six layers per cube.
+    pub size: wgpu::Extent3d,
+    pub mip_level_count: u32,
+    pub bytes: Vec<u8>,
+}
+
+impl TextureData {
+    pub fn dimension(&self) -> wgpu::TextureDimension {
+        match self.view_dimension {
+            wgpu::TextureViewDimension::D3 => wgpu::TextureDimension::D3,
+            _ => wgpu::TextureDimension::D2,
+        }
+    }
+
+    /// Size of mip `level` rounded up to whole compression blocks.
+    fn level_size(&self, level: u32) -> wgpu::Extent3d {
+        self.size
+            .mip_level_size(level, self.dimension())
+            .physical_size(self.format)
+    }
+
+    /// Bytes in one row of blocks at `level`.
+    fn bytes_per_row(&self, level: u32) -> u32 {
+        let (block_width, _) = self.format.block_dimensions();
+        let block_size = self.format.block_copy_size(None).unwrap();
+        self.level_size(level).width / block_width * block_size
+    }
+
+    /// Rows of blocks in one layer or slice at `level`.
+    fn rows_per_image(&self, level: u32) -> u32 {
+        self.level_size(level).height / self.format.block_dimensions().1
+    }
+
+    /// Bytes in mip `level`, across every layer or slice; `None` when that
+    /// doesn't fit in memory, as a corrupt header can claim.
+    pub fn level_len(&self, level: u32) -> Option<usize> {
+        let mip = |extent: u32| Some(u64::from(extent.checked_shr(level)?.max(1)));
+        let (block_width, block_height) = self.format.block_dimensions();
+        let block_size = self.format.block_copy_size(None)?;
+        let layers = match self.dimension() {
+            wgpu::TextureDimension::D3 => mip(self.size.depth_or_array_layers)?,
+            _ => u64::from(self.size.depth_or_array_layers),
+        };
+        let len = mip(self.size.width)?
+            .div_ceil(block_width.into())
+            .checked_mul(block_size.into())?
+            .checked_mul(mip(self.size.height)?.div_ceil(block_height.into()))?
+            .checked_mul(layers)?;
+        usize::try_from(len).ok()
+    }
+
+    /// Rejects what a file header can claim but no texture can have: an
+    /// empty size, or more mips than halving the size allows.
+    pub fn validate(&self) -> Result<(), String> {
+        let size = self.size;
+        if size.width == 0 || size.height == 0 || size.depth_or_array_layers == 0 {
+            return Err("texture has a zero size".to_string());
+        }
+        let mut max_dim = size.width.max(size.height);
+        if self.dimension() == wgpu::TextureDimension::D3 {
+            max_dim = max_dim.max(size.depth_or_array_layers);
+        }
+        if self.mip_level_count == 0 || self.mip_level_count > 32 - max_dim.leading_zeros() {
+            return Err(format!(
+                "{} mip levels for a {}x{} texture",
+                self.mip_level_count, size.width, size.height
+            ));
+        }
+        Ok(())
+    }
+
+    /// Rejects sizes past what the device allows, which `from_data` would
+    /// otherwise fail on with a validation error.
+    pub fn check_limits(&self, limits: &wgpu::Limits) -> Result<(), String> {
+        let size = self.size;
+        let (max_dim, max_layers) = match self.dimension() {
+            wgpu::TextureDimension::D3 => (limits.max_texture_dimension_3d, u32::MAX),
+            _ => (
+                limits.max_texture_dimension_2d,
+                limits.max_texture_array_layers,
+            ),
+        };
+        let depth = match self.dimension() {
+            wgpu::TextureDimension::D3 => size.depth_or_array_layers,
+            _ => 1,
+        };
+        if size.width.max(size.height).max(depth) > max_dim {
+            return Err(format!(
+                "{}x{}x{depth} is larger than the device's {max_dim}",
+                size.width, size.height
+            ));
+        }
+        if size.depth_or_array_layers > max_layers {
+            return Err(format!(
+                "{} layers is more than the device's {max_layers}",
+                size.depth_or_array_layers
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Texture {
+    /// `pixels` are tightly packed RGBA8 rows.
+    pub fn from_rgba8(
+        state: &State,
+        label: &str,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+        srgb: bool,
+    ) -> Self {
+        let format = if srgb {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
+        };
+        Self::from_data(
+            state,
+            label,
+            &TextureData {
+                format,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                bytes: pixels.to_vec(),
+            },
+        )
+    }
+
+    /// Uploads every level and layer of `data`. The format's features,
+    /// e.g. BC compression, must be enabled on the device, and its size
+    /// within the device's limits.
+    pub fn from_data(state: &State, label: &str, data: &TextureData) -> Self {
+        let texture = state.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: data.size,
+            mip_level_count: data.mip_level_count,
+            sample_count: 1,
+            dimension: data.dimension(),
+            format: data.format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let mut offset = 0;
+        for level in 0..data.mip_level_count {
+            let len = data.level_len(level).expect("texture level is too large");
+            state.queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    mip_level: level,
+                    ..texture.as_image_copy()
+                },
+                &data.bytes[offset..offset + len],
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(data.bytes_per_row(level)),
+                    rows_per_image: Some(data.rows_per_image(level)),
+                },
+                data.level_size(level),
+            );
+            offset += len;
+        }
+
+        Self::with_view_dimension(state, texture, data.view_dimension)
+    }
+
+    /// Wraps a texture created elsewhere (e.g. a compute target) with a
+    /// default view and sampler.
+    pub fn from_texture(state: &State, texture: wgpu::Texture) -> Self {
+        let view_dimension = match texture.dimension() {
+            wgpu::TextureDimension::D1 => wgpu::TextureViewDimension::D1,
+            wgpu::TextureDimension::D2 if texture.depth_or_array_layers() > 1 => {
+                wgpu::TextureViewDimension::D2Array
+            }
+            wgpu::TextureDimension::D2 => wgpu::TextureViewDimension::D2,
+            wgpu::TextureDimension::D3 => wgpu::TextureViewDimension::D3,
+        };
+        Self::with_view_dimension(state, texture, view_dimension)
+    }
+
+    /// Cube maps and volumes clamp at their edges; everything else repeats.
+    /// Formats that can't be filtered get a nearest sampler.
+    fn with_view_dimension(
+        state: &State,
+        texture: wgpu::Texture,
+        view_dimension: wgpu::TextureViewDimension,
+    ) -> Self {
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(view_dimension),
+            ..Default::default()
+        });
+        let address_mode = match view_dimension {
+            wgpu::TextureViewDimension::D2 | wgpu::TextureViewDimension::D2Array => {
+                wgpu::AddressMode::Repeat
+            }
+            _ => wgpu::AddressMode::ClampToEdge,
+        };
+        let filter = if filterable(&state.device, texture.format()) {
+            wgpu::FilterMode::Linear
+        } else {
+            wgpu::FilterMode::Nearest
+        };
+        let sampler_desc = SamplerDesc::new()
+            .with_filter(filter)
+            .with_address_mode(address_mode);
+
+        Texture {
+            sampler_desc,
+            texture,
+            view,
+            view_dimension,
+        }
+    }
+
+    /// PNG, JPEG and the other formats the `image` crate reads load as 2D
+    /// RGBA8. `.dds` and `.ktx2` files keep their own format, mips and
+    /// layout; `srgb` only applies to DDS files without a DX10 header.
+    pub fn load(state: &State, path: impl AsRef<Path>, srgb: bool) -> Result<Self, String> {
+        let path = path.as_ref();
+        let label = path.display().to_string();
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        let read = |path: &Path| crate::vfs::read(path).map_err(|e| e.to_string());
+        let data = match extension.as_deref() {
+            Some("dds") => crate::texture_file::parse_dds(&read(path)?, srgb)?,
+            Some("ktx2") => crate::texture_file::parse_ktx2(&read(path)?)?,
+            _ => {
+                let (width, height, pixels) = decode_image(&read(path)?)?;
+                crate::diagnostics::record_asset(&label);
+                return Ok(Self::from_rgba8(
+                    state, &label, width, height, &pixels, srgb,
+                ));
+            }
+        };
+        data.check_limits(&state.device.limits())?;
+        let missing = data.format.required_features() - state.device.features();
+        if !missing.is_empty() {
+            return Err(format!(
+                "{:?} needs device features {missing:?}",
+                data.format
+            ));
+        }
+        crate::diagnostics::record_asset(&label);
+        Ok(Self::from_data(state, &label, &data))
+    }
+
+    /// Layout for a filterable 2D texture at binding 0 and its sampler at
+    /// binding 1.
+    pub fn create_bind_group_layout(
+        device: &wgpu::Device,
+        visibility: wgpu::ShaderStages,
+    ) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Texture Bind Group Layout"),
+            entries: &layout_entries(
+                visibility,
+                wgpu::TextureViewDimension::D2,
+                wgpu::TextureSampleType::Float { filterable: true },
+                false,
+                0,
+            ),
+        })
+    }
+
+    /// Layout entries matching this texture's dimension and format, with
+    /// the texture at `binding` and a sampler like `sampler` after it.
+    /// Filterable textures take any non-comparison sampler, so their
+    /// filters can change later without a new layout.
+    pub fn layout_entries(
+        &self,
+        device: &wgpu::Device,
+        visibility: wgpu::ShaderStages,
+        sampler: &SamplerDesc,
+        binding: u32,
+    ) -> [wgpu::BindGroupLayoutEntry; 2] {
+        let sample_type = self
+            .texture
+            .format()
+            .sample_type(None, Some(device.features()))
+            .expect("depth-stencil textures can't be sampled as one binding");
+        layout_entries(
+            visibility,
+            self.view_dimension,
+            sample_type,
+            sampler.compare.is_some(),
+            binding,
+        )
+    }
+
+    /// Whether linear filtering works on this texture's format.
+    pub fn filterable(&self, device: &wgpu::Device) -> bool {
+        filterable(device, self.texture.format())
+    }
+
+    pub fn create_bind_group(
+        &self,
+        state: &State,
+        layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::BindGroup {
+        let sampler = state.samplers.get(&state.device, self.sampler_desc);
+        self.create_bind_group_with(&state.device, layout, &sampler)
+    }
+
+    /// Like `create_bind_group`, with another sampler in place of the
+    /// texture's own.
+    pub fn create_bind_group_with(
+        &self,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&self.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        })
+    }
+}
+
+fn filterable(device: &wgpu::Device, format: wgpu::TextureFormat) -> bool {
+    format.sample_type(None, Some(device.features()))
+        == Some(wgpu::TextureSampleType::Float { filterable: true })
+}
+
+pub(crate) fn layout_entries(
+    visibility: wgpu::ShaderStages,
+    view_dimension: wgpu::TextureViewDimension,
+    sample_type: wgpu::TextureSampleType,
+    compare: bool,
+    binding: u32,
+) -> [wgpu::BindGroupLayoutEntry; 2] {
+    let sampler = match sample_type {
+        _ if compare => wgpu::SamplerBindingType::Comparison,
+        wgpu::TextureSampleType::Float { filterable: true } => wgpu::SamplerBindingType::Filtering,
+        _ => wgpu::SamplerBindingType::NonFiltering,
+    };
+    [
+        wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension,
+                multisampled: false,
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: binding + 1,
+            visibility,
+            ty: wgpu::BindingType::Sampler(sampler),
+            count: None,
+        },
+    ]
+}
+
+/// RGBA8 pixels of a PNG, JPEG or the like. Not cached: raw pixels are
+/// several times the file's size and no quicker to read back than to
+/// decode.
+pub(crate) fn decode_image(bytes: &[u8]) -> Result<(u32, u32, Vec<u8>), String> {
+    let image = image::load_from_memory(bytes)
+        .map_err(|e| e.to_string())?
+        .into_rgba8();
+    Ok((image.width(), image.height(), image.into_raw()))
+}
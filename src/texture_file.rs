@@ -0,0 +1,392 @@
+//! DDS and KTX2 container parsing, for textures that PNG can't hold:
+//! cube maps, arrays, volumes, mip chains and block-compressed formats.
+//! KTX2 supercompression (Basis Universal, zstd) is not supported.
+
+use crate::texture::TextureData;
+use wgpu::TextureFormat as F;
+use wgpu::TextureViewDimension as View;
+
+fn u32_at(bytes: &[u8], offset: usize) -> Result<u32, String> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| "file is truncated".to_string())
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> Result<u64, String> {
+    bytes
+        .get(offset..offset + 8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| "file is truncated".to_string())
+}
+
+fn view_dimension(volume: bool, cube: bool, layers: u32) -> View {
+    match (volume, cube, layers > 1) {
+        (true, _, _) => View::D3,
+        (false, true, true) => View::CubeArray,
+        (false, true, false) => View::Cube,
+        (false, false, true) => View::D2Array,
+        (false, false, false) => View::D2,
+    }
+}
+
+const DDS_MAGIC: &[u8] = b"DDS ";
+const DDSD_MIPMAPCOUNT: u32 = 0x2_0000;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDPF_LUMINANCE: u32 = 0x2_0000;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_VOLUME: u32 = 0x20_0000;
+const DX10_TEXTURE3D: u32 = 4;
+const DX10_TEXTURECUBE: u32 = 0x4;
+
+/// Reads a DDS file. `srgb` picks the sRGB variant of formats from legacy
+/// headers, which can't say; DX10 headers name the format exactly.
+pub fn parse_dds(bytes: &[u8], srgb: bool) -> Result<TextureData, String> {
+    if bytes.get(..4) != Some(DDS_MAGIC) {
+        return Err("not a DDS file".to_string());
+    }
+    // offsets below are into the header, which follows the magic
+    let header = &bytes[4..];
+    let flags = u32_at(header, 4)?;
+    let height = u32_at(header, 8)?;
+    let width = u32_at(header, 12)?;
+    let depth = u32_at(header, 20)?.max(1);
+    let mip_level_count = if flags & DDSD_MIPMAPCOUNT != 0 {
+        u32_at(header, 24)?.max(1)
+    } else {
+        1
+    };
+    let pixel_flags = u32_at(header, 76)?;
+    let four_cc = header.get(80..84).ok_or("file is truncated")?;
+    let caps2 = u32_at(header, 108)?;
+
+    let (format, volume, cube, array_size, data_start) = if four_cc == b"DX10" {
+        // the DX10 header follows the 124-byte legacy one
+        let format = dxgi_format(u32_at(header, 124)?)?;
+        let volume = u32_at(header, 128)? == DX10_TEXTURE3D;
+        let cube = u32_at(header, 132)? & DX10_TEXTURECUBE != 0;
+        (
+            format,
+            volume,
+            cube,
+            u32_at(header, 136)?.max(1),
+            4 + 124 + 20,
+        )
+    } else {
+        let format = if pixel_flags & DDPF_FOURCC != 0 {
+            match four_cc {
+                b"DXT1" => F::Bc1RgbaUnorm,
+                b"DXT3" => F::Bc2RgbaUnorm,
+                b"DXT5" => F::Bc3RgbaUnorm,
+                b"ATI2" | b"BC5U" => F::Bc5RgUnorm,
+                // D3DFMT_A16B16G16R16F and D3DFMT_A32B32G32R32F
+                [113, 0, 0, 0] => F::Rgba16Float,
+                [116, 0, 0, 0] => F::Rgba32Float,
+                _ => return Err(format!("unsupported DDS format {four_cc:?}")),
+            }
+        } else {
+            let bits = u32_at(header, 84)?;
+            let red_mask = u32_at(header, 88)?;
+            match (
+                pixel_flags & (DDPF_RGB | DDPF_LUMINANCE) != 0,
+                bits,
+                red_mask,
+            ) {
+                (true, 32, 0xff) => F::Rgba8Unorm,
+                (true, 32, 0xff_0000) => F::Bgra8Unorm,
+                (true, 8, 0xff) => F::R8Unorm,
+                _ => return Err(format!("unsupported {bits}-bit DDS pixel format")),
+            }
+        };
+        let format = if srgb {
+            format.add_srgb_suffix()
+        } else {
+            format
+        };
+        let volume = caps2 & DDSCAPS2_VOLUME != 0;
+        let cube = caps2 & DDSCAPS2_CUBEMAP != 0;
+        (format, volume, cube, 1, 4 + 124)
+    };
+
+    let layers = array_size
+        .checked_mul(if cube { 6 } else { 1 })
+        .ok_or("too many DDS layers")?;
+    let mut data = TextureData {
+        format,
+        view_dimension: view_dimension(volume, cube, array_size),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: if volume { depth } else { layers },
+        },
+        mip_level_count,
+        bytes: vec![],
+    };
+    data.validate()?;
+
+    // DDS stores each layer's whole mip chain in turn; TextureData wants
+    // each level's layers together. A volume's slices are one image.
+    let images = if volume { 1 } else { layers as usize };
+    let per_image = (0..mip_level_count)
+        .map(|level| data.level_len(level).map(|len| len / images))
+        .collect::<Option<Vec<usize>>>()
+        .ok_or("DDS texture is too large")?;
+    let chain: usize = per_image.iter().sum();
+    let payload = chain
+        .checked_mul(images)
+        .and_then(|len| bytes.get(data_start..usize::checked_add(data_start, len)?))
+        .ok_or("DDS data is truncated")?;
+    for level in 0..mip_level_count as usize {
+        let level_offset: usize = per_image[..level].iter().sum();
+        for layer in 0..images {
+            let start = layer * chain + level_offset;
+            data.bytes
+                .extend_from_slice(&payload[start..start + per_image[level]]);
+        }
+    }
+    Ok(data)
+}
+
+fn dxgi_format(format: u32) -> Result<F, String> {
+    Ok(match format {
+        2 => F::Rgba32Float,
+        10 => F::Rgba16Float,
+        28 => F::Rgba8Unorm,
+        29 => F::Rgba8UnormSrgb,
+        41 => F::R32Float,
+        54 => F::R16Float,
+        61 => F::R8Unorm,
+        71 => F::Bc1RgbaUnorm,
+        72 => F::Bc1RgbaUnormSrgb,
+        74 => F::Bc2RgbaUnorm,
+        75 => F::Bc2RgbaUnormSrgb,
+        77 => F::Bc3RgbaUnorm,
+        78 => F::Bc3RgbaUnormSrgb,
+        80 => F::Bc4RUnorm,
+        83 => F::Bc5RgUnorm,
+        87 => F::Bgra8Unorm,
+        91 => F::Bgra8UnormSrgb,
+        95 => F::Bc6hRgbUfloat,
+        98 => F::Bc7RgbaUnorm,
+        99 => F::Bc7RgbaUnormSrgb,
+        _ => return Err(format!("unsupported DXGI format {format}")),
+    })
+}
+
+const KTX2_MAGIC: [u8; 12] = [
+    0xab, b'K', b'T', b'X', b' ', b'2', b'0', 0xbb, b'\r', b'\n', 0x1a, b'\n',
+];
+
+/// Reads a KTX2 file. Its levels are already stored layer by layer, so the
+/// level data is copied as is.
+pub fn parse_ktx2(bytes: &[u8]) -> Result<TextureData, String> {
+    if bytes.get(..12) != Some(&KTX2_MAGIC[..]) {
+        return Err("not a KTX2 file".to_string());
+    }
+    let format = vk_format(u32_at(bytes, 12)?)?;
+    let width = u32_at(bytes, 20)?;
+    let height = u32_at(bytes, 24)?.max(1);
+    let depth = u32_at(bytes, 28)?;
+    let layer_count = u32_at(bytes, 32)?.max(1);
+    let faces = u32_at(bytes, 36)?.max(1);
+    // zero asks the loader to generate mips, which isn't supported
+    let mip_level_count = u32_at(bytes, 40)?.max(1);
+    if u32_at(bytes, 44)? != 0 {
+        return Err("supercompressed KTX2 files are not supported".to_string());
+    }
+
+    let volume = depth > 1;
+    let cube = faces == 6;
+    let layers = layer_count
+        .checked_mul(faces)
+        .ok_or("too many KTX2 layers")?;
+    let mut data = TextureData {
+        format,
+        view_dimension: view_dimension(volume, cube, layer_count),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: if volume { depth } else { layers },
+        },
+        mip_level_count,
+        bytes: vec![],
+    };
+    data.validate()?;
+    // the level index lists mip 0 first, though the data stores it last
+    for level in 0..mip_level_count {
+        let entry = 80 + 24 * level as usize;
+        let offset = u64_at(bytes, entry)?;
+        let len = u64_at(bytes, entry + 8)?;
+        let expected = data.level_len(level).ok_or("KTX2 texture is too large")?;
+        if len != expected as u64 {
+            return Err(format!("KTX2 level {level} has an unexpected size"));
+        }
+        let level_bytes = offset
+            .checked_add(len)
+            .and_then(|end| bytes.get(usize::try_from(offset).ok()?..usize::try_from(end).ok()?))
+            .ok_or("KTX2 data is truncated")?;
+        data.bytes.extend_from_slice(level_bytes);
+    }
+    Ok(data)
+}
+
+fn vk_format(format: u32) -> Result<F, String> {
+    Ok(match format {
+        9 => F::R8Unorm,
+        37 => F::Rgba8Unorm,
+        43 => F::Rgba8UnormSrgb,
+        44 => F::Bgra8Unorm,
+        50 => F::Bgra8UnormSrgb,
+        76 => F::R16Float,
+        97 => F::Rgba16Float,
+        100 => F::R32Float,
+        109 => F::Rgba32Float,
+        133 => F::Bc1RgbaUnorm,
+        134 => F::Bc1RgbaUnormSrgb,
+        135 => F::Bc2RgbaUnorm,
+        136 => F::Bc2RgbaUnormSrgb,
+        137 => F::Bc3RgbaUnorm,
+        138 => F::Bc3RgbaUnormSrgb,
+        139 => F::Bc4RUnorm,
+        141 => F::Bc5RgUnorm,
+        143 => F::Bc6hRgbUfloat,
+        145 => F::Bc7RgbaUnorm,
+        146 => F::Bc7RgbaUnormSrgb,
+        _ => return Err(format!("unsupported Vulkan format {format}")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put(bytes: &mut [u8], offset: usize, value: u32) {
+        bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// A legacy RGBA8 DDS header followed by `payload`.
+    fn dds(width: u32, height: u32, mips: u32, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = b"DDS ".to_vec();
+        bytes.resize(4 + 124, 0);
+        let header = &mut bytes[4..];
+        put(header, 0, 124);
+        put(header, 4, DDSD_MIPMAPCOUNT);
+        put(header, 8, height);
+        put(header, 12, width);
+        put(header, 24, mips);
+        put(header, 76, DDPF_RGB);
+        put(header, 84, 32);
+        put(header, 88, 0xff);
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    /// A DX10 RGBA8 cube map DDS header followed by `payload`.
+    fn dds_cube(size: u32, mips: u32, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = dds(size, size, mips, &[]);
+        bytes[4 + 80..4 + 84].copy_from_slice(b"DX10");
+        put(&mut bytes[4..], 76, DDPF_FOURCC);
+        bytes.resize(4 + 124 + 20, 0);
+        let header = &mut bytes[4..];
+        put(header, 124, 28);
+        put(header, 128, 3);
+        put(header, 132, DX10_TEXTURECUBE);
+        put(header, 136, 1);
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    /// A single-level RGBA8 KTX2 header whose level index says `offset`
+    /// and `len`, followed by `payload`.
+    fn ktx2(width: u32, height: u32, offset: u64, len: u64, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = KTX2_MAGIC.to_vec();
+        bytes.resize(80 + 24, 0);
+        put(&mut bytes, 12, 37);
+        put(&mut bytes, 16, 1);
+        put(&mut bytes, 20, width);
+        put(&mut bytes, 24, height);
+        put(&mut bytes, 40, 1);
+        bytes[80..88].copy_from_slice(&offset.to_le_bytes());
+        bytes[88..96].copy_from_slice(&len.to_le_bytes());
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    #[test]
+    fn dds_mip_chains_read() {
+        let payload: Vec<u8> = (0..20).collect();
+        let data = parse_dds(&dds(2, 2, 2, &payload), false).unwrap();
+        assert_eq!(data.format, F::Rgba8Unorm);
+        assert_eq!(data.view_dimension, View::D2);
+        assert_eq!(data.mip_level_count, 2);
+        assert_eq!(data.bytes, payload);
+        let srgb = parse_dds(&dds(2, 2, 2, &payload), true).unwrap();
+        assert_eq!(srgb.format, F::Rgba8UnormSrgb);
+    }
+
+    #[test]
+    fn dds_cube_faces_regroup_by_level() {
+        // each face's 2x2 then 1x1 level, filled with the face's number
+        let payload: Vec<u8> = (0..6).flat_map(|face| [face; 20]).collect();
+        let data = parse_dds(&dds_cube(2, 2, &payload), false).unwrap();
+        assert_eq!(data.view_dimension, View::Cube);
+        assert_eq!(data.size.depth_or_array_layers, 6);
+        let (level0, level1) = data.bytes.split_at(6 * 16);
+        for face in 0..6 {
+            assert!(level0[face * 16..][..16].iter().all(|&b| b == face as u8));
+            assert!(level1[face * 4..][..4].iter().all(|&b| b == face as u8));
+        }
+    }
+
+    #[test]
+    fn malformed_dds_headers_are_errors() {
+        assert!(parse_dds(b"DDS ", false).is_err());
+        assert!(parse_dds(&dds(2, 2, 1, &[0; 15]), false).is_err());
+        assert!(parse_dds(&dds(0, 2, 1, &[]), false).is_err());
+        // a 2x2 texture has two levels at most
+        assert!(parse_dds(&dds(2, 2, 3, &[0; 24]), false).is_err());
+        assert!(parse_dds(&dds(2, 2, 40, &[0; 24]), false).is_err());
+        // more bytes than u32 holds, but too few in the file
+        assert!(parse_dds(&dds(70000, 70000, 1, &[0; 16]), false).is_err());
+        assert!(parse_dds(&dds(u32::MAX, u32::MAX, 32, &[0; 16]), false).is_err());
+    }
+
+    #[test]
+    fn ktx2_levels_read() {
+        let payload: Vec<u8> = (0..16).collect();
+        let data = parse_ktx2(&ktx2(2, 2, 104, 16, &payload)).unwrap();
+        assert_eq!(data.format, F::Rgba8Unorm);
+        assert_eq!(data.size.depth_or_array_layers, 1);
+        assert_eq!(data.bytes, payload);
+    }
+
+    #[test]
+    fn malformed_ktx2_headers_are_errors() {
+        let payload = [0; 16];
+        assert!(parse_ktx2(&ktx2(2, 2, u64::MAX, 16, &payload)).is_err());
+        assert!(parse_ktx2(&ktx2(2, 2, 104, u64::MAX, &payload)).is_err());
+        assert!(parse_ktx2(&ktx2(2, 2, 104, 12, &payload)).is_err());
+        assert!(parse_ktx2(&ktx2(2, 2, 200, 16, &payload)).is_err());
+        assert!(parse_ktx2(&ktx2(0, 2, 104, 0, &[])).is_err());
+        assert!(parse_ktx2(&KTX2_MAGIC).is_err());
+    }
+
+    #[test]
+    fn sizes_past_the_device_limits_are_errors() {
+        let limits = wgpu::Limits::default();
+        let payload: Vec<u8> = (0..20).collect();
+        let small = parse_dds(&dds(2, 2, 2, &payload), false).unwrap();
+        assert!(small.check_limits(&limits).is_ok());
+        let large = TextureData {
+            size: wgpu::Extent3d {
+                width: 70000,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            ..small
+        };
+        assert!(large.check_limits(&limits).is_err());
+    }
+}
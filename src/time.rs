@@ -0,0 +1,74 @@
+use std::time::Instant;
+
+/// Frame timing, updated once per redraw.
+///
+/// `delta_seconds` and `elapsed_seconds` are game time: scaled by
+/// `time_scale` and zero while paused. Anything that should keep working
+/// while paused, like the camera, uses `real_delta_seconds`.
+pub struct Time {
+    last_frame: Instant,
+    pub delta_seconds: f32,
+    pub elapsed_seconds: f32,
+    pub real_delta_seconds: f32,
+    /// Smoothed real frame time, for display.
+    pub smoothed_dt: f32,
+    pub time_scale: f32,
+    pub paused: bool,
+    step: bool,
+}
+
+impl Default for Time {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Time {
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Time {
+            last_frame: now,
+            delta_seconds: 0.0,
+            elapsed_seconds: 0.0,
+            real_delta_seconds: 0.0,
+            smoothed_dt: 0.0,
+            time_scale: 1.0,
+            paused: false,
+            step: false,
+        }
+    }
+
+    pub fn update(&mut self) {
+        let now = Instant::now();
+        let dt = now.duration_since(self.last_frame).as_secs_f32();
+        self.last_frame = now;
+        self.advance(dt);
+    }
+
+    /// Steps by a fixed `dt` instead of the wall clock, for replays and tests.
+    pub fn advance(&mut self, dt: f32) {
+        self.real_delta_seconds = dt;
+        self.smoothed_dt = 0.01 * dt + 0.99 * self.smoothed_dt;
+
+        let running = !self.paused || std::mem::take(&mut self.step);
+        self.delta_seconds = if running { dt * self.time_scale } else { 0.0 };
+        self.elapsed_seconds += self.delta_seconds;
+    }
+
+    /// Advances game time by one frame while paused.
+    pub fn step(&mut self) {
+        self.step = true;
+    }
+
+    /// Makes the next `update` ignore the time since the last frame, e.g.
+    /// after the window was hidden and nothing was drawn.
+    pub fn skip(&mut self) {
+        self.last_frame = Instant::now();
+    }
+
+    /// Restarts elapsed time from zero, e.g. at the start of a replay.
+    pub fn reset(&mut self) {
+        self.last_frame = Instant::now();
+        self.elapsed_seconds = 0.0;
+    }
+}
@@ -0,0 +1,392 @@
+//! Trails record where models have been and draw a camera-facing ribbon
+//! through the points, tapering and running through a color gradient as
+//! they age out. Points are taken after transforms update each frame and
+//! age with the world clock, so pausing freezes them.
+
+use crate::app::State;
+use crate::mesh_builder::{BuilderVertex, MeshBuilder};
+use crate::model::EntityId;
+use crate::plugin::{Plugin, PluginContext};
+use crate::world::World;
+use std::collections::VecDeque;
+
+const SHADER: &str = r#"
+struct Camera { view_proj: mat4x4<f32> };
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vsMain(@location(0) pos: vec3<f32>, @location(3) color: vec4<f32>) -> VSOut {
+    var out: VSOut;
+    out.pos = camera.view_proj * vec4(pos, 1.0);
+    out.color = color;
+    return out;
+}
+
+@fragment
+fn psMain(in: VSOut) -> @location(0) vec4<f32> {
+    return in.color;
+}
+"#;
+
+/// Colors at positions from 0 (the head) to 1 (the end of the trail),
+/// blended linearly between.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradient {
+    /// Sorted by position.
+    pub stops: Vec<(f32, [f32; 4])>,
+}
+
+impl Gradient {
+    pub fn sample(&self, t: f32) -> [f32; 4] {
+        let Some(first) = self.stops.first() else {
+            return [1.0; 4];
+        };
+        if t <= first.0 {
+            return first.1;
+        }
+        for pair in self.stops.windows(2) {
+            let ((a, from), (b, to)) = (pair[0], pair[1]);
+            if t <= b {
+                let s = if b > a { (t - a) / (b - a) } else { 1.0 };
+                return glam::Vec4::from(from).lerp(to.into(), s).into();
+            }
+        }
+        self.stops.last().unwrap().1
+    }
+}
+
+impl Default for Gradient {
+    fn default() -> Self {
+        Gradient {
+            stops: vec![
+                (0.0, [1.0, 0.9, 0.4, 1.0]),
+                (0.4, [1.0, 0.4, 0.1, 0.7]),
+                (1.0, [0.6, 0.1, 0.4, 0.0]),
+            ],
+        }
+    }
+}
+
+pub struct Trail {
+    pub entity: EntityId,
+    /// Traced point, in the model's space.
+    pub offset: glam::Vec3,
+    /// Seconds a point lasts.
+    pub lifetime: f32,
+    /// Across the head, in world units.
+    pub width: f32,
+    /// Width at the end of the trail, as a fraction of `width`.
+    pub taper: f32,
+    pub gradient: Gradient,
+    /// The model has to move this far before another point is recorded.
+    pub min_distance: f32,
+    /// Oldest first, with the time each was recorded.
+    points: VecDeque<(glam::Vec3, f32)>,
+    /// Where the model is now, joined to the newest point.
+    head: Option<glam::Vec3>,
+}
+
+impl Trail {
+    pub fn new(entity: EntityId) -> Self {
+        Trail {
+            entity,
+            offset: glam::Vec3::ZERO,
+            lifetime: 1.5,
+            width: 8.0,
+            taper: 0.0,
+            gradient: Gradient::default(),
+            min_distance: 1.0,
+            points: VecDeque::new(),
+            head: None,
+        }
+    }
+
+    pub fn point_count(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.points.clear();
+    }
+
+    /// Drops expired points and records where the model is at `now`.
+    pub fn record(&mut self, world: &World, now: f32) {
+        while self
+            .points
+            .front()
+            .is_some_and(|&(_, time)| now - time > self.lifetime)
+        {
+            self.points.pop_front();
+        }
+        self.head = world
+            .model(self.entity)
+            .map(|m| m.global_matrix().transform_point3(self.offset));
+        if let Some(head) = self.head {
+            let moved = self
+                .points
+                .back()
+                .is_none_or(|&(p, _)| p.distance(head) >= self.min_distance);
+            if moved {
+                self.points.push_back((head, now));
+            }
+        }
+    }
+
+    /// Queues the ribbon, facing `eye`.
+    fn build(&self, builder: &mut MeshBuilder, eye: glam::Vec3, now: f32) {
+        let mut points: Vec<(glam::Vec3, f32)> = self
+            .points
+            .iter()
+            .map(|&(p, time)| (p, ((now - time) / self.lifetime).clamp(0.0, 1.0)))
+            .collect();
+        if let Some(head) = self.head {
+            if points.last().is_none_or(|&(p, _)| p != head) {
+                points.push((head, 0.0));
+            }
+        }
+        if points.len() < 2 {
+            return;
+        }
+        let mut previous: Option<[u32; 2]> = None;
+        for (i, &(position, age)) in points.iter().enumerate() {
+            let before = points[i.saturating_sub(1)].0;
+            let after = points[(i + 1).min(points.len() - 1)].0;
+            let side = (after - before).cross(eye - position).normalize_or_zero();
+            let half = self.width * (1.0 + (self.taper - 1.0) * age) * 0.5;
+            let color = self.gradient.sample(age);
+            let edge = [
+                builder.vertex(BuilderVertex::colored(position - side * half, color)),
+                builder.vertex(BuilderVertex::colored(position + side * half, color)),
+            ];
+            if let Some([a, b]) = previous {
+                builder.triangle_indices(a, b, edge[1]);
+                builder.triangle_indices(a, edge[1], edge[0]);
+            }
+            previous = Some(edge);
+        }
+    }
+}
+
+pub struct Trails {
+    pub trails: Vec<Trail>,
+    ribbons: MeshBuilder,
+    pipeline: Option<(wgpu::RenderPipeline, wgpu::BindGroup)>,
+    now: f32,
+}
+
+impl Default for Trails {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Trails {
+    pub fn new() -> Self {
+        Trails {
+            trails: vec![],
+            ribbons: MeshBuilder::new(),
+            pipeline: None,
+            now: 0.0,
+        }
+    }
+
+    /// Starts a trail behind `entity`, returning its index.
+    pub fn add(&mut self, entity: EntityId) -> usize {
+        self.trails.push(Trail::new(entity));
+        self.trails.len() - 1
+    }
+
+    fn create_pipeline(state: &State, world: &World) -> (wgpu::RenderPipeline, wgpu::BindGroup) {
+        let device = &state.device;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Trails"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let camera_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Trails Camera"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Trails Camera"),
+            layout: &camera_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: world.camera.buffer_ref().as_entire_binding(),
+            }],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Trails"),
+            bind_group_layouts: &[&camera_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Trails"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vsMain"),
+                buffers: &[BuilderVertex::layout()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("psMain"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: state.surface_config.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            // ribbons twist to face the camera, so either side can show
+            primitive: wgpu::PrimitiveState::default(),
+            // tested against the scene but not written, so trails don't
+            // hide each other
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        (pipeline, camera_bind_group)
+    }
+}
+
+fn gradient_ui(ui: &mut egui::Ui, gradient: &mut Gradient) {
+    let mut remove = None;
+    for (i, (position, color)) in gradient.stops.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            ui.add(egui::Slider::new(position, 0.0..=1.0));
+            ui.color_edit_button_rgba_unmultiplied(color);
+            if ui.small_button("x").clicked() {
+                remove = Some(i);
+            }
+        });
+    }
+    if let Some(i) = remove {
+        gradient.stops.remove(i);
+    }
+    if ui.small_button("Add stop").clicked() {
+        let color = gradient.sample(0.5);
+        gradient.stops.push((0.5, color));
+    }
+    gradient.stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+}
+
+impl Plugin for Trails {
+    fn name(&self) -> &str {
+        "Trails"
+    }
+
+    fn build(&mut self, ctx: &mut PluginContext) {
+        self.pipeline = Some(Self::create_pipeline(ctx.state, ctx.world));
+    }
+
+    fn update(&mut self, ctx: &mut PluginContext) {
+        self.now = ctx.time.elapsed_seconds;
+    }
+
+    fn prepare(&mut self, state: &State, world: &World, _encoder: &mut wgpu::CommandEncoder) {
+        self.ribbons.clear();
+        for trail in &mut self.trails {
+            trail.record(world, self.now);
+            trail.build(&mut self.ribbons, world.camera.eye, self.now);
+        }
+        // trails of despawned models linger until their points expire
+        self.trails
+            .retain(|t| world.model(t.entity).is_some() || !t.points.is_empty());
+        self.ribbons.flush(&state.device, &state.queue);
+    }
+
+    fn render(&self, _world: &World, renderpass: &mut wgpu::RenderPass) {
+        let Some((pipeline, camera_bind_group)) = &self.pipeline else {
+            return;
+        };
+        if self.ribbons.is_empty() {
+            return;
+        }
+        renderpass.set_pipeline(pipeline);
+        renderpass.set_bind_group(0, camera_bind_group, &[]);
+        self.ribbons.draw_triangles(renderpass);
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, _state: &State, world: &mut World) {
+        egui::Window::new("Trails")
+            .default_open(false)
+            .vscroll(true)
+            .show(ctx, |ui| {
+                let selected = world
+                    .selection
+                    .primary()
+                    .filter(|&id| self.trails.iter().all(|t| t.entity != id));
+                if ui
+                    .add_enabled(
+                        selected.is_some(),
+                        egui::Button::new("Add trail to selected"),
+                    )
+                    .clicked()
+                {
+                    if let Some(id) = selected {
+                        self.add(id);
+                    }
+                }
+
+                let mut remove = None;
+                for (i, trail) in self.trails.iter_mut().enumerate() {
+                    ui.push_id(i, |ui| {
+                        ui.separator();
+                        let name = world.model(trail.entity).map_or("(despawned)", |m| &m.name);
+                        ui.horizontal(|ui| {
+                            ui.label(format!(
+                                "#{} {name}, {} points",
+                                trail.entity,
+                                trail.point_count()
+                            ));
+                            if ui.small_button("Clear").clicked() {
+                                trail.clear();
+                            }
+                            if ui.small_button("Remove").clicked() {
+                                remove = Some(i);
+                            }
+                        });
+                        ui.add(egui::Slider::new(&mut trail.lifetime, 0.1..=10.0).text("Lifetime"));
+                        ui.add(egui::Slider::new(&mut trail.width, 0.1..=100.0).text("Width"));
+                        ui.add(egui::Slider::new(&mut trail.taper, 0.0..=1.0).text("Taper"));
+                        ui.add(
+                            egui::Slider::new(&mut trail.min_distance, 0.0..=20.0)
+                                .text("Point spacing"),
+                        );
+                        ui.horizontal(|ui| {
+                            ui.label("Offset");
+                            ui.add(egui::DragValue::new(&mut trail.offset.x).speed(0.1));
+                            ui.add(egui::DragValue::new(&mut trail.offset.y).speed(0.1));
+                            ui.add(egui::DragValue::new(&mut trail.offset.z).speed(0.1));
+                        });
+                        ui.collapsing("Gradient", |ui| gradient_ui(ui, &mut trail.gradient));
+                    });
+                }
+                if let Some(i) = remove {
+                    self.trails.remove(i);
+                }
+            });
+    }
+}
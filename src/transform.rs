@@ -0,0 +1,146 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub translation: glam::Vec3,
+    pub rotation: glam::Quat,
+    pub scale: glam::Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform {
+            translation: glam::Vec3::ZERO,
+            rotation: glam::Quat::IDENTITY,
+            scale: glam::Vec3::ONE,
+        }
+    }
+}
+
+impl Transform {
+    pub fn from_translation(translation: glam::Vec3) -> Self {
+        Transform {
+            translation,
+            ..Default::default()
+        }
+    }
+
+    pub fn matrix(&self) -> glam::Mat4 {
+        glam::Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+/// World matrices for a set of transforms with parents, recomputed only
+/// for subtrees whose local transform changed since the last
+/// `propagate`. Nodes are indices into the caller's storage.
+#[derive(Default)]
+pub struct TransformHierarchy {
+    /// Each node's parent, as given to `set_parents`.
+    parents: Vec<Option<usize>>,
+    /// Nodes with their parents before them.
+    order: Vec<usize>,
+    /// The local transform each node's matrix was computed from; `None`
+    /// forces a recompute.
+    computed_from: Vec<Option<Transform>>,
+    globals: Vec<glam::Mat4>,
+    changed: Vec<bool>,
+}
+
+impl TransformHierarchy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.parents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parents.is_empty()
+    }
+
+    pub fn parents(&self) -> &[Option<usize>] {
+        &self.parents
+    }
+
+    /// Replaces the hierarchy; every node is recomputed on the next
+    /// `propagate`. A parent cycle is broken by treating the node where
+    /// it was found as a root.
+    pub fn set_parents(&mut self, parents: Vec<Option<usize>>) {
+        let count = parents.len();
+        let mut children = vec![vec![]; count];
+        let mut roots = vec![];
+        for (i, parent) in parents.iter().enumerate() {
+            match parent.filter(|&p| p < count && p != i) {
+                Some(p) => children[p].push(i),
+                None => roots.push(i),
+            }
+        }
+        let mut order = Vec::with_capacity(count);
+        let mut visited = vec![false; count];
+        for root in roots {
+            visit(root, &children, &mut visited, &mut order);
+        }
+        // whatever is left only hangs off a cycle
+        let mut cut = parents;
+        for i in 0..count {
+            if !visited[i] {
+                cut[i] = None;
+                visit(i, &children, &mut visited, &mut order);
+            }
+        }
+        self.parents = cut;
+        self.order = order;
+        self.computed_from = vec![None; count];
+        self.globals = vec![glam::Mat4::IDENTITY; count];
+        self.changed = vec![true; count];
+    }
+
+    /// Recomputes the world matrix of every node whose `local` transform
+    /// changed, and of everything under it. Returns how many were
+    /// recomputed.
+    pub fn propagate(&mut self, local: impl Fn(usize) -> Transform) -> usize {
+        let mut recomputed = 0;
+        for &i in &self.order {
+            let transform = local(i);
+            let parent = self.parents[i];
+            let dirty =
+                self.computed_from[i] != Some(transform) || parent.is_some_and(|p| self.changed[p]);
+            self.changed[i] = dirty;
+            if dirty {
+                let parent = parent.map_or(glam::Mat4::IDENTITY, |p| self.globals[p]);
+                self.globals[i] = parent * transform.matrix();
+                self.computed_from[i] = Some(transform);
+                recomputed += 1;
+            }
+        }
+        recomputed
+    }
+
+    /// Recomputes every node, changed or not. Kept for comparison with
+    /// `propagate`.
+    pub fn propagate_all(&mut self, local: impl Fn(usize) -> Transform) -> usize {
+        self.computed_from.fill(None);
+        self.propagate(local)
+    }
+
+    /// `node`'s world matrix as of the last `propagate`.
+    pub fn global(&self, node: usize) -> glam::Mat4 {
+        self.globals[node]
+    }
+
+    /// Whether the last `propagate` recomputed `node`.
+    pub fn changed(&self, node: usize) -> bool {
+        self.changed[node]
+    }
+}
+
+/// Appends `start` and its unvisited descendants to `order`, depth first.
+fn visit(start: usize, children: &[Vec<usize>], visited: &mut [bool], order: &mut Vec<usize>) {
+    let mut stack = vec![start];
+    while let Some(i) = stack.pop() {
+        if std::mem::replace(&mut visited[i], true) {
+            continue;
+        }
+        order.push(i);
+        stack.extend(children[i].iter().rev());
+    }
+}
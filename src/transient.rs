@@ -0,0 +1,152 @@
+//! Pool for render targets that only live for part of a frame. A texture
+//! goes back to the pool as soon as its last handle is dropped, so a later
+//! pass in the same frame asking for the same size and format gets the same
+//! texture instead of a new allocation.
+
+use std::sync::{Arc, Mutex};
+
+/// Textures are only shared between requests with identical descriptions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TransientDesc {
+    pub width: u32,
+    pub height: u32,
+    pub format: wgpu::TextureFormat,
+    pub usage: wgpu::TextureUsages,
+}
+
+impl TransientDesc {
+    /// Approximate VRAM size, ignoring driver padding.
+    pub fn bytes(&self) -> u64 {
+        let texel = self.format.block_copy_size(None).unwrap_or(4) as u64;
+        self.width as u64 * self.height as u64 * texel
+    }
+}
+
+pub struct TransientTexture {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub desc: TransientDesc,
+}
+
+/// What the last finished frame asked of the pool.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TransientStats {
+    pub requests: u32,
+    /// Distinct textures handed out.
+    pub textures: u32,
+    /// Total size had every request got its own texture.
+    pub requested_bytes: u64,
+    pub allocated_bytes: u64,
+}
+
+impl TransientStats {
+    pub fn saved_bytes(&self) -> u64 {
+        self.requested_bytes - self.allocated_bytes
+    }
+}
+
+/// Frames a texture may go unused before it is freed, e.g. after a resize.
+const MAX_IDLE_FRAMES: u64 = 3;
+
+struct Entry {
+    texture: Arc<TransientTexture>,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: Vec<Entry>,
+    frame: u64,
+    requests: u32,
+    requested_bytes: u64,
+    stats: TransientStats,
+}
+
+/// Lives on `State` so passes can borrow targets from `encode` with only
+/// shared access to it.
+#[derive(Default)]
+pub struct TransientPool {
+    inner: Mutex<Inner>,
+}
+
+impl TransientPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hold the handle for as long as the pass records into or reads the
+    /// texture, and drop it afterwards so later passes can reuse it.
+    pub fn acquire(&self, device: &wgpu::Device, desc: TransientDesc) -> Arc<TransientTexture> {
+        let mut inner = self.inner.lock().unwrap();
+        let frame = inner.frame;
+        inner.requests += 1;
+        inner.requested_bytes += desc.bytes();
+
+        // the pool's own reference is the only one left once a pass is done
+        if let Some(entry) = inner
+            .entries
+            .iter_mut()
+            .find(|e| e.texture.desc == desc && Arc::strong_count(&e.texture) == 1)
+        {
+            entry.last_used = frame;
+            return entry.texture.clone();
+        }
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Transient Texture"),
+            size: wgpu::Extent3d {
+                width: desc.width,
+                height: desc.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: desc.format,
+            usage: desc.usage,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let texture = Arc::new(TransientTexture {
+            texture,
+            view,
+            desc,
+        });
+        inner.entries.push(Entry {
+            texture: texture.clone(),
+            last_used: frame,
+        });
+        texture
+    }
+
+    /// Call once per frame after submitting. Updates `stats` and frees
+    /// textures nobody has asked for in a while.
+    pub fn end_frame(&self) {
+        let mut guard = self.inner.lock().unwrap();
+        let inner = &mut *guard;
+        let frame = inner.frame;
+        let used = inner.entries.iter().filter(|e| e.last_used == frame);
+        inner.stats = TransientStats {
+            requests: inner.requests,
+            textures: used.clone().count() as u32,
+            requested_bytes: inner.requested_bytes,
+            allocated_bytes: used.map(|e| e.texture.desc.bytes()).sum(),
+        };
+        inner
+            .entries
+            .retain(|e| frame - e.last_used < MAX_IDLE_FRAMES);
+        inner.frame += 1;
+        inner.requests = 0;
+        inner.requested_bytes = 0;
+    }
+
+    pub fn stats(&self) -> TransientStats {
+        self.inner.lock().unwrap().stats
+    }
+
+    /// Bytes held by the pool, including textures idle this frame.
+    pub fn resident_bytes(&self) -> u64 {
+        let inner = self.inner.lock().unwrap();
+        inner.entries.iter().map(|e| e.texture.desc.bytes()).sum()
+    }
+}
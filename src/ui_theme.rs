@@ -0,0 +1,106 @@
+//! The UI's look: dark, light or following the OS, an optional accent
+//! color, and a scale on top of the OS scale factor. Saved in the config
+//! and applied to the egui context at startup.
+
+use crate::i18n::tr;
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+
+pub const SCALE_RANGE: RangeInclusive<f32> = 0.5..=3.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ThemeMode {
+    #[default]
+    System,
+    Dark,
+    Light,
+}
+
+impl ThemeMode {
+    pub const ALL: [ThemeMode; 3] = [ThemeMode::System, ThemeMode::Dark, ThemeMode::Light];
+
+    pub fn label(self) -> String {
+        tr(match self {
+            ThemeMode::System => "ui_theme.system",
+            ThemeMode::Dark => "ui_theme.dark",
+            ThemeMode::Light => "ui_theme.light",
+        })
+    }
+
+    fn preference(self) -> egui::ThemePreference {
+        match self {
+            ThemeMode::System => egui::ThemePreference::System,
+            ThemeMode::Dark => egui::ThemePreference::Dark,
+            ThemeMode::Light => egui::ThemePreference::Light,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiTheme {
+    pub mode: ThemeMode,
+    /// Selection and link color; egui's own when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accent: Option<[u8; 3]>,
+    /// Multiplies the OS scale factor; see `SCALE_RANGE`.
+    pub scale: f32,
+}
+
+impl Default for UiTheme {
+    fn default() -> Self {
+        UiTheme {
+            mode: ThemeMode::System,
+            accent: None,
+            scale: 1.0,
+        }
+    }
+}
+
+impl UiTheme {
+    /// Sets the context's theme and colors. The scale is applied through
+    /// `State::ui_scale`, since the renderer sets pixels per point each
+    /// frame.
+    pub fn apply(&self, ctx: &egui::Context) {
+        ctx.set_theme(self.mode.preference());
+        // both, so the accent survives the OS switching under `System`
+        for theme in [egui::Theme::Dark, egui::Theme::Light] {
+            let mut visuals = theme.default_visuals();
+            if let Some([r, g, b]) = self.accent {
+                let accent = egui::Color32::from_rgb(r, g, b);
+                visuals.selection.bg_fill = accent;
+                visuals.hyperlink_color = accent;
+            }
+            ctx.set_visuals_of(theme, visuals);
+        }
+    }
+}
+
+/// Theme, accent and scale controls. Returns true if anything changed;
+/// the caller applies it.
+pub fn ui(ui: &mut egui::Ui, theme: &mut UiTheme) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label(tr("ui_theme.theme"));
+        for mode in ThemeMode::ALL {
+            changed |= ui
+                .selectable_value(&mut theme.mode, mode, mode.label())
+                .changed();
+        }
+    });
+    ui.horizontal(|ui| {
+        let mut custom = theme.accent.is_some();
+        if ui.checkbox(&mut custom, tr("ui_theme.accent")).changed() {
+            theme.accent = custom.then_some([0x5a, 0xaa, 0x5a]);
+            changed = true;
+        }
+        if let Some(accent) = &mut theme.accent {
+            changed |= ui.color_edit_button_srgb(accent).changed();
+        }
+    });
+    // applied on release, so the slider doesn't move under the pointer
+    let response =
+        ui.add(egui::Slider::new(&mut theme.scale, SCALE_RANGE).text(tr("ui_theme.scale")));
+    changed |= response.drag_stopped() || (response.changed() && !response.dragged());
+    changed
+}
@@ -0,0 +1,276 @@
+//! std140 layouts for uniform buffers whose contents are described at
+//! runtime instead of with a hand-padded `#[repr(C)]` struct.
+
+use wgpu::naga;
+
+/// A uniform member type, with its std140 alignment and size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UniformType {
+    F32,
+    I32,
+    U32,
+    Vec2,
+    Vec3,
+    Vec4,
+    Mat4,
+}
+
+impl UniformType {
+    pub fn align(self) -> u32 {
+        match self {
+            UniformType::F32 | UniformType::I32 | UniformType::U32 => 4,
+            UniformType::Vec2 => 8,
+            UniformType::Vec3 | UniformType::Vec4 | UniformType::Mat4 => 16,
+        }
+    }
+
+    pub fn size(self) -> u32 {
+        match self {
+            UniformType::F32 | UniformType::I32 | UniformType::U32 => 4,
+            UniformType::Vec2 => 8,
+            UniformType::Vec3 => 12,
+            UniformType::Vec4 => 16,
+            UniformType::Mat4 => 64,
+        }
+    }
+
+    /// The WGSL spelling, for error messages.
+    pub fn wgsl(self) -> &'static str {
+        match self {
+            UniformType::F32 => "f32",
+            UniformType::I32 => "i32",
+            UniformType::U32 => "u32",
+            UniformType::Vec2 => "vec2<f32>",
+            UniformType::Vec3 => "vec3<f32>",
+            UniformType::Vec4 => "vec4<f32>",
+            UniformType::Mat4 => "mat4x4<f32>",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UniformField {
+    pub name: String,
+    pub ty: UniformType,
+    /// Element count for arrays.
+    pub count: Option<u32>,
+    pub offset: u32,
+}
+
+impl UniformField {
+    /// Distance between array elements; std140 rounds it up to 16.
+    pub fn stride(&self) -> u32 {
+        match self.count {
+            Some(_) => round_up(self.ty.size(), 16),
+            None => self.ty.size(),
+        }
+    }
+}
+
+/// Member offsets and padding for a uniform struct, following std140:
+/// vec3 and larger align to 16, array elements are 16 apart, and the struct
+/// size is a multiple of 16.
+///
+/// ```
+/// use rust_graphics_sandbox::uniform::{UniformLayout, UniformType};
+///
+/// const SHADER: &str = "struct Light { direction: vec3<f32>, intensity: f32 };";
+/// let layout = UniformLayout::new()
+///     .field("direction", UniformType::Vec3)
+///     .field("intensity", UniformType::F32);
+/// layout.assert_wgsl(SHADER, "Light");
+/// assert_eq!(layout.offset("intensity"), Some(12));
+/// let mut data = layout.zeroed();
+/// layout.write(&mut data, "intensity", 2.0f32);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UniformLayout {
+    fields: Vec<UniformField>,
+    end: u32,
+}
+
+impl UniformLayout {
+    pub fn new() -> Self {
+        UniformLayout::default()
+    }
+
+    pub fn field(self, name: &str, ty: UniformType) -> Self {
+        self.push(name, ty, None)
+    }
+
+    pub fn array(self, name: &str, ty: UniformType, count: u32) -> Self {
+        self.push(name, ty, Some(count))
+    }
+
+    fn push(mut self, name: &str, ty: UniformType, count: Option<u32>) -> Self {
+        let align = if count.is_some() { 16 } else { ty.align() };
+        let field = UniformField {
+            name: name.to_string(),
+            ty,
+            count,
+            offset: round_up(self.end, align),
+        };
+        self.end = field.offset + field.stride() * count.unwrap_or(1);
+        // std140 pads after arrays to the next 16 bytes
+        if count.is_some() {
+            self.end = round_up(self.end, 16);
+        }
+        self.fields.push(field);
+        self
+    }
+
+    pub fn fields(&self) -> &[UniformField] {
+        &self.fields
+    }
+
+    pub fn field_named(&self, name: &str) -> Option<&UniformField> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+
+    pub fn offset(&self, name: &str) -> Option<u32> {
+        self.field_named(name).map(|f| f.offset)
+    }
+
+    /// Buffer size in bytes, including the trailing padding.
+    pub fn size(&self) -> u64 {
+        round_up(self.end, 16).max(16) as u64
+    }
+
+    /// A zeroed buffer of `size` bytes to `write` into.
+    pub fn zeroed(&self) -> Vec<u8> {
+        vec![0; self.size() as usize]
+    }
+
+    /// Copies `value` to the field's offset. Panics if there is no such
+    /// field or `value` is the wrong size for it.
+    pub fn write<T: bytemuck::Pod>(&self, data: &mut [u8], name: &str, value: T) {
+        self.write_at(data, name, 0, value);
+    }
+
+    /// Like `write`, for element `index` of an array field.
+    pub fn write_at<T: bytemuck::Pod>(&self, data: &mut [u8], name: &str, index: u32, value: T) {
+        let field = self
+            .field_named(name)
+            .unwrap_or_else(|| panic!("uniform has no field {name}"));
+        let bytes = bytemuck::bytes_of(&value);
+        assert_eq!(
+            bytes.len() as u32,
+            field.ty.size(),
+            "{name} is a {}",
+            field.ty.wgsl()
+        );
+        assert!(
+            index < field.count.unwrap_or(1),
+            "{name}[{index}] is out of range"
+        );
+        let start = (field.offset + index * field.stride()) as usize;
+        data[start..start + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// Compares this layout with the struct `name` in a WGSL module: the
+    /// same members in the same order, with the same types, offsets and
+    /// total size.
+    pub fn check_wgsl(&self, source: &str, name: &str) -> Result<(), String> {
+        let module = naga::front::wgsl::parse_str(source).map_err(|e| e.emit_to_string(source))?;
+        let (members, span) = module
+            .types
+            .iter()
+            .find_map(|(_, ty)| match &ty.inner {
+                naga::TypeInner::Struct { members, span } if ty.name.as_deref() == Some(name) => {
+                    Some((members, *span))
+                }
+                _ => None,
+            })
+            .ok_or_else(|| format!("no struct {name} in the shader"))?;
+
+        if members.len() != self.fields.len() {
+            return Err(format!(
+                "{name} has {} members in the shader but {} in the layout",
+                members.len(),
+                self.fields.len()
+            ));
+        }
+        for (member, field) in members.iter().zip(&self.fields) {
+            let member_name = member.name.as_deref().unwrap_or("?");
+            if member_name != field.name {
+                return Err(format!(
+                    "{name}.{member_name} is {} in the layout",
+                    field.name
+                ));
+            }
+            let shader_type = reflect(&module, member.ty);
+            if shader_type != Some((field.ty, field.count)) {
+                return Err(format!(
+                    "{name}.{member_name} has a different type in the shader than {}",
+                    field.ty.wgsl()
+                ));
+            }
+            if member.offset != field.offset {
+                return Err(format!(
+                    "{name}.{member_name} is at offset {} in the shader but {} in the layout",
+                    member.offset, field.offset
+                ));
+            }
+        }
+        // WGSL rounds uniform structs up to 16 bytes as well
+        let shader_size = round_up(span, 16) as u64;
+        if shader_size != self.size() {
+            return Err(format!(
+                "{name} is {shader_size} bytes in the shader but {} in the layout",
+                self.size()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Panics with `check_wgsl`'s error, for catching layout drift as soon
+    /// as a material is built.
+    pub fn assert_wgsl(&self, source: &str, name: &str) {
+        if let Err(e) = self.check_wgsl(source, name) {
+            panic!("uniform layout mismatch: {e}");
+        }
+    }
+}
+
+/// The layout type for a naga type, if it is one the layout supports.
+fn reflect(
+    module: &naga::Module,
+    ty: naga::Handle<naga::Type>,
+) -> Option<(UniformType, Option<u32>)> {
+    use naga::{ScalarKind, TypeInner, VectorSize};
+    let float = |scalar: naga::Scalar| scalar == naga::Scalar::F32;
+    let ty = match module.types[ty].inner {
+        TypeInner::Scalar(scalar) => match (scalar.kind, scalar.width) {
+            (ScalarKind::Float, 4) => UniformType::F32,
+            (ScalarKind::Sint, 4) => UniformType::I32,
+            (ScalarKind::Uint, 4) => UniformType::U32,
+            _ => return None,
+        },
+        TypeInner::Vector { size, scalar } if float(scalar) => match size {
+            VectorSize::Bi => UniformType::Vec2,
+            VectorSize::Tri => UniformType::Vec3,
+            VectorSize::Quad => UniformType::Vec4,
+        },
+        TypeInner::Matrix {
+            columns: VectorSize::Quad,
+            rows: VectorSize::Quad,
+            scalar,
+        } if float(scalar) => UniformType::Mat4,
+        TypeInner::Array {
+            base,
+            size: naga::ArraySize::Constant(count),
+            ..
+        } => {
+            return match reflect(module, base)? {
+                (ty, None) => Some((ty, Some(count.get()))),
+                _ => None,
+            };
+        }
+        _ => return None,
+    };
+    Some((ty, None))
+}
+
+fn round_up(value: u32, align: u32) -> u32 {
+    value.div_ceil(align) * align
+}
@@ -0,0 +1,229 @@
+use crate::app::State;
+use crate::material::{Binding, MaterialInstance};
+use crate::model::EntityId;
+use crate::plugin::{Plugin, PluginContext};
+use crate::shader::Shader;
+use crate::uniform::{UniformLayout, UniformType};
+use crate::world::World;
+use std::collections::HashMap;
+use std::sync::Arc;
+use wgpu::util::DeviceExt;
+
+const SHADER: &str = r#"
+struct Camera { view_proj: mat4x4<f32> };
+struct Checker { tiles: f32 };
+struct Model { model: mat4x4<f32>, base_color: vec4<f32>, alpha_cutoff: f32 };
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(1) @binding(0) var<uniform> checker: Checker;
+@group(2) @binding(0) var<uniform> model: Model;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+fn vertex(pos: vec3<f32>, uv: vec2<f32>) -> VSOut {
+    var out: VSOut;
+    out.pos = camera.view_proj * model.model * vec4(pos, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+@vertex
+fn vsMain(
+    @location(0) pos: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+) -> VSOut {
+    return vertex(pos, uv);
+}
+
+// normals aren't used, so there is nothing to decode
+@vertex
+fn vsMainPacked(
+    @location(0) pos: vec3<f32>,
+    @location(1) normal: vec2<f32>,
+    @location(2) uv: vec2<f32>,
+) -> VSOut {
+    return vertex(pos, uv);
+}
+
+@fragment
+fn psMain(in: VSOut) -> @location(0) vec4<f32> {
+    let cell = floor(in.uv * checker.tiles);
+    let parity = (cell.x + cell.y) - 2.0 * floor((cell.x + cell.y) * 0.5);
+    // tint by UV so flipped or mirrored islands are easy to spot
+    let tint = vec3(fract(in.uv), 0.5);
+    let base = mix(vec3(0.15), vec3(0.9), parity);
+    var color = mix(base, tint, 0.35);
+    // red outside 0..1, where textures wrap
+    if any(in.uv < vec2(0.0)) || any(in.uv > vec2(1.0)) {
+        color = mix(color, vec3(1.0, 0.0, 0.0), 0.5);
+    }
+    return vec4(color, 1.0);
+}
+"#;
+
+const LAYOUT_SIZE: f32 = 320.0;
+/// Triangles beyond this aren't drawn in the layout viewer.
+const MAX_LAYOUT_TRIANGLES: usize = 50_000;
+
+/// Built-in UV checker material and a 2D viewer for the selected mesh's UV
+/// layout, for spotting stretching and seams on imported models.
+pub struct UvDebug {
+    pub tiles: f32,
+    layout: UniformLayout,
+    material: Option<Arc<MaterialInstance>>,
+    buffer: Option<Arc<wgpu::Buffer>>,
+    /// Materials the checker replaced, restored when it is removed.
+    replaced: HashMap<EntityId, Arc<MaterialInstance>>,
+}
+
+impl Default for UvDebug {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UvDebug {
+    pub fn new() -> Self {
+        UvDebug {
+            tiles: 8.0,
+            layout: UniformLayout::new().field("tiles", UniformType::F32),
+            material: None,
+            buffer: None,
+            replaced: HashMap::new(),
+        }
+    }
+
+    fn uniform(&self) -> Vec<u8> {
+        let mut data = self.layout.zeroed();
+        self.layout.write(&mut data, "tiles", self.tiles);
+        data
+    }
+
+    fn toggle_checker(&mut self, state: &State, world: &mut World, id: EntityId) {
+        let Some(model) = world.model_mut(id) else {
+            return;
+        };
+        match self.replaced.remove(&id) {
+            Some(original) => model.set_material(&state.device, original),
+            None => {
+                let Some(checker) = &self.material else {
+                    return;
+                };
+                self.replaced.insert(id, model.material().clone());
+                model.set_material(&state.device, checker.clone());
+            }
+        }
+    }
+}
+
+impl Plugin for UvDebug {
+    fn name(&self) -> &str {
+        "UV Debug"
+    }
+
+    fn build(&mut self, ctx: &mut PluginContext) {
+        self.layout.assert_wgsl(SHADER, "Checker");
+        let buffer = Arc::new(ctx.state.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("UV Checker Uniform"),
+                contents: &self.uniform(),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+        ));
+        let bindings = vec![
+            Binding::uniform(
+                ctx.world.camera.buffer_ref().clone(),
+                wgpu::ShaderStages::VERTEX,
+            ),
+            Binding::uniform(buffer.clone(), wgpu::ShaderStages::FRAGMENT),
+        ];
+        let material = MaterialInstance::new_arc(ctx.state, bindings, &Shader::from_wgsl(SHADER));
+        ctx.world.materials.push(material.clone());
+        self.material = Some(material);
+        self.buffer = Some(buffer);
+    }
+
+    fn update(&mut self, ctx: &mut PluginContext) {
+        // despawned models don't need their material back
+        self.replaced.retain(|id, _| ctx.world.model(*id).is_some());
+        if let Some(buffer) = &self.buffer {
+            ctx.state.queue.write_buffer(buffer, 0, &self.uniform());
+        }
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, state: &State, world: &mut World) {
+        egui::Window::new("UV Layout")
+            .default_open(false)
+            .show(ctx, |ui| {
+                let Some(id) = world
+                    .selection
+                    .primary()
+                    .filter(|id| world.model(*id).is_some())
+                else {
+                    ui.label("Select a model in the inspector");
+                    return;
+                };
+                ui.horizontal(|ui| {
+                    let mut checker = self.replaced.contains_key(&id);
+                    if ui.checkbox(&mut checker, "Checker material").changed() {
+                        self.toggle_checker(state, world, id);
+                    }
+                    ui.add(egui::Slider::new(&mut self.tiles, 1.0..=64.0).text("tiles"));
+                });
+                layout_ui(ui, world.model(id).unwrap().mesh.as_ref());
+            });
+    }
+}
+
+/// Draws every triangle in UV space, with the 0..1 square outlined and
+/// triangles that leave it in red.
+fn layout_ui(ui: &mut egui::Ui, mesh: &crate::mesh::Mesh) {
+    if mesh.uvs.is_empty() {
+        ui.label("This mesh has no UVs");
+        return;
+    }
+    let (response, painter) =
+        ui.allocate_painter(egui::vec2(LAYOUT_SIZE, LAYOUT_SIZE), egui::Sense::hover());
+    let painter = painter.with_clip_rect(response.rect);
+    let rect = response.rect.shrink(8.0);
+    painter.rect_filled(response.rect, 0.0, egui::Color32::from_gray(24));
+    // UV v runs down, like image rows
+    let to_screen = |uv: glam::Vec2| rect.min + egui::vec2(uv.x, uv.y) * rect.size();
+    painter.rect_stroke(
+        rect,
+        0.0,
+        egui::Stroke::new(1.0, egui::Color32::GRAY),
+        egui::StrokeKind::Middle,
+    );
+
+    let inside = egui::Stroke::new(0.5, egui::Color32::from_rgb(120, 200, 255));
+    let outside = egui::Stroke::new(0.5, egui::Color32::from_rgb(255, 80, 80));
+    let mut wrapped = 0;
+    let triangles = mesh.indices.chunks_exact(3);
+    let total = triangles.len();
+    for triangle in triangles.take(MAX_LAYOUT_TRIANGLES) {
+        let uvs = [0, 1, 2].map(|i| mesh.uvs[triangle[i] as usize]);
+        let in_unit = uvs
+            .iter()
+            .all(|uv| uv.cmpge(glam::Vec2::ZERO).all() && uv.cmple(glam::Vec2::ONE).all());
+        if !in_unit {
+            wrapped += 1;
+        }
+        let points = uvs.map(to_screen);
+        let stroke = if in_unit { inside } else { outside };
+        painter.add(egui::Shape::closed_line(points.to_vec(), stroke));
+    }
+
+    ui.label(format!("{total} triangles, {wrapped} outside 0..1"));
+    if total > MAX_LAYOUT_TRIANGLES {
+        ui.label(format!("Showing the first {MAX_LAYOUT_TRIANGLES}"));
+    }
+    if let Some(pos) = response.hover_pos() {
+        let uv = (pos - rect.min) / rect.size();
+        ui.label(format!("uv {:.3}, {:.3}", uv.x, uv.y));
+    }
+}
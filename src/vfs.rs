@@ -0,0 +1,400 @@
+//! Where asset loaders read files from. Directories, zip archives and
+//! files compiled into the binary can be mounted under a path prefix;
+//! paths no mount has fall through to the real filesystem, so nothing
+//! needs mounting to run from a checkout. A single-file demo build mounts
+//! its assets with `include_bytes!`:
+//!
+//! ```ignore
+//! vfs::mount_embedded("assets", &[("fox.glb", include_bytes!("../assets/fox.glb"))]);
+//! ```
+
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+pub type EmbeddedFiles = &'static [(&'static str, &'static [u8])];
+
+enum Source {
+    Directory(PathBuf),
+    /// Entries by normalized name.
+    Zip(Arc<ZipArchive>),
+    Embedded(HashMap<String, &'static [u8]>),
+}
+
+struct Mount {
+    /// Normalized, without a trailing slash; empty mounts at the root.
+    prefix: String,
+    source: Source,
+}
+
+/// Later mounts shadow earlier ones.
+static MOUNTS: RwLock<Vec<Mount>> = RwLock::new(Vec::new());
+
+/// Forward slashes, no `./` or empty components, and `..` resolved
+/// against the components before it. Only leading `..`s are left.
+fn normalize(path: &str) -> String {
+    let mut components: Vec<&str> = vec![];
+    for component in path.split(['/', '\\']) {
+        match component {
+            "" | "." => {}
+            ".." if components.last().is_some_and(|&c| c != "..") => {
+                components.pop();
+            }
+            _ => components.push(component),
+        }
+    }
+    components.join("/")
+}
+
+fn mount(prefix: &str, source: Source) {
+    MOUNTS.write().unwrap().push(Mount {
+        prefix: normalize(prefix),
+        source,
+    });
+}
+
+/// Serves `prefix/...` from `dir/...`.
+pub fn mount_dir(prefix: &str, dir: impl Into<PathBuf>) {
+    mount(prefix, Source::Directory(dir.into()));
+}
+
+/// Serves `prefix/...` from the zip archive at `path`. Entries must be
+/// stored or deflated.
+pub fn mount_zip(prefix: &str, path: impl AsRef<Path>) -> Result<(), String> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let archive = ZipArchive::parse(bytes).map_err(|e| format!("{}: {e}", path.display()))?;
+    log::info!(
+        "Mounted {} ({} files) at /{}",
+        path.display(),
+        archive.entries.len(),
+        normalize(prefix)
+    );
+    mount(prefix, Source::Zip(Arc::new(archive)));
+    Ok(())
+}
+
+pub fn mount_embedded(prefix: &str, files: EmbeddedFiles) {
+    let files = files
+        .iter()
+        .map(|(name, bytes)| (normalize(name), *bytes))
+        .collect();
+    mount(prefix, Source::Embedded(files));
+}
+
+pub fn unmount_all() {
+    MOUNTS.write().unwrap().clear();
+}
+
+/// `path` relative to `prefix`, if it's under it. A normalized path that
+/// still starts with `..` is outside every mount.
+fn strip<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    if path == ".." || path.starts_with("../") {
+        return None;
+    }
+    if prefix.is_empty() {
+        return Some(path);
+    }
+    path.strip_prefix(prefix)?.strip_prefix('/')
+}
+
+/// Opens `path` from the newest mount that has it, or the filesystem.
+pub fn open(path: impl AsRef<Path>) -> std::io::Result<Box<dyn Read + Send>> {
+    let path = path.as_ref();
+    let normalized = normalize(&path.to_string_lossy());
+    for mount in MOUNTS.read().unwrap().iter().rev() {
+        let Some(relative) = strip(&normalized, &mount.prefix) else {
+            continue;
+        };
+        match &mount.source {
+            Source::Directory(dir) => match std::fs::File::open(dir.join(relative)) {
+                Ok(file) => return Ok(Box::new(file)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            },
+            Source::Zip(archive) => {
+                if let Some(bytes) = archive.read(relative)? {
+                    return Ok(Box::new(Cursor::new(bytes)));
+                }
+            }
+            Source::Embedded(files) => {
+                if let Some(bytes) = files.get(relative) {
+                    return Ok(Box::new(Cursor::new(*bytes)));
+                }
+            }
+        }
+    }
+    Ok(Box::new(std::fs::File::open(path)?))
+}
+
+/// The size in bytes of what [`open`] would read.
+pub fn len(path: impl AsRef<Path>) -> std::io::Result<u64> {
+    let path = path.as_ref();
+    let normalized = normalize(&path.to_string_lossy());
+    for mount in MOUNTS.read().unwrap().iter().rev() {
+        let Some(relative) = strip(&normalized, &mount.prefix) else {
+            continue;
+        };
+        match &mount.source {
+            Source::Directory(dir) => match std::fs::metadata(dir.join(relative)) {
+                Ok(metadata) => return Ok(metadata.len()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            },
+            Source::Zip(archive) => {
+                if let Some(entry) = archive.entries.get(relative) {
+                    return Ok(entry.size as u64);
+                }
+            }
+            Source::Embedded(files) => {
+                if let Some(bytes) = files.get(relative) {
+                    return Ok(bytes.len() as u64);
+                }
+            }
+        }
+    }
+    Ok(std::fs::metadata(path)?.len())
+}
+
+pub fn read(path: impl AsRef<Path>) -> std::io::Result<Vec<u8>> {
+    let mut bytes = vec![];
+    open(path)?.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+pub fn read_to_string(path: impl AsRef<Path>) -> std::io::Result<String> {
+    let mut text = String::new();
+    open(path)?.read_to_string(&mut text)?;
+    Ok(text)
+}
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+struct ZipEntry {
+    method: u16,
+    compressed_size: usize,
+    size: usize,
+    /// Of the local file header.
+    offset: usize,
+}
+
+/// A zip file's central directory over its bytes. Entries are
+/// decompressed when read.
+struct ZipArchive {
+    bytes: Vec<u8>,
+    entries: HashMap<String, ZipEntry>,
+}
+
+impl ZipArchive {
+    fn parse(bytes: Vec<u8>) -> Result<Self, String> {
+        let u16_at = |at: usize| -> Result<u16, String> {
+            let b = bytes.get(at..at + 2).ok_or("truncated zip")?;
+            Ok(u16::from_le_bytes([b[0], b[1]]))
+        };
+        let u32_at = |at: usize| -> Result<u32, String> {
+            let b = bytes.get(at..at + 4).ok_or("truncated zip")?;
+            Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        };
+        // the end of central directory record sits before a comment of up
+        // to 64 KiB
+        let search_from = bytes.len().saturating_sub(22 + u16::MAX as usize);
+        let end = (search_from..bytes.len().saturating_sub(21))
+            .rev()
+            .find(|&at| u32_at(at) == Ok(0x0605_4b50))
+            .ok_or("not a zip archive")?;
+        let count = u16_at(end + 10)? as usize;
+        let mut at = u32_at(end + 16)? as usize;
+
+        let mut entries = HashMap::new();
+        for _ in 0..count {
+            if u32_at(at)? != 0x0201_4b50 {
+                return Err("bad central directory".to_string());
+            }
+            let name_length = u16_at(at + 28)? as usize;
+            let extra_length = u16_at(at + 30)? as usize;
+            let comment_length = u16_at(at + 32)? as usize;
+            let name = bytes
+                .get(at + 46..at + 46 + name_length)
+                .ok_or("truncated zip")?;
+            let name = String::from_utf8_lossy(name);
+            if !name.ends_with('/') {
+                entries.insert(
+                    normalize(&name),
+                    ZipEntry {
+                        method: u16_at(at + 10)?,
+                        compressed_size: u32_at(at + 20)? as usize,
+                        size: u32_at(at + 24)? as usize,
+                        offset: u32_at(at + 42)? as usize,
+                    },
+                );
+            }
+            at += 46 + name_length + extra_length + comment_length;
+        }
+        Ok(ZipArchive { bytes, entries })
+    }
+
+    /// `None` if there's no such entry.
+    fn read(&self, name: &str) -> std::io::Result<Option<Vec<u8>>> {
+        let Some(entry) = self.entries.get(name) else {
+            return Ok(None);
+        };
+        let invalid =
+            |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+        let header = self
+            .bytes
+            .get(entry.offset..entry.offset + 30)
+            .ok_or_else(|| invalid(format!("{name}: truncated")))?;
+        // the local header's name and extra field lengths can differ from
+        // the central directory's
+        let name_length = u16::from_le_bytes([header[26], header[27]]) as usize;
+        let extra_length = u16::from_le_bytes([header[28], header[29]]) as usize;
+        let start = entry.offset + 30 + name_length + extra_length;
+        let data = self
+            .bytes
+            .get(start..start + entry.compressed_size)
+            .ok_or_else(|| invalid(format!("{name}: truncated")))?;
+        let bytes = match entry.method {
+            STORED => data.to_vec(),
+            DEFLATED => miniz_oxide::inflate::decompress_to_vec_with_limit(data, entry.size)
+                .map_err(|e| invalid(format!("{name}: {e}")))?,
+            method => return Err(invalid(format!("{name}: compression method {method}"))),
+        };
+        Ok(Some(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_normalize() {
+        assert_eq!(normalize("./models\\fox//Fox.gltf"), "models/fox/Fox.gltf");
+        assert_eq!(normalize("/assets/./a.png/"), "assets/a.png");
+        assert_eq!(normalize(""), "");
+        assert_eq!(
+            normalize("assets/models/../textures/a.png"),
+            "assets/textures/a.png"
+        );
+        assert_eq!(normalize("assets/../../a.png"), "../a.png");
+        assert_eq!(normalize("../../a.png"), "../../a.png");
+    }
+
+    #[test]
+    fn prefixes_strip_whole_components() {
+        assert_eq!(strip("assets/a.png", "assets"), Some("a.png"));
+        assert_eq!(strip("assets2/a.png", "assets"), None);
+        assert_eq!(strip("assets", "assets"), None);
+        assert_eq!(strip("a.png", ""), Some("a.png"));
+        assert_eq!(strip("../a.png", ""), None);
+        assert_eq!(strip("..", ""), None);
+    }
+
+    #[test]
+    fn embedded_files_read_through_their_mount() {
+        // mounts are global, so the prefix is this test's alone
+        mount_embedded("vfs_test", &[("dir/a.txt", b"first"), ("b.txt", b"second")]);
+        assert_eq!(read_to_string("vfs_test/dir/a.txt").unwrap(), "first");
+        assert_eq!(read_to_string(".\\vfs_test\\b.txt").unwrap(), "second");
+        assert_eq!(len("vfs_test/dir//a.txt").unwrap(), 5);
+        assert_eq!(read_to_string("vfs_test/x/../b.txt").unwrap(), "second");
+        // missing files fall through to the filesystem
+        assert!(open("vfs_test/missing.txt").is_err());
+    }
+
+    #[test]
+    fn dotdot_cannot_leave_a_directory_mount() {
+        let root = std::env::temp_dir().join("vfs_dotdot_test");
+        std::fs::create_dir_all(root.join("mounted")).unwrap();
+        std::fs::write(root.join("outside.txt"), "outside").unwrap();
+        std::fs::write(root.join("mounted/inside.txt"), "inside").unwrap();
+        mount_dir("vfs_dotdot_test", root.join("mounted"));
+        assert_eq!(
+            read_to_string("vfs_dotdot_test/sub/../inside.txt").unwrap(),
+            "inside"
+        );
+        // resolves to outside.txt in the working directory, not the mount's parent
+        assert!(open("vfs_dotdot_test/../outside.txt").is_err());
+        assert!(open("vfs_dotdot_test/../../outside.txt").is_err());
+    }
+
+    /// A zip of `(name, method, data)` entries; deflated data is
+    /// compressed here.
+    fn zip(entries: &[(&str, u16, &[u8])]) -> Vec<u8> {
+        let mut bytes = vec![];
+        let mut central = vec![];
+        for &(name, method, data) in entries {
+            let stored = match method {
+                DEFLATED => miniz_oxide::deflate::compress_to_vec(data, 6),
+                _ => data.to_vec(),
+            };
+            let offset = bytes.len() as u32;
+            let sizes = [stored.len() as u32, data.len() as u32];
+            bytes.extend(0x0403_4b50u32.to_le_bytes());
+            bytes.extend([0; 4]);
+            bytes.extend(method.to_le_bytes());
+            bytes.extend([0; 8]);
+            bytes.extend(sizes.iter().flat_map(|s| s.to_le_bytes()));
+            bytes.extend((name.len() as u16).to_le_bytes());
+            bytes.extend([0; 2]);
+            bytes.extend(name.as_bytes());
+            bytes.extend(&stored);
+
+            central.extend(0x0201_4b50u32.to_le_bytes());
+            central.extend([0; 6]);
+            central.extend(method.to_le_bytes());
+            central.extend([0; 8]);
+            central.extend(sizes.iter().flat_map(|s| s.to_le_bytes()));
+            central.extend((name.len() as u16).to_le_bytes());
+            central.extend([0; 12]);
+            central.extend(offset.to_le_bytes());
+            central.extend(name.as_bytes());
+        }
+        let central_offset = bytes.len() as u32;
+        bytes.extend(&central);
+        bytes.extend(0x0605_4b50u32.to_le_bytes());
+        bytes.extend([0; 4]);
+        bytes.extend((entries.len() as u16).to_le_bytes());
+        bytes.extend((entries.len() as u16).to_le_bytes());
+        bytes.extend((central.len() as u32).to_le_bytes());
+        bytes.extend(central_offset.to_le_bytes());
+        bytes.extend([0; 2]);
+        bytes
+    }
+
+    #[test]
+    fn zip_entries_read() {
+        let text = b"deflated, deflated, deflated, deflated";
+        let archive = ZipArchive::parse(zip(&[
+            ("models/", STORED, b""),
+            ("models/a.txt", STORED, b"stored"),
+            ("./b.txt", DEFLATED, text),
+        ]))
+        .unwrap();
+        assert_eq!(archive.entries.len(), 2);
+        assert_eq!(archive.read("models/a.txt").unwrap().unwrap(), b"stored");
+        assert_eq!(archive.read("b.txt").unwrap().unwrap(), text);
+        assert!(archive.read("c.txt").unwrap().is_none());
+    }
+
+    #[test]
+    fn truncated_zips_are_errors() {
+        let bytes = zip(&[
+            ("a.txt", STORED, b"stored"),
+            ("b.txt", DEFLATED, b"deflated"),
+        ]);
+        // no end of central directory record
+        assert!(ZipArchive::parse(bytes[..bytes.len() - 4].to_vec()).is_err());
+        // the central directory cut off
+        let mut cut = bytes[..40].to_vec();
+        cut.extend(&bytes[bytes.len() - 22..]);
+        assert!(ZipArchive::parse(cut).is_err());
+        // entries past the end of the data
+        let mut short = ZipArchive::parse(bytes).unwrap();
+        short.bytes.truncate(20);
+        assert!(short.read("a.txt").is_err());
+        assert!(short.read("b.txt").is_err());
+    }
+}
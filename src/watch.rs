@@ -0,0 +1,196 @@
+//! Named values recorded once per frame and plotted over time, e.g.
+//! `world.watch.record("particles", count as f32)` from a plugin's
+//! `update`. The Watch window shows each series and exports them as CSV.
+
+use crate::detachable::DetachableWindow;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::path::Path;
+
+/// Samples kept per series.
+const HISTORY: usize = 600;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Sample {
+    pub frame: u64,
+    /// Seconds since the first frame.
+    pub time: f32,
+    pub value: f32,
+}
+
+pub struct Series {
+    pub name: String,
+    pub samples: VecDeque<Sample>,
+    pub visible: bool,
+}
+
+pub struct Watch {
+    series: Vec<Series>,
+    frame: u64,
+    time: f32,
+    /// Stops recording so a spike can be looked at.
+    pub paused: bool,
+}
+
+impl Default for Watch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Watch {
+    pub fn new() -> Self {
+        Watch {
+            series: vec![],
+            frame: 0,
+            time: 0.0,
+            paused: false,
+        }
+    }
+
+    /// Starts a frame; values recorded until the next call are stamped
+    /// with it.
+    pub fn begin_frame(&mut self, time: f32) {
+        self.frame += 1;
+        self.time = time;
+    }
+
+    /// Sets `name`'s value for this frame, replacing one already recorded.
+    pub fn record(&mut self, name: &str, value: f32) {
+        if self.paused {
+            return;
+        }
+        let index = match self.series.iter().position(|s| s.name == name) {
+            Some(index) => index,
+            None => {
+                self.series.push(Series {
+                    name: name.to_string(),
+                    samples: VecDeque::new(),
+                    visible: true,
+                });
+                self.series.len() - 1
+            }
+        };
+        let samples = &mut self.series[index].samples;
+        if samples.back().is_some_and(|s| s.frame == self.frame) {
+            samples.pop_back();
+        }
+        if samples.len() == HISTORY {
+            samples.pop_front();
+        }
+        samples.push_back(Sample {
+            frame: self.frame,
+            time: self.time,
+            value,
+        });
+    }
+
+    pub fn series(&self) -> &[Series] {
+        &self.series
+    }
+
+    pub fn clear(&mut self) {
+        self.series.clear();
+    }
+
+    /// One `series,frame,time,value` row per sample.
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("series,frame,time,value\n");
+        for series in &self.series {
+            // quote names so commas in them don't split the column
+            let name = format!("\"{}\"", series.name.replace('"', "\"\""));
+            for s in &series.samples {
+                let _ = writeln!(out, "{name},{},{},{}", s.frame, s.time, s.value);
+            }
+        }
+        out
+    }
+
+    pub fn save_csv(&self, path: &Path) -> Result<(), String> {
+        std::fs::write(path, self.to_csv()).map_err(|e| e.to_string())
+    }
+
+    pub fn ui(&mut self, ctx: &egui::Context) {
+        DetachableWindow::new("Watch")
+            .default_open(false)
+            .resizable(true)
+            .vscroll(true)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.paused, "Pause");
+                    if ui.button("Clear").clicked() {
+                        self.clear();
+                    }
+                    if ui.button("Export CSV").clicked() {
+                        let unix = std::time::SystemTime::now()
+                            .duration_since(std::time::UNIX_EPOCH)
+                            .map_or(0, |d| d.as_secs());
+                        let path = format!("watch-{unix}.csv");
+                        match self.save_csv(Path::new(&path)) {
+                            Ok(()) => log::info!("Wrote watched values to {path}"),
+                            Err(e) => log::error!("Failed to write {path}: {e}"),
+                        }
+                    }
+                });
+                if self.series.is_empty() {
+                    ui.label("Nothing recorded yet");
+                }
+                for series in &mut self.series {
+                    ui.separator();
+                    let current = series.samples.back().map_or(0.0, |s| s.value);
+                    ui.checkbox(
+                        &mut series.visible,
+                        format!("{}: {current:.3}", series.name),
+                    );
+                    if series.visible {
+                        plot_ui(ui, &series.samples);
+                    }
+                }
+            });
+    }
+}
+
+/// A line graph of `samples` scaled to their own range.
+fn plot_ui(ui: &mut egui::Ui, samples: &VecDeque<Sample>) {
+    let size = egui::vec2(ui.available_width().max(200.0), 60.0);
+    let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+    let (min, max) = samples.iter().fold((f32::MAX, f32::MIN), |(lo, hi), s| {
+        (lo.min(s.value), hi.max(s.value))
+    });
+    if samples.len() < 2 {
+        return;
+    }
+    let range = (max - min).max(f32::EPSILON);
+    let points: Vec<egui::Pos2> = samples
+        .iter()
+        .enumerate()
+        .map(|(i, s)| {
+            let x = i as f32 / (HISTORY - 1) as f32;
+            let y = (s.value - min) / range;
+            egui::pos2(
+                rect.left() + x * rect.width(),
+                rect.bottom() - y * rect.height(),
+            )
+        })
+        .collect();
+    let stroke = egui::Stroke::new(1.5, ui.visuals().selection.bg_fill);
+    painter.add(egui::Shape::line(points, stroke));
+    let text = ui.visuals().weak_text_color();
+    let font = egui::FontId::monospace(10.0);
+    painter.text(
+        rect.left_top(),
+        egui::Align2::LEFT_TOP,
+        format!("{max:.3}"),
+        font.clone(),
+        text,
+    );
+    painter.text(
+        rect.left_bottom(),
+        egui::Align2::LEFT_BOTTOM,
+        format!("{min:.3}"),
+        font,
+        text,
+    );
+}
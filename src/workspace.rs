@@ -0,0 +1,119 @@
+use crate::app::State;
+use crate::import::ImportSettings;
+use crate::world::World;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use winit::window::Window;
+
+const WORKSPACE_PATH: &str = "workspace.toml";
+const MAX_RECENT: usize = 10;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CameraPose {
+    pub eye: [f32; 3],
+    pub center: [f32; 3],
+    pub up: [f32; 3],
+}
+
+/// Outer position and inner size, in physical pixels.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub maximized: bool,
+}
+
+/// What was open when the app last closed, restored on the next start.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Workspace {
+    /// Most recent first.
+    pub recent: Vec<String>,
+    /// The file last opened with Open Scene.
+    pub scene: Option<String>,
+    /// Files opened with Open Model since then.
+    pub models: Vec<String>,
+    pub camera: Option<CameraPose>,
+    pub window: Option<WindowGeometry>,
+    /// Import settings by file path.
+    pub imports: HashMap<String, ImportSettings>,
+}
+
+impl Workspace {
+    /// Falls back to an empty workspace when the file is missing or broken.
+    pub fn load() -> Self {
+        let Ok(text) = std::fs::read_to_string(WORKSPACE_PATH) else {
+            return Workspace::default();
+        };
+        toml::from_str(&text).unwrap_or_else(|e| {
+            log::warn!("Failed to parse {WORKSPACE_PATH}, starting fresh: {e}");
+            Workspace::default()
+        })
+    }
+
+    pub fn save(&self) {
+        let text = toml::to_string_pretty(self).expect("Failed to serialize workspace");
+        if let Err(e) = std::fs::write(WORKSPACE_PATH, text) {
+            log::warn!("Failed to write {WORKSPACE_PATH}: {e}");
+        }
+    }
+
+    pub fn opened_scene(&mut self, path: &str) {
+        self.scene = Some(path.to_string());
+        self.models.clear();
+        self.push_recent(path);
+    }
+
+    pub fn opened_model(&mut self, path: &str) {
+        self.models.push(path.to_string());
+        self.push_recent(path);
+    }
+
+    fn push_recent(&mut self, path: &str) {
+        self.recent.retain(|p| p != path);
+        self.recent.insert(0, path.to_string());
+        self.recent.truncate(MAX_RECENT);
+    }
+
+    /// Records the camera, window and import settings as they are now.
+    pub fn capture(&mut self, world: &World, window: &Window) {
+        self.imports = world.import_settings.clone();
+        let camera = &world.camera;
+        self.camera = Some(CameraPose {
+            eye: camera.eye.into(),
+            center: camera.center.into(),
+            up: camera.up.into(),
+        });
+        let size = window.inner_size();
+        let position = window.outer_position().unwrap_or_default();
+        self.window = Some(WindowGeometry {
+            x: position.x,
+            y: position.y,
+            width: size.width,
+            height: size.height,
+            maximized: window.is_maximized(),
+        });
+    }
+
+    /// Reloads the scene and models and moves the camera back. Files that
+    /// no longer load are skipped.
+    pub fn restore(&self, state: &State, world: &mut World) {
+        world.import_settings = self.imports.clone();
+        if let Some(scene) = &self.scene {
+            world.clear();
+            world.load_model(state, scene);
+        }
+        for model in &self.models {
+            world.load_model(state, model);
+        }
+        if let Some(pose) = self.camera {
+            world.camera.eye = pose.eye.into();
+            world.camera.center = pose.center.into();
+            world.camera.up = pose.up.into();
+            world.camera.update_uniform();
+            world.camera_controller.sync_from_camera(&world.camera);
+        }
+    }
+}
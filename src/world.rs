@@ -1,69 +1,547 @@
 use crate::{
     app::State,
+    assets::MeshLoaders,
+    background::Background,
     camera::Camera,
-    material::{Binding, Material},
+    camera_controller::CameraController,
+    commands::{self, CommandRegistry},
+    culling::Culling,
+    debug_draw::DebugDraw,
+    hdr_still::HdrStill,
+    import::ImportSettings,
+    input::{Action, Input},
+    lens_flare::LensFlare,
+    material::{Binding, MaterialInstance},
     // mesh::create_test_mesh,
-    mesh::load_gltf,
-    model::Model,
-    shader::Shader,
+    mesh::{Mesh, OCTAHEDRAL_WGSL},
+    model::{EntityId, Model},
+    quality::Quality,
+    selection::Selection,
+    shader::{Shader, FALLBACK_MODEL_WGSL},
+    sky::Sky,
+    snapping::Snapping,
+    spatial::{Aabb, SpatialIndex},
+    sprites::SpriteLayer,
+    time::Time,
+    transform::{Transform, TransformHierarchy},
+    watch::Watch,
 };
 
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc};
 use std::time::Instant;
 
+/// Scene contents: camera, loaded meshes, materials and spawned models.
+/// What `MeshLoaders::load` returns, from a background thread.
+type PendingLoad = mpsc::Receiver<Option<Vec<Arc<Mesh>>>>;
+
 pub struct World {
     pub camera: Camera,
-    materials: Vec<Arc<Material>>,
-    models: Vec<Model>,
+    pub camera_controller: CameraController,
+    pub materials: Vec<Arc<MaterialInstance>>,
+    pub meshes: Vec<Arc<Mesh>>,
+    pub mesh_loaders: MeshLoaders,
+    /// Per-file import settings, by path. Files without an entry load
+    /// with the defaults.
+    pub import_settings: HashMap<String, ImportSettings>,
+    pub models: Vec<Model>,
+    /// Screen-space sprites drawn after the 3D scene.
+    pub sprites: SpriteLayer,
+    /// World-space lines and labels queued for this frame.
+    pub debug_draw: DebugDraw,
+    /// Which models the scene pass skips, and how many.
+    pub culling: Culling,
+    /// Procedural background and the sun's light.
+    pub sky: Sky,
+    /// Clear color, gradient or sky behind the models.
+    pub background: Background,
+    /// Flare from the sky's sun, drawn over the finished frame.
+    pub lens_flare: LensFlare,
+    pub quality: Quality,
+    /// Values recorded each frame for the Watch window.
+    pub watch: Watch,
+    /// Editor commands shown in the menu bar and command palette.
+    pub commands: CommandRegistry,
+    /// The entities the inspector edits.
+    pub selection: Selection,
+    /// Grid, angle and vertex snapping for transform edits.
+    pub snapping: Snapping,
+    /// Where to save the next presented frame.
+    pub screenshot: Option<PathBuf>,
+    /// Renders screenshots again with only the scene: no UI, debug draw or
+    /// plugin overlays.
+    pub clean_screenshots: bool,
+    /// Where to save the next frame's scene as an HDR still, with HDR
+    /// output on.
+    pub hdr_still: Option<HdrStill>,
+    /// Stops queuing uniforms and running plugins' `prepare` passes, so the
+    /// GPU keeps the last frame's data while frames are still drawn and
+    /// presented with it.
+    pub freeze_frame_data: bool,
+    /// Lets one frame's updates through while frozen.
+    pub step_frame_data: bool,
+    /// Set to have the next frame copied into `captured_frame` before the
+    /// UI is drawn over it.
+    pub capture_frame: bool,
+    pub captured_frame: Option<wgpu::Texture>,
+    /// Set when the compiled shaders couldn't be loaded and the embedded
+    /// WGSL ones are used instead.
+    pub fallback_shaders: bool,
     shaders: Vec<Shader>,
+    hierarchy: TransformHierarchy,
+    /// Each model's id and parent when `hierarchy` was built.
+    hierarchy_keys: Vec<(EntityId, Option<EntityId>)>,
+    spatial: SpatialIndex,
+    /// Files spawned by `load_model` since the last `clear`, with how many
+    /// meshes each had.
+    loaded_files: Vec<(String, usize)>,
+    /// Files being loaded on a background thread.
+    pending_loads: Vec<(String, PendingLoad)>,
     start_time: Instant,
+    next_id: EntityId,
 }
 
 impl World {
     pub fn new(state: &State) -> Self {
         let mut bindings = vec![];
         let mut materials = vec![];
-        let mut models = vec![];
         let mut shaders = vec![];
 
         let camera = Camera::new(state);
+        let camera_controller = CameraController::new(&camera);
 
-        bindings.push(Binding {
-            buffer: camera.buffer_ref().clone(),
-            visibility: wgpu::ShaderStages::VERTEX,
-        });
-        shaders.push(Shader::new(
-            "shaders/model.vert.spv",
-            "shaders/model.frag.spv",
+        bindings.push(Binding::uniform(
+            camera.buffer_ref().clone(),
+            wgpu::ShaderStages::VERTEX,
+        ));
+        let mut fallback_shaders = false;
+        shaders.push(
+            match Shader::load("shaders/model.vert.spv", "shaders/model.frag.spv") {
+                Ok(shader) => shader,
+                Err(e) => {
+                    log::warn!("{e}; using the embedded fallback shader");
+                    fallback_shaders = true;
+                    Shader::from_wgsl(&format!("{FALLBACK_MODEL_WGSL}{OCTAHEDRAL_WGSL}"))
+                }
+            },
+        );
+        materials.push(MaterialInstance::new_arc(
+            state,
+            bindings,
+            shaders.last().unwrap(),
         ));
-        materials.push(Material::new_arc(state, bindings, shaders.last().unwrap()));
+        // the default material is every other material's fallback, so it
+        // has to be usable from the first frame
+        materials[0].pipeline().wait();
 
         // let test_mesh = create_test_mesh(&state);
-        // models.push(Model {
-        //	 mesh: test_mesh,
-        //	 material: materials.last().unwrap().clone(),
-        // });
-
-        let test_mesh = load_gltf(&state.device, "models/Fox.gltf");
-        models.push(Model {
-            mesh: test_mesh.last().unwrap().clone(),
-            material: materials.last().unwrap().clone(),
-        });
+        let mesh_loaders = MeshLoaders::new();
+        let meshes = mesh_loaders
+            .load(&state.device, "models/Fox.gltf", &ImportSettings::default())
+            .expect("Failed to load models/Fox.gltf");
 
         let start_time = Instant::now();
+        let debug_draw = DebugDraw::new(state, camera.buffer_ref());
 
-        World {
+        let mut world = World {
             camera,
+            camera_controller,
             materials,
-            models,
+            meshes,
+            mesh_loaders,
+            import_settings: HashMap::new(),
+            models: vec![],
+            sprites: SpriteLayer::new(state),
+            debug_draw,
+            culling: Culling::new(),
+            sky: Sky::new(state),
+            background: Background::new(state),
+            lens_flare: LensFlare::new(state),
+            quality: Quality::new(),
+            watch: Watch::new(),
+            commands: CommandRegistry::new(),
+            selection: Selection::default(),
+            snapping: Snapping::default(),
+            screenshot: None,
+            clean_screenshots: false,
+            hdr_still: None,
+            freeze_frame_data: false,
+            step_frame_data: false,
+            capture_frame: false,
+            captured_frame: None,
+            fallback_shaders,
             shaders,
+            hierarchy: TransformHierarchy::new(),
+            hierarchy_keys: vec![],
+            spatial: SpatialIndex::default(),
+            loaded_files: vec![],
+            pending_loads: vec![],
             start_time,
+            next_id: 0,
+        };
+
+        commands::register_builtin(&mut world.commands);
+
+        let fox = world.meshes.last().unwrap().clone();
+        let material = world.default_material();
+        world.spawn(state, "Fox", fox, material, Transform::default());
+
+        world
+    }
+
+    pub fn default_material(&self) -> Arc<MaterialInstance> {
+        self.materials[0].clone()
+    }
+
+    /// The id that the next call to `spawn` will hand out.
+    pub fn next_id(&self) -> EntityId {
+        self.next_id
+    }
+
+    pub fn spawn(
+        &mut self,
+        state: &State,
+        name: &str,
+        mesh: Arc<Mesh>,
+        material: Arc<MaterialInstance>,
+        transform: Transform,
+    ) -> EntityId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.models
+            .push(Model::new(state, id, name, mesh, material, transform));
+        id
+    }
+
+    pub fn import_settings(&self, path: &str) -> ImportSettings {
+        self.import_settings.get(path).copied().unwrap_or_default()
+    }
+
+    /// Loads every mesh in `path` with the registered loaders and its import
+    /// settings, and spawns a model for each with the default material.
+    pub fn load_model(&mut self, state: &State, path: &str) -> Vec<EntityId> {
+        let settings = self.import_settings(path);
+        let Some(meshes) = self.mesh_loaders.load(&state.device, path, &settings) else {
+            return vec![];
+        };
+        self.spawn_loaded(state, path, meshes)
+    }
+
+    /// `load_model` on a background thread, so the window stays responsive
+    /// while a large file imports. The models spawn in `finish_loads`.
+    pub fn load_model_in_background(&mut self, state: &State, path: &str) {
+        let (sender, receiver) = mpsc::channel();
+        let (loaders, device) = (self.mesh_loaders.clone(), state.device.clone());
+        let (settings, file) = (self.import_settings(path), path.to_string());
+        std::thread::spawn(move || {
+            let _ = sender.send(loaders.load(&device, &file, &settings));
+        });
+        self.pending_loads.push((path.to_string(), receiver));
+    }
+
+    /// Whether any background loads are still running.
+    pub fn is_loading(&self) -> bool {
+        !self.pending_loads.is_empty()
+    }
+
+    /// Spawns models for background loads that have finished. Returns
+    /// their ids.
+    pub fn finish_loads(&mut self, state: &State) -> Vec<EntityId> {
+        let mut ids = vec![];
+        for (path, receiver) in std::mem::take(&mut self.pending_loads) {
+            match receiver.try_recv() {
+                Ok(Some(meshes)) => ids.extend(self.spawn_loaded(state, &path, meshes)),
+                Ok(None) => {}
+                Err(mpsc::TryRecvError::Empty) => self.pending_loads.push((path, receiver)),
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    log::warn!("Loading {path} stopped without a result")
+                }
+            }
         }
+        ids
     }
 
-    pub fn render(&self, renderpass: &mut wgpu::RenderPass) {
+    fn spawn_loaded(&mut self, state: &State, path: &str, meshes: Vec<Arc<Mesh>>) -> Vec<EntityId> {
+        if meshes.is_empty() {
+            log::warn!("{path} has no meshes");
+        }
+        let material = self.default_material();
+        self.loaded_files.push((path.to_string(), meshes.len()));
+        let mut ids = vec![];
+        for mesh in meshes {
+            self.meshes.push(mesh.clone());
+            let name = mesh.name.clone();
+            ids.push(self.spawn(state, &name, mesh, material.clone(), Transform::default()));
+        }
+        ids
+    }
+
+    /// Loads `path` again with its current import settings and swaps the
+    /// new meshes into every model using the old ones. Returns how many
+    /// models changed.
+    pub fn reimport(&mut self, state: &State, path: &str) -> usize {
+        let settings = self.import_settings(path);
+        let Some(meshes) = self.mesh_loaders.load(&state.device, path, &settings) else {
+            return 0;
+        };
+        let replacement = |mesh: &Arc<Mesh>| {
+            let source = mesh.source.as_ref().filter(|s| s.path == path)?;
+            meshes.get(source.index).cloned()
+        };
+        for mesh in &mut self.meshes {
+            if let Some(new) = replacement(mesh) {
+                *mesh = new;
+            }
+        }
+        let mut changed = 0;
+        for model in &mut self.models {
+            if let Some(new) = replacement(&model.mesh) {
+                model.pose = new.joint_matrices.clone();
+                model.mesh = new;
+                changed += 1;
+            }
+        }
+        // the new meshes' bounds may differ
+        self.hierarchy_keys.clear();
+        log::info!("Reimported {path} into {changed} model(s)");
+        changed
+    }
+
+    /// Children of the despawned model move up to its parent.
+    pub fn despawn(&mut self, id: EntityId) -> bool {
+        let count = self.models.len();
+        let parent = self.model(id).and_then(|m| m.parent);
+        self.models.retain(|m| m.id != id);
+        for model in &mut self.models {
+            if model.parent == Some(id) {
+                model.parent = parent;
+            }
+        }
+        self.selection.remove(id);
+        self.models.len() != count
+    }
+
+    /// Despawns every model and drops unfinished background loads; loaded
+    /// meshes and materials stay available.
+    pub fn clear(&mut self) {
+        self.models.clear();
+        self.loaded_files.clear();
+        self.pending_loads.clear();
+        self.selection.clear();
+    }
+
+    pub fn loaded_files(&self) -> &[(String, usize)] {
+        &self.loaded_files
+    }
+
+    pub fn model(&self, id: EntityId) -> Option<&Model> {
+        self.models.iter().find(|m| m.id == id)
+    }
+
+    pub fn model_mut(&mut self, id: EntityId) -> Option<&mut Model> {
+        self.models.iter_mut().find(|m| m.id == id)
+    }
+
+    /// Models whose parent is `id`.
+    pub fn children(&self, id: EntityId) -> impl Iterator<Item = &Model> {
+        self.models.iter().filter(move |m| m.parent == Some(id))
+    }
+
+    /// Refuses to make a model its own ancestor.
+    pub fn set_parent(&mut self, id: EntityId, parent: Option<EntityId>) -> bool {
+        let mut ancestor = parent;
+        while let Some(a) = ancestor {
+            if a == id {
+                return false;
+            }
+            ancestor = self.model(a).and_then(|m| m.parent);
+        }
+        match self.model_mut(id) {
+            Some(model) => {
+                model.parent = parent;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Recomputes each model's inherited visibility from its own `visible`
+    /// and its ancestors'.
+    pub fn update_visibility(&mut self) {
+        let own: HashMap<EntityId, (bool, Option<EntityId>)> = self
+            .models
+            .iter()
+            .map(|m| (m.id, (m.visible, m.parent)))
+            .collect();
+        for model in &mut self.models {
+            let mut visible = model.visible;
+            let mut parent = model.parent;
+            // the length bound stops a cycle made by editing `parent` directly
+            for _ in 0..own.len() {
+                let Some((parent_visible, grandparent)) = parent.and_then(|p| own.get(&p)) else {
+                    break;
+                };
+                visible &= parent_visible;
+                parent = *grandparent;
+            }
+            model.set_inherited_visible(visible);
+        }
+    }
+
+    /// World-space bounds of the models, as of the last `update_transforms`.
+    pub fn spatial(&self) -> &SpatialIndex {
+        &self.spatial
+    }
+
+    /// Recomputes the world matrices and spatial index entries of models
+    /// whose transform, or an ancestor's, changed since the last call.
+    /// Returns how many were recomputed.
+    pub fn update_transforms(&mut self) -> usize {
+        let keys = self.models.iter().map(|m| (m.id, m.parent));
+        if !keys.eq(self.hierarchy_keys.iter().copied()) {
+            let index: HashMap<EntityId, usize> = self
+                .models
+                .iter()
+                .enumerate()
+                .map(|(i, m)| (m.id, i))
+                .collect();
+            let parents = self
+                .models
+                .iter()
+                .map(|m| m.parent.and_then(|p| index.get(&p).copied()))
+                .collect();
+            self.hierarchy.set_parents(parents);
+            self.spatial.retain(|id| index.contains_key(&id));
+            self.hierarchy_keys = self.models.iter().map(|m| (m.id, m.parent)).collect();
+        }
+        let models = &mut self.models;
+        let recomputed = self.hierarchy.propagate(|i| models[i].transform);
+        if recomputed > 0 {
+            for (i, model) in models.iter_mut().enumerate() {
+                if self.hierarchy.changed(i) {
+                    let matrix = self.hierarchy.global(i);
+                    model.set_global_matrix(matrix);
+                    let (min, max) = model.mesh.bounds();
+                    self.spatial
+                        .update(model.id, Aabb::transformed(min, max, matrix));
+                }
+            }
+        }
+        recomputed
+    }
+
+    pub fn update(&mut self, time: &Time, input: &Input) {
+        if input.just_pressed(Action::ToggleCameraMode) {
+            self.camera_controller.toggle_mode(&self.camera);
+        }
+        let eye = self.camera.eye;
+        self.camera_controller
+            .update(&mut self.camera, input, time.real_delta_seconds);
+        self.camera.update_uniform();
+        if time.real_delta_seconds > 0.0 {
+            let speed = self.camera.eye.distance(eye) / time.real_delta_seconds;
+            self.watch.record("camera speed", speed);
+        }
+        self.sky.advance(time.delta_seconds);
+        self.quality.record_frame(time.real_delta_seconds);
+        if self.sky.cycle.running {
+            self.watch.record("time of day", self.sky.time_of_day);
+        }
+        self.update_visibility();
+        let recomputed = self.update_transforms();
+        self.watch
+            .record("transforms recomputed", recomputed as f32);
+        // as of the last scene pass
+        let culled = self.culling.stats();
+        self.watch.record("cull tested", culled.tested as f32);
+        self.watch
+            .record("frustum culled", culled.frustum_culled as f32);
+        self.watch.record("size culled", culled.size_culled as f32);
+        self.watch
+            .record("occlusion culled", culled.occlusion_culled as f32);
+        self.culling.debug_draw(
+            &mut self.debug_draw,
+            &self.models,
+            &self.spatial,
+            &self.camera,
+        );
+    }
+
+    /// Copies `frame` into `captured_frame` if `capture_frame` is set.
+    pub fn encode_capture(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        frame: &wgpu::Texture,
+    ) {
+        if !std::mem::take(&mut self.capture_frame) {
+            return;
+        }
+        if !frame.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+            log::warn!("This surface can't be copied from; no frame captured");
+            return;
+        }
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Captured Frame"),
+            size: frame.size(),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: frame.format(),
+            usage: wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        encoder.copy_texture_to_texture(
+            frame.as_image_copy(),
+            texture.as_image_copy(),
+            frame.size(),
+        );
+        self.captured_frame = Some(texture);
+    }
+
+    /// Whether this frame should update GPU data; see `freeze_frame_data`.
+    pub fn take_frame_update(&mut self) -> bool {
+        let step = std::mem::take(&mut self.step_frame_data);
+        !self.freeze_frame_data || step
+    }
+
+    pub fn queue_uniforms(&self, queue: &wgpu::Queue) {
+        self.camera.queue_uniform(queue);
+        self.sky.queue_uniform(queue, &self.camera);
+        self.background.queue_uniform(queue);
         for model in &self.models {
-            model.render(renderpass);
+            model.queue_uniform(queue);
         }
     }
+
+    /// Rebuilds the bind groups of every material in use, e.g. after the
+    /// sampler cache's anisotropy cap changed.
+    pub fn refresh_samplers(&self, state: &State) {
+        let models = self.models.iter().map(|m| m.material());
+        for material in self.materials.iter().chain(models) {
+            material.refresh_samplers(state);
+        }
+    }
+
+    /// Draws the visible models in the camera's view, then the background
+    /// behind them. Returns the number of draws.
+    pub fn render(&self, renderpass: &mut wgpu::RenderPass) -> u32 {
+        let fallback = &self.materials[0];
+        let visible = self
+            .culling
+            .visible(&self.models, &self.spatial, &self.camera);
+        let drawn = visible
+            .into_iter()
+            .filter(|m| m.render(renderpass, fallback))
+            .count() as u32;
+        let background = if self.background.shows_sky(&self.sky) {
+            self.sky.render(renderpass)
+        } else {
+            self.background.render(renderpass)
+        };
+        drawn + background as u32
+    }
 }
@@ -0,0 +1,75 @@
+//! Alpha cutouts: MASK meshes discard where their base color texture's
+//! alpha is under the cutoff, not only where the base color factor's is.
+//!
+//! `cargo test --features gpu-tests`.
+#![cfg(feature = "gpu-tests")]
+
+use rust_graphics_sandbox::background::BackgroundMode;
+use rust_graphics_sandbox::headless::Headless;
+use rust_graphics_sandbox::mesh::{Mesh, MeshImage, Vertex};
+use rust_graphics_sandbox::transform::Transform;
+use std::sync::Arc;
+
+const SIZE: u32 = 64;
+
+/// A 2 wide square facing the camera, with u running left to right.
+fn quad(device: &wgpu::Device) -> Mesh {
+    let corners = [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]];
+    let vertices: Vec<Vertex> = corners
+        .iter()
+        .map(|&[x, y]| Vertex {
+            pos: [x, y, 0.0],
+            normal: [0.0, 0.0, 1.0],
+            uv: [(x + 1.0) / 2.0, (1.0 - y) / 2.0],
+            uv1: [0.0; 2],
+        })
+        .collect();
+    Mesh::new(device, "Quad", &vertices, &[0, 1, 2, 0, 2, 3])
+}
+
+/// Pixels on the middle row, either side of the middle.
+fn render(texture: Option<MeshImage>) -> ([u8; 4], [u8; 4]) {
+    let mut headless = Headless::new(SIZE, SIZE);
+    headless.world.clear();
+    headless.world.background.settings.mode = BackgroundMode::Solid;
+    headless.world.background.settings.color = [0.0; 3];
+    headless.world.camera.eye = glam::vec3(0.0, 0.0, 3.0);
+    headless.world.camera.center = glam::Vec3::ZERO;
+    let mut mesh = quad(&headless.state.device);
+    mesh.alpha_cutoff = Some(0.5);
+    mesh.base_color_texture = texture.map(Arc::new);
+    let material = headless.world.default_material();
+    headless.world.spawn(
+        &headless.state,
+        "Quad",
+        Arc::new(mesh),
+        material,
+        Transform::default(),
+    );
+    let capture = headless.render();
+    let pixel = |x: u32| {
+        let i = ((SIZE / 2 * SIZE + x) * 4) as usize;
+        <[u8; 4]>::try_from(&capture.pixels[i..i + 4]).unwrap()
+    };
+    (pixel(SIZE * 3 / 8), pixel(SIZE * 5 / 8))
+}
+
+#[test]
+fn texture_alpha_cuts_out() {
+    // transparent on the left, opaque on the right
+    let texture = MeshImage {
+        width: 2,
+        height: 1,
+        pixels: vec![255, 255, 255, 0, 255, 255, 255, 255],
+    };
+    let (left, right) = render(Some(texture));
+    assert_eq!(left, [0, 0, 0, 255]);
+    assert_ne!(right, [0, 0, 0, 255]);
+}
+
+#[test]
+fn untextured_cutouts_stay_opaque() {
+    let (left, right) = render(None);
+    assert_ne!(left, [0, 0, 0, 255]);
+    assert_eq!(left, right);
+}
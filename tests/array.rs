@@ -0,0 +1,37 @@
+//! Array tools: copies share the source's mesh and material. Where the
+//! copies go is unit tested in `src/array.rs`.
+//!
+//! `cargo test --features gpu-tests`.
+#![cfg(feature = "gpu-tests")]
+
+use rust_graphics_sandbox::array::{self, GridArray};
+use rust_graphics_sandbox::headless::Headless;
+use rust_graphics_sandbox::mesh::create_test_mesh;
+use rust_graphics_sandbox::transform::Transform;
+use std::sync::Arc;
+
+#[test]
+fn copies_share_mesh_and_material() {
+    let mut headless = Headless::new(64, 64);
+    headless.world.clear();
+    let mesh = create_test_mesh(&headless.state.device);
+    let material = headless.world.default_material();
+    let source = headless.world.spawn(
+        &headless.state,
+        "Triangle",
+        mesh,
+        material,
+        Transform::default(),
+    );
+    headless.world.model_mut(source).unwrap().base_color = [1.0, 0.0, 0.0, 1.0];
+    let transforms = GridArray::default().transforms(Transform::default());
+    let copies = array::duplicate(&headless.state, &mut headless.world, source, &transforms);
+    assert_eq!(copies.len(), transforms.len());
+    let source = headless.world.model(source).unwrap();
+    for id in copies {
+        let copy = headless.world.model(id).unwrap();
+        assert!(Arc::ptr_eq(&copy.mesh, &source.mesh));
+        assert!(Arc::ptr_eq(copy.material(), source.material()));
+        assert_eq!(copy.base_color, source.base_color);
+    }
+}
@@ -0,0 +1,137 @@
+//! Box selection through the id readback: a Ctrl+drag over the scene picks
+//! what's visible in the box, and leaves out what's hidden behind it.
+//!
+//! `cargo test --features gpu-tests`.
+#![cfg(feature = "gpu-tests")]
+
+use rust_graphics_sandbox::headless::Headless;
+use rust_graphics_sandbox::input::{Binding, Input, InputBindings, InputEvent};
+use rust_graphics_sandbox::mesh::create_test_mesh;
+use rust_graphics_sandbox::model::EntityId;
+use rust_graphics_sandbox::plugin::{Plugin, PluginContext};
+use rust_graphics_sandbox::selection::SelectionTool;
+use rust_graphics_sandbox::time::Time;
+use rust_graphics_sandbox::transform::Transform;
+use winit::event::MouseButton;
+use winit::keyboard::KeyCode;
+
+const SIZE: u32 = 256;
+/// Frames allowed for the readback to come back.
+const MAX_FRAMES: usize = 16;
+
+struct Harness {
+    headless: Headless,
+    tool: SelectionTool,
+    input: Input,
+    time: Time,
+}
+
+impl Harness {
+    fn new() -> Self {
+        let mut harness = Harness {
+            headless: Headless::new(SIZE, SIZE),
+            tool: SelectionTool::new(),
+            input: Input::new(InputBindings::default()),
+            time: Time::new(),
+        };
+        harness.headless.world.clear();
+        harness.headless.world.camera.eye = glam::vec3(0.0, 0.0, 3.0);
+        harness.headless.world.camera.center = glam::Vec3::ZERO;
+        harness.hook(|tool, ctx| tool.build(ctx));
+        harness
+    }
+
+    fn hook(&mut self, f: impl FnOnce(&mut SelectionTool, &mut PluginContext)) {
+        let mut ctx = PluginContext {
+            state: &self.headless.state,
+            world: &mut self.headless.world,
+            time: &self.time,
+            input: &self.input,
+        };
+        f(&mut self.tool, &mut ctx);
+    }
+
+    fn spawn(&mut self, name: &str, translation: glam::Vec3) -> EntityId {
+        let headless = &mut self.headless;
+        let mesh = create_test_mesh(&headless.state.device);
+        let material = headless.world.default_material();
+        let transform = Transform {
+            translation,
+            ..Default::default()
+        };
+        headless
+            .world
+            .spawn(&headless.state, name, mesh, material, transform)
+    }
+
+    /// Runs the tool's hooks and renders, like one frame of the app.
+    fn frame(&mut self, events: &[InputEvent]) {
+        for &event in events {
+            self.input.apply(event);
+        }
+        self.hook(|tool, ctx| tool.update(ctx));
+        // the camera controller takes this each frame
+        self.headless.world.camera_controller.held = false;
+        let state = &self.headless.state;
+        let mut encoder = state
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.tool.prepare(state, &self.headless.world, &mut encoder);
+        state.queue.submit(Some(encoder.finish()));
+        self.headless.render();
+        let _ = self
+            .headless
+            .state
+            .device
+            .poll(wgpu::PollType::wait_indefinitely());
+        self.input.end_frame();
+    }
+
+    /// Ctrl+drags from `a` to `b`, in logical pixels, and waits for the ids.
+    fn box_select(&mut self, a: glam::Vec2, b: glam::Vec2) -> Vec<EntityId> {
+        let ctrl = Binding::Key(KeyCode::ControlLeft);
+        let left = Binding::Mouse(MouseButton::Left);
+        self.frame(&[
+            InputEvent::CursorMoved(a.into()),
+            InputEvent::Pressed(ctrl),
+            InputEvent::Pressed(left),
+        ]);
+        self.frame(&[InputEvent::CursorMoved(b.into())]);
+        self.frame(&[InputEvent::Released(left), InputEvent::Released(ctrl)]);
+        for _ in 0..MAX_FRAMES {
+            if !self.headless.world.selection.is_empty() {
+                break;
+            }
+            self.frame(&[]);
+        }
+        let mut ids = self.headless.world.selection.ids().to_vec();
+        ids.sort_unstable();
+        ids
+    }
+}
+
+#[test]
+fn occluded_models_are_left_out() {
+    let mut harness = Harness::new();
+    let front = harness.spawn("Front", glam::vec3(0.0, 0.0, 0.5));
+    // smaller on screen, and entirely behind the front triangle
+    harness.spawn("Behind", glam::vec3(0.0, 0.0, -0.5));
+    let center = glam::Vec2::splat(SIZE as f32 * 0.5);
+    let ids = harness.box_select(center - 8.0, center + 8.0);
+    assert_eq!(ids, vec![front]);
+}
+
+#[test]
+fn models_partly_inside_are_picked() {
+    let mut harness = Harness::new();
+    let left = harness.spawn("Left", glam::vec3(-0.3, 0.0, 0.0));
+    let right = harness.spawn("Right", glam::vec3(0.3, 0.0, 0.0));
+    // off to the side, outside the box
+    harness.spawn("Aside", glam::vec3(0.0, 0.9, 0.0));
+    // covers the inner corners of both, but neither's centre
+    let center = glam::Vec2::splat(SIZE as f32 * 0.5);
+    let ids = harness.box_select(center - 12.0, center + 12.0);
+    let mut expected = vec![left, right];
+    expected.sort_unstable();
+    assert_eq!(ids, expected);
+}
@@ -0,0 +1,35 @@
+//! Fixtures shared by the integration tests. Each test file that needs
+//! them has `mod common;`, so not every one uses everything.
+#![allow(dead_code)]
+
+use rust_graphics_sandbox::headless::Headless;
+use rust_graphics_sandbox::mesh::create_test_mesh;
+use rust_graphics_sandbox::transform::Transform;
+
+/// A world with one triangle, (0, 0.5), (-0.5, -0.5) and (0.5, -0.5),
+/// moved by `translation`.
+pub fn triangle_world(translation: glam::Vec3) -> Headless {
+    let mut headless = Headless::new(64, 64);
+    headless.world.clear();
+    let mesh = create_test_mesh(&headless.state.device);
+    let material = headless.world.default_material();
+    let transform = Transform {
+        translation,
+        ..Default::default()
+    };
+    headless
+        .world
+        .spawn(&headless.state, "Triangle", mesh, material, transform);
+    headless.world.update_transforms();
+    headless
+}
+
+/// Writes `source` to a file of its own per test, so they can run in
+/// parallel, and returns its path.
+pub fn scratch(name: &str, source: &str) -> String {
+    let dir = std::env::temp_dir().join(concat!(env!("CARGO_CRATE_NAME"), "_test"));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, source).unwrap();
+    path.to_string_lossy().into_owned()
+}
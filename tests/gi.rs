@@ -0,0 +1,64 @@
+//! Global illumination: the voxelizer fills the voxels triangles pass
+//! through with their color, and the probe update passes validation.
+//!
+//! `cargo test --features gpu-tests`.
+#![cfg(feature = "gpu-tests")]
+
+mod common;
+
+use common::triangle_world;
+use rust_graphics_sandbox::gi::{ProbeGrid, Voxels};
+use rust_graphics_sandbox::headless::Headless;
+
+/// The shared triangle, red.
+fn red_triangle_world() -> Headless {
+    let mut headless = triangle_world(glam::Vec3::ZERO);
+    headless.world.models[0].base_color = [1.0, 0.0, 0.0, 1.0];
+    headless
+}
+
+#[test]
+fn triangles_fill_the_voxels_they_cross() {
+    let headless = red_triangle_world();
+    let voxels = Voxels::from_world(&headless.world, 8);
+    assert_eq!(voxels.size, 0.125);
+    // a voxel of padding either side of the flat triangle
+    assert_eq!(voxels.dims[2], 3);
+    assert_eq!(voxels.at(glam::Vec3::ZERO), Some([255, 0, 0, 255]));
+    assert_eq!(
+        voxels.at(glam::vec3(0.0, -0.45, 0.0)),
+        Some([255, 0, 0, 255])
+    );
+    // beside the tip
+    assert_eq!(voxels.at(glam::vec3(0.4, 0.4, 0.0)), Some([0; 4]));
+    assert_eq!(voxels.at(glam::vec3(0.0, 0.0, 1.0)), None);
+    assert!(voxels.filled() > 8 && voxels.filled() < 64);
+}
+
+#[test]
+fn probe_update_passes_validation() {
+    let headless = red_triangle_world();
+    let state = &headless.state;
+    if !state.caps.compute {
+        return;
+    }
+    state.device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let mut grid = ProbeGrid::new(state, [4, 4, 2]);
+    assert!(!grid.is_active());
+    grid.voxelize(state, &headless.world);
+    assert!(grid.is_active());
+    assert_eq!(grid.probe_positions().len(), 32);
+    let mut encoder = state.device.create_command_encoder(&Default::default());
+    for _ in 0..3 {
+        grid.update(
+            &state.queue,
+            -glam::Vec3::Z,
+            glam::Vec3::ONE,
+            glam::Vec3::splat(0.2),
+        );
+        grid.encode(&mut encoder);
+    }
+    state.queue.submit([encoder.finish()]);
+    let error = pollster::block_on(state.device.pop_error_scope());
+    assert!(error.is_none(), "{error:?}");
+}
@@ -0,0 +1,130 @@
+//! Golden-image tests: render known scenes headlessly and compare them with
+//! reference PNGs in `tests/golden/`.
+//!
+//! `cargo test --features gpu-tests`; after an intentional rendering change,
+//! set `UPDATE_GOLDEN=1` to rewrite the references and commit them.
+#![cfg(feature = "gpu-tests")]
+
+use rust_graphics_sandbox::headless::{Capture, Headless};
+use rust_graphics_sandbox::mesh::create_test_mesh;
+use rust_graphics_sandbox::transform::Transform;
+use std::path::PathBuf;
+
+const WIDTH: u32 = 256;
+const HEIGHT: u32 = 256;
+
+/// Per-pixel YIQ distance (0..1) above which two pixels count as different.
+const PIXEL_THRESHOLD: f32 = 0.1;
+/// Fraction of pixels allowed to differ, to absorb driver rasterization noise.
+const MAX_DIFF_RATIO: f32 = 0.001;
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from("tests/golden").join(format!("{name}.png"))
+}
+
+fn output_path(name: &str, suffix: &str) -> PathBuf {
+    PathBuf::from("target/golden").join(format!("{name}.{suffix}.png"))
+}
+
+/// Perceptual color distance from pixelmatch: YIQ with luma weighted most.
+fn yiq_distance(a: &[u8], b: &[u8]) -> f32 {
+    let channel = |p: &[u8], i: usize| {
+        // composite over white so differences in transparent pixels still count
+        let alpha = p[3] as f32 / 255.0;
+        255.0 + (p[i] as f32 - 255.0) * alpha
+    };
+    let (r1, g1, b1) = (channel(a, 0), channel(a, 1), channel(a, 2));
+    let (r2, g2, b2) = (channel(b, 0), channel(b, 1), channel(b, 2));
+    let (dr, dg, db) = (r1 - r2, g1 - g2, b1 - b2);
+
+    let y = dr * 0.298_895_3 + dg * 0.586_622_5 + db * 0.114_482_2;
+    let i = dr * 0.595_978 - dg * 0.274_176_1 - db * 0.321_801_9;
+    let q = dr * 0.211_470_2 - dg * 0.522_617_1 + db * 0.311_146_9;
+
+    // 35215 is the largest possible value of the weighted sum
+    (0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q) / 35215.0
+}
+
+fn assert_matches_golden(name: &str, actual: &Capture) {
+    let golden = golden_path(name);
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(golden.parent().unwrap()).unwrap();
+        actual.save_png(&golden).unwrap();
+        return;
+    }
+
+    // references are committed, so a missing one is a failure like any other
+    let expected = Capture::load_png(&golden)
+        .unwrap_or_else(|e| panic!("{name}: can't read {}: {e}", golden.display()));
+    assert_eq!(
+        (expected.width, expected.height),
+        (actual.width, actual.height),
+        "{name}: size differs from reference"
+    );
+
+    let mut diff = Capture {
+        width: actual.width,
+        height: actual.height,
+        pixels: vec![0; actual.pixels.len()],
+    };
+    let mut differing = 0;
+    for (i, (a, e)) in actual
+        .pixels
+        .chunks(4)
+        .zip(expected.pixels.chunks(4))
+        .enumerate()
+    {
+        let out = &mut diff.pixels[i * 4..i * 4 + 4];
+        if yiq_distance(a, e) > PIXEL_THRESHOLD * PIXEL_THRESHOLD {
+            differing += 1;
+            out.copy_from_slice(&[255, 0, 0, 255]);
+        } else {
+            // faded copy of the reference for context
+            let luma = (e[0] as u32 + e[1] as u32 + e[2] as u32) / 3 / 4 + 191;
+            out.copy_from_slice(&[luma as u8, luma as u8, luma as u8, 255]);
+        }
+    }
+
+    let ratio = differing as f32 / (actual.width * actual.height) as f32;
+    if ratio > MAX_DIFF_RATIO {
+        std::fs::create_dir_all("target/golden").unwrap();
+        actual.save_png(output_path(name, "actual")).unwrap();
+        diff.save_png(output_path(name, "diff")).unwrap();
+        panic!(
+            "{name}: {differing} pixels ({:.3}%) differ from {}; see target/golden/",
+            ratio * 100.0,
+            golden.display()
+        );
+    }
+}
+
+#[test]
+fn default_scene() {
+    let mut headless = Headless::new(WIDTH, HEIGHT);
+    assert_matches_golden("default_scene", &headless.render());
+}
+
+#[test]
+fn default_scene_side_view() {
+    let mut headless = Headless::new(WIDTH, HEIGHT);
+    headless.world.camera.eye = glam::vec3(150.0, 50.0, 0.0);
+    headless.world.camera.center = glam::vec3(0.0, 30.0, 0.0);
+    assert_matches_golden("default_scene_side_view", &headless.render());
+}
+
+#[test]
+fn triangle() {
+    let mut headless = Headless::new(WIDTH, HEIGHT);
+    headless.world.clear();
+    let mesh = create_test_mesh(&headless.state.device);
+    let material = headless.world.default_material();
+    headless.world.spawn(
+        &headless.state,
+        "Triangle",
+        mesh,
+        material,
+        Transform::default(),
+    );
+    assert_matches_golden("triangle", &headless.render());
+}
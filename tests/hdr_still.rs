@@ -0,0 +1,62 @@
+//! HDR stills: a float target keeps values over 1.0, the brackets expose
+//! them by whole stops, and the `.hdr` round-trips them.
+//!
+//! `cargo test --features gpu-tests`.
+#![cfg(feature = "gpu-tests")]
+
+use rust_graphics_sandbox::background::BackgroundMode;
+use rust_graphics_sandbox::hdr_output;
+use rust_graphics_sandbox::hdr_still::{HdrImage, HdrStill};
+use rust_graphics_sandbox::headless::Headless;
+
+/// An empty scene cleared to `color`, read back as floats.
+fn render_cleared(color: [f32; 3]) -> HdrImage {
+    let mut headless = Headless::with_format(16, 16, hdr_output::FORMAT);
+    headless.world.clear();
+    headless.world.background.settings.mode = BackgroundMode::Solid;
+    headless.world.background.settings.color = color;
+    headless.render_hdr().unwrap()
+}
+
+#[test]
+fn float_targets_keep_highlights() {
+    let image = render_cleared([4.0, 1.0, 0.25]);
+    assert_eq!(image.pixels.len(), 16 * 16);
+    assert!(image.pixels.iter().all(|&p| p == [4.0, 1.0, 0.25]));
+
+    // two stops down brings the red back into range
+    let under = image.exposed(-2.0);
+    assert_eq!(&under.pixels[..4], &[255, 137, 71, 255]);
+    let over = image.exposed(2.0);
+    assert_eq!(&over.pixels[..4], &[255, 255, 255, 255]);
+}
+
+#[test]
+fn sdr_targets_are_refused() {
+    let mut headless = Headless::new(16, 16);
+    assert!(headless.render_hdr().is_err());
+}
+
+#[test]
+fn stills_save_an_hdr_and_a_png_per_bracket() {
+    let image = render_cleared([8.0, 0.5, 0.0]);
+    let dir = std::env::temp_dir().join("hdr_still_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut still = HdrStill::new(dir.join("still"));
+    still.brackets = vec![-3.0, 0.0];
+    let files = still.save(&image).unwrap();
+    let names: Vec<_> = files
+        .iter()
+        .map(|f| f.file_name().unwrap().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(names, ["still.hdr", "still_ev-3.png", "still_ev+0.png"]);
+
+    let loaded = HdrImage::load_hdr(&files[0]).unwrap();
+    assert_eq!((loaded.width, loaded.height), (16, 16));
+    // RGBE shares an exponent, so the smaller channels lose some precision
+    let [r, g, b] = loaded.pixels[0];
+    assert_eq!(r, 8.0);
+    assert!((g - 0.5).abs() < 0.05, "{g}");
+    assert_eq!(b, 0.0);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,89 @@
+//! Kernel playground: dispatches over the resources set up in the window,
+//! keeps their contents between dispatches, and refuses shaders whose
+//! bindings don't match them.
+//!
+//! `cargo test --features gpu-tests`.
+#![cfg(feature = "gpu-tests")]
+
+mod common;
+
+use common::scratch;
+use rust_graphics_sandbox::headless::Headless;
+use rust_graphics_sandbox::kernel_playground::{
+    Element, Fill, KernelPlayground, Output, Resource, ResourceKind,
+};
+
+const DOUBLE: &str = r#"
+@group(0) @binding(0) var<storage, read_write> values: array<u32>;
+
+@compute @workgroup_size(64)
+fn double(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x < arrayLength(&values) {
+        values[id.x] *= 2u;
+    }
+}
+"#;
+
+fn values(playground: &KernelPlayground, index: usize) -> &[f32] {
+    match playground.output(index) {
+        Some(Output::Values(values)) => values,
+        _ => panic!("no values for resource {index}"),
+    }
+}
+
+/// A playground over `DOUBLE` with 100 `u32`s counting up.
+fn doubling(headless: &Headless) -> KernelPlayground {
+    let mut playground = KernelPlayground::new();
+    playground.path = scratch("double.wgsl", DOUBLE);
+    let mut counting = Resource::buffer("values", Element::U32, 100);
+    if let ResourceKind::Buffer { fill, .. } = &mut counting.kind {
+        *fill = Fill::Index;
+    }
+    playground.resources = vec![counting];
+    playground.workgroups = [2, 1, 1];
+    playground.reload(&headless.state.device);
+    playground
+}
+
+#[test]
+fn starter_fills_both_resources() {
+    let headless = Headless::new(64, 64);
+    let mut playground = KernelPlayground::new();
+    playground.poll(&headless.state.device);
+    playground.dispatch(&headless.state);
+    assert_eq!(playground.error(), None);
+
+    let values = values(&playground, 0);
+    assert_eq!(values.len(), 256);
+    assert!((values[10] - 1.0_f32.sin()).abs() < 1e-5, "{}", values[10]);
+    let Some(Output::Pixels(image)) = playground.output(1) else {
+        panic!("no image");
+    };
+    assert_eq!((image.width, image.height), (256, 256));
+    // uv (0, 0), blue 0.5
+    assert_eq!(&image.pixels[..4], &[0, 0, 128, 255]);
+}
+
+#[test]
+fn dispatches_build_on_each_other_until_reset() {
+    let headless = Headless::new(64, 64);
+    let mut playground = doubling(&headless);
+    playground.dispatch(&headless.state);
+    playground.dispatch(&headless.state);
+    assert_eq!(playground.error(), None);
+    assert_eq!(values(&playground, 0)[99], 99.0 * 4.0);
+
+    playground.reset(&headless.state.device);
+    playground.dispatch(&headless.state);
+    assert_eq!(values(&playground, 0)[99], 99.0 * 2.0);
+}
+
+#[test]
+fn bindings_must_match_the_resources() {
+    let headless = Headless::new(64, 64);
+    let mut playground = doubling(&headless);
+    playground.resources = vec![Resource::texture("image", 16, 16)];
+    playground.dispatch(&headless.state);
+    assert!(playground.error().is_some());
+    assert!(playground.output(0).is_none());
+}
@@ -0,0 +1,152 @@
+//! Lightmapper: generated lightmap UVs keep triangles apart, and a bake of
+//! an open floor under an overhead sun comes out evenly lit.
+//!
+//! `cargo test --features gpu-tests`.
+#![cfg(feature = "gpu-tests")]
+
+use rust_graphics_sandbox::headless::{self, Headless};
+use rust_graphics_sandbox::lightmapper::{self, Lightmapper};
+use rust_graphics_sandbox::mesh::{Mesh, Vertex};
+use rust_graphics_sandbox::transform::Transform;
+use std::sync::Arc;
+
+/// A 10 wide square at y = 0 facing up, without TEXCOORD_1.
+fn floor(device: &wgpu::Device) -> Mesh {
+    let corners = [[-5.0, 5.0], [5.0, 5.0], [5.0, -5.0], [-5.0, -5.0]];
+    let vertices: Vec<Vertex> = corners
+        .iter()
+        .map(|&[x, z]| Vertex {
+            pos: [x, 0.0, z],
+            normal: [0.0, 1.0, 0.0],
+            uv: [0.0; 2],
+            uv1: [0.0; 2],
+        })
+        .collect();
+    Mesh::new(device, "Floor", &vertices, &[0, 1, 2, 0, 2, 3])
+}
+
+fn f16_to_f32(bits: u16) -> f32 {
+    let exponent = (bits >> 10) & 0x1f;
+    let mantissa = (bits & 0x3ff) as f32 / 1024.0;
+    let magnitude = match exponent {
+        0 => mantissa * 2f32.powi(-14),
+        _ => (1.0 + mantissa) * 2f32.powi(exponent as i32 - 15),
+    };
+    if bits & 0x8000 != 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+/// Every texel of an `Rgba16Float` texture.
+fn read_rgba16f(headless: &Headless, texture: &wgpu::Texture) -> Vec<[f32; 4]> {
+    let (device, queue) = (&headless.state.device, &headless.state.queue);
+    let (width, height) = (texture.width(), texture.height());
+    let row = (width * 8).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+        * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: (row * height) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&Default::default());
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::TexelCopyBufferInfo {
+            buffer: &buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(row),
+                rows_per_image: None,
+            },
+        },
+        texture.size(),
+    );
+    queue.submit([encoder.finish()]);
+    let bytes = headless::read_buffer(device, queue, &buffer);
+    (0..height as usize)
+        .flat_map(|y| {
+            let row = &bytes[y * row as usize..][..width as usize * 8];
+            row.chunks_exact(8)
+                .map(|texel| {
+                    let bits: [u16; 4] = bytemuck::pod_read_unaligned(texel);
+                    bits.map(f16_to_f32)
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+#[test]
+fn atlas_triangles_stay_apart() {
+    let size = 64;
+    let uvs = lightmapper::atlas_uvs(7, size);
+    assert_eq!(uvs.len(), 21);
+    assert!(uvs.iter().flatten().all(|&c| (0.0..=1.0).contains(&c)));
+    // 4 pairs, in a 2 by 2 atlas; both halves of the first cell
+    let texel = 1.0 / size as f32;
+    let lower = uvs[..3].iter().map(|[u, v]| u + v).fold(f32::MIN, f32::max);
+    let upper = uvs[3..6]
+        .iter()
+        .map(|[u, v]| u + v)
+        .fold(f32::MAX, f32::min);
+    assert!(upper - lower >= 2.0 * texel, "{lower} {upper}");
+    // the next pair is a cell over
+    assert!(uvs[6..12].iter().all(|&[u, v]| u > 0.5 && v < 0.5));
+}
+
+#[test]
+fn unwrapped_meshes_get_a_vertex_per_corner() {
+    let headless = Headless::new(64, 64);
+    let mesh = floor(&headless.state.device);
+    let unwrapped = lightmapper::unwrap(&headless.state.device, &mesh, 32);
+    assert_eq!(unwrapped.positions.len(), 6);
+    assert_eq!(unwrapped.indices, vec![0, 1, 2, 3, 4, 5]);
+    assert_eq!(unwrapped.positions[4], mesh.positions[2]);
+    assert!(unwrapped.attributes.iter().any(|a| a == "TEXCOORD_1"));
+}
+
+#[test]
+fn open_floor_bakes_to_the_sun() {
+    let mut headless = Headless::new(64, 64);
+    headless.world.clear();
+    let mesh = Arc::new(floor(&headless.state.device));
+    let material = headless.world.default_material();
+    let id = headless.world.spawn(
+        &headless.state,
+        "Floor",
+        mesh.clone(),
+        material,
+        Transform::default(),
+    );
+    headless.world.update_transforms();
+
+    let state = &headless.state;
+    let mut lightmapper = Lightmapper::new(state);
+    lightmapper.settings.target_samples = 16;
+    lightmapper.start(state, &mut headless.world);
+    // replaced by an unwrapped copy
+    assert!(!Arc::ptr_eq(&headless.world.model(id).unwrap().mesh, &mesh));
+    let mut encoder = state.device.create_command_encoder(&Default::default());
+    while lightmapper.is_baking() {
+        lightmapper.update(
+            &state.queue,
+            -glam::Vec3::Y,
+            glam::Vec3::ONE,
+            glam::Vec3::ZERO,
+        );
+        lightmapper.encode(state, &headless.world, &mut encoder);
+    }
+    state.queue.submit([encoder.finish()]);
+    assert_eq!(lightmapper.samples(), 16);
+
+    let lightmap = lightmapper.lightmap(id).unwrap();
+    let texels = read_rgba16f(&headless, &lightmap.texture);
+    let covered: Vec<_> = texels.iter().filter(|t| t[3] > 0.0).collect();
+    assert!(covered.len() > texels.len() / 4, "{}", covered.len());
+    for texel in covered {
+        assert!((texel[0] - 1.0).abs() < 0.01, "{texel:?}");
+    }
+}
@@ -0,0 +1,77 @@
+//! Resize storms: the window is resized, minimized to zero and restored
+//! between frames, and everything sized from the surface has to follow
+//! without a zero-sized configure reaching wgpu.
+//!
+//! `cargo test --features gpu-tests`.
+#![cfg(feature = "gpu-tests")]
+
+use rust_graphics_sandbox::headless::Headless;
+
+/// Sizes in the order the window goes through them; zeros are minimizes.
+const STORM: &[(u32, u32)] = &[
+    (64, 64),
+    (0, 0),
+    (128, 32),
+    (0, 48),
+    (31, 97),
+    (48, 0),
+    (1, 1),
+    (0, 0),
+    (0, 0),
+    (200, 120),
+    (199, 121),
+    (64, 64),
+];
+
+/// Checks the surface, depth texture, camera and UI all agree on the size
+/// the window last had that wasn't zero.
+fn assert_consistent(headless: &Headless, (width, height): (u32, u32)) {
+    let state = &headless.state;
+    let config = (state.surface_config.width, state.surface_config.height);
+    assert_eq!(config, (width, height), "surface size");
+
+    let depth = state.depth_texture.texture.size();
+    assert_eq!((depth.width, depth.height), (width, height), "depth size");
+
+    let screen = state.screen_descriptor();
+    assert_eq!(screen.size_in_pixels, [width, height], "screen descriptor");
+
+    let aspect = headless.world.camera.aspect_ratio();
+    assert!(
+        (aspect - width as f32 / height as f32).abs() < 1e-6,
+        "camera aspect {aspect} for {width}x{height}"
+    );
+    assert!(
+        headless.world.camera.view_proj().is_finite(),
+        "camera matrix"
+    );
+}
+
+#[test]
+fn resize_storm() {
+    let mut headless = Headless::new(64, 64);
+    let mut last = (64, 64);
+    for &(width, height) in STORM {
+        headless.resize(width, height);
+        if width > 0 && height > 0 {
+            last = (width, height);
+        }
+        let capture = headless.render();
+        assert_eq!((capture.width, capture.height), last, "capture size");
+        assert_eq!(capture.pixels.len(), (last.0 * last.1 * 4) as usize);
+        assert_consistent(&headless, last);
+    }
+}
+
+#[test]
+fn render_while_minimized() {
+    let mut headless = Headless::new(96, 48);
+    headless.resize(0, 0);
+    for _ in 0..3 {
+        let capture = headless.render();
+        assert_eq!((capture.width, capture.height), (96, 48));
+    }
+    headless.resize(48, 96);
+    headless.render();
+    assert_consistent(&headless, (48, 96));
+}
@@ -0,0 +1,116 @@
+//! Per-model shaders: assigning one of `shaders/` to a model changes only
+//! that model, models on the same shader share a template, and shaders
+//! that don't fit the material are turned down.
+//!
+//! `cargo test --features gpu-tests`.
+#![cfg(feature = "gpu-tests")]
+
+use rust_graphics_sandbox::headless::Headless;
+use rust_graphics_sandbox::mesh::create_test_mesh;
+use rust_graphics_sandbox::model::EntityId;
+use rust_graphics_sandbox::shader;
+use rust_graphics_sandbox::transform::Transform;
+use std::sync::Arc;
+
+const SIZE: u32 = 64;
+
+fn two_triangles() -> (Headless, EntityId, EntityId) {
+    let mut headless = Headless::new(SIZE, SIZE);
+    headless.world.clear();
+    headless.world.camera.eye = glam::vec3(0.0, 0.0, 3.0);
+    headless.world.camera.center = glam::Vec3::ZERO;
+    let mut spawn = |name: &str, translation: glam::Vec3| {
+        let mesh = create_test_mesh(&headless.state.device);
+        let material = headless.world.default_material();
+        let transform = Transform {
+            translation,
+            ..Default::default()
+        };
+        headless
+            .world
+            .spawn(&headless.state, name, mesh, material, transform)
+    };
+    let a = spawn("A", glam::Vec3::ZERO);
+    // out of view, so the centre pixel is always A's
+    let b = spawn("B", glam::vec3(0.0, 0.0, 10.0));
+    (headless, a, b)
+}
+
+fn assign(headless: &mut Headless, id: EntityId, name: &str) -> Result<(), String> {
+    let shader = shader::load_named(name)?;
+    let model = headless.world.model_mut(id).unwrap();
+    let material = model
+        .material()
+        .with_shader(&headless.state, name, &shader)?;
+    model.set_material(&headless.state.device, material);
+    Ok(())
+}
+
+fn center_pixel(headless: &mut Headless) -> [u8; 4] {
+    let capture = headless.render();
+    let i = ((SIZE / 2 * SIZE + SIZE / 2) * 4) as usize;
+    capture.pixels[i..i + 4].try_into().unwrap()
+}
+
+#[test]
+fn shaders_dir_is_listed() {
+    let names = shader::available();
+    assert!(names.iter().any(|n| n == "world_position"), "{names:?}");
+}
+
+#[test]
+fn assigned_shader_changes_only_that_model() {
+    let (mut headless, a, b) = two_triangles();
+    let before = center_pixel(&mut headless);
+    assign(&mut headless, a, "world_position").unwrap();
+    assert_ne!(center_pixel(&mut headless), before);
+
+    let (model_a, model_b) = (
+        headless.world.model(a).unwrap(),
+        headless.world.model(b).unwrap(),
+    );
+    assert_eq!(model_a.material().shader_name(), Some("world_position"));
+    assert_eq!(model_b.material().shader_name(), None);
+    assert!(Arc::ptr_eq(
+        model_b.material(),
+        &headless.world.default_material()
+    ));
+
+    assign(&mut headless, b, "world_position").unwrap();
+    let (model_a, model_b) = (
+        headless.world.model(a).unwrap(),
+        headless.world.model(b).unwrap(),
+    );
+    assert!(Arc::ptr_eq(
+        model_a.material().template(),
+        model_b.material().template()
+    ));
+}
+
+#[test]
+fn own_shader_comes_back() {
+    let (mut headless, a, _) = two_triangles();
+    let before = center_pixel(&mut headless);
+    assign(&mut headless, a, "world_position").unwrap();
+    let model = headless.world.model_mut(a).unwrap();
+    let material = model.material().with_own_shader(&headless.state);
+    model.set_material(&headless.state.device, material);
+    assert!(Arc::ptr_eq(
+        headless.world.model(a).unwrap().material().template(),
+        headless.world.default_material().template()
+    ));
+    assert_eq!(center_pixel(&mut headless), before);
+}
+
+#[test]
+fn mismatched_bindings_are_refused() {
+    let (mut headless, a, _) = two_triangles();
+    // binds parameters and textures the default material doesn't have
+    let result = assign(&mut headless, a, "parallax");
+    assert!(result.is_err());
+    let model = headless.world.model(a).unwrap();
+    assert!(Arc::ptr_eq(
+        model.material(),
+        &headless.world.default_material()
+    ));
+}
@@ -0,0 +1,70 @@
+//! Shader playground: the starter compiles, and a broken file reports the
+//! error at its own line while the last good shader keeps drawing.
+//!
+//! `cargo test --features gpu-tests`.
+#![cfg(feature = "gpu-tests")]
+
+mod common;
+
+use common::scratch;
+use rust_graphics_sandbox::headless::Headless;
+use rust_graphics_sandbox::input::{Input, InputBindings};
+use rust_graphics_sandbox::plugin::{Plugin, PluginContext};
+use rust_graphics_sandbox::shader_playground::ShaderPlayground;
+use rust_graphics_sandbox::time::Time;
+
+fn built(headless: &mut Headless) -> ShaderPlayground {
+    let mut playground = ShaderPlayground::new();
+    let (time, input) = (Time::new(), Input::new(InputBindings::default()));
+    playground.build(&mut PluginContext {
+        state: &headless.state,
+        world: &mut headless.world,
+        time: &time,
+        input: &input,
+    });
+    playground
+}
+
+#[test]
+fn starter_compiles() {
+    let mut headless = Headless::new(64, 64);
+    let mut playground = built(&mut headless);
+    playground.poll(&headless.state.device);
+    assert_eq!(playground.error(), None);
+    assert!(playground.is_ready());
+}
+
+#[test]
+fn errors_point_at_the_file_and_keep_the_last_shader() {
+    let mut headless = Headless::new(64, 64);
+    let mut playground = built(&mut headless);
+    playground.path = scratch(
+        "good.wgsl",
+        "fn mainImage(coord: vec2<f32>) -> vec4<f32> {\n    return vec4(1.0);\n}\n",
+    );
+    playground.reload(&headless.state.device);
+    assert!(playground.is_ready());
+
+    playground.path = scratch(
+        "bad.wgsl",
+        "fn mainImage(coord: vec2<f32>) -> vec4<f32> {\n    let a = 1.0;\n    return vec4(a) +;\n}\n",
+    );
+    playground.reload(&headless.state.device);
+    let error = playground.error().expect("the stray + should fail");
+    assert!(
+        error.contains(&format!("{}:3:", playground.path)),
+        "{error}"
+    );
+    assert!(playground.is_ready());
+}
+
+#[test]
+fn missing_main_image_is_reported() {
+    let mut headless = Headless::new(64, 64);
+    let mut playground = built(&mut headless);
+    playground.path = scratch("empty.wgsl", "// nothing yet\n");
+    playground.reload(&headless.state.device);
+    let error = playground.error().expect("no mainImage should fail");
+    assert!(error.contains("mainImage"), "{error}");
+    assert!(!playground.is_ready());
+}
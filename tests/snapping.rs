@@ -0,0 +1,50 @@
+//! Snapping: drags onto the vertex under the cursor, through the same
+//! raycast as picking, and onto the grid when there's none.
+//!
+//! `cargo test --features gpu-tests`.
+#![cfg(feature = "gpu-tests")]
+
+mod common;
+
+use common::triangle_world;
+use rust_graphics_sandbox::picking::Ray;
+use rust_graphics_sandbox::snapping::Snapping;
+
+fn ray_towards(target: glam::Vec3) -> Ray {
+    let origin = glam::vec3(0.0, 0.0, 3.0);
+    Ray {
+        origin,
+        direction: (target - origin).normalize(),
+    }
+}
+
+#[test]
+fn drags_land_on_the_nearest_vertex() {
+    let offset = glam::vec3(0.25, 0.0, 0.0);
+    let headless = triangle_world(offset);
+    let snapping = Snapping {
+        vertex: true,
+        ..Default::default()
+    };
+    let ray = ray_towards(glam::vec3(0.4, -0.4, 0.0) + offset);
+    let snapped = snapping.snap_drag(&headless.world, &ray, glam::Vec3::ZERO, |_| false);
+    assert!(
+        snapped.distance(glam::vec3(0.5, -0.5, 0.0) + offset) < 1e-4,
+        "{snapped}"
+    );
+}
+
+#[test]
+fn ignored_models_fall_back_to_the_grid() {
+    let headless = triangle_world(glam::Vec3::ZERO);
+    let snapping = Snapping {
+        vertex: true,
+        grid: true,
+        grid_size: 0.5,
+        ..Default::default()
+    };
+    let ray = ray_towards(glam::vec3(0.4, -0.4, 0.0));
+    let target = glam::vec3(1.3, -0.2, 0.7);
+    let snapped = snapping.snap_drag(&headless.world, &ray, target, |_| true);
+    assert_eq!(snapped, glam::vec3(1.5, 0.0, 0.5));
+}